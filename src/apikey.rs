@@ -0,0 +1,340 @@
+//! Opaque API keys for machine-to-machine authentication
+//!
+//! Keys take the form `<prefix>_<id>_<secret>`.  The prefix identifies the issuing
+//! application (making leaked keys easy to find with secret scanners), the id is used to
+//! look up the stored [`ApiKeyRecord`] and the secret is 256 bits of randomness.  Only a
+//! hash of the secret (computed with the password [`Hasher`]) is ever stored.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::apikey::ApiKeys;
+//!
+//! let keys = ApiKeys::new("myapp").ttl(90 * 24 * 60 * 60);
+//!
+//! // show `key` to the user once, store `record`
+//! let (key, record) = keys.generate()?;
+//!
+//! // when a key is presented
+//! let record = db.find_key(keys.id(&presented)?)?;
+//! keys.verify(&presented, &record)?;
+//! ```
+
+use crate::{
+    password::{Hasher, HasherError},
+    time::now,
+};
+use rand::RngCore;
+use ring::constant_time;
+#[cfg(feature = "secrecy")]
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+/// Length, in bytes, of a key's id
+const ID_LEN: usize = 8;
+
+/// Length, in bytes, of a key's secret
+const SECRET_LEN: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum ApiKeyError {
+    #[error("api key is malformed")]
+    Malformed,
+
+    #[error("api key is invalid")]
+    Invalid,
+
+    #[error("api key has expired")]
+    Expired,
+
+    #[error("api key has been revoked")]
+    Revoked,
+
+    #[error("failed to hash api key: {0}")]
+    Hasher(#[from] HasherError),
+}
+
+/// A newly generated API key, to be shown to its owner exactly once
+pub struct ApiKey {
+    /// Full key, including the prefix and id
+    key: String,
+
+    /// Id of the key
+    id: String,
+}
+
+impl ApiKey {
+    /// Returns the id used to look up the key's record
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the full key
+    pub fn as_str(&self) -> &str {
+        &self.key
+    }
+}
+
+impl fmt::Display for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.key)
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // never leak the secret into logs
+        f.debug_struct("ApiKey").field("id", &self.id).finish()
+    }
+}
+
+/// Stored representation of an API key
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ApiKeyRecord {
+    /// Id of the key
+    id: String,
+
+    /// Encoded hash of the key's secret
+    hash: String,
+
+    /// Time (seconds since the unix epoch) the key was created
+    created: u64,
+
+    /// Time (seconds since the unix epoch) the key expires, if ever
+    expires: Option<u64>,
+
+    /// Time (seconds since the unix epoch) the key was revoked, if revoked
+    revoked: Option<u64>,
+}
+
+impl ApiKeyRecord {
+    /// Returns the id of the key
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the time (seconds since the unix epoch) the key was created
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    /// Returns the time (seconds since the unix epoch) the key expires, if ever
+    pub fn expires(&self) -> Option<u64> {
+        self.expires
+    }
+
+    /// Returns the time (seconds since the unix epoch) the key was revoked, if revoked
+    pub fn revoked(&self) -> Option<u64> {
+        self.revoked
+    }
+
+    /// Returns true if the key has expired
+    pub fn is_expired(&self) -> bool {
+        self.expires.map(|exp| exp <= now()).unwrap_or(false)
+    }
+
+    /// Returns true if the key has been revoked
+    pub fn is_revoked(&self) -> bool {
+        self.revoked.is_some()
+    }
+
+    /// Revokes the key.  The record must be saved for the revocation to take effect
+    pub fn revoke(&mut self) {
+        if self.revoked.is_none() {
+            self.revoked = Some(now());
+        }
+    }
+}
+
+/// Generates and verifies API keys
+pub struct ApiKeys {
+    /// Prefix placed at the start of every key
+    prefix: String,
+
+    /// Hasher used to hash key secrets
+    hasher: Hasher,
+
+    /// Lifetime of generated keys, in seconds, if they expire
+    ttl: Option<u64>,
+}
+
+impl ApiKeys {
+    /// Creates a new generator of non-expiring keys hashed with the default [`Hasher`]
+    ///
+    /// # Arguments
+    /// * `prefix` - Prefix identifying the issuing application (e.g., `myapp`)
+    pub fn new<S: Into<String>>(prefix: S) -> ApiKeys {
+        ApiKeys {
+            prefix: prefix.into(),
+            hasher: Hasher::default(),
+            ttl: None,
+        }
+    }
+
+    /// Sets the hasher used to hash key secrets
+    pub fn hasher(mut self, hasher: Hasher) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// Sets the lifetime of generated keys, in seconds
+    pub fn ttl(mut self, ttl: u64) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Generates a new key, returning the key to hand to its owner and the record to store
+    pub fn generate(&self) -> Result<(ApiKey, ApiKeyRecord), ApiKeyError> {
+        let mut id = [0u8; ID_LEN];
+        let mut secret = [0u8; SECRET_LEN];
+        rand::thread_rng().fill_bytes(&mut id);
+        rand::thread_rng().fill_bytes(&mut secret);
+
+        let id = hex(&id);
+        let secret = base64::encode_config(secret, base64::URL_SAFE_NO_PAD);
+        let created = now();
+
+        let record = ApiKeyRecord {
+            id: id.clone(),
+            hash: self.hasher.hash(&secret)?,
+            created,
+            expires: self.ttl.map(|ttl| created + ttl),
+            revoked: None,
+        };
+
+        let key = ApiKey {
+            key: format!("{}_{}_{}", self.prefix, id, secret),
+            id,
+        };
+
+        Ok((key, record))
+    }
+
+    /// Returns the id of a presented key, used to look up its record
+    ///
+    /// # Arguments
+    /// * `key` - Key presented by the client
+    pub fn id<'a>(&self, key: &'a str) -> Result<&'a str, ApiKeyError> {
+        self.split(key).map(|(id, _)| id)
+    }
+
+    /// Verifies a presented key against its stored record
+    ///
+    /// # Arguments
+    /// * `key` - Key presented by the client
+    /// * `record` - Stored record of the key
+    pub fn verify(&self, key: &str, record: &ApiKeyRecord) -> Result<(), ApiKeyError> {
+        let (id, secret) = self.split(key)?;
+        constant_time::verify_slices_are_equal(id.as_bytes(), record.id.as_bytes())
+            .map_err(|_| ApiKeyError::Invalid)?;
+
+        self.hasher
+            .verify(secret, &record.hash)
+            .map_err(|_| ApiKeyError::Invalid)?;
+
+        if record.is_revoked() {
+            return Err(ApiKeyError::Revoked);
+        }
+
+        if record.is_expired() {
+            return Err(ApiKeyError::Expired);
+        }
+
+        Ok(())
+    }
+
+    /// Verifies a presented key held in a `SecretString` against its stored record
+    ///
+    /// # Arguments
+    /// * `key` - Key presented by the client
+    /// * `record` - Stored record of the key
+    #[cfg(feature = "secrecy")]
+    pub fn verify_secret(
+        &self,
+        key: &SecretString,
+        record: &ApiKeyRecord,
+    ) -> Result<(), ApiKeyError> {
+        self.verify(key.expose_secret(), record)
+    }
+
+    /// Splits a key into its id and secret, checking the prefix
+    fn split<'a>(&self, key: &'a str) -> Result<(&'a str, &'a str), ApiKeyError> {
+        let rest = key
+            .strip_prefix(self.prefix.as_str())
+            .and_then(|rest| rest.strip_prefix('_'))
+            .ok_or(ApiKeyError::Malformed)?;
+
+        let id_len = ID_LEN * 2;
+        if rest.len() <= id_len + 1 || !rest.is_char_boundary(id_len) {
+            return Err(ApiKeyError::Malformed);
+        }
+
+        let (id, secret) = rest.split_at(id_len);
+        let secret = secret.strip_prefix('_').ok_or(ApiKeyError::Malformed)?;
+        Ok((id, secret))
+    }
+}
+
+/// Hex-encodes a byte slice
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time;
+
+    #[test]
+    fn generated_key_verifies() {
+        let keys = ApiKeys::new("test");
+        let (key, record) = keys.generate().unwrap();
+
+        assert!(key.as_str().starts_with("test_"));
+        assert_eq!(keys.id(key.as_str()).unwrap(), record.id());
+        keys.verify(key.as_str(), &record).unwrap();
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected() {
+        let keys = ApiKeys::new("test");
+        let (key, mut record) = keys.generate().unwrap();
+        let (other, _) = keys.generate().unwrap();
+
+        assert!(matches!(
+            keys.verify(other.as_str(), &record),
+            Err(ApiKeyError::Invalid)
+        ));
+        assert!(matches!(
+            ApiKeys::new("prod").verify(key.as_str(), &record),
+            Err(ApiKeyError::Malformed)
+        ));
+
+        record.revoke();
+        assert!(matches!(
+            keys.verify(key.as_str(), &record),
+            Err(ApiKeyError::Revoked)
+        ));
+    }
+
+    #[test]
+    fn expired_keys_are_rejected() {
+        let clock = time::freeze(1_600_000_000);
+        let keys = ApiKeys::new("test").ttl(60);
+        let (key, record) = keys.generate().unwrap();
+        assert_eq!(record.expires(), Some(1_600_000_060));
+
+        clock.advance(59);
+        assert!(!record.is_expired());
+        keys.verify(key.as_str(), &record).unwrap();
+
+        clock.advance(1);
+        assert!(record.is_expired());
+        assert!(matches!(
+            keys.verify(key.as_str(), &record),
+            Err(ApiKeyError::Expired)
+        ));
+    }
+}
@@ -0,0 +1,280 @@
+//! Parsing of HTTP `Authorization` headers
+//!
+//! [`Credentials::parse`] turns the value of an `Authorization` header into typed
+//! credentials for the `Basic` ([RFC 7617](https://tools.ietf.org/html/rfc7617)) and
+//! `Bearer` ([RFC 6750](https://tools.ietf.org/html/rfc6750)) schemes, so framework
+//! integrations don't each have to.  Basic credentials are decoded as UTF-8, falling back
+//! to ISO-8859-1 for older clients.
+//!
+//! With the matching features enabled, basic credentials can be checked with the password
+//! [`Hasher`](crate::password::Hasher) and bearer tokens verified with a
+//! [`JwtIssuer`](crate::tokens::jwt::JwtIssuer) or
+//! [`PasetoIssuer`](crate::tokens::paseto::PasetoIssuer).
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::authorization::Credentials;
+//!
+//! let creds = Credentials::parse(req.headers()["authorization"].to_str()?)?;
+//! let claims = creds.verify_jwt(&issuer)?;
+//! ```
+
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+#[cfg(feature = "password")]
+use crate::password::{Hasher, HasherError};
+#[cfg(feature = "jwt")]
+use crate::tokens::jwt::{self, JwtError, JwtIssuer};
+#[cfg(feature = "paseto")]
+use crate::tokens::paseto::{self, PasetoError, PasetoIssuer};
+
+#[derive(Error, Debug)]
+pub enum AuthorizationError {
+    #[error("malformed authorization header: {0}")]
+    Malformed(&'static str),
+
+    #[error("unsupported authorization scheme `{0}`")]
+    UnsupportedScheme(String),
+
+    #[error("expected {0} credentials")]
+    WrongScheme(&'static str),
+
+    #[cfg(feature = "password")]
+    #[error("password verification failed: {0}")]
+    Password(#[from] HasherError),
+
+    #[cfg(feature = "jwt")]
+    #[error("bearer token rejected: {0}")]
+    Jwt(#[from] JwtError),
+
+    #[cfg(feature = "paseto")]
+    #[error("bearer token rejected: {0}")]
+    Paseto(#[from] PasetoError),
+}
+
+/// Username and password sent with the `Basic` scheme
+#[derive(Clone, PartialEq, Eq)]
+pub struct BasicCredentials {
+    /// Name of the user
+    username: String,
+
+    /// Password of the user
+    password: String,
+}
+
+impl BasicCredentials {
+    /// Returns the name of the user
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Returns the password of the user
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+
+    /// Verifies the password against the user's stored hash
+    ///
+    /// # Arguments
+    /// * `hasher` - Hasher the password was hashed with
+    /// * `hash` - Encoded hash of the user's password
+    #[cfg(feature = "password")]
+    pub fn verify(&self, hasher: &Hasher, hash: &str) -> Result<(), AuthorizationError> {
+        Ok(hasher.verify(&self.password, hash)?)
+    }
+}
+
+impl fmt::Debug for BasicCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BasicCredentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Credentials sent in an `Authorization` header
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// `Basic` username and password
+    Basic(BasicCredentials),
+
+    /// `Bearer` token
+    Bearer(String),
+}
+
+impl Credentials {
+    /// Parses the value of an `Authorization` header
+    ///
+    /// # Arguments
+    /// * `header` - Value of the header (e.g., `Bearer mF_9.B5f-4.1JqM`)
+    pub fn parse(header: &str) -> Result<Credentials, AuthorizationError> {
+        let header = header.trim();
+        let (scheme, value) = match header.find(' ') {
+            Some(idx) => (&header[..idx], header[idx..].trim_start()),
+            None => (header, ""),
+        };
+
+        if scheme.eq_ignore_ascii_case("basic") {
+            parse_basic(value).map(Credentials::Basic)
+        } else if scheme.eq_ignore_ascii_case("bearer") {
+            parse_bearer(value).map(|token| Credentials::Bearer(token.to_owned()))
+        } else {
+            Err(AuthorizationError::UnsupportedScheme(scheme.to_owned()))
+        }
+    }
+
+    /// Returns the username and password, if these are `Basic` credentials
+    pub fn basic(&self) -> Option<&BasicCredentials> {
+        match self {
+            Credentials::Basic(basic) => Some(basic),
+            _ => None,
+        }
+    }
+
+    /// Returns the token, if these are `Bearer` credentials
+    pub fn bearer(&self) -> Option<&str> {
+        match self {
+            Credentials::Bearer(token) => Some(token),
+            _ => None,
+        }
+    }
+
+    /// Verifies `Basic` credentials against the user's stored password hash
+    ///
+    /// # Arguments
+    /// * `hasher` - Hasher the password was hashed with
+    /// * `hash` - Encoded hash of the user's password
+    #[cfg(feature = "password")]
+    pub fn verify_password(&self, hasher: &Hasher, hash: &str) -> Result<(), AuthorizationError> {
+        self.basic()
+            .ok_or(AuthorizationError::WrongScheme("basic"))?
+            .verify(hasher, hash)
+    }
+
+    /// Verifies a `Bearer` JWT, returning its claims
+    ///
+    /// # Arguments
+    /// * `issuer` - Issuer the token was signed by
+    #[cfg(feature = "jwt")]
+    pub fn verify_jwt(&self, issuer: &JwtIssuer) -> Result<jwt::Claims, AuthorizationError> {
+        let token = self
+            .bearer()
+            .ok_or(AuthorizationError::WrongScheme("bearer"))?;
+        Ok(issuer.verify(token)?)
+    }
+
+    /// Verifies a `Bearer` PASETO, returning its claims
+    ///
+    /// # Arguments
+    /// * `issuer` - Issuer the token was encrypted or signed by
+    #[cfg(feature = "paseto")]
+    pub fn verify_paseto(
+        &self,
+        issuer: &PasetoIssuer,
+    ) -> Result<paseto::Claims, AuthorizationError> {
+        let token = self
+            .bearer()
+            .ok_or(AuthorizationError::WrongScheme("bearer"))?;
+        Ok(issuer.verify(token)?)
+    }
+}
+
+impl FromStr for Credentials {
+    type Err = AuthorizationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Credentials::parse(s)
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Credentials::Basic(basic) => f.debug_tuple("Basic").field(basic).finish(),
+            Credentials::Bearer(_) => f.debug_tuple("Bearer").field(&"<redacted>").finish(),
+        }
+    }
+}
+
+/// Parses the base64-encoded `user-id:password` of the `Basic` scheme
+fn parse_basic(value: &str) -> Result<BasicCredentials, AuthorizationError> {
+    let decoded =
+        base64::decode(value).map_err(|_| AuthorizationError::Malformed("invalid base64"))?;
+
+    // RFC 7617 recommends UTF-8, but older clients send ISO-8859-1, whose code points
+    // map directly to the first 256 unicode characters
+    let decoded = match String::from_utf8(decoded) {
+        Ok(decoded) => decoded,
+        Err(e) => e.into_bytes().into_iter().map(char::from).collect(),
+    };
+
+    let idx = decoded
+        .find(':')
+        .ok_or(AuthorizationError::Malformed("missing password"))?;
+    if decoded.chars().any(char::is_control) {
+        return Err(AuthorizationError::Malformed(
+            "control character in credentials",
+        ));
+    }
+
+    Ok(BasicCredentials {
+        username: decoded[..idx].to_owned(),
+        password: decoded[idx + 1..].to_owned(),
+    })
+}
+
+/// Checks the token of the `Bearer` scheme matches the `b64token` syntax
+fn parse_bearer(value: &str) -> Result<&str, AuthorizationError> {
+    let token = value.trim_end_matches('=');
+    let valid = !token.is_empty()
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._~+/".contains(&b));
+
+    match valid {
+        true => Ok(value),
+        false => Err(AuthorizationError::Malformed("invalid bearer token")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic(header: &str) -> (String, String) {
+        let creds = Credentials::parse(header).unwrap();
+        let basic = creds.basic().unwrap();
+        (basic.username().to_owned(), basic.password().to_owned())
+    }
+
+    #[test]
+    fn parse_basic_credentials() {
+        assert_eq!(
+            basic("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="),
+            ("Aladdin".into(), "open sesame".into())
+        );
+        // `test:123£` encoded as UTF-8 (RFC 7617, section 2.1) and ISO-8859-1
+        assert_eq!(
+            basic("basic dGVzdDoxMjPCow=="),
+            ("test".into(), "123£".into())
+        );
+        assert_eq!(basic("Basic dGVzdDoxMjOj"), ("test".into(), "123£".into()));
+        assert!(Credentials::parse("Basic QWxhZGRpbg==").is_err());
+    }
+
+    #[test]
+    fn parse_bearer_token() {
+        let creds: Credentials = "Bearer mF_9.B5f-4.1JqM".parse().unwrap();
+        assert_eq!(creds.bearer(), Some("mF_9.B5f-4.1JqM"));
+        assert!(creds.basic().is_none());
+        assert!(Credentials::parse("Bearer ").is_err());
+        assert!(Credentials::parse("Bearer a b").is_err());
+        assert!(matches!(
+            Credentials::parse("Digest username=\"Mufasa\""),
+            Err(AuthorizationError::UnsupportedScheme(_))
+        ));
+    }
+}
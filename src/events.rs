@@ -0,0 +1,247 @@
+//! Audit events emitted by every authentication module
+//!
+//! The WebAuthn ceremonies, password hasher, Google and Microsoft token verifiers,
+//! social-login presets and LDAP authenticator report what they do as an [`AuthEvent`]
+//! to the process-wide [`EventSink`] installed with [`set_sink`], so applications can
+//! centralize security logging without wrapping every call.  No events are delivered until a sink is installed.
+//!
+//! Sinks that write through an async client implement [`AsyncEventSink`] instead and are
+//! handed to the async WebAuthn ceremony runner, which awaits them.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::events::{self, AuthEvent};
+//!
+//! events::set_sink(|event: &AuthEvent| log::info!(target: "audit", "{:?}", event));
+//! ```
+
+use std::{
+    future::{self, Future},
+    pin::Pin,
+    sync::{Arc, RwLock},
+};
+
+/// A security-relevant event
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AuthEvent {
+    /// A new WebAuthn credential was registered
+    RegistrationSucceeded {
+        /// Id of the new credential
+        credential: Vec<u8>,
+    },
+
+    /// A WebAuthn registration response failed validation
+    RegistrationFailed {
+        /// Why validation failed
+        reason: String,
+    },
+
+    /// A user authenticated with a WebAuthn credential
+    AssertionSucceeded {
+        /// Id of the user
+        user: Vec<u8>,
+
+        /// Id of the credential used
+        credential: Vec<u8>,
+    },
+
+    /// A WebAuthn authentication response failed validation
+    AssertionFailed {
+        /// Id of the user
+        user: Vec<u8>,
+
+        /// Why validation failed
+        reason: String,
+    },
+
+    /// An authenticator's signature counter did not increase, which may indicate a cloned
+    /// authenticator
+    AssertionCounterRegressed {
+        /// Id of the credential used
+        credential: Vec<u8>,
+
+        /// Counter saved after the previous authentication
+        stored: u32,
+
+        /// Counter received in this authentication
+        received: u32,
+    },
+
+    /// A password was hashed
+    PasswordHashed,
+
+    /// A password matched its hash
+    PasswordVerified,
+
+    /// A password did not match its hash
+    PasswordVerifyFailed,
+
+    /// Google's signing keys were refreshed
+    GoogleKeyRefresh {
+        /// Number of keys received
+        keys: usize,
+    },
+
+    /// Google's signing keys could not be refreshed
+    GoogleKeyRefreshFailed {
+        /// Why the refresh failed
+        reason: String,
+    },
+
+    /// A Google id token was rejected
+    GoogleTokenRejected {
+        /// Why the token was rejected
+        reason: String,
+    },
+
+    /// A Google Play Integrity token was rejected
+    PlayIntegrityRejected {
+        /// Why the token was rejected
+        reason: String,
+    },
+
+    /// Microsoft's signing keys were refreshed
+    MicrosoftKeyRefresh {
+        /// Number of keys received
+        keys: usize,
+    },
+
+    /// Microsoft's signing keys could not be refreshed
+    MicrosoftKeyRefreshFailed {
+        /// Why the refresh failed
+        reason: String,
+    },
+
+    /// A Microsoft identity platform token was rejected
+    MicrosoftTokenRejected {
+        /// Why the token was rejected
+        reason: String,
+    },
+
+    /// A user signed in with an OAuth 2.0 provider
+    OAuthLoginSucceeded {
+        /// Name of the provider (e.g., `github`)
+        provider: String,
+
+        /// Id of the user at the provider
+        subject: String,
+    },
+
+    /// Signing in with an OAuth 2.0 provider failed
+    OAuthLoginFailed {
+        /// Name of the provider (e.g., `github`)
+        provider: String,
+
+        /// Why signing in failed
+        reason: String,
+    },
+
+    /// A user authenticated by binding to an LDAP directory
+    LdapBindSucceeded {
+        /// Distinguished name the user bound as
+        dn: String,
+    },
+
+    /// A user failed to bind to an LDAP directory
+    LdapBindFailed {
+        /// Username presented
+        user: String,
+
+        /// Why the bind failed
+        reason: String,
+    },
+}
+
+/// Receives audit events
+pub trait EventSink: Send + Sync {
+    /// Called for every event emitted by the crate
+    ///
+    /// # Arguments
+    /// * `event` - The event that occurred
+    fn emit(&self, event: &AuthEvent);
+}
+
+impl<F> EventSink for F
+where
+    F: Fn(&AuthEvent) + Send + Sync,
+{
+    fn emit(&self, event: &AuthEvent) {
+        self(event)
+    }
+}
+
+/// The future returned by [`AsyncEventSink::emit`]
+pub type SinkFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// Receives audit events without blocking, see [`EventSink`].  Every `EventSink` is also
+/// an `AsyncEventSink`
+pub trait AsyncEventSink: Send + Sync {
+    /// Called for every event emitted by the ceremonies the sink is attached to
+    ///
+    /// # Arguments
+    /// * `event` - The event that occurred
+    fn emit<'a>(&'a self, event: &'a AuthEvent) -> SinkFuture<'a>;
+}
+
+impl<T: EventSink> AsyncEventSink for T {
+    fn emit<'a>(&'a self, event: &'a AuthEvent) -> SinkFuture<'a> {
+        EventSink::emit(self, event);
+        Box::pin(future::ready(()))
+    }
+}
+
+/// Sink events are delivered to
+static SINK: RwLock<Option<Arc<dyn EventSink>>> = RwLock::new(None);
+
+/// Installs the sink events are delivered to, replacing any existing sink
+///
+/// # Arguments
+/// * `sink` - Receives every event emitted by the crate
+pub fn set_sink<S: EventSink + 'static>(sink: S) {
+    if let Ok(mut current) = SINK.write() {
+        *current = Some(Arc::new(sink));
+    }
+}
+
+/// Removes the installed sink, if any
+pub fn clear_sink() {
+    if let Ok(mut current) = SINK.write() {
+        *current = None;
+    }
+}
+
+/// Delivers an event to the installed sink, if any
+///
+/// # Arguments
+/// * `event` - The event that occurred
+pub fn emit(event: AuthEvent) {
+    // clone the sink so it isn't called while holding the lock
+    let sink = SINK.read().ok().and_then(|sink| sink.clone());
+    if let Some(sink) = sink {
+        sink.emit(&event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn events_are_delivered_to_sink() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let events = Arc::clone(&received);
+        set_sink(move |event: &AuthEvent| events.lock().unwrap().push(event.clone()));
+
+        // other tests may emit events concurrently, so only look for our own
+        emit(AuthEvent::GoogleKeyRefresh { keys: 42 });
+        clear_sink();
+        emit(AuthEvent::GoogleKeyRefresh { keys: 43 });
+
+        let received = received.lock().unwrap();
+        assert!(received.contains(&AuthEvent::GoogleKeyRefresh { keys: 42 }));
+        assert!(!received.contains(&AuthEvent::GoogleKeyRefresh { keys: 43 }));
+    }
+}
@@ -0,0 +1,99 @@
+//! `no_std` parsing core for WebAuthn responses
+//!
+//! Decodes the pieces of a WebAuthn response a relying party verifies: the CBOR
+//! attestation object, the authenticator data it wraps, the COSE credential public key
+//! and the client data JSON.  Only `alloc` is needed, so the checks can run on embedded
+//! relying parties and in constrained sandboxes; the `webauthn` module builds
+//! its server-side validation on the same parsers.  Every parser is bounds checked and
+//! fails with an [`Error`] instead of panicking on truncated or malformed input.
+//!
+//! Signature verification needs the `fido-crypto` feature, which brings in `ring`.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::fido::{verify, AuthenticatorData, CollectedClientData};
+//!
+//! let client_data = CollectedClientData::parse(&client_data_json)?;
+//! client_data.validate("webauthn.get", &challenge, "https://app.example.com")?;
+//!
+//! let auth_data = AuthenticatorData::parse(&authenticator_data)?;
+//! verify::verify_assertion(&stored_key, &authenticator_data, &client_data_json, &signature)?;
+//! ```
+
+pub mod attestation;
+pub mod auth_data;
+pub mod cbor;
+pub mod client_data;
+pub mod cose;
+#[cfg(feature = "fido-crypto")]
+pub mod verify;
+
+pub use self::{
+    attestation::AttestationObject,
+    auth_data::{
+        AttestedCredential, AttestedCredentialRef, AuthenticatorData, AuthenticatorDataRef,
+    },
+    client_data::CollectedClientData,
+    cose::CoseKey,
+};
+
+use core::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Occurs when the input ends before the structure being parsed
+    Truncated,
+
+    /// Occurs when bytes are left over after the structure being parsed
+    TrailingData,
+
+    /// Occurs when CBOR is malformed, or uses an encoding WebAuthn does not allow
+    InvalidCbor,
+
+    /// Occurs when the client data is not a JSON object
+    InvalidJson,
+
+    /// Occurs when a required member is missing or has the wrong type
+    MissingField(&'static str),
+
+    /// Occurs when a COSE key uses a key type, curve or algorithm that is not supported
+    UnsupportedKey,
+
+    /// Occurs when the attestation statement format is not supported
+    UnsupportedFormat,
+
+    /// Occurs when the client data was collected for another ceremony
+    TypeMismatch,
+
+    /// Occurs when the client data carries another challenge than the one issued
+    ChallengeMismatch,
+
+    /// Occurs when the client data was collected for another origin
+    OriginMismatch,
+
+    /// Occurs when a signature does not verify
+    BadSignature,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "input ended unexpectedly"),
+            Error::TrailingData => write!(f, "unexpected data after the end of the input"),
+            Error::InvalidCbor => write!(f, "malformed CBOR"),
+            Error::InvalidJson => write!(f, "malformed client data JSON"),
+            Error::MissingField(name) => write!(f, "missing or invalid field `{}`", name),
+            Error::UnsupportedKey => write!(f, "unsupported COSE key"),
+            Error::UnsupportedFormat => write!(f, "unsupported attestation statement format"),
+            Error::TypeMismatch => write!(f, "client data type mismatch"),
+            Error::ChallengeMismatch => write!(f, "challenge mismatch"),
+            Error::OriginMismatch => write!(f, "origin mismatch"),
+            Error::BadSignature => write!(f, "signature verification failed"),
+        }
+    }
+}
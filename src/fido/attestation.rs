@@ -0,0 +1,122 @@
+//! Attestation objects
+//!
+//! The attestation object returned when registering is a CBOR map of the attestation
+//! statement format (`fmt`), the statement itself (`attStmt`) and the authenticator
+//! data (`authData`).
+
+use super::{
+    auth_data::AuthenticatorData,
+    cbor::{self, Value},
+    Error,
+};
+use alloc::{string::String, vec::Vec};
+
+/// A decoded attestation object
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttestationObject {
+    fmt: String,
+    att_stmt: Value,
+    auth_data: Vec<u8>,
+}
+
+impl AttestationObject {
+    /// Parses an attestation object
+    ///
+    /// # Arguments
+    /// * `data` - CBOR encoded attestation object
+    pub fn parse(data: &[u8]) -> Result<AttestationObject, Error> {
+        let object = cbor::decode(data)?;
+        let fmt = object
+            .get_text("fmt")
+            .and_then(Value::as_text)
+            .ok_or(Error::MissingField("fmt"))?;
+        let att_stmt = match object.get_text("attStmt") {
+            Some(stmt @ Value::Map(_)) => stmt.clone(),
+            _ => return Err(Error::MissingField("attStmt")),
+        };
+        let auth_data = object
+            .get_text("authData")
+            .and_then(Value::as_bytes)
+            .ok_or(Error::MissingField("authData"))?;
+
+        Ok(AttestationObject {
+            fmt: fmt.into(),
+            att_stmt,
+            auth_data: auth_data.to_vec(),
+        })
+    }
+
+    /// Returns the attestation statement format (e.g., `packed`, `fido-u2f`, `none`)
+    pub fn fmt(&self) -> &str {
+        &self.fmt
+    }
+
+    /// Returns the attestation statement, a map whose members depend on the format
+    pub fn att_stmt(&self) -> &Value {
+        &self.att_stmt
+    }
+
+    /// Returns the raw authenticator data, as signed by the attestation statement
+    pub fn auth_data_bytes(&self) -> &[u8] {
+        &self.auth_data
+    }
+
+    /// Parses the authenticator data
+    pub fn auth_data(&self) -> Result<AuthenticatorData, Error> {
+        AuthenticatorData::parse(&self.auth_data)
+    }
+
+    /// Returns the signature of the statement, for formats that carry one
+    pub fn sig(&self) -> Option<&[u8]> {
+        self.att_stmt.get_text("sig").and_then(Value::as_bytes)
+    }
+
+    /// Returns the COSE algorithm of the statement's signature, for formats that name it
+    pub fn alg(&self) -> Option<i128> {
+        self.att_stmt.get_text("alg").and_then(Value::as_integer)
+    }
+
+    /// Returns the DER encoded attestation certificate chain, leaf first (empty for
+    /// self attestation)
+    pub fn x5c(&self) -> Vec<&[u8]> {
+        self.att_stmt
+            .get_text("x5c")
+            .and_then(Value::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(Value::as_bytes)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn parse_none() {
+        // {"fmt": "none", "attStmt": {}, "authData": h'00 x 37'}
+        let mut data = vec![0xa3, 0x63];
+        data.extend_from_slice(b"fmt");
+        data.push(0x64);
+        data.extend_from_slice(b"none");
+        data.push(0x67);
+        data.extend_from_slice(b"attStmt");
+        data.push(0xa0);
+        data.push(0x68);
+        data.extend_from_slice(b"authData");
+        data.extend_from_slice(&[0x58, 37]);
+        data.extend_from_slice(&[0; 37]);
+
+        let object = AttestationObject::parse(&data).unwrap();
+        assert_eq!(object.fmt(), "none");
+        assert_eq!(object.sig(), None);
+        assert!(object.x5c().is_empty());
+        assert_eq!(object.auth_data().unwrap().counter(), 0);
+
+        // authData missing
+        data[0] = 0xa2;
+        assert!(AttestationObject::parse(&data[..data.len() - 48]).is_err());
+    }
+}
@@ -0,0 +1,333 @@
+//! Authenticator data
+//!
+//! `rpIdHash (32) | flags (1) | signCount (4)`, followed by the attested credential data
+//! when the `AT` flag is set and by a CBOR map of extension outputs when the `ED` flag
+//! is set.
+
+use super::{cbor, cose::CoseKey, Error};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+/// Flag set when the user is present
+pub const FLAG_USER_PRESENT: u8 = 0x01;
+
+/// Flag set when the user is verified
+pub const FLAG_USER_VERIFIED: u8 = 0x04;
+
+/// Flag set when the credential is eligible for backup
+pub const FLAG_BACKUP_ELIGIBLE: u8 = 0x08;
+
+/// Flag set when the credential is backed up
+pub const FLAG_BACKED_UP: u8 = 0x10;
+
+/// Flag set when attested credential data follows the counter
+pub const FLAG_ATTESTED: u8 = 0x40;
+
+/// Flag set when extension outputs end the authenticator data
+pub const FLAG_EXTENSIONS: u8 = 0x80;
+
+/// A credential created by the authenticator, from the attested credential data
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttestedCredential {
+    aaguid: [u8; 16],
+    id: Vec<u8>,
+    public_key: Vec<u8>,
+}
+
+impl AttestedCredential {
+    /// Returns the AAGUID identifying the authenticator's model
+    pub fn aaguid(&self) -> &[u8; 16] {
+        &self.aaguid
+    }
+
+    /// Returns the credential id
+    pub fn id(&self) -> &[u8] {
+        &self.id
+    }
+
+    /// Returns the CBOR encoded COSE public key
+    pub fn public_key_bytes(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Parses the COSE public key
+    pub fn public_key(&self) -> Result<CoseKey, Error> {
+        CoseKey::parse(&self.public_key)
+    }
+}
+
+/// Attested credential data borrowed from the authenticator data it was parsed from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AttestedCredentialRef<'a> {
+    aaguid: &'a [u8; 16],
+    id: &'a [u8],
+    public_key: &'a [u8],
+}
+
+impl<'a> AttestedCredentialRef<'a> {
+    /// Returns the AAGUID identifying the authenticator's model
+    pub fn aaguid(&self) -> &'a [u8; 16] {
+        self.aaguid
+    }
+
+    /// Returns the credential id
+    pub fn id(&self) -> &'a [u8] {
+        self.id
+    }
+
+    /// Returns the CBOR encoded COSE public key
+    pub fn public_key_bytes(&self) -> &'a [u8] {
+        self.public_key
+    }
+
+    /// Parses the COSE public key
+    pub fn public_key(&self) -> Result<CoseKey, Error> {
+        CoseKey::parse(self.public_key)
+    }
+
+    /// Copies the credential out of the authenticator data
+    pub fn into_owned(self) -> AttestedCredential {
+        AttestedCredential {
+            aaguid: *self.aaguid,
+            id: self.id.to_vec(),
+            public_key: self.public_key.to_vec(),
+        }
+    }
+}
+
+/// Decoded authenticator data
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthenticatorData {
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    counter: u32,
+    credential: Option<AttestedCredential>,
+    extensions: Option<Vec<u8>>,
+}
+
+impl AuthenticatorData {
+    /// Parses authenticator data, failing on truncated data or unexpected trailing bytes
+    ///
+    /// # Arguments
+    /// * `data` - Raw authenticator data
+    pub fn parse(data: &[u8]) -> Result<AuthenticatorData, Error> {
+        AuthenticatorDataRef::parse(data).map(AuthenticatorDataRef::into_owned)
+    }
+
+    /// Returns the SHA-256 hash of the relying party id the credential is scoped to
+    pub fn rp_id_hash(&self) -> &[u8; 32] {
+        &self.rp_id_hash
+    }
+
+    /// Returns the flags byte
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Returns the signature counter
+    pub fn counter(&self) -> u32 {
+        self.counter
+    }
+
+    /// Returns true if the user present flag is set
+    pub fn user_present(&self) -> bool {
+        self.flags & FLAG_USER_PRESENT != 0
+    }
+
+    /// Returns true if the user verified flag is set
+    pub fn user_verified(&self) -> bool {
+        self.flags & FLAG_USER_VERIFIED != 0
+    }
+
+    /// Returns the attested credential data, present when registering
+    pub fn credential(&self) -> Option<&AttestedCredential> {
+        self.credential.as_ref()
+    }
+
+    /// Returns the CBOR encoded map of extension outputs, if any
+    pub fn extensions(&self) -> Option<&[u8]> {
+        self.extensions.as_deref()
+    }
+}
+
+/// Authenticator data borrowed from the buffer it was parsed from, so checking an
+/// assertion does not copy the credential or the extension outputs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AuthenticatorDataRef<'a> {
+    rp_id_hash: &'a [u8; 32],
+    flags: u8,
+    counter: u32,
+    credential: Option<AttestedCredentialRef<'a>>,
+    extensions: Option<&'a [u8]>,
+}
+
+impl<'a> AuthenticatorDataRef<'a> {
+    /// Parses authenticator data, failing on truncated data or unexpected trailing bytes
+    ///
+    /// # Arguments
+    /// * `data` - Raw authenticator data
+    pub fn parse(data: &'a [u8]) -> Result<AuthenticatorDataRef<'a>, Error> {
+        if data.len() < 37 {
+            return Err(Error::Truncated);
+        }
+        let rp_id_hash = array_ref(data, 0)?;
+        let flags = data[32];
+        let counter = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+        let mut rest = &data[37..];
+
+        // aaguid (16) | length (2) | credential id | COSE public key
+        let credential = if flags & FLAG_ATTESTED != 0 {
+            if rest.len() < 18 {
+                return Err(Error::Truncated);
+            }
+            let aaguid = array_ref(rest, 0)?;
+            let len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+            let id = rest.get(18..18 + len).ok_or(Error::Truncated)?;
+            rest = &rest[18 + len..];
+
+            let (_, key_len) = cbor::decode_prefix(rest)?;
+            let public_key = &rest[..key_len];
+            rest = &rest[key_len..];
+            Some(AttestedCredentialRef {
+                aaguid,
+                id,
+                public_key,
+            })
+        } else {
+            None
+        };
+
+        let extensions = if flags & FLAG_EXTENSIONS != 0 {
+            match cbor::decode(rest)? {
+                cbor::Value::Map(_) => Some(rest),
+                _ => return Err(Error::MissingField("extensions")),
+            }
+        } else if !rest.is_empty() {
+            return Err(Error::TrailingData);
+        } else {
+            None
+        };
+
+        Ok(AuthenticatorDataRef {
+            rp_id_hash,
+            flags,
+            counter,
+            credential,
+            extensions,
+        })
+    }
+
+    /// Returns the SHA-256 hash of the relying party id the credential is scoped to
+    pub fn rp_id_hash(&self) -> &'a [u8; 32] {
+        self.rp_id_hash
+    }
+
+    /// Returns the flags byte
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Returns the signature counter
+    pub fn counter(&self) -> u32 {
+        self.counter
+    }
+
+    /// Returns true if the user present flag is set
+    pub fn user_present(&self) -> bool {
+        self.flags & FLAG_USER_PRESENT != 0
+    }
+
+    /// Returns true if the user verified flag is set
+    pub fn user_verified(&self) -> bool {
+        self.flags & FLAG_USER_VERIFIED != 0
+    }
+
+    /// Returns the attested credential data, present when registering
+    pub fn credential(&self) -> Option<AttestedCredentialRef<'a>> {
+        self.credential
+    }
+
+    /// Returns the CBOR encoded map of extension outputs, if any
+    pub fn extensions(&self) -> Option<&'a [u8]> {
+        self.extensions
+    }
+
+    /// Copies the authenticator data out of the buffer it was parsed from
+    pub fn into_owned(self) -> AuthenticatorData {
+        AuthenticatorData {
+            rp_id_hash: *self.rp_id_hash,
+            flags: self.flags,
+            counter: self.counter,
+            credential: self.credential.map(AttestedCredentialRef::into_owned),
+            extensions: self.extensions.map(<[u8]>::to_vec),
+        }
+    }
+}
+
+/// Views the `N` bytes at an offset of a buffer as an array
+fn array_ref<const N: usize>(data: &[u8], offset: usize) -> Result<&[u8; N], Error> {
+    data.get(offset..)
+        .and_then(|rest| rest.get(..N))
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(Error::Truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn parse_attested() {
+        let mut data = vec![7; 32];
+        data.push(FLAG_USER_PRESENT | FLAG_ATTESTED | FLAG_EXTENSIONS);
+        data.extend_from_slice(&[0, 0, 1, 2]);
+        data.extend_from_slice(&[9; 16]);
+        data.extend_from_slice(&[0, 2, 0xaa, 0xbb]);
+        // {1: 2} stands in for the key, {"credProtect": 1} for the extensions
+        data.extend_from_slice(&[0xa1, 0x01, 0x02]);
+        data.extend_from_slice(&[0xa1, 0x6b]);
+        data.extend_from_slice(b"credProtect");
+        data.push(0x01);
+
+        let auth_data = AuthenticatorData::parse(&data).unwrap();
+        assert_eq!(auth_data.rp_id_hash(), &[7; 32]);
+        assert_eq!(auth_data.counter(), 0x0102);
+        assert!(auth_data.user_present() && !auth_data.user_verified());
+        let credential = auth_data.credential().unwrap();
+        assert_eq!(credential.aaguid(), &[9; 16]);
+        assert_eq!(credential.id(), &[0xaa, 0xbb]);
+        assert_eq!(credential.public_key_bytes(), &[0xa1, 0x01, 0x02]);
+        assert_eq!(auth_data.extensions().map(<[u8]>::len), Some(14));
+
+        // every truncation fails cleanly
+        for len in 0..data.len() {
+            assert!(AuthenticatorData::parse(&data[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn borrowed_view() {
+        let mut data = vec![3; 32];
+        data.push(FLAG_USER_PRESENT | FLAG_USER_VERIFIED | FLAG_ATTESTED);
+        data.extend_from_slice(&[0, 0, 0, 5]);
+        data.extend_from_slice(&[9; 16]);
+        data.extend_from_slice(&[0, 1, 0xcc, 0xa1, 0x01, 0x02]);
+
+        let view = AuthenticatorDataRef::parse(&data).unwrap();
+        assert!(view.user_verified());
+        let credential = view.credential().unwrap();
+        assert_eq!(credential.id(), &[0xcc]);
+        // the view points into the buffer instead of copying it
+        assert_eq!(credential.public_key_bytes().as_ptr(), data[56..].as_ptr());
+        assert_eq!(view.into_owned(), AuthenticatorData::parse(&data).unwrap());
+    }
+
+    #[test]
+    fn reject_trailing_data() {
+        let mut data = vec![0; 37];
+        assert!(AuthenticatorData::parse(&data).is_ok());
+        data.push(0);
+        assert_eq!(AuthenticatorData::parse(&data), Err(Error::TrailingData));
+    }
+}
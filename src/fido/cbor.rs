@@ -0,0 +1,247 @@
+//! Minimal CBOR decoder
+//!
+//! Decodes the subset of CBOR ([RFC 8949](https://www.rfc-editor.org/rfc/rfc8949)) that
+//! authenticators produce: integers, byte and text strings, arrays, maps, booleans and
+//! null, all with definite lengths.  Tags are skipped.  Floats and indefinite lengths
+//! never appear in WebAuthn structures and are rejected, as is nesting deeper than a
+//! fixed limit.
+
+use super::Error;
+use alloc::{string::String, vec::Vec};
+use core::convert::TryFrom;
+
+/// Deepest nesting of arrays and maps accepted
+const MAX_DEPTH: usize = 16;
+
+/// A decoded CBOR data item
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Integer(i128),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    /// Members in the order they were encoded
+    Map(Vec<(Value, Value)>),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    /// Returns the member of a map with an integer key
+    ///
+    /// # Arguments
+    /// * `key` - Key of the member
+    pub fn get_int(&self, key: i128) -> Option<&Value> {
+        self.get(|k| *k == Value::Integer(key))
+    }
+
+    /// Returns the member of a map with a text key
+    ///
+    /// # Arguments
+    /// * `key` - Key of the member
+    pub fn get_text(&self, key: &str) -> Option<&Value> {
+        self.get(|k| matches!(k, Value::Text(k) if k == key))
+    }
+
+    /// Returns the integer, if this is one
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string, if this is one
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the text string, if this is one
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Value::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the items of an array, if this is one
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// Returns the first member of a map whose key matches
+    fn get(&self, matches: impl Fn(&Value) -> bool) -> Option<&Value> {
+        match self {
+            Value::Map(members) => members.iter().find(|(k, _)| matches(k)).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes a buffer holding exactly one data item
+///
+/// # Arguments
+/// * `data` - Encoded data item
+pub fn decode(data: &[u8]) -> Result<Value, Error> {
+    match decode_prefix(data)? {
+        (value, len) if len == data.len() => Ok(value),
+        _ => Err(Error::TrailingData),
+    }
+}
+
+/// Decodes the data item at the start of a buffer, returning it and its encoded length
+///
+/// # Arguments
+/// * `data` - Buffer starting with an encoded data item
+pub fn decode_prefix(data: &[u8]) -> Result<(Value, usize), Error> {
+    let mut decoder = Decoder { data, offset: 0 };
+    let value = decoder.value(0)?;
+    Ok((value, decoder.offset))
+}
+
+/// Reads data items from a buffer
+struct Decoder<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Decoder<'a> {
+    /// Decodes the next data item
+    fn value(&mut self, depth: usize) -> Result<Value, Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::InvalidCbor);
+        }
+
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if major == 7 {
+            return match info {
+                20 => Ok(Value::Bool(false)),
+                21 => Ok(Value::Bool(true)),
+                22 => Ok(Value::Null),
+                _ => Err(Error::InvalidCbor),
+            };
+        }
+
+        let arg = self.argument(info)?;
+        match major {
+            0 => Ok(Value::Integer(arg.into())),
+            1 => Ok(Value::Integer(-1 - i128::from(arg))),
+            2 => Ok(Value::Bytes(self.take(self.length(arg)?)?.to_vec())),
+            3 => String::from_utf8(self.take(self.length(arg)?)?.to_vec())
+                .map(Value::Text)
+                .map_err(|_| Error::InvalidCbor),
+            4 => {
+                let len = self.length(arg)?;
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(self.value(depth + 1)?);
+                }
+                Ok(Value::Array(items))
+            }
+            5 => {
+                let len = self.length(arg)?;
+                let mut members = Vec::new();
+                for _ in 0..len {
+                    let key = self.value(depth + 1)?;
+                    members.push((key, self.value(depth + 1)?));
+                }
+                Ok(Value::Map(members))
+            }
+            // tag, the tagged item follows
+            _ => self.value(depth + 1),
+        }
+    }
+
+    /// Reads the argument following the initial byte
+    fn argument(&mut self, info: u8) -> Result<u64, Error> {
+        let len = match info {
+            0..=23 => return Ok(info.into()),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            // reserved, or an indefinite length
+            _ => return Err(Error::InvalidCbor),
+        };
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |arg, &b| (arg << 8) | u64::from(b)))
+    }
+
+    /// Converts a length argument, rejecting lengths longer than what is left to read
+    /// (every item takes at least one byte)
+    fn length(&self, arg: u64) -> Result<usize, Error> {
+        match usize::try_from(arg) {
+            Ok(len) if len <= self.data.len() - self.offset => Ok(len),
+            _ => Err(Error::Truncated),
+        }
+    }
+
+    /// Consumes bytes from the buffer
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or(Error::Truncated)?;
+        self.offset += len;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn decode_items() {
+        // {1: 2, "a": [-1, h'0102', true, null], 3: "b"}
+        let data = [
+            0xa3, 0x01, 0x02, 0x61, 0x61, 0x84, 0x20, 0x42, 0x01, 0x02, 0xf5, 0xf6, 0x03, 0x61,
+            0x62,
+        ];
+        let value = decode(&data).unwrap();
+        assert_eq!(value.get_int(1), Some(&Value::Integer(2)));
+        assert_eq!(value.get_int(3).and_then(Value::as_text), Some("b"));
+        assert_eq!(
+            value.get_text("a"),
+            Some(&Value::Array(vec![
+                Value::Integer(-1),
+                Value::Bytes(vec![1, 2]),
+                Value::Bool(true),
+                Value::Null,
+            ]))
+        );
+
+        assert_eq!(
+            decode(&[0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Ok(Value::Integer(u64::MAX.into()))
+        );
+        assert_eq!(decode_prefix(&[0x01, 0x02]), Ok((Value::Integer(1), 1)));
+        assert_eq!(decode(&[0x01, 0x02]), Err(Error::TrailingData));
+    }
+
+    #[test]
+    fn reject_malformed() {
+        // truncated string, oversized array, indefinite length, float, bad UTF-8
+        assert_eq!(decode(&[0x43, 0x01]), Err(Error::Truncated));
+        assert_eq!(
+            decode(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Err(Error::Truncated)
+        );
+        assert_eq!(decode(&[0x9f, 0xff]), Err(Error::InvalidCbor));
+        assert_eq!(decode(&[0xf9, 0x3c, 0x00]), Err(Error::InvalidCbor));
+        assert_eq!(decode(&[0x61, 0xff]), Err(Error::InvalidCbor));
+
+        let nested = [0x81; MAX_DEPTH + 2];
+        assert_eq!(decode(&nested), Err(Error::InvalidCbor));
+    }
+}
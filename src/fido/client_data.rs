@@ -0,0 +1,359 @@
+//! Client data JSON
+//!
+//! The client serializes `CollectedClientData` as a JSON object.  Only the members a
+//! relying party checks are kept: `type`, `challenge`, `origin`, `crossOrigin` and
+//! `topOrigin`; any other member (e.g., `tokenBinding`) is validated as JSON and skipped.
+
+use super::Error;
+use alloc::string::String;
+use core::char;
+
+/// Deepest nesting of skipped arrays and objects accepted
+const MAX_DEPTH: usize = 16;
+
+/// The client data a credential signs over
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CollectedClientData {
+    ty: String,
+    challenge: String,
+    origin: String,
+    cross_origin: bool,
+    top_origin: Option<String>,
+}
+
+impl CollectedClientData {
+    /// Parses client data JSON
+    ///
+    /// # Arguments
+    /// * `json` - Raw client data JSON
+    pub fn parse(json: &[u8]) -> Result<CollectedClientData, Error> {
+        let mut parser = Parser { json, offset: 0 };
+        let mut client_data = CollectedClientData::default();
+        let (mut ty, mut challenge) = (false, false);
+
+        parser.expect(b'{')?;
+        if !parser.eat(b'}') {
+            loop {
+                let key = parser.string()?;
+                parser.expect(b':')?;
+                match key.as_str() {
+                    "type" => {
+                        client_data.ty = parser.string()?;
+                        ty = true;
+                    }
+                    "challenge" => {
+                        client_data.challenge = parser.string()?;
+                        challenge = true;
+                    }
+                    "origin" => client_data.origin = parser.string()?,
+                    "crossOrigin" => client_data.cross_origin = parser.boolean()?,
+                    "topOrigin" => client_data.top_origin = Some(parser.string()?),
+                    _ => parser.skip(0)?,
+                }
+                if parser.eat(b'}') {
+                    break;
+                }
+                parser.expect(b',')?;
+            }
+        }
+
+        parser.whitespace();
+        if parser.offset != json.len() {
+            return Err(Error::TrailingData);
+        }
+        if !ty {
+            return Err(Error::MissingField("type"));
+        }
+        if !challenge {
+            return Err(Error::MissingField("challenge"));
+        }
+        Ok(client_data)
+    }
+
+    /// Checks the ceremony, challenge and origin the client data was collected for
+    ///
+    /// # Arguments
+    /// * `ty` - Expected type, `webauthn.create` or `webauthn.get`
+    /// * `challenge` - Base64url encoded challenge issued with the request
+    /// * `origin` - Origin of the relying party
+    pub fn validate(&self, ty: &str, challenge: &str, origin: &str) -> Result<(), Error> {
+        if self.ty != ty {
+            return Err(Error::TypeMismatch);
+        }
+        if self.challenge != challenge {
+            return Err(Error::ChallengeMismatch);
+        }
+        if self.origin != origin {
+            return Err(Error::OriginMismatch);
+        }
+        Ok(())
+    }
+
+    /// Returns the ceremony (`webauthn.create` or `webauthn.get`)
+    pub fn ty(&self) -> &str {
+        &self.ty
+    }
+
+    /// Returns the base64url encoded challenge
+    pub fn challenge(&self) -> &str {
+        &self.challenge
+    }
+
+    /// Returns the origin of the caller
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// Returns true if the caller was in a cross-origin iframe
+    pub fn cross_origin(&self) -> bool {
+        self.cross_origin
+    }
+
+    /// Returns the origin of the top-level page, present when the caller was in a
+    /// cross-origin iframe
+    pub fn top_origin(&self) -> Option<&str> {
+        self.top_origin.as_deref()
+    }
+}
+
+/// Reads JSON values from a buffer
+struct Parser<'a> {
+    json: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Returns the next byte after whitespace, without consuming it
+    fn peek(&mut self) -> Result<u8, Error> {
+        self.whitespace();
+        self.json.get(self.offset).copied().ok_or(Error::Truncated)
+    }
+
+    /// Consumes the next byte after whitespace if it matches
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.peek() == Ok(byte) {
+            self.offset += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes the next byte after whitespace, failing unless it matches
+    fn expect(&mut self, byte: u8) -> Result<(), Error> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(Error::InvalidJson)
+        }
+    }
+
+    /// Consumes whitespace
+    fn whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.json.get(self.offset)
+        {
+            self.offset += 1;
+        }
+    }
+
+    /// Consumes a literal (e.g., `true`)
+    fn literal(&mut self, literal: &[u8]) -> bool {
+        self.whitespace();
+        if self.json[self.offset..].starts_with(literal) {
+            self.offset += literal.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Reads a boolean
+    fn boolean(&mut self) -> Result<bool, Error> {
+        if self.literal(b"true") {
+            Ok(true)
+        } else if self.literal(b"false") {
+            Ok(false)
+        } else {
+            Err(Error::InvalidJson)
+        }
+    }
+
+    /// Reads a string, unescaping it
+    fn string(&mut self) -> Result<String, Error> {
+        self.expect(b'"')?;
+        let mut s = String::new();
+        loop {
+            let start = self.offset;
+            while let Some(&b) = self.json.get(self.offset) {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.offset += 1;
+            }
+            s.push_str(
+                core::str::from_utf8(&self.json[start..self.offset])
+                    .map_err(|_| Error::InvalidJson)?,
+            );
+
+            match self.next()? {
+                b'"' => return Ok(s),
+                b'\\' => s.push(self.escape()?),
+                _ => return Err(Error::InvalidJson),
+            }
+        }
+    }
+
+    /// Reads the character of an escape sequence, after its backslash
+    fn escape(&mut self) -> Result<char, Error> {
+        Ok(match self.next()? {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => {
+                let unit = self.hex()?;
+                let code = if (0xd800..0xdc00).contains(&unit) {
+                    // high surrogate, a low surrogate must follow
+                    if self.next()? != b'\\' || self.next()? != b'u' {
+                        return Err(Error::InvalidJson);
+                    }
+                    let low = self.hex()?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(Error::InvalidJson);
+                    }
+                    0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    unit
+                };
+                char::from_u32(code).ok_or(Error::InvalidJson)?
+            }
+            _ => return Err(Error::InvalidJson),
+        })
+    }
+
+    /// Reads the four hex digits of a `\u` escape
+    fn hex(&mut self) -> Result<u32, Error> {
+        let digits = self
+            .json
+            .get(self.offset..self.offset + 4)
+            .ok_or(Error::Truncated)?;
+        let digits = core::str::from_utf8(digits).map_err(|_| Error::InvalidJson)?;
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(Error::InvalidJson);
+        }
+        self.offset += 4;
+        u32::from_str_radix(digits, 16).map_err(|_| Error::InvalidJson)
+    }
+
+    /// Consumes the next byte
+    fn next(&mut self) -> Result<u8, Error> {
+        let b = *self.json.get(self.offset).ok_or(Error::Truncated)?;
+        self.offset += 1;
+        Ok(b)
+    }
+
+    /// Consumes a value of any type
+    fn skip(&mut self, depth: usize) -> Result<(), Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::InvalidJson);
+        }
+
+        match self.peek()? {
+            b'"' => self.string().map(|_| ()),
+            open @ b'{' | open @ b'[' => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                self.offset += 1;
+                if self.eat(close) {
+                    return Ok(());
+                }
+                loop {
+                    if open == b'{' {
+                        self.string()?;
+                        self.expect(b':')?;
+                    }
+                    self.skip(depth + 1)?;
+                    if self.eat(close) {
+                        return Ok(());
+                    }
+                    self.expect(b',')?;
+                }
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = self.offset;
+                while let Some(b'-') | Some(b'+') | Some(b'.') | Some(b'e') | Some(b'E')
+                | Some(b'0'..=b'9') = self.json.get(self.offset)
+                {
+                    self.offset += 1;
+                }
+                // validated loosely, the value is never used
+                if self.json[start..self.offset].iter().any(u8::is_ascii_digit) {
+                    Ok(())
+                } else {
+                    Err(Error::InvalidJson)
+                }
+            }
+            _ if self.literal(b"true") || self.literal(b"false") || self.literal(b"null") => Ok(()),
+            _ => Err(Error::InvalidJson),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_client_data() {
+        let json = r#" {"type":"webauthn.get","challenge":"c2l0ZQ","origin":"https://app.example.com",
+            "crossOrigin":false,"tokenBinding":{"status":"supported"},"other":[1,-2.5e3,null,"é\n"]} "#;
+        let client_data = CollectedClientData::parse(json.as_bytes()).unwrap();
+        assert_eq!(client_data.ty(), "webauthn.get");
+        assert_eq!(client_data.challenge(), "c2l0ZQ");
+        assert!(!client_data.cross_origin());
+        assert_eq!(client_data.top_origin(), None);
+        assert!(client_data
+            .validate("webauthn.get", "c2l0ZQ", "https://app.example.com")
+            .is_ok());
+        assert_eq!(
+            client_data.validate("webauthn.create", "c2l0ZQ", "https://app.example.com"),
+            Err(Error::TypeMismatch)
+        );
+        assert_eq!(
+            client_data.validate("webauthn.get", "c2l0ZQ", "https://evil.example.com"),
+            Err(Error::OriginMismatch)
+        );
+
+        let escaped = CollectedClientData::parse(
+            r#"{"type":"webauthn.create","challenge":"a\"b","origin":"é\ud83d\ude00"}"#.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(escaped.challenge(), "a\"b");
+        assert_eq!(escaped.origin(), "\u{e9}\u{1f600}");
+
+        let embedded = CollectedClientData::parse(
+            br#"{"type":"webauthn.get","challenge":"a","crossOrigin":true,"topOrigin":"https://shop.example.net"}"#,
+        )
+        .unwrap();
+        assert!(embedded.cross_origin());
+        assert_eq!(embedded.top_origin(), Some("https://shop.example.net"));
+    }
+
+    #[test]
+    fn reject_malformed() {
+        assert_eq!(
+            CollectedClientData::parse(br#"{"type":"webauthn.get"}"#),
+            Err(Error::MissingField("challenge"))
+        );
+        assert_eq!(
+            CollectedClientData::parse(br#"{"type":"webauthn.get","challenge":"a"}x"#),
+            Err(Error::TrailingData)
+        );
+        assert!(CollectedClientData::parse(br#"{"type":"webauthn.get","#).is_err());
+        assert!(CollectedClientData::parse(br#"{"type":"\ud83d"}"#).is_err());
+        assert!(CollectedClientData::parse(br#"["type"]"#).is_err());
+    }
+}
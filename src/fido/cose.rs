@@ -0,0 +1,141 @@
+//! COSE credential public keys
+//!
+//! Credential public keys are COSE_Key maps ([RFC 9053](https://www.rfc-editor.org/rfc/rfc9053)).
+//! The algorithms authenticators use in practice are supported: ES256 keys on P-256,
+//! EdDSA keys on Ed25519 and RS256 keys.
+
+use super::{cbor::Value, Error};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// COSE identifier of ES256 (ECDSA with SHA-256)
+pub const ES256: i64 = -7;
+
+/// COSE identifier of EdDSA
+pub const EDDSA: i64 = -8;
+
+/// COSE identifier of RS256 (RSASSA-PKCS1-v1_5 with SHA-256)
+pub const RS256: i64 = -257;
+
+/// A credential public key
+#[derive(Clone, Debug, PartialEq)]
+pub enum CoseKey {
+    /// Elliptic curve key on P-256 (kty 2)
+    Ec2 { x: Vec<u8>, y: Vec<u8> },
+
+    /// Octet key pair on Ed25519 (kty 1)
+    Okp { x: Vec<u8> },
+
+    /// RSA key (kty 3)
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+}
+
+impl CoseKey {
+    /// Parses an encoded COSE key
+    ///
+    /// # Arguments
+    /// * `data` - CBOR encoded COSE_Key map
+    pub fn parse(data: &[u8]) -> Result<CoseKey, Error> {
+        Self::from_value(&super::cbor::decode(data)?)
+    }
+
+    /// Converts a decoded COSE key
+    ///
+    /// # Arguments
+    /// * `key` - Decoded COSE_Key map
+    pub fn from_value(key: &Value) -> Result<CoseKey, Error> {
+        let int = |label, name| {
+            key.get_int(label)
+                .and_then(Value::as_integer)
+                .ok_or(Error::MissingField(name))
+        };
+        let bytes = |label, name| {
+            key.get_int(label)
+                .and_then(Value::as_bytes)
+                .map(<[u8]>::to_vec)
+                .ok_or(Error::MissingField(name))
+        };
+
+        let alg = i64::try_from(int(3, "alg")?).map_err(|_| Error::UnsupportedKey)?;
+        match (int(1, "kty")?, alg) {
+            (2, ES256) => {
+                // P-256
+                if int(-1, "crv")? != 1 {
+                    return Err(Error::UnsupportedKey);
+                }
+                let (x, y) = (bytes(-2, "x")?, bytes(-3, "y")?);
+                if x.len() != 32 || y.len() != 32 {
+                    return Err(Error::UnsupportedKey);
+                }
+                Ok(CoseKey::Ec2 { x, y })
+            }
+            (1, EDDSA) => {
+                // Ed25519
+                if int(-1, "crv")? != 6 {
+                    return Err(Error::UnsupportedKey);
+                }
+                let x = bytes(-2, "x")?;
+                if x.len() != 32 {
+                    return Err(Error::UnsupportedKey);
+                }
+                Ok(CoseKey::Okp { x })
+            }
+            (3, RS256) => Ok(CoseKey::Rsa {
+                n: bytes(-1, "n")?,
+                e: bytes(-2, "e")?,
+            }),
+            _ => Err(Error::UnsupportedKey),
+        }
+    }
+
+    /// Returns the COSE identifier of the key's algorithm
+    pub fn alg(&self) -> i64 {
+        match self {
+            CoseKey::Ec2 { .. } => ES256,
+            CoseKey::Okp { .. } => EDDSA,
+            CoseKey::Rsa { .. } => RS256,
+        }
+    }
+
+    /// Returns an EC2 key as an uncompressed point (`0x04 || x || y`), the raw format
+    /// `fido-u2f` attestation signs over
+    pub fn to_uncompressed(&self) -> Option<Vec<u8>> {
+        match self {
+            CoseKey::Ec2 { x, y } => Some([&[0x04][..], x, y].concat()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Builds an ES256 key map
+    fn ec2(crv: i128) -> Value {
+        Value::Map(vec![
+            (Value::Integer(1), Value::Integer(2)),
+            (Value::Integer(3), Value::Integer(ES256.into())),
+            (Value::Integer(-1), Value::Integer(crv)),
+            (Value::Integer(-2), Value::Bytes(vec![1; 32])),
+            (Value::Integer(-3), Value::Bytes(vec![2; 32])),
+        ])
+    }
+
+    #[test]
+    fn parse_ec2() {
+        let key = CoseKey::from_value(&ec2(1)).unwrap();
+        assert_eq!(key.alg(), ES256);
+        let raw = key.to_uncompressed().unwrap();
+        assert_eq!(raw.len(), 65);
+        assert_eq!((raw[0], raw[1], raw[64]), (0x04, 1, 2));
+
+        // P-384
+        assert_eq!(CoseKey::from_value(&ec2(2)), Err(Error::UnsupportedKey));
+        assert_eq!(
+            CoseKey::from_value(&Value::Map(vec![])),
+            Err(Error::MissingField("alg"))
+        );
+    }
+}
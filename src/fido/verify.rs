@@ -0,0 +1,115 @@
+//! Signature verification (`fido-crypto` feature)
+//!
+//! Verifies assertions against a stored credential public key, and registrations
+//! carrying `none` or `packed` self attestation.  Attestation statements with a
+//! certificate chain are left to the `webauthn` module, which validates certificates.
+
+use super::{attestation::AttestationObject, auth_data::AuthenticatorData, cose::CoseKey, Error};
+use ring::{
+    digest::{digest, SHA256},
+    signature::{self, RsaPublicKeyComponents, UnparsedPublicKey},
+};
+
+/// Verifies a signature made with a credential's private key
+///
+/// # Arguments
+/// * `key` - Public key of the credential
+/// * `message` - Signed message
+/// * `sig` - Signature (ASN.1 DER encoded for ES256)
+pub fn verify_signature(key: &CoseKey, message: &[u8], sig: &[u8]) -> Result<(), Error> {
+    let result = match key {
+        CoseKey::Ec2 { .. } => {
+            let point = key.to_uncompressed().ok_or(Error::UnsupportedKey)?;
+            UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, point).verify(message, sig)
+        }
+        CoseKey::Okp { x } => UnparsedPublicKey::new(&signature::ED25519, x).verify(message, sig),
+        CoseKey::Rsa { n, e } => RsaPublicKeyComponents { n, e }.verify(
+            &signature::RSA_PKCS1_2048_8192_SHA256,
+            message,
+            sig,
+        ),
+    };
+    result.map_err(|_| Error::BadSignature)
+}
+
+/// Verifies an assertion, which signs `authenticatorData || SHA-256(clientDataJSON)`
+///
+/// # Arguments
+/// * `key` - Public key stored when the credential was registered
+/// * `auth_data` - Raw authenticator data of the assertion
+/// * `client_data_json` - Raw client data JSON of the assertion
+/// * `sig` - Signature of the assertion
+pub fn verify_assertion(
+    key: &CoseKey,
+    auth_data: &[u8],
+    client_data_json: &[u8],
+    sig: &[u8],
+) -> Result<(), Error> {
+    let message = [auth_data, digest(&SHA256, client_data_json).as_ref()].concat();
+    verify_signature(key, &message, sig)
+}
+
+/// Verifies the attestation statement of a registration and returns its authenticator
+/// data.  `none` statements are accepted as is; `packed` statements must be self
+/// attestation, signed by the new credential itself
+///
+/// # Arguments
+/// * `object` - Attestation object of the registration
+/// * `client_data_json` - Raw client data JSON of the registration
+pub fn verify_attestation(
+    object: &AttestationObject,
+    client_data_json: &[u8],
+) -> Result<AuthenticatorData, Error> {
+    let auth_data = object.auth_data()?;
+    let key = auth_data
+        .credential()
+        .ok_or(Error::MissingField("attestedCredentialData"))?
+        .public_key()?;
+
+    match object.fmt() {
+        "none" => Ok(auth_data),
+        "packed" if object.x5c().is_empty() => {
+            if object.alg() != Some(key.alg().into()) {
+                return Err(Error::UnsupportedKey);
+            }
+            let sig = object.sig().ok_or(Error::MissingField("sig"))?;
+            verify_assertion(&key, object.auth_data_bytes(), client_data_json, sig)?;
+            Ok(auth_data)
+        }
+        _ => Err(Error::UnsupportedFormat),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+    };
+
+    #[test]
+    fn assertion_signature() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref()).unwrap();
+        let point = pair.public_key().as_ref();
+        let key = CoseKey::Ec2 {
+            x: point[1..33].to_vec(),
+            y: point[33..].to_vec(),
+        };
+
+        let auth_data = vec![1; 37];
+        let client_data = br#"{"type":"webauthn.get","challenge":"a"}"#;
+        let message = [&auth_data[..], digest(&SHA256, client_data).as_ref()].concat();
+        let sig = pair.sign(&rng, &message).unwrap();
+
+        assert!(verify_assertion(&key, &auth_data, client_data, sig.as_ref()).is_ok());
+        assert_eq!(
+            verify_assertion(&key, &auth_data, b"{}", sig.as_ref()),
+            Err(Error::BadSignature)
+        );
+    }
+}
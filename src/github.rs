@@ -0,0 +1,249 @@
+//! Sign in with GitHub
+//!
+//! [`GitHub`] is a preset of the [`OAuth2Client`] for GitHub's OAuth apps: after the user
+//! authorizes the application, [`GitHub::authenticate`] exchanges the code for an access
+//! token, then fetches the user's profile and primary verified email address.  GitHub
+//! only reports the email a user chose to make public on `/user`, so the `user:email`
+//! scope is requested to read the primary address from `/user/emails` instead.
+//!
+//! Source: [Authorizing OAuth
+//! apps](https://docs.github.com/en/apps/oauth-apps/building-oauth-apps/authorizing-oauth-apps)
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::github::GitHub;
+//!
+//! let github = GitHub::new("my-client-id", "my-client-secret")
+//!     .redirect_uri("https://app.example.com/auth/github/callback");
+//!
+//! // store `request` in the session and redirect the user to `request.url`...
+//! let request = github.authorize()?;
+//!
+//! // ...then authenticate with the state and code they return with
+//! let profile = github.authenticate(&request, &params.state, &params.code).await?;
+//! ```
+
+use crate::{
+    events::{self, AuthEvent},
+    oauth2::{AuthorizationRequest, OAuth2Client, OAuth2Error},
+};
+use reqwest::header::{ACCEPT, USER_AGENT};
+use serde::{de::DeserializeOwned, Deserialize};
+use thiserror::Error;
+
+/// GitHub's authorization endpoint
+const AUTH_URL: &str = "https://github.com/login/oauth/authorize";
+
+/// GitHub's token endpoint
+const TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+
+/// GitHub's REST api
+const API_URL: &str = "https://api.github.com";
+
+/// Scopes needed to read the user's profile and email addresses
+const SCOPES: &[&str] = &["read:user", "user:email"];
+
+/// Errors that may occur when signing in with GitHub
+#[derive(Debug, Error)]
+pub enum GitHubError {
+    /// The authorization code could not be exchanged for an access token
+    #[error(transparent)]
+    OAuth2(#[from] OAuth2Error),
+
+    /// A request to GitHub's api failed
+    #[error("api request failed: {0}")]
+    Api(#[from] reqwest::Error),
+}
+
+/// A GitHub user
+#[derive(Clone, Debug, Deserialize)]
+pub struct GitHubProfile {
+    /// Immutable id of the user
+    pub id: u64,
+
+    /// Username, which the user may change
+    pub login: String,
+
+    /// Display name
+    pub name: Option<String>,
+
+    /// Primary email address, if it has been verified
+    #[serde(skip)]
+    pub email: Option<String>,
+
+    /// Link to the user's avatar
+    pub avatar_url: String,
+
+    /// Link to the user's profile page
+    pub html_url: String,
+}
+
+/// An entry of `/user/emails`
+#[derive(Deserialize)]
+struct Email {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+/// Signs users in with GitHub
+#[derive(Clone, Debug)]
+pub struct GitHub {
+    client: OAuth2Client,
+    api_url: String,
+    http: reqwest::Client,
+}
+
+impl GitHub {
+    /// Creates a new GitHub sign in
+    ///
+    /// # Arguments
+    /// * `client_id` - Client id of the OAuth app
+    /// * `client_secret` - Client secret of the OAuth app
+    pub fn new(client_id: impl Into<String>, client_secret: impl Into<String>) -> GitHub {
+        GitHub {
+            client: OAuth2Client::new(client_id, AUTH_URL, TOKEN_URL)
+                .client_secret(client_secret)
+                .scopes(SCOPES),
+            api_url: API_URL.to_owned(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Sets the url GitHub redirects the user back to, if not the app's default
+    ///
+    /// # Arguments
+    /// * `uri` - Callback url registered with the OAuth app
+    pub fn redirect_uri(mut self, uri: impl Into<String>) -> Self {
+        self.client = self.client.redirect_uri(uri);
+        self
+    }
+
+    /// Starts signing in, returning the request to keep until GitHub redirects back
+    pub fn authorize(&self) -> Result<AuthorizationRequest, GitHubError> {
+        Ok(self.client.authorize()?)
+    }
+
+    /// Exchanges an authorization code for an access token and returns the user's profile
+    ///
+    /// # Arguments
+    /// * `request` - Request returned by `authorize()`
+    /// * `state` - State GitHub redirected the user back with
+    /// * `code` - Code GitHub redirected the user back with
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "github.authenticate",
+            skip_all,
+            fields(login = tracing::field::Empty),
+            err
+        )
+    )]
+    pub async fn authenticate(
+        &self,
+        request: &AuthorizationRequest,
+        state: &str,
+        code: &str,
+    ) -> Result<GitHubProfile, GitHubError> {
+        let result = self.profile(request, state, code).await;
+        match result {
+            Ok(ref profile) => {
+                trace_record!("login", &profile.login);
+                metric_counter!("auth_oauth_logins_total", "provider" => "github", "outcome" => "success");
+                events::emit(AuthEvent::OAuthLoginSucceeded {
+                    provider: "github".to_owned(),
+                    subject: profile.id.to_string(),
+                });
+            }
+            Err(ref e) => {
+                metric_counter!("auth_oauth_logins_total", "provider" => "github", "outcome" => "failure");
+                events::emit(AuthEvent::OAuthLoginFailed {
+                    provider: "github".to_owned(),
+                    reason: e.to_string(),
+                });
+            }
+        }
+        result
+    }
+
+    /// Exchanges the code and fetches the profile, see `authenticate()`
+    async fn profile(
+        &self,
+        request: &AuthorizationRequest,
+        state: &str,
+        code: &str,
+    ) -> Result<GitHubProfile, GitHubError> {
+        let token = self.client.exchange(request, state, code).await?;
+
+        let mut profile: GitHubProfile = self.get(&token.access_token, "/user").await?;
+        let emails: Vec<Email> = self.get(&token.access_token, "/user/emails").await?;
+        profile.email = primary_email(emails);
+        Ok(profile)
+    }
+
+    /// Fetches a resource from GitHub's api as the user
+    ///
+    /// # Arguments
+    /// * `token` - User's access token
+    /// * `path` - Path of the resource
+    async fn get<T: DeserializeOwned>(&self, token: &str, path: &str) -> Result<T, GitHubError> {
+        // GitHub rejects requests without a user agent
+        let value = self
+            .http
+            .get(&format!("{}{}", self.api_url, path))
+            .bearer_auth(token)
+            .header(ACCEPT, "application/vnd.github+json")
+            .header(USER_AGENT, concat!("auth-rs/", env!("CARGO_PKG_VERSION")))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(value)
+    }
+}
+
+/// Returns the primary email address, if it has been verified
+fn primary_email(emails: Vec<Email>) -> Option<String> {
+    emails
+        .into_iter()
+        .find(|e| e.primary && e.verified)
+        .map(|e| e.email)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile() {
+        let profile: GitHubProfile = serde_json::from_str(
+            r#"{"login":"octocat","id":1,"name":"The Octocat","email":"public@example.com",
+                "avatar_url":"https://github.com/images/error/octocat_happy.gif",
+                "html_url":"https://github.com/octocat","type":"User"}"#,
+        )
+        .unwrap();
+        assert_eq!(profile.login, "octocat");
+
+        // the public email is ignored in favor of the primary verified one
+        assert!(profile.email.is_none());
+    }
+
+    #[test]
+    fn primary_verified_email() {
+        let emails = |json| serde_json::from_str::<Vec<Email>>(json).unwrap();
+        assert_eq!(
+            primary_email(emails(
+                r#"[{"email":"work@example.com","primary":false,"verified":true,"visibility":null},
+                    {"email":"octocat@example.com","primary":true,"verified":true,"visibility":"public"}]"#
+            ))
+            .as_deref(),
+            Some("octocat@example.com")
+        );
+        assert!(primary_email(emails(
+            r#"[{"email":"octocat@example.com","primary":true,"verified":false}]"#
+        ))
+        .is_none());
+    }
+}
@@ -1,96 +1,270 @@
 //! Validate a Google JWT received when using a Google Login
 //!
+//! Keys are fetched with `reqwest` when the `reqwest` feature is enabled, or with any
+//! HTTP client through a [`KeyFetcher`].
+//!
+//! With the `refresher` feature, [`GoogleAuth::spawn_refresher`] keeps the keys fresh
+//! from a background tokio task.
+//!
+//! [`ServiceAccount`] signs JWTs with a service account's key and exchanges them for
+//! access tokens to call Google APIs.
+//!
+//! With the `integrity` feature, [`integrity`] verifies Google Play Integrity tokens.
+//!
 //! Source: [Google Sign-In for
 //! Websites](https://developers.google.com/identity/sign-in/web/sign-in)
 
+mod cache;
+use cache::TokenCache;
+
+mod fetch;
+pub use fetch::*;
+
 mod key;
 pub use key::*;
 
 mod store;
 pub use store::*;
 
+mod service;
+pub use service::*;
+
+#[cfg(feature = "refresher")]
+mod refresh;
+#[cfg(feature = "refresher")]
+pub use refresh::*;
+
+#[cfg(feature = "integrity")]
+pub mod integrity;
+
+use crate::events::{self, AuthEvent};
 use chrono::{prelude::*, Duration};
-use jsonwebtoken::{decode, decode_header, Algorithm, Validation};
-use parking_lot::RwLock;
-use serde::Deserialize;
-use std::{collections::HashSet, default::Default, sync::Arc};
+use jsonwebtoken::{decode, decode_header, errors::ErrorKind, Algorithm, Validation};
+use parking_lot::{Mutex, RwLock};
+use ring::constant_time;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+use std::{collections::HashSet, default::Default, ops::Deref, sync::Arc};
+use thiserror::Error;
 
 const TYP_JWT: &str = "jwt";
 
+/// Issuers of Google id tokens, which Google uses interchangeably
+const ISSUERS: &[&str] = &["accounts.google.com", "https://accounts.google.com"];
+
+/// Url of Google's JSON Web Key Set
+const CERTS_URL: &str = "https://www.googleapis.com/oauth2/v3/certs";
+
 /// All errors that may occur from using this library
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum GoogleError {
     /// Occurs when the header fails to decode or if the `typ` field is not JWT (case insenstive)
+    #[error("malformed token header")]
     BadHeader,
 
     /// Occurs when the header is missing the `kid` field
+    #[error("token header is missing a key id")]
     MissingKeyId,
 
     /// Occurs when attempting the fetch the keys fails
-    FetchKeysFailed,
+    #[error("failed to fetch keys: {0}")]
+    Network(#[source] FetchError),
+
+    /// Occurs when fetching the keys times out (see [`FetchTimeout`])
+    #[error("timed out fetching keys")]
+    Timeout,
 
     /// Occurs when was not found in either our cache or from Google
+    #[error("signing key not found")]
     KeyNotFound,
 
-    /// Occurs if validating the JWT fails
-    ValidationFailed,
+    /// Occurs when the token has expired
+    #[error("token expired")]
+    Expired,
+
+    /// Occurs when the token was not issued to this client
+    #[error("audience mismatch")]
+    AudienceMismatch,
+
+    /// Occurs when the `iss` claim is not one of the accepted issuers
+    #[error("issuer mismatch")]
+    IssuerMismatch,
+
+    /// Occurs when the token's signature does not verify
+    #[error("invalid signature")]
+    SignatureInvalid,
+
+    /// Occurs if validating the JWT fails for any other reason
+    #[error("token validation failed: {0}")]
+    ValidationFailed(#[source] jsonwebtoken::errors::Error),
+
+    /// Occurs when a required claim is missing
+    #[error("missing required claim `{0}`")]
+    MissingClaim(String),
+
+    /// Occurs when the `nonce` claim is missing or does not match the expected nonce
+    #[error("nonce mismatch")]
+    NonceMismatch,
+
+    /// Occurs when hosted domains are required and the `hd` claim is missing or not one
+    /// of them
+    #[error("hosted domain not allowed")]
+    HostedDomainNotAllowed,
+
+    /// Occurs when a service account key file or its private key is invalid, or signing
+    /// a JWT with it fails
+    #[error("invalid service account: {0}")]
+    InvalidServiceAccount(#[source] jsonwebtoken::errors::Error),
+
+    /// Occurs when Google's token endpoint rejects a service account's JWT
+    #[error("token exchange failed: {error}")]
+    TokenExchange {
+        error: String,
+        description: Option<String>,
+    },
+}
+
+impl From<jsonwebtoken::errors::Error> for GoogleError {
+    fn from(e: jsonwebtoken::errors::Error) -> GoogleError {
+        match e.kind() {
+            ErrorKind::ExpiredSignature => GoogleError::Expired,
+            ErrorKind::InvalidAudience => GoogleError::AudienceMismatch,
+            ErrorKind::InvalidIssuer => GoogleError::IssuerMismatch,
+            ErrorKind::InvalidSignature => GoogleError::SignatureInvalid,
+            _ => GoogleError::ValidationFailed(e),
+        }
+    }
+}
+
+/// The claims of a verified id token: the registered claims every Google id token
+/// carries, plus a caller-supplied type `T` (by default [`GoogleClaims`]) holding the
+/// rest.  `T`'s fields can be read directly from the profile
+#[derive(Clone, Deserialize, Debug)]
+pub struct Profile<T = GoogleClaims> {
+    /// Issuer of the token
+    pub iss: String,
+
+    /// Unique id of the user's Google account
+    pub sub: String,
+
+    /// Audience (the client id of the application)
+    pub aud: String,
+
+    /// Client id of the authorized presenter, if different from the audience
+    #[serde(default)]
+    pub azp: Option<String>,
+
+    /// Expiration time (seconds since the epoch)
+    pub exp: i64,
+
+    /// Issued at time (seconds since the epoch)
+    pub iat: i64,
+
+    /// Hosted (G Suite / Workspace) domain of the user, absent for consumer accounts
+    #[serde(default)]
+    pub hd: Option<String>,
+
+    /// Nonce passed in the authentication request, if any
+    #[serde(default)]
+    pub nonce: Option<String>,
+
+    /// All other claims
+    #[serde(flatten)]
+    pub claims: T,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct Profile {
+impl<T> Deref for Profile<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.claims
+    }
+}
+
+/// The profile claims Google includes with the `email` and `profile` scopes
+#[derive(Clone, Default, Deserialize, Debug)]
+pub struct GoogleClaims {
     /// User's Google email address
-    pub email: String,
+    #[serde(default)]
+    pub email: Option<String>,
 
     /// True if the user has verified their email address
+    #[serde(default)]
     pub email_verified: bool,
 
     /// Name the user goes by (username)
-    pub name: String,
+    #[serde(default)]
+    pub name: Option<String>,
 
     /// Link to profile picture image
-    pub picture: String,
+    #[serde(default)]
+    pub picture: Option<String>,
 
     /// Given (or first) name
-    pub given_name: String,
+    #[serde(default)]
+    pub given_name: Option<String>,
 
     /// Family (or last) name
-    pub family_name: String,
+    #[serde(default)]
+    pub family_name: Option<String>,
 
     /// Locale
-    pub locale: String,
-}
-
-/// The response from Google with new keys
-#[derive(Deserialize, Debug)]
-struct Response {
-    pub keys: Vec<Jwk>,
+    #[serde(default)]
+    pub locale: Option<String>,
 }
 
 #[derive(Clone)]
 pub struct GoogleAuth<S> {
     inner: Arc<RwLock<GoogleAuthInner<S>>>,
+    fetcher: Arc<dyn KeyFetcher>,
+    cache: Option<Arc<Mutex<TokenCache>>>,
 }
 
 #[derive(Clone)]
 struct GoogleAuthInner<S> {
     store: S,
     expire: Option<DateTime<Utc>>,
-    validation: Validation
+    validation: Validation,
+    issuers: HashSet<String>,
+    hosted_domains: Option<HashSet<String>>,
+    required_claims: Vec<String>,
+    stale_grace: Option<Duration>,
 }
 
 impl<S> GoogleAuth<S>
 where
     S: CertStore,
 {
+    /// Creates a new verifier, fetching keys with `reqwest`
+    ///
+    /// # Arguments
+    /// * `store` - Store for Google's signing keys
+    /// * `client_id` - Client id tokens must be issued to
+    #[cfg(feature = "reqwest")]
     pub fn new(store: S, client_id: impl Into<String>) -> GoogleAuth<S> {
+        Self::with_fetcher(store, ReqwestFetcher::new(), client_id)
+    }
+
+    /// Creates a new verifier, fetching keys with a custom HTTP client
+    ///
+    /// # Arguments
+    /// * `store` - Store for Google's signing keys
+    /// * `fetcher` - Fetcher to fetch Google's signing keys with
+    /// * `client_id` - Client id tokens must be issued to
+    pub fn with_fetcher(
+        store: S,
+        fetcher: impl KeyFetcher + 'static,
+        client_id: impl Into<String>,
+    ) -> GoogleAuth<S> {
         // build the validation struct
         let mut aud = HashSet::new();
         aud.insert(client_id.into());
 
+        // jsonwebtoken only accepts a single issuer, so the issuer is checked separately
         let validation = Validation {
             leeway: 0,
             validate_exp: true,
-            iss: Some("accounts.google.com".to_owned()),
+            iss: None,
             aud: Some(aud),
             algorithms: vec![Algorithm::RS256],
             ..Default::default()
@@ -98,42 +272,158 @@ where
 
         GoogleAuth {
             inner: Arc::new(RwLock::new(GoogleAuthInner {
+                expire: store.expiry().or_else(|| Some(Utc::now())),
                 store,
-                expire: Some(Utc::now()),
                 validation,
-            }))
+                issuers: ISSUERS.iter().map(|iss| iss.to_string()).collect(),
+                hosted_domains: None,
+                required_claims: Vec::new(),
+                stale_grace: None,
+            })),
+            fetcher: Arc::new(fetcher),
+            cache: None,
         }
     }
 
-    async fn fetch(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let resp = reqwest::get("https://www.googleapis.com/oauth2/v3/certs").await?;
+    /// Sets the accepted issuers, replacing the default of both forms Google uses
+    /// (`accounts.google.com` and `https://accounts.google.com`)
+    ///
+    /// # Arguments
+    /// * `issuers` - Accepted values of the `iss` claim
+    pub fn issuers(self, issuers: &[&str]) -> Self {
+        self.inner.write().issuers = issuers.iter().map(|iss| iss.to_string()).collect();
+        self
+    }
 
-        // examine the `Cache-Control` header per Google documentation
-        let mut cache = CacheControl::new();
-        let headers = resp.headers().get_all(reqwest::header::CACHE_CONTROL);
-        for header in headers {
-            cache.update(header.to_str().unwrap());
-        }
+    /// Requires the `hd` claim to be one of a set of hosted (G Suite / Workspace)
+    /// domains, rejecting tokens of consumer accounts and other organizations
+    ///
+    /// # Arguments
+    /// * `domains` - Accepted hosted domains
+    pub fn hosted_domains(self, domains: &[&str]) -> Self {
+        self.inner.write().hosted_domains =
+            Some(domains.iter().map(|d| d.to_ascii_lowercase()).collect());
+        self
+    }
+
+    /// Sets the allowed clock skew (in seconds) when checking a token's `exp` and `nbf`
+    /// claims.  Defaults to no skew
+    ///
+    /// # Arguments
+    /// * `leeway` - Allowed clock skew, in seconds
+    pub fn leeway(self, leeway: u64) -> Self {
+        self.inner.write().validation.leeway = leeway;
+        self
+    }
+
+    /// Sets whether expired tokens are rejected.  Defaults to true
+    ///
+    /// # Arguments
+    /// * `validate` - True to check the `exp` claim
+    pub fn validate_exp(self, validate: bool) -> Self {
+        self.inner.write().validation.validate_exp = validate;
+        self
+    }
+
+    /// Sets whether tokens used before their `nbf` claim are rejected.  Defaults to false
+    ///
+    /// # Arguments
+    /// * `validate` - True to check the `nbf` claim
+    pub fn validate_nbf(self, validate: bool) -> Self {
+        self.inner.write().validation.validate_nbf = validate;
+        self
+    }
+
+    /// Sets the accepted signing algorithms (e.g., ES256 for providers signing with EC
+    /// keys).  Defaults to RS256
+    ///
+    /// # Arguments
+    /// * `algorithms` - Accepted values of the header's `alg` field
+    pub fn algorithms(self, algorithms: &[Algorithm]) -> Self {
+        self.inner.write().validation.algorithms = algorithms.to_vec();
+        self
+    }
+
+    /// Keeps verifying tokens against expired keys for a grace period when refetching
+    /// them fails, so an outage at Google does not fail every login.  Disabled by default
+    ///
+    /// # Arguments
+    /// * `grace` - How long after the keys expire they may still be used
+    pub fn stale_grace(self, grace: std::time::Duration) -> Self {
+        self.inner.write().stale_grace = Duration::from_std(grace).ok();
+        self
+    }
+
+    /// Remembers verified tokens until they expire, so a token presented on every request
+    /// only has its signature checked once.  The other checks still run on every
+    /// verification
+    ///
+    /// # Arguments
+    /// * `capacity` - Maximum number of tokens to remember
+    pub fn cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(Arc::new(Mutex::new(TokenCache::new(capacity))));
+        self
+    }
 
-        if cache.max_age > 0 {
+    /// Rejects tokens missing any of a set of claims
+    ///
+    /// # Arguments
+    /// * `claims` - Names of the required claims
+    pub fn required_claims(self, claims: &[&str]) -> Self {
+        self.inner.write().required_claims = claims.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "google.fetch_keys",
+            skip_all,
+            fields(keys = tracing::field::Empty),
+            err
+        )
+    )]
+    async fn fetch(&mut self) -> Result<(), FetchError> {
+        let (keys, max_age) = fetch_jwks(self.fetcher.as_ref(), CERTS_URL).await?;
+
+        if max_age > 0 {
             // set the new expiration time
-            if let Ok(duration) = Duration::from_std(std::time::Duration::from_secs(cache.max_age)) {
+            if let Ok(duration) = Duration::from_std(std::time::Duration::from_secs(max_age)) {
                 let mut inner = self.inner.write();
                 inner.expire = Some(Utc::now() + duration);
             }
         }
 
-        let response = resp.json::<Response>().await?;
+        trace_record!("keys", keys.len());
+        metric_counter!("auth_google_key_refreshes_total", "outcome" => "success");
+        events::emit(AuthEvent::GoogleKeyRefresh { keys: keys.len() });
+
         let mut inner = self.inner.write();
-        inner.store.update(response.keys);
+        let expire = inner.expire;
+        inner.store.update_with_expiry(keys, expire);
         Ok(())
     }
 
+    /// Fetches the keys, reporting failures
+    async fn refresh(&mut self) -> Result<(), GoogleError> {
+        self.fetch().await.map_err(|e| {
+            metric_counter!("auth_google_key_refreshes_total", "outcome" => "failure");
+            events::emit(AuthEvent::GoogleKeyRefreshFailed {
+                reason: e.to_string(),
+            });
+            if e.is::<FetchTimeout>() {
+                GoogleError::Timeout
+            } else {
+                GoogleError::Network(e)
+            }
+        })
+    }
+
     /// Returns true of the keys in this store are expired
     fn is_expired(&self) -> bool {
         let inner = self.inner.read();
         if let Some(expire) = inner.expire {
-            Utc::now() > expire 
+            Utc::now() > expire
         } else {
             false
         }
@@ -143,12 +433,109 @@ where
     ///
     /// # Arguments
     /// * `token` - JWT token (as a base64-encoded string)
-    pub async fn verify(&mut self, token: impl AsRef<str>) -> Result<Profile, GoogleError>
-    where
-        S: CertStore,
-    {
-        let token = token.as_ref();
+    pub async fn verify(&mut self, token: impl AsRef<str>) -> Result<Profile, GoogleError> {
+        self.verify_claims(token, None).await
+    }
+
+    /// Verifies a JWT token is valid and was issued in response to an authentication
+    /// request carrying `nonce`, so a token obtained for another request cannot be
+    /// replayed
+    ///
+    /// # Arguments
+    /// * `token` - JWT token (as a base64-encoded string)
+    /// * `nonce` - Nonce sent in the authentication request
+    pub async fn verify_with_nonce(
+        &mut self,
+        token: impl AsRef<str>,
+        nonce: impl AsRef<str>,
+    ) -> Result<Profile, GoogleError> {
+        self.verify_claims(token, Some(nonce.as_ref())).await
+    }
 
+    /// Verifies a JWT token is valid and, if set, that it carries `nonce`, deserializing
+    /// the claims other than the registered ones into `T`
+    ///
+    /// # Arguments
+    /// * `token` - JWT token (as a base64-encoded string)
+    /// * `nonce` - Nonce sent in the authentication request, if any
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "google.verify",
+            skip_all,
+            fields(outcome = tracing::field::Empty)
+        )
+    )]
+    pub async fn verify_claims<T: DeserializeOwned>(
+        &mut self,
+        token: impl AsRef<str>,
+        nonce: Option<&str>,
+    ) -> Result<Profile<T>, GoogleError> {
+        let result = self.validate(token.as_ref(), nonce).await;
+        trace_record!(
+            "outcome",
+            match result {
+                Ok(_) => "success".to_owned(),
+                Err(ref e) => format!("{:?}", e),
+            }
+        );
+
+        if let Err(ref e) = result {
+            events::emit(AuthEvent::GoogleTokenRejected {
+                reason: format!("{:?}", e),
+            });
+        }
+        result
+    }
+
+    /// Validates a JWT token, see `verify()`
+    async fn validate<T: DeserializeOwned>(
+        &mut self,
+        token: &str,
+        nonce: Option<&str>,
+    ) -> Result<Profile<T>, GoogleError> {
+        let now = Utc::now().timestamp();
+        let cached = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.lock().get(token, now));
+        let claims = match cached {
+            Some(claims) => {
+                metric_counter!("auth_google_token_cache_total", "outcome" => "hit");
+                claims
+            }
+            None => {
+                let claims = self.decode(token).await?;
+                if let Some(cache) = &self.cache {
+                    metric_counter!("auth_google_token_cache_total", "outcome" => "miss");
+                    cache.lock().insert(token, &claims, now);
+                }
+                claims
+            }
+        };
+
+        let inner = self.inner.read();
+        check_required_claims(&claims, &inner.required_claims)?;
+        let profile: Profile<T> =
+            serde_json::from_value(claims).map_err(jsonwebtoken::errors::Error::from)?;
+
+        if !inner.issuers.contains(&profile.iss) {
+            return Err(GoogleError::IssuerMismatch);
+        }
+
+        if let Some(nonce) = nonce {
+            check_nonce(profile.nonce.as_deref(), nonce)?;
+        }
+
+        if let Some(domains) = &inner.hosted_domains {
+            check_hosted_domain(profile.hd.as_deref(), domains)?;
+        }
+
+        Ok(profile)
+    }
+
+    /// Verifies a JWT token's signature, audience and lifetime, returning its claims
+    async fn decode(&mut self, token: &str) -> Result<Value, GoogleError> {
         // validate the header
         // Requirements:
         // * alg = RS256
@@ -165,20 +552,262 @@ where
         let kid = header.kid.ok_or_else(|| GoogleError::MissingKeyId)?;
 
         // check if the store is expired
+        let mut stale = None;
         if self.is_expired() {
             // if we don't have the request key, fetch them
-            self.fetch().await.map_err(|_| GoogleError::FetchKeysFailed)?;
+            if let Err(e) = self.refresh().await {
+                let inner = self.inner.read();
+                if !within_grace(inner.expire, inner.stale_grace, Utc::now()) {
+                    return Err(e);
+                }
+                metric_counter!("auth_google_stale_keys_total");
+                stale = Some(e);
+            }
         }
 
         let inner = self.inner.read();
-        let key = inner.store.get(&kid).ok_or_else(|| GoogleError::KeyNotFound)?;
+        let key = match (inner.store.get(&kid), stale) {
+            (Some(key), _) => key,
+            // the key may have been published since the keys expired
+            (None, Some(e)) => return Err(e),
+            (None, None) => return Err(GoogleError::KeyNotFound),
+        };
 
-        let profile: Profile = decode(token, &key, &inner.validation)
-            .map_err(|_| GoogleError::ValidationFailed)
-            .map(|data| data.claims)?;
+        let validation = validation_for(&inner.validation, header.alg)?;
+        Ok(decode(token, &key, &validation)?.claims)
+    }
+}
 
-        // by default, the token is invalid
-        Ok(profile)
+/// Narrows the accepted algorithms to the one the token was signed with, since
+/// `jsonwebtoken` rejects keys whose family differs from any accepted algorithm (e.g., an
+/// EC key when both RS256 and ES256 are accepted)
+///
+/// # Arguments
+/// * `validation` - Validation configured for the verifier
+/// * `alg` - Algorithm from the token's header
+fn validation_for(validation: &Validation, alg: Algorithm) -> Result<Validation, GoogleError> {
+    if !validation.algorithms.contains(&alg) {
+        return Err(GoogleError::ValidationFailed(
+            ErrorKind::InvalidAlgorithm.into(),
+        ));
+    }
+
+    let mut validation = validation.clone();
+    validation.algorithms = vec![alg];
+    Ok(validation)
+}
+
+/// Returns true if expired keys may still be used because they expired less than `grace`
+/// ago
+///
+/// # Arguments
+/// * `expire` - When the keys expired
+/// * `grace` - How long after the keys expire they may still be used, if at all
+/// * `now` - Current time
+fn within_grace(
+    expire: Option<DateTime<Utc>>,
+    grace: Option<Duration>,
+    now: DateTime<Utc>,
+) -> bool {
+    match (expire, grace) {
+        (Some(expire), Some(grace)) => now <= expire + grace,
+        _ => false,
+    }
+}
+
+/// Checks a token carries every required claim
+///
+/// # Arguments
+/// * `claims` - Claims of the token
+/// * `required` - Names of the required claims
+fn check_required_claims(claims: &Value, required: &[String]) -> Result<(), GoogleError> {
+    match required
+        .iter()
+        .find(|claim| claims.get(claim.as_str()).is_none())
+    {
+        Some(claim) => Err(GoogleError::MissingClaim(claim.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Checks the `nonce` claim of a token matches the nonce of the authentication request
+///
+/// # Arguments
+/// * `claim` - Value of the token's `nonce` claim
+/// * `expected` - Nonce sent in the authentication request
+fn check_nonce(claim: Option<&str>, expected: &str) -> Result<(), GoogleError> {
+    let claim = claim.ok_or(GoogleError::NonceMismatch)?;
+    constant_time::verify_slices_are_equal(claim.as_bytes(), expected.as_bytes())
+        .map_err(|_| GoogleError::NonceMismatch)
+}
+
+/// Checks the `hd` claim of a token is one of the accepted hosted domains
+///
+/// # Arguments
+/// * `claim` - Value of the token's `hd` claim
+/// * `domains` - Accepted hosted domains, in lowercase
+fn check_hosted_domain(claim: Option<&str>, domains: &HashSet<String>) -> Result<(), GoogleError> {
+    match claim {
+        Some(hd) if domains.contains(&hd.to_ascii_lowercase()) => Ok(()),
+        _ => Err(GoogleError::HostedDomainNotAllowed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        future::Future,
+        task::{Context, Poll, Waker},
+    };
+
+    #[test]
+    fn profile() {
+        let profile: Profile = serde_json::from_str(
+            r#"{"iss":"https://accounts.google.com","sub":"110169484474386276334",
+                "aud":"client","exp":1600000000,"iat":1599996400,
+                "email":"alice@example.com","email_verified":true}"#,
+        )
+        .unwrap();
+        assert_eq!(profile.sub, "110169484474386276334");
+        assert_eq!(profile.email.as_deref(), Some("alice@example.com"));
+        assert!(profile.locale.is_none());
+
+        #[derive(Deserialize)]
+        struct Custom {
+            groups: Vec<String>,
+        }
+        let profile: Profile<Custom> = serde_json::from_str(
+            r#"{"iss":"accounts.google.com","sub":"1","aud":"client","exp":1600000000,
+                "iat":1599996400,"groups":["admins"]}"#,
+        )
+        .unwrap();
+        assert_eq!(profile.groups, ["admins"]);
+    }
+
+    #[test]
+    fn validation_errors() {
+        let error = |kind: ErrorKind| GoogleError::from(jsonwebtoken::errors::Error::from(kind));
+        assert!(matches!(
+            error(ErrorKind::ExpiredSignature),
+            GoogleError::Expired
+        ));
+        assert!(matches!(
+            error(ErrorKind::InvalidAudience),
+            GoogleError::AudienceMismatch
+        ));
+        assert!(matches!(
+            error(ErrorKind::InvalidSignature),
+            GoogleError::SignatureInvalid
+        ));
+        assert!(matches!(
+            error(ErrorKind::ImmatureSignature),
+            GoogleError::ValidationFailed(_)
+        ));
+    }
+
+    #[test]
+    fn validation_tuning() {
+        let auth = GoogleAuth::with_fetcher(MemoryCertStore::new(), StaticFetcher(""), "client")
+            .leeway(60)
+            .validate_nbf(true)
+            .algorithms(&[Algorithm::RS256, Algorithm::ES256]);
+        let inner = auth.inner.read();
+        assert_eq!(inner.validation.leeway, 60);
+        assert!(inner.validation.validate_exp);
+        assert!(inner.validation.validate_nbf);
+        assert_eq!(inner.validation.algorithms.len(), 2);
+    }
+
+    #[test]
+    fn fetch_timeout() {
+        struct SlowFetcher;
+
+        impl KeyFetcher for SlowFetcher {
+            fn fetch<'a>(&'a self, _url: &'a str) -> FetchFuture<'a> {
+                Box::pin(async { Err(FetchTimeout.into()) })
+            }
+        }
+
+        let mut auth = GoogleAuth::with_fetcher(MemoryCertStore::new(), SlowFetcher, "client");
+        let mut refresh = Box::pin(auth.refresh());
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(matches!(
+            refresh.as_mut().poll(&mut cx),
+            Poll::Ready(Err(GoogleError::Timeout))
+        ));
+    }
+
+    #[test]
+    fn algorithms() {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.algorithms.push(Algorithm::ES256);
+
+        let narrowed = validation_for(&validation, Algorithm::ES256).unwrap();
+        assert_eq!(narrowed.algorithms, [Algorithm::ES256]);
+        assert!(matches!(
+            validation_for(&validation, Algorithm::HS256),
+            Err(GoogleError::ValidationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn stale_grace() {
+        let now = Utc::now();
+        let grace = Some(Duration::minutes(30));
+        assert!(within_grace(Some(now - Duration::minutes(10)), grace, now));
+        assert!(!within_grace(Some(now - Duration::minutes(40)), grace, now));
+        assert!(!within_grace(Some(now - Duration::minutes(10)), None, now));
+        assert!(!within_grace(None, grace, now));
+    }
+
+    #[test]
+    fn required_claims() {
+        let claims = serde_json::json!({ "sub": "1", "email": "alice@example.com" });
+        let required = |claims: &[&str]| claims.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        assert!(check_required_claims(&claims, &required(&["sub", "email"])).is_ok());
+        assert!(matches!(
+            check_required_claims(&claims, &required(&["email", "picture"])),
+            Err(GoogleError::MissingClaim(claim)) if claim == "picture"
+        ));
+    }
+
+    #[test]
+    fn nonce() {
+        assert!(check_nonce(Some("n-0S6_WzA2Mj"), "n-0S6_WzA2Mj").is_ok());
+        assert!(matches!(
+            check_nonce(Some("n-0S6_WzA2Mj"), "other"),
+            Err(GoogleError::NonceMismatch)
+        ));
+        assert!(matches!(
+            check_nonce(None, "n-0S6_WzA2Mj"),
+            Err(GoogleError::NonceMismatch)
+        ));
+    }
+
+    #[test]
+    fn issuers() {
+        let auth = GoogleAuth::with_fetcher(MemoryCertStore::new(), StaticFetcher(""), "client");
+        for iss in ISSUERS {
+            assert!(auth.inner.read().issuers.contains(*iss));
+        }
+
+        let auth = auth.issuers(&["https://accounts.google.com"]);
+        assert!(!auth.inner.read().issuers.contains("accounts.google.com"));
+    }
+
+    #[test]
+    fn hosted_domain() {
+        let domains: HashSet<String> = vec!["example.com".to_owned()].into_iter().collect();
+        assert!(check_hosted_domain(Some("example.com"), &domains).is_ok());
+        assert!(check_hosted_domain(Some("Example.COM"), &domains).is_ok());
+        assert!(matches!(
+            check_hosted_domain(Some("example.org"), &domains),
+            Err(GoogleError::HostedDomainNotAllowed)
+        ));
+
+        // consumer accounts have no hosted domain
+        assert!(check_hosted_domain(None, &domains).is_err());
     }
 }
 
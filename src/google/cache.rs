@@ -0,0 +1,122 @@
+//! A bounded cache of verified tokens
+
+use ring::digest;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Remembers the claims of tokens whose signature has been verified, keyed by the
+/// SHA-256 hash of the token, until the token expires
+#[derive(Debug)]
+pub(crate) struct TokenCache {
+    capacity: usize,
+    entries: HashMap<[u8; 32], Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    claims: Value,
+    exp: i64,
+}
+
+impl TokenCache {
+    /// Creates a cache holding at most `capacity` tokens
+    pub(crate) fn new(capacity: usize) -> TokenCache {
+        TokenCache {
+            capacity,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the claims of a verified token, if it is cached and has not expired
+    ///
+    /// # Arguments
+    /// * `token` - JWT token (as a base64-encoded string)
+    /// * `now` - Current time, in seconds since the epoch
+    pub(crate) fn get(&self, token: &str, now: i64) -> Option<Value> {
+        self.entries
+            .get(&hash(token))
+            .filter(|entry| entry.exp > now)
+            .map(|entry| entry.claims.clone())
+    }
+
+    /// Caches the claims of a verified token until its `exp` claim.  Tokens without an
+    /// `exp` claim are not cached
+    ///
+    /// # Arguments
+    /// * `token` - JWT token (as a base64-encoded string)
+    /// * `claims` - Verified claims of the token
+    /// * `now` - Current time, in seconds since the epoch
+    pub(crate) fn insert(&mut self, token: &str, claims: &Value, now: i64) {
+        let exp = match claims.get("exp").and_then(Value::as_i64) {
+            Some(exp) if exp > now => exp,
+            _ => return,
+        };
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() >= self.capacity {
+            self.entries.retain(|_, entry| entry.exp > now);
+        }
+        if self.entries.len() >= self.capacity {
+            // evict the token closest to expiring
+            if let Some(key) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.exp)
+                .map(|(key, _)| *key)
+            {
+                self.entries.remove(&key);
+            }
+        }
+
+        self.entries.insert(
+            hash(token),
+            Entry {
+                claims: claims.clone(),
+                exp,
+            },
+        );
+    }
+}
+
+/// Returns the SHA-256 hash of a token, so the cache never holds usable tokens
+fn hash(token: &str) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(digest::digest(&digest::SHA256, token.as_bytes()).as_ref());
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn cached_until_expiry() {
+        let mut cache = TokenCache::new(2);
+        cache.insert("a", &json!({ "sub": "a", "exp": 200 }), 100);
+        assert_eq!(cache.get("a", 150).unwrap()["sub"], "a");
+        assert!(cache.get("a", 200).is_none());
+        assert!(cache.get("b", 150).is_none());
+
+        // tokens without (or past) their expiry are never cached
+        cache.insert("c", &json!({ "sub": "c" }), 100);
+        cache.insert("d", &json!({ "sub": "d", "exp": 50 }), 100);
+        assert!(cache.get("c", 100).is_none());
+        assert!(cache.get("d", 10).is_none());
+    }
+
+    #[test]
+    fn bounded() {
+        let mut cache = TokenCache::new(2);
+        cache.insert("a", &json!({ "exp": 300 }), 100);
+        cache.insert("b", &json!({ "exp": 200 }), 100);
+        cache.insert("c", &json!({ "exp": 400 }), 100);
+
+        assert!(cache.get("a", 100).is_some());
+        assert!(cache.get("b", 100).is_none());
+        assert!(cache.get("c", 100).is_some());
+        assert_eq!(cache.entries.len(), 2);
+    }
+}
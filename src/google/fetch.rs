@@ -0,0 +1,228 @@
+//! A trait describing how JSON Web Key Sets are fetched
+
+use crate::google::key::*;
+use serde::Deserialize;
+#[cfg(feature = "reqwest")]
+use std::time::Duration;
+use std::{error::Error, future::Future, pin::Pin};
+
+/// Default time allowed to connect to Google when fetching keys
+#[cfg(feature = "reqwest")]
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default time allowed for a whole key fetch, from connecting to reading the body
+#[cfg(feature = "reqwest")]
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Error returned by a [`KeyFetcher`]
+pub type FetchError = Box<dyn Error + Send + Sync>;
+
+/// Future returned by a [`KeyFetcher`]
+pub type FetchFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<KeySetResponse, FetchError>> + Send + 'a>>;
+
+/// Error a [`KeyFetcher`] returns when fetching the key set timed out, reported as
+/// [`GoogleError::Timeout`](super::GoogleError::Timeout) instead of a generic network error
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+#[error("timed out fetching keys")]
+pub struct FetchTimeout;
+
+/// The parts of a key set response used to update a cert store
+#[derive(Clone, Debug, Default)]
+pub struct KeySetResponse {
+    /// JSON body of the response
+    pub body: Vec<u8>,
+
+    /// Values of the `Cache-Control` headers
+    pub cache_control: Vec<String>,
+}
+
+/// Fetches JSON Web Key Sets over HTTP(S)
+///
+/// Implement this to fetch keys with an HTTP client other than `reqwest` (e.g., `hyper`,
+/// `ureq` or one configured for a corporate proxy).
+///
+/// ```ignore
+/// struct UreqFetcher;
+///
+/// impl KeyFetcher for UreqFetcher {
+///     fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+///         Box::pin(async move {
+///             let resp = ureq::get(url).call()?;
+///             let cache_control = resp.all("cache-control").iter().map(|h| h.to_string()).collect();
+///             let mut body = Vec::new();
+///             resp.into_reader().read_to_end(&mut body)?;
+///             Ok(KeySetResponse { body, cache_control })
+///         })
+///     }
+/// }
+/// ```
+pub trait KeyFetcher: Send + Sync {
+    /// Fetches the key set at `url`
+    ///
+    /// # Arguments
+    /// * `url` - Url of the key set
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a>;
+}
+
+/// Fetches keys with `reqwest`
+///
+/// Fetches time out after [`DEFAULT_CONNECT_TIMEOUT`] and [`DEFAULT_REQUEST_TIMEOUT`]
+/// unless other timeouts are set with [`ReqwestFetcher::with_timeouts`]
+#[cfg(feature = "reqwest")]
+#[derive(Clone, Debug)]
+pub struct ReqwestFetcher {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl Default for ReqwestFetcher {
+    fn default() -> ReqwestFetcher {
+        // like `reqwest::Client::new`, panics if the TLS backend cannot be initialized
+        Self::with_timeouts(DEFAULT_CONNECT_TIMEOUT, DEFAULT_REQUEST_TIMEOUT)
+            .expect("failed to initialize the HTTP client")
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestFetcher {
+    pub fn new() -> ReqwestFetcher {
+        Self::default()
+    }
+
+    /// Creates a fetcher with custom timeouts
+    ///
+    /// # Arguments
+    /// * `connect` - Time allowed to connect to Google
+    /// * `request` - Time allowed for the whole fetch, from connecting to reading the body
+    pub fn with_timeouts(
+        connect: Duration,
+        request: Duration,
+    ) -> Result<ReqwestFetcher, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(connect)
+            .timeout(request)
+            .build()?;
+        Ok(ReqwestFetcher { client })
+    }
+
+    /// Creates a fetcher using a configured client (e.g., with a proxy).  The client's
+    /// own timeouts apply
+    ///
+    /// # Arguments
+    /// * `client` - Client to fetch keys with
+    pub fn with_client(client: reqwest::Client) -> ReqwestFetcher {
+        ReqwestFetcher { client }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl KeyFetcher for ReqwestFetcher {
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+        Box::pin(async move {
+            let resp = self
+                .client
+                .get(url)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(classify)?;
+            let cache_control = resp
+                .headers()
+                .get_all(reqwest::header::CACHE_CONTROL)
+                .iter()
+                .filter_map(|header| header.to_str().ok())
+                .map(str::to_owned)
+                .collect();
+            let body = resp.bytes().await.map_err(classify)?.to_vec();
+
+            Ok(KeySetResponse {
+                body,
+                cache_control,
+            })
+        })
+    }
+}
+
+/// Reports timeouts as [`FetchTimeout`]
+///
+/// # Arguments
+/// * `e` - Error returned by `reqwest`
+#[cfg(feature = "reqwest")]
+fn classify(e: reqwest::Error) -> FetchError {
+    if e.is_timeout() {
+        Box::new(FetchTimeout)
+    } else {
+        Box::new(e)
+    }
+}
+
+/// The body of a key set response
+#[derive(Deserialize, Debug)]
+struct Response {
+    pub keys: Vec<Jwk>,
+}
+
+/// Fetches a JSON Web Key Set, returning its keys and how long (in seconds) the
+/// `Cache-Control` header allows them to be cached
+///
+/// # Arguments
+/// * `fetcher` - Fetcher to fetch the key set with
+/// * `url` - Url of the key set
+pub(crate) async fn fetch_jwks(
+    fetcher: &dyn KeyFetcher,
+    url: &str,
+) -> Result<(Vec<Jwk>, u64), FetchError> {
+    let response = fetcher.fetch(url).await?;
+
+    // examine the `Cache-Control` header per Google documentation
+    let mut cache = CacheControl::new();
+    for header in response.cache_control {
+        cache.update(header);
+    }
+
+    let body: Response = serde_json::from_slice(&response.body)?;
+    Ok((body.keys, cache.max_age))
+}
+
+/// Serves a fixed key set, for tests
+#[cfg(test)]
+pub(crate) struct StaticFetcher(pub &'static str);
+
+#[cfg(test)]
+impl KeyFetcher for StaticFetcher {
+    fn fetch<'a>(&'a self, _url: &'a str) -> FetchFuture<'a> {
+        let response = KeySetResponse {
+            body: self.0.as_bytes().to_vec(),
+            cache_control: vec![
+                "public, max-age=19204".to_owned(),
+                "must-revalidate".to_owned(),
+            ],
+        };
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::{Context, Poll, Waker};
+
+    #[test]
+    fn fetch_with_custom_fetcher() {
+        let fetcher = StaticFetcher(
+            r#"{"keys":[{"kid":"1","n":"AQAB","e":"AQAB","kty":"RSA","use":"sig","alg":"RS256"}]}"#,
+        );
+        let mut future = Box::pin(fetch_jwks(&fetcher, "https://example.com/certs"));
+
+        // the fetcher never waits, so the key set is ready on the first poll
+        let mut cx = Context::from_waker(Waker::noop());
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(Ok((keys, max_age))) => {
+                assert_eq!(keys[0].kid, "1");
+                assert_eq!(max_age, 19204);
+            }
+            _ => panic!("fetch did not complete"),
+        }
+    }
+}
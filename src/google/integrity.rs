@@ -0,0 +1,616 @@
+//! Verify Google Play Integrity tokens on an Android backend
+//!
+//! Integrity tokens are decrypted and verified locally with the response encryption keys
+//! from the Play Console (Release > App integrity > Response encryption): the token is an
+//! `A256KW`/`A256GCM` JWE wrapping an `ES256` JWS whose payload holds the verdicts.  The
+//! request details are checked against the app's package name and the nonce (or request
+//! hash) the backend issued, then the app, device and licensing verdicts are checked
+//! against the configured requirements.
+//!
+//! Source: [Play Integrity API](https://developer.android.com/google/play/integrity/verdicts)
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::google::integrity::{DeviceIntegrity, IntegrityVerifier};
+//!
+//! let verifier = IntegrityVerifier::new("com.example.app", DECRYPTION_KEY, VERIFICATION_KEY)?
+//!     .certificate("6a6a1474b5cbbb2b1aa57e0bc3")
+//!     .device_integrity(DeviceIntegrity::Strong);
+//!
+//! let verdict = verifier.verify(&token, &session.nonce)?;
+//! ```
+
+use crate::{
+    events::{self, AuthEvent},
+    time::now_millis,
+};
+use aes::{Aes256, BlockCipher, NewBlockCipher};
+use ring::{
+    aead::{self, Aad, LessSafeKey, Nonce, UnboundKey},
+    constant_time,
+    signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED},
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+/// DER prefix of a P-256 `SubjectPublicKeyInfo`, followed by the uncompressed point
+const P256_SPKI_PREFIX: &[u8] = &[
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+/// Initial value of RFC 3394 key wrapping
+const KEY_WRAP_IV: [u8; 8] = [0xa6; 8];
+
+/// App recognition verdict of an app installed from Play
+const PLAY_RECOGNIZED: &str = "PLAY_RECOGNIZED";
+
+/// Licensing verdict of a user who installed or bought the app on Play
+const LICENSED: &str = "LICENSED";
+
+#[derive(Error, Debug)]
+pub enum IntegrityError {
+    #[error("integrity token is malformed: {0}")]
+    Malformed(&'static str),
+
+    #[error("integrity key is invalid")]
+    InvalidKey,
+
+    #[error("integrity token could not be decrypted")]
+    Decryption,
+
+    #[error("integrity token signature is invalid")]
+    InvalidSignature,
+
+    #[error("integrity token was requested by another package")]
+    PackageMismatch,
+
+    #[error("integrity token nonce does not match")]
+    NonceMismatch,
+
+    #[error("integrity token has expired")]
+    Expired,
+
+    #[error("app is not recognized by Play: {0}")]
+    AppNotRecognized(String),
+
+    #[error("app is not signed with a trusted certificate")]
+    CertificateMismatch,
+
+    #[error("device does not meet the required integrity")]
+    DeviceIntegrity,
+
+    #[error("user is not licensed")]
+    NotLicensed,
+}
+
+/// Device integrity an app requires, from weakest to strongest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceIntegrity {
+    /// Any device, including ones failing basic integrity
+    None,
+
+    /// Device passes basic integrity checks (`MEETS_BASIC_INTEGRITY`)
+    Basic,
+
+    /// Genuine Android device with Play Protect certification (`MEETS_DEVICE_INTEGRITY`)
+    Device,
+
+    /// Device with hardware-backed proof of boot integrity (`MEETS_STRONG_INTEGRITY`)
+    Strong,
+}
+
+impl DeviceIntegrity {
+    /// Returns the device recognition verdict label, if any verdict is required
+    fn label(self) -> Option<&'static str> {
+        match self {
+            DeviceIntegrity::None => None,
+            DeviceIntegrity::Basic => Some("MEETS_BASIC_INTEGRITY"),
+            DeviceIntegrity::Device => Some("MEETS_DEVICE_INTEGRITY"),
+            DeviceIntegrity::Strong => Some("MEETS_STRONG_INTEGRITY"),
+        }
+    }
+}
+
+/// Verdicts of a verified integrity token
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntegrityVerdict {
+    /// Package name of the app
+    pub package_name: String,
+
+    /// Version code of the app, if recognized by Play
+    pub version_code: Option<String>,
+
+    /// App recognition verdict (e.g., `PLAY_RECOGNIZED` or `UNRECOGNIZED_VERSION`)
+    pub app_recognition_verdict: String,
+
+    /// SHA-256 digests of the app's signing certificates (websafe base64)
+    pub certificate_digests: Vec<String>,
+
+    /// Device recognition verdicts (e.g., `MEETS_DEVICE_INTEGRITY`)
+    pub device_recognition_verdicts: Vec<String>,
+
+    /// Licensing verdict (e.g., `LICENSED`), if evaluated
+    pub app_licensing_verdict: Option<String>,
+
+    /// Time (milliseconds since the unix epoch) the token was requested
+    pub timestamp_millis: u64,
+}
+
+/// Decrypted payload of an integrity token
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Payload {
+    request_details: RequestDetails,
+
+    #[serde(default)]
+    app_integrity: AppIntegrity,
+
+    #[serde(default)]
+    device_integrity: DeviceDetails,
+
+    #[serde(default)]
+    account_details: AccountDetails,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestDetails {
+    request_package_name: String,
+
+    /// Nonce of a classic request
+    nonce: Option<String>,
+
+    /// Request hash of a standard request
+    request_hash: Option<String>,
+
+    timestamp_millis: Millis,
+}
+
+/// Milliseconds, which Play encodes as a string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Millis {
+    Number(u64),
+    String(String),
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AppIntegrity {
+    app_recognition_verdict: String,
+    package_name: Option<String>,
+    #[serde(default)]
+    certificate_sha256_digest: Vec<String>,
+    version_code: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceDetails {
+    #[serde(default)]
+    device_recognition_verdict: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountDetails {
+    app_licensing_verdict: Option<String>,
+}
+
+/// Decrypts and verifies integrity tokens for an app
+#[derive(Clone)]
+pub struct IntegrityVerifier {
+    /// Package name of the app
+    package_name: String,
+
+    /// AES-256 key the token's content key is wrapped with
+    decryption_key: Vec<u8>,
+
+    /// Uncompressed P-256 public key the token is signed with
+    verification_key: Vec<u8>,
+
+    /// Trusted signing certificate digests, if restricted
+    certificates: Vec<String>,
+
+    /// Device integrity required
+    device: DeviceIntegrity,
+
+    /// Require the app to be recognized by Play
+    play_recognized: bool,
+
+    /// Require the user to be licensed
+    licensed: bool,
+
+    /// Maximum age of a token, in milliseconds
+    max_age: u64,
+}
+
+impl IntegrityVerifier {
+    /// Creates a verifier requiring an app recognized by Play on a device meeting device
+    /// integrity, with tokens at most five minutes old
+    ///
+    /// # Arguments
+    /// * `package_name` - Package name of the app (e.g., `com.example.app`)
+    /// * `decryption_key` - Base64 decryption key from the Play Console
+    /// * `verification_key` - Base64 verification key from the Play Console
+    pub fn new(
+        package_name: impl Into<String>,
+        decryption_key: &str,
+        verification_key: &str,
+    ) -> Result<IntegrityVerifier, IntegrityError> {
+        let decryption_key = base64::decode(decryption_key.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or(IntegrityError::InvalidKey)?;
+
+        let verification_key = base64::decode(verification_key.trim())
+            .ok()
+            .and_then(|spki| spki.strip_prefix(P256_SPKI_PREFIX).map(<[u8]>::to_vec))
+            .filter(|point| point.len() == 65)
+            .ok_or(IntegrityError::InvalidKey)?;
+
+        Ok(IntegrityVerifier {
+            package_name: package_name.into(),
+            decryption_key,
+            verification_key,
+            certificates: vec![],
+            device: DeviceIntegrity::Device,
+            play_recognized: true,
+            licensed: false,
+            max_age: 5 * 60 * 1000,
+        })
+    }
+
+    /// Trusts an app signing certificate, rejecting apps signed with any other once set
+    ///
+    /// # Arguments
+    /// * `digest` - Websafe base64 SHA-256 digest of the certificate, as in the verdict
+    pub fn certificate(mut self, digest: impl Into<String>) -> Self {
+        self.certificates.push(digest.into());
+        self
+    }
+
+    /// Sets the device integrity required
+    pub fn device_integrity(mut self, device: DeviceIntegrity) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Sets whether the app must be recognized by Play (`PLAY_RECOGNIZED`)
+    pub fn require_play_recognized(mut self, required: bool) -> Self {
+        self.play_recognized = required;
+        self
+    }
+
+    /// Sets whether the user must be licensed (`LICENSED`)
+    pub fn require_licensed(mut self, required: bool) -> Self {
+        self.licensed = required;
+        self
+    }
+
+    /// Sets the maximum age of a token, in seconds
+    pub fn max_age(mut self, secs: u64) -> Self {
+        self.max_age = secs * 1000;
+        self
+    }
+
+    /// Decrypts and verifies an integrity token, returning its verdicts
+    ///
+    /// # Arguments
+    /// * `token` - Integrity token sent by the app
+    /// * `nonce` - Nonce (classic requests) or request hash (standard requests) the
+    ///   backend issued for this request
+    pub fn verify(&self, token: &str, nonce: &str) -> Result<IntegrityVerdict, IntegrityError> {
+        let result = self.verify_at(token, nonce, now_millis());
+        if let Err(ref e) = result {
+            events::emit(AuthEvent::PlayIntegrityRejected {
+                reason: e.to_string(),
+            });
+        }
+        result
+    }
+
+    /// Decrypts and verifies an integrity token at a time (milliseconds since the unix epoch)
+    fn verify_at(
+        &self,
+        token: &str,
+        nonce: &str,
+        now: u64,
+    ) -> Result<IntegrityVerdict, IntegrityError> {
+        let jws = self.decrypt(token)?;
+        let payload = self.verify_signature(&jws)?;
+        let payload: Payload = serde_json::from_slice(&payload)
+            .map_err(|_| IntegrityError::Malformed("invalid payload"))?;
+
+        let request = payload.request_details;
+        if request.request_package_name != self.package_name {
+            return Err(IntegrityError::PackageMismatch);
+        }
+
+        let issued = request.nonce.or(request.request_hash).unwrap_or_default();
+        constant_time::verify_slices_are_equal(issued.as_bytes(), nonce.as_bytes())
+            .map_err(|_| IntegrityError::NonceMismatch)?;
+
+        let timestamp = match request.timestamp_millis {
+            Millis::Number(ms) => ms,
+            Millis::String(ms) => ms
+                .parse()
+                .map_err(|_| IntegrityError::Malformed("invalid timestamp"))?,
+        };
+        if now.saturating_sub(timestamp) > self.max_age {
+            return Err(IntegrityError::Expired);
+        }
+
+        let app = payload.app_integrity;
+        if self.play_recognized && app.app_recognition_verdict != PLAY_RECOGNIZED {
+            return Err(IntegrityError::AppNotRecognized(
+                app.app_recognition_verdict,
+            ));
+        }
+        if app
+            .package_name
+            .as_ref()
+            .is_some_and(|p| *p != self.package_name)
+        {
+            return Err(IntegrityError::PackageMismatch);
+        }
+        if !self.certificates.is_empty()
+            && !app
+                .certificate_sha256_digest
+                .iter()
+                .any(|d| self.certificates.contains(d))
+        {
+            return Err(IntegrityError::CertificateMismatch);
+        }
+
+        let devices = payload.device_integrity.device_recognition_verdict;
+        if let Some(label) = self.device.label() {
+            if !devices.iter().any(|v| v == label) {
+                return Err(IntegrityError::DeviceIntegrity);
+            }
+        }
+
+        let licensing = payload.account_details.app_licensing_verdict;
+        if self.licensed && licensing.as_deref() != Some(LICENSED) {
+            return Err(IntegrityError::NotLicensed);
+        }
+
+        Ok(IntegrityVerdict {
+            package_name: self.package_name.clone(),
+            version_code: app.version_code,
+            app_recognition_verdict: app.app_recognition_verdict,
+            certificate_digests: app.certificate_sha256_digest,
+            device_recognition_verdicts: devices,
+            app_licensing_verdict: licensing,
+            timestamp_millis: timestamp,
+        })
+    }
+
+    /// Decrypts the JWE, returning the JWS it contains
+    fn decrypt(&self, token: &str) -> Result<Vec<u8>, IntegrityError> {
+        let parts: Vec<&str> = token.trim().split('.').collect();
+        let (header, wrapped, iv, ciphertext, tag) = match parts.as_slice() {
+            [h, k, i, c, t] => (*h, decode(k)?, decode(i)?, decode(c)?, decode(t)?),
+            _ => return Err(IntegrityError::Malformed("expected a JWE")),
+        };
+
+        let alg: JweHeader = serde_json::from_slice(&decode(header)?)
+            .map_err(|_| IntegrityError::Malformed("invalid JWE header"))?;
+        if alg.alg != "A256KW" || alg.enc != "A256GCM" {
+            return Err(IntegrityError::Malformed("unsupported JWE algorithm"));
+        }
+
+        let cek = unwrap_key(&self.decryption_key, &wrapped).ok_or(IntegrityError::Decryption)?;
+        let key = UnboundKey::new(&aead::AES_256_GCM, &cek)
+            .map(LessSafeKey::new)
+            .map_err(|_| IntegrityError::Decryption)?;
+        let nonce =
+            Nonce::try_assume_unique_for_key(&iv).map_err(|_| IntegrityError::Decryption)?;
+
+        let mut data = ciphertext;
+        data.extend_from_slice(&tag);
+        let plaintext = key
+            .open_in_place(nonce, Aad::from(header.as_bytes()), &mut data)
+            .map_err(|_| IntegrityError::Decryption)?;
+
+        Ok(plaintext.to_vec())
+    }
+
+    /// Verifies the JWS signature, returning its payload
+    fn verify_signature(&self, jws: &[u8]) -> Result<Vec<u8>, IntegrityError> {
+        let jws = std::str::from_utf8(jws).map_err(|_| IntegrityError::Malformed("invalid JWS"))?;
+        let parts: Vec<&str> = jws.split('.').collect();
+        let (header, payload, signature) = match parts.as_slice() {
+            [h, p, s] => (*h, *p, decode(s)?),
+            _ => return Err(IntegrityError::Malformed("expected a JWS")),
+        };
+
+        let alg: JwsHeader = serde_json::from_slice(&decode(header)?)
+            .map_err(|_| IntegrityError::Malformed("invalid JWS header"))?;
+        if alg.alg != "ES256" {
+            return Err(IntegrityError::Malformed("unsupported JWS algorithm"));
+        }
+
+        let signed = &jws[..header.len() + 1 + payload.len()];
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, &self.verification_key)
+            .verify(signed.as_bytes(), &signature)
+            .map_err(|_| IntegrityError::InvalidSignature)?;
+
+        decode(payload)
+    }
+}
+
+#[derive(Deserialize)]
+struct JweHeader {
+    alg: String,
+    enc: String,
+}
+
+#[derive(Deserialize)]
+struct JwsHeader {
+    alg: String,
+}
+
+/// Decodes websafe base64 without padding
+fn decode(value: &str) -> Result<Vec<u8>, IntegrityError> {
+    base64::decode_config(value.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+        .map_err(|_| IntegrityError::Malformed("invalid base64"))
+}
+
+/// Unwraps a key wrapped with AES key wrap (RFC 3394)
+fn unwrap_key(kek: &[u8], wrapped: &[u8]) -> Option<Vec<u8>> {
+    if !wrapped.len().is_multiple_of(8) || wrapped.len() < 24 {
+        return None;
+    }
+
+    let cipher = Aes256::new_varkey(kek).ok()?;
+    let n = wrapped.len() / 8 - 1;
+    let mut a = [0u8; 8];
+    a.copy_from_slice(&wrapped[..8]);
+    let mut r = wrapped[8..].to_vec();
+
+    for j in (0..6).rev() {
+        for i in (1..=n).rev() {
+            let t = (n * j + i) as u64;
+            let mut block = [0u8; 16];
+            for (b, (a, t)) in block.iter_mut().zip(a.iter().zip(t.to_be_bytes().iter())) {
+                *b = a ^ t;
+            }
+            block[8..].copy_from_slice(&r[(i - 1) * 8..i * 8]);
+
+            cipher.decrypt_block((&mut block).into());
+            a.copy_from_slice(&block[..8]);
+            r[(i - 1) * 8..i * 8].copy_from_slice(&block[8..]);
+        }
+    }
+
+    constant_time::verify_slices_are_equal(&a, &KEY_WRAP_IV).ok()?;
+    Some(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time;
+
+    /// Decryption key (32 bytes of `07`)
+    const DECRYPTION_KEY: &str = "BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=";
+
+    /// Verification key
+    const VERIFICATION_KEY: &str = "\
+        MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE7UUFZ28K1oto2um175kUug7e+7I343CjuIQ5F/yEt2kNAS0hW89a\
+        eG0OgBn6s0S6gvBKbp2bRcnHTUC8ejfTOg==";
+
+    /// Token for `com.example.app` with nonce `bm9uY2UtMTIz`, requested 2025-01-01T00:00:00Z
+    const TOKEN: &str = "\
+        eyJhbGciOiJBMjU2S1ciLCJlbmMiOiJBMjU2R0NNIn0.BOl-1wepsihRbwNONgXB_VzB8hFZludu2ERLJR_Fskc-\
+        CbILwtdCLg.sJc4frWtGJDLO7in._6K7E0tPa4OVCZKJrs23u23MgMfqojFVp-PDU1I0sYNqlmzP3xhl0FtYch5Y\
+        pgktrSCYMCl9Ly7MYi5c36RGzPhntoZUvu3KJHHFvL9NHPWIksc5B7cOG2vLD7DFmma5IJ7gc5ZT7lPesAQ5x5Sr\
+        C6jdS94gXr72ND3F2kyhRQRuf5Krz74PIDQJXJGfuUhmzjDfqPGgi8cIgIPwGcJbGiFgo-Z5mHrm7-E8u7QIrMj6\
+        m2T7GEfwj3wJvxYd5WN8Eqnjwc2QeDQIQmfLyuxrojFOhYeko-rFJWtGVdwkHHUa_OM2_KyiwmbWHRxlIrgrv-H1\
+        -bH8iJxDIVQkiRFs2x_jtLJbhGj5QPwISdjDmbVKPi4A0PI15B55W67tAYx6JFyvXi-JGcHfK4BsR0PFAfa2tLdo\
+        hNikg0AZVjA7Rrl0iCTONgjnaCKvMbdzjVBMOclboLmUM0whb-rct81iS1P5AJd-k3huK5lt0hdIGfwyIr8qfkGt\
+        0C0brTbHsd_uKEULd8v_Ix9cF5689B7axA8y1JJ3rwjDwZhAV6Gsl0H2tg-FgNTPUBaK6349Y_7n3gFhpZJ_PA10\
+        jWgGKNm-2k3k4qblaK4FHSuM0gk6ZCvstWTgeIInRw1Bf75ooZvawCcrzUCUV5ClEqlYumtGH4CpWk0oBlN3UZ2B\
+        hJERQeU8i0L8mplvTx_tnHt9xNn8jVGzq1U84YxlSmoQRTSShNPhFl7n3Ri3oENUG-_TMz_QlgU5mvWjw94jixBC\
+        5gF1CYO4MvcJQJFMLdKKepPJ-auT5i6fbT51Eye0gKlLU6baKQ6ZiUeItZYKYkwUlENKrzfMDnDUxk5kMUhKs_58\
+        0Ule9h0CZ2v7LxpyM5AWDBuGpz4ZkAd3SqQ9VAvgp4Xc.4tf0iKpJEXQMYRCDanGpKw";
+
+    /// 2025-01-01T00:01:00Z
+    const NOW: u64 = 1_735_689_660_000;
+
+    fn verifier() -> IntegrityVerifier {
+        IntegrityVerifier::new("com.example.app", DECRYPTION_KEY, VERIFICATION_KEY).unwrap()
+    }
+
+    #[test]
+    fn token_is_verified() {
+        let verdict = verifier()
+            .certificate("c2lnbmluZy1jZXJ0")
+            .require_licensed(true)
+            .verify_at(TOKEN, "bm9uY2UtMTIz", NOW)
+            .unwrap();
+
+        assert_eq!(verdict.package_name, "com.example.app");
+        assert_eq!(verdict.version_code.as_deref(), Some("42"));
+        assert_eq!(verdict.app_recognition_verdict, "PLAY_RECOGNIZED");
+        assert_eq!(verdict.timestamp_millis, 1_735_689_600_000);
+    }
+
+    #[test]
+    fn verify_reads_the_clock() {
+        let clock = time::freeze(NOW / 1000);
+        verifier().verify(TOKEN, "bm9uY2UtMTIz").unwrap();
+
+        clock.advance(3_600);
+        assert!(matches!(
+            verifier().verify(TOKEN, "bm9uY2UtMTIz"),
+            Err(IntegrityError::Expired)
+        ));
+    }
+
+    #[test]
+    fn request_and_verdicts_are_checked() {
+        assert!(matches!(
+            verifier().verify_at(TOKEN, "b3RoZXI", NOW),
+            Err(IntegrityError::NonceMismatch)
+        ));
+        assert!(matches!(
+            verifier().verify_at(TOKEN, "bm9uY2UtMTIz", NOW + 3_600_000),
+            Err(IntegrityError::Expired)
+        ));
+        assert!(matches!(
+            verifier()
+                .device_integrity(DeviceIntegrity::Strong)
+                .verify_at(TOKEN, "bm9uY2UtMTIz", NOW),
+            Err(IntegrityError::DeviceIntegrity)
+        ));
+        assert!(matches!(
+            verifier()
+                .certificate("b3RoZXI")
+                .verify_at(TOKEN, "bm9uY2UtMTIz", NOW),
+            Err(IntegrityError::CertificateMismatch)
+        ));
+        assert!(matches!(
+            IntegrityVerifier::new("com.example.other", DECRYPTION_KEY, VERIFICATION_KEY)
+                .unwrap()
+                .verify_at(TOKEN, "bm9uY2UtMTIz", NOW),
+            Err(IntegrityError::PackageMismatch)
+        ));
+    }
+
+    #[test]
+    fn keys_are_checked() {
+        let other = base64::encode([8u8; 32]);
+        assert!(matches!(
+            IntegrityVerifier::new("com.example.app", &other, VERIFICATION_KEY)
+                .unwrap()
+                .verify_at(TOKEN, "bm9uY2UtMTIz", NOW),
+            Err(IntegrityError::Decryption)
+        ));
+        assert!(IntegrityVerifier::new("com.example.app", "c2hvcnQ=", VERIFICATION_KEY).is_err());
+    }
+
+    #[test]
+    fn key_unwrap() {
+        // RFC 3394, section 4.6
+        let kek: Vec<u8> = (0..32).collect();
+        let wrapped = [
+            0x28, 0xc9, 0xf4, 0x04, 0xc4, 0xb8, 0x10, 0xf4, 0xcb, 0xcc, 0xb3, 0x5c, 0xfb, 0x87,
+            0xf8, 0x26, 0x3f, 0x57, 0x86, 0xe2, 0xd8, 0x0e, 0xd3, 0x26, 0xcb, 0xc7, 0xf0, 0xe7,
+            0x1a, 0x99, 0xf4, 0x3b, 0xfb, 0x98, 0x8b, 0x9b, 0x7a, 0x02, 0xdd, 0x21,
+        ];
+        let key = unwrap_key(&kek, &wrapped).unwrap();
+        assert_eq!(
+            key[..16],
+            [
+                0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+                0xee, 0xff,
+            ]
+        );
+        assert_eq!(key[16..], (0..16).collect::<Vec<u8>>()[..]);
+        assert!(unwrap_key(&[0u8; 32], &wrapped).is_none());
+    }
+}
@@ -1,29 +1,75 @@
-use serde::Deserialize;
+use jsonwebtoken::DecodingKey;
+use serde::{Deserialize, Serialize};
 
 /// A JSON Web Key, returned from Google and used to validate the JWT
-#[derive(Clone, Deserialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct Jwk {
     /// Key Id corresponding to this key
     pub kid: String,
 
-    /// The public key's modulus
+    /// The public key's modulus (RSA keys only)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub n: String,
 
-    /// The public key's public exponent
+    /// The public key's public exponent (RSA keys only)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub e: String,
 
-    /// The key's type (should be RSA)
+    /// The curve of the key, `P-256` or `P-384` (EC keys only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+
+    /// The x coordinate of the public point (EC keys only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+
+    /// The y coordinate of the public point (EC keys only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+
+    /// The key's type (`RSA` or `EC`)
     pub kty: String,
 
     /// The use case for this key (renamed due to Rust's keywords)
     /// Should be signature
-    #[serde(rename = "use")]
+    #[serde(rename = "use", default)]
     pub typ: String,
 
-    /// The specific algorithm (should be RS256)
+    /// The specific algorithm (should be RS256).  Microsoft omits this field
+    #[serde(default)]
     pub alg: String,
 }
 
+impl Jwk {
+    /// Returns the key used to verify signatures, or `None` if the key's type or curve
+    /// is not supported or its coordinates are malformed
+    pub fn decoding_key(&self) -> Option<DecodingKey<'_>> {
+        match self.kty.as_str() {
+            "RSA" => Some(DecodingKey::from_rsa_components(&self.n, &self.e)),
+            "EC" => {
+                let size = match self.crv.as_deref()? {
+                    "P-256" => 32,
+                    "P-384" => 48,
+                    _ => return None,
+                };
+                let x = base64::decode_config(self.x.as_ref()?, base64::URL_SAFE_NO_PAD).ok()?;
+                let y = base64::decode_config(self.y.as_ref()?, base64::URL_SAFE_NO_PAD).ok()?;
+                if x.len() != size || y.len() != size {
+                    return None;
+                }
+
+                // ring expects the uncompressed form of the public point
+                let mut point = Vec::with_capacity(1 + 2 * size);
+                point.push(0x04);
+                point.extend_from_slice(&x);
+                point.extend_from_slice(&y);
+                Some(DecodingKey::from_ec_der(&point).into_static())
+            }
+            _ => None,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub enum Cacheability {
     /// May be stored by any cache, even if the response is normally non-cacheable.
@@ -77,7 +123,7 @@ impl CacheControl {
                 _ => {
                     if directive.starts_with("max-age") {
                         if let Some(age) = directive.split("=").last() {
-                            self.max_age = age.parse().unwrap_or_else(|_| 0);
+                            self.max_age = age.parse().unwrap_or(0);
                         }
                     }
                 }
@@ -0,0 +1,94 @@
+//! Refreshes Google's signing keys from a background task
+
+use crate::google::{CertStore, GoogleAuth};
+use chrono::prelude::*;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// How long to wait before refetching keys the response did not say how long to cache
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long to wait before retrying a failed refresh
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Handle to a background refresh task, which stops the task when dropped
+#[derive(Debug)]
+pub struct Refresher {
+    task: JoinHandle<()>,
+}
+
+impl Drop for Refresher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl<S> GoogleAuth<S>
+where
+    S: CertStore + Send + Sync + 'static,
+{
+    /// Spawns a tokio task that refetches the keys shortly before they expire, so
+    /// verifying a token never waits on a fetch.  The task runs until the returned
+    /// handle is dropped
+    ///
+    /// Must be called from within a tokio runtime.
+    ///
+    /// # Arguments
+    /// * `margin` - How long before the keys expire to refetch them
+    pub fn spawn_refresher(&self, margin: Duration) -> Refresher {
+        let mut auth = self.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let expire = auth.inner.read().expire;
+                tokio::time::sleep(refresh_delay(expire, Utc::now(), margin)).await;
+
+                if auth.refresh().await.is_err() {
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                }
+            }
+        });
+
+        Refresher { task }
+    }
+}
+
+/// Returns how long to wait before refetching keys
+///
+/// # Arguments
+/// * `expire` - When the keys expire, if known
+/// * `now` - Current time
+/// * `margin` - How long before the keys expire to refetch them
+fn refresh_delay(expire: Option<DateTime<Utc>>, now: DateTime<Utc>, margin: Duration) -> Duration {
+    match expire {
+        Some(expire) => (expire - now)
+            .to_std()
+            .map(|remaining| remaining.saturating_sub(margin))
+            .unwrap_or_default(),
+        None => DEFAULT_INTERVAL,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay() {
+        let now = Utc::now();
+        let margin = Duration::from_secs(300);
+
+        let expire = now + chrono::Duration::seconds(3600);
+        assert_eq!(
+            refresh_delay(Some(expire), now, margin),
+            Duration::from_secs(3300)
+        );
+
+        // keys that are about to (or already did) expire are refetched immediately
+        let expire = now + chrono::Duration::seconds(60);
+        assert_eq!(refresh_delay(Some(expire), now, margin), Duration::ZERO);
+        let expire = now - chrono::Duration::seconds(60);
+        assert_eq!(refresh_delay(Some(expire), now, margin), Duration::ZERO);
+
+        assert_eq!(refresh_delay(None, now, margin), DEFAULT_INTERVAL);
+    }
+}
@@ -0,0 +1,421 @@
+//! Password authentication against an LDAP directory (e.g., Active Directory)
+//!
+//! [`LdapAuthenticator`] checks a username and password by performing a simple bind as
+//! the user, then reads a configurable set of attributes from the user's entry so the
+//! application can map them onto its own accounts (e.g., `mail` or `memberOf`).  The
+//! user's distinguished name is either built from a template (`uid={user},ou=people,...`)
+//! or found by searching the directory, optionally bound as a service account, which is
+//! the usual arrangement for Active Directory.
+//!
+//! Connections are made per authentication and secured with LDAPS (an `ldaps://` url) or
+//! StartTLS.  Empty passwords are always rejected, since most directories treat a bind
+//! with an empty password as an anonymous bind and report success.
+//!
+//! The authenticator is driven by `tokio` and must be used from within a tokio runtime.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::ldap::LdapAuthenticator;
+//!
+//! let ldap = LdapAuthenticator::new("ldap://dc1.corp.example.com")
+//!     .starttls(true)
+//!     .search("dc=corp,dc=example,dc=com", "(sAMAccountName={user})")
+//!     .service_account("cn=svc-auth,ou=service,dc=corp,dc=example,dc=com", "secret")
+//!     .attributes(&["mail", "memberOf"]);
+//!
+//! let user = ldap.authenticate("alice", "hunter2").await?;
+//! let email = user.attribute("mail");
+//! ```
+
+use crate::events::{self, AuthEvent};
+use ldap3::{dn_escape, ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use std::{collections::HashMap, fmt, time::Duration};
+use thiserror::Error;
+
+/// Placeholder replaced with the (escaped) username in templates and filters
+const USER_PLACEHOLDER: &str = "{user}";
+
+/// LDAP result code returned when a bind's credentials are rejected
+const INVALID_CREDENTIALS: u32 = 49;
+
+#[derive(Error, Debug)]
+pub enum LdapError {
+    #[error("invalid username or password")]
+    InvalidCredentials,
+
+    #[error("user not found in directory")]
+    UserNotFound,
+
+    #[error("username matches more than one directory entry")]
+    AmbiguousUser,
+
+    #[error("no user lookup configured")]
+    NotConfigured,
+
+    #[error("directory error: {0}")]
+    Directory(#[from] ldap3::LdapError),
+}
+
+/// How a username is turned into a distinguished name
+#[derive(Clone)]
+enum Lookup {
+    /// Not yet configured
+    None,
+
+    /// Substitute the username into a DN template
+    Template(String),
+
+    /// Search for the user's entry
+    Search {
+        /// DN the search starts from
+        base: String,
+
+        /// Search filter containing the username placeholder
+        filter: String,
+
+        /// DN and password of the account to search as, or anonymous
+        service: Option<(String, String)>,
+    },
+}
+
+impl fmt::Debug for Lookup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lookup::None => f.write_str("None"),
+            Lookup::Template(template) => f.debug_tuple("Template").field(template).finish(),
+            Lookup::Search {
+                base,
+                filter,
+                service,
+            } => f
+                .debug_struct("Search")
+                .field("base", base)
+                .field("filter", filter)
+                // never leak the service account's password into logs
+                .field("service", &service.as_ref().map(|(dn, _)| dn))
+                .finish(),
+        }
+    }
+}
+
+/// A user authenticated by the directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LdapUser {
+    /// Distinguished name of the user's entry
+    dn: String,
+
+    /// Requested attributes read from the user's entry
+    attributes: HashMap<String, Vec<String>>,
+}
+
+impl LdapUser {
+    /// Returns the distinguished name of the user's entry
+    pub fn dn(&self) -> &str {
+        &self.dn
+    }
+
+    /// Returns the first value of an attribute, if present
+    ///
+    /// # Arguments
+    /// * `name` - Name of the attribute (e.g., `mail`)
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes(name)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    }
+
+    /// Returns all values of an attribute, if present
+    ///
+    /// # Arguments
+    /// * `name` - Name of the attribute (e.g., `memberOf`)
+    pub fn attributes(&self, name: &str) -> Option<&[String]> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, values)| values.as_slice())
+    }
+}
+
+/// Authenticates users by binding to an LDAP directory
+#[derive(Clone, Debug)]
+pub struct LdapAuthenticator {
+    /// Url of the directory (`ldap://` or `ldaps://`)
+    url: String,
+
+    /// Upgrade `ldap://` connections with StartTLS
+    starttls: bool,
+
+    /// Timeout for establishing a connection
+    timeout: Duration,
+
+    /// How usernames are turned into distinguished names
+    lookup: Lookup,
+
+    /// Attributes read from the user's entry
+    attributes: Vec<String>,
+}
+
+impl LdapAuthenticator {
+    /// Creates an authenticator for a directory.  A user lookup must be configured with
+    /// [`user_dn_template`](Self::user_dn_template) or [`search`](Self::search)
+    ///
+    /// # Arguments
+    /// * `url` - Url of the directory (e.g., `ldaps://ldap.example.com`)
+    pub fn new<S: Into<String>>(url: S) -> LdapAuthenticator {
+        LdapAuthenticator {
+            url: url.into(),
+            starttls: false,
+            timeout: Duration::from_secs(10),
+            lookup: Lookup::None,
+            attributes: vec![],
+        }
+    }
+
+    /// Upgrades `ldap://` connections to TLS with StartTLS
+    pub fn starttls(mut self, starttls: bool) -> Self {
+        self.starttls = starttls;
+        self
+    }
+
+    /// Sets the timeout for establishing a connection to the directory
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Binds as the DN produced by substituting the username into a template
+    ///
+    /// # Arguments
+    /// * `template` - DN containing `{user}` (e.g., `uid={user},ou=people,dc=example,dc=com`)
+    pub fn user_dn_template<S: Into<String>>(mut self, template: S) -> Self {
+        self.lookup = Lookup::Template(template.into());
+        self
+    }
+
+    /// Searches the directory for the user's entry, anonymously unless a service account
+    /// is set with [`service_account`](Self::service_account)
+    ///
+    /// # Arguments
+    /// * `base` - DN the search starts from (e.g., `dc=corp,dc=example,dc=com`)
+    /// * `filter` - Filter containing `{user}` (e.g., `(sAMAccountName={user})`)
+    pub fn search<B, F>(mut self, base: B, filter: F) -> Self
+    where
+        B: Into<String>,
+        F: Into<String>,
+    {
+        let service = match self.lookup {
+            Lookup::Search { service, .. } => service,
+            _ => None,
+        };
+
+        self.lookup = Lookup::Search {
+            base: base.into(),
+            filter: filter.into(),
+            service,
+        };
+        self
+    }
+
+    /// Sets the account the user search is performed as.  Has no effect unless
+    /// [`search`](Self::search) is configured first
+    ///
+    /// # Arguments
+    /// * `dn` - Distinguished name of the service account
+    /// * `password` - Password of the service account
+    pub fn service_account<D, P>(mut self, dn: D, password: P) -> Self
+    where
+        D: Into<String>,
+        P: Into<String>,
+    {
+        if let Lookup::Search { service, .. } = &mut self.lookup {
+            *service = Some((dn.into(), password.into()));
+        }
+        self
+    }
+
+    /// Sets the attributes read from the user's entry after a successful bind
+    ///
+    /// # Arguments
+    /// * `attributes` - Names of the attributes (e.g., `["mail", "memberOf"]`)
+    pub fn attributes(mut self, attributes: &[&str]) -> Self {
+        self.attributes = attributes.iter().map(|a| (*a).to_owned()).collect();
+        self
+    }
+
+    /// Authenticates a user, returning their directory entry
+    ///
+    /// # Arguments
+    /// * `username` - Name presented by the user
+    /// * `password` - Password presented by the user
+    pub async fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<LdapUser, LdapError> {
+        let result = self.bind(username, password).await;
+
+        match &result {
+            Ok(user) => events::emit(AuthEvent::LdapBindSucceeded {
+                dn: user.dn.clone(),
+            }),
+            Err(e) => events::emit(AuthEvent::LdapBindFailed {
+                user: username.to_owned(),
+                reason: e.to_string(),
+            }),
+        }
+
+        result
+    }
+
+    /// Connects to the directory, binds as the user and reads their attributes
+    async fn bind(&self, username: &str, password: &str) -> Result<LdapUser, LdapError> {
+        self.check(password)?;
+
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(self.timeout)
+            .set_starttls(self.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.url).await?;
+        ldap3::drive!(conn);
+
+        let result = self.bind_on(&mut ldap, username, password).await;
+        let _ = ldap.unbind().await;
+        result
+    }
+
+    /// Rejects attempts that must not reach the directory
+    fn check(&self, password: &str) -> Result<(), LdapError> {
+        if let Lookup::None = self.lookup {
+            return Err(LdapError::NotConfigured);
+        }
+
+        // an empty password would be treated as an anonymous bind, which succeeds
+        match password.is_empty() {
+            true => Err(LdapError::InvalidCredentials),
+            false => Ok(()),
+        }
+    }
+
+    /// Binds as the user over an established connection and reads their attributes
+    async fn bind_on(
+        &self,
+        ldap: &mut Ldap,
+        username: &str,
+        password: &str,
+    ) -> Result<LdapUser, LdapError> {
+        let dn = match &self.lookup {
+            Lookup::None => return Err(LdapError::NotConfigured),
+            Lookup::Template(template) => user_dn(template, username),
+            Lookup::Search {
+                base,
+                filter,
+                service,
+            } => {
+                if let Some((dn, password)) = service {
+                    ldap.simple_bind(dn, password).await?.success()?;
+                }
+
+                // "1.1" requests no attributes; only the DN is needed
+                let (mut entries, _) = ldap
+                    .search(
+                        base,
+                        Scope::Subtree,
+                        &user_filter(filter, username),
+                        vec!["1.1"],
+                    )
+                    .await?
+                    .success()?;
+
+                match entries.len() {
+                    0 => return Err(LdapError::UserNotFound),
+                    1 => SearchEntry::construct(entries.remove(0)).dn,
+                    _ => return Err(LdapError::AmbiguousUser),
+                }
+            }
+        };
+
+        ldap.simple_bind(&dn, password)
+            .await?
+            .success()
+            .map_err(|e| match e {
+                ldap3::LdapError::LdapResult { result } if result.rc == INVALID_CREDENTIALS => {
+                    LdapError::InvalidCredentials
+                }
+                e => LdapError::Directory(e),
+            })?;
+
+        let attributes = match self.attributes.is_empty() {
+            true => HashMap::new(),
+            false => {
+                let (mut entries, _) = ldap
+                    .search(&dn, Scope::Base, "(objectClass=*)", &self.attributes)
+                    .await?
+                    .success()?;
+                entries
+                    .pop()
+                    .map(|entry| SearchEntry::construct(entry).attrs)
+                    .unwrap_or_default()
+            }
+        };
+
+        Ok(LdapUser { dn, attributes })
+    }
+}
+
+/// Substitutes a username into a DN template, escaping DN special characters
+fn user_dn(template: &str, username: &str) -> String {
+    template.replace(USER_PLACEHOLDER, &dn_escape(username))
+}
+
+/// Substitutes a username into a search filter, escaping filter special characters
+fn user_filter(filter: &str, username: &str) -> String {
+    filter.replace(USER_PLACEHOLDER, &ldap_escape(username))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usernames_are_escaped() {
+        assert_eq!(
+            user_dn("uid={user},ou=people,dc=example,dc=com", "doe, john"),
+            "uid=doe\\2c john,ou=people,dc=example,dc=com"
+        );
+        assert_eq!(
+            user_filter("(sAMAccountName={user})", "*)(uid=*"),
+            "(sAMAccountName=\\2a\\29\\28uid=\\2a)"
+        );
+    }
+
+    #[test]
+    fn service_account_requires_search() {
+        let ldap = LdapAuthenticator::new("ldap://localhost").service_account("cn=svc", "pw");
+        assert!(matches!(ldap.lookup, Lookup::None));
+
+        let ldap = LdapAuthenticator::new("ldap://localhost")
+            .search("dc=example", "(uid={user})")
+            .service_account("cn=svc", "pw")
+            .search("dc=example,dc=com", "(mail={user})");
+        assert!(matches!(
+            ldap.lookup,
+            Lookup::Search {
+                service: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn unauthenticated_binds_are_rejected() {
+        let ldap = LdapAuthenticator::new("ldap://localhost");
+        assert!(matches!(
+            ldap.check("hunter2"),
+            Err(LdapError::NotConfigured)
+        ));
+
+        let ldap = ldap.user_dn_template("uid={user},dc=example,dc=com");
+        assert!(matches!(ldap.check(""), Err(LdapError::InvalidCredentials)));
+        assert!(ldap.check("hunter2").is_ok());
+    }
+}
@@ -1,12 +1,111 @@
 //! FIDO2 WebAuthn implementation
+//!
+//! # Metrics
+//!
+//! With the `metrics` feature enabled, the following are recorded through the
+//! [`metrics`](https://docs.rs/metrics) facade (install any exporter, e.g. Prometheus):
+//!
+//! * `auth_ceremonies_started_total{ceremony}` - WebAuthn requests created
+//! * `auth_ceremonies_succeeded_total{ceremony}` - WebAuthn responses validated
+//! * `auth_ceremonies_failed_total{ceremony, reason}` - WebAuthn responses rejected
+//! * `auth_google_key_refreshes_total{outcome}` - Fetches of Google's signing keys
+//! * `auth_google_stale_keys_total` - Verifications against expired Google keys after a
+//!   failed refetch
+//! * `auth_google_token_cache_total{outcome}` - Lookups of the verified Google token cache
+//! * `auth_microsoft_key_refreshes_total{outcome}` - Fetches of Microsoft's signing keys
+//! * `auth_oauth_logins_total{provider, outcome}` - Social logins
+//! * `auth_password_hash_seconds` - Time taken to hash a password
+//! * `auth_password_verifications_total{outcome}` - Passwords verified
+//!
+//! # `no_std`
+//!
+//! Everything but the `fido` parsing core needs the (default) `std` feature.  Building
+//! with `default-features = false, features = ["fido"]` only needs `alloc`, and
+//! `fido-crypto` adds signature verification with `ring`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[macro_use]
+mod macros;
+
+#[cfg(feature = "apikey")]
+pub mod apikey;
+
+#[cfg(feature = "std")]
+pub mod authorization;
+
+#[cfg(feature = "std")]
+pub mod events;
+
+#[cfg(feature = "fido")]
+pub mod fido;
+
+#[cfg(feature = "github")]
+pub mod github;
 
 #[cfg(feature = "google")]
 pub mod google;
 
+#[cfg(feature = "ldap")]
+pub mod ldap;
+
+#[cfg(feature = "lockout")]
+pub mod lockout;
+
+#[cfg(feature = "mfa")]
+pub mod mfa;
+
+#[cfg(feature = "microsoft")]
+pub mod microsoft;
+
+#[cfg(feature = "mtls")]
+pub mod mtls;
+
+#[cfg(feature = "oauth2")]
+pub mod oauth2;
+
+#[cfg(feature = "otp")]
+pub mod otp;
+
 #[cfg(feature = "password")]
 pub mod password;
 
+#[cfg(feature = "ratelimit")]
+pub mod ratelimit;
+
+#[cfg(feature = "recovery")]
+pub mod recovery;
+
+#[cfg(feature = "saml")]
+pub mod saml;
+
+#[cfg(feature = "scram")]
+pub mod scram;
+
+#[cfg(any(feature = "jwt", feature = "paseto"))]
+pub mod tokens;
+
+#[cfg(feature = "u2f")]
+pub mod u2f;
+
 #[cfg(feature = "webauthn")]
 pub mod webauthn;
 
+#[cfg(feature = "std")]
 mod parsers;
+
+#[cfg(any(
+    feature = "apikey",
+    feature = "integrity",
+    feature = "jwt",
+    feature = "lockout",
+    feature = "mfa",
+    feature = "mtls",
+    feature = "otp",
+    feature = "recovery",
+    feature = "saml",
+    feature = "webauthn"
+))]
+mod time;
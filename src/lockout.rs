@@ -0,0 +1,339 @@
+//! Account lockout after repeated failed verifications
+//!
+//! A [`LockoutPolicy`] locks an account once `max_failures` failed verifications (of a
+//! password, WebAuthn assertion, one-time code, ...) are recorded within `window` seconds.
+//! The account unlocks automatically after `duration` seconds, or can be unlocked (or
+//! locked indefinitely) by an administrator.
+//!
+//! Per-user state is kept in a [`LockoutStore`] so it can live in any database; a
+//! [`MemoryLockoutStore`] is provided for testing and single-process deployments.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::lockout::{Lockout, LockoutPolicy, MemoryLockoutStore};
+//!
+//! let lockout = Lockout::new(LockoutPolicy::new(5, 15 * 60, 30 * 60), MemoryLockoutStore::new());
+//!
+//! lockout.check(user.id())?;
+//! match hasher.verify(&password, &user.hash) {
+//!     Ok(()) => lockout.record_success(user.id())?,
+//!     Err(e) => {
+//!         lockout.record_failure(user.id())?;
+//!         return Err(e.into());
+//!     }
+//! }
+//! ```
+
+use crate::time::now;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Mutex};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LockoutError {
+    #[error("account is locked")]
+    Locked {
+        /// Time (seconds since the unix epoch) the account unlocks, or `None` if locked
+        /// until an administrator unlocks it
+        until: Option<u64>,
+    },
+
+    #[error("lockout store failure: {0}")]
+    Store(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl LockoutError {
+    /// Wraps an error returned by a lockout store
+    ///
+    /// # Arguments
+    /// * `e` - The underlying store error
+    pub fn store<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> LockoutError {
+        LockoutError::Store(e.into())
+    }
+}
+
+/// When accounts are locked, and for how long
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    /// Number of failures that locks an account
+    max_failures: u32,
+
+    /// Window (in seconds) failures are counted within
+    window: u64,
+
+    /// Time (in seconds) an account remains locked
+    duration: u64,
+}
+
+impl LockoutPolicy {
+    /// Creates a new policy
+    ///
+    /// # Arguments
+    /// * `max_failures` - Number of failures that locks an account
+    /// * `window` - Window (in seconds) failures are counted within
+    /// * `duration` - Time (in seconds) an account remains locked
+    pub fn new(max_failures: u32, window: u64, duration: u64) -> LockoutPolicy {
+        LockoutPolicy {
+            max_failures,
+            window,
+            duration,
+        }
+    }
+}
+
+impl Default for LockoutPolicy {
+    /// Five failures within 15 minutes locks an account for 15 minutes
+    fn default() -> Self {
+        LockoutPolicy::new(5, 15 * 60, 15 * 60)
+    }
+}
+
+/// Lockout state of a single account
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct LockoutState {
+    /// Times (seconds since the unix epoch) of recent failures
+    failures: Vec<u64>,
+
+    /// Time (seconds since the unix epoch) the account unlocks, if locked by the policy
+    locked_until: Option<u64>,
+
+    /// True if an administrator locked the account
+    admin_locked: bool,
+}
+
+impl LockoutState {
+    /// Returns the times (seconds since the unix epoch) of recent failures
+    pub fn failures(&self) -> &[u64] {
+        &self.failures
+    }
+
+    /// Returns true if the account is locked at time `now`
+    pub fn is_locked(&self, now: u64) -> bool {
+        self.admin_locked || self.locked_until.map(|t| t > now).unwrap_or(false)
+    }
+
+    /// Returns the error describing the lock, if the account is locked at time `now`
+    fn locked(&self, now: u64) -> Result<(), LockoutError> {
+        if self.admin_locked {
+            Err(LockoutError::Locked { until: None })
+        } else if self.is_locked(now) {
+            Err(LockoutError::Locked {
+                until: self.locked_until,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Stores the lockout state of accounts, keyed by the user's id
+pub trait LockoutStore: Send + Sync {
+    /// Returns the lockout state of an account, if any has been saved
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user
+    fn load(&self, user: &[u8]) -> Result<Option<LockoutState>, LockoutError>;
+
+    /// Saves the lockout state of an account
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user
+    /// * `state` - New lockout state
+    fn save(&self, user: &[u8], state: LockoutState) -> Result<(), LockoutError>;
+}
+
+/// A simple in-memory lockout store
+#[derive(Debug, Default)]
+pub struct MemoryLockoutStore {
+    accounts: Mutex<HashMap<Vec<u8>, LockoutState>>,
+}
+
+impl MemoryLockoutStore {
+    pub fn new() -> MemoryLockoutStore {
+        Self::default()
+    }
+}
+
+impl LockoutStore for MemoryLockoutStore {
+    fn load(&self, user: &[u8]) -> Result<Option<LockoutState>, LockoutError> {
+        let accounts = self
+            .accounts
+            .lock()
+            .map_err(|_| LockoutError::store("poisoned lock"))?;
+        Ok(accounts.get(user).cloned())
+    }
+
+    fn save(&self, user: &[u8], state: LockoutState) -> Result<(), LockoutError> {
+        let mut accounts = self
+            .accounts
+            .lock()
+            .map_err(|_| LockoutError::store("poisoned lock"))?;
+        accounts.insert(user.to_vec(), state);
+        Ok(())
+    }
+}
+
+/// Applies a [`LockoutPolicy`] to the accounts in a [`LockoutStore`]
+pub struct Lockout<S> {
+    policy: LockoutPolicy,
+    store: S,
+}
+
+impl<S: LockoutStore> Lockout<S> {
+    /// Creates a new lockout engine
+    ///
+    /// # Arguments
+    /// * `policy` - When accounts are locked, and for how long
+    /// * `store` - Where the lockout state of accounts is kept
+    pub fn new(policy: LockoutPolicy, store: S) -> Lockout<S> {
+        Lockout { policy, store }
+    }
+
+    /// Returns the policy being applied
+    pub fn policy(&self) -> &LockoutPolicy {
+        &self.policy
+    }
+
+    /// Fails if an account is currently locked.  Call before verifying credentials
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user
+    pub fn check(&self, user: &[u8]) -> Result<(), LockoutError> {
+        self.check_at(user, now())
+    }
+
+    /// Records a failed verification, locking the account if the policy is exceeded
+    ///
+    /// Returns the error describing the lock if this failure locked the account
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user
+    pub fn record_failure(&self, user: &[u8]) -> Result<(), LockoutError> {
+        self.record_failure_at(user, now())
+    }
+
+    /// Records a successful verification, clearing any recorded failures
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user
+    pub fn record_success(&self, user: &[u8]) -> Result<(), LockoutError> {
+        let mut state = self.load(user)?;
+        if state.failures.is_empty() && state.locked_until.is_none() {
+            return Ok(());
+        }
+
+        state.failures.clear();
+        state.locked_until = None;
+        self.store.save(user, state)
+    }
+
+    /// Locks an account until an administrator unlocks it
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user
+    pub fn lock(&self, user: &[u8]) -> Result<(), LockoutError> {
+        let mut state = self.load(user)?;
+        state.admin_locked = true;
+        self.store.save(user, state)
+    }
+
+    /// Unlocks an account and clears its recorded failures (administrator override)
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user
+    pub fn unlock(&self, user: &[u8]) -> Result<(), LockoutError> {
+        self.store.save(user, LockoutState::default())
+    }
+
+    /// Returns the lockout state of an account
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user
+    pub fn state(&self, user: &[u8]) -> Result<LockoutState, LockoutError> {
+        self.load(user)
+    }
+
+    fn check_at(&self, user: &[u8], now: u64) -> Result<(), LockoutError> {
+        self.load(user)?.locked(now)
+    }
+
+    fn record_failure_at(&self, user: &[u8], now: u64) -> Result<(), LockoutError> {
+        let mut state = self.load(user)?;
+
+        // an expired lock starts a fresh window
+        if state.locked_until.map(|t| t <= now).unwrap_or(false) {
+            state.locked_until = None;
+            state.failures.clear();
+        }
+
+        let window = self.policy.window;
+        state.failures.retain(|t| t + window > now);
+        state.failures.push(now);
+
+        let locked = state.locked_until.is_none()
+            && state.failures.len() >= self.policy.max_failures as usize;
+        if locked {
+            state.locked_until = Some(now + self.policy.duration);
+        }
+
+        let result = if locked { state.locked(now) } else { Ok(()) };
+        self.store.save(user, state)?;
+        result
+    }
+
+    fn load(&self, user: &[u8]) -> Result<LockoutState, LockoutError> {
+        Ok(self.store.load(user)?.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: &[u8] = &[0, 1, 2, 3];
+
+    fn lockout() -> Lockout<MemoryLockoutStore> {
+        Lockout::new(LockoutPolicy::new(3, 60, 300), MemoryLockoutStore::new())
+    }
+
+    #[test]
+    fn failures_lock_and_unlock_account() {
+        let lockout = lockout();
+        lockout.record_failure_at(USER, 1000).unwrap();
+        lockout.record_failure_at(USER, 1010).unwrap();
+        assert!(matches!(
+            lockout.record_failure_at(USER, 1020),
+            Err(LockoutError::Locked { until: Some(1320) })
+        ));
+
+        assert!(lockout.check_at(USER, 1319).is_err());
+        lockout.check_at(USER, 1320).unwrap();
+    }
+
+    #[test]
+    fn failures_outside_window_are_forgotten() {
+        let lockout = lockout();
+        lockout.record_failure_at(USER, 1000).unwrap();
+        lockout.record_failure_at(USER, 1010).unwrap();
+        lockout.record_failure_at(USER, 1065).unwrap();
+        lockout.check_at(USER, 1065).unwrap();
+        assert_eq!(lockout.state(USER).unwrap().failures(), &[1010, 1065]);
+
+        lockout.record_success(USER).unwrap();
+        assert!(lockout.state(USER).unwrap().failures().is_empty());
+    }
+
+    #[test]
+    fn admin_can_lock_and_unlock() {
+        let lockout = lockout();
+        lockout.lock(USER).unwrap();
+        assert!(matches!(
+            lockout.check(USER),
+            Err(LockoutError::Locked { until: None })
+        ));
+
+        lockout.unlock(USER).unwrap();
+        lockout.check(USER).unwrap();
+    }
+}
@@ -0,0 +1,29 @@
+//! Internal helper macros
+
+/// Records a value on a field of the current tracing span.  Expands to nothing (and the
+/// value is never evaluated) unless the `tracing` feature is enabled
+#[allow(unused_macros)]
+macro_rules! trace_record {
+    ($field:literal, $value:expr) => {
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record($field, tracing::field::display($value));
+    };
+}
+
+/// Increments a counter, with optional labels, when the `metrics` feature is enabled
+#[allow(unused_macros)]
+macro_rules! metric_counter {
+    ($name:literal $(, $label:literal => $value:expr)*) => {
+        #[cfg(feature = "metrics")]
+        metrics::counter!($name $(, $label => $value)*).increment(1);
+    };
+}
+
+/// Records a value in a histogram when the `metrics` feature is enabled
+#[allow(unused_macros)]
+macro_rules! metric_histogram {
+    ($name:literal, $value:expr) => {
+        #[cfg(feature = "metrics")]
+        metrics::histogram!($name).record($value);
+    };
+}
@@ -0,0 +1,410 @@
+//! Multi-factor authentication policies
+//!
+//! A [`Policy`] composes the factors supported by this crate into a requirement such as
+//! `password AND (webauthn OR totp)`.  Policies can be built in code or parsed from a
+//! string (`AND` binds tighter than `OR`, parentheses group).  The factors completed by a
+//! session are tracked in an [`MfaState`], which reports whether the policy is satisfied
+//! and, if not, what is still required.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::mfa::{Factor, MfaState, Policy};
+//!
+//! let policy: Policy = "password AND (webauthn OR totp)".parse()?;
+//!
+//! let mut state = MfaState::new();
+//! state.complete(Factor::Password);
+//!
+//! // Some(webauthn OR totp)
+//! let remaining = state.remaining(&policy);
+//! ```
+
+use crate::time::now;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MfaError {
+    #[error("unknown factor `{0}`")]
+    UnknownFactor(String),
+
+    #[error("invalid policy: {0}")]
+    InvalidPolicy(String),
+}
+
+/// A single authentication factor
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Factor {
+    /// Password verified with the `password` module
+    Password,
+
+    /// WebAuthn assertion
+    WebAuthn,
+
+    /// Time-based one-time password
+    Totp,
+
+    /// Single-use recovery code
+    RecoveryCode,
+}
+
+impl Factor {
+    /// Returns the name of the factor as used in policy strings
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Factor::Password => "password",
+            Factor::WebAuthn => "webauthn",
+            Factor::Totp => "totp",
+            Factor::RecoveryCode => "recovery_code",
+        }
+    }
+}
+
+impl fmt::Display for Factor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Factor {
+    type Err = MfaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "password" => Ok(Factor::Password),
+            "webauthn" => Ok(Factor::WebAuthn),
+            "totp" => Ok(Factor::Totp),
+            "recovery_code" => Ok(Factor::RecoveryCode),
+            _ => Err(MfaError::UnknownFactor(s.to_owned())),
+        }
+    }
+}
+
+/// A requirement on the factors a session must complete
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// A single factor must be completed
+    Factor(Factor),
+
+    /// Every policy must be satisfied
+    All(Vec<Policy>),
+
+    /// At least one policy must be satisfied
+    Any(Vec<Policy>),
+}
+
+impl Policy {
+    /// Creates a policy requiring a single factor
+    pub fn factor(factor: Factor) -> Policy {
+        Policy::Factor(factor)
+    }
+
+    /// Requires both this policy and `other`
+    pub fn and(self, other: Policy) -> Policy {
+        match self {
+            Policy::All(mut all) => {
+                all.push(other);
+                Policy::All(all)
+            }
+            policy => Policy::All(vec![policy, other]),
+        }
+    }
+
+    /// Requires either this policy or `other`
+    pub fn or(self, other: Policy) -> Policy {
+        match self {
+            Policy::Any(mut any) => {
+                any.push(other);
+                Policy::Any(any)
+            }
+            policy => Policy::Any(vec![policy, other]),
+        }
+    }
+
+    /// Returns true if the completed factors satisfy the policy
+    ///
+    /// # Arguments
+    /// * `completed` - Returns true if a factor has been completed
+    pub fn is_satisfied<F: Fn(Factor) -> bool>(&self, completed: &F) -> bool {
+        self.remaining(completed).is_none()
+    }
+
+    /// Returns the part of the policy that is not yet satisfied, or `None` if the policy
+    /// is satisfied
+    ///
+    /// # Arguments
+    /// * `completed` - Returns true if a factor has been completed
+    pub fn remaining<F: Fn(Factor) -> bool>(&self, completed: &F) -> Option<Policy> {
+        match self {
+            Policy::Factor(factor) if completed(*factor) => None,
+            Policy::Factor(factor) => Some(Policy::Factor(*factor)),
+            Policy::All(all) => {
+                let mut remaining: Vec<Policy> =
+                    all.iter().filter_map(|p| p.remaining(completed)).collect();
+                match remaining.len() {
+                    0 => None,
+                    1 => remaining.pop(),
+                    _ => Some(Policy::All(remaining)),
+                }
+            }
+            Policy::Any(any) => {
+                let mut remaining = Vec::with_capacity(any.len());
+                for policy in any {
+                    remaining.push(policy.remaining(completed)?);
+                }
+
+                match remaining.len() {
+                    // an empty `Any` can never be satisfied
+                    0 => Some(Policy::Any(remaining)),
+                    1 => remaining.pop(),
+                    _ => Some(Policy::Any(remaining)),
+                }
+            }
+        }
+    }
+
+    /// Returns every factor that appears in the policy
+    pub fn factors(&self) -> Vec<Factor> {
+        let mut factors = vec![];
+        self.collect(&mut factors);
+        factors.sort();
+        factors.dedup();
+        factors
+    }
+
+    fn collect(&self, factors: &mut Vec<Factor>) {
+        match self {
+            Policy::Factor(factor) => factors.push(*factor),
+            Policy::All(policies) | Policy::Any(policies) => {
+                policies.iter().for_each(|p| p.collect(factors))
+            }
+        }
+    }
+}
+
+impl From<Factor> for Policy {
+    fn from(factor: Factor) -> Policy {
+        Policy::Factor(factor)
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Policy::Factor(factor) => write!(f, "{}", factor),
+            Policy::All(policies) => join(f, policies, " AND "),
+            Policy::Any(policies) => join(f, policies, " OR "),
+        }
+    }
+}
+
+/// Writes a list of policies separated by `sep`, grouping nested lists with parentheses
+fn join(f: &mut fmt::Formatter, policies: &[Policy], sep: &str) -> fmt::Result {
+    for (i, policy) in policies.iter().enumerate() {
+        if i > 0 {
+            f.write_str(sep)?;
+        }
+
+        match policy {
+            Policy::Factor(_) => write!(f, "{}", policy)?,
+            _ => write!(f, "({})", policy)?,
+        }
+    }
+    Ok(())
+}
+
+impl FromStr for Policy {
+    type Err = MfaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s);
+        let mut pos = 0;
+        let policy = parse_or(&tokens, &mut pos)?;
+        match tokens.get(pos) {
+            None => Ok(policy),
+            Some(token) => Err(MfaError::InvalidPolicy(format!("unexpected `{}`", token))),
+        }
+    }
+}
+
+/// Splits a policy string into identifiers and parentheses
+fn tokenize(s: &str) -> Vec<String> {
+    s.replace('(', " ( ")
+        .replace(')', " ) ")
+        .split_whitespace()
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Parses `and ("OR" and)*`
+fn parse_or(tokens: &[String], pos: &mut usize) -> Result<Policy, MfaError> {
+    let mut policy = parse_and(tokens, pos)?;
+    while tokens.get(*pos).map(|t| t.eq_ignore_ascii_case("or")) == Some(true) {
+        *pos += 1;
+        policy = policy.or(parse_and(tokens, pos)?);
+    }
+    Ok(policy)
+}
+
+/// Parses `atom ("AND" atom)*`
+fn parse_and(tokens: &[String], pos: &mut usize) -> Result<Policy, MfaError> {
+    let mut policy = parse_atom(tokens, pos)?;
+    while tokens.get(*pos).map(|t| t.eq_ignore_ascii_case("and")) == Some(true) {
+        *pos += 1;
+        policy = policy.and(parse_atom(tokens, pos)?);
+    }
+    Ok(policy)
+}
+
+/// Parses a factor or a parenthesized policy
+fn parse_atom(tokens: &[String], pos: &mut usize) -> Result<Policy, MfaError> {
+    let token = tokens
+        .get(*pos)
+        .ok_or_else(|| MfaError::InvalidPolicy("unexpected end of policy".to_owned()))?;
+    *pos += 1;
+
+    if token == "(" {
+        let policy = parse_or(tokens, pos)?;
+        if tokens.get(*pos).map(String::as_str) != Some(")") {
+            return Err(MfaError::InvalidPolicy("missing `)`".to_owned()));
+        }
+        *pos += 1;
+        return Ok(policy);
+    }
+
+    token.parse::<Factor>().map(Policy::Factor)
+}
+
+/// Factors completed by a session
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct MfaState {
+    /// Time (seconds since the unix epoch) each factor was completed
+    completed: BTreeMap<Factor, u64>,
+}
+
+impl MfaState {
+    /// Creates a new state with no completed factors
+    pub fn new() -> MfaState {
+        Self::default()
+    }
+
+    /// Marks a factor as completed now
+    ///
+    /// # Arguments
+    /// * `factor` - Factor the user completed
+    pub fn complete(&mut self, factor: Factor) {
+        self.completed.insert(factor, now());
+    }
+
+    /// Returns the time (seconds since the unix epoch) a factor was completed, if completed
+    pub fn completed_at(&self, factor: Factor) -> Option<u64> {
+        self.completed.get(&factor).copied()
+    }
+
+    /// Returns true if the session has completed a factor
+    pub fn has_completed(&self, factor: Factor) -> bool {
+        self.completed.contains_key(&factor)
+    }
+
+    /// Forgets every completed factor (e.g., on logout)
+    pub fn reset(&mut self) {
+        self.completed.clear();
+    }
+
+    /// Returns true if the completed factors satisfy a policy
+    ///
+    /// # Arguments
+    /// * `policy` - Policy to check
+    pub fn is_satisfied(&self, policy: &Policy) -> bool {
+        policy.is_satisfied(&|f| self.has_completed(f))
+    }
+
+    /// Returns what is still required to satisfy a policy, or `None` if it is satisfied
+    ///
+    /// # Arguments
+    /// * `policy` - Policy to check
+    pub fn remaining(&self, policy: &Policy) -> Option<Policy> {
+        policy.remaining(&|f| self.has_completed(f))
+    }
+
+    /// Returns true if the completed factors, ignoring any completed more than `max_age`
+    /// seconds ago, satisfy a policy
+    ///
+    /// # Arguments
+    /// * `policy` - Policy to check
+    /// * `max_age` - Maximum age, in seconds, of a completed factor
+    pub fn is_satisfied_within(&self, policy: &Policy, max_age: u64) -> bool {
+        let now = now();
+        policy.is_satisfied(&|f| {
+            self.completed_at(f)
+                .map(|t| now.saturating_sub(t) <= max_age)
+                .unwrap_or(false)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time;
+
+    #[test]
+    fn policy_parses_and_displays() {
+        let policy: Policy = "password AND (webauthn OR totp)".parse().unwrap();
+        assert_eq!(
+            policy,
+            Policy::factor(Factor::Password)
+                .and(Policy::factor(Factor::WebAuthn).or(Factor::Totp.into()))
+        );
+        assert_eq!(policy.to_string(), "password AND (webauthn OR totp)");
+        assert_eq!(
+            "webauthn or password and totp"
+                .parse::<Policy>()
+                .unwrap()
+                .to_string(),
+            "webauthn OR (password AND totp)"
+        );
+
+        assert!("password AND".parse::<Policy>().is_err());
+        assert!("(password".parse::<Policy>().is_err());
+        assert!("password OR sms".parse::<Policy>().is_err());
+    }
+
+    #[test]
+    fn state_reports_remaining_factors() {
+        let policy: Policy = "password AND (webauthn OR totp)".parse().unwrap();
+        let mut state = MfaState::new();
+        assert!(!state.is_satisfied(&policy));
+
+        state.complete(Factor::Password);
+        assert_eq!(
+            state.remaining(&policy),
+            Some(Policy::factor(Factor::WebAuthn).or(Factor::Totp.into()))
+        );
+
+        state.complete(Factor::Totp);
+        assert!(state.is_satisfied(&policy));
+        assert!(state.is_satisfied_within(&policy, 60));
+    }
+
+    #[test]
+    fn stale_factors_are_ignored() {
+        let policy: Policy = "password AND totp".parse().unwrap();
+        let clock = time::freeze(1_600_000_000);
+        let mut state = MfaState::new();
+
+        state.complete(Factor::Password);
+        clock.advance(30);
+        state.complete(Factor::Totp);
+        assert_eq!(state.completed_at(Factor::Totp), Some(1_600_000_030));
+        assert!(state.is_satisfied_within(&policy, 30));
+
+        clock.advance(1);
+        assert!(!state.is_satisfied_within(&policy, 30));
+        assert!(state.is_satisfied_within(&policy, 31));
+        assert!(state.is_satisfied(&policy));
+    }
+}
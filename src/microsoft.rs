@@ -0,0 +1,501 @@
+//! Validate tokens issued by the Microsoft identity platform (Azure AD / Entra ID)
+//!
+//! [`MicrosoftAuth`] verifies id and access tokens signed with the platform's published
+//! keys.  It is configured with a tenant: either a specific tenant id, or
+//! one of the multi-tenant aliases `common`, `organizations` or `consumers`.  Since
+//! multi-tenant tokens are issued by the user's own tenant, the issuer is checked
+//! against the `{tenantid}` template with the token's `tid` claim substituted, and
+//! applications can restrict which tenants they accept with
+//! [`MicrosoftAuth::allowed_tenants`].
+//!
+//! Microsoft rolls its signing keys over without notice, so a token signed by an unknown
+//! key triggers a refresh of the key set (at most once every few minutes) before it is
+//! rejected.
+//!
+//! Source: [Microsoft identity platform id
+//! tokens](https://learn.microsoft.com/en-us/entra/identity-platform/id-tokens)
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::{google::MemoryCertStore, microsoft::MicrosoftAuth};
+//!
+//! let mut auth = MicrosoftAuth::new(MemoryCertStore::new(), "my-client-id", "organizations")
+//!     .allowed_tenants(&["72f988bf-86f1-41af-91ab-2d7cd011db47"]);
+//!
+//! let claims = auth.verify(token).await?;
+//! println!("{} ({:?})", claims.oid, claims.upn);
+//! ```
+
+use crate::{
+    events::{self, AuthEvent},
+    google::{fetch_jwks, CertStore, FetchError, KeyFetcher},
+};
+use chrono::{prelude::*, Duration};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use thiserror::Error;
+
+#[cfg(feature = "reqwest")]
+use crate::google::ReqwestFetcher;
+
+/// Authority hosting the key sets and issuing v2.0 tokens
+const AUTHORITY: &str = "https://login.microsoftonline.com";
+
+/// Placeholder replaced with the token's tenant id in issuer templates
+const TENANT_PLACEHOLDER: &str = "{tenantid}";
+
+/// Issuer of v1.0 tokens
+const ISSUER_V1: &str = "https://sts.windows.net/{tenantid}/";
+
+/// Issuer of v2.0 tokens
+const ISSUER_V2: &str = "https://login.microsoftonline.com/{tenantid}/v2.0";
+
+/// Tenant that personal Microsoft accounts belong to
+const CONSUMERS_TENANT: &str = "9188040d-6c67-4c5b-b112-36a304b66dad";
+
+/// Minimum time (in seconds) between refreshes caused by unknown key ids
+const MIN_REFRESH_INTERVAL: i64 = 300;
+
+/// Errors that may occur when validating a Microsoft token
+#[derive(Debug, Error)]
+pub enum MicrosoftError {
+    /// The header failed to decode or does not describe an RS256 JWT
+    #[error("malformed token header")]
+    BadHeader,
+
+    /// The header is missing the `kid` field
+    #[error("token header is missing a key id")]
+    MissingKeyId,
+
+    /// Fetching the signing keys failed
+    #[error("failed to fetch signing keys")]
+    FetchKeysFailed,
+
+    /// The key that signed the token was not found, even after refreshing the keys
+    #[error("signing key not found")]
+    KeyNotFound,
+
+    /// The signature, audience or lifetime of the token is invalid
+    #[error("token validation failed")]
+    ValidationFailed,
+
+    /// The issuer does not match the tenant that issued the token
+    #[error("invalid issuer")]
+    InvalidIssuer,
+
+    /// The token was issued by a tenant this application does not accept
+    #[error("tenant `{0}` is not allowed")]
+    TenantNotAllowed(String),
+}
+
+/// Claims common to Microsoft identity platform id and access tokens
+#[derive(Clone, Debug, Deserialize)]
+pub struct MicrosoftClaims {
+    /// Immutable id of the user, the same across every application in the tenant
+    pub oid: String,
+
+    /// Id of the tenant the user signed in to
+    pub tid: String,
+
+    /// Subject, unique to the user and this application
+    pub sub: String,
+
+    /// Issuer of the token
+    pub iss: String,
+
+    /// Audience (the application's client id or app id uri)
+    pub aud: String,
+
+    /// Expiration time (seconds since the epoch)
+    pub exp: i64,
+
+    /// Issued at time (seconds since the epoch)
+    #[serde(default)]
+    pub iat: i64,
+
+    /// Token version, `1.0` or `2.0`
+    #[serde(default)]
+    pub ver: String,
+
+    /// User principal name (v1.0 tokens and work or school accounts only)
+    pub upn: Option<String>,
+
+    /// Username the user signed in with (v2.0 tokens)
+    pub preferred_username: Option<String>,
+
+    /// Display name
+    pub name: Option<String>,
+
+    /// Email address, if the `email` claim was requested
+    pub email: Option<String>,
+
+    /// Application roles assigned to the user
+    #[serde(default)]
+    pub roles: Vec<String>,
+
+    /// Groups the user belongs to, if group claims are configured
+    #[serde(default)]
+    pub groups: Vec<String>,
+
+    /// Delegated scopes granted to the client (access tokens only), space separated
+    pub scp: Option<String>,
+
+    /// All other claims
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl MicrosoftClaims {
+    /// Returns the delegated scopes granted to the client
+    pub fn scopes(&self) -> impl Iterator<Item = &str> {
+        self.scp.iter().flat_map(|scp| scp.split_whitespace())
+    }
+}
+
+#[derive(Clone)]
+pub struct MicrosoftAuth<S> {
+    tenant: String,
+    allowed_tenants: Option<HashSet<String>>,
+    validation: Validation,
+    keys: Arc<RwLock<KeySet<S>>>,
+    fetcher: Arc<dyn KeyFetcher>,
+}
+
+/// Signing keys shared between clones of an authenticator
+struct KeySet<S> {
+    store: S,
+    expire: Option<DateTime<Utc>>,
+    fetched: Option<DateTime<Utc>>,
+}
+
+impl<S> MicrosoftAuth<S>
+where
+    S: CertStore,
+{
+    /// Creates a new authenticator, fetching keys with `reqwest`
+    ///
+    /// # Arguments
+    /// * `store` - Store for the signing keys
+    /// * `client_id` - Client id (or app id uri) tokens must be issued to
+    /// * `tenant` - Tenant id, or `common`, `organizations` or `consumers`
+    #[cfg(feature = "reqwest")]
+    pub fn new(
+        store: S,
+        client_id: impl Into<String>,
+        tenant: impl Into<String>,
+    ) -> MicrosoftAuth<S> {
+        Self::with_fetcher(store, ReqwestFetcher::new(), client_id, tenant)
+    }
+
+    /// Creates a new authenticator, fetching keys with a custom HTTP client
+    ///
+    /// # Arguments
+    /// * `store` - Store for the signing keys
+    /// * `fetcher` - Fetcher to fetch the signing keys with
+    /// * `client_id` - Client id (or app id uri) tokens must be issued to
+    /// * `tenant` - Tenant id, or `common`, `organizations` or `consumers`
+    pub fn with_fetcher(
+        store: S,
+        fetcher: impl KeyFetcher + 'static,
+        client_id: impl Into<String>,
+        tenant: impl Into<String>,
+    ) -> MicrosoftAuth<S> {
+        let mut aud = HashSet::new();
+        aud.insert(client_id.into());
+
+        // the issuer depends on the token's tenant, so it is checked separately
+        let validation = Validation {
+            leeway: 0,
+            validate_exp: true,
+            validate_nbf: true,
+            iss: None,
+            aud: Some(aud),
+            algorithms: vec![Algorithm::RS256],
+            ..Default::default()
+        };
+
+        MicrosoftAuth {
+            tenant: tenant.into(),
+            allowed_tenants: None,
+            validation,
+            keys: Arc::new(RwLock::new(KeySet {
+                expire: store.expiry().or_else(|| Some(Utc::now())),
+                store,
+                fetched: None,
+            })),
+            fetcher: Arc::new(fetcher),
+        }
+    }
+
+    /// Restricts the tenants whose tokens are accepted, for multi-tenant applications
+    ///
+    /// # Arguments
+    /// * `tenants` - Ids of the accepted tenants
+    pub fn allowed_tenants(mut self, tenants: &[&str]) -> Self {
+        self.allowed_tenants = Some(tenants.iter().map(|t| t.to_ascii_lowercase()).collect());
+        self
+    }
+
+    /// Sets the allowed clock skew (in seconds) when checking a token's lifetime
+    ///
+    /// # Arguments
+    /// * `leeway` - Allowed clock skew, in seconds
+    pub fn leeway(mut self, leeway: u64) -> Self {
+        self.validation.leeway = leeway;
+        self
+    }
+
+    /// Returns the url of the tenant's JSON Web Key Set
+    fn keys_url(&self) -> String {
+        format!("{}/{}/discovery/v2.0/keys", AUTHORITY, self.tenant)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "microsoft.fetch_keys",
+            skip_all,
+            fields(keys = tracing::field::Empty),
+            err
+        )
+    )]
+    async fn fetch(&self) -> Result<(), FetchError> {
+        let (keys, max_age) = fetch_jwks(self.fetcher.as_ref(), &self.keys_url()).await?;
+
+        trace_record!("keys", keys.len());
+        metric_counter!("auth_microsoft_key_refreshes_total", "outcome" => "success");
+        events::emit(AuthEvent::MicrosoftKeyRefresh { keys: keys.len() });
+
+        let now = Utc::now();
+        let mut set = self.keys.write();
+        set.fetched = Some(now);
+        set.expire = match Duration::from_std(std::time::Duration::from_secs(max_age)) {
+            Ok(duration) if max_age > 0 => Some(now + duration),
+            _ => None,
+        };
+        let expire = set.expire;
+        set.store.update_with_expiry(keys, expire);
+        Ok(())
+    }
+
+    /// Refreshes the signing keys, reporting failures
+    async fn refresh(&self) -> Result<(), MicrosoftError> {
+        self.fetch().await.map_err(|e| {
+            metric_counter!("auth_microsoft_key_refreshes_total", "outcome" => "failure");
+            events::emit(AuthEvent::MicrosoftKeyRefreshFailed {
+                reason: e.to_string(),
+            });
+            MicrosoftError::FetchKeysFailed
+        })
+    }
+
+    /// Returns true if the keys in the store are expired
+    fn is_expired(&self) -> bool {
+        match self.keys.read().expire {
+            Some(expire) => Utc::now() > expire,
+            None => false,
+        }
+    }
+
+    /// Returns true if an unknown key id may trigger a refresh of the keys
+    fn may_refresh(&self) -> bool {
+        match self.keys.read().fetched {
+            Some(fetched) => Utc::now() - fetched > Duration::seconds(MIN_REFRESH_INTERVAL),
+            None => true,
+        }
+    }
+
+    /// Verifies a JWT issued by the Microsoft identity platform
+    ///
+    /// # Arguments
+    /// * `token` - JWT token (as a base64-encoded string)
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "microsoft.verify",
+            skip_all,
+            fields(outcome = tracing::field::Empty)
+        )
+    )]
+    pub async fn verify(
+        &mut self,
+        token: impl AsRef<str>,
+    ) -> Result<MicrosoftClaims, MicrosoftError> {
+        let result = self.validate(token.as_ref()).await;
+        trace_record!(
+            "outcome",
+            match result {
+                Ok(_) => "success".to_owned(),
+                Err(ref e) => format!("{:?}", e),
+            }
+        );
+
+        if let Err(ref e) = result {
+            events::emit(AuthEvent::MicrosoftTokenRejected {
+                reason: e.to_string(),
+            });
+        }
+        result
+    }
+
+    /// Validates a JWT token, see `verify()`
+    async fn validate(&self, token: &str) -> Result<MicrosoftClaims, MicrosoftError> {
+        let header = decode_header(token).map_err(|_| MicrosoftError::BadHeader)?;
+        if header.alg != Algorithm::RS256 {
+            return Err(MicrosoftError::BadHeader);
+        }
+        let kid = header.kid.ok_or(MicrosoftError::MissingKeyId)?;
+
+        if self.is_expired() {
+            self.refresh().await?;
+        }
+
+        // an unknown key id usually means the keys were rolled over since the last fetch
+        let known = self.keys.read().store.get(&kid).is_some();
+        if !known && self.may_refresh() {
+            self.refresh().await?;
+        }
+
+        let set = self.keys.read();
+        let key = set.store.get(&kid).ok_or(MicrosoftError::KeyNotFound)?;
+        self.check(token, &key)
+    }
+
+    /// Checks a token's signature and claims against a signing key
+    ///
+    /// # Arguments
+    /// * `token` - JWT token (as a base64-encoded string)
+    /// * `key` - Key that signed the token
+    fn check(&self, token: &str, key: &DecodingKey) -> Result<MicrosoftClaims, MicrosoftError> {
+        let claims: MicrosoftClaims = decode(token, key, &self.validation)
+            .map_err(|_| MicrosoftError::ValidationFailed)
+            .map(|data| data.claims)?;
+
+        self.check_tenant(&claims)?;
+        Ok(claims)
+    }
+
+    /// Checks a token was issued by a tenant this application accepts
+    ///
+    /// # Arguments
+    /// * `claims` - Claims of a token whose signature has been verified
+    fn check_tenant(&self, claims: &MicrosoftClaims) -> Result<(), MicrosoftError> {
+        let tid = claims.tid.to_ascii_lowercase();
+        let issuer = match claims.ver.as_str() {
+            "1.0" => ISSUER_V1,
+            _ => ISSUER_V2,
+        };
+        if claims.tid.is_empty() || claims.iss != issuer.replace(TENANT_PLACEHOLDER, &tid) {
+            return Err(MicrosoftError::InvalidIssuer);
+        }
+
+        let allowed = match self.tenant.to_ascii_lowercase().as_str() {
+            "common" => true,
+            "organizations" => tid != CONSUMERS_TENANT,
+            "consumers" => tid == CONSUMERS_TENANT,
+            tenant => tid == tenant,
+        };
+        let allowed = allowed
+            && self
+                .allowed_tenants
+                .as_ref()
+                .is_none_or(|tenants| tenants.contains(&tid));
+
+        match allowed {
+            true => Ok(()),
+            false => Err(MicrosoftError::TenantNotAllowed(claims.tid.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::google::{MemoryCertStore, StaticFetcher};
+
+    const TENANT: &str = "72f988bf-86f1-41af-91ab-2d7cd011db47";
+
+    fn claims(tid: &str, iss: &str, ver: &str) -> MicrosoftClaims {
+        serde_json::from_value(serde_json::json!({
+            "oid": "00000000-0000-0000-66f3-3332eca7ea81",
+            "tid": tid,
+            "sub": "AAAAAAAAAAAAAAAAAAAAAIkzqFVrSaSaFHy782bbtaQ",
+            "iss": iss,
+            "aud": "client",
+            "exp": 1_600_000_000,
+            "ver": ver,
+            "upn": "alice@contoso.com",
+            "scp": "User.Read Mail.Read",
+            "xms_tpl": "en",
+        }))
+        .unwrap()
+    }
+
+    fn auth(tenant: &str) -> MicrosoftAuth<MemoryCertStore> {
+        MicrosoftAuth::with_fetcher(MemoryCertStore::new(), StaticFetcher(""), "client", tenant)
+    }
+
+    #[test]
+    fn typed_claims() {
+        let claims = claims(TENANT, "", "2.0");
+        assert_eq!(claims.upn.as_deref(), Some("alice@contoso.com"));
+        assert_eq!(
+            claims.scopes().collect::<Vec<_>>(),
+            ["User.Read", "Mail.Read"]
+        );
+        assert!(claims.roles.is_empty());
+        assert_eq!(claims.extra["xms_tpl"], "en");
+    }
+
+    #[test]
+    fn issuer_substitutes_tenant() {
+        let v2 = claims(TENANT, &format!("{}/{}/v2.0", AUTHORITY, TENANT), "2.0");
+        let v1 = claims(
+            TENANT,
+            &format!("https://sts.windows.net/{}/", TENANT),
+            "1.0",
+        );
+        assert!(auth(TENANT).check_tenant(&v2).is_ok());
+        assert!(auth("common").check_tenant(&v1).is_ok());
+
+        // the issuer must name the tenant in the `tid` claim
+        let other = claims(
+            TENANT,
+            &format!("{}/{}/v2.0", AUTHORITY, CONSUMERS_TENANT),
+            "2.0",
+        );
+        assert!(matches!(
+            auth("common").check_tenant(&other),
+            Err(MicrosoftError::InvalidIssuer)
+        ));
+    }
+
+    #[test]
+    fn tenant_restrictions() {
+        let work = claims(TENANT, &format!("{}/{}/v2.0", AUTHORITY, TENANT), "2.0");
+        let personal = claims(
+            CONSUMERS_TENANT,
+            &format!("{}/{}/v2.0", AUTHORITY, CONSUMERS_TENANT),
+            "2.0",
+        );
+
+        assert!(auth("organizations").check_tenant(&work).is_ok());
+        assert!(auth("organizations").check_tenant(&personal).is_err());
+        assert!(auth("consumers").check_tenant(&personal).is_ok());
+        assert!(auth("consumers").check_tenant(&work).is_err());
+        assert!(auth(CONSUMERS_TENANT).check_tenant(&work).is_err());
+
+        let restricted = auth("common").allowed_tenants(&[CONSUMERS_TENANT]);
+        assert!(restricted.check_tenant(&personal).is_ok());
+        assert!(matches!(
+            restricted.check_tenant(&work),
+            Err(MicrosoftError::TenantNotAllowed(_))
+        ));
+    }
+}
@@ -0,0 +1,519 @@
+//! Client-certificate (mutual TLS) authentication
+//!
+//! When TLS is terminated with client certificates requested (by the application or a
+//! proxy forwarding the certificate chain), [`ClientCertVerifier`] validates the chain the
+//! client presented against the configured root CAs with the same `webpki` machinery used
+//! for WebAuthn attestation: signatures, validity windows, basic constraints and the
+//! `clientAuth` extended key usage are all checked.  The subject and subject alternative
+//! names of the certificate are then returned as a [`ClientIdentity`], which the caller
+//! maps to a user.
+//!
+//! Certificates can additionally be pinned to a set of public keys, given as the base64
+//! SHA-256 hash of their DER-encoded `SubjectPublicKeyInfo` (as produced by
+//! `openssl pkey -pubin -outform DER | openssl dgst -sha256 -binary | base64`).
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::mtls::ClientCertVerifier;
+//!
+//! let verifier = ClientCertVerifier::new().add_root(ca_der)?;
+//!
+//! // `chain` is the end-entity certificate followed by any intermediates
+//! let user = verifier.authenticate(&chain, |identity| db.find_user(identity.common_name()?))?;
+//! ```
+
+use crate::time::now;
+use ring::{constant_time, digest};
+use std::net::IpAddr;
+use thiserror::Error;
+use untrusted::{Input, Reader};
+use webpki::{trust_anchor_util, EndEntityCert, SignatureAlgorithm, TLSClientTrustAnchors, Time};
+
+/// Signature algorithms accepted in certificate chains
+static SIG_ALGS: &[&SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+];
+
+/// DER tags
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const OID: u8 = 0x06;
+const BOOLEAN: u8 = 0x01;
+const OCTET_STRING: u8 = 0x04;
+
+/// `id-ce-subjectAltName` (2.5.29.17)
+const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+#[derive(Error, Debug)]
+pub enum MtlsError {
+    #[error("no client certificate presented")]
+    EmptyChain,
+
+    #[error("certificate is malformed: {0}")]
+    Malformed(&'static str),
+
+    #[error("certificate rejected: {0}")]
+    Certificate(#[from] webpki::Error),
+
+    #[error("certificate public key pin is invalid")]
+    InvalidPin,
+
+    #[error("certificate public key does not match any pin")]
+    PinMismatch,
+
+    #[error("certificate does not identify a known user")]
+    UnknownUser,
+}
+
+/// Identity asserted by a validated client certificate
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientIdentity {
+    /// Subject attributes (e.g., `("CN", "alice")`), in certificate order
+    subject: Vec<(String, String)>,
+
+    /// DNS names from the subject alternative names
+    dns_names: Vec<String>,
+
+    /// Email addresses from the subject alternative names
+    emails: Vec<String>,
+
+    /// URIs (e.g., SPIFFE ids) from the subject alternative names
+    uris: Vec<String>,
+
+    /// IP addresses from the subject alternative names
+    ip_addresses: Vec<IpAddr>,
+
+    /// SHA-256 hash of the DER-encoded `SubjectPublicKeyInfo`
+    spki_sha256: Vec<u8>,
+}
+
+impl ClientIdentity {
+    /// Returns the subject attributes as (short name or dotted OID, value) pairs
+    pub fn subject(&self) -> &[(String, String)] {
+        &self.subject
+    }
+
+    /// Returns the first value of a subject attribute
+    ///
+    /// # Arguments
+    /// * `name` - Short name (e.g., `O`) or dotted OID of the attribute
+    pub fn subject_attribute(&self, name: &str) -> Option<&str> {
+        self.subject
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the subject's common name (CN)
+    pub fn common_name(&self) -> Option<&str> {
+        self.subject_attribute("CN")
+    }
+
+    /// Returns the DNS names from the subject alternative names
+    pub fn dns_names(&self) -> &[String] {
+        &self.dns_names
+    }
+
+    /// Returns the email addresses from the subject alternative names
+    pub fn emails(&self) -> &[String] {
+        &self.emails
+    }
+
+    /// Returns the URIs from the subject alternative names
+    pub fn uris(&self) -> &[String] {
+        &self.uris
+    }
+
+    /// Returns the IP addresses from the subject alternative names
+    pub fn ip_addresses(&self) -> &[IpAddr] {
+        &self.ip_addresses
+    }
+
+    /// Returns the base64 SHA-256 hash of the certificate's public key, as used for pinning
+    pub fn public_key_pin(&self) -> String {
+        base64::encode(&self.spki_sha256)
+    }
+}
+
+/// Validates client certificate chains
+#[derive(Clone, Debug, Default)]
+pub struct ClientCertVerifier {
+    /// DER-encoded root CA certificates
+    roots: Vec<Vec<u8>>,
+
+    /// SHA-256 hashes of pinned public keys
+    pins: Vec<Vec<u8>>,
+}
+
+impl ClientCertVerifier {
+    /// Creates a verifier that trusts no CAs
+    pub fn new() -> ClientCertVerifier {
+        ClientCertVerifier::default()
+    }
+
+    /// Trusts a root CA
+    ///
+    /// # Arguments
+    /// * `der` - DER-encoded CA certificate
+    pub fn add_root(mut self, der: Vec<u8>) -> Result<Self, MtlsError> {
+        trust_anchor_util::cert_der_as_trust_anchor(&der)?;
+        self.roots.push(der);
+        Ok(self)
+    }
+
+    /// Pins a public key.  Once any key is pinned, certificates with other keys are
+    /// rejected even if they chain to a trusted CA
+    ///
+    /// # Arguments
+    /// * `pin` - Base64 SHA-256 hash of the DER-encoded `SubjectPublicKeyInfo`
+    pub fn pin(mut self, pin: &str) -> Result<Self, MtlsError> {
+        match base64::decode(pin) {
+            Ok(pin) if pin.len() == digest::SHA256_OUTPUT_LEN => self.pins.push(pin),
+            _ => return Err(MtlsError::InvalidPin),
+        }
+        Ok(self)
+    }
+
+    /// Validates a certificate chain at the current time, returning the client's identity
+    ///
+    /// # Arguments
+    /// * `chain` - DER-encoded end-entity certificate followed by any intermediates
+    pub fn verify(&self, chain: &[&[u8]]) -> Result<ClientIdentity, MtlsError> {
+        self.verify_at(chain, now())
+    }
+
+    /// Validates a certificate chain at a time, returning the client's identity
+    ///
+    /// # Arguments
+    /// * `chain` - DER-encoded end-entity certificate followed by any intermediates
+    /// * `time` - Seconds since the unix epoch
+    pub fn verify_at(&self, chain: &[&[u8]], time: u64) -> Result<ClientIdentity, MtlsError> {
+        let (cert, intermediates) = chain.split_first().ok_or(MtlsError::EmptyChain)?;
+
+        let anchors = self
+            .roots
+            .iter()
+            .map(|root| trust_anchor_util::cert_der_as_trust_anchor(root))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        EndEntityCert::from(cert)?.verify_is_valid_tls_client_cert(
+            SIG_ALGS,
+            &TLSClientTrustAnchors(&anchors),
+            intermediates,
+            Time::from_seconds_since_unix_epoch(time),
+        )?;
+
+        let identity = parse_identity(cert)?;
+        if !self.pins.is_empty()
+            && !self.pins.iter().any(|pin| {
+                constant_time::verify_slices_are_equal(pin, &identity.spki_sha256).is_ok()
+            })
+        {
+            return Err(MtlsError::PinMismatch);
+        }
+
+        Ok(identity)
+    }
+
+    /// Validates a certificate chain and maps the client's identity to a user
+    ///
+    /// # Arguments
+    /// * `chain` - DER-encoded end-entity certificate followed by any intermediates
+    /// * `map` - Returns the user identified by the certificate, if any
+    pub fn authenticate<U, F>(&self, chain: &[&[u8]], map: F) -> Result<U, MtlsError>
+    where
+        F: FnOnce(&ClientIdentity) -> Option<U>,
+    {
+        let identity = self.verify(chain)?;
+        map(&identity).ok_or(MtlsError::UnknownUser)
+    }
+}
+
+/// Extracts the subject, subject alternative names and public key hash of a certificate
+/// that webpki has already validated
+fn parse_identity(der: &[u8]) -> Result<ClientIdentity, MtlsError> {
+    let mut cert = Reader::new(expect(&mut Reader::new(Input::from(der)), SEQUENCE)?);
+    let mut tbs = Reader::new(expect(&mut cert, SEQUENCE)?);
+
+    // version, serial number, signature algorithm, issuer and validity
+    if tbs.peek(0xa0) {
+        read_tlv(&mut tbs)?;
+    }
+    for _ in 0..4 {
+        read_tlv(&mut tbs)?;
+    }
+
+    let mut identity = ClientIdentity {
+        subject: parse_name(expect(&mut tbs, SEQUENCE)?)?,
+        ..ClientIdentity::default()
+    };
+
+    let (spki, _) = tbs.read_partial(|r| read_tlv(r))?;
+    identity.spki_sha256 = digest::digest(&digest::SHA256, spki.as_slice_less_safe())
+        .as_ref()
+        .to_vec();
+
+    // issuer/subject unique ids and the extensions
+    while !tbs.at_end() {
+        let (tag, value) = read_tlv(&mut tbs)?;
+        if tag != 0xa3 {
+            continue;
+        }
+
+        let mut extensions = Reader::new(expect(&mut Reader::new(value), SEQUENCE)?);
+        while !extensions.at_end() {
+            let mut extension = Reader::new(expect(&mut extensions, SEQUENCE)?);
+            let oid = expect(&mut extension, OID)?;
+            if extension.peek(BOOLEAN) {
+                read_tlv(&mut extension)?;
+            }
+            let value = expect(&mut extension, OCTET_STRING)?;
+
+            if oid.as_slice_less_safe() == SUBJECT_ALT_NAME {
+                parse_alt_names(value, &mut identity)?;
+            }
+        }
+    }
+
+    Ok(identity)
+}
+
+/// Parses a `Name` into (short name or dotted OID, value) pairs
+fn parse_name(name: Input) -> Result<Vec<(String, String)>, MtlsError> {
+    let mut attributes = vec![];
+    let mut rdns = Reader::new(name);
+    while !rdns.at_end() {
+        let mut rdn = Reader::new(expect(&mut rdns, SET)?);
+        while !rdn.at_end() {
+            let mut attribute = Reader::new(expect(&mut rdn, SEQUENCE)?);
+            let oid = expect(&mut attribute, OID)?;
+            let (tag, value) = read_tlv(&mut attribute)?;
+
+            // UTF8String, PrintableString, TeletexString and IA5String
+            if let (0x0c | 0x13 | 0x14 | 0x16, Ok(value)) =
+                (tag, std::str::from_utf8(value.as_slice_less_safe()))
+            {
+                attributes.push((attribute_name(oid.as_slice_less_safe()), value.to_owned()));
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+/// Parses the `GeneralNames` of a subject alternative name extension
+fn parse_alt_names(value: Input, identity: &mut ClientIdentity) -> Result<(), MtlsError> {
+    let mut names = Reader::new(expect(&mut Reader::new(value), SEQUENCE)?);
+    while !names.at_end() {
+        let (tag, name) = read_tlv(&mut names)?;
+        let bytes = name.as_slice_less_safe();
+        let text = || {
+            std::str::from_utf8(bytes)
+                .map(str::to_owned)
+                .map_err(|_| MtlsError::Malformed("subject alternative name"))
+        };
+
+        match tag {
+            0x81 => identity.emails.push(text()?),
+            0x82 => identity.dns_names.push(text()?),
+            0x86 => identity.uris.push(text()?),
+            0x87 => match bytes.len() {
+                4 => identity
+                    .ip_addresses
+                    .push(IpAddr::from([bytes[0], bytes[1], bytes[2], bytes[3]])),
+                16 => {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(bytes);
+                    identity.ip_addresses.push(IpAddr::from(octets));
+                }
+                _ => return Err(MtlsError::Malformed("subject alternative name")),
+            },
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the short name of a well-known attribute type, or its dotted OID
+fn attribute_name(oid: &[u8]) -> String {
+    let name = match oid {
+        [0x55, 0x04, 0x03] => "CN",
+        [0x55, 0x04, 0x06] => "C",
+        [0x55, 0x04, 0x07] => "L",
+        [0x55, 0x04, 0x08] => "ST",
+        [0x55, 0x04, 0x0a] => "O",
+        [0x55, 0x04, 0x0b] => "OU",
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01] => "emailAddress",
+        _ => return dotted_oid(oid),
+    };
+    name.to_owned()
+}
+
+/// Formats a DER-encoded object identifier in dotted-decimal notation
+fn dotted_oid(oid: &[u8]) -> String {
+    let mut arcs: Vec<u64> = vec![];
+    let mut value = 0u64;
+    for b in oid {
+        value = (value << 7) | u64::from(b & 0x7f);
+        if b & 0x80 != 0 {
+            continue;
+        }
+
+        if arcs.is_empty() {
+            // the first subidentifier encodes the first two arcs
+            let first = (value / 40).min(2);
+            arcs.push(first);
+            arcs.push(value - first * 40);
+        } else {
+            arcs.push(value);
+        }
+        value = 0;
+    }
+
+    arcs.iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Reads a DER tag-length-value, returning the tag and value
+fn read_tlv<'a>(reader: &mut Reader<'a>) -> Result<(u8, Input<'a>), MtlsError> {
+    let malformed = |_| MtlsError::Malformed("truncated DER");
+    let tag = reader.read_byte().map_err(malformed)?;
+    if tag & 0x1f == 0x1f {
+        return Err(MtlsError::Malformed("unsupported DER tag"));
+    }
+
+    let len = match reader.read_byte().map_err(malformed)? {
+        len if len < 0x80 => usize::from(len),
+        0x81 => usize::from(reader.read_byte().map_err(malformed)?),
+        0x82 => {
+            let high = reader.read_byte().map_err(malformed)?;
+            let low = reader.read_byte().map_err(malformed)?;
+            usize::from(u16::from_be_bytes([high, low]))
+        }
+        _ => return Err(MtlsError::Malformed("unsupported DER length")),
+    };
+
+    let value = reader.read_bytes(len).map_err(malformed)?;
+    Ok((tag, value))
+}
+
+/// Reads a DER tag-length-value, failing if the tag isn't the expected tag
+fn expect<'a>(reader: &mut Reader<'a>, expected: u8) -> Result<Input<'a>, MtlsError> {
+    match read_tlv(reader)? {
+        (tag, value) if tag == expected => Ok(value),
+        _ => Err(MtlsError::Malformed("unexpected DER tag")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed P-256 root CA, valid 2020-2040
+    const CA: &str = "\
+        MIIBtzCCAV2gAwIBAgIUb1/AA0w3UTkemBmA5HxZ8Rl+EUcwCgYIKoZIzj0EAwIwKTEVMBMGA1UEAwwMVGVzdCBS\
+        b290IENBMRAwDgYDVQQKDAdhdXRoLXJzMB4XDTIwMDEwMTAwMDAwMFoXDTQwMDEwMTAwMDAwMFowKTEVMBMGA1UE\
+        AwwMVGVzdCBSb290IENBMRAwDgYDVQQKDAdhdXRoLXJzMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEoYXS8MEj\
+        EqVVWm5Nbcd6ICqp0wChh1/XJSI9w0dznQDzNxv0BoU3sM55mojGC066RsQ9Hr6WBJgNJ5PCF17d8aNjMGEwHQYD\
+        VR0OBBYEFIpBEVhDYkalKhxZHSkLrXqSnghyMB8GA1UdIwQYMBaAFIpBEVhDYkalKhxZHSkLrXqSnghyMA8GA1Ud\
+        EwEB/wQFMAMBAf8wDgYDVR0PAQH/BAQDAgEGMAoGCCqGSM49BAMCA0gAMEUCIBKgR8EfimqOjdFpgEii71v8QEnk\
+        psTwbu56tL3EF55zAiEA4NAr5eEQNga5FgDW/0DxMCQh6Htm13upexBsxbdbfVQ=";
+
+    /// Client certificate for `CN=alice, O=auth-rs` issued by `CA`, valid 2020-2030
+    const CLIENT: &str = "\
+        MIICAjCCAaigAwIBAgICA+gwCgYIKoZIzj0EAwIwKTEVMBMGA1UEAwwMVGVzdCBSb290IENBMRAwDgYDVQQKDAdh\
+        dXRoLXJzMB4XDTIwMDEwMTAwMDAwMFoXDTMwMDEwMTAwMDAwMFowIjEOMAwGA1UEAwwFYWxpY2UxEDAOBgNVBAoM\
+        B2F1dGgtcnMwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAARARZrzNGGpNcwIlPXPL9jK9S6OfStTXXdPyiPOtjtm\
+        cCI+NVTTy77xTd/dBCmxNDtc/78bG3CAnPkcF3PDw8BHo4HGMIHDMAkGA1UdEwQCMAAwDgYDVR0PAQH/BAQDAgeA\
+        MBMGA1UdJQQMMAoGCCsGAQUFBwMCMFEGA1UdEQRKMEiCEWFsaWNlLmV4YW1wbGUuY29tgRFhbGljZUBleGFtcGxl\
+        LmNvbYYac3BpZmZlOi8vZXhhbXBsZS5jb20vYWxpY2WHBAoAAAEwHQYDVR0OBBYEFLBUryz+7du7jLBgmJh2DYlP\
+        D9lOMB8GA1UdIwQYMBaAFIpBEVhDYkalKhxZHSkLrXqSnghyMAoGCCqGSM49BAMCA0gAMEUCIENrnGaL9zk4Vv83\
+        3Dc2Jl38k9HqWd7kfwjs6vuylAq/AiEAw+zyRhVyZY0YVE6n9hi4mcWoMyoLq//Ql0bAc9JS2CQ=";
+
+    /// SHA-256 of the client certificate's public key
+    const CLIENT_PIN: &str = "Sr41IKOBt5uWa4ZD9sqSlxyvgZbYVx/fAlU6o16FqTg=";
+
+    /// 2025-01-01T00:00:00Z
+    const NOW: u64 = 1_735_689_600;
+
+    fn verifier() -> ClientCertVerifier {
+        ClientCertVerifier::new()
+            .add_root(base64::decode(CA).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn identity_is_extracted() {
+        let client = base64::decode(CLIENT).unwrap();
+        let identity = verifier().verify_at(&[&client], NOW).unwrap();
+
+        assert_eq!(identity.common_name(), Some("alice"));
+        assert_eq!(identity.subject_attribute("O"), Some("auth-rs"));
+        assert_eq!(identity.dns_names(), ["alice.example.com"]);
+        assert_eq!(identity.emails(), ["alice@example.com"]);
+        assert_eq!(identity.uris(), ["spiffe://example.com/alice"]);
+        assert_eq!(identity.ip_addresses(), [IpAddr::from([10, 0, 0, 1])]);
+        assert_eq!(identity.public_key_pin(), CLIENT_PIN);
+    }
+
+    #[test]
+    fn chain_and_validity_are_checked() {
+        let client = base64::decode(CLIENT).unwrap();
+
+        // expired
+        assert!(matches!(
+            verifier().verify_at(&[&client], 1_900_000_000),
+            Err(MtlsError::Certificate(webpki::Error::CertExpired))
+        ));
+
+        // untrusted issuer
+        assert!(matches!(
+            ClientCertVerifier::new().verify_at(&[&client], NOW),
+            Err(MtlsError::Certificate(webpki::Error::UnknownIssuer))
+        ));
+
+        assert!(matches!(
+            verifier().verify_at(&[], NOW),
+            Err(MtlsError::EmptyChain)
+        ));
+    }
+
+    #[test]
+    fn pins_are_enforced() {
+        let client = base64::decode(CLIENT).unwrap();
+        let other = base64::encode([0u8; 32]);
+
+        verifier()
+            .pin(CLIENT_PIN)
+            .unwrap()
+            .verify_at(&[&client], NOW)
+            .unwrap();
+        assert!(matches!(
+            verifier().pin(&other).unwrap().verify_at(&[&client], NOW),
+            Err(MtlsError::PinMismatch)
+        ));
+        assert!(verifier().pin("c2hvcnQ=").is_err());
+    }
+
+    #[test]
+    fn oid_is_formatted() {
+        assert_eq!(
+            dotted_oid(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d]),
+            "1.2.840.113549"
+        );
+        assert_eq!(attribute_name(&[0x55, 0x04, 0x05]), "2.5.4.5");
+    }
+}
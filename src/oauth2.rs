@@ -0,0 +1,476 @@
+//! OAuth 2.0 authorization-code flow ([RFC 6749](https://tools.ietf.org/html/rfc6749))
+//! with PKCE ([RFC 7636](https://tools.ietf.org/html/rfc7636))
+//!
+//! [`OAuth2Client::authorize`] starts a flow: it generates a random `state` and PKCE
+//! code verifier and builds the url the user is redirected to in order to authorize the
+//! application.  The returned [`AuthorizationRequest`] is kept (e.g., in the user's
+//! session) until the provider redirects back, when [`OAuth2Client::exchange`] checks
+//! the returned `state` against it and exchanges the code, along with the verifier, for
+//! tokens.  Access tokens are renewed with [`OAuth2Client::refresh`].
+//!
+//! Devices without a browser (e.g., command line tools and TVs) use the device
+//! authorization grant instead, see [`OAuth2Client::request_device_code`].
+//!
+//! Resource servers check opaque access tokens with [`OAuth2Client::introspect`].
+//!
+//! The client is the foundation of the social-login presets (e.g., [`github`]) and can
+//! be pointed at any standards-compliant provider.
+//!
+//! [`github`]: crate::github
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::oauth2::OAuth2Client;
+//!
+//! let client = OAuth2Client::new(
+//!     "my-client-id",
+//!     "https://provider.example.com/authorize",
+//!     "https://provider.example.com/token",
+//! )
+//! .client_secret("my-client-secret")
+//! .redirect_uri("https://app.example.com/callback")
+//! .scopes(&["profile", "email"]);
+//!
+//! // store `request` in the session and redirect the user to `request.url`...
+//! let request = client.authorize()?;
+//!
+//! // ...then exchange the code they return with
+//! let token = client.exchange(&request, &params.state, &params.code).await?;
+//! let token = client.refresh(token.refresh_token.as_deref().unwrap()).await?;
+//! ```
+
+mod device;
+pub use device::*;
+
+mod introspect;
+pub use introspect::*;
+
+use rand::RngCore;
+use reqwest::{header::ACCEPT, Url};
+use ring::{constant_time, digest};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+/// Errors that may occur during an OAuth 2.0 flow
+#[derive(Debug, Error)]
+pub enum OAuth2Error {
+    /// An endpoint is not configured or is not a valid url
+    #[error("invalid endpoint url")]
+    InvalidUrl,
+
+    /// The request to the provider failed
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The `state` returned with the code does not match the authorization request
+    #[error("state mismatch")]
+    StateMismatch,
+
+    /// The provider rejected the request
+    #[error("provider returned `{error}`")]
+    Provider {
+        /// Error code, as defined in RFC 6749 section 5.2
+        error: String,
+
+        /// Human-readable description of the error, if provided
+        description: Option<String>,
+    },
+
+    /// The user denied a device authorization request
+    #[error("access denied")]
+    AccessDenied,
+
+    /// The device code expired before the user authorized the device
+    #[error("device code expired")]
+    DeviceCodeExpired,
+
+    /// The provider's response could not be parsed
+    #[error("malformed response")]
+    Malformed,
+}
+
+/// An authorization request awaiting the provider's redirect
+///
+/// The request must be kept private to the user (e.g., in a server-side or encrypted
+/// session), since its `pkce_verifier` proves the code was requested by this client.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    /// Url to redirect the user to
+    pub url: String,
+
+    /// Random value the provider returns with the code
+    pub state: String,
+
+    /// PKCE code verifier, sent with the code to the token endpoint
+    pub pkce_verifier: String,
+}
+
+/// Tokens issued by a provider's token endpoint
+#[derive(Clone, Debug, Deserialize)]
+pub struct TokenResponse {
+    /// Token used to access the provider's apis
+    pub access_token: String,
+
+    /// Type of the access token, usually `bearer`
+    pub token_type: String,
+
+    /// Lifetime of the access token, in seconds
+    pub expires_in: Option<u64>,
+
+    /// Token used to obtain new access tokens
+    pub refresh_token: Option<String>,
+
+    /// Scopes granted, if different from those requested
+    pub scope: Option<String>,
+
+    /// OpenID Connect id token, if the `openid` scope was requested
+    pub id_token: Option<String>,
+}
+
+/// Error response from a provider's endpoint
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// Body of a provider's response.  Some providers (e.g., GitHub) report errors with a
+/// success status, so the shape of the body decides the outcome
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProviderResponse<T> {
+    Success(T),
+    Error(ErrorResponse),
+}
+
+/// A client of a provider's authorization and token endpoints
+#[derive(Clone)]
+pub struct OAuth2Client {
+    client_id: String,
+    client_secret: Option<String>,
+    auth_url: String,
+    token_url: String,
+    redirect_uri: Option<String>,
+    scopes: Vec<String>,
+    device_url: Option<String>,
+    introspection_url: Option<String>,
+    http: reqwest::Client,
+}
+
+impl fmt::Debug for OAuth2Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OAuth2Client")
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .field("auth_url", &self.auth_url)
+            .field("token_url", &self.token_url)
+            .field("redirect_uri", &self.redirect_uri)
+            .field("scopes", &self.scopes)
+            .field("device_url", &self.device_url)
+            .field("introspection_url", &self.introspection_url)
+            .finish()
+    }
+}
+
+impl OAuth2Client {
+    /// Creates a new client
+    ///
+    /// # Arguments
+    /// * `client_id` - Id the provider issued to this application
+    /// * `auth_url` - Url of the provider's authorization endpoint
+    /// * `token_url` - Url of the provider's token endpoint
+    pub fn new(
+        client_id: impl Into<String>,
+        auth_url: impl Into<String>,
+        token_url: impl Into<String>,
+    ) -> OAuth2Client {
+        OAuth2Client {
+            client_id: client_id.into(),
+            client_secret: None,
+            auth_url: auth_url.into(),
+            token_url: token_url.into(),
+            redirect_uri: None,
+            scopes: Vec::new(),
+            device_url: None,
+            introspection_url: None,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Sets the secret used to authenticate to the token endpoint (confidential clients)
+    ///
+    /// # Arguments
+    /// * `secret` - Secret the provider issued to this application
+    pub fn client_secret(mut self, secret: impl Into<String>) -> Self {
+        self.client_secret = Some(secret.into());
+        self
+    }
+
+    /// Sets the url the provider redirects the user back to
+    ///
+    /// # Arguments
+    /// * `uri` - Redirect uri registered with the provider
+    pub fn redirect_uri(mut self, uri: impl Into<String>) -> Self {
+        self.redirect_uri = Some(uri.into());
+        self
+    }
+
+    /// Sets the scopes requested
+    ///
+    /// # Arguments
+    /// * `scopes` - Scopes to request
+    pub fn scopes(mut self, scopes: &[&str]) -> Self {
+        self.scopes = scopes.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Sets the url of the provider's device authorization endpoint, enabling the device
+    /// authorization grant
+    ///
+    /// # Arguments
+    /// * `url` - Url of the device authorization endpoint
+    pub fn device_authorization_url(mut self, url: impl Into<String>) -> Self {
+        self.device_url = Some(url.into());
+        self
+    }
+
+    /// Sets the url of the provider's token introspection endpoint
+    ///
+    /// # Arguments
+    /// * `url` - Url of the introspection endpoint
+    pub fn introspection_url(mut self, url: impl Into<String>) -> Self {
+        self.introspection_url = Some(url.into());
+        self
+    }
+
+    /// Starts an authorization, generating a new `state` and PKCE code verifier
+    pub fn authorize(&self) -> Result<AuthorizationRequest, OAuth2Error> {
+        let state = random_token();
+        let pkce_verifier = random_token();
+        let url = self.authorize_url(&state, &pkce_challenge(&pkce_verifier))?;
+        Ok(AuthorizationRequest {
+            url,
+            state,
+            pkce_verifier,
+        })
+    }
+
+    /// Returns the url to redirect the user to in order to authorize this application
+    ///
+    /// # Arguments
+    /// * `state` - Opaque value the provider returns with the code, to prevent CSRF
+    /// * `challenge` - S256 PKCE code challenge
+    fn authorize_url(&self, state: &str, challenge: &str) -> Result<String, OAuth2Error> {
+        let mut url = Url::parse(&self.auth_url).map_err(|_| OAuth2Error::InvalidUrl)?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("response_type", "code")
+                .append_pair("client_id", &self.client_id);
+            if let Some(uri) = &self.redirect_uri {
+                query.append_pair("redirect_uri", uri);
+            }
+            if !self.scopes.is_empty() {
+                query.append_pair("scope", &self.scopes.join(" "));
+            }
+            query
+                .append_pair("state", state)
+                .append_pair("code_challenge", challenge)
+                .append_pair("code_challenge_method", "S256");
+        }
+        Ok(url.into())
+    }
+
+    /// Checks the `state` the provider returned and exchanges the authorization code for
+    /// tokens
+    ///
+    /// # Arguments
+    /// * `request` - Request returned by `authorize()` when the flow was started
+    /// * `state` - State the provider redirected the user back with
+    /// * `code` - Code the provider redirected the user back with
+    pub async fn exchange(
+        &self,
+        request: &AuthorizationRequest,
+        state: &str,
+        code: &str,
+    ) -> Result<TokenResponse, OAuth2Error> {
+        check_state(request, state)?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("code_verifier", request.pkce_verifier.as_str()),
+        ];
+        if let Some(uri) = &self.redirect_uri {
+            form.push(("redirect_uri", uri));
+        }
+        self.request_token(form).await
+    }
+
+    /// Obtains a new access token with a refresh token
+    ///
+    /// # Arguments
+    /// * `refresh_token` - Refresh token issued with an earlier access token
+    pub async fn refresh(&self, refresh_token: &str) -> Result<TokenResponse, OAuth2Error> {
+        let form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ];
+        self.request_token(form).await
+    }
+
+    /// Posts a request to the token endpoint, authenticating as this client
+    ///
+    /// # Arguments
+    /// * `form` - Parameters of the grant
+    async fn request_token(&self, form: Vec<(&str, &str)>) -> Result<TokenResponse, OAuth2Error> {
+        let body = self.post(&self.token_url, form).await?;
+        parse_response(&body)
+    }
+
+    /// Posts a form to one of the provider's endpoints, authenticating as this client, and
+    /// returns the body of the response
+    ///
+    /// # Arguments
+    /// * `url` - Url of the endpoint
+    /// * `form` - Parameters of the request
+    async fn post(&self, url: &str, mut form: Vec<(&str, &str)>) -> Result<Vec<u8>, OAuth2Error> {
+        form.push(("client_id", &self.client_id));
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let body = self
+            .http
+            .post(url)
+            .header(ACCEPT, "application/json")
+            .form(&form)
+            .send()
+            .await?
+            .bytes()
+            .await?;
+
+        Ok(body.to_vec())
+    }
+}
+
+/// Returns 32 random bytes, base64url encoded, for use as a `state` or code verifier
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// Returns the S256 code challenge of a PKCE code verifier
+fn pkce_challenge(verifier: &str) -> String {
+    let hash = digest::digest(&digest::SHA256, verifier.as_bytes());
+    base64::encode_config(hash.as_ref(), base64::URL_SAFE_NO_PAD)
+}
+
+/// Checks the `state` returned by the provider matches the authorization request
+fn check_state(request: &AuthorizationRequest, state: &str) -> Result<(), OAuth2Error> {
+    constant_time::verify_slices_are_equal(request.state.as_bytes(), state.as_bytes())
+        .map_err(|_| OAuth2Error::StateMismatch)
+}
+
+/// Parses the body of a provider's response
+///
+/// # Arguments
+/// * `body` - JSON body of the response
+fn parse_response<T: DeserializeOwned>(body: &[u8]) -> Result<T, OAuth2Error> {
+    match serde_json::from_slice(body).map_err(|_| OAuth2Error::Malformed)? {
+        ProviderResponse::Success(value) => Ok(value),
+        ProviderResponse::Error(e) => Err(OAuth2Error::Provider {
+            error: e.error,
+            description: e.error_description,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> OAuth2Client {
+        OAuth2Client::new(
+            "client",
+            "https://provider.example.com/authorize?prompt=consent",
+            "https://provider.example.com/token",
+        )
+        .client_secret("secret")
+        .redirect_uri("https://app.example.com/callback")
+        .scopes(&["profile", "email"])
+    }
+
+    #[test]
+    fn authorize_url() {
+        let url = client().authorize_url("xyz", "challenge").unwrap();
+        assert_eq!(
+            url,
+            "https://provider.example.com/authorize?prompt=consent&response_type=code\
+             &client_id=client&redirect_uri=https%3A%2F%2Fapp.example.com%2Fcallback\
+             &scope=profile+email&state=xyz&code_challenge=challenge\
+             &code_challenge_method=S256"
+        );
+
+        let invalid = OAuth2Client::new("client", "not a url", "");
+        assert!(matches!(invalid.authorize(), Err(OAuth2Error::InvalidUrl)));
+        assert!(!format!("{:?}", client()).contains("\"secret\""));
+    }
+
+    #[test]
+    fn authorize_generates_state_and_pkce() {
+        let first = client().authorize().unwrap();
+        let second = client().authorize().unwrap();
+        assert_ne!(first.state, second.state);
+        assert_eq!(first.pkce_verifier.len(), 43);
+        assert!(first.url.contains(&format!("state={}", first.state)));
+        assert!(first.url.contains(&format!(
+            "code_challenge={}",
+            pkce_challenge(&first.pkce_verifier)
+        )));
+
+        assert!(check_state(&first, &first.state).is_ok());
+        assert!(matches!(
+            check_state(&first, &second.state),
+            Err(OAuth2Error::StateMismatch)
+        ));
+    }
+
+    #[test]
+    fn pkce_s256_challenge() {
+        // RFC 7636, appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn token_responses() {
+        let token: TokenResponse = parse_response(
+            br#"{"access_token":"gho_16C7e42F","token_type":"bearer","scope":"user:email"}"#,
+        )
+        .unwrap();
+        assert_eq!(token.access_token, "gho_16C7e42F");
+        assert_eq!(token.scope.as_deref(), Some("user:email"));
+        assert!(token.refresh_token.is_none());
+
+        let error = parse_response::<TokenResponse>(
+            br#"{"error":"bad_verification_code","error_description":"The code is incorrect"}"#,
+        );
+        assert!(matches!(
+            error,
+            Err(OAuth2Error::Provider { ref error, .. }) if error == "bad_verification_code"
+        ));
+        assert!(matches!(
+            parse_response::<TokenResponse>(b"<html>"),
+            Err(OAuth2Error::Malformed)
+        ));
+    }
+}
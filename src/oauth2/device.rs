@@ -0,0 +1,218 @@
+//! OAuth 2.0 device authorization grant ([RFC 8628](https://tools.ietf.org/html/rfc8628))
+//!
+//! The device asks the provider for a device code and a short user code, and shows the
+//! user code and verification url to the user, who authorizes the device from another
+//! browser.  Meanwhile the device polls the token endpoint until the user completes (or
+//! denies) the authorization or the code expires.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::oauth2::OAuth2Client;
+//!
+//! let client = OAuth2Client::new("my-client-id", AUTH_URL, TOKEN_URL)
+//!     .device_authorization_url("https://provider.example.com/device/code")
+//!     .scopes(&["profile"]);
+//!
+//! let authorization = client.request_device_code().await?;
+//! println!("Visit {} and enter {}", authorization.verification_uri, authorization.user_code);
+//!
+//! let token = client.wait_for_device_token(&authorization, tokio::time::sleep).await?;
+//! ```
+
+use super::{parse_response, OAuth2Client, OAuth2Error, TokenResponse};
+use serde::{Deserialize, Serialize};
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+/// Grant type of device access token requests
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Polling interval (in seconds) used when the provider does not specify one
+const DEFAULT_INTERVAL: u64 = 5;
+
+/// Seconds added to the polling interval each time the provider asks to slow down
+const SLOW_DOWN_INCREMENT: u64 = 5;
+
+/// Response of a device authorization request
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceAuthorization {
+    /// Code the device polls the token endpoint with
+    pub device_code: String,
+
+    /// Code the user enters at the verification url
+    pub user_code: String,
+
+    /// Url the user visits to authorize the device.  Google calls this field
+    /// `verification_url`
+    #[serde(alias = "verification_url")]
+    pub verification_uri: String,
+
+    /// Verification url that includes the user code, e.g., for a QR code
+    pub verification_uri_complete: Option<String>,
+
+    /// Lifetime of the codes, in seconds
+    pub expires_in: u64,
+
+    /// Minimum time between polls, in seconds
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+fn default_interval() -> u64 {
+    DEFAULT_INTERVAL
+}
+
+/// The state of a device authorization, as reported when polling the token endpoint
+#[derive(Debug)]
+pub enum DeviceTokenState {
+    /// The user has not yet completed the authorization
+    Pending,
+
+    /// The device is polling too often and must wait 5 more seconds between polls
+    SlowDown,
+
+    /// The user denied the authorization
+    Denied,
+
+    /// The device code expired
+    Expired,
+
+    /// The user authorized the device
+    Authorized(TokenResponse),
+}
+
+impl OAuth2Client {
+    /// Starts a device authorization, returning the codes to show the user
+    pub async fn request_device_code(&self) -> Result<DeviceAuthorization, OAuth2Error> {
+        let url = self.device_url.as_ref().ok_or(OAuth2Error::InvalidUrl)?;
+
+        let scope = self.scopes.join(" ");
+        let mut form = Vec::new();
+        if !scope.is_empty() {
+            form.push(("scope", scope.as_str()));
+        }
+
+        let body = self.post(url, form).await?;
+        parse_response(&body)
+    }
+
+    /// Polls the token endpoint once for the state of a device authorization
+    ///
+    /// # Arguments
+    /// * `authorization` - Authorization returned by `request_device_code()`
+    pub async fn poll_device_token(
+        &self,
+        authorization: &DeviceAuthorization,
+    ) -> Result<DeviceTokenState, OAuth2Error> {
+        let form = vec![
+            ("grant_type", DEVICE_CODE_GRANT),
+            ("device_code", authorization.device_code.as_str()),
+        ];
+        device_state(self.request_token(form).await)
+    }
+
+    /// Polls the token endpoint, at the interval the provider asks for, until the user
+    /// authorizes the device
+    ///
+    /// # Arguments
+    /// * `authorization` - Authorization returned by `request_device_code()`
+    /// * `sleep` - The async runtime's sleep function (e.g., `tokio::time::sleep`)
+    pub async fn wait_for_device_token<F, Fut>(
+        &self,
+        authorization: &DeviceAuthorization,
+        mut sleep: F,
+    ) -> Result<TokenResponse, OAuth2Error>
+    where
+        F: FnMut(Duration) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+        let mut interval = authorization.interval;
+
+        while Instant::now() < deadline {
+            sleep(Duration::from_secs(interval)).await;
+            match self.poll_device_token(authorization).await? {
+                DeviceTokenState::Pending => (),
+                DeviceTokenState::SlowDown => interval += SLOW_DOWN_INCREMENT,
+                DeviceTokenState::Denied => return Err(OAuth2Error::AccessDenied),
+                DeviceTokenState::Expired => break,
+                DeviceTokenState::Authorized(token) => return Ok(token),
+            }
+        }
+        Err(OAuth2Error::DeviceCodeExpired)
+    }
+}
+
+/// Maps the outcome of a device access token request onto the state of the authorization
+///
+/// # Arguments
+/// * `result` - Outcome of the token request
+fn device_state(
+    result: Result<TokenResponse, OAuth2Error>,
+) -> Result<DeviceTokenState, OAuth2Error> {
+    match result {
+        Ok(token) => Ok(DeviceTokenState::Authorized(token)),
+        Err(OAuth2Error::Provider { error, description }) => match error.as_str() {
+            "authorization_pending" => Ok(DeviceTokenState::Pending),
+            "slow_down" => Ok(DeviceTokenState::SlowDown),
+            "access_denied" => Ok(DeviceTokenState::Denied),
+            "expired_token" => Ok(DeviceTokenState::Expired),
+            _ => Err(OAuth2Error::Provider { error, description }),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_authorization() {
+        let authorization: DeviceAuthorization = parse_response(
+            br#"{"device_code":"GmRhmhcxhwAzkoEqiMEg_DnyEysNkuNhszIySk9eS","user_code":"WDJB-MJHT",
+                 "verification_url":"https://example.com/device","expires_in":1800}"#,
+        )
+        .unwrap();
+        assert_eq!(authorization.user_code, "WDJB-MJHT");
+        assert_eq!(authorization.verification_uri, "https://example.com/device");
+        assert_eq!(authorization.interval, DEFAULT_INTERVAL);
+    }
+
+    #[test]
+    fn device_states() {
+        let state = |error: &str| {
+            device_state(Err(OAuth2Error::Provider {
+                error: error.to_owned(),
+                description: None,
+            }))
+        };
+
+        assert!(matches!(
+            state("authorization_pending"),
+            Ok(DeviceTokenState::Pending)
+        ));
+        assert!(matches!(state("slow_down"), Ok(DeviceTokenState::SlowDown)));
+        assert!(matches!(
+            state("access_denied"),
+            Ok(DeviceTokenState::Denied)
+        ));
+        assert!(matches!(
+            state("expired_token"),
+            Ok(DeviceTokenState::Expired)
+        ));
+        assert!(matches!(
+            state("invalid_client"),
+            Err(OAuth2Error::Provider { .. })
+        ));
+
+        let token = parse_response(br#"{"access_token":"token","token_type":"bearer"}"#);
+        assert!(matches!(
+            device_state(token),
+            Ok(DeviceTokenState::Authorized(_))
+        ));
+    }
+}
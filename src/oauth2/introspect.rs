@@ -0,0 +1,158 @@
+//! OAuth 2.0 token introspection ([RFC 7662](https://tools.ietf.org/html/rfc7662))
+//!
+//! Resource servers receiving opaque access tokens (rather than JWTs they can verify
+//! themselves) ask the provider's introspection endpoint whether a token is active and,
+//! if so, who it was issued to and for which scopes.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::oauth2::OAuth2Client;
+//!
+//! let client = OAuth2Client::new("my-resource-server", AUTH_URL, TOKEN_URL)
+//!     .client_secret("my-client-secret")
+//!     .introspection_url("https://provider.example.com/introspect");
+//!
+//! let introspection = client.introspect(&bearer_token, Some("access_token")).await?;
+//! if !introspection.active || !introspection.has_scope("orders:read") {
+//!     return Err(Unauthorized);
+//! }
+//! ```
+
+use super::{parse_response, OAuth2Client, OAuth2Error};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Response of an introspection request
+///
+/// Only `active` is guaranteed to be present; an inactive token carries no other fields.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TokenIntrospection {
+    /// Whether the token is currently active (issued, not revoked and not expired)
+    pub active: bool,
+
+    /// Space-separated scopes granted to the token
+    pub scope: Option<String>,
+
+    /// Id of the client the token was issued to
+    pub client_id: Option<String>,
+
+    /// Human-readable identifier of the resource owner
+    pub username: Option<String>,
+
+    /// Type of the token, e.g., `bearer`
+    pub token_type: Option<String>,
+
+    /// When the token expires, in seconds since the epoch
+    pub exp: Option<i64>,
+
+    /// When the token was issued, in seconds since the epoch
+    pub iat: Option<i64>,
+
+    /// When the token becomes valid, in seconds since the epoch
+    pub nbf: Option<i64>,
+
+    /// Subject (usually the resource owner's id) of the token
+    pub sub: Option<String>,
+
+    /// Intended audiences of the token
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub aud: Vec<String>,
+
+    /// Issuer of the token
+    pub iss: Option<String>,
+
+    /// Unique identifier of the token
+    pub jti: Option<String>,
+}
+
+impl TokenIntrospection {
+    /// Returns the scopes granted to the token
+    pub fn scopes(&self) -> impl Iterator<Item = &str> {
+        self.scope.as_deref().unwrap_or_default().split_whitespace()
+    }
+
+    /// Returns true if the token was granted a scope
+    ///
+    /// # Arguments
+    /// * `scope` - Scope to look for
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes().any(|s| s == scope)
+    }
+}
+
+impl OAuth2Client {
+    /// Asks the provider's introspection endpoint for the state of a token,
+    /// authenticating as this client
+    ///
+    /// # Arguments
+    /// * `token` - Token to introspect
+    /// * `token_type_hint` - Type of the token (`access_token` or `refresh_token`), if known
+    pub async fn introspect(
+        &self,
+        token: &str,
+        token_type_hint: Option<&str>,
+    ) -> Result<TokenIntrospection, OAuth2Error> {
+        let url = self
+            .introspection_url
+            .as_ref()
+            .ok_or(OAuth2Error::InvalidUrl)?;
+
+        let mut form = vec![("token", token)];
+        if let Some(hint) = token_type_hint {
+            form.push(("token_type_hint", hint));
+        }
+
+        let body = self.post(url, form).await?;
+        parse_response(&body)
+    }
+}
+
+/// Deserializes a claim that is either a single string or an array of strings
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_token() {
+        let introspection: TokenIntrospection = parse_response(
+            br#"{"active":true,"client_id":"l238j323ds-23ij4","username":"jdoe",
+                 "scope":"read write dolphin","sub":"Z5O3upPC88QrAjx00dis","aud":"https://protected.example.net/resource",
+                 "iss":"https://server.example.com/","exp":1419356238,"iat":1419350238}"#,
+        )
+        .unwrap();
+        assert!(introspection.active);
+        assert_eq!(introspection.sub.as_deref(), Some("Z5O3upPC88QrAjx00dis"));
+        assert_eq!(introspection.exp, Some(1419356238));
+        assert_eq!(
+            introspection.aud,
+            ["https://protected.example.net/resource"]
+        );
+        assert!(introspection.has_scope("write"));
+        assert!(!introspection.has_scope("admin"));
+    }
+
+    #[test]
+    fn inactive_token() {
+        let introspection: TokenIntrospection = parse_response(br#"{"active":false}"#).unwrap();
+        assert!(!introspection.active);
+        assert!(introspection.aud.is_empty());
+        assert_eq!(introspection.scopes().count(), 0);
+
+        let error = parse_response::<TokenIntrospection>(br#"{"error":"invalid_client"}"#);
+        assert!(matches!(error, Err(OAuth2Error::Provider { .. })));
+    }
+}
@@ -0,0 +1,59 @@
+//! One-time passwords
+//!
+//! Short-lived codes delivered out-of-band (e.g., by email) are generated and verified by
+//! [`OneTimeCodes`].  Delivering the code is left to the caller; only a keyed hash of the
+//! code is kept in the [`OneTimeCode`] record, which the caller stores until the code is
+//! presented.  [`Delivery`] issues codes through an application-provided [`Sender`] (e.g.,
+//! an SMS gateway), rate limiting the codes sent to each destination.
+//!
+//! Codes generated by an authenticator app from a shared [`Secret`] are verified by
+//! [`Hotp`] (counter-based, RFC 4226) and [`Totp`] (time-based, RFC 6238), and enrolled
+//! in the app with a [`ProvisioningUri`].
+
+pub mod code;
+pub mod delivery;
+pub mod hotp;
+pub mod key;
+pub mod totp;
+pub mod uri;
+
+pub use self::code::{Charset, OneTimeCode, OneTimeCodes};
+pub use self::delivery::{Channel, Delivery, SendError, Sender};
+pub use self::hotp::Hotp;
+pub use self::key::{Algorithm, Secret};
+pub use self::totp::Totp;
+pub use self::uri::ProvisioningUri;
+
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum OtpError {
+    #[error("one-time code is invalid")]
+    Invalid,
+
+    #[error("one-time code has expired")]
+    Expired,
+
+    #[error("one-time code has already been used")]
+    Used,
+
+    #[error("too many attempts to verify one-time code")]
+    TooManyAttempts,
+
+    #[error("one-time password secret is not valid base32")]
+    InvalidSecret,
+
+    #[error("too many codes sent, retry after {retry_after:?}")]
+    Limited { retry_after: Duration },
+
+    #[error("rate limit backend failure: {0}")]
+    Backend(String),
+
+    #[error("failed to deliver one-time code: {0}")]
+    Delivery(String),
+
+    #[cfg(feature = "qr")]
+    #[error("failed to render QR code: {0}")]
+    Qr(String),
+}
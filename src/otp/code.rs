@@ -0,0 +1,317 @@
+//! One-time codes delivered out-of-band
+//!
+//! A code is generated for a destination (e.g., an email address) and sent by the caller.
+//! The returned [`OneTimeCode`] holds an HMAC-SHA256 of the code bound to the destination,
+//! so a leaked record can't be brute-forced without the server-side secret, along with its
+//! expiry and the number of failed attempts.  Once `max_attempts` incorrect codes have been
+//! presented the record is locked and a new code must be generated.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::otp::OneTimeCodes;
+//!
+//! let codes = OneTimeCodes::new(b"a long, random server-side secret").ttl(600);
+//!
+//! let (code, mut record) = codes.generate("user@example.com");
+//! send_email("user@example.com", &code)?;
+//!
+//! // when the code is presented, then save the updated record
+//! codes.verify(&mut record, "user@example.com", &presented)?;
+//! ```
+
+use crate::{otp::OtpError, time::now};
+use rand::Rng;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+
+/// Default number of characters in a code
+pub const DEFAULT_LENGTH: usize = 6;
+
+/// Fewest characters in a code, so codes can't be guessed within a few attempts
+pub const MIN_LENGTH: usize = 6;
+
+/// Most characters in a code
+pub const MAX_LENGTH: usize = 16;
+
+/// Default lifetime (in seconds) of a code
+pub const DEFAULT_TTL: u64 = 600;
+
+/// Default number of incorrect codes accepted before a record is locked
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// Characters a code is made of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Charset {
+    /// Digits only (`0-9`)
+    Numeric,
+
+    /// Upper-case letters and digits, excluding the easily confused `0`, `1`, `I`, `L`
+    /// and `O`
+    Alphanumeric,
+}
+
+impl Charset {
+    /// Returns the characters in this set
+    fn chars(self) -> &'static [u8] {
+        match self {
+            Charset::Numeric => b"0123456789",
+            Charset::Alphanumeric => b"23456789ABCDEFGHJKMNPQRSTUVWXYZ",
+        }
+    }
+}
+
+/// A generated code, as stored until it is presented
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct OneTimeCode {
+    /// Base64url-encoded HMAC of the destination and code
+    tag: String,
+
+    /// Time (seconds since the unix epoch) the code expires
+    expires: u64,
+
+    /// Number of incorrect codes presented
+    attempts: u32,
+
+    /// True once the code has been successfully verified
+    used: bool,
+}
+
+impl OneTimeCode {
+    /// Returns the time (seconds since the unix epoch) the code expires
+    pub fn expires(&self) -> u64 {
+        self.expires
+    }
+
+    /// Returns the number of incorrect codes presented
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Returns true if the code has been successfully verified
+    pub fn is_used(&self) -> bool {
+        self.used
+    }
+}
+
+/// Generates and verifies one-time codes
+pub struct OneTimeCodes {
+    /// Key used to hash codes
+    key: hmac::Key,
+
+    /// Number of characters in a code
+    length: usize,
+
+    /// Characters a code is made of
+    charset: Charset,
+
+    /// Lifetime, in seconds, of a code
+    ttl: u64,
+
+    /// Number of incorrect codes accepted before a record is locked
+    max_attempts: u32,
+}
+
+impl OneTimeCodes {
+    /// Creates a new generator producing six digit codes valid for ten minutes
+    ///
+    /// # Arguments
+    /// * `secret` - Server-side secret used to hash codes.  Should be at least 32 random bytes
+    pub fn new(secret: &[u8]) -> OneTimeCodes {
+        OneTimeCodes {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            length: DEFAULT_LENGTH,
+            charset: Charset::Numeric,
+            ttl: DEFAULT_TTL,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+
+    /// Sets the number of characters in a code, clamped to between [`MIN_LENGTH`] and
+    /// [`MAX_LENGTH`]
+    ///
+    /// # Arguments
+    /// * `length` - Number of characters
+    pub fn length(mut self, length: usize) -> Self {
+        self.length = length.clamp(MIN_LENGTH, MAX_LENGTH);
+        self
+    }
+
+    /// Sets the characters a code is made of
+    ///
+    /// # Arguments
+    /// * `charset` - Characters to use
+    pub fn charset(mut self, charset: Charset) -> Self {
+        self.charset = charset;
+        self
+    }
+
+    /// Sets the lifetime of a code
+    ///
+    /// # Arguments
+    /// * `ttl` - Lifetime, in seconds
+    pub fn ttl(mut self, ttl: u64) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the number of incorrect codes accepted before a record is locked
+    ///
+    /// # Arguments
+    /// * `max_attempts` - Number of incorrect codes
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Generates a code, returning the code to deliver and the record to store
+    ///
+    /// # Arguments
+    /// * `destination` - Where the code will be delivered (e.g., an email address)
+    pub fn generate(&self, destination: &str) -> (String, OneTimeCode) {
+        self.generate_at(destination, now())
+    }
+
+    /// Verifies a presented code, marking the record as used if it matches.  The record
+    /// must be saved afterwards so failed attempts and use are remembered
+    ///
+    /// # Arguments
+    /// * `record` - Record returned by `generate()`
+    /// * `destination` - Where the code was delivered
+    /// * `code` - Code presented by the user (case and surrounding whitespace are ignored)
+    pub fn verify(
+        &self,
+        record: &mut OneTimeCode,
+        destination: &str,
+        code: &str,
+    ) -> Result<(), OtpError> {
+        self.verify_at(record, destination, code, now())
+    }
+
+    fn generate_at(&self, destination: &str, now: u64) -> (String, OneTimeCode) {
+        let chars = self.charset.chars();
+        let mut rng = rand::thread_rng();
+        let code: String = (0..self.length)
+            .map(|_| chars[rng.gen_range(0, chars.len())] as char)
+            .collect();
+
+        let tag = hmac::sign(&self.key, &message(destination, &code));
+        let record = OneTimeCode {
+            tag: base64::encode_config(tag.as_ref(), base64::URL_SAFE_NO_PAD),
+            expires: now.saturating_add(self.ttl),
+            attempts: 0,
+            used: false,
+        };
+
+        (code, record)
+    }
+
+    fn verify_at(
+        &self,
+        record: &mut OneTimeCode,
+        destination: &str,
+        code: &str,
+        now: u64,
+    ) -> Result<(), OtpError> {
+        if record.used {
+            return Err(OtpError::Used);
+        }
+
+        if record.attempts >= self.max_attempts {
+            return Err(OtpError::TooManyAttempts);
+        }
+
+        if now >= record.expires {
+            return Err(OtpError::Expired);
+        }
+
+        let code = code.trim().to_ascii_uppercase();
+        let tag = base64::decode_config(&record.tag, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| OtpError::Invalid)?;
+        if hmac::verify(&self.key, &message(destination, &code), &tag).is_err() {
+            record.attempts += 1;
+            return Err(OtpError::Invalid);
+        }
+
+        record.used = true;
+        Ok(())
+    }
+}
+
+/// Builds the message authenticated for a code, length-prefixing the destination so it
+/// can't run into the code
+fn message(destination: &str, code: &str) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(8 + destination.len() + code.len());
+    msg.extend_from_slice(&(destination.len() as u64).to_be_bytes());
+    msg.extend_from_slice(destination.as_bytes());
+    msg.extend_from_slice(code.as_bytes());
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+    const EMAIL: &str = "user@example.com";
+
+    #[test]
+    fn code_is_single_use() {
+        let codes = OneTimeCodes::new(SECRET)
+            .length(8)
+            .charset(Charset::Alphanumeric);
+        let (code, mut record) = codes.generate_at(EMAIL, 1000);
+        assert_eq!(code.len(), 8);
+
+        assert_eq!(
+            codes.verify_at(&mut record, "other@example.com", &code, 1000),
+            Err(OtpError::Invalid)
+        );
+        codes
+            .verify_at(&mut record, EMAIL, &code.to_lowercase(), 1000)
+            .unwrap();
+        assert_eq!(
+            codes.verify_at(&mut record, EMAIL, &code, 1000),
+            Err(OtpError::Used)
+        );
+    }
+
+    #[test]
+    fn length_is_clamped() {
+        let codes = OneTimeCodes::new(SECRET).length(0);
+        let (code, mut record) = codes.generate_at(EMAIL, 1000);
+        assert_eq!(code.len(), MIN_LENGTH);
+        assert_eq!(
+            codes.verify_at(&mut record, EMAIL, "", 1000),
+            Err(OtpError::Invalid)
+        );
+
+        let (code, _) = OneTimeCodes::new(SECRET)
+            .length(64)
+            .generate_at(EMAIL, 1000);
+        assert_eq!(code.len(), MAX_LENGTH);
+    }
+
+    #[test]
+    fn code_expires_and_locks() {
+        let codes = OneTimeCodes::new(SECRET).ttl(60).max_attempts(2);
+        let (code, mut record) = codes.generate_at(EMAIL, 1000);
+        assert!(code.bytes().all(|b| b.is_ascii_digit()));
+        assert_eq!(
+            codes.verify_at(&mut record, EMAIL, &code, 1060),
+            Err(OtpError::Expired)
+        );
+
+        let wrong = if code == "000000" { "111111" } else { "000000" };
+        for _ in 0..2 {
+            assert_eq!(
+                codes.verify_at(&mut record, EMAIL, wrong, 1000),
+                Err(OtpError::Invalid)
+            );
+        }
+        assert_eq!(
+            codes.verify_at(&mut record, EMAIL, &code, 1000),
+            Err(OtpError::TooManyAttempts)
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Issuing one-time codes over SMS, voice and other channels
+//!
+//! [`Delivery`] ties [`OneTimeCodes`] to a [`Sender`] that transports the code (e.g., a
+//! Twilio or SNS client implemented by the application).  Before a code is generated, the
+//! destination is checked against a [`RateLimiter`] so a phone number can't be flooded
+//! with messages (or used to run up the bill); by default at most 5 codes are sent to a
+//! destination per hour.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::otp::{Channel, Delivery, OneTimeCodes, SendError, Sender};
+//!
+//! struct Twilio { /* ... */ }
+//!
+//! impl Sender for Twilio {
+//!     fn send(&self, channel: Channel, destination: &str, code: &str) -> Result<(), SendError> {
+//!         // call the Twilio API
+//!     }
+//! }
+//!
+//! let delivery = Delivery::new(OneTimeCodes::new(b"a long, random server-side secret"), twilio);
+//!
+//! let mut record = delivery.issue(Channel::Sms, "+15555550100")?;
+//!
+//! // when the code is presented, then save the updated record
+//! delivery.verify(&mut record, "+15555550100", &presented)?;
+//! ```
+
+use crate::{
+    otp::{OneTimeCode, OneTimeCodes, OtpError},
+    ratelimit::{RateLimitError, RateLimitKey, RateLimiter, SlidingWindow},
+};
+use std::{fmt, time::Duration};
+
+/// Error returned by a [`Sender`]
+pub type SendError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Default number of codes sent to a destination per window
+pub const DEFAULT_SEND_LIMIT: u32 = 5;
+
+/// Default window, in seconds, codes sent to a destination are counted in
+pub const DEFAULT_SEND_WINDOW: u64 = 60 * 60;
+
+/// How a code is delivered
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// Email message
+    Email,
+
+    /// Text message
+    Sms,
+
+    /// Voice call reading out the code
+    Voice,
+}
+
+impl Channel {
+    /// Returns the name of the channel
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Channel::Email => "email",
+            Channel::Sms => "sms",
+            Channel::Voice => "voice",
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Transports a code to its destination
+pub trait Sender: Send + Sync {
+    /// Sends a code
+    ///
+    /// # Arguments
+    /// * `channel` - How the code should be delivered
+    /// * `destination` - Where the code should be delivered (e.g., a phone number)
+    /// * `code` - The code to deliver
+    fn send(&self, channel: Channel, destination: &str, code: &str) -> Result<(), SendError>;
+}
+
+impl<F> Sender for F
+where
+    F: Fn(Channel, &str, &str) -> Result<(), SendError> + Send + Sync,
+{
+    fn send(&self, channel: Channel, destination: &str, code: &str) -> Result<(), SendError> {
+        self(channel, destination, code)
+    }
+}
+
+/// Issues one-time codes through a sender
+pub struct Delivery<S> {
+    /// Generates and verifies codes
+    codes: OneTimeCodes,
+
+    /// Transports codes
+    sender: S,
+
+    /// Limits the codes sent to a destination
+    limiter: Box<dyn RateLimiter>,
+}
+
+impl<S: Sender> Delivery<S> {
+    /// Creates a new pipeline sending at most 5 codes to a destination per hour
+    ///
+    /// # Arguments
+    /// * `codes` - Generates and verifies codes
+    /// * `sender` - Transports codes
+    pub fn new(codes: OneTimeCodes, sender: S) -> Delivery<S> {
+        Delivery {
+            codes,
+            sender,
+            limiter: Box::new(SlidingWindow::new(
+                DEFAULT_SEND_LIMIT,
+                Duration::from_secs(DEFAULT_SEND_WINDOW),
+            )),
+        }
+    }
+
+    /// Sets the rate limiter consulted before sending a code to a destination
+    ///
+    /// # Arguments
+    /// * `limiter` - Rate limiter, checked with [`RateLimitKey::Destination`]
+    pub fn limiter<L: RateLimiter + 'static>(mut self, limiter: L) -> Self {
+        self.limiter = Box::new(limiter);
+        self
+    }
+
+    /// Generates a code and sends it to a destination, returning the record to store
+    ///
+    /// # Arguments
+    /// * `channel` - How the code should be delivered
+    /// * `destination` - Where the code should be delivered (e.g., a phone number)
+    pub fn issue(&self, channel: Channel, destination: &str) -> Result<OneTimeCode, OtpError> {
+        self.limiter
+            .check(&RateLimitKey::Destination(destination.to_owned()))
+            .map_err(|e| match e {
+                RateLimitError::Limited { retry_after } => OtpError::Limited { retry_after },
+                RateLimitError::Backend(e) => OtpError::Backend(e.to_string()),
+            })?;
+
+        let (code, record) = self.codes.generate(destination);
+        self.sender
+            .send(channel, destination, &code)
+            .map_err(|e| OtpError::Delivery(e.to_string()))?;
+
+        Ok(record)
+    }
+
+    /// Verifies a presented code, marking the record as used if it matches.  The record
+    /// must be saved afterwards so failed attempts and use are remembered
+    ///
+    /// # Arguments
+    /// * `record` - Record returned by `issue()`
+    /// * `destination` - Where the code was delivered
+    /// * `code` - Code presented by the user
+    pub fn verify(
+        &self,
+        record: &mut OneTimeCode,
+        destination: &str,
+        code: &str,
+    ) -> Result<(), OtpError> {
+        self.codes.verify(record, destination, code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const PHONE: &str = "+15555550100";
+
+    #[test]
+    fn codes_are_sent_and_limited() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let outbox = Arc::clone(&sent);
+        let sender =
+            move |channel: Channel, destination: &str, code: &str| -> Result<(), SendError> {
+                outbox
+                    .lock()
+                    .unwrap()
+                    .push((channel, destination.to_owned(), code.to_owned()));
+                Ok(())
+            };
+
+        let delivery = Delivery::new(OneTimeCodes::new(b"secret"), sender)
+            .limiter(SlidingWindow::new(1, Duration::from_secs(60)));
+
+        let mut record = delivery.issue(Channel::Sms, PHONE).unwrap();
+        assert!(matches!(
+            delivery.issue(Channel::Voice, PHONE),
+            Err(OtpError::Limited { .. })
+        ));
+
+        let (channel, destination, code) = sent.lock().unwrap().remove(0);
+        assert_eq!((channel, destination.as_str()), (Channel::Sms, PHONE));
+        delivery.verify(&mut record, PHONE, &code).unwrap();
+    }
+
+    #[test]
+    fn send_failure_is_reported() {
+        let sender = |_: Channel, _: &str, _: &str| -> Result<(), SendError> {
+            Err("gateway unavailable".into())
+        };
+
+        let delivery = Delivery::new(OneTimeCodes::new(b"secret"), sender);
+        assert_eq!(
+            delivery.issue(Channel::Sms, PHONE).unwrap_err(),
+            OtpError::Delivery("gateway unavailable".into())
+        );
+    }
+}
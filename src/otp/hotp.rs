@@ -0,0 +1,229 @@
+//! Counter-based one-time passwords ([RFC 4226](https://tools.ietf.org/html/rfc4226))
+//!
+//! Both the server and the authenticator keep a counter that the authenticator advances
+//! every time a code is generated.  Since codes may be generated without being used, a
+//! code is accepted if it matches any of the next `look_ahead` counter values, and the
+//! counter to store is returned.  If the authenticator gets further ahead than that, the
+//! user can resynchronize by presenting two consecutive codes.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::otp::{Hotp, Secret};
+//!
+//! let hotp = Hotp::new(Secret::from_base32(&user.otp_secret)?);
+//!
+//! user.otp_counter = hotp.verify(&presented, user.otp_counter)?;
+//! ```
+
+use crate::otp::{
+    key::{codes_match, Algorithm, Secret, MAX_DIGITS, MIN_DIGITS},
+    uri::ProvisioningUri,
+    OtpError,
+};
+#[cfg(feature = "secrecy")]
+use secrecy::{ExposeSecret, SecretString};
+
+/// Default number of digits in a code
+pub const DEFAULT_DIGITS: u32 = 6;
+
+/// Default number of counter values checked after the stored counter
+pub const DEFAULT_LOOK_AHEAD: u64 = 10;
+
+/// Default number of counter values searched when resynchronizing
+pub const DEFAULT_RESYNC_WINDOW: u64 = 100;
+
+/// Generates and verifies counter-based one-time passwords
+#[derive(Clone, Debug)]
+pub struct Hotp {
+    /// Secret shared with the authenticator
+    secret: Secret,
+
+    /// HMAC algorithm
+    algorithm: Algorithm,
+
+    /// Number of digits in a code
+    digits: u32,
+
+    /// Number of counter values checked after the stored counter
+    look_ahead: u64,
+
+    /// Number of counter values searched when resynchronizing
+    resync_window: u64,
+}
+
+impl Hotp {
+    /// Creates a new generator producing six digit HMAC-SHA1 codes
+    ///
+    /// # Arguments
+    /// * `secret` - Secret shared with the authenticator
+    pub fn new(secret: Secret) -> Hotp {
+        Hotp {
+            secret,
+            algorithm: Algorithm::default(),
+            digits: DEFAULT_DIGITS,
+            look_ahead: DEFAULT_LOOK_AHEAD,
+            resync_window: DEFAULT_RESYNC_WINDOW,
+        }
+    }
+
+    /// Sets the HMAC algorithm
+    ///
+    /// # Arguments
+    /// * `algorithm` - HMAC algorithm
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the number of digits in a code, clamped to between [`MIN_DIGITS`] and
+    /// [`MAX_DIGITS`]
+    ///
+    /// # Arguments
+    /// * `digits` - Number of digits, usually 6 or 8
+    pub fn digits(mut self, digits: u32) -> Self {
+        self.digits = digits.clamp(MIN_DIGITS, MAX_DIGITS);
+        self
+    }
+
+    /// Sets the number of counter values checked after the stored counter
+    ///
+    /// # Arguments
+    /// * `look_ahead` - Number of counter values
+    pub fn look_ahead(mut self, look_ahead: u64) -> Self {
+        self.look_ahead = look_ahead;
+        self
+    }
+
+    /// Sets the number of counter values searched when resynchronizing
+    ///
+    /// # Arguments
+    /// * `resync_window` - Number of counter values
+    pub fn resync_window(mut self, resync_window: u64) -> Self {
+        self.resync_window = resync_window;
+        self
+    }
+
+    /// Returns the secret shared with the authenticator
+    pub fn secret(&self) -> &Secret {
+        &self.secret
+    }
+
+    /// Returns a provisioning URI for this generator's secret and parameters
+    ///
+    /// # Arguments
+    /// * `account` - Name of the account (e.g., the user's email address)
+    /// * `counter` - Initial counter value
+    pub fn provisioning_uri<S: Into<String>>(&self, account: S, counter: u64) -> ProvisioningUri {
+        ProvisioningUri::hotp(&self.secret, account, counter)
+            .algorithm(self.algorithm)
+            .digits(self.digits)
+    }
+
+    /// Generates the code for a counter value
+    ///
+    /// # Arguments
+    /// * `counter` - Counter value
+    pub fn generate(&self, counter: u64) -> String {
+        self.secret.code(self.algorithm, counter, self.digits)
+    }
+
+    /// Verifies a code, returning the counter to store for the next verification
+    ///
+    /// # Arguments
+    /// * `code` - Code presented by the user
+    /// * `counter` - Counter stored after the previous verification
+    pub fn verify(&self, code: &str, counter: u64) -> Result<u64, OtpError> {
+        self.find(code.trim(), counter, self.look_ahead)
+            .map(|matched| matched + 1)
+            .ok_or(OtpError::Invalid)
+    }
+
+    /// Verifies a code held in a `SecretString`, returning the counter to store
+    ///
+    /// # Arguments
+    /// * `code` - Code presented by the user
+    /// * `counter` - Counter stored after the previous verification
+    #[cfg(feature = "secrecy")]
+    pub fn verify_secret(&self, code: &SecretString, counter: u64) -> Result<u64, OtpError> {
+        self.verify(code.expose_secret(), counter)
+    }
+
+    /// Resynchronizes with an authenticator that has moved past the look-ahead window,
+    /// returning the counter to store.  The codes must be consecutive and within the
+    /// resynchronization window
+    ///
+    /// # Arguments
+    /// * `first` - First code presented by the user
+    /// * `second` - Code generated by the authenticator immediately after `first`
+    /// * `counter` - Counter stored after the previous verification
+    pub fn resync(&self, first: &str, second: &str, counter: u64) -> Result<u64, OtpError> {
+        let (first, second) = (first.trim(), second.trim());
+        let end = counter.saturating_add(self.resync_window);
+        (counter..end)
+            .find(|c| {
+                codes_match(&self.generate(*c), first) && codes_match(&self.generate(c + 1), second)
+            })
+            .map(|matched| matched + 2)
+            .ok_or(OtpError::Invalid)
+    }
+
+    /// Returns the first counter value in `[counter, counter + window)` producing a code
+    fn find(&self, code: &str, counter: u64, window: u64) -> Option<u64> {
+        (counter..counter.saturating_add(window)).find(|c| codes_match(&self.generate(*c), code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test values from RFC 4226, appendix D
+    const CODES: [&str; 10] = [
+        "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871",
+        "520489",
+    ];
+
+    fn hotp() -> Hotp {
+        Hotp::new(Secret::new(b"12345678901234567890".to_vec()))
+    }
+
+    #[test]
+    fn rfc4226_test_values() {
+        let hotp = hotp();
+        for (counter, code) in CODES.iter().enumerate() {
+            assert_eq!(hotp.generate(counter as u64), *code);
+        }
+    }
+
+    #[test]
+    fn verify_within_look_ahead() {
+        let hotp = hotp().look_ahead(3);
+        assert_eq!(hotp.verify(CODES[2], 0), Ok(3));
+        assert_eq!(hotp.verify(CODES[2], 3), Err(OtpError::Invalid));
+        assert_eq!(hotp.verify(CODES[6], 3), Err(OtpError::Invalid));
+    }
+
+    #[test]
+    fn resync_requires_consecutive_codes() {
+        let hotp = hotp().look_ahead(1).resync_window(10);
+        assert_eq!(hotp.resync(CODES[7], CODES[8], 0), Ok(9));
+        assert_eq!(hotp.resync(CODES[7], CODES[9], 0), Err(OtpError::Invalid));
+        assert_eq!(
+            hotp.resync_window(5).resync(CODES[7], CODES[8], 0),
+            Err(OtpError::Invalid)
+        );
+    }
+
+    #[test]
+    fn digits_are_clamped() {
+        assert_eq!(hotp().digits(1).generate(0), CODES[0]);
+        assert_eq!(hotp().digits(10).generate(0), "1284755224");
+        assert_eq!(hotp().digits(u32::MAX).generate(0), "1284755224");
+        assert!(hotp()
+            .digits(0)
+            .provisioning_uri("user", 0)
+            .to_string()
+            .contains("&digits=6&"));
+    }
+}
@@ -0,0 +1,196 @@
+//! Shared secrets for HOTP and TOTP
+//!
+//! A [`Secret`] is shared with the user's authenticator app, usually as a base32 string
+//! (RFC 4648, without padding).  Both [`Hotp`](crate::otp::Hotp) and
+//! [`Totp`](crate::otp::Totp) derive codes from it with the HMAC-based truncation of
+//! [RFC 4226](https://tools.ietf.org/html/rfc4226#section-5.3).
+
+use crate::otp::OtpError;
+use rand::RngCore;
+use ring::{constant_time, hmac};
+use std::fmt;
+
+/// RFC 4648 base32 alphabet
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Length, in bytes, of a generated secret (160 bits, as recommended by RFC 4226)
+pub const SECRET_LEN: usize = 20;
+
+/// Fewest digits in a code (RFC 4226, section 5.3)
+pub const MIN_DIGITS: u32 = 6;
+
+/// Most digits in a code: the truncated HMAC is a 31-bit value, so longer codes would
+/// only be padded with zeros
+pub const MAX_DIGITS: u32 = 10;
+
+/// HMAC algorithm used to derive codes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// HMAC-SHA1, the default and the only algorithm supported by most authenticator apps
+    #[default]
+    Sha1,
+
+    /// HMAC-SHA256
+    Sha256,
+
+    /// HMAC-SHA512
+    Sha512,
+}
+
+impl Algorithm {
+    /// Returns the name of the algorithm as used in `otpauth://` URIs
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+        }
+    }
+
+    fn hmac(self) -> hmac::Algorithm {
+        match self {
+            Algorithm::Sha1 => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            Algorithm::Sha256 => hmac::HMAC_SHA256,
+            Algorithm::Sha512 => hmac::HMAC_SHA512,
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A secret shared with an authenticator
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(Vec<u8>);
+
+impl Secret {
+    /// Creates a secret from raw bytes
+    ///
+    /// # Arguments
+    /// * `bytes` - The secret
+    pub fn new(bytes: Vec<u8>) -> Secret {
+        Secret(bytes)
+    }
+
+    /// Generates a new, random secret
+    pub fn generate() -> Secret {
+        let mut bytes = vec![0; SECRET_LEN];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Secret(bytes)
+    }
+
+    /// Decodes a base32-encoded secret.  Case, spaces and padding are ignored
+    ///
+    /// # Arguments
+    /// * `encoded` - The base32-encoded secret
+    pub fn from_base32(encoded: &str) -> Result<Secret, OtpError> {
+        let mut bytes = Vec::with_capacity(encoded.len() * 5 / 8);
+        let (mut buffer, mut bits) = (0u32, 0u32);
+        for c in encoded.bytes().filter(|c| !matches!(c, b' ' | b'=')) {
+            let c = c.to_ascii_uppercase();
+            let value = BASE32
+                .iter()
+                .position(|b| *b == c)
+                .ok_or(OtpError::InvalidSecret)?;
+
+            buffer = (buffer << 5) | value as u32;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((buffer >> bits) as u8);
+                buffer &= (1 << bits) - 1;
+            }
+        }
+
+        if bytes.is_empty() {
+            return Err(OtpError::InvalidSecret);
+        }
+
+        Ok(Secret(bytes))
+    }
+
+    /// Encodes the secret as base32, without padding
+    pub fn to_base32(&self) -> String {
+        let mut encoded = String::with_capacity((self.0.len() * 8).div_ceil(5));
+        let (mut buffer, mut bits) = (0u32, 0u32);
+        for b in &self.0 {
+            buffer = (buffer << 8) | *b as u32;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                encoded.push(BASE32[(buffer >> bits) as usize & 0x1f] as char);
+            }
+            buffer &= (1 << bits) - 1;
+        }
+
+        if bits > 0 {
+            encoded.push(BASE32[(buffer << (5 - bits)) as usize & 0x1f] as char);
+        }
+
+        encoded
+    }
+
+    /// Returns the raw bytes of the secret
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Derives the code for a counter value (RFC 4226, section 5.3)
+    ///
+    /// # Arguments
+    /// * `algorithm` - HMAC algorithm
+    /// * `counter` - Counter (HOTP) or time step (TOTP)
+    /// * `digits` - Number of digits in the code, clamped to `MIN_DIGITS..=MAX_DIGITS`
+    pub(crate) fn code(&self, algorithm: Algorithm, counter: u64, digits: u32) -> String {
+        let digits = digits.clamp(MIN_DIGITS, MAX_DIGITS);
+        let key = hmac::Key::new(algorithm.hmac(), &self.0);
+        let tag = hmac::sign(&key, &counter.to_be_bytes());
+        let tag = tag.as_ref();
+
+        let offset = (tag[tag.len() - 1] & 0x0f) as usize;
+        let value = u32::from_be_bytes([
+            tag[offset],
+            tag[offset + 1],
+            tag[offset + 2],
+            tag[offset + 3],
+        ]) & 0x7fff_ffff;
+
+        format!(
+            "{:0width$}",
+            value as u64 % 10u64.pow(digits),
+            width = digits as usize
+        )
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Secret").field(&"<redacted>").finish()
+    }
+}
+
+/// Compares a presented code with an expected code in constant time
+pub(crate) fn codes_match(expected: &str, presented: &str) -> bool {
+    constant_time::verify_slices_are_equal(expected.as_bytes(), presented.as_bytes()).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base32_round_trip() {
+        let secret = Secret::new(b"12345678901234567890".to_vec());
+        assert_eq!(secret.to_base32(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(
+            Secret::from_base32("gezd gnbv gy3t qojq gezd gnbv gy3t qojq").unwrap(),
+            secret
+        );
+        assert_eq!(Secret::new(b"f".to_vec()).to_base32(), "MY");
+        assert_eq!(Secret::from_base32("MY======").unwrap().as_bytes(), b"f");
+        assert!(Secret::from_base32("GEZ1").is_err());
+    }
+}
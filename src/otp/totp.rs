@@ -0,0 +1,212 @@
+//! Time-based one-time passwords ([RFC 6238](https://tools.ietf.org/html/rfc6238))
+//!
+//! TOTP is HOTP with the counter replaced by the number of `period`s elapsed since the
+//! unix epoch.  To tolerate clock drift, codes for up to `skew` periods before or after
+//! the current period are accepted.  [`Totp::verify`] returns the matched time step; storing
+//! it and passing it back on the next verification prevents a code from being replayed.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::otp::{Secret, Totp};
+//!
+//! let totp = Totp::new(Secret::from_base32(&user.otp_secret)?);
+//!
+//! user.otp_last_step = Some(totp.verify(&presented, user.otp_last_step)?);
+//! ```
+
+use crate::{
+    otp::{
+        key::{codes_match, Algorithm, Secret, MAX_DIGITS, MIN_DIGITS},
+        uri::ProvisioningUri,
+        OtpError,
+    },
+    time::now,
+};
+#[cfg(feature = "secrecy")]
+use secrecy::{ExposeSecret, SecretString};
+
+/// Default number of digits in a code
+pub const DEFAULT_DIGITS: u32 = 6;
+
+/// Default length, in seconds, of a time step
+pub const DEFAULT_PERIOD: u64 = 30;
+
+/// Default number of time steps accepted either side of the current time step
+pub const DEFAULT_SKEW: u64 = 1;
+
+/// Generates and verifies time-based one-time passwords
+#[derive(Clone, Debug)]
+pub struct Totp {
+    /// Secret shared with the authenticator
+    secret: Secret,
+
+    /// HMAC algorithm
+    algorithm: Algorithm,
+
+    /// Number of digits in a code
+    digits: u32,
+
+    /// Length, in seconds, of a time step
+    period: u64,
+
+    /// Number of time steps accepted either side of the current time step
+    skew: u64,
+}
+
+impl Totp {
+    /// Creates a new generator producing six digit HMAC-SHA1 codes every 30 seconds
+    ///
+    /// # Arguments
+    /// * `secret` - Secret shared with the authenticator
+    pub fn new(secret: Secret) -> Totp {
+        Totp {
+            secret,
+            algorithm: Algorithm::default(),
+            digits: DEFAULT_DIGITS,
+            period: DEFAULT_PERIOD,
+            skew: DEFAULT_SKEW,
+        }
+    }
+
+    /// Sets the HMAC algorithm
+    ///
+    /// # Arguments
+    /// * `algorithm` - HMAC algorithm
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the number of digits in a code, clamped to between [`MIN_DIGITS`] and
+    /// [`MAX_DIGITS`]
+    ///
+    /// # Arguments
+    /// * `digits` - Number of digits, usually 6 or 8
+    pub fn digits(mut self, digits: u32) -> Self {
+        self.digits = digits.clamp(MIN_DIGITS, MAX_DIGITS);
+        self
+    }
+
+    /// Sets the length of a time step
+    ///
+    /// # Arguments
+    /// * `period` - Length, in seconds
+    pub fn period(mut self, period: u64) -> Self {
+        self.period = period.max(1);
+        self
+    }
+
+    /// Sets the number of time steps accepted either side of the current time step
+    ///
+    /// # Arguments
+    /// * `skew` - Number of time steps
+    pub fn skew(mut self, skew: u64) -> Self {
+        self.skew = skew;
+        self
+    }
+
+    /// Returns the secret shared with the authenticator
+    pub fn secret(&self) -> &Secret {
+        &self.secret
+    }
+
+    /// Returns a provisioning URI for this generator's secret and parameters
+    ///
+    /// # Arguments
+    /// * `account` - Name of the account (e.g., the user's email address)
+    pub fn provisioning_uri<S: Into<String>>(&self, account: S) -> ProvisioningUri {
+        ProvisioningUri::totp(&self.secret, account, self.period)
+            .algorithm(self.algorithm)
+            .digits(self.digits)
+    }
+
+    /// Generates the code for the current time
+    pub fn generate(&self) -> String {
+        self.generate_at(now())
+    }
+
+    /// Generates the code for a time
+    ///
+    /// # Arguments
+    /// * `time` - Seconds since the unix epoch
+    pub fn generate_at(&self, time: u64) -> String {
+        self.secret
+            .code(self.algorithm, time / self.period, self.digits)
+    }
+
+    /// Verifies a code against the current time, returning the matched time step
+    ///
+    /// # Arguments
+    /// * `code` - Code presented by the user
+    /// * `last` - Time step returned by the previous verification, if any.  Codes for this
+    ///   or earlier time steps are rejected
+    pub fn verify(&self, code: &str, last: Option<u64>) -> Result<u64, OtpError> {
+        self.verify_at(code, last, now())
+    }
+
+    /// Verifies a code held in a `SecretString` against the current time
+    ///
+    /// # Arguments
+    /// * `code` - Code presented by the user
+    /// * `last` - Time step returned by the previous verification, if any
+    #[cfg(feature = "secrecy")]
+    pub fn verify_secret(&self, code: &SecretString, last: Option<u64>) -> Result<u64, OtpError> {
+        self.verify(code.expose_secret(), last)
+    }
+
+    /// Verifies a code against a time, returning the matched time step
+    ///
+    /// # Arguments
+    /// * `code` - Code presented by the user
+    /// * `last` - Time step returned by the previous verification, if any
+    /// * `time` - Seconds since the unix epoch
+    pub fn verify_at(&self, code: &str, last: Option<u64>, time: u64) -> Result<u64, OtpError> {
+        let code = code.trim();
+        let step = time / self.period;
+        let start = step.saturating_sub(self.skew);
+        let start = last.map_or(start, |last| start.max(last.saturating_add(1)));
+
+        (start..=step.saturating_add(self.skew))
+            .find(|s| codes_match(&self.secret.code(self.algorithm, *s, self.digits), code))
+            .ok_or(OtpError::Invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc6238_test_values() {
+        let sha1 = Totp::new(Secret::new(b"12345678901234567890".to_vec())).digits(8);
+        assert_eq!(sha1.generate_at(59), "94287082");
+        assert_eq!(sha1.generate_at(1111111109), "07081804");
+
+        let sha256 = Totp::new(Secret::new(b"12345678901234567890123456789012".to_vec()))
+            .algorithm(Algorithm::Sha256)
+            .digits(8);
+        assert_eq!(sha256.generate_at(59), "46119246");
+
+        let sha512 = Totp::new(Secret::new(
+            b"1234567890123456789012345678901234567890123456789012345678901234".to_vec(),
+        ))
+        .algorithm(Algorithm::Sha512)
+        .digits(8);
+        assert_eq!(sha512.generate_at(59), "90693936");
+    }
+
+    #[test]
+    fn verify_allows_skew_and_rejects_replay() {
+        let totp = Totp::new(Secret::generate());
+        let code = totp.generate_at(1000);
+        let step = 1000 / DEFAULT_PERIOD;
+
+        assert_eq!(totp.verify_at(&code, None, 1030), Ok(step));
+        assert_eq!(
+            totp.verify_at(&code, Some(step), 1030),
+            Err(OtpError::Invalid)
+        );
+        assert!(totp.verify_at(&code, None, 1100).is_err());
+    }
+}
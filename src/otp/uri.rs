@@ -0,0 +1,208 @@
+//! `otpauth://` provisioning URIs
+//!
+//! Authenticator apps enroll a secret by scanning a QR code of an `otpauth://` URI in the
+//! [Key URI Format](https://github.com/google/google-authenticator/wiki/Key-Uri-Format).
+//! [`ProvisioningUri`] builds the URI from a [`Totp`](crate::otp::Totp) or
+//! [`Hotp`](crate::otp::Hotp) so the parameters match the ones used for verification.
+//! With the `qr` feature enabled the URI can also be rendered as an SVG QR code.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::otp::{Secret, Totp};
+//!
+//! let totp = Totp::new(Secret::generate());
+//! let uri = totp.provisioning_uri("alice@example.com").issuer("Example");
+//!
+//! // otpauth://totp/Example:alice%40example.com?secret=...&issuer=Example&...
+//! let link = uri.to_string();
+//! let svg = uri.qr_svg()?;
+//! ```
+
+use crate::otp::{
+    key::{MAX_DIGITS, MIN_DIGITS},
+    Algorithm, Secret,
+};
+use std::fmt;
+
+#[cfg(feature = "qr")]
+use crate::otp::OtpError;
+
+/// Kind of one-time password a URI provisions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// Time-based, with the period in seconds
+    Totp(u64),
+
+    /// Counter-based, with the initial counter
+    Hotp(u64),
+}
+
+/// Builds an `otpauth://` URI
+#[derive(Clone, Debug)]
+pub struct ProvisioningUri {
+    /// Kind of one-time password
+    kind: Kind,
+
+    /// Base32-encoded secret
+    secret: String,
+
+    /// Name of the account the secret belongs to
+    account: String,
+
+    /// Name of the service the account belongs to
+    issuer: Option<String>,
+
+    /// HMAC algorithm
+    algorithm: Algorithm,
+
+    /// Number of digits in a code
+    digits: u32,
+}
+
+impl ProvisioningUri {
+    /// Creates a URI for a time-based one-time password
+    ///
+    /// # Arguments
+    /// * `secret` - Secret shared with the authenticator
+    /// * `account` - Name of the account (e.g., the user's email address)
+    /// * `period` - Length, in seconds, of a time step
+    pub fn totp<S: Into<String>>(secret: &Secret, account: S, period: u64) -> ProvisioningUri {
+        ProvisioningUri::new(Kind::Totp(period), secret, account.into())
+    }
+
+    /// Creates a URI for a counter-based one-time password
+    ///
+    /// # Arguments
+    /// * `secret` - Secret shared with the authenticator
+    /// * `account` - Name of the account (e.g., the user's email address)
+    /// * `counter` - Initial counter value
+    pub fn hotp<S: Into<String>>(secret: &Secret, account: S, counter: u64) -> ProvisioningUri {
+        ProvisioningUri::new(Kind::Hotp(counter), secret, account.into())
+    }
+
+    fn new(kind: Kind, secret: &Secret, account: String) -> ProvisioningUri {
+        ProvisioningUri {
+            kind,
+            secret: secret.to_base32(),
+            account,
+            issuer: None,
+            algorithm: Algorithm::default(),
+            digits: crate::otp::totp::DEFAULT_DIGITS,
+        }
+    }
+
+    /// Sets the name of the service the account belongs to, shown by the authenticator
+    ///
+    /// # Arguments
+    /// * `issuer` - Name of the service
+    pub fn issuer<S: Into<String>>(mut self, issuer: S) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Sets the HMAC algorithm
+    ///
+    /// # Arguments
+    /// * `algorithm` - HMAC algorithm
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the number of digits in a code, clamped to between [`MIN_DIGITS`] and
+    /// [`MAX_DIGITS`]
+    ///
+    /// # Arguments
+    /// * `digits` - Number of digits
+    pub fn digits(mut self, digits: u32) -> Self {
+        self.digits = digits.clamp(MIN_DIGITS, MAX_DIGITS);
+        self
+    }
+
+    /// Renders the URI as an SVG QR code
+    #[cfg(feature = "qr")]
+    pub fn qr_svg(&self) -> Result<String, OtpError> {
+        use qrcode::{render::svg, QrCode};
+
+        let code = QrCode::new(self.to_string()).map_err(|e| OtpError::Qr(e.to_string()))?;
+        Ok(code.render::<svg::Color>().min_dimensions(200, 200).build())
+    }
+}
+
+impl fmt::Display for ProvisioningUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            Kind::Totp(_) => "totp",
+            Kind::Hotp(_) => "hotp",
+        };
+
+        write!(f, "otpauth://{}/", kind)?;
+        if let Some(issuer) = &self.issuer {
+            write!(f, "{}:", encode(issuer))?;
+        }
+        write!(f, "{}?secret={}", encode(&self.account), self.secret)?;
+
+        if let Some(issuer) = &self.issuer {
+            write!(f, "&issuer={}", encode(issuer))?;
+        }
+        write!(f, "&algorithm={}&digits={}", self.algorithm, self.digits)?;
+
+        match self.kind {
+            Kind::Totp(period) => write!(f, "&period={}", period),
+            Kind::Hotp(counter) => write!(f, "&counter={}", counter),
+        }
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986
+fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::otp::{Hotp, Totp};
+
+    #[test]
+    fn totp_uri() {
+        let totp = Totp::new(Secret::new(b"12345678901234567890".to_vec()))
+            .algorithm(Algorithm::Sha256)
+            .digits(8)
+            .period(60);
+        let uri = totp.provisioning_uri("alice@example.com").issuer("ACME Co");
+
+        assert_eq!(
+            uri.to_string(),
+            "otpauth://totp/ACME%20Co:alice%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+             &issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60"
+        );
+    }
+
+    #[test]
+    fn hotp_uri() {
+        let hotp = Hotp::new(Secret::new(b"12345678901234567890".to_vec()));
+        assert_eq!(
+            hotp.provisioning_uri("alice", 5).to_string(),
+            "otpauth://hotp/alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+             &algorithm=SHA1&digits=6&counter=5"
+        );
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn qr_svg() {
+        let uri = ProvisioningUri::totp(&Secret::generate(), "alice", 30);
+        assert!(uri.qr_svg().unwrap().contains("<svg"));
+    }
+}
@@ -1,6 +1,6 @@
-//! Support for deserializing different types of fields
+//! Support for (de)serializing different types of fields
 
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serializer};
 
 /// Deserializes an optional string, returning `None` of the string is empty
 /// instead of `Some("")`
@@ -13,14 +13,14 @@ pub fn optional_str<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D
     Ok(o.filter(|s| !s.is_empty()))
 }
 
+/// Deserializes an optional base64-encoded string (in either alphabet, see
+/// [`decode_base64`]), returning `None` if the string is empty
 #[allow(dead_code)]
 pub fn optional_base64<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
     let o: Option<String> = Option::deserialize(d)?;
     Ok(match o {
         Some(enc) if enc.is_empty() => None,
-        Some(enc) => {
-            Some(base64::decode_config(&enc, base64::STANDARD).map_err(de::Error::custom)?)
-        }
+        Some(enc) => Some(decode_base64(&enc).map_err(de::Error::custom)?),
         None => None,
     })
 }
@@ -29,12 +29,49 @@ pub fn optional_base64<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>
 #[allow(dead_code)]
 pub fn base64url<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
     let s: String = String::deserialize(d)?;
-    Ok(base64::decode_config(&s, base64::URL_SAFE_NO_PAD).map_err(de::Error::custom)?)
+    base64::decode_config(&s, base64::URL_SAFE_NO_PAD).map_err(de::Error::custom)
 }
 
-/// Deserializes a base64url-enocded string into the underlying bytes
+/// Deserializes a base64-encoded string (in either alphabet, see [`decode_base64`]) into
+/// the underlying bytes
 #[allow(dead_code)]
 pub fn base64<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
     let s: String = String::deserialize(d)?;
-    Ok(base64::decode_config(&s, base64::STANDARD).map_err(de::Error::custom)?)
+    decode_base64(&s).map_err(de::Error::custom)
+}
+
+/// Decodes base64 in the standard or the url-safe alphabet, with or without padding.
+/// Browsers and frontend libraries (e.g., @simplewebauthn/browser, webauthn-json)
+/// disagree on the encoding of binary fields
+///
+/// # Arguments
+/// * `s` - Base64-encoded string
+#[allow(dead_code)]
+pub fn decode_base64(s: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    let s: String = s
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    base64::decode_config(&s, base64::STANDARD_NO_PAD)
+}
+
+/// Serializes bytes as a base64-encoded string, the inverse of [`base64`]
+#[allow(dead_code)]
+pub fn to_base64<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&base64::encode_config(bytes, base64::STANDARD))
+}
+
+/// Serializes optional bytes as a base64-encoded string or `null`, the inverse of
+/// [`optional_base64`]
+#[allow(dead_code)]
+pub fn to_optional_base64<S: Serializer>(bytes: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+    match bytes {
+        Some(bytes) => to_base64(bytes, s),
+        None => s.serialize_none(),
+    }
 }
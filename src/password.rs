@@ -1,69 +1,824 @@
 //! Password based authentication using argon2
+//!
+//! New passwords are hashed with argon2, but [`Hasher::verify`] detects the algorithm of
+//! a stored hash from its prefix, so databases holding bcrypt, scrypt or PBKDF2 hashes
+//! from an earlier system keep verifying.
+//!
+//! [`HashParams`] decodes the algorithm and parameters of a stored hash, e.g., to audit a
+//! password database.
+//!
+//! [`Hasher::calibrate`] benchmarks the host to pick argon2 parameters hitting a target
+//! hash duration.
+//!
+//! [`Hasher::balloon`] hashes with Balloon-SHA256 instead, for requirements that rule
+//! out argon2.
+//!
+//! Argon2 is computed by `rust-argon2` (the `rust-argon2` feature) or by the pure-Rust
+//! RustCrypto `argon2` crate (the `argon2-rustcrypto` feature).  Exactly one of them must
+//! be enabled alongside `password`.  Both produce and accept the same hashes, and
+//! [`Argon2Params`] describes them for either.
+//!
+//! # Breaking changes
+//!
+//! `password` no longer pulls in `rust-argon2` on its own: enable `rust-argon2` to keep
+//! the previous backend.  `Hasher` is a struct rather than an enum, so build it with
+//! [`Hasher::argon2`] (the deprecated [`Hasher::Argon2`] is kept for existing callers),
+//! and [`Variant`] is this crate's own type rather than a re-export of `rust-argon2`'s.
+//!
+//! Unsalted MD5, SHA-1 or SHA-256 digests can be hardened in place with
+//! [`Hasher::wrap_legacy`], without waiting for their users to log in.
+//!
+//! [`Hasher::pepper`] mixes rotatable server-side secrets into new hashes.
+//!
+//! [`Hasher::check_history`] refuses passwords matching one of a user's recent passwords,
+//! fetched through the [`PasswordHistory`] trait.
+//!
+//! With the `breach` feature, new passwords can be checked against the Have I Been Pwned
+//! corpus, online or from an offline filter (see [`PwnedPasswords`] and [`BreachFilter`]).
 
-use argon2::{self, Config};
+mod balloon;
+mod bcrypt;
+#[cfg(feature = "breach")]
+mod breach;
+mod calibrate;
+mod config;
+#[cfg(feature = "breach")]
+pub use breach::*;
+pub use config::{Argon2Params, UnknownVariant, Variant, Version};
+mod history;
+pub use history::*;
+mod legacy;
+pub use legacy::LegacyDigest;
+mod params;
+pub use params::HashParams;
+mod pbkdf2;
+mod pepper;
+mod phc;
+#[cfg(all(feature = "rust-argon2", not(feature = "argon2-rustcrypto")))]
+mod rust_argon2;
+#[cfg(feature = "argon2-rustcrypto")]
+mod rustcrypto;
+mod scrypt;
+
+use crate::events::{self, AuthEvent};
 use rand::RngCore;
-use std::default::Default;
+#[cfg(all(feature = "rust-argon2", not(feature = "argon2-rustcrypto")))]
+use rust_argon2::{hash_encoded, verify_encoded};
+#[cfg(feature = "argon2-rustcrypto")]
+use rustcrypto::{hash_encoded, verify_encoded};
+#[cfg(feature = "secrecy")]
+use secrecy::{ExposeSecret, Secret, Zeroize};
+use std::{collections::BTreeMap, default::Default, fmt, sync::Arc};
 use thiserror::Error;
 
-// Re-export error type for use downstream
-pub use argon2::Variant;
+#[cfg(all(feature = "rust-argon2", feature = "argon2-rustcrypto"))]
+compile_error!("the `rust-argon2` and `argon2-rustcrypto` features are mutually exclusive");
+
+#[cfg(not(any(feature = "rust-argon2", feature = "argon2-rustcrypto")))]
+compile_error!(
+    "the `password` feature needs an argon2 backend: enable `rust-argon2` or `argon2-rustcrypto`"
+);
 
 #[derive(Error, Debug)]
 pub enum HasherError {
     #[error("password validation failed")]
     ValidationFailed,
 
+    #[cfg(all(feature = "rust-argon2", not(feature = "argon2-rustcrypto")))]
     #[error("argon2 backend failure: {0}")]
     Argon2(#[from] argon2::Error),
+
+    #[cfg(feature = "argon2-rustcrypto")]
+    #[error("argon2 backend failure: {0}")]
+    RustCrypto(argon2_rustcrypto::Error),
+
+    #[error("unrecognized hash format")]
+    UnknownFormat,
+
+    #[error("malformed {0} hash")]
+    MalformedHash(HashAlgorithm),
+
+    #[error("hash uses unknown pepper {0}")]
+    UnknownPepper(u32),
+
+    #[error("password was used recently")]
+    PasswordReused,
+
+    #[error("password history store failure: {0}")]
+    Store(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl HasherError {
+    /// Wraps an error returned by a password history store
+    ///
+    /// # Arguments
+    /// * `e` - The underlying store error
+    pub fn store<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> HasherError {
+        HasherError::Store(e.into())
+    }
+}
+
+/// Algorithms of the hashes [`Hasher::verify`] recognizes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// `$argon2i$`, `$argon2d$` and `$argon2id$`
+    Argon2,
+
+    /// `$balloon-sha256$`
+    Balloon,
+
+    /// `$2a$`, `$2b$` and `$2y$`
+    Bcrypt,
+
+    /// `$scrypt$`
+    Scrypt,
+
+    /// `$pbkdf2-sha256$`
+    Pbkdf2Sha256,
+
+    /// `$wrap-md5$`, `$wrap-sha1$` and `$wrap-sha256$`, a legacy digest wrapped by
+    /// [`Hasher::wrap_legacy`]
+    Wrapped(LegacyDigest),
+}
+
+impl HashAlgorithm {
+    /// Detects the algorithm of a stored hash from its prefix
+    ///
+    /// # Arguments
+    /// * `hash` - Stored hash
+    pub fn detect(hash: &str) -> Option<HashAlgorithm> {
+        let id = hash.strip_prefix('$')?.split('$').next()?;
+        match id {
+            "argon2i" | "argon2d" | "argon2id" => Some(HashAlgorithm::Argon2),
+            "balloon-sha256" => Some(HashAlgorithm::Balloon),
+            "2a" | "2b" | "2y" => Some(HashAlgorithm::Bcrypt),
+            "scrypt" => Some(HashAlgorithm::Scrypt),
+            "pbkdf2-sha256" => Some(HashAlgorithm::Pbkdf2Sha256),
+            _ => LegacyDigest::from_name(id.strip_prefix("wrap-")?).map(HashAlgorithm::Wrapped),
+        }
+    }
+
+    /// Returns the algorithm's name
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Argon2 => "argon2",
+            HashAlgorithm::Balloon => "balloon-sha256",
+            HashAlgorithm::Bcrypt => "bcrypt",
+            HashAlgorithm::Scrypt => "scrypt",
+            HashAlgorithm::Pbkdf2Sha256 => "pbkdf2-sha256",
+            HashAlgorithm::Wrapped(LegacyDigest::Md5) => "wrap-md5",
+            HashAlgorithm::Wrapped(LegacyDigest::Sha1) => "wrap-sha1",
+            HashAlgorithm::Wrapped(LegacyDigest::Sha256) => "wrap-sha256",
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Length, in bytes, of the salts generated by default
+const DEFAULT_SALT_LENGTH: usize = 16;
+
+/// Generates the salts of new hashes
+///
+/// The default source fills salts from `rand::thread_rng()`.  Implement this to draw
+/// salts from an approved generator (e.g., a FIPS-validated DRBG or an HSM) or, in tests,
+/// to make hashes deterministic.
+pub trait SaltSource: Send + Sync {
+    /// Fills `salt` with random bytes
+    fn fill(&self, salt: &mut [u8]);
+}
+
+impl<F> SaltSource for F
+where
+    F: Fn(&mut [u8]) + Send + Sync,
+{
+    fn fill(&self, salt: &mut [u8]) {
+        self(salt)
+    }
+}
+
+/// Salt source drawing from `rand::thread_rng()`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadRngSalt;
+
+impl SaltSource for ThreadRngSalt {
+    fn fill(&self, salt: &mut [u8]) {
+        rand::thread_rng().fill_bytes(salt);
+    }
 }
 
-pub enum Hasher {
-    Argon2(Config<'static>),
+#[derive(Clone)]
+enum Backend {
+    Argon2(Argon2Params),
+    Balloon(balloon::Params),
+}
+
+#[derive(Clone)]
+pub struct Hasher {
+    backend: Backend,
+    salt_length: usize,
+    salt_source: Arc<dyn SaltSource>,
+    peppers: BTreeMap<u32, ring::hmac::Key>,
 }
 
 impl Hasher {
-    pub fn new(lanes: u32, memory: u32, passes: u32, variant: Variant) -> Self {
-        let mut argon = Config::default();
-        argon.lanes = lanes;
-        argon.mem_cost = memory;
-        argon.time_cost = passes;
-        argon.variant = variant;
-        Hasher::Argon2(argon)
+    #[deprecated(note = "start from a preset (e.g., `Hasher::owasp_default()`) and adjust it")]
+    pub fn new(lanes: u32, memory: u32, passes: u32, variant: impl Into<Variant>) -> Self {
+        Self::argon2(Argon2Params::default())
+            .lanes(lanes)
+            .memory(memory)
+            .passes(passes)
+            .variant(variant)
+    }
+
+    /// Argon2id with 19 MiB of memory, 2 passes and 1 lane, the minimum recommended by
+    /// the [OWASP Password Storage Cheat
+    /// Sheet](https://cheatsheetseries.owasp.org/cheatsheets/Password_Storage_Cheat_Sheet.html)
+    pub fn owasp_default() -> Self {
+        Self::argon2(Argon2Params::default())
+            .variant(Variant::Argon2id)
+            .memory(19 * 1024)
+            .passes(2)
+            .lanes(1)
+    }
+
+    /// Argon2id with 64 MiB of memory, 2 passes and 1 lane, suited to interactive logins
+    /// (libsodium's `INTERACTIVE` limits)
+    pub fn interactive() -> Self {
+        Self::owasp_default().memory(64 * 1024)
+    }
+
+    /// Argon2id with 1 GiB of memory, 4 passes and 1 lane, for rarely used, highly
+    /// sensitive secrets such as the passphrase of a key store (libsodium's `SENSITIVE`
+    /// limits).  Hashing takes seconds
+    pub fn sensitive() -> Self {
+        Self::owasp_default().memory(1024 * 1024).passes(4)
+    }
+
+    /// Creates a hasher producing argon2 hashes with a full set of argon2 parameters
+    ///
+    /// # Arguments
+    /// * `params` - Variant, version and costs of new hashes, or a `rust-argon2` `Config`
+    pub fn argon2(params: impl Into<Argon2Params>) -> Self {
+        Hasher {
+            backend: Backend::Argon2(params.into()),
+            salt_length: DEFAULT_SALT_LENGTH,
+            salt_source: Arc::new(ThreadRngSalt),
+            peppers: BTreeMap::new(),
+        }
+    }
+
+    /// Creates an argon2 hasher, as the former `Hasher::Argon2` enum variant did
+    ///
+    /// # Arguments
+    /// * `params` - Variant, version and costs of new hashes, or a `rust-argon2` `Config`
+    #[deprecated(note = "use `Hasher::argon2()`")]
+    #[allow(non_snake_case)]
+    pub fn Argon2(params: impl Into<Argon2Params>) -> Self {
+        Self::argon2(params)
+    }
+
+    /// Creates a hasher producing Balloon-SHA256 hashes, with 1 MiB of memory and 3
+    /// passes.  Adjust the costs with `memory()` and `passes()`
+    pub fn balloon() -> Self {
+        Hasher {
+            backend: Backend::Balloon(balloon::Params {
+                space: (1024 * 1024 / balloon::BLOCK_LEN) as u32,
+                time: 3,
+            }),
+            salt_length: DEFAULT_SALT_LENGTH,
+            salt_source: Arc::new(ThreadRngSalt),
+            peppers: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the argon2 variant.  Argon2id is recommended.  Ignored by Balloon hashers
+    ///
+    /// # Arguments
+    /// * `variant` - Argon2 variant
+    pub fn variant(mut self, variant: impl Into<Variant>) -> Self {
+        if let Backend::Argon2(params) = &mut self.backend {
+            params.variant = variant.into();
+        }
+        self
+    }
+
+    /// Sets the memory used to hash a password
+    ///
+    /// # Arguments
+    /// * `kib` - Memory, in KiB
+    pub fn memory(mut self, kib: u32) -> Self {
+        match &mut self.backend {
+            Backend::Argon2(params) => params.memory = kib,
+            Backend::Balloon(params) => {
+                params.space = (kib.saturating_mul(1024) / balloon::BLOCK_LEN as u32).max(1)
+            }
+        }
+        self
+    }
+
+    /// Sets the number of passes over the memory
+    ///
+    /// # Arguments
+    /// * `passes` - Number of passes (argon2's time cost)
+    pub fn passes(mut self, passes: u32) -> Self {
+        match &mut self.backend {
+            Backend::Argon2(params) => params.passes = passes,
+            Backend::Balloon(params) => params.time = passes.max(1),
+        }
+        self
+    }
+
+    /// Sets the degree of parallelism.  Ignored by Balloon hashers, which use one lane
+    ///
+    /// # Arguments
+    /// * `lanes` - Number of lanes
+    pub fn lanes(mut self, lanes: u32) -> Self {
+        if let Backend::Argon2(params) = &mut self.backend {
+            params.lanes = lanes;
+        }
+        self
+    }
+
+    /// Sets the length of generated salts.  Defaults to 16 bytes; argon2 requires at
+    /// least 8
+    ///
+    /// # Arguments
+    /// * `length` - Length of salts, in bytes
+    pub fn salt_length(mut self, length: usize) -> Self {
+        self.salt_length = length;
+        self
+    }
+
+    /// Sets where salts are drawn from.  Defaults to `rand::thread_rng()`
+    ///
+    /// # Arguments
+    /// * `source` - Generator of salts
+    pub fn salt_source(mut self, source: impl SaltSource + 'static) -> Self {
+        self.salt_source = Arc::new(source);
+        self
     }
 
     pub fn hash<S: AsRef<str>>(&self, password: S) -> Result<String, HasherError> {
-        match self {
-            Hasher::Argon2(cfg) => {
-                // use a 16-byte salt
-                let mut salt = [0u8; 16];
-                rand::thread_rng().fill_bytes(&mut salt);
+        self.hash_bytes(password.as_ref().as_bytes())
+    }
+
+    /// Hashes a password held in a `secrecy` container (e.g., `SecretString` or
+    /// `SecretVec<u8>`), without exposing it to the caller
+    ///
+    /// # Arguments
+    /// * `password` - Password to hash
+    #[cfg(feature = "secrecy")]
+    pub fn hash_secret<T>(&self, password: &Secret<T>) -> Result<String, HasherError>
+    where
+        T: Zeroize + AsRef<[u8]>,
+    {
+        self.hash_bytes(password.expose_secret().as_ref())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "password.hash",
+            skip_all,
+            fields(alg = tracing::field::Empty),
+            err
+        )
+    )]
+    fn hash_bytes(&self, password: &[u8]) -> Result<String, HasherError> {
+        let mut salt = vec![0u8; self.salt_length];
+        self.salt_source.fill(&mut salt);
 
-                let hashed = argon2::hash_encoded(password.as_ref().as_bytes(), &salt, cfg)?;
-                Ok(hashed)
+        #[cfg(feature = "metrics")]
+        let start = std::time::Instant::now();
+
+        let peppered;
+        let (prefix, password) = match self.current_pepper() {
+            Some((id, key)) => {
+                peppered = pepper::apply(key, password);
+                (pepper::prefix(id), peppered.as_slice())
+            }
+            None => (String::new(), password),
+        };
+
+        let hashed = match &self.backend {
+            Backend::Argon2(params) => {
+                trace_record!("alg", HashAlgorithm::Argon2);
+                hash_encoded(password, &salt, params)?
             }
+            Backend::Balloon(params) => {
+                trace_record!("alg", HashAlgorithm::Balloon);
+                balloon::hash(password, &salt, *params)
+            }
+        };
+
+        metric_histogram!("auth_password_hash_seconds", start.elapsed().as_secs_f64());
+        events::emit(AuthEvent::PasswordHashed);
+        Ok(prefix + &hashed)
+    }
+
+    /// Returns true if a stored hash was not produced with this hasher's algorithm and
+    /// parameters, and should be replaced the next time the user logs in
+    ///
+    /// # Arguments
+    /// * `hash` - Stored hash
+    pub fn needs_rehash(&self, hash: impl AsRef<str>) -> bool {
+        let (pepper, hash) = match pepper::split(hash.as_ref()) {
+            Some((id, hash)) => (Some(id), hash),
+            None => (None, hash.as_ref()),
+        };
+        if pepper != self.current_pepper().map(|(id, _)| id) {
+            return true;
+        }
+
+        match &self.backend {
+            Backend::Argon2(params) => !argon2_matches(params, self.salt_length, hash),
+            Backend::Balloon(params) => !balloon::matches(*params, self.salt_length, hash),
+        }
+    }
+
+    /// Verifies a password against a stored hash and, if it matches but the hash
+    /// `needs_rehash()`, returns a new hash of the password to store in its place
+    ///
+    /// # Arguments
+    /// * `password` - Password to verify
+    /// * `hash` - Stored hash
+    pub fn verify_and_maybe_rehash<S, H>(
+        &self,
+        password: S,
+        hash: H,
+    ) -> Result<Option<String>, HasherError>
+    where
+        S: AsRef<str>,
+        H: AsRef<str>,
+    {
+        self.verify(password.as_ref(), hash.as_ref())?;
+        if self.needs_rehash(hash) {
+            self.hash(password).map(Some)
+        } else {
+            Ok(None)
         }
     }
 
+    /// Verifies a password against a stored hash, dispatching on the hash's algorithm
+    /// rather than this hasher's configuration
+    ///
+    /// # Arguments
+    /// * `password` - Password to verify
+    /// * `hash` - Stored hash, in the PHC string (or bcrypt's modular crypt) format
     pub fn verify<S, H>(&self, password: S, hash: H) -> Result<(), HasherError>
     where
         S: AsRef<str>,
         H: AsRef<str>,
     {
-        match self {
-            Hasher::Argon2(_) => {
-                let result = argon2::verify_encoded(hash.as_ref(), password.as_ref().as_bytes())?;
-                if result {
-                    Ok(())
-                } else {
-                    Err(HasherError::ValidationFailed)
-                }
-            }
+        self.verify_bytes(password.as_ref().as_bytes(), hash.as_ref())
+    }
+
+    /// Verifies a password held in a `secrecy` container (e.g., `SecretString` or
+    /// `SecretVec<u8>`) against a stored hash, without exposing it to the caller
+    ///
+    /// # Arguments
+    /// * `password` - Password to verify
+    /// * `hash` - Stored hash
+    #[cfg(feature = "secrecy")]
+    pub fn verify_secret<T, H>(&self, password: &Secret<T>, hash: H) -> Result<(), HasherError>
+    where
+        T: Zeroize + AsRef<[u8]>,
+        H: AsRef<str>,
+    {
+        self.verify_bytes(password.expose_secret().as_ref(), hash.as_ref())
+    }
+
+    /// Verifies a fixed password against a fake hash with this hasher's parameters,
+    /// taking as long as a real verification.  Call it when a login names a user that
+    /// does not exist, so the response time doesn't reveal which usernames are taken
+    pub fn verify_dummy(&self) {
+        let _ = self.matches(b"dummy password", &self.dummy_hash());
+    }
+
+    /// Returns a hash in this hasher's format and parameters that no password matches
+    fn dummy_hash(&self) -> String {
+        let salt = base64::encode_config(vec![0u8; self.salt_length], base64::STANDARD_NO_PAD);
+        let encode = |len| base64::encode_config(vec![0u8; len], base64::STANDARD_NO_PAD);
+        let prefix = self
+            .current_pepper()
+            .map_or_else(String::new, |(id, _)| pepper::prefix(id));
+
+        let hash = match &self.backend {
+            Backend::Argon2(params) => format!(
+                "${}$v={}$m={},t={},p={}${}${}",
+                params.variant.as_lowercase_str(),
+                params.version.as_u32(),
+                params.memory,
+                params.passes,
+                params.lanes,
+                salt,
+                encode(params.hash_length as usize),
+            ),
+            Backend::Balloon(params) => format!(
+                "$balloon-sha256$s={},t={}${}${}",
+                params.space,
+                params.time,
+                salt,
+                encode(balloon::BLOCK_LEN),
+            ),
+        };
+        prefix + &hash
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "password.verify",
+            skip_all,
+            fields(alg = tracing::field::Empty, outcome = tracing::field::Empty)
+        )
+    )]
+    fn verify_bytes(&self, password: &[u8], hash: &str) -> Result<(), HasherError> {
+        trace_record!(
+            "alg",
+            HashAlgorithm::detect(pepper::split(hash).map_or(hash, |(_, hash)| hash))
+                .map_or("unknown", |algorithm| algorithm.name())
+        );
+
+        if self.matches(password, hash)? {
+            trace_record!("outcome", "success");
+            metric_counter!("auth_password_verifications_total", "outcome" => "success");
+            events::emit(AuthEvent::PasswordVerified);
+            Ok(())
+        } else {
+            trace_record!("outcome", "failure");
+            metric_counter!("auth_password_verifications_total", "outcome" => "failure");
+            events::emit(AuthEvent::PasswordVerifyFailed);
+            Err(HasherError::ValidationFailed)
+        }
+    }
+}
+
+impl Hasher {
+    /// Returns true if a password matches a stored hash of any recognized algorithm,
+    /// peppered with any of this hasher's peppers
+    ///
+    /// # Arguments
+    /// * `password` - Password to check
+    /// * `hash` - Stored hash
+    fn matches(&self, password: &[u8], hash: &str) -> Result<bool, HasherError> {
+        if let Some((id, hash)) = pepper::split(hash) {
+            return self.matches(&self.apply_pepper(id, password)?, hash);
+        }
+
+        match HashAlgorithm::detect(hash).ok_or(HasherError::UnknownFormat)? {
+            HashAlgorithm::Argon2 => Ok(verify_encoded(hash, password)?),
+            HashAlgorithm::Balloon => balloon::verify(password, hash),
+            HashAlgorithm::Bcrypt => bcrypt::verify(password, hash),
+            HashAlgorithm::Scrypt => scrypt::verify(password, hash),
+            HashAlgorithm::Pbkdf2Sha256 => pbkdf2::verify(password, hash),
+            HashAlgorithm::Wrapped(digest) => legacy::verify(self, digest, password, hash),
         }
     }
 }
 
+/// Returns true if a stored hash is an argon2 hash produced with a set of argon2
+/// parameters and salt length
+///
+/// # Arguments
+/// * `params` - Parameters hashes should be produced with
+/// * `salt_length` - Length of the salts of new hashes
+/// * `hash` - Stored hash
+fn argon2_matches(params: &Argon2Params, salt_length: usize, hash: &str) -> bool {
+    let expected = HashParams {
+        algorithm: HashAlgorithm::Argon2,
+        variant: Some(params.variant),
+        version: Some(params.version.as_u32()),
+        memory: Some(params.memory),
+        iterations: Some(params.passes.into()),
+        parallelism: Some(params.lanes),
+        salt_length,
+        hash_length: params.hash_length as usize,
+        pepper: None,
+    };
+    HashParams::parse(hash).ok() == Some(expected)
+}
+
 impl Default for Hasher {
+    /// Argon2i with `rust-argon2`'s default parameters or, with the `argon2-rustcrypto`
+    /// feature, Argon2id
     fn default() -> Self {
-        Hasher::Argon2(Config::default())
+        if cfg!(feature = "argon2-rustcrypto") {
+            Self::argon2(Argon2Params::default())
+        } else {
+            Self::argon2(Argon2Params::default()).variant(Variant::Argon2i)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cheap hasher, so tests run quickly
+    fn test_hasher() -> Hasher {
+        Hasher::owasp_default().memory(1024).passes(1)
+    }
+
+    #[test]
+    fn presets() {
+        let config = |hasher: Hasher| match hasher.backend {
+            Backend::Argon2(params) => (params.variant, params.memory, params.passes, params.lanes),
+            Backend::Balloon(_) => unreachable!(),
+        };
+        assert_eq!(
+            config(Hasher::owasp_default()),
+            (Variant::Argon2id, 19456, 2, 1)
+        );
+        assert_eq!(
+            config(Hasher::interactive()),
+            (Variant::Argon2id, 65536, 2, 1)
+        );
+        assert_eq!(
+            config(Hasher::sensitive()),
+            (Variant::Argon2id, 1048576, 4, 1)
+        );
+        assert_eq!(
+            config(test_hasher().lanes(4)),
+            (Variant::Argon2id, 1024, 1, 4)
+        );
+    }
+
+    #[test]
+    fn default_keeps_backend_variant() {
+        let variant = match Hasher::default().backend {
+            Backend::Argon2(params) => params.variant,
+            Backend::Balloon(_) => unreachable!(),
+        };
+        if cfg!(feature = "argon2-rustcrypto") {
+            assert_eq!(variant, Variant::Argon2id);
+        } else {
+            assert_eq!(variant, Variant::Argon2i);
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_constructors() {
+        let hasher = Hasher::Argon2(Argon2Params::default())
+            .memory(1024)
+            .passes(1);
+        let hash = hasher.hash("hunter2").unwrap();
+        assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+        assert!(hasher.verify("hunter2", &hash).is_ok());
+
+        let hasher = Hasher::new(1, 1024, 1, Variant::Argon2i);
+        assert!(hasher.hash("hunter2").unwrap().starts_with("$argon2i$"));
+    }
+
+    #[test]
+    fn known_answer() {
+        // both backends must produce this hash, so either verifies the other's hashes
+        let hash = "$argon2id$v=19$m=1024,t=1,p=1$c2FsdHlzYWx0eXNhbHR5IQ$\
+                    PlBS82a76ds8zBsxpiCZG1qYBIfeqZ402NTO3G9gFFU";
+        let hasher =
+            test_hasher().salt_source(|salt: &mut [u8]| salt.copy_from_slice(b"saltysaltysalty!"));
+        assert_eq!(hasher.hash("hunter2").unwrap(), hash);
+        assert!(hasher.verify("hunter2", hash).is_ok());
+    }
+
+    #[test]
+    fn balloon() {
+        let hasher = Hasher::balloon().memory(16).passes(1);
+        let hash = hasher.hash("hunter2").unwrap();
+        assert!(hash.starts_with("$balloon-sha256$s=512,t=1$"));
+        assert!(hasher.verify("hunter2", &hash).is_ok());
+        assert!(hasher.verify("hunter3", &hash).is_err());
+
+        // switching algorithm upgrades hashes, in both directions
+        assert!(!hasher.needs_rehash(&hash));
+        assert!(test_hasher().needs_rehash(&hash));
+        assert!(hasher.needs_rehash(test_hasher().hash("hunter2").unwrap()));
+        assert!(test_hasher().verify("hunter2", &hash).is_ok());
+    }
+
+    #[cfg(feature = "secrecy")]
+    #[test]
+    fn secret() {
+        use secrecy::{SecretString, SecretVec};
+
+        let hasher = test_hasher();
+        let hash = hasher
+            .hash_secret(&SecretString::new("hunter2".into()))
+            .unwrap();
+        assert!(hasher
+            .verify_secret(&SecretVec::new(b"hunter2".to_vec()), &hash)
+            .is_ok());
+        assert!(hasher.verify("hunter2", &hash).is_ok());
+    }
+
+    #[test]
+    fn dummy() {
+        let hashers = [
+            test_hasher(),
+            test_hasher().pepper(1, "pepper"),
+            Hasher::balloon().memory(16),
+        ];
+        for hasher in hashers.iter() {
+            // the dummy has the hasher's own parameters, so verifying it costs the same
+            let dummy = hasher.dummy_hash();
+            assert!(!hasher.needs_rehash(&dummy));
+            assert!(!hasher.matches(b"dummy password", &dummy).unwrap());
+            hasher.verify_dummy();
+        }
+    }
+
+    #[test]
+    fn detect_algorithm() {
+        let detect = HashAlgorithm::detect;
+        assert_eq!(
+            detect("$argon2id$v=19$m=4096,t=3,p=1$c2FsdA$aGFzaA"),
+            Some(HashAlgorithm::Argon2)
+        );
+        assert_eq!(
+            detect("$2y$10$N9qo8uLOickgx2ZMRZoMyeIjZAgcfl7p92ldGxad68LJZdL17lhWy"),
+            Some(HashAlgorithm::Bcrypt)
+        );
+        assert_eq!(
+            detect("$scrypt$ln=4,r=8,p=1$c2FsdA$aGFzaA"),
+            Some(HashAlgorithm::Scrypt)
+        );
+        assert_eq!(
+            detect("$pbkdf2-sha256$1000$c2FsdA$aGFzaA"),
+            Some(HashAlgorithm::Pbkdf2Sha256)
+        );
+        assert_eq!(detect("$1$saltsalt$hash"), None);
+        assert_eq!(detect("5f4dcc3b5aa765d61d8327deb882cf99"), None);
+    }
+
+    #[test]
+    fn rehash() {
+        let old = test_hasher().variant(Variant::Argon2i);
+        let hasher = test_hasher().memory(2048).passes(2);
+
+        let hash = hasher.hash("hunter2").unwrap();
+        assert!(!hasher.needs_rehash(&hash));
+        assert_eq!(
+            hasher.verify_and_maybe_rehash("hunter2", &hash).unwrap(),
+            None
+        );
+
+        let hash = old.hash("hunter2").unwrap();
+        assert!(hasher.needs_rehash(&hash));
+        let upgraded = hasher
+            .verify_and_maybe_rehash("hunter2", &hash)
+            .unwrap()
+            .unwrap();
+        assert!(!hasher.needs_rehash(&upgraded));
+        assert!(hasher.verify("hunter2", &upgraded).is_ok());
+        assert!(hasher.verify_and_maybe_rehash("hunter3", &hash).is_err());
+
+        let bcrypt = "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW";
+        assert!(hasher.needs_rehash(bcrypt));
+        assert!(hasher.needs_rehash("garbage"));
+    }
+
+    #[test]
+    fn salt() {
+        let hasher = test_hasher()
+            .salt_length(32)
+            .salt_source(|salt: &mut [u8]| salt.iter_mut().for_each(|b| *b = 7));
+
+        // a fixed salt makes hashes deterministic
+        let hash = hasher.hash("hunter2").unwrap();
+        assert_eq!(hash, hasher.hash("hunter2").unwrap());
+        assert!(hash.contains(&base64::encode_config([7u8; 32], base64::STANDARD_NO_PAD)));
+        assert!(hasher.verify("hunter2", &hash).is_ok());
+
+        // hashes with salts of another length are upgraded
+        assert!(!hasher.needs_rehash(&hash));
+        let short = test_hasher().hash("hunter2").unwrap();
+        assert!(hasher.needs_rehash(short));
+    }
+
+    #[test]
+    fn verify_mixed_hashes() {
+        let hasher = test_hasher();
+        let hashes = [
+            hasher.hash("U*U").unwrap(),
+            "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW".to_owned(),
+        ];
+        for hash in hashes.iter() {
+            assert!(hasher.verify("U*U", hash).is_ok());
+            assert!(matches!(
+                hasher.verify("U*V", hash),
+                Err(HasherError::ValidationFailed)
+            ));
+        }
+
+        assert!(matches!(
+            hasher.verify("U*U", "5f4dcc3b5aa765d61d8327deb882cf99"),
+            Err(HasherError::UnknownFormat)
+        ));
     }
 }
@@ -0,0 +1,178 @@
+//! Hashes and verifies Balloon hashes
+//!
+//! [Balloon hashing](https://eprint.iacr.org/2016/027) is a memory-hard function built
+//! only from a standard hash (here SHA-256), for deployments that must avoid Argon2.
+//! Hashes use the PHC string format:
+//! `$balloon-sha256$s=<space cost>,t=<time cost>$<salt>$<hash>`, with the space cost
+//! counted in 32-byte blocks.
+
+use super::{phc, HashAlgorithm, HasherError};
+use ring::{constant_time, digest};
+use std::convert::TryInto;
+
+/// Identifier of Balloon hashes in the PHC string format
+const ID: &str = "balloon-sha256";
+
+/// Size of a block, the output of SHA-256
+pub(super) const BLOCK_LEN: usize = 32;
+
+/// Number of pseudorandomly chosen blocks mixed into each block per round
+const DELTA: u64 = 3;
+
+/// Largest amount of memory (in bytes) a stored hash may make verification allocate
+const MAX_MEMORY: usize = 1 << 30;
+
+/// Costs of a Balloon hash
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Params {
+    /// Number of blocks in the buffer
+    pub space: u32,
+
+    /// Number of rounds of mixing over the buffer
+    pub time: u32,
+}
+
+/// Hashes a password, returning the hash in the PHC string format
+///
+/// # Arguments
+/// * `password` - Password to hash
+/// * `salt` - Salt of the hash
+/// * `params` - Costs to hash with
+pub(super) fn hash(password: &[u8], salt: &[u8], params: Params) -> String {
+    let hash = balloon(password, salt, params);
+    format!(
+        "${}$s={},t={}${}${}",
+        ID,
+        params.space,
+        params.time,
+        base64::encode_config(salt, base64::STANDARD_NO_PAD),
+        base64::encode_config(hash, base64::STANDARD_NO_PAD),
+    )
+}
+
+/// Checks a password against a Balloon hash
+///
+/// # Arguments
+/// * `password` - Password to check
+/// * `hash` - Stored hash
+pub(super) fn verify(password: &[u8], hash: &str) -> Result<bool, HasherError> {
+    let malformed = || HasherError::MalformedHash(HashAlgorithm::Balloon);
+
+    let phc = phc::PhcString::parse(hash).ok_or_else(malformed)?;
+    let params = parse_params(&phc).ok_or_else(malformed)?;
+    let salt = phc::decode(phc.salt).ok_or_else(malformed)?;
+    let expected = phc::decode(phc.hash).ok_or_else(malformed)?;
+
+    // reject parameters that would exhaust memory before allocating anything
+    if params.space as usize > MAX_MEMORY / BLOCK_LEN || expected.len() != BLOCK_LEN {
+        return Err(malformed());
+    }
+
+    let actual = balloon(password, &salt, params);
+    Ok(constant_time::verify_slices_are_equal(&actual, &expected).is_ok())
+}
+
+/// Returns true if a stored hash is a Balloon hash produced with some costs and salt
+/// length
+///
+/// # Arguments
+/// * `params` - Costs hashes should be produced with
+/// * `salt_length` - Length of the salts of new hashes
+/// * `hash` - Stored hash
+pub(super) fn matches(params: Params, salt_length: usize, hash: &str) -> bool {
+    let phc = match phc::PhcString::parse(hash) {
+        Some(phc) => phc,
+        None => return false,
+    };
+
+    parse_params(&phc) == Some(params)
+        && phc::decode(phc.salt).map(|salt| salt.len()) == Some(salt_length)
+}
+
+/// Reads the costs of a Balloon PHC string
+fn parse_params(phc: &phc::PhcString) -> Option<Params> {
+    let param = |name| phc.param(name)?.parse().ok().filter(|cost| *cost > 0);
+    if phc.id != ID || phc.version.is_some() {
+        return None;
+    }
+
+    Some(Params {
+        space: param("s")?,
+        time: param("t")?,
+    })
+}
+
+/// Computes Balloon-SHA256
+///
+/// # Arguments
+/// * `password` - Password to hash
+/// * `salt` - Salt
+/// * `params` - Space and time costs, each at least 1
+fn balloon(password: &[u8], salt: &[u8], params: Params) -> [u8; BLOCK_LEN] {
+    let space = params.space.max(1) as usize;
+    let mut counter: u64 = 0;
+    let mut hash = |parts: &[&[u8]]| -> [u8; BLOCK_LEN] {
+        let mut ctx = digest::Context::new(&digest::SHA256);
+        ctx.update(&counter.to_le_bytes());
+        counter += 1;
+        for part in parts {
+            ctx.update(part);
+        }
+        ctx.finish().as_ref().try_into().unwrap()
+    };
+
+    // expand the password into the buffer
+    let mut buf = vec![[0u8; BLOCK_LEN]; space];
+    buf[0] = hash(&[password, salt]);
+    for m in 1..space {
+        buf[m] = hash(&[&buf[m - 1]]);
+    }
+
+    // mix each block with its predecessor and with pseudorandomly chosen blocks
+    for t in 0..u64::from(params.time.max(1)) {
+        for m in 0..space {
+            let prev = buf[(m + space - 1) % space];
+            buf[m] = hash(&[&prev, &buf[m]]);
+
+            for i in 0..DELTA {
+                let index = digest::digest(
+                    &digest::SHA256,
+                    &[t.to_le_bytes(), (m as u64).to_le_bytes(), i.to_le_bytes()].concat(),
+                );
+                let other = hash(&[salt, index.as_ref()]);
+                let other = u64::from_le_bytes(other[..8].try_into().unwrap()) % space as u64;
+                buf[m] = hash(&[&buf[m], &buf[other as usize]]);
+            }
+        }
+    }
+
+    buf[space - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_hash() {
+        let params = Params { space: 64, time: 2 };
+        let hash = hash(b"hunter2", b"saltysaltysalty!", params);
+        assert!(hash.starts_with("$balloon-sha256$s=64,t=2$"));
+        assert!(verify(b"hunter2", &hash).unwrap());
+        assert!(!verify(b"hunter3", &hash).unwrap());
+
+        assert!(matches(params, 16, &hash));
+        assert!(!matches(
+            Params {
+                space: 128,
+                time: 2
+            },
+            16,
+            &hash
+        ));
+        assert!(!matches(params, 32, &hash));
+
+        assert!(verify(b"hunter2", "$balloon-sha256$s=0,t=1$c2FsdA$aGFzaA").is_err());
+        assert!(verify(b"hunter2", "$balloon-sha256$s=4294967295,t=1$c2FsdA$aGFzaA").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Verifies bcrypt (`$2a$`, `$2b$` and `$2y$`) hashes
+//!
+//! Modular crypt format: `$2b$<cost>$<22 character salt><31 character hash>`, encoded
+//! with bcrypt's own base64 alphabet.  The hashing itself is done by the `bcrypt` crate.
+
+use super::{HashAlgorithm, HasherError};
+use ::bcrypt::HashParts;
+
+/// Checks a password against a bcrypt hash
+///
+/// # Arguments
+/// * `password` - Password to check
+/// * `hash` - Stored hash
+pub(super) fn verify(password: &[u8], hash: &str) -> Result<bool, HasherError> {
+    let malformed = || HasherError::MalformedHash(HashAlgorithm::Bcrypt);
+
+    cost(hash).ok_or_else(malformed)?;
+    ::bcrypt::verify(password, hash).map_err(|_| malformed())
+}
+
+/// Reads the cost of a bcrypt hash, or `None` if the hash is malformed
+///
+/// # Arguments
+/// * `hash` - Stored hash
+pub(super) fn cost(hash: &str) -> Option<u32> {
+    let prefix = hash.get(..4)?;
+    if !matches!(prefix, "$2a$" | "$2b$" | "$2y$") {
+        return None;
+    }
+
+    hash.parse::<HashParts>()
+        .ok()
+        .map(|parts| parts.get_cost())
+        .filter(|cost| (4..=31).contains(cost))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_hashes() {
+        let vectors = [
+            (
+                "",
+                "$2a$06$DCq7YPn5Rq63x1Lad4cll.TV4S6ytwfsfvkgY8jIucDrjc8deX1s.",
+            ),
+            (
+                "a",
+                "$2a$06$m0CrhHm10qJ3lXRY.5zDGO3rS2KdeeWLuGmsfGlMfOxih58VYVfxe",
+            ),
+            (
+                "abc",
+                "$2a$06$If6bvum7DFjUnE9p2uDeDu0YHzrHM6tf.iqN8.yx.jNN1ILEf7h0i",
+            ),
+            (
+                "U*U",
+                "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW",
+            ),
+        ];
+        for (password, hash) in vectors.iter() {
+            assert!(verify(password.as_bytes(), hash).unwrap(), "{}", hash);
+            assert!(!verify(b"wrong", hash).unwrap());
+        }
+    }
+
+    #[test]
+    fn malformed_hashes() {
+        // 53 bytes after the cost, but the salt would end inside a multi-byte character
+        let split = format!("$2b$05${}é{}", "C".repeat(21), "C".repeat(30));
+        assert_eq!(split.len() - 7, 53);
+
+        for hash in [
+            split.as_str(),
+            "$2x$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW",
+            "$2a$32$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW",
+            "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOe",
+        ]
+        .iter()
+        {
+            assert!(cost(hash).is_none(), "{}", hash);
+            assert!(verify(b"U*U", hash).is_err(), "{}", hash);
+        }
+    }
+}
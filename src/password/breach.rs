@@ -0,0 +1,317 @@
+//! Refuses passwords known to have appeared in data breaches
+//!
+//! [`PwnedPasswords`] queries the Have I Been Pwned range API with k-anonymity: only the
+//! first 5 hex characters of the password's SHA-1 hash leave the process, and the match
+//! happens locally against the ~800 suffixes returned.  It requires the `reqwest`
+//! feature.
+//!
+//! [`BreachFilter`] answers the same question offline from a bloom filter built out of
+//! the downloadable hash list, for deployments that cannot call out to the internet.  It
+//! never misses a breached password but rejects a small, configurable fraction of
+//! passwords that were never breached.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::password::PwnedPasswords;
+//!
+//! if PwnedPasswords::new().is_breached(&new_password).await? {
+//!     return Err("this password appeared in a data breach, please choose another");
+//! }
+//! ```
+
+use ring::digest;
+use std::convert::TryInto;
+
+/// Length of a SHA-1 digest
+const SHA1_LEN: usize = 20;
+
+/// Returns the SHA-1 digest of a password
+fn sha1(password: &str) -> [u8; SHA1_LEN] {
+    digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes())
+        .as_ref()
+        .try_into()
+        .unwrap()
+}
+
+#[cfg(feature = "reqwest")]
+pub use online::*;
+
+#[cfg(feature = "reqwest")]
+mod online {
+    use super::sha1;
+    use thiserror::Error;
+
+    /// Url of the Pwned Passwords range API
+    const RANGE_URL: &str = "https://api.pwnedpasswords.com/range/";
+
+    #[derive(Error, Debug)]
+    pub enum BreachError {
+        #[error("request failed: {0}")]
+        Http(#[from] reqwest::Error),
+    }
+
+    /// Client of the Have I Been Pwned Pwned Passwords range API
+    #[derive(Clone, Debug)]
+    pub struct PwnedPasswords {
+        client: reqwest::Client,
+        url: String,
+    }
+
+    impl Default for PwnedPasswords {
+        fn default() -> PwnedPasswords {
+            PwnedPasswords::with_client(reqwest::Client::new())
+        }
+    }
+
+    impl PwnedPasswords {
+        pub fn new() -> PwnedPasswords {
+            Self::default()
+        }
+
+        /// Creates a client using a configured HTTP client (e.g., with a proxy or timeout)
+        ///
+        /// # Arguments
+        /// * `client` - Client to query the API with
+        pub fn with_client(client: reqwest::Client) -> PwnedPasswords {
+            PwnedPasswords {
+                client,
+                url: RANGE_URL.to_owned(),
+            }
+        }
+
+        /// Sets the url of the range API, e.g., to query a self-hosted mirror.  The prefix
+        /// of the hash is appended to it
+        ///
+        /// # Arguments
+        /// * `url` - Url of the range API
+        pub fn api_url(mut self, url: impl Into<String>) -> Self {
+            self.url = url.into();
+            self
+        }
+
+        /// Returns how many times a password appears in the breach corpus, or 0 if it
+        /// has never been seen
+        ///
+        /// # Arguments
+        /// * `password` - Password to check
+        pub async fn count(&self, password: &str) -> Result<u64, BreachError> {
+            let hash = hex(&sha1(password));
+            let (prefix, suffix) = hash.split_at(5);
+
+            // padding hides the size of the response, which could otherwise hint at the prefix
+            let body = self
+                .client
+                .get(&format!("{}{}", self.url, prefix))
+                .header("Add-Padding", "true")
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+
+            Ok(count_in_range(&body, suffix))
+        }
+
+        /// Returns true if a password appears in the breach corpus
+        ///
+        /// # Arguments
+        /// * `password` - Password to check
+        pub async fn is_breached(&self, password: &str) -> Result<bool, BreachError> {
+            Ok(self.count(password).await? > 0)
+        }
+    }
+
+    /// Encodes bytes as uppercase hex, the format of the range API
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02X}", b)).collect()
+    }
+
+    /// Finds a hash suffix in a range response (`SUFFIX:COUNT` lines), returning its count
+    ///
+    /// # Arguments
+    /// * `body` - Body of the range response
+    /// * `suffix` - Uppercase hex suffix of the password's hash
+    pub(super) fn count_in_range(body: &str, suffix: &str) -> u64 {
+        body.lines()
+            .filter_map(|line| {
+                let mut parts = line.trim().splitn(2, ':');
+                Some((parts.next()?, parts.next()?))
+            })
+            .find(|(candidate, _)| candidate.eq_ignore_ascii_case(suffix))
+            .and_then(|(_, count)| count.parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+/// An offline set of breached passwords, stored as a bloom filter over their SHA-1 hashes
+#[derive(Clone, Debug)]
+pub struct BreachFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl BreachFilter {
+    /// Creates an empty filter sized for a number of passwords and a false positive rate
+    ///
+    /// # Arguments
+    /// * `expected` - Number of passwords that will be inserted
+    /// * `false_positive_rate` - Fraction of unbreached passwords allowed to be rejected
+    pub fn new(expected: usize, false_positive_rate: f64) -> BreachFilter {
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(expected.max(1) as f64) * false_positive_rate.ln() / (ln2 * ln2)).ceil();
+        let words = ((bits as u64).max(64) as usize).div_ceil(64);
+        let hashes = ((words * 64) as f64 / expected.max(1) as f64 * ln2).round();
+
+        BreachFilter {
+            bits: vec![0; words],
+            hashes: (hashes as u32).max(1),
+        }
+    }
+
+    /// Adds a breached password
+    ///
+    /// # Arguments
+    /// * `password` - Breached password
+    pub fn insert(&mut self, password: &str) {
+        self.insert_hash(&sha1(password));
+    }
+
+    /// Adds a breached password by its SHA-1 hash, as found in each line of the Pwned
+    /// Passwords hash list (`HASH:COUNT`).  Returns false if the line is not a hash
+    ///
+    /// # Arguments
+    /// * `line` - Hex SHA-1 hash, optionally followed by `:` and a count
+    pub fn insert_hex(&mut self, line: &str) -> bool {
+        let hash = line.split(':').next().unwrap_or_default().trim();
+        match decode_hex(hash) {
+            Some(hash) => {
+                self.insert_hash(&hash);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns true if a password is (probably) breached
+    ///
+    /// # Arguments
+    /// * `password` - Password to check
+    pub fn is_breached(&self, password: &str) -> bool {
+        let hash = sha1(password);
+        self.positions(&hash)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Serializes the filter, so it can be built once and shipped with the application
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.bits.len() * 8);
+        bytes.extend_from_slice(&self.hashes.to_le_bytes());
+        for word in &self.bits {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Loads a filter serialized by `to_bytes()`, or returns `None` if the bytes are not
+    /// a serialized filter
+    ///
+    /// # Arguments
+    /// * `bytes` - Serialized filter
+    pub fn from_bytes(bytes: &[u8]) -> Option<BreachFilter> {
+        if bytes.len() < 12 || !(bytes.len() - 4).is_multiple_of(8) {
+            return None;
+        }
+
+        let hashes = u32::from_le_bytes(bytes[..4].try_into().ok()?);
+        let bits = bytes[4..]
+            .chunks(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        if hashes == 0 {
+            return None;
+        }
+        Some(BreachFilter { bits, hashes })
+    }
+
+    fn insert_hash(&mut self, hash: &[u8; SHA1_LEN]) {
+        let positions: Vec<usize> = self.positions(hash).collect();
+        for bit in positions {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Returns the bits a hash sets.  SHA-1 output is uniform, so two of its words seed
+    /// the double hashing directly
+    fn positions(&self, hash: &[u8; SHA1_LEN]) -> impl Iterator<Item = usize> {
+        let len = (self.bits.len() * 64) as u64;
+        let h1 = u64::from_le_bytes(hash[..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(hash[8..16].try_into().unwrap()) | 1;
+        (0..u64::from(self.hashes))
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+}
+
+/// Decodes a 40 character hex SHA-1 hash
+fn decode_hex(hex: &str) -> Option<[u8; SHA1_LEN]> {
+    if hex.len() != 2 * SHA1_LEN || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut hash = [0u8; SHA1_LEN];
+    for (byte, pair) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_prefix() {
+        // the SHA-1 of "password", as listed by the range API under prefix 5BAA6
+        assert_eq!(
+            Some(sha1("password")),
+            decode_hex("5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8")
+        );
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[test]
+    fn range_response() {
+        let body = "1D2DA4053E34E76F6576ED1DA63134B5E2A:2\r\n\
+                    1E4C9B93F3F0682250B6CF8331B7EE68FD8:9659365\r\n\
+                    1F2B668E8AABEF1C59E9EC6F82E3F3CD786:0\r\n";
+        assert_eq!(
+            count_in_range(body, "1E4C9B93F3F0682250B6CF8331B7EE68FD8"),
+            9659365
+        );
+        // padding entries have a count of 0
+        assert_eq!(
+            count_in_range(body, "1F2B668E8AABEF1C59E9EC6F82E3F3CD786"),
+            0
+        );
+        assert_eq!(
+            count_in_range(body, "0000000000000000000000000000000000A"),
+            0
+        );
+    }
+
+    #[test]
+    fn filter() {
+        let mut filter = BreachFilter::new(1000, 0.001);
+        filter.insert("password");
+        assert!(filter.insert_hex("7C4A8D09CA3762AF61E59520943DC26494F8941B:24230577"));
+        assert!(!filter.insert_hex("not a hash"));
+
+        assert!(filter.is_breached("password"));
+        assert!(filter.is_breached("123456"));
+        assert!(!filter.is_breached("correct horse battery staple"));
+
+        let filter = BreachFilter::from_bytes(&filter.to_bytes()).unwrap();
+        assert!(filter.is_breached("123456"));
+        assert!(BreachFilter::from_bytes(&[1, 2, 3]).is_none());
+    }
+}
@@ -0,0 +1,119 @@
+//! Picks argon2 parameters by benchmarking the host
+//!
+//! The same parameters can take 100ms on one machine and a second on another, so
+//! services deployed on heterogeneous hardware can calibrate at startup instead of
+//! hard-coding them.  Calibration never goes below the OWASP minimum
+//! ([`Hasher::owasp_default`]), raising memory first and passes once memory is capped.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::password::Hasher;
+//! use std::time::Duration;
+//!
+//! // about 250ms per hash, using at most 256 MiB
+//! let hasher = Hasher::calibrate(Duration::from_millis(250), 256 * 1024)?;
+//! ```
+
+use super::{hash_encoded, Argon2Params, Backend, Hasher, HasherError};
+use std::time::{Duration, Instant};
+
+/// Number of calibration rounds; each round rescales from the previous measurement
+const ROUNDS: usize = 3;
+
+/// Memory (KiB) and passes of `Hasher::owasp_default()`
+const MINIMUM: (u32, u32) = (19 * 1024, 2);
+
+impl Hasher {
+    /// Benchmarks Argon2id on this host and returns a hasher whose hashes take about
+    /// `target` to compute
+    ///
+    /// # Arguments
+    /// * `target` - Desired duration of a hash
+    /// * `max_memory` - Most memory, in KiB, a hash may use
+    pub fn calibrate(target: Duration, max_memory: u32) -> Result<Hasher, HasherError> {
+        let mut hasher = Hasher::owasp_default();
+
+        for _ in 0..ROUNDS {
+            let (current, elapsed) = match &hasher.backend {
+                Backend::Argon2(params) => (
+                    (params.memory, params.passes),
+                    time_hash(params, hasher.salt_length)?,
+                ),
+                Backend::Balloon(_) => unreachable!("calibration starts from argon2"),
+            };
+
+            let (memory, passes) = scale(current, MINIMUM, max_memory, elapsed, target);
+            if (memory, passes) == current {
+                break;
+            }
+            hasher = hasher.memory(memory).passes(passes);
+        }
+
+        Ok(hasher)
+    }
+}
+
+/// Times one hash with a set of argon2 parameters
+fn time_hash(params: &Argon2Params, salt_length: usize) -> Result<Duration, HasherError> {
+    let salt = vec![0u8; salt_length];
+    let start = Instant::now();
+    hash_encoded(b"calibration", &salt, params)?;
+    Ok(start.elapsed())
+}
+
+/// Scales memory, then passes, so a hash that took `elapsed` takes about `target`.
+/// Hashing time grows linearly with both
+///
+/// # Arguments
+/// * `current` - Memory (KiB) and passes that were timed
+/// * `minimum` - Least memory (KiB) and passes to return
+/// * `max_memory` - Most memory (KiB) to return
+/// * `elapsed` - Duration of a hash with the current parameters
+/// * `target` - Desired duration of a hash
+fn scale(
+    (memory, passes): (u32, u32),
+    (min_memory, min_passes): (u32, u32),
+    max_memory: u32,
+    elapsed: Duration,
+    target: Duration,
+) -> (u32, u32) {
+    let ratio = target.as_secs_f64() / elapsed.as_secs_f64().max(1e-6);
+    let work = f64::from(memory) * f64::from(passes) * ratio;
+
+    let memory = (work / f64::from(min_passes))
+        .min(f64::from(max_memory))
+        .max(f64::from(min_memory)) as u32;
+    let passes = (work / f64::from(memory))
+        .round()
+        .max(f64::from(min_passes)) as u32;
+    (memory, passes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaling() {
+        let ms = Duration::from_millis;
+        let minimum = (19456, 2);
+
+        // memory grows first
+        assert_eq!(
+            scale(minimum, minimum, 1 << 20, ms(50), ms(200)),
+            (77824, 2)
+        );
+        // then passes, once memory is capped
+        assert_eq!(scale(minimum, minimum, 38912, ms(50), ms(200)), (38912, 4));
+        // never below the minimum
+        assert_eq!(scale(minimum, minimum, 1 << 20, ms(50), ms(1)), minimum);
+    }
+
+    #[test]
+    fn calibrate() {
+        // a target every host misses keeps the minimum
+        let hasher = Hasher::calibrate(Duration::from_nanos(1), 1 << 20).unwrap();
+        assert!(!hasher.needs_rehash(Hasher::owasp_default().hash("hunter2").unwrap()));
+    }
+}
@@ -0,0 +1,149 @@
+//! Argon2 variants and parameters
+//!
+//! These describe argon2 hashes independently of the backend computing them (`rust-argon2`
+//! or RustCrypto's `argon2`).  With the `rust-argon2` feature, `rust-argon2`'s own types
+//! convert into them.
+
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// Argon2 variants
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Variant {
+    /// Data-dependent memory access, resisting GPU cracking but not side channels
+    Argon2d,
+
+    /// Data-independent memory access, resisting side channels
+    Argon2i,
+
+    /// Argon2i for the first half of the first pass, then Argon2d.  Recommended
+    Argon2id,
+}
+
+impl Variant {
+    /// Returns the variant's identifier in PHC strings, e.g., `argon2id`
+    pub fn as_lowercase_str(&self) -> &'static str {
+        match self {
+            Variant::Argon2d => "argon2d",
+            Variant::Argon2i => "argon2i",
+            Variant::Argon2id => "argon2id",
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = UnknownVariant;
+
+    fn from_str(s: &str) -> Result<Variant, UnknownVariant> {
+        match s {
+            "argon2d" => Ok(Variant::Argon2d),
+            "argon2i" => Ok(Variant::Argon2i),
+            "argon2id" => Ok(Variant::Argon2id),
+            _ => Err(UnknownVariant),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_lowercase_str())
+    }
+}
+
+/// Returned when parsing a string that names no argon2 variant
+#[derive(Error, Clone, Copy, Debug, PartialEq, Eq)]
+#[error("unknown argon2 variant")]
+pub struct UnknownVariant;
+
+/// Versions of the argon2 algorithm
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Version {
+    /// Version 1.0 (`v=16`)
+    Version10,
+
+    /// Version 1.3 (`v=19`)
+    Version13,
+}
+
+impl Version {
+    /// Returns the version number written in PHC strings
+    pub fn as_u32(&self) -> u32 {
+        match self {
+            Version::Version10 => 0x10,
+            Version::Version13 => 0x13,
+        }
+    }
+}
+
+/// Variant, version and costs of new argon2 hashes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Argon2Params {
+    /// Argon2 variant
+    pub variant: Variant,
+
+    /// Version of the algorithm
+    pub version: Version,
+
+    /// Memory used, in KiB
+    pub memory: u32,
+
+    /// Number of passes over the memory
+    pub passes: u32,
+
+    /// Degree of parallelism
+    pub lanes: u32,
+
+    /// Length of the hash, in bytes
+    pub hash_length: u32,
+}
+
+impl Default for Argon2Params {
+    /// Argon2id 1.3 with 4 MiB of memory, 3 passes, 1 lane and 32-byte hashes
+    fn default() -> Argon2Params {
+        Argon2Params {
+            variant: Variant::Argon2id,
+            version: Version::Version13,
+            memory: 4096,
+            passes: 3,
+            lanes: 1,
+            hash_length: 32,
+        }
+    }
+}
+
+#[cfg(feature = "rust-argon2")]
+impl From<argon2::Variant> for Variant {
+    fn from(variant: argon2::Variant) -> Variant {
+        match variant {
+            argon2::Variant::Argon2d => Variant::Argon2d,
+            argon2::Variant::Argon2i => Variant::Argon2i,
+            argon2::Variant::Argon2id => Variant::Argon2id,
+        }
+    }
+}
+
+#[cfg(feature = "rust-argon2")]
+impl From<argon2::Version> for Version {
+    fn from(version: argon2::Version) -> Version {
+        match version {
+            argon2::Version::Version10 => Version::Version10,
+            argon2::Version::Version13 => Version::Version13,
+        }
+    }
+}
+
+/// Takes the variant, version and costs of a `rust-argon2` `Config`.  Its secret and
+/// associated data are dropped, as `Hasher::verify` never supported them
+#[cfg(feature = "rust-argon2")]
+impl From<argon2::Config<'_>> for Argon2Params {
+    fn from(config: argon2::Config<'_>) -> Argon2Params {
+        Argon2Params {
+            variant: config.variant.into(),
+            version: config.version.into(),
+            memory: config.mem_cost,
+            passes: config.time_cost,
+            lanes: config.lanes,
+            hash_length: config.hash_length,
+        }
+    }
+}
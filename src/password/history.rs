@@ -0,0 +1,141 @@
+//! Refuses new passwords that match one of a user's recent passwords
+//!
+//! Implement [`PasswordHistory`] over wherever old hashes are kept, then call
+//! [`Hasher::check_history`] before accepting a new password.  Include the current hash
+//! in the history to also refuse "changing" a password to itself.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::password::{Hasher, HasherError};
+//!
+//! // "cannot reuse your last 5 passwords"
+//! match hasher.check_history(&history, user.id(), &new_password, 5) {
+//!     Err(HasherError::PasswordReused) => return Err("choose a password you have not used recently"),
+//!     result => result?,
+//! }
+//! history.push(user.id(), hasher.hash(&new_password)?);
+//! ```
+
+use super::{Hasher, HasherError};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+/// Fetches the hashes of the passwords users have had, keyed by the user's id
+pub trait PasswordHistory: Send + Sync {
+    /// Returns up to `count` of a user's most recent password hashes, newest first
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user
+    /// * `count` - Number of hashes to return
+    fn recent(&self, user: &[u8], count: usize) -> Result<Vec<String>, HasherError>;
+}
+
+/// A simple in-memory password history, keeping a bounded number of hashes per user
+#[derive(Debug)]
+pub struct MemoryPasswordHistory {
+    depth: usize,
+    users: Mutex<HashMap<Vec<u8>, VecDeque<String>>>,
+}
+
+impl MemoryPasswordHistory {
+    /// Creates an empty history
+    ///
+    /// # Arguments
+    /// * `depth` - Number of hashes kept per user
+    pub fn new(depth: usize) -> MemoryPasswordHistory {
+        MemoryPasswordHistory {
+            depth,
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a user's new password hash, forgetting the oldest once `depth` are kept
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user
+    /// * `hash` - Hash of the user's new password
+    pub fn push(&self, user: &[u8], hash: String) -> Result<(), HasherError> {
+        let mut users = self
+            .users
+            .lock()
+            .map_err(|_| HasherError::store("poisoned lock"))?;
+        let hashes = users.entry(user.to_vec()).or_default();
+        hashes.push_front(hash);
+        hashes.truncate(self.depth);
+        Ok(())
+    }
+}
+
+impl PasswordHistory for MemoryPasswordHistory {
+    fn recent(&self, user: &[u8], count: usize) -> Result<Vec<String>, HasherError> {
+        let users = self
+            .users
+            .lock()
+            .map_err(|_| HasherError::store("poisoned lock"))?;
+        Ok(users
+            .get(user)
+            .map(|hashes| hashes.iter().take(count).cloned().collect())
+            .unwrap_or_default())
+    }
+}
+
+impl Hasher {
+    /// Returns `HasherError::PasswordReused` if a proposed password matches any of a
+    /// user's `count` most recent password hashes.  Hashes in unrecognized formats are
+    /// skipped
+    ///
+    /// # Arguments
+    /// * `history` - Where the user's old hashes are kept
+    /// * `user` - Id of the user
+    /// * `password` - Proposed new password
+    /// * `count` - Number of recent passwords that may not be reused
+    pub fn check_history<H: PasswordHistory + ?Sized>(
+        &self,
+        history: &H,
+        user: &[u8],
+        password: &str,
+        count: usize,
+    ) -> Result<(), HasherError> {
+        let reused = history
+            .recent(user, count)?
+            .iter()
+            .take(count)
+            .any(|hash| self.matches(password.as_bytes(), hash).unwrap_or(false));
+
+        if reused {
+            Err(HasherError::PasswordReused)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse() {
+        let hasher = Hasher::owasp_default().memory(1024).passes(1);
+        let history = MemoryPasswordHistory::new(3);
+        for password in ["first", "second", "third", "fourth"].iter() {
+            history
+                .push(b"alice", hasher.hash(password).unwrap())
+                .unwrap();
+        }
+
+        assert!(matches!(
+            hasher.check_history(&history, b"alice", "third", 3),
+            Err(HasherError::PasswordReused)
+        ));
+        // only the last 2 are checked, and only 3 are kept
+        assert!(hasher
+            .check_history(&history, b"alice", "second", 2)
+            .is_ok());
+        assert!(hasher.check_history(&history, b"alice", "first", 5).is_ok());
+        assert!(hasher.check_history(&history, b"bob", "fourth", 5).is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+//! Hardens databases of unsalted MD5, SHA-1 or SHA-256 password digests
+//!
+//! Rehashing a legacy digest normally waits until its user next logs in, leaving every
+//! other row crackable in the meantime.  [`Hasher::wrap_legacy`] instead hashes the
+//! stored digest itself, so a whole table can be converted in one migration:
+//!
+//! `$wrap-<digest>` followed by the hash of the lowercase hex digest, e.g.
+//! `$wrap-sha1$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`
+//!
+//! [`Hasher::verify`] understands the wrapped form, and `needs_rehash()` reports it, so
+//! `verify_and_maybe_rehash()` replaces it with a plain hash on the user's next login.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::password::LegacyDigest;
+//!
+//! for (id, sha1_hex) in db.legacy_hashes()? {
+//!     db.set_hash(id, hasher.wrap_legacy(LegacyDigest::Sha1, &sha1_hex)?)?;
+//! }
+//! ```
+
+use super::{HashAlgorithm, Hasher, HasherError};
+use ring::digest;
+use std::fmt::Write;
+
+/// Unsalted digests that may be wrapped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LegacyDigest {
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+impl LegacyDigest {
+    /// Returns the digest's name
+    pub fn name(&self) -> &'static str {
+        match self {
+            LegacyDigest::Md5 => "md5",
+            LegacyDigest::Sha1 => "sha1",
+            LegacyDigest::Sha256 => "sha256",
+        }
+    }
+
+    /// Returns the digest whose name follows `wrap-` in a wrapped hash's identifier
+    pub(super) fn from_name(name: &str) -> Option<LegacyDigest> {
+        match name {
+            "md5" => Some(LegacyDigest::Md5),
+            "sha1" => Some(LegacyDigest::Sha1),
+            "sha256" => Some(LegacyDigest::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Returns the length of the digest, in bytes
+    fn len(&self) -> usize {
+        match self {
+            LegacyDigest::Md5 => 16,
+            LegacyDigest::Sha1 => 20,
+            LegacyDigest::Sha256 => 32,
+        }
+    }
+
+    /// Computes the digest of a password, as lowercase hex
+    fn hex(&self, password: &[u8]) -> String {
+        let digest = match self {
+            LegacyDigest::Md5 => md5::compute(password).0.to_vec(),
+            LegacyDigest::Sha1 => digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, password)
+                .as_ref()
+                .to_vec(),
+            LegacyDigest::Sha256 => digest::digest(&digest::SHA256, password).as_ref().to_vec(),
+        };
+        digest.iter().fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{:02x}", b);
+            hex
+        })
+    }
+
+    /// Returns the prefix of hashes wrapping this digest
+    fn prefix(&self) -> String {
+        format!("$wrap-{}", self.name())
+    }
+}
+
+impl Hasher {
+    /// Wraps a legacy digest in this hasher's algorithm, so it is protected before its
+    /// user next logs in
+    ///
+    /// # Arguments
+    /// * `digest` - Algorithm of the legacy digest
+    /// * `legacy_hash` - The stored digest, hex encoded
+    pub fn wrap_legacy(
+        &self,
+        digest: LegacyDigest,
+        legacy_hash: &str,
+    ) -> Result<String, HasherError> {
+        let legacy_hash = legacy_hash.trim();
+        if legacy_hash.len() != 2 * digest.len()
+            || !legacy_hash.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            return Err(HasherError::MalformedHash(HashAlgorithm::Wrapped(digest)));
+        }
+
+        let inner = self.hash(legacy_hash.to_ascii_lowercase())?;
+        Ok(format!("{}{}", digest.prefix(), inner))
+    }
+}
+
+/// Checks a password against a wrapped legacy hash
+///
+/// # Arguments
+/// * `hasher` - Hasher verifying the wrapping hash
+/// * `digest` - Algorithm of the wrapped digest
+/// * `password` - Password to check
+/// * `hash` - Stored hash
+pub(super) fn verify(
+    hasher: &Hasher,
+    digest: LegacyDigest,
+    password: &[u8],
+    hash: &str,
+) -> Result<bool, HasherError> {
+    let malformed = || HasherError::MalformedHash(HashAlgorithm::Wrapped(digest));
+
+    let inner = hash.strip_prefix(&digest.prefix()).ok_or_else(malformed)?;
+    match HashAlgorithm::detect(inner) {
+        Some(HashAlgorithm::Wrapped(_)) => Err(malformed()),
+        _ => hasher.matches(digest.hex(password).as_bytes(), inner),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap() {
+        let hasher = Hasher::owasp_default().memory(1024).passes(1);
+        let digests = [
+            (LegacyDigest::Md5, "2AB96390C7DBE3439DE74D0C9B0B1767"),
+            (
+                LegacyDigest::Sha1,
+                "f3bbbd66a63d4bf1747940578ec3d0103530e21d",
+            ),
+            (
+                LegacyDigest::Sha256,
+                "f52fbd32b2b3b86ff88ef6c490628285f482af15ddcb29541f94bcf526a3f6c7",
+            ),
+        ];
+
+        for (digest, legacy) in digests.iter() {
+            let wrapped = hasher.wrap_legacy(*digest, legacy).unwrap();
+            assert!(wrapped.starts_with(&format!("$wrap-{}$argon2id$", digest.name())));
+            assert_eq!(
+                HashAlgorithm::detect(&wrapped),
+                Some(HashAlgorithm::Wrapped(*digest))
+            );
+            assert!(hasher.verify("hunter2", &wrapped).is_ok());
+            assert!(hasher.verify("hunter3", &wrapped).is_err());
+
+            // the next login replaces it with a plain hash
+            let upgraded = hasher
+                .verify_and_maybe_rehash("hunter2", &wrapped)
+                .unwrap()
+                .unwrap();
+            assert!(upgraded.starts_with("$argon2id$"));
+        }
+
+        assert!(hasher.wrap_legacy(LegacyDigest::Sha1, "not hex").is_err());
+        assert!(hasher
+            .verify(
+                "hunter2",
+                "$wrap-sha1$wrap-md5$argon2id$v=19$m=8,t=1,p=1$c2FsdA$aGFzaA"
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! Decodes the algorithm and parameters of stored hashes
+//!
+//! [`HashParams::parse`] reads a stored hash without verifying anything, e.g., to audit
+//! which parameters a password database holds before raising them.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::password::HashParams;
+//!
+//! for hash in db.password_hashes()? {
+//!     let params = HashParams::parse(&hash)?;
+//!     if params.memory.unwrap_or(0) < 19 * 1024 {
+//!         println!("{} hash below the OWASP minimum: {:?}", params.algorithm, params);
+//!     }
+//! }
+//! ```
+
+use super::{bcrypt, pepper, phc, HashAlgorithm, HasherError, Variant};
+
+/// The algorithm and parameters a stored hash was produced with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HashParams {
+    /// Algorithm of the hash.  For a wrapped legacy digest, the parameters are those of
+    /// the wrapping hash
+    pub algorithm: HashAlgorithm,
+
+    /// Argon2 variant, for argon2 hashes
+    pub variant: Option<Variant>,
+
+    /// Version of the algorithm, if it has one (e.g., 19 for argon2 1.3)
+    pub version: Option<u32>,
+
+    /// Memory used, in KiB, if the algorithm is memory-hard
+    pub memory: Option<u32>,
+
+    /// Iterations: argon2 and Balloon passes, PBKDF2 rounds, bcrypt key expansion rounds
+    /// (`2^cost`) or scrypt's CPU/memory cost (`N`)
+    pub iterations: Option<u64>,
+
+    /// Degree of parallelism, if the algorithm has one
+    pub parallelism: Option<u32>,
+
+    /// Length of the salt, in bytes
+    pub salt_length: usize,
+
+    /// Length of the hash, in bytes
+    pub hash_length: usize,
+
+    /// Id of the pepper mixed into the password, if any
+    pub pepper: Option<u32>,
+}
+
+impl HashParams {
+    /// Decodes a stored hash
+    ///
+    /// # Arguments
+    /// * `hash` - Stored hash
+    pub fn parse(hash: &str) -> Result<HashParams, HasherError> {
+        if let Some((id, hash)) = pepper::split(hash) {
+            return Ok(HashParams {
+                pepper: Some(id),
+                ..HashParams::parse(hash)?
+            });
+        }
+
+        let algorithm = HashAlgorithm::detect(hash).ok_or(HasherError::UnknownFormat)?;
+        let malformed = || HasherError::MalformedHash(algorithm);
+
+        match algorithm {
+            HashAlgorithm::Bcrypt => {
+                let cost = bcrypt::cost(hash).ok_or_else(malformed)?;
+                Ok(HashParams {
+                    algorithm,
+                    variant: None,
+                    version: None,
+                    // four 256-entry S-boxes of 32-bit words
+                    memory: Some(4),
+                    iterations: Some(1 << cost),
+                    parallelism: None,
+                    salt_length: 16,
+                    hash_length: 23,
+                    pepper: None,
+                })
+            }
+            HashAlgorithm::Wrapped(_) => {
+                // the wrapped hash follows the `$wrap-<digest>` identifier
+                let inner = &hash[hash[1..].find('$').ok_or_else(malformed)? + 1..];
+                match HashAlgorithm::detect(inner) {
+                    Some(HashAlgorithm::Wrapped(_)) => Err(malformed()),
+                    _ => Ok(HashParams {
+                        algorithm,
+                        ..HashParams::parse(inner)?
+                    }),
+                }
+            }
+            _ => HashParams::from_phc(algorithm, hash),
+        }
+    }
+
+    /// Decodes a hash in the PHC string format
+    fn from_phc(algorithm: HashAlgorithm, hash: &str) -> Result<HashParams, HasherError> {
+        let malformed = || HasherError::MalformedHash(algorithm);
+
+        let phc = phc::PhcString::parse(hash).ok_or_else(malformed)?;
+        let param = |name| -> Result<u32, HasherError> {
+            phc.param(name)
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(malformed)
+        };
+        let mut params = HashParams {
+            algorithm,
+            variant: None,
+            version: None,
+            memory: None,
+            iterations: None,
+            parallelism: None,
+            salt_length: phc::decode(phc.salt).ok_or_else(malformed)?.len(),
+            hash_length: phc::decode(phc.hash).ok_or_else(malformed)?.len(),
+            pepper: None,
+        };
+
+        match algorithm {
+            HashAlgorithm::Argon2 => {
+                params.variant = Some(phc.id.parse::<Variant>().map_err(|_| malformed())?);
+                // hashes predating version 1.3 omit the version
+                params.version = Some(
+                    phc.version
+                        .unwrap_or("16")
+                        .parse()
+                        .map_err(|_| malformed())?,
+                );
+                params.memory = Some(param("m")?);
+                params.iterations = Some(param("t")?.into());
+                params.parallelism = Some(param("p")?);
+            }
+            HashAlgorithm::Balloon => {
+                let blocks = u64::from(param("s")?);
+                params.memory = Some((blocks * super::balloon::BLOCK_LEN as u64 / 1024) as u32);
+                params.iterations = Some(param("t")?.into());
+                params.parallelism = Some(1);
+            }
+            HashAlgorithm::Scrypt => {
+                let (log_n, r) = (param("ln")?, param("r")?);
+                let n = 1u64.checked_shl(log_n).ok_or_else(malformed)?;
+                params.memory = Some(
+                    ((128 * u64::from(r)).saturating_mul(n) / 1024).min(u64::from(u32::MAX)) as u32,
+                );
+                params.iterations = Some(n);
+                params.parallelism = Some(param("p")?);
+            }
+            HashAlgorithm::Pbkdf2Sha256 => {
+                let rounds = phc.param("i").unwrap_or(phc.params);
+                params.iterations = Some(rounds.parse().map_err(|_| malformed())?);
+            }
+            HashAlgorithm::Bcrypt | HashAlgorithm::Wrapped(_) => unreachable!(),
+        }
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::password::LegacyDigest;
+
+    #[test]
+    fn parse() {
+        let params = HashParams::parse(
+            "$argon2id$v=19$m=19456,t=2,p=1$c2FsdHlzYWx0eXNhbHR5IQ$aGFzaGhhc2hoYXNoaGFzaA",
+        )
+        .unwrap();
+        assert_eq!(
+            params,
+            HashParams {
+                algorithm: HashAlgorithm::Argon2,
+                variant: Some(Variant::Argon2id),
+                version: Some(19),
+                memory: Some(19456),
+                iterations: Some(2),
+                parallelism: Some(1),
+                salt_length: 16,
+                hash_length: 16,
+                pepper: None,
+            }
+        );
+
+        let params = HashParams::parse("$scrypt$ln=15,r=8,p=1$c2FsdA$aGFzaA").unwrap();
+        assert_eq!(
+            (params.memory, params.iterations, params.parallelism),
+            (Some(32768), Some(32768), Some(1))
+        );
+
+        let params =
+            HashParams::parse("$2b$12$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW")
+                .unwrap();
+        assert_eq!(params.iterations, Some(4096));
+
+        let params = HashParams::parse("$pbkdf2-sha256$29000$c2FsdA$aGFzaA").unwrap();
+        assert_eq!((params.iterations, params.salt_length), (Some(29000), 4));
+
+        let params =
+            HashParams::parse("$wrap-sha1$argon2i$v=19$m=4096,t=3,p=1$c2FsdA$aGFzaA").unwrap();
+        assert_eq!(params.algorithm, HashAlgorithm::Wrapped(LegacyDigest::Sha1));
+        assert_eq!(params.variant, Some(Variant::Argon2i));
+
+        let params =
+            HashParams::parse("$pepper-3$argon2i$v=19$m=4096,t=3,p=1$c2FsdA$aGFzaA").unwrap();
+        assert_eq!(
+            (params.algorithm, params.pepper),
+            (HashAlgorithm::Argon2, Some(3))
+        );
+
+        assert!(matches!(
+            HashParams::parse("$argon2id$v=19$m=x,t=2,p=1$c2FsdA$aGFzaA"),
+            Err(HasherError::MalformedHash(HashAlgorithm::Argon2))
+        ));
+        assert!(matches!(
+            HashParams::parse("5f4dcc3b5aa765d61d8327deb882cf99"),
+            Err(HasherError::UnknownFormat)
+        ));
+    }
+}
@@ -0,0 +1,62 @@
+//! Verifies PBKDF2-HMAC-SHA256 hashes
+//!
+//! Accepts both passlib's format, `$pbkdf2-sha256$<rounds>$<salt>$<hash>`, and the PHC
+//! string format, `$pbkdf2-sha256$i=<rounds>,l=<length>$<salt>$<hash>`.
+
+use super::{phc, HashAlgorithm, HasherError};
+use ring::pbkdf2;
+use std::num::NonZeroU32;
+
+/// Checks a password against a PBKDF2-HMAC-SHA256 hash
+///
+/// # Arguments
+/// * `password` - Password to check
+/// * `hash` - Stored hash
+pub(super) fn verify(password: &[u8], hash: &str) -> Result<bool, HasherError> {
+    let malformed = || HasherError::MalformedHash(HashAlgorithm::Pbkdf2Sha256);
+
+    let phc = phc::PhcString::parse(hash)
+        .filter(|phc| phc.id == "pbkdf2-sha256")
+        .ok_or_else(malformed)?;
+    let rounds = phc
+        .param("i")
+        .unwrap_or(phc.params)
+        .parse()
+        .ok()
+        .and_then(NonZeroU32::new)
+        .ok_or_else(malformed)?;
+    let salt = phc::decode(phc.salt).ok_or_else(malformed)?;
+    let expected = phc::decode(phc.hash).ok_or_else(malformed)?;
+    if expected.is_empty() {
+        return Err(malformed());
+    }
+
+    // ring compares the derived key in constant time
+    Ok(pbkdf2::verify(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        rounds,
+        &salt,
+        password,
+        &expected,
+    )
+    .is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_hashes() {
+        let hashes = [
+            "$pbkdf2-sha256$1000$c2FsdHlzYWx0eXNhbHR5IQ$uPkl1f7vk5Ih8E1RFG2BwOzhe59VIHy/k8Nmlaunv1U",
+            "$pbkdf2-sha256$i=1000,l=32$c2FsdHlzYWx0eXNhbHR5IQ$uPkl1f7vk5Ih8E1RFG2BwOzhe59VIHy/k8Nmlaunv1U",
+        ];
+        for hash in hashes.iter() {
+            assert!(verify(b"hunter2", hash).unwrap());
+            assert!(!verify(b"hunter3", hash).unwrap());
+        }
+
+        assert!(verify(b"hunter2", "$pbkdf2-sha256$0$c2FsdA$aGFzaA").is_err());
+    }
+}
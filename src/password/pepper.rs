@@ -0,0 +1,121 @@
+//! Mixes a server-side secret (a pepper) into passwords before they are hashed
+//!
+//! A pepper is kept out of the database (e.g., in a secrets manager), so a leaked
+//! password table can't be cracked without it.  The password is replaced by its
+//! HMAC-SHA256 under the pepper, and the pepper's id is recorded in front of the hash:
+//!
+//! `$pepper-<id>` followed by the hash, e.g. `$pepper-2$argon2id$v=19$...`
+//!
+//! Peppers are rotated by adding one with a higher id: new hashes use the highest id,
+//! hashes on older peppers keep verifying, and `needs_rehash()` reports them so they are
+//! moved to the new pepper as users log in.  Once no hashes use an old pepper, it can be
+//! removed.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::password::Hasher;
+//!
+//! let hasher = Hasher::owasp_default()
+//!     .pepper(1, secrets.get("password-pepper-1")?)
+//!     .pepper(2, secrets.get("password-pepper-2")?);
+//! ```
+
+use super::{Hasher, HasherError};
+use ring::hmac;
+
+impl Hasher {
+    /// Adds a pepper.  New hashes use the pepper with the highest id
+    ///
+    /// # Arguments
+    /// * `id` - Id of the pepper, recorded in hashes that use it
+    /// * `secret` - The pepper
+    pub fn pepper(mut self, id: u32, secret: impl AsRef<[u8]>) -> Self {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref());
+        self.peppers.insert(id, key);
+        self
+    }
+
+    /// Returns the id and key of the pepper new hashes use, if any
+    pub(super) fn current_pepper(&self) -> Option<(u32, &hmac::Key)> {
+        self.peppers.iter().next_back().map(|(id, key)| (*id, key))
+    }
+
+    /// Peppers a password with the pepper recorded in a hash, returning the peppered
+    /// password
+    ///
+    /// # Arguments
+    /// * `id` - Id of the pepper
+    /// * `password` - Password to pepper
+    pub(super) fn apply_pepper(&self, id: u32, password: &[u8]) -> Result<Vec<u8>, HasherError> {
+        let key = self
+            .peppers
+            .get(&id)
+            .ok_or(HasherError::UnknownPepper(id))?;
+        Ok(apply(key, password))
+    }
+}
+
+/// Peppers a password
+///
+/// # Arguments
+/// * `key` - The pepper
+/// * `password` - Password to pepper
+pub(super) fn apply(key: &hmac::Key, password: &[u8]) -> Vec<u8> {
+    hmac::sign(key, password).as_ref().to_vec()
+}
+
+/// Formats the prefix of hashes using a pepper
+pub(super) fn prefix(id: u32) -> String {
+    format!("$pepper-{}", id)
+}
+
+/// Splits a peppered hash into the pepper's id and the hash, or returns `None` if the
+/// hash is not peppered
+///
+/// # Arguments
+/// * `hash` - Stored hash
+pub(super) fn split(hash: &str) -> Option<(u32, &str)> {
+    let rest = hash.strip_prefix("$pepper-")?;
+    let end = rest.find('$')?;
+    let id = rest[..end].parse().ok()?;
+    Some((id, &rest[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation() {
+        let old = Hasher::owasp_default()
+            .memory(1024)
+            .passes(1)
+            .pepper(1, "first pepper");
+        let new = old.clone().pepper(2, "second pepper");
+
+        let hash = old.hash("hunter2").unwrap();
+        assert!(hash.starts_with("$pepper-1$argon2id$"));
+        assert!(new.verify("hunter2", &hash).is_ok());
+        assert!(new.verify("hunter3", &hash).is_err());
+
+        // rotating flags hashes on the old pepper, and upgrades them on login
+        assert!(!old.needs_rehash(&hash));
+        assert!(new.needs_rehash(&hash));
+        let upgraded = new
+            .verify_and_maybe_rehash("hunter2", &hash)
+            .unwrap()
+            .unwrap();
+        assert!(upgraded.starts_with("$pepper-2$"));
+        assert!(!new.needs_rehash(&upgraded));
+
+        // the pepper is required to verify
+        let plain = Hasher::owasp_default().memory(1024).passes(1);
+        assert!(matches!(
+            plain.verify("hunter2", &upgraded),
+            Err(HasherError::UnknownPepper(2))
+        ));
+        assert!(plain.needs_rehash(&upgraded));
+        assert!(new.needs_rehash(plain.hash("hunter2").unwrap()));
+    }
+}
@@ -0,0 +1,106 @@
+//! Parses hashes in the PHC string format
+//!
+//! `$<id>[$v=<version>]$<param>=<value>(,<param>=<value>)*$<salt>$<hash>`, with the salt
+//! and hash base64 encoded without padding
+//! ([spec](https://github.com/P-H-C/phc-string-format/blob/master/phc-sf-spec.md)).
+
+/// The fields of a PHC string
+#[derive(Debug)]
+pub(super) struct PhcString<'a> {
+    /// Identifier of the algorithm
+    pub id: &'a str,
+
+    /// Version of the algorithm, if present
+    pub version: Option<&'a str>,
+
+    /// The comma-separated parameters, unparsed
+    pub params: &'a str,
+
+    /// The encoded salt
+    pub salt: &'a str,
+
+    /// The encoded hash
+    pub hash: &'a str,
+}
+
+impl<'a> PhcString<'a> {
+    /// Splits a hash into its fields, or returns `None` if it is not a PHC string
+    ///
+    /// # Arguments
+    /// * `hash` - Stored hash
+    pub fn parse(hash: &'a str) -> Option<PhcString<'a>> {
+        let mut fields = hash.split('$');
+        if fields.next() != Some("") {
+            return None;
+        }
+
+        let id = fields.next()?;
+        let mut params = fields.next()?;
+        let mut version = None;
+        if let Some(v) = params.strip_prefix("v=") {
+            version = Some(v);
+            params = fields.next()?;
+        }
+        let salt = fields.next()?;
+        let hash = fields.next()?;
+        if fields.next().is_some() {
+            return None;
+        }
+
+        Some(PhcString {
+            id,
+            version,
+            params,
+            salt,
+            hash,
+        })
+    }
+
+    /// Returns the value of a parameter
+    ///
+    /// # Arguments
+    /// * `name` - Name of the parameter
+    pub fn param(&self, name: &str) -> Option<&'a str> {
+        self.params.split(',').find_map(|param| {
+            let mut parts = param.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if key == name => Some(value),
+                _ => None,
+            }
+        })
+    }
+}
+
+/// Decodes a salt or hash.  Accepts passlib's variant of base64, which uses `.` in
+/// place of `+`
+///
+/// # Arguments
+/// * `encoded` - Base64 encoded value, without padding
+pub(super) fn decode(encoded: &str) -> Option<Vec<u8>> {
+    base64::decode_config(encoded.replace('.', "+"), base64::STANDARD_NO_PAD).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let phc = PhcString::parse("$argon2id$v=19$m=4096,t=3,p=1$c2FsdHlzYWx0$aGFzaA").unwrap();
+        assert_eq!(phc.id, "argon2id");
+        assert_eq!(phc.version, Some("19"));
+        assert_eq!(phc.param("m"), Some("4096"));
+        assert_eq!(phc.param("p"), Some("1"));
+        assert_eq!(phc.param("x"), None);
+        assert_eq!(decode(phc.salt).unwrap(), b"saltysalt");
+
+        let phc = PhcString::parse("$pbkdf2-sha256$29000$N2YMIWQsBWBMae09x1jrPQ$as6d").unwrap();
+        assert_eq!(phc.params, "29000");
+
+        assert!(PhcString::parse("argon2id$v=19$m=4096$salt$hash").is_none());
+        assert!(
+            PhcString::parse("$2b$10$N9qo8uLOickgx2ZMRZoMyeIjZAgcfl7p92ldGxad68LJZdL17lhWy")
+                .is_none()
+        );
+    }
+}
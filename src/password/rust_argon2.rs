@@ -0,0 +1,85 @@
+//! Hashes and verifies argon2 hashes with `rust-argon2`
+//!
+//! Enabled by the `rust-argon2` feature, in place of the RustCrypto `argon2` crate.  Both
+//! encode hashes identically, so either backend verifies the other's hashes.
+
+use super::{Argon2Params, HasherError, Variant, Version};
+use argon2::Config;
+
+/// Hashes a password, returning the hash in the PHC string format
+///
+/// # Arguments
+/// * `password` - Password to hash
+/// * `salt` - Salt of the hash
+/// * `params` - Variant and parameters to hash with
+pub(super) fn hash_encoded(
+    password: &[u8],
+    salt: &[u8],
+    params: &Argon2Params,
+) -> Result<String, HasherError> {
+    let config = Config {
+        variant: match params.variant {
+            Variant::Argon2d => argon2::Variant::Argon2d,
+            Variant::Argon2i => argon2::Variant::Argon2i,
+            Variant::Argon2id => argon2::Variant::Argon2id,
+        },
+        version: match params.version {
+            Version::Version10 => argon2::Version::Version10,
+            Version::Version13 => argon2::Version::Version13,
+        },
+        mem_cost: params.memory,
+        time_cost: params.passes,
+        lanes: params.lanes,
+        hash_length: params.hash_length,
+        ..Config::default()
+    };
+    Ok(argon2::hash_encoded(password, salt, &config)?)
+}
+
+/// Checks a password against an argon2 hash
+///
+/// # Arguments
+/// * `hash` - Stored hash
+/// * `password` - Password to check
+pub(super) fn verify_encoded(hash: &str, password: &[u8]) -> Result<bool, HasherError> {
+    Ok(argon2::verify_encoded(hash, password)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut params = Argon2Params {
+            memory: 1024,
+            passes: 1,
+            ..Argon2Params::default()
+        };
+        let salt = b"saltysaltysalty!";
+
+        for variant in [Variant::Argon2id, Variant::Argon2i, Variant::Argon2d].iter() {
+            params.variant = *variant;
+            let hash = hash_encoded(b"hunter2", salt, &params).unwrap();
+            assert!(hash.starts_with(&format!("${}$v=19$m=1024,t=1,p=1$", variant)));
+            assert!(verify_encoded(&hash, b"hunter2").unwrap());
+            assert!(!verify_encoded(&hash, b"hunter3").unwrap());
+        }
+    }
+
+    #[test]
+    fn from_config() {
+        let config = argon2::Config {
+            mem_cost: 1024,
+            ..argon2::Config::default()
+        };
+        assert_eq!(
+            Argon2Params::from(config),
+            Argon2Params {
+                variant: Variant::Argon2i,
+                memory: 1024,
+                ..Argon2Params::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Hashes and verifies argon2 hashes with the pure-Rust RustCrypto `argon2` crate
+//!
+//! Enabled by the `argon2-rustcrypto` feature, in place of `rust-argon2`.  Hashes are
+//! encoded identically, so either backend verifies the other's hashes.  The functions
+//! mirror `rust-argon2`'s.
+
+use super::{phc, Argon2Params, HashAlgorithm, HasherError, Variant, Version};
+use argon2_rustcrypto::{Algorithm, Argon2, Params};
+use ring::constant_time;
+
+/// Hashes a password, returning the hash in the PHC string format
+///
+/// # Arguments
+/// * `password` - Password to hash
+/// * `salt` - Salt of the hash
+/// * `params` - Variant and parameters to hash with
+pub(super) fn hash_encoded(
+    password: &[u8],
+    salt: &[u8],
+    params: &Argon2Params,
+) -> Result<String, HasherError> {
+    let algorithm = match params.variant {
+        Variant::Argon2d => Algorithm::Argon2d,
+        Variant::Argon2i => Algorithm::Argon2i,
+        Variant::Argon2id => Algorithm::Argon2id,
+    };
+    let version = match params.version {
+        Version::Version10 => argon2_rustcrypto::Version::V0x10,
+        Version::Version13 => argon2_rustcrypto::Version::V0x13,
+    };
+
+    let mut output = vec![0u8; params.hash_length as usize];
+    derive(
+        password,
+        salt,
+        algorithm,
+        version,
+        (params.memory, params.passes, params.lanes),
+        &mut output,
+    )?;
+
+    Ok(format!(
+        "${}$v={}$m={},t={},p={}${}${}",
+        params.variant.as_lowercase_str(),
+        params.version.as_u32(),
+        params.memory,
+        params.passes,
+        params.lanes,
+        base64::encode_config(salt, base64::STANDARD_NO_PAD),
+        base64::encode_config(&output, base64::STANDARD_NO_PAD),
+    ))
+}
+
+/// Checks a password against an argon2 hash
+///
+/// # Arguments
+/// * `hash` - Stored hash
+/// * `password` - Password to check
+pub(super) fn verify_encoded(hash: &str, password: &[u8]) -> Result<bool, HasherError> {
+    let malformed = || HasherError::MalformedHash(HashAlgorithm::Argon2);
+
+    let phc = phc::PhcString::parse(hash).ok_or_else(malformed)?;
+    let algorithm = phc.id.parse::<Algorithm>().map_err(|_| malformed())?;
+    let version = match phc.version.unwrap_or("16") {
+        "16" => argon2_rustcrypto::Version::V0x10,
+        "19" => argon2_rustcrypto::Version::V0x13,
+        _ => return Err(malformed()),
+    };
+    let param = |name| -> Result<u32, HasherError> {
+        phc.param(name)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(malformed)
+    };
+    let params = (param("m")?, param("t")?, param("p")?);
+    let salt = phc::decode(phc.salt).ok_or_else(malformed)?;
+    let expected = phc::decode(phc.hash).ok_or_else(malformed)?;
+
+    let mut actual = vec![0u8; expected.len()];
+    derive(password, &salt, algorithm, version, params, &mut actual)?;
+    Ok(constant_time::verify_slices_are_equal(&actual, &expected).is_ok())
+}
+
+/// Derives the raw hash of a password into `output`
+fn derive(
+    password: &[u8],
+    salt: &[u8],
+    algorithm: Algorithm,
+    version: argon2_rustcrypto::Version,
+    (memory, passes, lanes): (u32, u32, u32),
+    output: &mut [u8],
+) -> Result<(), HasherError> {
+    let params =
+        Params::new(memory, passes, lanes, Some(output.len())).map_err(HasherError::RustCrypto)?;
+    Argon2::new(algorithm, version, params)
+        .hash_password_into(password, salt, output)
+        .map_err(HasherError::RustCrypto)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut params = Argon2Params {
+            memory: 1024,
+            passes: 1,
+            ..Argon2Params::default()
+        };
+        let salt = b"saltysaltysalty!";
+
+        for variant in [Variant::Argon2id, Variant::Argon2i, Variant::Argon2d].iter() {
+            params.variant = *variant;
+            let hash = hash_encoded(b"hunter2", salt, &params).unwrap();
+            assert!(hash.starts_with(&format!("${}$v=19$m=1024,t=1,p=1$", variant)));
+            assert!(verify_encoded(&hash, b"hunter2").unwrap());
+            assert!(!verify_encoded(&hash, b"hunter3").unwrap());
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Verifies scrypt ([RFC 7914](https://tools.ietf.org/html/rfc7914)) hashes
+//!
+//! PHC string format: `$scrypt$ln=<log2 N>,r=<block size>,p=<parallelism>$<salt>$<hash>`,
+//! as produced by passlib and the RustCrypto `scrypt` crate, which also derives the key.
+
+use super::{phc, HashAlgorithm, HasherError};
+use ring::constant_time;
+use std::convert::TryFrom;
+
+/// Largest amount of memory (in bytes) a stored hash may make verification allocate
+const MAX_MEMORY: usize = 1 << 30;
+
+/// Checks a password against an scrypt hash
+///
+/// # Arguments
+/// * `password` - Password to check
+/// * `hash` - Stored hash
+pub(super) fn verify(password: &[u8], hash: &str) -> Result<bool, HasherError> {
+    let malformed = || HasherError::MalformedHash(HashAlgorithm::Scrypt);
+
+    let phc = phc::PhcString::parse(hash)
+        .filter(|phc| phc.id == "scrypt")
+        .ok_or_else(malformed)?;
+    let param = |name| -> Result<u32, HasherError> {
+        phc.param(name)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(malformed)
+    };
+    let (log_n, r, p) = (param("ln")?, param("r")?, param("p")?);
+    let salt = phc::decode(phc.salt).ok_or_else(malformed)?;
+    let expected = phc::decode(phc.hash).ok_or_else(malformed)?;
+
+    // reject parameters that would exhaust memory before allocating anything
+    let memory = (128 * r as usize)
+        .checked_mul(1usize.checked_shl(log_n).ok_or_else(malformed)?)
+        .filter(|memory| *memory <= MAX_MEMORY)
+        .ok_or_else(malformed)?;
+    if log_n == 0 || r == 0 || p == 0 || memory == 0 || expected.is_empty() {
+        return Err(malformed());
+    }
+
+    let actual = scrypt(password, &salt, log_n, r, p, expected.len()).ok_or_else(malformed)?;
+    Ok(constant_time::verify_slices_are_equal(&actual, &expected).is_ok())
+}
+
+/// Derives a key with the `scrypt` crate
+///
+/// # Arguments
+/// * `password` - Password to derive the key from
+/// * `salt` - Salt
+/// * `log_n` - Base-2 logarithm of the CPU/memory cost
+/// * `r` - Block size
+/// * `p` - Parallelism
+/// * `len` - Length of the derived key
+fn scrypt(password: &[u8], salt: &[u8], log_n: u32, r: u32, p: u32, len: usize) -> Option<Vec<u8>> {
+    let params = ::scrypt::Params::new(u8::try_from(log_n).ok()?, r, p, len).ok()?;
+    let mut key = vec![0u8; len];
+    ::scrypt::scrypt(password, salt, &params, &mut key).ok()?;
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn rfc7914_vectors() {
+        assert_eq!(
+            hex(&scrypt(b"", b"", 4, 1, 1, 64).unwrap()),
+            "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442\
+             fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906"
+        );
+        assert_eq!(
+            hex(&scrypt(b"password", b"NaCl", 10, 8, 16, 64).unwrap()),
+            "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162\
+             2eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"
+        );
+    }
+
+    #[test]
+    fn verify_hash() {
+        let hash = "$scrypt$ln=4,r=8,p=1$c2FsdHlzYWx0eXNhbHR5IQ$Yw/Rej9NB+JqTidP/Ubez1ec7hAAfBJ3ja3R+ATnL4A";
+        assert!(verify(b"hunter2", hash).unwrap());
+        assert!(!verify(b"hunter3", hash).unwrap());
+
+        assert!(verify(b"hunter2", "$scrypt$ln=40,r=8,p=1$c2FsdA$aGFzaA").is_err());
+        assert!(verify(b"hunter2", "$scrypt$r=8,p=1$c2FsdA$aGFzaA").is_err());
+    }
+}
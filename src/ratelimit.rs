@@ -0,0 +1,343 @@
+//! Rate limiting of authentication attempts
+//!
+//! A [`RateLimiter`] is consulted before verifying a password, WebAuthn assertion or
+//! one-time code and records the attempt against the user and/or client IP address.  Once
+//! a key exceeds its limit, further attempts fail with [`RateLimitError::Limited`] until
+//! enough time has passed.
+//!
+//! Two in-memory limiters are provided for single-process deployments:
+//!
+//! * [`TokenBucket`] - Allows bursts of up to `capacity` attempts, refilling one attempt
+//!   every `refill` interval
+//! * [`SlidingWindow`] - Allows at most `limit` attempts within any `window`
+//!
+//! Limits shared between processes (e.g., stored in Redis) can be implemented with the
+//! [`RateLimiter`] trait.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::ratelimit::{RateLimitKey, RateLimiter, SlidingWindow};
+//! use std::time::Duration;
+//!
+//! let limiter = SlidingWindow::new(5, Duration::from_secs(15 * 60));
+//!
+//! limiter.check_attempt(Some(user.id()), Some(remote_addr.ip()))?;
+//! hasher.verify(&password, &user.hash)?;
+//! limiter.reset(&RateLimitKey::User(user.id().to_vec()))?;
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RateLimitError {
+    #[error("too many attempts, retry after {retry_after:?}")]
+    Limited { retry_after: Duration },
+
+    #[error("rate limit backend failure: {0}")]
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl RateLimitError {
+    /// Wraps an error returned by a rate limit backend
+    ///
+    /// # Arguments
+    /// * `e` - The underlying backend error
+    pub fn backend<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> RateLimitError {
+        RateLimitError::Backend(e.into())
+    }
+}
+
+/// What an attempt is counted against
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// Attempts made for a user (by id), from any address
+    User(Vec<u8>),
+
+    /// Attempts made from an address, for any user
+    Ip(IpAddr),
+
+    /// Attempts made for a user from a specific address
+    UserIp(Vec<u8>, IpAddr),
+
+    /// Messages sent to a destination (e.g., a one-time code sent to a phone number)
+    Destination(String),
+}
+
+/// Limits the rate of authentication attempts
+pub trait RateLimiter: Send + Sync {
+    /// Records an attempt, failing if the key has exceeded its limit
+    ///
+    /// # Arguments
+    /// * `key` - What the attempt is counted against
+    fn check(&self, key: &RateLimitKey) -> Result<(), RateLimitError>;
+
+    /// Clears all recorded attempts for a key (e.g., after a successful login)
+    ///
+    /// # Arguments
+    /// * `key` - Key to clear
+    fn reset(&self, key: &RateLimitKey) -> Result<(), RateLimitError>;
+
+    /// Records an attempt against both the user and the client's address, failing if
+    /// either has exceeded its limit
+    ///
+    /// # Arguments
+    /// * `user` - Id of the user the attempt is for, if known
+    /// * `ip` - Address of the client, if known
+    fn check_attempt(&self, user: Option<&[u8]>, ip: Option<IpAddr>) -> Result<(), RateLimitError> {
+        if let Some(user) = user {
+            self.check(&RateLimitKey::User(user.to_vec()))?;
+        }
+
+        if let Some(ip) = ip {
+            self.check(&RateLimitKey::Ip(ip))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// State of a single bucket
+#[derive(Debug)]
+struct Bucket {
+    /// Attempts remaining in the bucket
+    tokens: f64,
+
+    /// Last time the bucket was refilled
+    updated: Instant,
+}
+
+/// An in-memory token bucket rate limiter
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// Maximum number of attempts that can be made in a burst
+    capacity: u32,
+
+    /// Time taken to refill a single attempt
+    refill: Duration,
+
+    buckets: Mutex<HashMap<RateLimitKey, Bucket>>,
+}
+
+impl TokenBucket {
+    /// Creates a new token bucket limiter
+    ///
+    /// # Arguments
+    /// * `capacity` - Maximum number of attempts that can be made in a burst
+    /// * `refill` - Time taken to refill a single attempt
+    pub fn new(capacity: u32, refill: Duration) -> TokenBucket {
+        TokenBucket {
+            capacity,
+            refill,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Removes buckets that have completely refilled, bounding memory usage
+    pub fn purge(&self) -> Result<(), RateLimitError> {
+        let now = Instant::now();
+        let mut buckets = self.lock()?;
+        buckets.retain(|_, bucket| self.refilled(bucket, now) < f64::from(self.capacity));
+        Ok(())
+    }
+
+    fn check_at(&self, key: &RateLimitKey, now: Instant) -> Result<(), RateLimitError> {
+        let mut buckets = self.lock()?;
+        let bucket = buckets.entry(key.clone()).or_insert(Bucket {
+            tokens: f64::from(self.capacity),
+            updated: now,
+        });
+
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - bucket.tokens;
+            Err(RateLimitError::Limited {
+                retry_after: self.refill.mul_f64(missing),
+            })
+        }
+    }
+
+    /// Returns the number of tokens in a bucket after refilling it up to `now`
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        let added = if self.refill.as_nanos() == 0 {
+            f64::from(self.capacity)
+        } else {
+            elapsed.as_secs_f64() / self.refill.as_secs_f64()
+        };
+        (bucket.tokens + added).min(f64::from(self.capacity))
+    }
+
+    fn lock(
+        &self,
+    ) -> Result<std::sync::MutexGuard<'_, HashMap<RateLimitKey, Bucket>>, RateLimitError> {
+        self.buckets
+            .lock()
+            .map_err(|_| RateLimitError::backend("poisoned lock"))
+    }
+}
+
+impl RateLimiter for TokenBucket {
+    fn check(&self, key: &RateLimitKey) -> Result<(), RateLimitError> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn reset(&self, key: &RateLimitKey) -> Result<(), RateLimitError> {
+        self.lock()?.remove(key);
+        Ok(())
+    }
+}
+
+/// An in-memory sliding window rate limiter
+#[derive(Debug)]
+pub struct SlidingWindow {
+    /// Maximum number of attempts within a window
+    limit: u32,
+
+    /// Length of the window
+    window: Duration,
+
+    attempts: Mutex<HashMap<RateLimitKey, VecDeque<Instant>>>,
+}
+
+impl SlidingWindow {
+    /// Creates a new sliding window limiter
+    ///
+    /// # Arguments
+    /// * `limit` - Maximum number of attempts within a window
+    /// * `window` - Length of the window
+    pub fn new(limit: u32, window: Duration) -> SlidingWindow {
+        SlidingWindow {
+            limit,
+            window,
+            attempts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Removes keys with no attempts inside the window, bounding memory usage
+    pub fn purge(&self) -> Result<(), RateLimitError> {
+        let now = Instant::now();
+        let mut attempts = self.lock()?;
+        attempts.retain(|_, times| {
+            self.expire(times, now);
+            !times.is_empty()
+        });
+        Ok(())
+    }
+
+    fn check_at(&self, key: &RateLimitKey, now: Instant) -> Result<(), RateLimitError> {
+        let mut attempts = self.lock()?;
+        let times = attempts.entry(key.clone()).or_default();
+        self.expire(times, now);
+
+        if times.len() < self.limit as usize {
+            times.push_back(now);
+            return Ok(());
+        }
+
+        let retry_after = times
+            .front()
+            .map(|oldest| (*oldest + self.window).saturating_duration_since(now))
+            .unwrap_or(self.window);
+        Err(RateLimitError::Limited { retry_after })
+    }
+
+    /// Drops attempts that have fallen out of the window
+    fn expire(&self, times: &mut VecDeque<Instant>, now: Instant) {
+        while let Some(oldest) = times.front() {
+            if now.saturating_duration_since(*oldest) >= self.window {
+                times.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn lock(
+        &self,
+    ) -> Result<std::sync::MutexGuard<'_, HashMap<RateLimitKey, VecDeque<Instant>>>, RateLimitError>
+    {
+        self.attempts
+            .lock()
+            .map_err(|_| RateLimitError::backend("poisoned lock"))
+    }
+}
+
+impl RateLimiter for SlidingWindow {
+    fn check(&self, key: &RateLimitKey) -> Result<(), RateLimitError> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn reset(&self, key: &RateLimitKey) -> Result<(), RateLimitError> {
+        self.lock()?.remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> RateLimitKey {
+        RateLimitKey::User(vec![0, 1, 2, 3])
+    }
+
+    #[test]
+    fn token_bucket_refills() {
+        let limiter = TokenBucket::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+
+        limiter.check_at(&user(), start).unwrap();
+        limiter.check_at(&user(), start).unwrap();
+        assert!(matches!(
+            limiter.check_at(&user(), start),
+            Err(RateLimitError::Limited { retry_after }) if retry_after == Duration::from_secs(10)
+        ));
+
+        limiter
+            .check_at(&user(), start + Duration::from_secs(10))
+            .unwrap();
+        limiter.reset(&user()).unwrap();
+        limiter.check_at(&user(), start).unwrap();
+    }
+
+    #[test]
+    fn sliding_window_expires_attempts() {
+        let limiter = SlidingWindow::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        limiter.check_at(&user(), start).unwrap();
+        limiter
+            .check_at(&user(), start + Duration::from_secs(30))
+            .unwrap();
+        assert!(matches!(
+            limiter.check_at(&user(), start + Duration::from_secs(40)),
+            Err(RateLimitError::Limited { retry_after }) if retry_after == Duration::from_secs(20)
+        ));
+
+        limiter
+            .check_at(&user(), start + Duration::from_secs(60))
+            .unwrap();
+    }
+
+    #[test]
+    fn attempts_count_against_user_and_address() {
+        let limiter = SlidingWindow::new(1, Duration::from_secs(60));
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+
+        limiter.check_attempt(Some(&[1]), Some(ip)).unwrap();
+        assert!(limiter.check_attempt(Some(&[2]), Some(ip)).is_err());
+        assert!(limiter.check_attempt(Some(&[1]), None).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Single-use recovery codes
+//!
+//! Recovery codes let a user sign in when their other factors (e.g., a lost security key)
+//! are unavailable.  Each code is 50 random bits formatted as two groups of five
+//! [Crockford base32](https://www.crockford.com/base32.html) characters (`7K3QZ-M1XPD`).
+//! Only hashes (computed with the password [`Hasher`]) are stored in a [`RecoverySet`],
+//! and each code is burned after it is used once.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::recovery::RecoveryCodes;
+//!
+//! let codes = RecoveryCodes::new(10);
+//!
+//! // show `plain` to the user once, store `set`
+//! let (plain, mut set) = codes.generate()?;
+//!
+//! // when a code is presented, then save the updated set
+//! codes.verify(&mut set, "7k3qz-m1xpd")?;
+//! ```
+
+use crate::{
+    password::{Hasher, HasherError},
+    time::now,
+};
+use rand::RngCore;
+#[cfg(feature = "secrecy")]
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Crockford base32 alphabet
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Number of characters in a group
+const GROUP_LEN: usize = 5;
+
+/// Default number of codes generated
+pub const DEFAULT_COUNT: usize = 10;
+
+#[derive(Error, Debug)]
+pub enum RecoveryError {
+    #[error("recovery code is invalid or has already been used")]
+    Invalid,
+
+    #[error("failed to hash recovery code: {0}")]
+    Hasher(#[from] HasherError),
+}
+
+/// A stored recovery code
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+struct StoredCode {
+    /// Encoded hash of the normalized code
+    hash: String,
+
+    /// Time (seconds since the unix epoch) the code was used, if used
+    used: Option<u64>,
+}
+
+/// The stored (hashed) recovery codes of a user
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RecoverySet {
+    /// Hashed codes
+    codes: Vec<StoredCode>,
+
+    /// Time (seconds since the unix epoch) the codes were generated
+    created: u64,
+}
+
+impl RecoverySet {
+    /// Returns the time (seconds since the unix epoch) the codes were generated
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    /// Returns the number of codes that have not been used
+    pub fn remaining(&self) -> usize {
+        self.codes.iter().filter(|c| c.used.is_none()).count()
+    }
+
+    /// Returns true if every code has been used
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+/// Generates and verifies recovery codes
+pub struct RecoveryCodes {
+    /// Number of codes to generate
+    count: usize,
+
+    /// Hasher used to hash codes
+    hasher: Hasher,
+}
+
+impl RecoveryCodes {
+    /// Creates a new generator hashing codes with the default [`Hasher`]
+    ///
+    /// # Arguments
+    /// * `count` - Number of codes to generate
+    pub fn new(count: usize) -> RecoveryCodes {
+        RecoveryCodes {
+            count,
+            hasher: Hasher::default(),
+        }
+    }
+
+    /// Sets the hasher used to hash codes
+    pub fn hasher(mut self, hasher: Hasher) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// Generates a new set of codes, returning the plain codes to show the user and the
+    /// hashed set to store
+    pub fn generate(&self) -> Result<(Vec<String>, RecoverySet), RecoveryError> {
+        let mut plain = Vec::with_capacity(self.count);
+        let mut codes = Vec::with_capacity(self.count);
+        for _ in 0..self.count {
+            let code = new_code();
+            codes.push(StoredCode {
+                hash: self.hasher.hash(normalize(&code))?,
+                used: None,
+            });
+            plain.push(code);
+        }
+
+        let set = RecoverySet {
+            codes,
+            created: now(),
+        };
+
+        Ok((plain, set))
+    }
+
+    /// Replaces every code in a set (used or not) with newly generated codes, returning
+    /// the plain codes to show the user
+    ///
+    /// # Arguments
+    /// * `set` - Set to regenerate
+    pub fn regenerate(&self, set: &mut RecoverySet) -> Result<Vec<String>, RecoveryError> {
+        let (plain, new) = self.generate()?;
+        *set = new;
+        Ok(plain)
+    }
+
+    /// Verifies a presented code, burning it if it matches an unused code.  The set must
+    /// be saved afterwards for the code to remain burned
+    ///
+    /// # Arguments
+    /// * `set` - Stored codes of the user
+    /// * `code` - Code presented by the user (case, dashes and spaces are ignored)
+    pub fn verify(&self, set: &mut RecoverySet, code: &str) -> Result<(), RecoveryError> {
+        let code = normalize(code);
+
+        // check every code so the time taken doesn't reveal which code matched
+        let mut matched = None;
+        for (i, stored) in set.codes.iter().enumerate() {
+            let valid = self.hasher.verify(&code, &stored.hash).is_ok();
+            if valid && stored.used.is_none() && matched.is_none() {
+                matched = Some(i);
+            }
+        }
+
+        let i = matched.ok_or(RecoveryError::Invalid)?;
+        set.codes[i].used = Some(now());
+        Ok(())
+    }
+
+    /// Verifies a presented code held in a `SecretString`, burning it if it matches an
+    /// unused code
+    ///
+    /// # Arguments
+    /// * `set` - Stored codes of the user
+    /// * `code` - Code presented by the user
+    #[cfg(feature = "secrecy")]
+    pub fn verify_secret(
+        &self,
+        set: &mut RecoverySet,
+        code: &SecretString,
+    ) -> Result<(), RecoveryError> {
+        self.verify(set, code.expose_secret())
+    }
+}
+
+impl Default for RecoveryCodes {
+    fn default() -> Self {
+        RecoveryCodes::new(DEFAULT_COUNT)
+    }
+}
+
+/// Generates a random, formatted code
+fn new_code() -> String {
+    let mut bytes = [0u8; GROUP_LEN * 2];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    let mut code = String::with_capacity(bytes.len() + 1);
+    for (i, b) in bytes.iter().enumerate() {
+        if i == GROUP_LEN {
+            code.push('-');
+        }
+        // 256 is a multiple of 32, so masking keeps the distribution uniform
+        code.push(ALPHABET[(b & 0x1f) as usize] as char);
+    }
+    code
+}
+
+/// Normalizes a code as presented by a user: separators are removed, letters upper-cased
+/// and the characters Crockford base32 treats as ambiguous are mapped to their digits
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_formatted() {
+        let code = new_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(code.as_bytes()[5], b'-');
+        assert!(code
+            .bytes()
+            .filter(|b| *b != b'-')
+            .all(|b| ALPHABET.contains(&b)));
+        assert_eq!(normalize("7k3qz-m1xpo"), "7K3QZM1XP0");
+    }
+
+    #[test]
+    fn codes_are_burned_on_use() {
+        let codes = RecoveryCodes::new(3);
+        let (plain, mut set) = codes.generate().unwrap();
+        assert_eq!(set.remaining(), 3);
+
+        codes.verify(&mut set, &plain[1].to_lowercase()).unwrap();
+        assert_eq!(set.remaining(), 2);
+        assert!(codes.verify(&mut set, &plain[1]).is_err());
+        assert!(codes.verify(&mut set, "00000-00000").is_err());
+
+        let plain = codes.regenerate(&mut set).unwrap();
+        assert_eq!(set.remaining(), 3);
+        codes.verify(&mut set, &plain[0]).unwrap();
+    }
+}
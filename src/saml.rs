@@ -0,0 +1,675 @@
+//! SAML 2.0 service provider response validation
+//!
+//! [`ServiceProvider`] validates the `<samlp:Response>` an identity provider posts to the
+//! assertion consumer service (the HTTP-POST binding) and returns the authenticated
+//! [`Assertion`].  A response is only accepted when:
+//!
+//! * the response or its assertion carries a valid enveloped XML signature by one of the
+//!   identity provider's certificates
+//! * the status is `Success` and the issuer is the identity provider
+//! * the destination, subject confirmation recipient and audience name this service
+//!   provider
+//! * `InResponseTo` matches the id of the request the service provider sent (or is absent,
+//!   if unsolicited responses are allowed)
+//! * the current time is within the assertion's conditions and subject confirmation window
+//!
+//! Only the signed elements themselves are ever read, so values smuggled in beside a
+//! signed element (XML signature wrapping) are never returned.  Encrypted assertions are
+//! not supported.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::saml::ServiceProvider;
+//!
+//! let sp = ServiceProvider::new(
+//!     "https://sp.example.com",
+//!     "https://sp.example.com/saml/acs",
+//!     "https://idp.example.com",
+//! )
+//! .add_certificate(idp_cert_der)?;
+//!
+//! // `SAMLResponse` form field posted to the assertion consumer service
+//! let assertion = sp.validate(&form.saml_response, Some(&session.request_id))?;
+//! let email = assertion.attribute("email");
+//! ```
+
+mod signature;
+mod xml;
+
+use self::xml::Element;
+use crate::time::now;
+use std::convert::TryFrom;
+use thiserror::Error;
+
+/// SAML protocol namespace
+const PROTOCOL_NS: &str = "urn:oasis:names:tc:SAML:2.0:protocol";
+
+/// SAML assertion namespace
+const ASSERTION_NS: &str = "urn:oasis:names:tc:SAML:2.0:assertion";
+
+/// Status code of a successful response
+const SUCCESS: &str = "urn:oasis:names:tc:SAML:2.0:status:Success";
+
+/// Subject confirmation method of the web browser SSO profile
+const BEARER: &str = "urn:oasis:names:tc:SAML:2.0:cm:bearer";
+
+#[derive(Error, Debug)]
+pub enum SamlError {
+    #[error("malformed SAML response: {0}")]
+    Malformed(&'static str),
+
+    #[error("SAML response is not signed")]
+    Unsigned,
+
+    #[error("invalid SAML signature: {0}")]
+    InvalidSignature(&'static str),
+
+    #[error("invalid identity provider certificate: {0}")]
+    Certificate(#[from] webpki::Error),
+
+    #[error("identity provider returned status `{0}`")]
+    Status(String),
+
+    #[error("SAML response was not issued by the identity provider")]
+    InvalidIssuer,
+
+    #[error("SAML response is not intended for this service provider")]
+    InvalidAudience,
+
+    #[error("SAML response was sent to a different destination")]
+    InvalidDestination,
+
+    #[error("SAML response does not answer the expected request")]
+    InResponseToMismatch,
+
+    #[error("SAML assertion is not yet valid")]
+    NotYetValid,
+
+    #[error("SAML assertion has expired")]
+    Expired,
+
+    #[error("encrypted SAML assertions are not supported")]
+    EncryptedAssertion,
+}
+
+/// The authenticated subject of a validated response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Assertion {
+    /// Entity id of the identity provider
+    issuer: String,
+
+    /// Identifier of the authenticated user
+    name_id: String,
+
+    /// Format of the name identifier, if specified
+    name_id_format: Option<String>,
+
+    /// Identity provider's session index, used for single logout
+    session_index: Option<String>,
+
+    /// Attributes asserted about the user, in document order
+    attributes: Vec<(String, Vec<String>)>,
+}
+
+impl Assertion {
+    /// Returns the entity id of the identity provider
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Returns the identifier of the authenticated user
+    pub fn name_id(&self) -> &str {
+        &self.name_id
+    }
+
+    /// Returns the format of the name identifier (e.g.,
+    /// `urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress`), if specified
+    pub fn name_id_format(&self) -> Option<&str> {
+        self.name_id_format.as_deref()
+    }
+
+    /// Returns the identity provider's session index, if specified
+    pub fn session_index(&self) -> Option<&str> {
+        self.session_index.as_deref()
+    }
+
+    /// Returns the first value of an attribute, if present
+    ///
+    /// # Arguments
+    /// * `name` - Name of the attribute
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes(name)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    }
+
+    /// Returns all values of an attribute, if present
+    ///
+    /// # Arguments
+    /// * `name` - Name of the attribute
+    pub fn attributes(&self, name: &str) -> Option<&[String]> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, values)| values.as_slice())
+    }
+}
+
+/// Validates responses from a single identity provider
+#[derive(Clone, Debug)]
+pub struct ServiceProvider {
+    /// Entity id of this service provider, expected as the audience
+    entity_id: String,
+
+    /// Url of this service provider's assertion consumer service
+    acs_url: String,
+
+    /// Entity id of the identity provider
+    idp_entity_id: String,
+
+    /// DER-encoded signing certificates of the identity provider
+    certificates: Vec<Vec<u8>>,
+
+    /// Clock skew tolerated, in seconds
+    clock_skew: u64,
+
+    /// Accept responses that don't answer a request (IdP-initiated SSO)
+    allow_unsolicited: bool,
+}
+
+impl ServiceProvider {
+    /// Creates a service provider that trusts no certificates
+    ///
+    /// # Arguments
+    /// * `entity_id` - Entity id of this service provider
+    /// * `acs_url` - Url of this service provider's assertion consumer service
+    /// * `idp_entity_id` - Entity id of the identity provider
+    pub fn new<E, A, I>(entity_id: E, acs_url: A, idp_entity_id: I) -> ServiceProvider
+    where
+        E: Into<String>,
+        A: Into<String>,
+        I: Into<String>,
+    {
+        ServiceProvider {
+            entity_id: entity_id.into(),
+            acs_url: acs_url.into(),
+            idp_entity_id: idp_entity_id.into(),
+            certificates: vec![],
+            clock_skew: 180,
+            allow_unsolicited: false,
+        }
+    }
+
+    /// Trusts a signing certificate of the identity provider
+    ///
+    /// # Arguments
+    /// * `der` - DER-encoded certificate, as found in the identity provider's metadata
+    pub fn add_certificate(mut self, der: Vec<u8>) -> Result<Self, SamlError> {
+        webpki::EndEntityCert::from(&der)?;
+        self.certificates.push(der);
+        Ok(self)
+    }
+
+    /// Sets the clock skew tolerated when checking validity windows, in seconds
+    pub fn clock_skew(mut self, clock_skew: u64) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    /// Accepts responses sent without a request (IdP-initiated SSO), which are more
+    /// exposed to replay
+    pub fn allow_unsolicited(mut self, allow: bool) -> Self {
+        self.allow_unsolicited = allow;
+        self
+    }
+
+    /// Validates a base64-encoded response, as posted in the `SAMLResponse` form field
+    ///
+    /// # Arguments
+    /// * `response` - Base64-encoded response
+    /// * `request_id` - Id of the `AuthnRequest` the response answers, if one was sent
+    pub fn validate(
+        &self,
+        response: &str,
+        request_id: Option<&str>,
+    ) -> Result<Assertion, SamlError> {
+        let response: String = response.split_whitespace().collect();
+        let xml = base64::decode(response)
+            .ok()
+            .and_then(|xml| String::from_utf8(xml).ok())
+            .ok_or(SamlError::Malformed("invalid base64"))?;
+        self.validate_xml(&xml, request_id)
+    }
+
+    /// Validates an XML response
+    ///
+    /// # Arguments
+    /// * `xml` - Response document
+    /// * `request_id` - Id of the `AuthnRequest` the response answers, if one was sent
+    pub fn validate_xml(
+        &self,
+        xml: &str,
+        request_id: Option<&str>,
+    ) -> Result<Assertion, SamlError> {
+        self.validate_at(xml, request_id, now())
+    }
+
+    /// Validates an XML response at a time (seconds since the unix epoch)
+    fn validate_at(
+        &self,
+        xml: &str,
+        request_id: Option<&str>,
+        now: u64,
+    ) -> Result<Assertion, SamlError> {
+        let response = xml::parse(xml)?;
+        if !response.is(PROTOCOL_NS, "Response") {
+            return Err(SamlError::Malformed("expected a Response"));
+        }
+
+        // signature references are by ID, so every ID must be unique
+        let mut ids = vec![];
+        response.walk(&mut |e| ids.extend(e.attr("ID")));
+        ids.sort_unstable();
+        if ids.windows(2).any(|w| w[0] == w[1]) {
+            return Err(SamlError::Malformed("duplicate ID"));
+        }
+
+        if response.child(ASSERTION_NS, "EncryptedAssertion").is_some() {
+            return Err(SamlError::EncryptedAssertion);
+        }
+        let mut assertions = response.children(ASSERTION_NS, "Assertion");
+        let assertion = match (assertions.next(), assertions.next()) {
+            (Some(assertion), None) => assertion,
+            _ => return Err(SamlError::Malformed("expected exactly one assertion")),
+        };
+
+        // the assertion is read from the verified tree, so a signed response covers it
+        let response_signed = signature::is_signed(&response);
+        let assertion_signed = signature::is_signed(assertion);
+        if response_signed {
+            signature::verify(&response, &self.certificates)?;
+        }
+        if assertion_signed {
+            signature::verify(assertion, &self.certificates)?;
+        }
+        if !response_signed && !assertion_signed {
+            return Err(SamlError::Unsigned);
+        }
+
+        self.check_response(&response, request_id)?;
+        self.check_assertion(assertion, request_id, now)
+    }
+
+    /// Checks the response's destination, status, issuer and `InResponseTo`
+    fn check_response(
+        &self,
+        response: &Element,
+        request_id: Option<&str>,
+    ) -> Result<(), SamlError> {
+        if let Some(destination) = response.attr("Destination") {
+            if destination != self.acs_url {
+                return Err(SamlError::InvalidDestination);
+            }
+        }
+
+        let status = response
+            .child(PROTOCOL_NS, "Status")
+            .and_then(|s| s.child(PROTOCOL_NS, "StatusCode"))
+            .and_then(|c| c.attr("Value"))
+            .ok_or(SamlError::Malformed("missing status"))?;
+        if status != SUCCESS {
+            return Err(SamlError::Status(status.to_owned()));
+        }
+
+        if let Some(issuer) = response.child(ASSERTION_NS, "Issuer") {
+            if issuer.text().trim() != self.idp_entity_id {
+                return Err(SamlError::InvalidIssuer);
+            }
+        }
+
+        self.check_in_response_to(response.attr("InResponseTo"), request_id)
+    }
+
+    /// Checks the assertion's issuer, subject and conditions, and extracts its contents
+    fn check_assertion(
+        &self,
+        assertion: &Element,
+        request_id: Option<&str>,
+        now: u64,
+    ) -> Result<Assertion, SamlError> {
+        let issuer = assertion
+            .child(ASSERTION_NS, "Issuer")
+            .map(|i| i.text().trim().to_owned())
+            .ok_or(SamlError::Malformed("missing issuer"))?;
+        if issuer != self.idp_entity_id {
+            return Err(SamlError::InvalidIssuer);
+        }
+
+        let subject = assertion
+            .child(ASSERTION_NS, "Subject")
+            .ok_or(SamlError::Malformed("missing subject"))?;
+        let name_id = subject
+            .child(ASSERTION_NS, "NameID")
+            .ok_or(SamlError::Malformed("missing name id"))?;
+
+        // at least one bearer confirmation must be satisfied
+        let mut confirmations = subject
+            .children(ASSERTION_NS, "SubjectConfirmation")
+            .filter(|c| c.attr("Method") == Some(BEARER))
+            .map(|c| self.check_confirmation(c, request_id, now))
+            .peekable();
+        if confirmations.peek().is_none() {
+            return Err(SamlError::Malformed("missing bearer subject confirmation"));
+        }
+        let mut error = None;
+        for result in confirmations {
+            match result {
+                Ok(()) => {
+                    error = None;
+                    break;
+                }
+                Err(e) => error = error.or(Some(e)),
+            }
+        }
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        let conditions = assertion
+            .child(ASSERTION_NS, "Conditions")
+            .ok_or(SamlError::Malformed("missing conditions"))?;
+        self.check_window(conditions, now)?;
+
+        let mut restrictions = conditions
+            .children(ASSERTION_NS, "AudienceRestriction")
+            .peekable();
+        if restrictions.peek().is_none() {
+            return Err(SamlError::InvalidAudience);
+        }
+        for restriction in restrictions {
+            if !restriction
+                .children(ASSERTION_NS, "Audience")
+                .any(|a| a.text().trim() == self.entity_id)
+            {
+                return Err(SamlError::InvalidAudience);
+            }
+        }
+
+        let session_index = assertion
+            .child(ASSERTION_NS, "AuthnStatement")
+            .and_then(|s| s.attr("SessionIndex"))
+            .map(str::to_owned);
+
+        let attributes = assertion
+            .children(ASSERTION_NS, "AttributeStatement")
+            .flat_map(|s| s.children(ASSERTION_NS, "Attribute"))
+            .filter_map(|attr| {
+                let values = attr
+                    .children(ASSERTION_NS, "AttributeValue")
+                    .map(|v| v.text())
+                    .collect();
+                attr.attr("Name").map(|name| (name.to_owned(), values))
+            })
+            .collect();
+
+        Ok(Assertion {
+            issuer,
+            name_id: name_id.text().trim().to_owned(),
+            name_id_format: name_id.attr("Format").map(str::to_owned),
+            session_index,
+            attributes,
+        })
+    }
+
+    /// Checks a bearer subject confirmation's recipient, window and `InResponseTo`
+    fn check_confirmation(
+        &self,
+        confirmation: &Element,
+        request_id: Option<&str>,
+        now: u64,
+    ) -> Result<(), SamlError> {
+        let data = confirmation
+            .child(ASSERTION_NS, "SubjectConfirmationData")
+            .ok_or(SamlError::Malformed("missing subject confirmation data"))?;
+
+        if data.attr("Recipient") != Some(self.acs_url.as_str()) {
+            return Err(SamlError::InvalidDestination);
+        }
+        if data.attr("NotOnOrAfter").is_none() {
+            return Err(SamlError::Malformed("subject confirmation does not expire"));
+        }
+
+        self.check_window(data, now)?;
+        self.check_in_response_to(data.attr("InResponseTo"), request_id)
+    }
+
+    /// Checks the `NotBefore` and `NotOnOrAfter` attributes of an element
+    fn check_window(&self, element: &Element, now: u64) -> Result<(), SamlError> {
+        if let Some(not_before) = element.attr("NotBefore") {
+            if parse_time(not_before)? > now + self.clock_skew {
+                return Err(SamlError::NotYetValid);
+            }
+        }
+
+        if let Some(not_on_or_after) = element.attr("NotOnOrAfter") {
+            if parse_time(not_on_or_after)? + self.clock_skew <= now {
+                return Err(SamlError::Expired);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks an `InResponseTo` attribute against the id of the request sent
+    fn check_in_response_to(
+        &self,
+        in_response_to: Option<&str>,
+        request_id: Option<&str>,
+    ) -> Result<(), SamlError> {
+        match (in_response_to, request_id) {
+            (Some(actual), Some(expected)) if actual == expected => Ok(()),
+            (None, None) if self.allow_unsolicited => Ok(()),
+            _ => Err(SamlError::InResponseToMismatch),
+        }
+    }
+}
+
+/// Parses an `xs:dateTime` (e.g., `2025-01-01T00:00:00.000Z`) into seconds since the unix
+/// epoch
+fn parse_time(value: &str) -> Result<u64, SamlError> {
+    let invalid = || SamlError::Malformed("invalid timestamp");
+    let num = |s: &str| s.parse::<i64>().map_err(|_| invalid());
+
+    let (date, time) = value.split_once('T').ok_or_else(invalid)?;
+    let mut date = date.splitn(3, '-');
+    let (year, month, day) = match (date.next(), date.next(), date.next()) {
+        (Some(y), Some(m), Some(d)) => (num(y)?, num(m)?, num(d)?),
+        _ => return Err(invalid()),
+    };
+
+    // split off the timezone, which is `Z` or `+hh:mm`/`-hh:mm`
+    let (time, offset) = match time.strip_suffix('Z') {
+        Some(time) => (time, 0),
+        None if time.len() > 6 => {
+            let (time, tz) = time.split_at(time.len() - 6);
+            let sign = match &tz[..1] {
+                "+" => 1,
+                "-" => -1,
+                _ => return Err(invalid()),
+            };
+            let (h, m) = tz[1..].split_once(':').ok_or_else(invalid)?;
+            (time, sign * (num(h)? * 3600 + num(m)? * 60))
+        }
+        None => return Err(invalid()),
+    };
+
+    let time = time.split('.').next().unwrap_or(time);
+    let mut time = time.splitn(3, ':');
+    let (hour, minute, second) = match (time.next(), time.next(), time.next()) {
+        (Some(h), Some(m), Some(s)) => (num(h)?, num(m)?, num(s)?),
+        _ => return Err(invalid()),
+    };
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return Err(invalid());
+    }
+
+    // days from the civil date (Howard Hinnant's algorithm)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    u64::try_from(secs).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Response with an assertion signed (RSA-SHA256) by `CERT`
+    const RESPONSE: &str = "\
+        <samlp:Response xmlns:samlp=\"urn:oasis:names:tc:SAML:2.0:protocol\" xmlns:saml=\"urn:oasis\
+        :names:tc:SAML:2.0:assertion\" Destination=\"https://sp.example.com/acs\" ID=\"_resp1\" InRes\
+        ponseTo=\"_req1\" IssueInstant=\"2025-01-01T00:00:00Z\" Version=\"2.0\"><saml:Issuer>https://i\
+        dp.example.com</saml:Issuer><samlp:Status><samlp:StatusCode Value=\"urn:oasis:names:tc:SA\
+        ML:2.0:status:Success\"/></samlp:Status><saml:Assertion xmlns:saml=\"urn:oasis:names:tc:SA\
+        ML:2.0:assertion\" ID=\"_assert1\" IssueInstant=\"2025-01-01T00:00:00Z\" Version=\"2.0\"><saml:\
+        Issuer>https://idp.example.com</saml:Issuer><ds:Signature xmlns:ds=\"http://www.w3.org/20\
+        00/09/xmldsig#\"><ds:SignedInfo><ds:CanonicalizationMethod Algorithm=\"http://www.w3.org/2\
+        001/10/xml-exc-c14n#\"></ds:CanonicalizationMethod><ds:SignatureMethod Algorithm=\"http://\
+        www.w3.org/2001/04/xmldsig-more#rsa-sha256\"></ds:SignatureMethod><ds:Reference URI=\"#_as\
+        sert1\"><ds:Transforms><ds:Transform Algorithm=\"http://www.w3.org/2000/09/xmldsig#envelop\
+        ed-signature\"></ds:Transform><ds:Transform Algorithm=\"http://www.w3.org/2001/10/xml-exc-\
+        c14n#\"></ds:Transform></ds:Transforms><ds:DigestMethod Algorithm=\"http://www.w3.org/2001\
+        /04/xmlenc#sha256\"></ds:DigestMethod><ds:DigestValue>UjRo3fCYBOujoDMJwoy0TI1QrKkSVSB866/\
+        kCVCX39Q=</ds:DigestValue></ds:Reference></ds:SignedInfo><ds:SignatureValue>VRfN94LMgrHk\
+        BvFL6HSOydxPJpK5y5MdZkjJtYWReSpSvQuicoDZD1nh+DwCUA2PjKW8DZBdssIks/8aNoovcf+ksbmFI2V7Kn+H\
+        +N5WecAIgg8VT9jCGEkK1KNdr5isfeLcwEUto6k7fIVMSlozuM1iFK9kihDToYn8QMMXQGSwXcbUywEuIQmO2Prm\
+        OTi2mXXwxKNRYxo2kTpqo9BL7waQRmVg8xEvcq2l5xJaya2N+fcUy/687pboQA045amPJXlr0F6Ac3EmSjQMLy+Y\
+        3y5SmJjlAvlc00X9QuYHgWEN6drHYGMgWW0/UJwy+Bi/C8uezYOoaC2QHmlApdHreg==</ds:SignatureValue>\
+        </ds:Signature><saml:Subject><saml:NameID Format=\"urn:oasis:names:tc:SAML:1.1:nameid-for\
+        mat:emailAddress\">alice@example.com</saml:NameID><saml:SubjectConfirmation Method=\"urn:o\
+        asis:names:tc:SAML:2.0:cm:bearer\"><saml:SubjectConfirmationData InResponseTo=\"_req1\" Not\
+        OnOrAfter=\"2025-01-01T00:05:00Z\" Recipient=\"https://sp.example.com/acs\"></saml:SubjectCo\
+        nfirmationData></saml:SubjectConfirmation></saml:Subject><saml:Conditions NotBefore=\"202\
+        4-12-31T23:59:00Z\" NotOnOrAfter=\"2025-01-01T00:05:00Z\"><saml:AudienceRestriction><saml:A\
+        udience>https://sp.example.com</saml:Audience></saml:AudienceRestriction></saml:Conditio\
+        ns><saml:AuthnStatement AuthnInstant=\"2025-01-01T00:00:00Z\" SessionIndex=\"_session1\"><sa\
+        ml:AuthnContext><saml:AuthnContextClassRef>urn:oasis:names:tc:SAML:2.0:ac:classes:Passwo\
+        rdProtectedTransport</saml:AuthnContextClassRef></saml:AuthnContext></saml:AuthnStatemen\
+        t><saml:AttributeStatement><saml:Attribute Name=\"email\"><saml:AttributeValue>alice@examp\
+        le.com</saml:AttributeValue></saml:Attribute><saml:Attribute Name=\"groups\"><saml:Attribu\
+        teValue>admins</saml:AttributeValue><saml:AttributeValue>staff</saml:AttributeValue></sa\
+        ml:Attribute></saml:AttributeStatement></saml:Assertion></samlp:Response>";
+
+    /// Self-signed RSA certificate of the identity provider
+    const CERT: &str = "\
+        MIIDEjCCAfqgAwIBAgIUCSBF6DGYiVZI/WndmiPs7dODRi8wDQYJKoZIhvcNAQELBQAwGjEYMBYGA1UEAwwPaWRw\
+        LmV4YW1wbGUuY29tMB4XDTI2MTAxNjEyMTExMFoXDTQ2MTAxMTEyMTExMFowGjEYMBYGA1UEAwwPaWRwLmV4YW1w\
+        bGUuY29tMIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEArpyR32f1g/zpV3tPYYyUoJ8jItDE39TA1aqX\
+        T9kgT6Rr9alCFGKVEAGM+FXpvnF7bijMNcz6shzYT1qdrTs4d7cgfSR5hLSIoa+s2PO4GWQpiqVM70yA/2wrasTs\
+        RG5Yi4uM19DQzLTwW1YZ9NGQA19sRoUJ6rdlevq1/aapH6kquSjScy3phnvPTlEH8UnSJtnOJvzoAamafg83RMFC\
+        qxdov0ObRQiO3KcwhTj9/Y1LZSGCEUiR2mRbrtA28QWVzP4vA1TAAiAnavlXI21BKH6Q03/9QKPivh2s8jXCDeMX\
+        pccQ3O+IG7sNvFv/2V2Yub2Tl+dK5HF9WqRzmdfLoQIDAQABo1AwTjAdBgNVHQ4EFgQU5vKUs2C/JWbAJRUTsIMg\
+        dohyICUwHwYDVR0jBBgwFoAU5vKUs2C/JWbAJRUTsIMgdohyICUwDAYDVR0TAQH/BAIwADANBgkqhkiG9w0BAQsF\
+        AAOCAQEACXXRaWe0+VHCLKtAq7xoN8iCIF6NnKbvPdpshsyDReZytcoEvCAPc3wZH9RL9+CaIjKfFVDtXys4DYQC\
+        2arTJYtP4ebe7XMpaRBKqj07CjKzqRSyvbsYGwF1EQSrM4otp9u7MT97lcfLo1Xqe0ZEaJ4dDtImIsitg18Aeod7\
+        ZcbTjIXNpiAOHG/WZu0NxPacbJYaR00KJE6PNpcNctbrquk9cJiLMRXLC7irReq/c0PsgwUHkAo3QRNDkeUFYa4G\
+        ezo2Yd16Inzyo86LrE2XyT2ajmvuBCFLlB14kKsVUlUlwr3rsIZnid+ScCz0iupheWTKwQjgCZ4O487pwk9Mag==";
+
+    /// 2025-01-01T00:01:00Z
+    const NOW: u64 = 1_735_689_660;
+
+    fn provider(entity_id: &str) -> ServiceProvider {
+        ServiceProvider::new(
+            entity_id,
+            "https://sp.example.com/acs",
+            "https://idp.example.com",
+        )
+        .add_certificate(base64::decode(CERT).unwrap())
+        .unwrap()
+    }
+
+    #[test]
+    fn signed_response_is_accepted() {
+        let assertion = provider("https://sp.example.com")
+            .validate_at(RESPONSE, Some("_req1"), NOW)
+            .unwrap();
+
+        assert_eq!(assertion.issuer(), "https://idp.example.com");
+        assert_eq!(assertion.name_id(), "alice@example.com");
+        assert_eq!(
+            assertion.name_id_format(),
+            Some("urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress")
+        );
+        assert_eq!(assertion.session_index(), Some("_session1"));
+        assert_eq!(assertion.attribute("email"), Some("alice@example.com"));
+        assert_eq!(
+            assertion.attributes("groups").unwrap(),
+            ["admins".to_owned(), "staff".to_owned()]
+        );
+    }
+
+    #[test]
+    fn tampered_response_is_rejected() {
+        let sp = provider("https://sp.example.com");
+        let tampered = RESPONSE.replacen("admins", "root", 1);
+        assert!(matches!(
+            sp.validate_at(&tampered, Some("_req1"), NOW),
+            Err(SamlError::InvalidSignature("digest mismatch"))
+        ));
+
+        let start = RESPONSE.find("<ds:Signature").unwrap();
+        let end = RESPONSE.find("</ds:Signature>").unwrap() + "</ds:Signature>".len();
+        let unsigned = format!("{}{}", &RESPONSE[..start], &RESPONSE[end..]);
+        assert!(matches!(
+            sp.validate_at(&unsigned, Some("_req1"), NOW),
+            Err(SamlError::Unsigned)
+        ));
+    }
+
+    #[test]
+    fn conditions_are_enforced() {
+        let sp = provider("https://sp.example.com");
+        assert!(matches!(
+            sp.validate_at(RESPONSE, Some("_req2"), NOW),
+            Err(SamlError::InResponseToMismatch)
+        ));
+        assert!(matches!(
+            sp.validate_at(RESPONSE, None, NOW),
+            Err(SamlError::InResponseToMismatch)
+        ));
+        assert!(matches!(
+            sp.validate_at(RESPONSE, Some("_req1"), NOW + 3600),
+            Err(SamlError::Expired)
+        ));
+        assert!(matches!(
+            sp.validate_at(RESPONSE, Some("_req1"), NOW - 3600),
+            Err(SamlError::NotYetValid)
+        ));
+        assert!(matches!(
+            provider("https://other.example.com").validate_at(RESPONSE, Some("_req1"), NOW),
+            Err(SamlError::InvalidAudience)
+        ));
+    }
+
+    #[test]
+    fn timestamps_are_parsed() {
+        assert_eq!(parse_time("2025-01-01T00:00:00Z").unwrap(), 1_735_689_600);
+        assert_eq!(
+            parse_time("2025-01-01T00:00:00.123Z").unwrap(),
+            1_735_689_600
+        );
+        assert_eq!(
+            parse_time("2025-01-01T01:00:00+01:00").unwrap(),
+            1_735_689_600
+        );
+        assert!(parse_time("2025-01-01 00:00:00").is_err());
+    }
+}
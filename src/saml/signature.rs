@@ -0,0 +1,144 @@
+//! Verification of enveloped XML signatures ([xmldsig-core](https://www.w3.org/TR/xmldsig-core1/))
+//!
+//! Only the profile SAML uses is accepted: a single reference to the signed element by
+//! its `ID`, the enveloped-signature and exclusive canonicalization transforms, SHA-256 or
+//! SHA-512 digests and RSA signatures.  Anything else fails verification.
+
+use super::{
+    xml::{self, Element},
+    SamlError,
+};
+use ring::{constant_time, digest};
+use webpki::{EndEntityCert, SignatureAlgorithm};
+
+/// XML signature namespace
+pub(crate) const DSIG_NS: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+/// Exclusive canonicalization, without comments
+const EXC_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+
+/// Enveloped signature transform
+const ENVELOPED: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+
+/// Returns true if an element carries an enveloped signature
+pub(crate) fn is_signed(element: &Element) -> bool {
+    element.child(DSIG_NS, "Signature").is_some()
+}
+
+/// Verifies the enveloped signature of an element against trusted certificates
+///
+/// # Arguments
+/// * `element` - Signed element, whose `ID` the signature must reference
+/// * `certificates` - DER-encoded certificates of the identity provider
+pub(crate) fn verify(element: &Element, certificates: &[Vec<u8>]) -> Result<(), SamlError> {
+    let invalid = SamlError::InvalidSignature;
+    let signature = element
+        .child(DSIG_NS, "Signature")
+        .ok_or(SamlError::Unsigned)?;
+    let signed_info = signature
+        .child(DSIG_NS, "SignedInfo")
+        .ok_or(invalid("missing SignedInfo"))?;
+
+    let c14n = signed_info
+        .child(DSIG_NS, "CanonicalizationMethod")
+        .ok_or(invalid("missing CanonicalizationMethod"))?;
+    if c14n.attr("Algorithm") != Some(EXC_C14N) {
+        return Err(invalid("unsupported canonicalization"));
+    }
+
+    let algorithm: &SignatureAlgorithm = match signed_info
+        .child(DSIG_NS, "SignatureMethod")
+        .and_then(|m| m.attr("Algorithm"))
+    {
+        Some("http://www.w3.org/2001/04/xmldsig-more#rsa-sha256") => {
+            &webpki::RSA_PKCS1_2048_8192_SHA256
+        }
+        Some("http://www.w3.org/2001/04/xmldsig-more#rsa-sha512") => {
+            &webpki::RSA_PKCS1_2048_8192_SHA512
+        }
+        _ => return Err(invalid("unsupported signature algorithm")),
+    };
+
+    // the signature must cover exactly the element it is enveloped in
+    let mut references = signed_info.children(DSIG_NS, "Reference");
+    let reference = match (references.next(), references.next()) {
+        (Some(reference), None) => reference,
+        _ => return Err(invalid("expected exactly one reference")),
+    };
+    match (reference.attr("URI"), element.attr("ID")) {
+        (Some(uri), Some(id)) if uri.strip_prefix('#') == Some(id) => (),
+        _ => return Err(invalid("reference does not match the signed element")),
+    }
+
+    let mut enveloped = false;
+    let mut inclusive = None;
+    for transform in reference
+        .child(DSIG_NS, "Transforms")
+        .into_iter()
+        .flat_map(|t| t.children(DSIG_NS, "Transform"))
+    {
+        match transform.attr("Algorithm") {
+            Some(ENVELOPED) => enveloped = true,
+            Some(EXC_C14N) => inclusive = Some(prefix_list(transform)),
+            _ => return Err(invalid("unsupported transform")),
+        }
+    }
+    let inclusive = match (enveloped, inclusive) {
+        (true, Some(inclusive)) => inclusive,
+        _ => return Err(invalid("unsupported transforms")),
+    };
+
+    let digest_alg = match reference
+        .child(DSIG_NS, "DigestMethod")
+        .and_then(|m| m.attr("Algorithm"))
+    {
+        Some("http://www.w3.org/2001/04/xmlenc#sha256") => &digest::SHA256,
+        Some("http://www.w3.org/2001/04/xmlenc#sha512") => &digest::SHA512,
+        _ => return Err(invalid("unsupported digest algorithm")),
+    };
+    let expected = reference
+        .child(DSIG_NS, "DigestValue")
+        .and_then(|v| decode(&v.text()))
+        .ok_or(invalid("malformed digest"))?;
+
+    let inclusive: Vec<&str> = inclusive.iter().map(String::as_str).collect();
+    let canonical = xml::canonicalize(element, Some(signature), &inclusive);
+    let actual = digest::digest(digest_alg, canonical.as_bytes());
+    constant_time::verify_slices_are_equal(actual.as_ref(), &expected)
+        .map_err(|_| invalid("digest mismatch"))?;
+
+    let value = signature
+        .child(DSIG_NS, "SignatureValue")
+        .and_then(|v| decode(&v.text()))
+        .ok_or(invalid("malformed signature value"))?;
+
+    let prefixes = prefix_list(c14n);
+    let prefixes: Vec<&str> = prefixes.iter().map(String::as_str).collect();
+    let canonical = xml::canonicalize(signed_info, None, &prefixes);
+
+    let verified = certificates.iter().any(|der| {
+        EndEntityCert::from(der)
+            .and_then(|cert| cert.verify_signature(algorithm, canonical.as_bytes(), &value))
+            .is_ok()
+    });
+
+    match verified {
+        true => Ok(()),
+        false => Err(invalid("signature does not verify")),
+    }
+}
+
+/// Returns the inclusive namespace prefixes of an exclusive canonicalization transform
+fn prefix_list(transform: &Element) -> Vec<String> {
+    transform
+        .child(EXC_C14N, "InclusiveNamespaces")
+        .and_then(|n| n.attr("PrefixList"))
+        .map(|list| list.split_whitespace().map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+/// Decodes base64 that may be wrapped over several lines
+fn decode(value: &str) -> Option<Vec<u8>> {
+    let value: String = value.split_whitespace().collect();
+    base64::decode(value).ok()
+}
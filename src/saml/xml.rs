@@ -0,0 +1,455 @@
+//! Minimal XML tree and exclusive canonicalization for signature validation
+//!
+//! Namespace prefixes are kept exactly as written, since exclusive XML canonicalization
+//! ([xml-exc-c14n](https://www.w3.org/TR/xml-exc-c14n/)) renders them.  Documents with a
+//! DTD are rejected outright, so entity expansion attacks never reach the tree.
+
+use super::SamlError;
+use std::collections::BTreeMap;
+use xmlparser::{ElementEnd, Token, Tokenizer};
+
+/// Namespace bound to the reserved `xml` prefix
+const XML_NS: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Deepest element nesting accepted
+const MAX_DEPTH: usize = 64;
+
+/// An attribute of an element
+#[derive(Clone, Debug)]
+pub(crate) struct Attribute {
+    /// Prefix as written, empty if unqualified
+    prefix: String,
+
+    /// Local name of the attribute
+    name: String,
+
+    /// Namespace of the attribute, empty if unqualified
+    ns: String,
+
+    /// Value, with references expanded
+    value: String,
+}
+
+/// Content of an element
+#[derive(Clone, Debug)]
+pub(crate) enum Node {
+    Element(Element),
+    Text(String),
+}
+
+/// An element and its content
+#[derive(Clone, Debug)]
+pub(crate) struct Element {
+    /// Prefix as written, empty if unprefixed
+    prefix: String,
+
+    /// Local name of the element
+    name: String,
+
+    /// Namespace of the element, empty if none
+    ns: String,
+
+    /// Attributes, excluding namespace declarations
+    attrs: Vec<Attribute>,
+
+    /// Namespaces in scope, keyed by prefix (empty for the default namespace)
+    scope: BTreeMap<String, String>,
+
+    /// Child elements and text
+    children: Vec<Node>,
+}
+
+impl Element {
+    /// Returns true if the element has a namespace and local name
+    pub fn is(&self, ns: &str, name: &str) -> bool {
+        self.ns == ns && self.name == name
+    }
+
+    /// Returns the value of an unqualified attribute
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|a| a.ns.is_empty() && a.name == name)
+            .map(|a| a.value.as_str())
+    }
+
+    /// Returns the child elements
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(e) => Some(e),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// Returns the child elements with a namespace and local name
+    pub fn children<'a>(&'a self, ns: &'a str, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.elements().filter(move |e| e.is(ns, name))
+    }
+
+    /// Returns the first child element with a namespace and local name
+    pub fn child(&self, ns: &str, name: &str) -> Option<&Element> {
+        self.elements().find(|e| e.is(ns, name))
+    }
+
+    /// Returns the text content of the element, excluding that of child elements
+    pub fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|child| match child {
+                Node::Text(text) => Some(text.as_str()),
+                Node::Element(_) => None,
+            })
+            .collect()
+    }
+
+    /// Calls a function for this element and every descendant
+    pub fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a Element)) {
+        f(self);
+        for e in self.elements() {
+            e.walk(f);
+        }
+    }
+}
+
+/// Parses a document, returning its root element
+pub(crate) fn parse(xml: &str) -> Result<Element, SamlError> {
+    let malformed = || SamlError::Malformed("invalid XML");
+    let mut stack: Vec<Element> = vec![];
+    let mut root = None;
+
+    for token in Tokenizer::from(xml) {
+        match token.map_err(|_| malformed())? {
+            Token::DtdStart { .. } | Token::EmptyDtd { .. } | Token::EntityDeclaration { .. } => {
+                return Err(SamlError::Malformed("DTDs are not allowed"));
+            }
+            Token::ElementStart { prefix, local, .. } => {
+                if root.is_some() || stack.len() >= MAX_DEPTH {
+                    return Err(malformed());
+                }
+
+                let scope = stack.last().map(|e| e.scope.clone()).unwrap_or_default();
+                stack.push(Element {
+                    prefix: prefix.as_str().to_owned(),
+                    name: local.as_str().to_owned(),
+                    ns: String::new(),
+                    attrs: vec![],
+                    scope,
+                    children: vec![],
+                });
+            }
+            Token::Attribute {
+                prefix,
+                local,
+                value,
+                ..
+            } => {
+                let element = stack.last_mut().ok_or_else(malformed)?;
+                let value = unescape(value.as_str(), true)?;
+                match (prefix.as_str(), local.as_str()) {
+                    ("", "xmlns") => {
+                        element.scope.insert(String::new(), value);
+                    }
+                    ("xmlns", prefix) => {
+                        element.scope.insert(prefix.to_owned(), value);
+                    }
+                    (prefix, name) => element.attrs.push(Attribute {
+                        prefix: prefix.to_owned(),
+                        name: name.to_owned(),
+                        ns: String::new(),
+                        value,
+                    }),
+                }
+            }
+            Token::ElementEnd { end, .. } => {
+                let close = match end {
+                    ElementEnd::Open => {
+                        resolve(stack.last_mut().ok_or_else(malformed)?)?;
+                        continue;
+                    }
+                    ElementEnd::Empty => {
+                        resolve(stack.last_mut().ok_or_else(malformed)?)?;
+                        None
+                    }
+                    ElementEnd::Close(prefix, local) => Some((prefix, local)),
+                };
+
+                let element = stack.pop().ok_or_else(malformed)?;
+                if let Some((prefix, local)) = close {
+                    if element.prefix != prefix.as_str() || element.name != local.as_str() {
+                        return Err(malformed());
+                    }
+                }
+
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(Node::Element(element)),
+                    None => root = Some(element),
+                }
+            }
+            Token::Text { text } => match stack.last_mut() {
+                Some(element) => push_text(element, unescape(text.as_str(), false)?),
+                None if text.as_str().trim().is_empty() => (),
+                None => return Err(malformed()),
+            },
+            Token::Cdata { text, .. } => {
+                let element = stack.last_mut().ok_or_else(malformed)?;
+                push_text(element, text.as_str().replace("\r\n", "\n"));
+            }
+            Token::Declaration { .. }
+            | Token::ProcessingInstruction { .. }
+            | Token::Comment { .. }
+            | Token::DtdEnd { .. } => (),
+        }
+    }
+
+    match stack.is_empty() {
+        true => root.ok_or_else(malformed),
+        false => Err(malformed()),
+    }
+}
+
+/// Resolves the namespaces of an element and its attributes once its start tag is read
+fn resolve(element: &mut Element) -> Result<(), SamlError> {
+    let lookup = |scope: &BTreeMap<String, String>, prefix: &str| match prefix {
+        "xml" => Some(XML_NS.to_owned()),
+        _ => scope.get(prefix).cloned(),
+    };
+
+    element.ns = match element.prefix.as_str() {
+        "" => element.scope.get("").cloned().unwrap_or_default(),
+        prefix => lookup(&element.scope, prefix)
+            .ok_or(SamlError::Malformed("undeclared namespace prefix"))?,
+    };
+
+    for attr in &mut element.attrs {
+        if !attr.prefix.is_empty() {
+            attr.ns = lookup(&element.scope, &attr.prefix)
+                .ok_or(SamlError::Malformed("undeclared namespace prefix"))?;
+        }
+    }
+
+    for (i, attr) in element.attrs.iter().enumerate() {
+        if element.attrs[..i]
+            .iter()
+            .any(|a| a.ns == attr.ns && a.name == attr.name)
+        {
+            return Err(SamlError::Malformed("duplicate attribute"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Appends text to an element, merging it with any preceding text
+fn push_text(element: &mut Element, text: String) {
+    match element.children.last_mut() {
+        Some(Node::Text(existing)) => existing.push_str(&text),
+        _ => element.children.push(Node::Text(text)),
+    }
+}
+
+/// Normalizes line endings and expands character and predefined entity references
+///
+/// # Arguments
+/// * `raw` - Text as it appears in the document
+/// * `attribute` - Whether the text is an attribute value, whose whitespace is normalized
+fn unescape(raw: &str, attribute: bool) -> Result<String, SamlError> {
+    let invalid = || SamlError::Malformed("invalid entity reference");
+    let raw = raw.replace("\r\n", "\n").replace('\r', "\n");
+
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw.as_str();
+    while let Some(idx) = rest.find(['&', '\t', '\n']) {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        if !rest.starts_with('&') {
+            out.push(if attribute {
+                ' '
+            } else {
+                rest.as_bytes()[0] as char
+            });
+            rest = &rest[1..];
+            continue;
+        }
+
+        let end = rest.find(';').ok_or_else(invalid)?;
+        let c = match &rest[1..end] {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            r => {
+                let code = match (r.strip_prefix("#x"), r.strip_prefix('#')) {
+                    (Some(hex), _) => u32::from_str_radix(hex, 16),
+                    (None, Some(dec)) => dec.parse(),
+                    (None, None) => return Err(invalid()),
+                };
+                code.ok().and_then(char::from_u32).ok_or_else(invalid)?
+            }
+        };
+        out.push(c);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+/// Serializes an element with exclusive XML canonicalization (without comments)
+///
+/// # Arguments
+/// * `element` - Apex of the subtree to canonicalize
+/// * `exclude` - Descendant left out of the output (the enveloped signature)
+/// * `inclusive` - Prefixes treated as in inclusive canonicalization (`#default` for the
+///   default namespace)
+pub(crate) fn canonicalize(
+    element: &Element,
+    exclude: Option<&Element>,
+    inclusive: &[&str],
+) -> String {
+    let inclusive: Vec<&str> = inclusive
+        .iter()
+        .map(|p| if *p == "#default" { "" } else { *p })
+        .collect();
+
+    let mut out = String::new();
+    write_element(&mut out, element, exclude, &inclusive, &BTreeMap::new());
+    out
+}
+
+/// Writes an element and its content in canonical form
+///
+/// # Arguments
+/// * `rendered` - Namespaces declared by output ancestors
+fn write_element(
+    out: &mut String,
+    element: &Element,
+    exclude: Option<&Element>,
+    inclusive: &[&str],
+    rendered: &BTreeMap<String, String>,
+) {
+    // namespaces visibly utilized by the element or its attributes, plus inclusive ones
+    let mut utilized: Vec<&str> = vec![element.prefix.as_str()];
+    utilized.extend(
+        element
+            .attrs
+            .iter()
+            .map(|a| a.prefix.as_str())
+            .filter(|p| !p.is_empty()),
+    );
+    utilized.extend(inclusive.iter().filter(|p| element.scope.contains_key(**p)));
+    utilized.retain(|p| *p != "xml");
+    utilized.sort_unstable();
+    utilized.dedup();
+
+    let mut scope = rendered.clone();
+    out.push('<');
+    write_name(out, &element.prefix, &element.name);
+    for prefix in utilized {
+        let uri = element.scope.get(prefix).map(String::as_str).unwrap_or("");
+        if rendered.get(prefix).map(String::as_str).unwrap_or("") == uri {
+            continue;
+        }
+
+        out.push_str(" xmlns");
+        if !prefix.is_empty() {
+            out.push(':');
+            out.push_str(prefix);
+        }
+        out.push_str("=\"");
+        escape(out, uri, true);
+        out.push('"');
+        scope.insert(prefix.to_owned(), uri.to_owned());
+    }
+
+    let mut attrs: Vec<&Attribute> = element.attrs.iter().collect();
+    attrs.sort_by(|a, b| (&a.ns, &a.name).cmp(&(&b.ns, &b.name)));
+    for attr in attrs {
+        out.push(' ');
+        write_name(out, &attr.prefix, &attr.name);
+        out.push_str("=\"");
+        escape(out, &attr.value, true);
+        out.push('"');
+    }
+    out.push('>');
+
+    for child in &element.children {
+        match child {
+            Node::Text(text) => escape(out, text, false),
+            Node::Element(e) if exclude.is_some_and(|x| std::ptr::eq(e, x)) => (),
+            Node::Element(e) => write_element(out, e, exclude, inclusive, &scope),
+        }
+    }
+
+    out.push_str("</");
+    write_name(out, &element.prefix, &element.name);
+    out.push('>');
+}
+
+/// Writes a possibly prefixed name
+fn write_name(out: &mut String, prefix: &str, name: &str) {
+    if !prefix.is_empty() {
+        out.push_str(prefix);
+        out.push(':');
+    }
+    out.push_str(name);
+}
+
+/// Escapes text or an attribute value as canonical XML requires
+fn escape(out: &mut String, text: &str, attribute: bool) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' if !attribute => out.push_str("&gt;"),
+            '"' if attribute => out.push_str("&quot;"),
+            '\t' if attribute => out.push_str("&#x9;"),
+            '\n' if attribute => out.push_str("&#xA;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = r#"<?xml version="1.0"?>
+<a:root xmlns:a="urn:a" xmlns:b="urn:b" xmlns="urn:d" z="1" b:y="2" a:x="3"><child attr="&quot;&lt;&#xA;"/><!-- comment --><b:other>1 &lt; 2 &amp; 3 &gt; 0</b:other><plain xmlns="">text</plain></a:root>"#;
+
+    #[test]
+    fn exclusive_canonicalization() {
+        let root = parse(DOC).unwrap();
+        assert_eq!(
+            canonicalize(&root, None, &[]),
+            "<a:root xmlns:a=\"urn:a\" xmlns:b=\"urn:b\" z=\"1\" a:x=\"3\" b:y=\"2\">\
+             <child xmlns=\"urn:d\" attr=\"&quot;&lt;&#xA;\"></child>\
+             <b:other>1 &lt; 2 &amp; 3 &gt; 0</b:other><plain>text</plain></a:root>"
+        );
+        assert_eq!(
+            canonicalize(&root, None, &["#default"]),
+            "<a:root xmlns=\"urn:d\" xmlns:a=\"urn:a\" xmlns:b=\"urn:b\" z=\"1\" a:x=\"3\" b:y=\"2\">\
+             <child attr=\"&quot;&lt;&#xA;\"></child>\
+             <b:other>1 &lt; 2 &amp; 3 &gt; 0</b:other><plain xmlns=\"\">text</plain></a:root>"
+        );
+
+        // a subtree renders the namespaces it inherits, and an excluded child is dropped
+        let other = root.child("urn:b", "other").unwrap();
+        assert_eq!(
+            canonicalize(other, None, &[]),
+            "<b:other xmlns:b=\"urn:b\">1 &lt; 2 &amp; 3 &gt; 0</b:other>"
+        );
+        assert!(!canonicalize(&root, Some(other), &[]).contains("b:other"));
+    }
+
+    #[test]
+    fn unsafe_documents_are_rejected() {
+        assert!(parse("<!DOCTYPE a [<!ENTITY e \"x\">]><a>&e;</a>").is_err());
+        assert!(parse("<a><b></a></b>").is_err());
+        assert!(parse("<p:a/>").is_err());
+        assert!(parse("<a x=\"1\" x=\"2\"/>").is_err());
+        assert!(parse("<a/><b/>").is_err());
+    }
+}
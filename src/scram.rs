@@ -0,0 +1,463 @@
+//! SCRAM-SHA-256 server-side authentication
+//!
+//! [SCRAM](https://tools.ietf.org/html/rfc5802) (as profiled for SHA-256 by
+//! [RFC 7677](https://tools.ietf.org/html/rfc7677)) is the challenge/response mechanism used
+//! by IMAP, SMTP, AMQP, PostgreSQL and others to authenticate with a password without
+//! sending it to the server.  The server keeps only [`StoredCredentials`] derived from the
+//! password, which can be serialized in the format used by PostgreSQL
+//! ([RFC 5803](https://tools.ietf.org/html/rfc5803)):
+//! `SCRAM-SHA-256$<iterations>:<salt>$<StoredKey>:<ServerKey>`.
+//!
+//! The exchange is driven by the caller's protocol:
+//!
+//! 1. The client-first message is parsed with [`ClientFirst::parse`], which yields the
+//!    username so the stored credentials can be looked up.
+//! 2. [`ClientFirst::respond`] returns the server-first message to send and the state of
+//!    the exchange.
+//! 3. [`ServerFirst::finish`] verifies the client-final message and returns the
+//!    server-final message to send.
+//!
+//! Channel binding (`SCRAM-SHA-256-PLUS`) is not supported.  Passwords are used as given,
+//! without SASLprep normalization.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::scram::{ClientFirst, StoredCredentials};
+//!
+//! // when the password is set
+//! user.scram = StoredCredentials::new(&password, 4096).to_string();
+//!
+//! // during authentication
+//! let client_first = ClientFirst::parse(&msg)?;
+//! let creds: StoredCredentials = lookup(client_first.username())?.scram.parse()?;
+//! let (server_first_msg, state) = client_first.respond(&creds);
+//! send(&server_first_msg);
+//!
+//! let server_final_msg = state.finish(&receive())?;
+//! send(&server_final_msg);
+//! ```
+
+use rand::RngCore;
+use ring::{constant_time, digest, hmac, pbkdf2};
+use std::{fmt, num::NonZeroU32, str::FromStr};
+use thiserror::Error;
+
+/// Name of the mechanism
+pub const MECHANISM: &str = "SCRAM-SHA-256";
+
+/// Minimum iteration count recommended by RFC 7677
+pub const MIN_ITERATIONS: u32 = 4096;
+
+/// Length, in bytes, of generated salts
+const SALT_LEN: usize = 16;
+
+/// Length, in bytes, of generated server nonces
+const NONCE_LEN: usize = 18;
+
+/// Length, in bytes, of a SHA-256 digest
+const KEY_LEN: usize = 32;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ScramError {
+    #[error("malformed SCRAM message: {0}")]
+    Malformed(&'static str),
+
+    #[error("SCRAM channel binding is not supported")]
+    ChannelBindingUnsupported,
+
+    #[error("SCRAM nonce does not match")]
+    NonceMismatch,
+
+    #[error("SCRAM client proof is invalid")]
+    InvalidProof,
+
+    #[error("malformed SCRAM stored credentials")]
+    InvalidCredentials,
+}
+
+/// Credentials derived from a password, as stored by the server
+#[derive(Clone, PartialEq, Eq)]
+pub struct StoredCredentials {
+    /// Number of PBKDF2 iterations
+    iterations: u32,
+
+    /// Salt used to derive the salted password
+    salt: Vec<u8>,
+
+    /// H(ClientKey)
+    stored_key: Vec<u8>,
+
+    /// HMAC(SaltedPassword, "Server Key")
+    server_key: Vec<u8>,
+}
+
+impl StoredCredentials {
+    /// Derives credentials from a password with a random salt
+    ///
+    /// # Arguments
+    /// * `password` - The user's password
+    /// * `iterations` - Number of PBKDF2 iterations (at least `MIN_ITERATIONS` is recommended)
+    pub fn new(password: &str, iterations: u32) -> StoredCredentials {
+        let mut salt = vec![0; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        StoredCredentials::with_salt(password, salt, iterations)
+    }
+
+    /// Derives credentials from a password with a given salt
+    ///
+    /// # Arguments
+    /// * `password` - The user's password
+    /// * `salt` - Salt to derive the salted password with
+    /// * `iterations` - Number of PBKDF2 iterations
+    pub fn with_salt(password: &str, salt: Vec<u8>, iterations: u32) -> StoredCredentials {
+        let iterations = iterations.max(1);
+        let mut salted = [0u8; KEY_LEN];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            NonZeroU32::new(iterations).unwrap_or(NonZeroU32::MIN),
+            &salt,
+            password.as_bytes(),
+            &mut salted,
+        );
+
+        let client_key = hmac_sha256(&salted, b"Client Key");
+        StoredCredentials {
+            iterations,
+            salt,
+            stored_key: digest::digest(&digest::SHA256, &client_key)
+                .as_ref()
+                .to_vec(),
+            server_key: hmac_sha256(&salted, b"Server Key"),
+        }
+    }
+
+    /// Returns the number of PBKDF2 iterations
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+}
+
+impl fmt::Display for StoredCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}${}:{}${}:{}",
+            MECHANISM,
+            self.iterations,
+            base64::encode(&self.salt),
+            base64::encode(&self.stored_key),
+            base64::encode(&self.server_key)
+        )
+    }
+}
+
+impl fmt::Debug for StoredCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StoredCredentials")
+            .field("iterations", &self.iterations)
+            .field("salt", &base64::encode(&self.salt))
+            .finish()
+    }
+}
+
+impl FromStr for StoredCredentials {
+    type Err = ScramError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(['$', ':']);
+        let (mechanism, iterations, salt, stored_key, server_key) = match (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) {
+            (Some(m), Some(i), Some(salt), Some(stored), Some(server), None) => {
+                (m, i, salt, stored, server)
+            }
+            _ => return Err(ScramError::InvalidCredentials),
+        };
+
+        if mechanism != MECHANISM {
+            return Err(ScramError::InvalidCredentials);
+        }
+
+        let decode = |s: &str| base64::decode(s).map_err(|_| ScramError::InvalidCredentials);
+        let creds = StoredCredentials {
+            iterations: iterations
+                .parse()
+                .ok()
+                .filter(|i| *i > 0)
+                .ok_or(ScramError::InvalidCredentials)?,
+            salt: decode(salt)?,
+            stored_key: decode(stored_key)?,
+            server_key: decode(server_key)?,
+        };
+
+        if creds.stored_key.len() != KEY_LEN || creds.server_key.len() != KEY_LEN {
+            return Err(ScramError::InvalidCredentials);
+        }
+
+        Ok(creds)
+    }
+}
+
+/// A parsed client-first message
+#[derive(Clone, Debug)]
+pub struct ClientFirst {
+    /// GS2 header (e.g., `n,,`), echoed back base64-encoded in the client-final message
+    gs2_header: String,
+
+    /// Client-first message without the GS2 header
+    bare: String,
+
+    /// Name of the user authenticating
+    username: String,
+
+    /// Nonce chosen by the client
+    nonce: String,
+}
+
+impl ClientFirst {
+    /// Parses a client-first message
+    ///
+    /// # Arguments
+    /// * `msg` - Client-first message (e.g., `n,,n=user,r=fyko+d2lbbFgONRv9qkxdawL`)
+    pub fn parse(msg: &str) -> Result<ClientFirst, ScramError> {
+        let mut parts = msg.splitn(3, ',');
+        let (cbind, authzid, bare) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(cbind), Some(authzid), Some(bare)) => (cbind, authzid, bare),
+            _ => return Err(ScramError::Malformed("client-first")),
+        };
+
+        match cbind {
+            "n" | "y" => (),
+            _ if cbind.starts_with("p=") => return Err(ScramError::ChannelBindingUnsupported),
+            _ => return Err(ScramError::Malformed("gs2-cbind-flag")),
+        }
+
+        if !authzid.is_empty() && !authzid.starts_with("a=") {
+            return Err(ScramError::Malformed("authzid"));
+        }
+
+        let mut attrs = bare.split(',');
+        let username = attrs
+            .next()
+            .and_then(|a| a.strip_prefix("n="))
+            .ok_or(ScramError::Malformed("username"))
+            .and_then(decode_username)?;
+        let nonce = attrs
+            .next()
+            .and_then(|a| a.strip_prefix("r="))
+            .filter(|r| !r.is_empty())
+            .ok_or(ScramError::Malformed("nonce"))?;
+
+        Ok(ClientFirst {
+            gs2_header: format!("{},{},", cbind, authzid),
+            bare: bare.to_owned(),
+            username,
+            nonce: nonce.to_owned(),
+        })
+    }
+
+    /// Returns the name of the user authenticating
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Responds to the client, returning the server-first message to send and the state
+    /// needed to verify the client-final message
+    ///
+    /// # Arguments
+    /// * `creds` - Stored credentials of the user.  If the user doesn't exist, credentials
+    ///   derived from a random password should be used so the exchange fails at the final
+    ///   step instead of revealing the user doesn't exist
+    pub fn respond(self, creds: &StoredCredentials) -> (String, ServerFirst) {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        self.respond_with_nonce(creds, &base64::encode(nonce))
+    }
+
+    fn respond_with_nonce(self, creds: &StoredCredentials, nonce: &str) -> (String, ServerFirst) {
+        let nonce = format!("{}{}", self.nonce, nonce);
+        let msg = format!(
+            "r={},s={},i={}",
+            nonce,
+            base64::encode(&creds.salt),
+            creds.iterations
+        );
+
+        let state = ServerFirst {
+            client_first: self,
+            server_first: msg.clone(),
+            nonce,
+            stored_key: creds.stored_key.clone(),
+            server_key: creds.server_key.clone(),
+        };
+
+        (msg, state)
+    }
+}
+
+/// State of an exchange after the server-first message was sent
+#[derive(Clone, Debug)]
+pub struct ServerFirst {
+    /// Client-first message
+    client_first: ClientFirst,
+
+    /// Server-first message
+    server_first: String,
+
+    /// Combined client and server nonce
+    nonce: String,
+
+    /// Stored key of the user
+    stored_key: Vec<u8>,
+
+    /// Server key of the user
+    server_key: Vec<u8>,
+}
+
+impl ServerFirst {
+    /// Returns the name of the user authenticating
+    pub fn username(&self) -> &str {
+        self.client_first.username()
+    }
+
+    /// Verifies the client-final message, returning the server-final message to send
+    ///
+    /// # Arguments
+    /// * `msg` - Client-final message
+    pub fn finish(&self, msg: &str) -> Result<String, ScramError> {
+        let (without_proof, proof) = match msg.rfind(",p=") {
+            Some(idx) => (&msg[..idx], &msg[idx + 3..]),
+            None => return Err(ScramError::Malformed("proof")),
+        };
+
+        let mut attrs = without_proof.split(',');
+        let binding = attrs
+            .next()
+            .and_then(|a| a.strip_prefix("c="))
+            .ok_or(ScramError::Malformed("channel-binding"))?;
+        let nonce = attrs
+            .next()
+            .and_then(|a| a.strip_prefix("r="))
+            .ok_or(ScramError::Malformed("nonce"))?;
+
+        if base64::decode(binding).ok().as_deref() != Some(self.client_first.gs2_header.as_bytes())
+        {
+            return Err(ScramError::ChannelBindingUnsupported);
+        }
+
+        if constant_time::verify_slices_are_equal(nonce.as_bytes(), self.nonce.as_bytes()).is_err()
+        {
+            return Err(ScramError::NonceMismatch);
+        }
+
+        let proof = base64::decode(proof).map_err(|_| ScramError::Malformed("proof"))?;
+        if proof.len() != KEY_LEN {
+            return Err(ScramError::InvalidProof);
+        }
+
+        let auth_message = format!(
+            "{},{},{}",
+            self.client_first.bare, self.server_first, without_proof
+        );
+
+        // ClientKey = ClientProof XOR HMAC(StoredKey, AuthMessage)
+        let signature = hmac_sha256(&self.stored_key, auth_message.as_bytes());
+        let client_key: Vec<u8> = proof.iter().zip(&signature).map(|(p, s)| p ^ s).collect();
+        let stored_key = digest::digest(&digest::SHA256, &client_key);
+        constant_time::verify_slices_are_equal(stored_key.as_ref(), &self.stored_key)
+            .map_err(|_| ScramError::InvalidProof)?;
+
+        let server_signature = hmac_sha256(&self.server_key, auth_message.as_bytes());
+        Ok(format!("v={}", base64::encode(&server_signature)))
+    }
+}
+
+/// Computes HMAC-SHA256
+fn hmac_sha256(key: &[u8], msg: &[u8]) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::sign(&key, msg).as_ref().to_vec()
+}
+
+/// Decodes a `saslname`, where `,` and `=` are escaped as `=2C` and `=3D`
+fn decode_username(name: &str) -> Result<String, ScramError> {
+    let mut decoded = String::with_capacity(name.len());
+    let mut chars = name.split('=');
+    decoded.push_str(chars.next().unwrap_or_default());
+    for escaped in chars {
+        match escaped.get(..2) {
+            Some("2C") => decoded.push(','),
+            Some("3D") => decoded.push('='),
+            _ => return Err(ScramError::Malformed("username")),
+        }
+        decoded.push_str(&escaped[2..]);
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exchange from RFC 7677, section 3
+    const CLIENT_FIRST: &str = "n,,n=user,r=rOprNGfwEbeRWgbNEkqO";
+    const SERVER_FIRST: &str =
+        "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096";
+    const CLIENT_FINAL: &str = "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+                                p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=";
+    const SERVER_FINAL: &str = "v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=";
+
+    fn exchange(password: &str) -> ServerFirst {
+        let salt = base64::decode("W22ZaJ0SNY7soEsUEjb6gQ==").unwrap();
+        let creds = StoredCredentials::with_salt(password, salt, 4096);
+
+        let client_first = ClientFirst::parse(CLIENT_FIRST).unwrap();
+        assert_eq!(client_first.username(), "user");
+
+        let (msg, state) =
+            client_first.respond_with_nonce(&creds, "%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0");
+        assert_eq!(msg, SERVER_FIRST);
+        state
+    }
+
+    #[test]
+    fn rfc7677_exchange() {
+        assert_eq!(
+            exchange("pencil").finish(CLIENT_FINAL).unwrap(),
+            SERVER_FINAL
+        );
+        assert_eq!(
+            exchange("pencil2").finish(CLIENT_FINAL),
+            Err(ScramError::InvalidProof)
+        );
+    }
+
+    #[test]
+    fn stored_credentials_round_trip() {
+        let creds = StoredCredentials::new("pencil", 4096);
+        assert_eq!(creds.to_string().parse::<StoredCredentials>(), Ok(creds));
+        assert!("SCRAM-SHA-1$4096:c2FsdA==$a2V5:a2V5"
+            .parse::<StoredCredentials>()
+            .is_err());
+    }
+
+    #[test]
+    fn parse_client_first() {
+        assert_eq!(
+            ClientFirst::parse("n,,n=a=2Cb=3Dc,r=abc")
+                .unwrap()
+                .username(),
+            "a,b=c"
+        );
+        assert_eq!(
+            ClientFirst::parse("p=tls-unique,,n=user,r=abc").unwrap_err(),
+            ScramError::ChannelBindingUnsupported
+        );
+        assert!(ClientFirst::parse("n,,n=user").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Wall clock
+//!
+//! `SystemTime` is not available on `wasm32-unknown-unknown`, where the time is read from
+//! the JavaScript host instead (e.g., a Cloudflare Worker).  Tests pin the clock with
+//! [`freeze`] rather than depending on the time they happen to run at.
+
+#[cfg(test)]
+use std::cell::Cell;
+
+#[cfg(test)]
+thread_local! {
+    /// Time, in seconds, returned by [`now`] on this thread while frozen
+    static FROZEN: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Returns the current time in seconds since the unix epoch
+#[cfg(any(
+    feature = "apikey",
+    feature = "jwt",
+    feature = "lockout",
+    feature = "mfa",
+    feature = "mtls",
+    feature = "otp",
+    feature = "recovery",
+    feature = "saml",
+    feature = "webauthn"
+))]
+pub(crate) fn now() -> u64 {
+    now_millis() / 1000
+}
+
+/// Returns the current time in milliseconds since the unix epoch
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) fn now_millis() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[cfg(test)]
+    if let Some(now) = FROZEN.with(Cell::get) {
+        return now * 1000;
+    }
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Returns the current time in milliseconds since the unix epoch
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) fn now_millis() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+/// Makes [`now`] return `now` on the current thread until the returned guard is dropped
+///
+/// # Arguments
+/// * `now` - Time, in seconds since the unix epoch
+// not every feature's tests freeze the clock
+#[cfg(test)]
+#[allow(dead_code)]
+pub(crate) fn freeze(now: u64) -> Frozen {
+    FROZEN.with(|frozen| frozen.set(Some(now)));
+    Frozen(())
+}
+
+/// Unfreezes the clock when dropped
+#[cfg(test)]
+#[allow(dead_code)]
+pub(crate) struct Frozen(());
+
+#[cfg(test)]
+#[allow(dead_code)]
+impl Frozen {
+    /// Moves the frozen clock forward
+    ///
+    /// # Arguments
+    /// * `secs` - Seconds to advance by
+    pub(crate) fn advance(&self, secs: u64) {
+        FROZEN.with(|frozen| frozen.set(frozen.get().map(|now| now + secs)));
+    }
+}
+
+#[cfg(test)]
+impl Drop for Frozen {
+    fn drop(&mut self) {
+        FROZEN.with(|frozen| frozen.set(None));
+    }
+}
@@ -0,0 +1,64 @@
+//! Session tokens issued after a user has authenticated
+//!
+//! Once a ceremony (WebAuthn, password, ...) succeeds, these modules mint the token that
+//! identifies the user on subsequent requests and verify it when it is presented again.
+
+#[cfg(feature = "jwt")]
+pub mod jwt;
+#[cfg(feature = "paseto")]
+pub mod paseto;
+
+use serde::{Deserialize, Serialize};
+
+/// Authentication method reference values ([RFC 8176](https://tools.ietf.org/html/rfc8176))
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMethod {
+    /// Biometric facial recognition
+    Face,
+
+    /// Fingerprint biometric
+    Fpt,
+
+    /// Proof-of-possession of a hardware-secured key (e.g., a WebAuthn authenticator)
+    Hwk,
+
+    /// Multiple-factor authentication
+    Mfa,
+
+    /// One-time password
+    Otp,
+
+    /// Personal Identification Number or pattern
+    Pin,
+
+    /// Password-based authentication
+    Pwd,
+
+    /// Smart card
+    Sc,
+
+    /// Confirmation using SMS
+    Sms,
+
+    /// Proof-of-possession of a software-secured key
+    Swk,
+
+    /// User presence test
+    User,
+}
+
+impl AuthMethod {
+    /// Returns the methods used by a successful WebAuthn authentication.  A hardware key
+    /// that also verified the user (PIN or biometric) counts as multiple factors
+    ///
+    /// # Arguments
+    /// * `user_verified` - If the authenticator verified the user
+    pub fn webauthn(user_verified: bool) -> Vec<AuthMethod> {
+        if user_verified {
+            vec![AuthMethod::Hwk, AuthMethod::Mfa]
+        } else {
+            vec![AuthMethod::Hwk, AuthMethod::User]
+        }
+    }
+}
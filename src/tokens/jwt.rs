@@ -0,0 +1,332 @@
+//! JSON Web Tokens (JWT) for post-authentication sessions
+//!
+//! A [`JwtIssuer`] signs (HS256, RS256 or ES256) and verifies tokens carrying the standard
+//! registered claims, the authentication methods used (`amr`, see
+//! [RFC 8176](https://tools.ietf.org/html/rfc8176)) and any additional claims.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::tokens::jwt::{AuthMethod, JwtIssuer};
+//!
+//! let issuer = JwtIssuer::hs256(b"a long, random server-side secret")
+//!     .issuer("https://app.example.com")
+//!     .ttl(3600);
+//!
+//! // after `webauthn::authenticate()` succeeds
+//! let token = issuer.issue(user.id(), &AuthMethod::webauthn(result.user_verified()))?;
+//!
+//! // on subsequent requests
+//! let claims = issuer.verify(&token)?;
+//! ```
+
+pub use super::AuthMethod;
+
+use crate::time::now;
+use jsonwebtoken::{
+    decode, encode, errors::Error as JwtLibError, Algorithm, DecodingKey, EncodingKey, Header,
+    Validation,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use thiserror::Error;
+
+/// Default lifetime (in seconds) of issued tokens
+pub const DEFAULT_TTL: u64 = 3600;
+
+#[derive(Error, Debug)]
+pub enum JwtError {
+    #[error("invalid signing or verification key: {0}")]
+    InvalidKey(JwtLibError),
+
+    #[error("token failed to sign or validate: {0}")]
+    Token(#[from] JwtLibError),
+}
+
+/// Claims contained in a session token
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Claims {
+    /// Subject (the user) the token was issued to
+    pub sub: String,
+
+    /// Issuer of the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+
+    /// Audience the token is intended for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+
+    /// Time (seconds since the unix epoch) the token was issued
+    pub iat: u64,
+
+    /// Time (seconds since the unix epoch) the token expires
+    pub exp: u64,
+
+    /// Authentication methods used to authenticate the subject
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub amr: Vec<AuthMethod>,
+
+    /// Any additional (private) claims
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl Claims {
+    /// Adds an authentication method to the claims
+    ///
+    /// # Arguments
+    /// * `method` - Method used to authenticate the subject
+    pub fn with_amr(mut self, method: AuthMethod) -> Self {
+        if !self.amr.contains(&method) {
+            self.amr.push(method);
+        }
+        self
+    }
+
+    /// Adds an additional claim, replacing any existing claim with the same name
+    ///
+    /// # Arguments
+    /// * `name` - Name of the claim
+    /// * `value` - Value of the claim
+    pub fn with_claim<S, V>(mut self, name: S, value: V) -> Result<Self, JwtError>
+    where
+        S: Into<String>,
+        V: Serialize,
+    {
+        let value = serde_json::to_value(value).map_err(JwtLibError::from)?;
+        self.extra.insert(name.into(), value);
+        Ok(self)
+    }
+}
+
+/// Signs and verifies session tokens
+pub struct JwtIssuer {
+    /// Algorithm used to sign tokens
+    algorithm: Algorithm,
+
+    /// Key used to sign tokens
+    encoding: EncodingKey,
+
+    /// Key used to verify tokens
+    decoding: DecodingKey<'static>,
+
+    /// Key id placed in the header of issued tokens
+    kid: Option<String>,
+
+    /// Issuer placed in (and required of) tokens
+    iss: Option<String>,
+
+    /// Audience placed in (and required of) tokens
+    aud: Option<String>,
+
+    /// Lifetime of issued tokens, in seconds
+    ttl: u64,
+
+    /// Allowed clock skew, in seconds, when validating tokens
+    leeway: u64,
+}
+
+impl JwtIssuer {
+    /// Creates an issuer signing tokens with HMAC-SHA256
+    ///
+    /// # Arguments
+    /// * `secret` - Shared secret.  Should be at least 32 random bytes
+    pub fn hs256(secret: &[u8]) -> JwtIssuer {
+        JwtIssuer::with_keys(
+            Algorithm::HS256,
+            EncodingKey::from_secret(secret),
+            DecodingKey::from_secret(secret).into_static(),
+        )
+    }
+
+    /// Creates an issuer signing tokens with RSASSA-PKCS1-v1_5 w/ SHA-256
+    ///
+    /// # Arguments
+    /// * `private_key` - PEM-encoded RSA private key
+    /// * `public_key` - PEM-encoded RSA public key
+    pub fn rs256(private_key: &[u8], public_key: &[u8]) -> Result<JwtIssuer, JwtError> {
+        Ok(JwtIssuer::with_keys(
+            Algorithm::RS256,
+            EncodingKey::from_rsa_pem(private_key).map_err(JwtError::InvalidKey)?,
+            DecodingKey::from_rsa_pem(public_key)
+                .map_err(JwtError::InvalidKey)?
+                .into_static(),
+        ))
+    }
+
+    /// Creates an issuer signing tokens with ECDSA w/ P-256 and SHA-256
+    ///
+    /// # Arguments
+    /// * `private_key` - PEM-encoded (PKCS#8) EC private key
+    /// * `public_key` - PEM-encoded EC public key
+    pub fn es256(private_key: &[u8], public_key: &[u8]) -> Result<JwtIssuer, JwtError> {
+        Ok(JwtIssuer::with_keys(
+            Algorithm::ES256,
+            EncodingKey::from_ec_pem(private_key).map_err(JwtError::InvalidKey)?,
+            DecodingKey::from_ec_pem(public_key)
+                .map_err(JwtError::InvalidKey)?
+                .into_static(),
+        ))
+    }
+
+    fn with_keys(
+        algorithm: Algorithm,
+        encoding: EncodingKey,
+        decoding: DecodingKey<'static>,
+    ) -> JwtIssuer {
+        JwtIssuer {
+            algorithm,
+            encoding,
+            decoding,
+            kid: None,
+            iss: None,
+            aud: None,
+            ttl: DEFAULT_TTL,
+            leeway: 0,
+        }
+    }
+
+    /// Sets the key id placed in the header of issued tokens
+    pub fn key_id<S: Into<String>>(mut self, kid: S) -> Self {
+        self.kid = Some(kid.into());
+        self
+    }
+
+    /// Sets the issuer placed in issued tokens and required when verifying
+    pub fn issuer<S: Into<String>>(mut self, iss: S) -> Self {
+        self.iss = Some(iss.into());
+        self
+    }
+
+    /// Sets the audience placed in issued tokens and required when verifying
+    pub fn audience<S: Into<String>>(mut self, aud: S) -> Self {
+        self.aud = Some(aud.into());
+        self
+    }
+
+    /// Sets the lifetime of issued tokens, in seconds
+    pub fn ttl(mut self, ttl: u64) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the allowed clock skew, in seconds, when verifying tokens
+    pub fn leeway(mut self, leeway: u64) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Builds the claims for a new token, filling in the issuer, audience, issue and
+    /// expiry times
+    ///
+    /// # Arguments
+    /// * `sub` - Subject (the user) the token is issued to
+    pub fn claims<S: Into<String>>(&self, sub: S) -> Claims {
+        let iat = now();
+        Claims {
+            sub: sub.into(),
+            iss: self.iss.clone(),
+            aud: self.aud.clone(),
+            iat,
+            exp: iat + self.ttl,
+            amr: vec![],
+            extra: BTreeMap::new(),
+        }
+    }
+
+    /// Signs a set of claims
+    ///
+    /// # Arguments
+    /// * `claims` - Claims to place in the token
+    pub fn sign(&self, claims: &Claims) -> Result<String, JwtError> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.kid.clone();
+        Ok(encode(&header, claims, &self.encoding)?)
+    }
+
+    /// Issues a token for a user, using the base64url-encoded user handle as the subject
+    ///
+    /// # Arguments
+    /// * `user_handle` - User handle (id) of the authenticated user
+    /// * `amr` - Authentication methods used
+    pub fn issue(&self, user_handle: &[u8], amr: &[AuthMethod]) -> Result<String, JwtError> {
+        let sub = base64::encode_config(user_handle, base64::URL_SAFE_NO_PAD);
+        let claims = amr
+            .iter()
+            .fold(self.claims(sub), |claims, method| claims.with_amr(*method));
+        self.sign(&claims)
+    }
+
+    /// Verifies a token's signature, expiry, issuer and audience, returning its claims
+    ///
+    /// # Arguments
+    /// * `token` - Token presented by the client
+    pub fn verify<S: AsRef<str>>(&self, token: S) -> Result<Claims, JwtError> {
+        let validation = Validation {
+            leeway: self.leeway,
+            validate_exp: true,
+            iss: self.iss.clone(),
+            aud: self.aud.clone().map(|aud| {
+                let mut set = HashSet::new();
+                set.insert(aud);
+                set
+            }),
+            algorithms: vec![self.algorithm],
+            ..Default::default()
+        };
+
+        Ok(decode(token.as_ref(), &self.decoding, &validation)?.claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn issued_token_verifies() {
+        let issuer = JwtIssuer::hs256(SECRET)
+            .issuer("https://app.example.com")
+            .audience("app");
+        let token = issuer
+            .issue(&[0, 1, 2, 3], &AuthMethod::webauthn(true))
+            .unwrap();
+
+        let claims = issuer.verify(&token).unwrap();
+        assert_eq!(claims.sub, "AAECAw");
+        assert_eq!(claims.amr, vec![AuthMethod::Hwk, AuthMethod::Mfa]);
+        assert_eq!(claims.iss.as_deref(), Some("https://app.example.com"));
+    }
+
+    #[test]
+    fn extra_claims_round_trip() {
+        let issuer = JwtIssuer::hs256(SECRET);
+        let claims = issuer
+            .claims("user")
+            .with_amr(AuthMethod::Pwd)
+            .with_claim("role", "admin")
+            .unwrap();
+        let token = issuer.sign(&claims).unwrap();
+
+        assert_eq!(issuer.verify(&token).unwrap(), claims);
+    }
+
+    #[test]
+    fn invalid_tokens_are_rejected() {
+        let issuer = JwtIssuer::hs256(SECRET);
+        let token = issuer.issue(&[1], &[AuthMethod::Pwd]).unwrap();
+        assert!(JwtIssuer::hs256(b"another secret").verify(&token).is_err());
+        assert!(JwtIssuer::hs256(SECRET)
+            .issuer("other")
+            .verify(&token)
+            .is_err());
+
+        let mut claims = issuer.claims("user");
+        claims.exp = claims.iat - 60;
+        let expired = issuer.sign(&claims).unwrap();
+        assert!(issuer.verify(&expired).is_err());
+    }
+}
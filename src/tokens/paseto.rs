@@ -0,0 +1,602 @@
+//! Platform-Agnostic Security Tokens (PASETO) for post-authentication sessions
+//!
+//! Implements version 4 of the [PASETO specification](https://github.com/paseto-standard/paseto-spec):
+//!
+//! * `v4.local` - Symmetric authenticated encryption (XChaCha20 + BLAKE2b-MAC)
+//! * `v4.public` - Asymmetric signatures (Ed25519)
+//!
+//! Unlike JWTs, the algorithm is fixed by the version and purpose in the token's header so
+//! there is no algorithm negotiation to get wrong.  A [`PasetoIssuer`] issues and verifies
+//! tokens carrying the registered claims and the authentication methods (`amr`) used.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::tokens::paseto::{AuthMethod, PasetoIssuer};
+//!
+//! let issuer = PasetoIssuer::local(key).issuer("https://app.example.com");
+//!
+//! // after `password::Hasher::verify()` succeeds
+//! let token = issuer.issue(user.id(), &[AuthMethod::Pwd])?;
+//!
+//! // on subsequent requests
+//! let claims = issuer.verify(&token)?;
+//! ```
+
+pub use super::AuthMethod;
+
+use blake2::{
+    digest::{
+        consts::{U32, U56},
+        Mac,
+    },
+    Blake2bMac,
+};
+use chacha20::{
+    cipher::{KeyIvInit, StreamCipher},
+    XChaCha20,
+};
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use rand::RngCore;
+use ring::{
+    constant_time,
+    signature::{self, Ed25519KeyPair, KeyPair, UnparsedPublicKey},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Header of locally encrypted tokens
+const LOCAL_HEADER: &str = "v4.local.";
+
+/// Header of publicly signed tokens
+const PUBLIC_HEADER: &str = "v4.public.";
+
+/// Default lifetime (in seconds) of issued tokens
+pub const DEFAULT_TTL: i64 = 3600;
+
+#[derive(Error, Debug)]
+pub enum PasetoError {
+    #[error("token is malformed or failed authentication")]
+    InvalidToken,
+
+    #[error("key cannot be used for this operation")]
+    InvalidKey,
+
+    #[error("token has expired")]
+    Expired,
+
+    #[error("token is not yet valid")]
+    NotYetValid,
+
+    #[error("token claim `{0}` does not match the expected value")]
+    InvalidClaim(&'static str),
+
+    #[error("failed to encode or decode claims: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Claims contained in a session token
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Claims {
+    /// Subject (the user) the token was issued to
+    pub sub: String,
+
+    /// Issuer of the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+
+    /// Audience the token is intended for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+
+    /// Time the token was issued
+    pub iat: DateTime<Utc>,
+
+    /// Time the token expires
+    pub exp: DateTime<Utc>,
+
+    /// Time before which the token must not be accepted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<DateTime<Utc>>,
+
+    /// Unique identifier of the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+
+    /// Authentication methods used to authenticate the subject
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub amr: Vec<AuthMethod>,
+
+    /// Any additional (private) claims
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl Claims {
+    /// Adds an authentication method to the claims
+    ///
+    /// # Arguments
+    /// * `method` - Method used to authenticate the subject
+    pub fn with_amr(mut self, method: AuthMethod) -> Self {
+        if !self.amr.contains(&method) {
+            self.amr.push(method);
+        }
+        self
+    }
+
+    /// Adds an additional claim, replacing any existing claim with the same name
+    ///
+    /// # Arguments
+    /// * `name` - Name of the claim
+    /// * `value` - Value of the claim
+    pub fn with_claim<S, V>(mut self, name: S, value: V) -> Result<Self, PasetoError>
+    where
+        S: Into<String>,
+        V: Serialize,
+    {
+        self.extra.insert(name.into(), serde_json::to_value(value)?);
+        Ok(self)
+    }
+}
+
+/// Keys used by an issuer
+enum Keys {
+    /// Symmetric key for `v4.local` tokens
+    Local([u8; 32]),
+
+    /// Ed25519 key pair for signing and verifying `v4.public` tokens
+    Public(Ed25519KeyPair),
+
+    /// Ed25519 public key for verifying `v4.public` tokens
+    Verify(Vec<u8>),
+}
+
+/// Issues and verifies session tokens
+pub struct PasetoIssuer {
+    /// Keys used to seal and open tokens
+    keys: Keys,
+
+    /// Issuer placed in (and required of) tokens
+    iss: Option<String>,
+
+    /// Audience placed in (and required of) tokens
+    aud: Option<String>,
+
+    /// Lifetime of issued tokens
+    ttl: Duration,
+
+    /// Footer appended to (and required of) tokens
+    footer: Vec<u8>,
+
+    /// Implicit assertion bound to tokens (not included in the token)
+    implicit: Vec<u8>,
+}
+
+impl PasetoIssuer {
+    /// Creates an issuer for `v4.local` (encrypted) tokens
+    ///
+    /// # Arguments
+    /// * `key` - 256-bit symmetric key
+    pub fn local(key: [u8; 32]) -> PasetoIssuer {
+        PasetoIssuer::with_keys(Keys::Local(key))
+    }
+
+    /// Creates an issuer for `v4.public` (signed) tokens
+    ///
+    /// # Arguments
+    /// * `seed` - 256-bit Ed25519 private key seed
+    pub fn public(seed: &[u8; 32]) -> Result<PasetoIssuer, PasetoError> {
+        let pair =
+            Ed25519KeyPair::from_seed_unchecked(seed).map_err(|_| PasetoError::InvalidKey)?;
+        Ok(PasetoIssuer::with_keys(Keys::Public(pair)))
+    }
+
+    /// Creates a verifier for `v4.public` tokens that cannot issue tokens
+    ///
+    /// # Arguments
+    /// * `public_key` - 256-bit Ed25519 public key
+    pub fn verifier(public_key: &[u8]) -> PasetoIssuer {
+        PasetoIssuer::with_keys(Keys::Verify(public_key.to_vec()))
+    }
+
+    fn with_keys(keys: Keys) -> PasetoIssuer {
+        PasetoIssuer {
+            keys,
+            iss: None,
+            aud: None,
+            ttl: Duration::seconds(DEFAULT_TTL),
+            footer: vec![],
+            implicit: vec![],
+        }
+    }
+
+    /// Returns the Ed25519 public key used to verify `v4.public` tokens
+    pub fn public_key(&self) -> Option<&[u8]> {
+        match self.keys {
+            Keys::Local(_) => None,
+            Keys::Public(ref pair) => Some(pair.public_key().as_ref()),
+            Keys::Verify(ref key) => Some(key),
+        }
+    }
+
+    /// Sets the issuer placed in issued tokens and required when verifying
+    pub fn issuer<S: Into<String>>(mut self, iss: S) -> Self {
+        self.iss = Some(iss.into());
+        self
+    }
+
+    /// Sets the audience placed in issued tokens and required when verifying
+    pub fn audience<S: Into<String>>(mut self, aud: S) -> Self {
+        self.aud = Some(aud.into());
+        self
+    }
+
+    /// Sets the lifetime of issued tokens, in seconds
+    pub fn ttl(mut self, ttl: i64) -> Self {
+        self.ttl = Duration::seconds(ttl);
+        self
+    }
+
+    /// Sets the (unencrypted, but authenticated) footer of issued tokens, such as a key id
+    pub fn footer<B: Into<Vec<u8>>>(mut self, footer: B) -> Self {
+        self.footer = footer.into();
+        self
+    }
+
+    /// Sets an implicit assertion that is authenticated but not stored in the token
+    pub fn implicit<B: Into<Vec<u8>>>(mut self, implicit: B) -> Self {
+        self.implicit = implicit.into();
+        self
+    }
+
+    /// Builds the claims for a new token, filling in the issuer, audience, issue and
+    /// expiry times
+    ///
+    /// # Arguments
+    /// * `sub` - Subject (the user) the token is issued to
+    pub fn claims<S: Into<String>>(&self, sub: S) -> Claims {
+        let iat = Utc::now().trunc_subsecs(0);
+        Claims {
+            sub: sub.into(),
+            iss: self.iss.clone(),
+            aud: self.aud.clone(),
+            iat,
+            exp: iat + self.ttl,
+            nbf: None,
+            jti: None,
+            amr: vec![],
+            extra: BTreeMap::new(),
+        }
+    }
+
+    /// Encrypts or signs a set of claims
+    ///
+    /// # Arguments
+    /// * `claims` - Claims to place in the token
+    pub fn sign(&self, claims: &Claims) -> Result<String, PasetoError> {
+        let message = serde_json::to_vec(claims)?;
+        match self.keys {
+            Keys::Local(ref key) => Ok(encrypt(key, &message, &self.footer, &self.implicit)),
+            Keys::Public(ref pair) => Ok(sign(pair, &message, &self.footer, &self.implicit)),
+            Keys::Verify(_) => Err(PasetoError::InvalidKey),
+        }
+    }
+
+    /// Issues a token for a user, using the base64url-encoded user handle as the subject
+    ///
+    /// # Arguments
+    /// * `user_handle` - User handle (id) of the authenticated user
+    /// * `amr` - Authentication methods used
+    pub fn issue(&self, user_handle: &[u8], amr: &[AuthMethod]) -> Result<String, PasetoError> {
+        let sub = base64::encode_config(user_handle, base64::URL_SAFE_NO_PAD);
+        let claims = amr
+            .iter()
+            .fold(self.claims(sub), |claims, method| claims.with_amr(*method));
+        self.sign(&claims)
+    }
+
+    /// Verifies a token's authenticity, expiry, issuer and audience, returning its claims
+    ///
+    /// # Arguments
+    /// * `token` - Token presented by the client
+    pub fn verify<S: AsRef<str>>(&self, token: S) -> Result<Claims, PasetoError> {
+        let token = token.as_ref();
+        let message = match self.keys {
+            Keys::Local(ref key) => decrypt(key, token, &self.footer, &self.implicit)?,
+            Keys::Public(ref pair) => verify(
+                pair.public_key().as_ref(),
+                token,
+                &self.footer,
+                &self.implicit,
+            )?,
+            Keys::Verify(ref key) => verify(key, token, &self.footer, &self.implicit)?,
+        };
+
+        let claims: Claims = serde_json::from_slice(&message)?;
+        let now = Utc::now();
+        if claims.exp < now {
+            return Err(PasetoError::Expired);
+        }
+
+        if claims.nbf.map(|nbf| nbf > now).unwrap_or(false) {
+            return Err(PasetoError::NotYetValid);
+        }
+
+        if self.iss.is_some() && claims.iss != self.iss {
+            return Err(PasetoError::InvalidClaim("iss"));
+        }
+
+        if self.aud.is_some() && claims.aud != self.aud {
+            return Err(PasetoError::InvalidClaim("aud"));
+        }
+
+        Ok(claims)
+    }
+}
+
+/// Encrypts a message into a `v4.local` token
+///
+/// # Arguments
+/// * `key` - 256-bit symmetric key
+/// * `message` - Message (payload) to encrypt
+/// * `footer` - Optional footer, authenticated but not encrypted
+/// * `implicit` - Optional implicit assertion, authenticated but not stored in the token
+pub fn encrypt(key: &[u8; 32], message: &[u8], footer: &[u8], implicit: &[u8]) -> String {
+    let mut nonce = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    encrypt_with_nonce(key, &nonce, message, footer, implicit)
+}
+
+fn encrypt_with_nonce(
+    key: &[u8; 32],
+    nonce: &[u8; 32],
+    message: &[u8],
+    footer: &[u8],
+    implicit: &[u8],
+) -> String {
+    let (ek, n2, ak) = split_key(key, nonce);
+
+    let mut c = message.to_vec();
+    XChaCha20::new(&ek.into(), &n2.into()).apply_keystream(&mut c);
+
+    let pre_auth = pae(&[LOCAL_HEADER.as_bytes(), nonce, &c, footer, implicit]);
+    let t = mac::<Blake2bMac<U32>>(&ak, &[&pre_auth]);
+
+    let mut body = nonce.to_vec();
+    body.extend_from_slice(&c);
+    body.extend_from_slice(&t);
+    token(LOCAL_HEADER, &body, footer)
+}
+
+/// Decrypts a `v4.local` token, returning the message
+///
+/// # Arguments
+/// * `key` - 256-bit symmetric key
+/// * `token` - Token to decrypt
+/// * `footer` - Footer the token is expected to contain
+/// * `implicit` - Implicit assertion the token was created with
+pub fn decrypt(
+    key: &[u8; 32],
+    token: &str,
+    footer: &[u8],
+    implicit: &[u8],
+) -> Result<Vec<u8>, PasetoError> {
+    let body = open(token, LOCAL_HEADER, footer)?;
+    if body.len() < 64 {
+        return Err(PasetoError::InvalidToken);
+    }
+
+    let (nonce, rest) = body.split_at(32);
+    let (c, t) = rest.split_at(rest.len() - 32);
+
+    let mut n = [0u8; 32];
+    n.copy_from_slice(nonce);
+    let (ek, n2, ak) = split_key(key, &n);
+
+    let pre_auth = pae(&[LOCAL_HEADER.as_bytes(), nonce, c, footer, implicit]);
+    let t2 = mac::<Blake2bMac<U32>>(&ak, &[&pre_auth]);
+    constant_time::verify_slices_are_equal(t, &t2).map_err(|_| PasetoError::InvalidToken)?;
+
+    let mut message = c.to_vec();
+    XChaCha20::new(&ek.into(), &n2.into()).apply_keystream(&mut message);
+    Ok(message)
+}
+
+/// Signs a message into a `v4.public` token
+///
+/// # Arguments
+/// * `pair` - Ed25519 key pair
+/// * `message` - Message (payload) to sign
+/// * `footer` - Optional footer
+/// * `implicit` - Optional implicit assertion, authenticated but not stored in the token
+pub fn sign(pair: &Ed25519KeyPair, message: &[u8], footer: &[u8], implicit: &[u8]) -> String {
+    let m2 = pae(&[PUBLIC_HEADER.as_bytes(), message, footer, implicit]);
+    let sig = pair.sign(&m2);
+
+    let mut body = message.to_vec();
+    body.extend_from_slice(sig.as_ref());
+    token(PUBLIC_HEADER, &body, footer)
+}
+
+/// Verifies a `v4.public` token, returning the message
+///
+/// # Arguments
+/// * `public_key` - Ed25519 public key
+/// * `token` - Token to verify
+/// * `footer` - Footer the token is expected to contain
+/// * `implicit` - Implicit assertion the token was created with
+pub fn verify(
+    public_key: &[u8],
+    token: &str,
+    footer: &[u8],
+    implicit: &[u8],
+) -> Result<Vec<u8>, PasetoError> {
+    let body = open(token, PUBLIC_HEADER, footer)?;
+    if body.len() < 64 {
+        return Err(PasetoError::InvalidToken);
+    }
+
+    let (message, sig) = body.split_at(body.len() - 64);
+    let m2 = pae(&[PUBLIC_HEADER.as_bytes(), message, footer, implicit]);
+    UnparsedPublicKey::new(&signature::ED25519, public_key)
+        .verify(&m2, sig)
+        .map_err(|_| PasetoError::InvalidToken)?;
+
+    Ok(message.to_vec())
+}
+
+/// Derives the encryption key, XChaCha20 nonce and authentication key for a token
+fn split_key(key: &[u8; 32], nonce: &[u8; 32]) -> ([u8; 32], [u8; 24], [u8; 32]) {
+    let tmp = mac::<Blake2bMac<U56>>(key, &[b"paseto-encryption-key", nonce]);
+    let ak = mac::<Blake2bMac<U32>>(key, &[b"paseto-auth-key-for-aead", nonce]);
+
+    let mut ek = [0u8; 32];
+    let mut n2 = [0u8; 24];
+    let mut a = [0u8; 32];
+    ek.copy_from_slice(&tmp[..32]);
+    n2.copy_from_slice(&tmp[32..]);
+    a.copy_from_slice(&ak);
+    (ek, n2, a)
+}
+
+/// Computes a keyed BLAKE2b hash over the concatenation of `parts`
+fn mac<M: Mac + blake2::digest::KeyInit>(key: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("key is at most 64 bytes");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Pre-Authentication Encoding (PAE) of a list of pieces
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    let mut out = le64(pieces.len() as u64).to_vec();
+    for piece in pieces {
+        out.extend_from_slice(&le64(piece.len() as u64));
+        out.extend_from_slice(piece);
+    }
+    out
+}
+
+/// Little-endian encoding of an unsigned integer with the most significant bit cleared
+fn le64(n: u64) -> [u8; 8] {
+    (n & (u64::MAX >> 1)).to_le_bytes()
+}
+
+/// Assembles a token from its header, body and footer
+fn token(header: &str, body: &[u8], footer: &[u8]) -> String {
+    let mut token = format!(
+        "{}{}",
+        header,
+        base64::encode_config(body, base64::URL_SAFE_NO_PAD)
+    );
+
+    if !footer.is_empty() {
+        token.push('.');
+        token.push_str(&base64::encode_config(footer, base64::URL_SAFE_NO_PAD));
+    }
+
+    token
+}
+
+/// Checks a token's header and footer, returning the decoded body
+fn open(token: &str, header: &str, footer: &[u8]) -> Result<Vec<u8>, PasetoError> {
+    if !token.starts_with(header) {
+        return Err(PasetoError::InvalidToken);
+    }
+
+    let mut parts = token[header.len()..].splitn(2, '.');
+    let body = parts.next().unwrap_or_default();
+    let found = match parts.next() {
+        Some(f) => base64::decode_config(f, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| PasetoError::InvalidToken)?,
+        None => vec![],
+    };
+
+    constant_time::verify_slices_are_equal(&found, footer)
+        .map_err(|_| PasetoError::InvalidToken)?;
+
+    base64::decode_config(body, base64::URL_SAFE_NO_PAD).map_err(|_| PasetoError::InvalidToken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    const MESSAGE: &str =
+        r#"{"data":"this is a secret message","exp":"2022-01-01T00:00:00+00:00"}"#;
+
+    #[test]
+    fn local_round_trips() {
+        let key = [7u8; 32];
+        let token = encrypt(&key, MESSAGE.as_bytes(), b"kid", b"");
+        assert!(token.starts_with("v4.local."));
+        assert_eq!(
+            decrypt(&key, &token, b"kid", b"").unwrap(),
+            MESSAGE.as_bytes()
+        );
+        assert!(decrypt(&key, &token, b"other", b"").is_err());
+        assert!(decrypt(&key, &token, b"kid", b"implicit").is_err());
+        assert!(decrypt(&[8u8; 32], &token, b"kid", b"").is_err());
+    }
+
+    #[test]
+    fn public_signature_matches_test_vector() {
+        // test vector 4-S-1 from the PASETO specification
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(&hex(
+            "b4cbfb43df4ce210727d953e4a713307fa19bb7d9f85041438d9e11b942a3774",
+        ));
+        let pair = Ed25519KeyPair::from_seed_unchecked(&seed).unwrap();
+        let message = r#"{"data":"this is a signed message","exp":"2022-01-01T00:00:00+00:00"}"#;
+
+        let token = sign(&pair, message.as_bytes(), b"", b"");
+        assert_eq!(token, "v4.public.eyJkYXRhIjoidGhpcyBpcyBhIHNpZ25lZCBtZXNzYWdlIiwiZXhwIjoiMjAyMi0wMS0wMVQwMDowMDowMCswMDowMCJ9bg_XBBzds8lTZShVlwwKSgeKpLT3yukTw6JUz3W4h_ExsQV-P0V54zemZDcAxFaSeef1QlXEFtkqxT1ciiQEDA");
+        assert_eq!(
+            verify(pair.public_key().as_ref(), &token, b"", b"").unwrap(),
+            message.as_bytes()
+        );
+    }
+
+    #[test]
+    fn local_encryption_matches_test_vector() {
+        // test vector 4-E-1 from the PASETO specification
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&hex(
+            "707172737475767778797a7b7c7d7e7f808182838485868788898a8b8c8d8e8f",
+        ));
+
+        let token = encrypt_with_nonce(&key, &[0u8; 32], MESSAGE.as_bytes(), b"", b"");
+        assert_eq!(token, "v4.local.AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAQAr68PS4AXe7If_ZgesdkUMvSwscFlAl1pk5HC0e8kApeaqMfGo_7OpBnwJOAbY9V7WU6abu74MmcUE8YWAiaArVI8XJ5hOb_4v9RmDkneN0S92dx0OW4pgy7omxgf3S8c3LlQg");
+    }
+
+    #[test]
+    fn issued_claims_verify() {
+        let issuer = PasetoIssuer::public(&[3u8; 32])
+            .unwrap()
+            .issuer("https://app.example.com");
+        let token = issuer
+            .issue(&[0, 1, 2, 3], &AuthMethod::webauthn(false))
+            .unwrap();
+
+        let claims = PasetoIssuer::verifier(issuer.public_key().unwrap())
+            .issuer("https://app.example.com")
+            .verify(&token)
+            .unwrap();
+        assert_eq!(claims.sub, "AAECAw");
+        assert_eq!(claims.amr, vec![AuthMethod::Hwk, AuthMethod::User]);
+
+        let mut expired = issuer.claims("user");
+        expired.exp = expired.iat - Duration::seconds(60);
+        let token = issuer.sign(&expired).unwrap();
+        assert!(matches!(issuer.verify(&token), Err(PasetoError::Expired)));
+    }
+}
@@ -0,0 +1,569 @@
+//! Legacy FIDO U2F registration and authentication
+//!
+//! Implements the relying party side of the U2F JavaScript API (`u2f.register` and
+//! `u2f.sign`) so services with existing U2F integrations can keep accepting security
+//! keys while they move to WebAuthn.  Responses are parsed from the raw message formats
+//! ([FIDO U2F Raw Message Formats](https://fidoalliance.org/specs/fido-u2f-v1.2-ps-20170411/fido-u2f-raw-message-formats-v1.2-ps-20170411.html))
+//! and checked against the application's `appId` and trusted facets.
+//!
+//! Keys registered here keep working after the move to WebAuthn: request the `appid`
+//! extension with the same `appId` and use the stored key handle as the credential id.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::u2f::U2f;
+//!
+//! let u2f = U2f::new("https://example.com");
+//!
+//! // registration: send `request` to `u2f.register`, keep its challenge in the session
+//! let request = u2f.register_request();
+//! let registration = u2f.register(&request.challenge, &response)?;
+//!
+//! // authentication: send `request` to `u2f.sign`
+//! let request = u2f.sign_request(&registrations);
+//! let registration = find_registration(&response.key_handle)?;
+//! u2f.sign(&request.challenge, &response, &mut registration)?;
+//! ```
+
+use crate::events::{self, AuthEvent};
+use rand::RngCore;
+use ring::{
+    constant_time,
+    digest::{self, digest},
+    signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use webpki::EndEntityCert;
+
+/// Protocol version of the U2F raw message formats
+const VERSION: &str = "U2F_V2";
+
+/// Length of an uncompressed P-256 public key
+const PUBLIC_KEY_LEN: usize = 65;
+
+/// Length, in bytes, of generated challenges
+const CHALLENGE_LEN: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum U2fError {
+    #[error("malformed u2f response: {0}")]
+    Malformed(&'static str),
+
+    #[error("u2f client reported error code {0}")]
+    Client(u32),
+
+    #[error("client data has the wrong type")]
+    WrongType,
+
+    #[error("challenge does not match")]
+    ChallengeMismatch,
+
+    #[error("origin is not a trusted facet of the app id")]
+    UntrustedFacet,
+
+    #[error("key handle does not match the registration")]
+    UnknownKeyHandle,
+
+    #[error("user presence was not asserted")]
+    UserNotPresent,
+
+    #[error("signature is invalid")]
+    InvalidSignature,
+
+    #[error("attestation certificate is invalid: {0}")]
+    Certificate(#[from] webpki::Error),
+}
+
+/// Parameters for `u2f.register`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterRequest {
+    /// Protocol version (`U2F_V2`)
+    pub version: String,
+
+    /// Websafe base64 challenge, to be kept until the response arrives
+    pub challenge: String,
+
+    /// App id the key is registered to
+    pub app_id: String,
+}
+
+/// Result of `u2f.register`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterResponse {
+    /// Websafe base64 registration response message
+    #[serde(default)]
+    pub registration_data: String,
+
+    /// Websafe base64 client data
+    #[serde(default)]
+    pub client_data: String,
+
+    /// Error reported by the client instead of a response
+    #[serde(default)]
+    pub error_code: u32,
+}
+
+/// A key registered for a user
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RegisteredKey {
+    /// Protocol version (`U2F_V2`)
+    pub version: String,
+
+    /// Websafe base64 key handle
+    #[serde(rename = "keyHandle")]
+    pub key_handle: String,
+}
+
+/// Parameters for `u2f.sign`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignRequest {
+    /// App id the keys were registered to
+    pub app_id: String,
+
+    /// Websafe base64 challenge, to be kept until the response arrives
+    pub challenge: String,
+
+    /// Keys the user may sign with
+    pub registered_keys: Vec<RegisteredKey>,
+}
+
+/// Result of `u2f.sign`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignResponse {
+    /// Websafe base64 key handle of the key used
+    #[serde(default)]
+    pub key_handle: String,
+
+    /// Websafe base64 authentication response message
+    #[serde(default)]
+    pub signature_data: String,
+
+    /// Websafe base64 client data
+    #[serde(default)]
+    pub client_data: String,
+
+    /// Error reported by the client instead of a response
+    #[serde(default)]
+    pub error_code: u32,
+}
+
+/// Client data signed over by the security key
+#[derive(Deserialize)]
+struct ClientData {
+    /// `navigator.id.finishEnrollment` or `navigator.id.getAssertion`
+    typ: String,
+
+    /// Challenge from the request
+    challenge: String,
+
+    /// Origin of the page that made the request
+    origin: String,
+}
+
+/// Stored representation of a registered security key
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct U2fRegistration {
+    /// Key handle identifying the key pair on the security key
+    key_handle: Vec<u8>,
+
+    /// Uncompressed P-256 public key
+    public_key: Vec<u8>,
+
+    /// DER-encoded attestation certificate
+    certificate: Vec<u8>,
+
+    /// Last signature counter received
+    counter: u32,
+}
+
+impl U2fRegistration {
+    /// Returns the key handle, which is also the credential id under WebAuthn
+    pub fn key_handle(&self) -> &[u8] {
+        &self.key_handle
+    }
+
+    /// Returns the uncompressed (ANSI X9.62) P-256 public key
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Returns the DER-encoded attestation certificate of the security key
+    pub fn attestation_certificate(&self) -> &[u8] {
+        &self.certificate
+    }
+
+    /// Returns the last signature counter received
+    pub fn counter(&self) -> u32 {
+        self.counter
+    }
+}
+
+/// Registers and authenticates U2F security keys for an app id
+#[derive(Clone, Debug)]
+pub struct U2f {
+    /// App id keys are registered to
+    app_id: String,
+
+    /// Origins allowed to use the app id
+    facets: Vec<String>,
+}
+
+impl U2f {
+    /// Creates a relying party whose only trusted facet is the origin of the app id
+    ///
+    /// # Arguments
+    /// * `app_id` - App id (e.g., `https://example.com` or the url of a facet list)
+    pub fn new<S: Into<String>>(app_id: S) -> U2f {
+        let app_id = app_id.into();
+        let facets = vec![origin(&app_id).to_owned()];
+        U2f { app_id, facets }
+    }
+
+    /// Trusts an additional facet (origin)
+    ///
+    /// # Arguments
+    /// * `origin` - Origin allowed to use the app id (e.g., `https://login.example.com`)
+    pub fn facet<S: Into<String>>(mut self, origin: S) -> Self {
+        self.facets.push(origin.into());
+        self
+    }
+
+    /// Creates the parameters for `u2f.register`
+    pub fn register_request(&self) -> RegisterRequest {
+        RegisterRequest {
+            version: VERSION.to_owned(),
+            challenge: challenge(),
+            app_id: self.app_id.clone(),
+        }
+    }
+
+    /// Validates the result of `u2f.register`, returning the registration to store
+    ///
+    /// # Arguments
+    /// * `challenge` - Challenge of the register request
+    /// * `response` - Result of `u2f.register`
+    pub fn register(
+        &self,
+        challenge: &str,
+        response: &RegisterResponse,
+    ) -> Result<U2fRegistration, U2fError> {
+        if response.error_code != 0 {
+            return Err(U2fError::Client(response.error_code));
+        }
+
+        let client_data = decode(&response.client_data)?;
+        self.check_client_data(&client_data, "navigator.id.finishEnrollment", challenge)?;
+
+        // 0x05 || public key || key handle length || key handle || certificate || signature
+        let data = decode(&response.registration_data)?;
+        let truncated = U2fError::Malformed("truncated registration data");
+        if data.first() != Some(&0x05) || data.len() < PUBLIC_KEY_LEN + 2 {
+            return Err(U2fError::Malformed("invalid registration data"));
+        }
+        let public_key = &data[1..1 + PUBLIC_KEY_LEN];
+        let handle_len = usize::from(data[1 + PUBLIC_KEY_LEN]);
+        let rest = &data[PUBLIC_KEY_LEN + 2..];
+        if rest.len() < handle_len {
+            return Err(truncated);
+        }
+        let (key_handle, rest) = rest.split_at(handle_len);
+        let cert_len = der_len(rest).ok_or(truncated)?;
+        let (certificate, signature) = rest.split_at(cert_len);
+
+        let mut signed = vec![0x00];
+        signed.extend_from_slice(digest(&digest::SHA256, self.app_id.as_bytes()).as_ref());
+        signed.extend_from_slice(digest(&digest::SHA256, &client_data).as_ref());
+        signed.extend_from_slice(key_handle);
+        signed.extend_from_slice(public_key);
+
+        EndEntityCert::from(certificate)?
+            .verify_signature(&webpki::ECDSA_P256_SHA256, &signed, signature)
+            .map_err(|_| U2fError::InvalidSignature)?;
+
+        Ok(U2fRegistration {
+            key_handle: key_handle.to_vec(),
+            public_key: public_key.to_vec(),
+            certificate: certificate.to_vec(),
+            counter: 0,
+        })
+    }
+
+    /// Creates the parameters for `u2f.sign`
+    ///
+    /// # Arguments
+    /// * `registrations` - Keys registered by the user
+    pub fn sign_request(&self, registrations: &[U2fRegistration]) -> SignRequest {
+        SignRequest {
+            app_id: self.app_id.clone(),
+            challenge: challenge(),
+            registered_keys: registrations
+                .iter()
+                .map(|r| RegisteredKey {
+                    version: VERSION.to_owned(),
+                    key_handle: base64::encode_config(&r.key_handle, base64::URL_SAFE_NO_PAD),
+                })
+                .collect(),
+        }
+    }
+
+    /// Validates the result of `u2f.sign`, updating the registration's counter.  The
+    /// registration must be saved afterwards
+    ///
+    /// # Arguments
+    /// * `challenge` - Challenge of the sign request
+    /// * `response` - Result of `u2f.sign`
+    /// * `registration` - Registration of the key identified by the response's key handle
+    pub fn sign(
+        &self,
+        challenge: &str,
+        response: &SignResponse,
+        registration: &mut U2fRegistration,
+    ) -> Result<(), U2fError> {
+        if response.error_code != 0 {
+            return Err(U2fError::Client(response.error_code));
+        }
+
+        let key_handle = decode(&response.key_handle)?;
+        constant_time::verify_slices_are_equal(&key_handle, &registration.key_handle)
+            .map_err(|_| U2fError::UnknownKeyHandle)?;
+
+        let client_data = decode(&response.client_data)?;
+        self.check_client_data(&client_data, "navigator.id.getAssertion", challenge)?;
+
+        // user presence || counter || signature
+        let data = decode(&response.signature_data)?;
+        if data.len() < 6 {
+            return Err(U2fError::Malformed("truncated signature data"));
+        }
+        let (flags_counter, signature) = data.split_at(5);
+
+        let mut signed = digest(&digest::SHA256, self.app_id.as_bytes())
+            .as_ref()
+            .to_vec();
+        signed.extend_from_slice(flags_counter);
+        signed.extend_from_slice(digest(&digest::SHA256, &client_data).as_ref());
+
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, &registration.public_key)
+            .verify(&signed, signature)
+            .map_err(|_| U2fError::InvalidSignature)?;
+
+        if flags_counter[0] & 0x01 == 0 {
+            return Err(U2fError::UserNotPresent);
+        }
+
+        // a counter that fails to increase may indicate a cloned security key
+        let counter = u32::from_be_bytes([
+            flags_counter[1],
+            flags_counter[2],
+            flags_counter[3],
+            flags_counter[4],
+        ]);
+        if counter <= registration.counter {
+            events::emit(AuthEvent::AssertionCounterRegressed {
+                credential: registration.key_handle.clone(),
+                stored: registration.counter,
+                received: counter,
+            });
+        }
+        registration.counter = counter;
+
+        Ok(())
+    }
+
+    /// Checks the type, challenge and origin of the client data
+    fn check_client_data(
+        &self,
+        client_data: &[u8],
+        typ: &str,
+        challenge: &str,
+    ) -> Result<(), U2fError> {
+        let client_data: ClientData = serde_json::from_slice(client_data)
+            .map_err(|_| U2fError::Malformed("invalid client data"))?;
+
+        if client_data.typ != typ {
+            return Err(U2fError::WrongType);
+        }
+
+        constant_time::verify_slices_are_equal(
+            client_data.challenge.trim_end_matches('=').as_bytes(),
+            challenge.trim_end_matches('=').as_bytes(),
+        )
+        .map_err(|_| U2fError::ChallengeMismatch)?;
+
+        match self.facets.contains(&client_data.origin) {
+            true => Ok(()),
+            false => Err(U2fError::UntrustedFacet),
+        }
+    }
+}
+
+/// Generates a random websafe base64 challenge
+fn challenge() -> String {
+    let mut challenge = [0u8; CHALLENGE_LEN];
+    rand::thread_rng().fill_bytes(&mut challenge);
+    base64::encode_config(challenge, base64::URL_SAFE_NO_PAD)
+}
+
+/// Decodes websafe base64, with or without padding
+fn decode(value: &str) -> Result<Vec<u8>, U2fError> {
+    base64::decode_config(value.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+        .map_err(|_| U2fError::Malformed("invalid base64"))
+}
+
+/// Returns the origin (`scheme://host[:port]`) of a url
+fn origin(url: &str) -> &str {
+    let start = url.find("://").map(|idx| idx + 3).unwrap_or(0);
+    match url[start..].find('/') {
+        Some(idx) => &url[..start + idx],
+        None => url,
+    }
+}
+
+/// Returns the total length of the DER element at the start of a buffer
+fn der_len(der: &[u8]) -> Option<usize> {
+    let (header, len) = match *der.get(1)? {
+        len if len < 0x80 => (2, usize::from(len)),
+        0x81 => (3, usize::from(*der.get(2)?)),
+        0x82 => (
+            4,
+            usize::from(u16::from_be_bytes([*der.get(2)?, *der.get(3)?])),
+        ),
+        _ => return None,
+    };
+
+    match header + len <= der.len() {
+        true => Some(header + len),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Registration of key handle `00..1f` to `https://example.com`, challenge `cmVnaXN0ZXI`
+    const REGISTRATION_DATA: &str = "\
+        BQRheBz8LOpzmM2xXZxzFt6IYNKdRtSMGKrdSMyurY_iOtjCuzcURJARexxz2lrFacYVbwg3AofyQurmwMNKRm91\
+        IAABAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHR4fMIIBPjCB5KADAgECAgID6DAKBggqhkjOPQQDAjAfMR0w\
+        GwYDVQQDDBRUZXN0IFUyRiBBdHRlc3RhdGlvbjAeFw0yMDAxMDEwMDAwMDBaFw00MDAxMDEwMDAwMDBaMB8xHTAb\
+        BgNVBAMMFFRlc3QgVTJGIEF0dGVzdGF0aW9uMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEhSWMWWHfIW_Xx98S\
+        LexPrMUnLtBOYXJbnKiF9PjLsoKXEtpBi8ivv83LmH-FkzTqom1yMlHV7eP_WGGq6n3tXaMQMA4wDAYDVR0TAQH_\
+        BAIwADAKBggqhkjOPQQDAgNJADBGAiEAmGDuXHTAm8VpJ8E_NiyEL6R5xNP3fxt1508ClFjXTRICIQCeOC9ReEqW\
+        66we-F0-QG35Ic7ucFWM6bKlKTq8fMrQejBGAiEA6BpSxX0KLBNgq2IevAfUqHhZiKfWYzbuuPKfTOZvCDkCIQC5\
+        mBJRg76UkiLwjHEtsK3C_1p7uvL73BSlvdYJBcHcsw";
+
+    /// Client data of the registration
+    const REGISTER_CLIENT_DATA: &str = "\
+        eyJ0eXAiOiJuYXZpZ2F0b3IuaWQuZmluaXNoRW5yb2xsbWVudCIsImNoYWxsZW5nZSI6ImNtVm5hWE4wWlhJIiwi\
+        b3JpZ2luIjoiaHR0cHM6Ly9leGFtcGxlLmNvbSJ9";
+
+    /// Signature with counter 7 over challenge `c2lnbg`
+    const SIGNATURE_DATA: &str = "\
+        AQAAAAcwRAIgc3I6Dth_25VPPCskLx-3x18n1aPENYevOBgEPtDPZWcCIHbKPf91s-L5ylkusKmiit2wzrz5SLve\
+        5tzZuN5RQNcT";
+
+    /// Client data of the signature
+    const SIGN_CLIENT_DATA: &str = "\
+        eyJ0eXAiOiJuYXZpZ2F0b3IuaWQuZ2V0QXNzZXJ0aW9uIiwiY2hhbGxlbmdlIjoiYzJsbmJnIiwib3JpZ2luIjoi\
+        aHR0cHM6Ly9leGFtcGxlLmNvbSJ9";
+
+    fn registration() -> U2fRegistration {
+        let response = RegisterResponse {
+            registration_data: REGISTRATION_DATA.to_owned(),
+            client_data: REGISTER_CLIENT_DATA.to_owned(),
+            error_code: 0,
+        };
+        U2f::new("https://example.com")
+            .register("cmVnaXN0ZXI", &response)
+            .unwrap()
+    }
+
+    fn sign_response() -> SignResponse {
+        SignResponse {
+            key_handle: "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8".to_owned(),
+            signature_data: SIGNATURE_DATA.to_owned(),
+            client_data: SIGN_CLIENT_DATA.to_owned(),
+            error_code: 0,
+        }
+    }
+
+    #[test]
+    fn register_and_sign() {
+        let u2f = U2f::new("https://example.com/u2f/app-id.json");
+        let mut registration = registration();
+        assert_eq!(registration.key_handle(), (0..32).collect::<Vec<u8>>());
+        assert_eq!(registration.public_key().len(), PUBLIC_KEY_LEN);
+
+        let request = u2f.sign_request(&[registration.clone()]);
+        assert_eq!(
+            request.registered_keys[0].key_handle,
+            sign_response().key_handle
+        );
+
+        // the app id differs from the one registered to, so the signature can't verify
+        assert!(matches!(
+            u2f.sign("c2lnbg", &sign_response(), &mut registration),
+            Err(U2fError::InvalidSignature)
+        ));
+
+        let u2f = U2f::new("https://example.com");
+        u2f.sign("c2lnbg", &sign_response(), &mut registration)
+            .unwrap();
+        assert_eq!(registration.counter(), 7);
+    }
+
+    #[test]
+    fn client_data_is_checked() {
+        let u2f = U2f::new("https://example.com");
+        let mut registration = registration();
+
+        assert!(matches!(
+            u2f.sign("b3RoZXI", &sign_response(), &mut registration),
+            Err(U2fError::ChallengeMismatch)
+        ));
+        assert!(matches!(
+            U2f::new("https://example.org").sign("c2lnbg", &sign_response(), &mut registration),
+            Err(U2fError::UntrustedFacet)
+        ));
+
+        let response = RegisterResponse {
+            registration_data: REGISTRATION_DATA.to_owned(),
+            client_data: SIGN_CLIENT_DATA.to_owned(),
+            error_code: 0,
+        };
+        assert!(matches!(
+            u2f.register("c2lnbg", &response),
+            Err(U2fError::WrongType)
+        ));
+    }
+
+    #[test]
+    fn tampered_signature_is_rejected() {
+        let u2f = U2f::new("https://example.com");
+        let mut registration = registration();
+        let mut response = sign_response();
+
+        // raise the counter without re-signing
+        response.signature_data = response.signature_data.replacen("AQAAAAc", "AQAAAAg", 1);
+        assert!(matches!(
+            u2f.sign("c2lnbg", &response, &mut registration),
+            Err(U2fError::InvalidSignature)
+        ));
+        assert_eq!(registration.counter(), 0);
+    }
+
+    #[test]
+    fn origin_of_app_id() {
+        assert_eq!(origin("https://example.com"), "https://example.com");
+        assert_eq!(
+            origin("https://example.com:8443/app-id.json"),
+            "https://example.com:8443"
+        );
+    }
+}
@@ -38,7 +38,7 @@
 //!
 //!     // Attempt to validate the register request
 //!     match webauthn::register(form, &cfg, challenge) {
-//!         Ok(device) => { /* save device in backing database/etc */ }
+//!         Ok(result) => { /* save result.device() in backing database/etc */ }
 //!         Err(e) => panic!("failed to validate register request: {}", e),
 //!     }
 //! }
@@ -64,11 +64,19 @@
 //!     let devices = /* load all registered devices for a user from backing database/etc. */;
 //!      
 //!     match webauthn::authenticate(form. &cfg, devices) {
-//!         Ok(_) => /* success! finish logging user in */,
+//!         Ok(result) => /* success! update the stored count, finish logging user in */,
 //!         Err(e) => panic!("failed to validate login request: {}", e),
 //!     }
 //! }
 //! ```
+//!
+//! # wasm32
+//!
+//! Validation runs on `wasm32-unknown-unknown` hosts such as Cloudflare Workers and Fastly
+//! Compute.  `ring` is built with its `wasm32_c` feature there (so `clang` must be able to
+//! target wasm32), randomness comes from the host's `crypto.getRandomValues` and the
+//! clock from `Date.now()`.  Use the async [`integrations::Webauthn`] ceremonies with async
+//! stores on wasm.
 
 mod common;
 mod config;
@@ -78,20 +86,52 @@ mod response;
 mod rp;
 mod user;
 
+#[cfg(feature = "aaguid-names")]
+pub mod aaguid;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod csrf;
+#[cfg(feature = "ctap")]
+pub mod ctap;
+pub mod extensions;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+pub mod fuzz;
+pub mod integrations;
+pub mod policy;
+pub mod report;
 pub mod request;
+pub mod revocation;
+pub mod state;
+pub mod stepup;
+pub mod store;
+pub mod trust;
+pub mod verifier;
 
-pub use config::Config;
+#[cfg(feature = "web")]
+pub mod web;
+
+pub use config::{Config, ConfigBuilder, ConfigError, ConfigSet, CounterPolicy, CrossOriginPolicy};
 pub use error::Error;
+pub use extensions::Extension;
+pub use pk::Transport;
+pub use policy::{Ceremony, CeremonyPolicy};
+pub use report::ValidationReport;
 pub use request::{AuthenticateRequest, RegisterRequest};
-pub use response::{authenticate, register, Response};
+pub use response::{
+    authenticate, authenticate_dry_run, authenticate_with_context, authenticate_with_report,
+    register, register_dry_run, register_with_report, AuthenticationResult, RegistrationResult,
+    Response,
+};
 pub use user::WebAuthnUser;
+pub use verifier::VerifierContext;
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// The different response types that are possible to receive after receiveing
 /// data from the client
-#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum WebAuthnType {
     /// Corresponds to the `navigator.credentials.create()` client api
     #[serde(alias = "webauthn.create")]
@@ -122,7 +162,7 @@ impl WebAuthnType {
 /// device that the user will use to authenticate with the app (e.g., YubiKey).
 /// The information contained in this struct is everything needed to authenticate
 /// a user against a specific token
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Device {
     /// The devices's credential id. A unique value per device
     id: Vec<u8>,
@@ -132,6 +172,10 @@ pub struct Device {
 
     /// The number of times this has been used
     count: u32,
+
+    /// Transports the client reported for the device when it was registered
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    transports: Vec<Transport>,
 }
 
 impl Device {
@@ -146,9 +190,20 @@ impl Device {
             id,
             pk: public_key,
             count,
+            transports: vec![],
         }
     }
 
+    /// Sets the transports the device can be reached with, sent as hints in
+    /// `allowCredentials` when authenticating
+    ///
+    /// # Arguments
+    /// * `transports` - Transports reported by the client when registering
+    pub fn with_transports(mut self, transports: Vec<Transport>) -> Device {
+        self.transports = transports;
+        self
+    }
+
     pub fn id(&self) -> &[u8] {
         &self.id
     }
@@ -160,6 +215,19 @@ impl Device {
     pub fn count(&self) -> u32 {
         self.count
     }
+
+    /// Returns the transports reported when the device was registered
+    pub fn transports(&self) -> &[Transport] {
+        &self.transports
+    }
+
+    /// Updates the number of times this device has been used
+    ///
+    /// # Arguments
+    /// * `count` - Signature counter returned from the latest authentication
+    pub fn set_count(&mut self, count: u32) {
+        self.count = count;
+    }
 }
 
 #[cfg(test)]
@@ -183,4 +251,70 @@ mod tests {
         let config = Config::new("app.example.com/");
         assert_eq!(config.id(), "app.example.com");
     }
+
+    #[test]
+    fn build_webauthn_config_validated() {
+        let config = Config::builder("HTTPS://App.Example.com:443/")
+            .finish()
+            .unwrap();
+        assert_eq!(config.origin(), "https://app.example.com");
+        assert_eq!(config.id(), "app.example.com");
+
+        let config = Config::builder("https://app.example.com:8443")
+            .id("example.com")
+            .finish()
+            .unwrap();
+        assert_eq!(config.origin(), "https://app.example.com:8443");
+        assert_eq!(config.id(), "example.com");
+
+        let config = Config::builder("http://localhost:8080").finish().unwrap();
+        assert_eq!(config.id(), "localhost");
+    }
+
+    #[test]
+    fn config_set_selects_by_host() {
+        let mut configs = ConfigSet::new();
+        configs
+            .insert(Config::new("https://a.example.com"))
+            .insert(Config::new("https://b.example.com:8443"));
+
+        assert_eq!(configs.get("A.example.com").unwrap().id(), "a.example.com");
+        assert_eq!(
+            configs.get("b.example.com:8443").unwrap().origin(),
+            "https://b.example.com:8443"
+        );
+        assert!(configs.get("c.example.com").is_none());
+        assert!(configs.for_origin("https://b.example.com:8443").is_some());
+        assert!(configs.for_origin("https://b.example.com").is_none());
+    }
+
+    #[test]
+    fn build_webauthn_config_rejects_invalid_origins() {
+        let invalid = |origin: &str| Config::builder(origin).finish().unwrap_err();
+        assert!(matches!(
+            invalid("http:://www.example.com"),
+            ConfigError::InvalidOrigin(_) | ConfigError::MissingDomain
+        ));
+        assert!(matches!(
+            invalid("app.example.com"),
+            ConfigError::InvalidOrigin(_)
+        ));
+        assert!(matches!(
+            invalid("https://app.example.com/login"),
+            ConfigError::NotAnOrigin
+        ));
+        assert!(matches!(
+            invalid("https://127.0.0.1"),
+            ConfigError::MissingDomain
+        ));
+        assert!(matches!(
+            invalid("http://app.example.com"),
+            ConfigError::InsecureScheme(_)
+        ));
+
+        for id in &["other.com", "ample.com", "www.app.example.com", ""] {
+            let result = Config::builder("https://app.example.com").id(*id).finish();
+            assert!(matches!(result, Err(ConfigError::InvalidRpId(_))));
+        }
+    }
 }
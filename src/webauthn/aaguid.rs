@@ -0,0 +1,117 @@
+//! Authenticator names
+//!
+//! Authenticators report an AAGUID (Authenticator Attestation GUID) identifying their
+//! make and model in the authenticator data of a registration.  With the `aaguid-names`
+//! feature enabled, [`authenticator_name`] maps well-known AAGUIDs to a human readable
+//! name (e.g., "iCloud Keychain" or "YubiKey 5 Series with NFC") that can be shown next to
+//! a user's registered passkeys.
+//!
+//! The table is an embedded snapshot of the community maintained passkey AAGUID list and
+//! the FIDO Metadata Service, so newer authenticators will not be recognized.  Names are
+//! self-reported by the authenticator and, unless the attestation was verified, should
+//! only be used for display.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let result = webauthn::register(form, &cfg, challenge, &user)?;
+//! let label = result.authenticator_name().unwrap_or("Passkey");
+//! ```
+
+/// Known AAGUIDs and their authenticator names, sorted by AAGUID
+const NAMES: &[(u128, &str)] = &[
+    (0x08987058_cadc_4b81_b6e1_30de50dcbe96, "Windows Hello"),
+    (
+        0x0bb43545_fd2c_4185_87dd_feb0b2916ace,
+        "Security Key NFC by Yubico - Enterprise Edition",
+    ),
+    (
+        0x149a2021_8ef6_4133_96b8_81f8d5b7f1f5,
+        "Security Key by Yubico with NFC",
+    ),
+    (
+        0x2fc0579f_8113_47ea_b116_bb5a8db9202a,
+        "YubiKey 5 Series with NFC",
+    ),
+    (
+        0x42b4fb4a_2866_43b2_9bf7_6c6669c2e5d3,
+        "Google Titan Security Key v2",
+    ),
+    (0x50726f74_6f6e_5061_7373_50726f746f6e, "Proton Pass"),
+    (0x531126d6_e717_415c_9320_3d9aa6981239, "Dashlane"),
+    (0x53414d53_554e_4700_0000_000000000000, "Samsung Pass"),
+    (0x6028b017_b1d4_4c02_b4b3_afcdafc96bb2, "Windows Hello"),
+    (
+        0x6d44ba9b_f6ec_2e49_b930_0c8fe920cb73,
+        "Security Key by Yubico with NFC",
+    ),
+    (
+        0x73bb0cd4_e502_49b8_9c6f_b59445bf720b,
+        "YubiKey 5 FIPS Series",
+    ),
+    (0x9ddd1817_af5a_4672_a2b9_3e3dd95000a9, "Windows Hello"),
+    (
+        0xa4e9fc6d_4cbe_4758_b8ba_37598bb5bbaa,
+        "Security Key NFC by Yubico",
+    ),
+    (0xadce0002_35bc_c60a_648b_0b25f1f05503, "Chrome on Mac"),
+    (0xb5397666_4885_aa6b_cebf_e52262a439a2, "Chromium Browser"),
+    (
+        0xb92c3f9a_c014_4056_887f_140a2501163b,
+        "Security Key by Yubico",
+    ),
+    (0xbada5566_a7aa_401f_bd96_45619a55120d, "1Password"),
+    (0xc5ef55ff_ad9a_4b9f_b580_adebafe026d0, "YubiKey 5Ci"),
+    (0xcb69481e_8ff7_4039_93ec_0a2729a154a8, "YubiKey 5 Series"),
+    (0xd548826e_79b4_db40_a3d8_11116f7e8349, "Bitwarden"),
+    (0xd8522d9f_575b_4866_88a9_ba99fa02f35b, "YubiKey Bio Series"),
+    (
+        0xdd4ec289_e01d_41c9_bb89_70fa845d4bf2,
+        "iCloud Keychain (Managed)",
+    ),
+    (
+        0xea9b8d66_4d01_1d21_3ce4_b6b48cb575d4,
+        "Google Password Manager",
+    ),
+    (0xee882879_721c_4913_9775_3dfcce97072a, "YubiKey 5 Series"),
+    (
+        0xf8a011f3_8c0a_4d15_8006_17111f9edc7d,
+        "Security Key by Yubico",
+    ),
+    (
+        0xfa2b99dc_9e39_4257_8f92_4a30d23c4118,
+        "YubiKey 5 Series with NFC",
+    ),
+    (0xfbfc3007_154e_4ecc_8c0b_6e020557d7bd, "iCloud Keychain"),
+    (0xfdb141b2_5d84_443e_8a35_4698c205a502, "KeePassXC"),
+];
+
+/// Returns the name of the authenticator model identified by an AAGUID, or `None` if the
+/// AAGUID is unknown or all zeros (i.e., the authenticator chose not to identify itself)
+///
+/// # Arguments
+/// * `aaguid` - AAGUID from the attested credential data
+pub fn authenticator_name(aaguid: &[u8; 16]) -> Option<&'static str> {
+    let aaguid = u128::from_be_bytes(*aaguid);
+    NAMES
+        .binary_search_by_key(&aaguid, |(id, _)| *id)
+        .ok()
+        .map(|idx| NAMES[idx].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        assert!(NAMES.windows(2).all(|w| w[0].0 < w[1].0));
+
+        let icloud = [
+            0xfb, 0xfc, 0x30, 0x07, 0x15, 0x4e, 0x4e, 0xcc, 0x8c, 0x0b, 0x6e, 0x02, 0x05, 0x57,
+            0xd7, 0xbd,
+        ];
+        assert_eq!(authenticator_name(&icloud), Some("iCloud Keychain"));
+        assert_eq!(authenticator_name(&[0; 16]), None);
+    }
+}
@@ -0,0 +1,205 @@
+//! Browser client for WebAssembly frontends
+//!
+//! Passes a [`RegisterRequest`] or [`AuthenticateRequest`] received from the server to
+//! `navigator.credentials.create()` or `navigator.credentials.get()` and converts the
+//! resulting `PublicKeyCredential` into a [`Response`] that can be serialized and posted
+//! back to the server, allowing Rust frontends and backends to share the same types.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::{client, RegisterRequest};
+//!
+//! let req: RegisterRequest = fetch_json("/fido/register").await?;
+//! let form = client::create(&req).await?;
+//! ```
+
+use crate::webauthn::{AuthenticateRequest, Error, RegisterRequest, Response};
+use js_sys::{Array, ArrayBuffer, Object, Reflect, Uint8Array, JSON};
+use serde::Serialize;
+use std::fmt;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    AuthenticatorAssertionResponse, AuthenticatorAttestationResponse, CredentialCreationOptions,
+    CredentialRequestOptions, PublicKeyCredential,
+};
+
+/// Errors that occur while calling the browser's credential api
+#[derive(Debug)]
+pub enum ClientError {
+    /// The browser doesn't expose `navigator.credentials`
+    Unsupported,
+
+    /// The browser rejected the call (e.g., the user cancelled or the timeout elapsed)
+    Browser(String),
+
+    /// The browser returned a credential that wasn't a `PublicKeyCredential`
+    InvalidCredential,
+
+    /// The request or credential couldn't be converted
+    WebAuthn(Error),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::Unsupported => write!(f, "WebAuthn is not supported by this browser"),
+            ClientError::Browser(e) => write!(f, "Browser error: {}", e),
+            ClientError::InvalidCredential => write!(f, "Browser returned an invalid credential"),
+            ClientError::WebAuthn(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<Error> for ClientError {
+    fn from(e: Error) -> ClientError {
+        ClientError::WebAuthn(e)
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(e: serde_json::Error) -> ClientError {
+        ClientError::WebAuthn(Error::JsonError(e))
+    }
+}
+
+impl From<JsValue> for ClientError {
+    fn from(e: JsValue) -> ClientError {
+        let msg = e
+            .dyn_ref::<js_sys::Error>()
+            .map(|e| String::from(e.message()))
+            .or_else(|| e.as_string())
+            .unwrap_or_else(|| format!("{:?}", e));
+
+        ClientError::Browser(msg)
+    }
+}
+
+/// Registers a new credential by calling `navigator.credentials.create()`
+///
+/// # Arguments
+/// * `req` - Register request received from the server
+pub async fn create(req: &RegisterRequest) -> Result<Response, ClientError> {
+    let public_key = to_js(req)?;
+    buffer(&public_key, "challenge")?;
+    buffer(&Reflect::get(&public_key, &"user".into())?, "id")?;
+    descriptors(&public_key, "excludeCredentials")?;
+
+    let options = CredentialCreationOptions::new();
+    Reflect::set(&options, &"publicKey".into(), &public_key)?;
+
+    let promise = credentials()?.create_with_options(&options)?;
+    let credential = credential(JsFuture::from(promise).await?)?;
+    let response = credential
+        .response()
+        .dyn_into::<AuthenticatorAttestationResponse>()
+        .map_err(|_| ClientError::InvalidCredential)?;
+
+    let response = serde_json::json!({
+        "type": "create",
+        "attestationObject": encode(&response.attestation_object(), base64::STANDARD),
+        "clientDataJSON": encode(&response.client_data_json(), base64::URL_SAFE),
+    });
+
+    into_response(&credential, response)
+}
+
+/// Authenticates with an existing credential by calling `navigator.credentials.get()`
+///
+/// # Arguments
+/// * `req` - Authenticate request received from the server
+pub async fn get(req: &AuthenticateRequest) -> Result<Response, ClientError> {
+    let public_key = to_js(req)?;
+    buffer(&public_key, "challenge")?;
+    descriptors(&public_key, "allowCredentials")?;
+
+    let options = CredentialRequestOptions::new();
+    Reflect::set(&options, &"publicKey".into(), &public_key)?;
+
+    let promise = credentials()?.get_with_options(&options)?;
+    let credential = credential(JsFuture::from(promise).await?)?;
+    let response = credential
+        .response()
+        .dyn_into::<AuthenticatorAssertionResponse>()
+        .map_err(|_| ClientError::InvalidCredential)?;
+
+    let response = serde_json::json!({
+        "type": "get",
+        "authenticatorData": encode(&response.authenticator_data(), base64::STANDARD),
+        "signature": encode(&response.signature(), base64::STANDARD),
+        "userHandle": response.user_handle().map(|h| encode(&h, base64::STANDARD)),
+        "clientDataJSON": encode(&response.client_data_json(), base64::STANDARD),
+    });
+
+    into_response(&credential, response)
+}
+
+/// Returns the browser's credential container
+fn credentials() -> Result<web_sys::CredentialsContainer, ClientError> {
+    let window = web_sys::window().ok_or(ClientError::Unsupported)?;
+    Ok(window.navigator().credentials())
+}
+
+/// Converts the result of a `create()` or `get()` call into a `PublicKeyCredential`
+fn credential(value: JsValue) -> Result<PublicKeyCredential, ClientError> {
+    value
+        .dyn_into::<PublicKeyCredential>()
+        .map_err(|_| ClientError::InvalidCredential)
+}
+
+/// Builds the crate's [`Response`] from a credential and its (already encoded) response
+fn into_response(
+    credential: &PublicKeyCredential,
+    response: serde_json::Value,
+) -> Result<Response, ClientError> {
+    let extensions = JSON::stringify(&credential.get_client_extension_results())?;
+    let extensions: serde_json::Value =
+        serde_json::from_str(&String::from(extensions)).unwrap_or_default();
+
+    let form = serde_json::json!({
+        "id": credential.id(),
+        "rawId": encode(&credential.raw_id(), base64::STANDARD),
+        "type": credential.type_(),
+        "response": response,
+        "clientExtensionResults": extensions,
+    });
+
+    Ok(serde_json::from_value(form)?)
+}
+
+/// Converts a request into a plain JavaScript object
+fn to_js<T: Serialize>(req: &T) -> Result<JsValue, ClientError> {
+    Ok(JSON::parse(&serde_json::to_string(req)?)?)
+}
+
+/// Replaces the byte array stored at `key` with a `Uint8Array`, as required by the
+/// credential api for binary fields
+fn buffer(obj: &JsValue, key: &str) -> Result<(), ClientError> {
+    let key = JsValue::from(key);
+    let value = Reflect::get(obj, &key)?;
+    if Array::is_array(&value) {
+        Reflect::set(obj, &key, &Uint8Array::new(&value))?;
+    }
+    Ok(())
+}
+
+/// Converts the ids of every credential descriptor stored at `key` into buffers
+fn descriptors(obj: &JsValue, key: &str) -> Result<(), ClientError> {
+    let list = Reflect::get(obj, &key.into())?;
+    if let Some(list) = list.dyn_ref::<Array>() {
+        for descriptor in list.iter() {
+            if descriptor.is_instance_of::<Object>() {
+                buffer(&descriptor, "id")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Base64-encodes the contents of a buffer returned by the credential api
+fn encode(buffer: &ArrayBuffer, config: base64::Config) -> String {
+    base64::encode_config(Uint8Array::new(buffer).to_vec(), config)
+}
@@ -12,6 +12,7 @@ pub type CoseMap = BTreeMap<i32, Value>;
 
 #[derive(Debug)]
 #[allow(dead_code)]
+#[non_exhaustive]
 pub enum CoseError {
     /// Occurs when we encounted an unknown or unrecognized key field
     UnknownKey(String),
@@ -32,7 +33,14 @@ pub enum CoseError {
     /// Occurs when CBOR parsing fails
     ParseError(serde_cbor::Error),
 }
-impl Error for CoseError {}
+impl Error for CoseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CoseError::ParseError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl fmt::Display for CoseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
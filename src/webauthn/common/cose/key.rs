@@ -3,7 +3,7 @@
 mod algorithm;
 
 pub use self::algorithm::CoseKeyAlgorithm;
-use crate::common::cose::{constants::*, CoseError, CoseMap};
+use crate::webauthn::common::cose::{constants::*, CoseError, CoseMap};
 use serde::Deserialize;
 use serde_cbor::Value;
 use serde_repr::Deserialize_repr;
@@ -187,16 +187,15 @@ impl CoseKeyBuilder {
 
     /// Finish building this CoseKey and generate the resulting structure
     pub fn finish(self) -> Result<CoseKey, CoseError> {
-        if self.kty.is_none() || self.alg.is_none() {
-            Err(CoseError::MissingFields)
-        } else {
-            Ok(CoseKey {
-                kty: self.kty.unwrap(),
+        match (self.kty, self.alg) {
+            (Some(kty), Some(alg)) => Ok(CoseKey {
+                kty,
                 kid: self.kid,
-                alg: self.alg.unwrap(),
+                alg,
                 key_ops: self.key_ops,
                 iv: self.iv,
-            })
+            }),
+            _ => Err(CoseError::MissingFields),
         }
     }
 }
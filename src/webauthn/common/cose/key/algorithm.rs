@@ -3,7 +3,7 @@
 mod es256;
 
 use self::es256::ES256Params;
-use crate::common::cose::{constants::*, CoseError, CoseMap};
+use crate::webauthn::common::cose::{constants::*, CoseError, CoseMap};
 use serde::Deserialize;
 use serde_cbor::Value;
 
@@ -1,6 +1,6 @@
 //! ES256 algorithm details
 
-use crate::common::cose::{constants::*, CoseError, CoseMap};
+use crate::webauthn::common::cose::{constants::*, CoseError, CoseMap};
 use serde::Deserialize;
 use serde_cbor::Value;
 
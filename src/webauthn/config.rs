@@ -1,6 +1,72 @@
 //! file: config.fs
 
-use super::rp::RelyingParty;
+use super::{
+    extensions::{Extension, ExtensionRegistry},
+    policy::{CeremonyPolicy, PolicyChain},
+    revocation::RevocationChecker,
+    rp::RelyingParty,
+    AuthenticationResult, Device, Error, RegistrationResult, Response, WebAuthnUser,
+};
+use std::{collections::HashMap, sync::Arc};
+use url::Url;
+
+/// Errors raised when building a [`Config`] from an invalid origin or RP ID
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// Occurs when the origin is not a valid URL
+    #[error("invalid origin: {0}")]
+    InvalidOrigin(#[from] url::ParseError),
+
+    /// Occurs when the origin has a path, query, fragment or credentials
+    #[error("origin must only contain a scheme, host and port")]
+    NotAnOrigin,
+
+    /// Occurs when the origin's host is an IP address (or missing) instead of a domain
+    #[error("origin must have a domain name")]
+    MissingDomain,
+
+    /// Occurs when the origin is not https, or http on localhost
+    #[error("origin must use https (or http on localhost), not `{0}`")]
+    InsecureScheme(String),
+
+    /// Occurs when the RP ID is neither the origin's domain nor a parent of it
+    #[error("relying party id `{0}` does not match the origin's domain")]
+    InvalidRpId(String),
+}
+
+/// What to do when an authenticator's signature counter fails to increase, which may
+/// indicate a cloned authenticator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterPolicy {
+    /// Accept the assertion without reporting anything
+    Ignore,
+
+    /// Accept the assertion, logging a warning and emitting
+    /// [`AuthEvent::AssertionCounterRegressed`](crate::events::AuthEvent) (default)
+    Warn,
+
+    /// Report the regression like `Warn`, then reject the assertion with
+    /// [`Error::CounterRegressed`](super::Error)
+    Reject,
+}
+
+/// Whether ceremonies run in a cross-origin iframe (e.g., an RP's sign in embedded in a
+/// partner site, or Secure Payment Confirmation on a merchant's checkout page) are
+/// accepted.  The client reports these with `crossOrigin` and, in browsers supporting
+/// it, the origin of the top-level page as `topOrigin`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CrossOriginPolicy {
+    /// Reject every cross-origin ceremony (default)
+    Deny,
+
+    /// Accept cross-origin ceremonies, embedded in any page
+    AllowAny,
+
+    /// Accept cross-origin ceremonies embedded in one of these top-level origins (e.g.,
+    /// `https://shop.example.net`).  Clients that do not report `topOrigin` are rejected
+    AllowTopOrigins(Vec<String>),
+}
 
 /// High Level configuration object that can be utilized to set
 /// information about the server ("Relying Party")
@@ -11,9 +77,29 @@ pub struct Config {
 
     /// A unique identifier for the Relying Party entity, which sets the RP ID
     rp_id: String,
+
+    /// Extensions recognized by the Relying Party
+    extensions: ExtensionRegistry,
+
+    /// What to do when a signature counter fails to increase
+    counter_policy: CounterPolicy,
+
+    /// Custom policies consulted while validating ceremonies
+    policies: PolicyChain,
+
+    /// Checks attestation certificates were not revoked
+    revocation: Option<Arc<RevocationChecker>>,
+
+    /// Whether ceremonies run in cross-origin iframes are accepted
+    cross_origin_policy: CrossOriginPolicy,
 }
 
 impl Config {
+    /// Creates a new config, taking the RP ID from the origin as is.  Prefer
+    /// [`Config::builder`], which validates the origin
+    ///
+    /// # Arguments
+    /// * `origin` - Origin of the server (e.g., `https://app.example.com`)
     pub fn new<S: Into<String>>(origin: S) -> Self {
         let origin = origin.into();
         let id = origin.clone();
@@ -23,6 +109,24 @@ impl Config {
         Config {
             rp_origin: origin,
             rp_id: domain.to_owned(),
+            extensions: ExtensionRegistry::new(),
+            counter_policy: CounterPolicy::Warn,
+            policies: PolicyChain::new(),
+            revocation: None,
+            cross_origin_policy: CrossOriginPolicy::Deny,
+        }
+    }
+
+    /// Starts building a config whose origin is parsed and validated
+    ///
+    /// # Arguments
+    /// * `origin` - Origin of the server (e.g., `https://app.example.com`)
+    pub fn builder<S: Into<String>>(origin: S) -> ConfigBuilder {
+        ConfigBuilder {
+            origin: origin.into(),
+            id: None,
+            counter_policy: CounterPolicy::Warn,
+            cross_origin_policy: CrossOriginPolicy::Deny,
         }
     }
 
@@ -45,11 +149,279 @@ impl Config {
         &self.rp_id
     }
 
+    /// Registers an extension with this config. Outputs returned for a registered
+    /// extension must decode into the extension's output types or the response
+    /// will be rejected
+    pub fn register_extension<E: Extension>(&mut self) -> &mut Self {
+        self.extensions.register::<E>();
+        self
+    }
+
+    /// Returns the extensions registered with this config
+    pub fn extensions(&self) -> &ExtensionRegistry {
+        &self.extensions
+    }
+
+    /// Sets what to do when an authenticator's signature counter fails to increase
+    /// (default: `CounterPolicy::Warn`)
+    ///
+    /// # Arguments
+    /// * `policy` - How to handle a counter regression
+    pub fn set_counter_policy(&mut self, policy: CounterPolicy) -> &mut Self {
+        self.counter_policy = policy;
+        self
+    }
+
+    /// Returns what to do when an authenticator's signature counter fails to increase
+    pub fn counter_policy(&self) -> CounterPolicy {
+        self.counter_policy
+    }
+
+    /// Sets whether ceremonies run in cross-origin iframes are accepted, and from which
+    /// top-level origins (default: `CrossOriginPolicy::Deny`)
+    ///
+    /// # Arguments
+    /// * `policy` - Which cross-origin ceremonies to accept
+    pub fn set_cross_origin_policy(&mut self, policy: CrossOriginPolicy) -> &mut Self {
+        self.cross_origin_policy = policy;
+        self
+    }
+
+    /// Returns whether ceremonies run in cross-origin iframes are accepted
+    pub fn cross_origin_policy(&self) -> &CrossOriginPolicy {
+        &self.cross_origin_policy
+    }
+
+    /// Adds a custom policy consulted while validating ceremonies, after any policy
+    /// added before it
+    ///
+    /// # Arguments
+    /// * `policy` - Policy to consult
+    pub fn add_policy<P: CeremonyPolicy + 'static>(&mut self, policy: P) -> &mut Self {
+        self.policies.push(policy);
+        self
+    }
+
+    /// Returns the custom policies consulted while validating ceremonies
+    pub fn policies(&self) -> &PolicyChain {
+        &self.policies
+    }
+
+    /// Checks the attestation certificates of registrations against revocation
+    /// information (default: no checking).  The checker is shared so the application
+    /// can keep stapling OCSP responses and adding CRLs to it
+    ///
+    /// # Arguments
+    /// * `checker` - Revocation checker to consult after verifying an attestation
+    pub fn set_revocation_checker(&mut self, checker: Arc<RevocationChecker>) -> &mut Self {
+        self.revocation = Some(checker);
+        self
+    }
+
+    /// Returns the revocation checker consulted during registration, if any
+    pub fn revocation_checker(&self) -> Option<&RevocationChecker> {
+        self.revocation.as_deref()
+    }
+
     pub fn as_relying_party(&self) -> RelyingParty {
         RelyingParty::builder(self).finish()
     }
 }
 
+/// Builds a [`Config`], parsing the origin so that malformed origins are rejected
+/// up front instead of failing every ceremony
+#[derive(Clone, Debug)]
+pub struct ConfigBuilder {
+    /// Origin as given by the caller
+    origin: String,
+
+    /// RP ID overriding the origin's domain
+    id: Option<String>,
+
+    /// What to do when a signature counter fails to increase
+    counter_policy: CounterPolicy,
+
+    /// Whether ceremonies run in cross-origin iframes are accepted
+    cross_origin_policy: CrossOriginPolicy,
+}
+
+impl ConfigBuilder {
+    /// Overrides the RP ID, which defaults to the origin's domain.  The RP ID must
+    /// be the domain or one of its parents (e.g., `example.com` for
+    /// `https://app.example.com`)
+    ///
+    /// # Arguments
+    /// * `id` - The Relying Party Id to use
+    pub fn id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets what to do when an authenticator's signature counter fails to increase
+    ///
+    /// # Arguments
+    /// * `policy` - How to handle a counter regression
+    pub fn counter_policy(mut self, policy: CounterPolicy) -> Self {
+        self.counter_policy = policy;
+        self
+    }
+
+    /// Sets whether ceremonies run in cross-origin iframes are accepted
+    ///
+    /// # Arguments
+    /// * `policy` - Which cross-origin ceremonies to accept
+    pub fn cross_origin_policy(mut self, policy: CrossOriginPolicy) -> Self {
+        self.cross_origin_policy = policy;
+        self
+    }
+
+    /// Validates the origin and RP ID and returns the config.  The origin is
+    /// normalized: the scheme and domain are lowercased, the default port and any
+    /// trailing slash are dropped
+    pub fn finish(self) -> Result<Config, ConfigError> {
+        let url = Url::parse(&self.origin)?;
+        if url.path() != "/"
+            || url.query().is_some()
+            || url.fragment().is_some()
+            || !url.username().is_empty()
+            || url.password().is_some()
+        {
+            return Err(ConfigError::NotAnOrigin);
+        }
+
+        let domain = url.domain().ok_or(ConfigError::MissingDomain)?;
+        match url.scheme() {
+            "https" => (),
+            "http" if domain == "localhost" => (),
+            scheme => return Err(ConfigError::InsecureScheme(scheme.to_owned())),
+        }
+
+        let id = match self.id {
+            Some(id) => {
+                let id = id.to_ascii_lowercase();
+                let parent = domain
+                    .strip_suffix(id.as_str())
+                    .is_some_and(|sub| sub.is_empty() || sub.ends_with('.'));
+                if id.is_empty() || !parent {
+                    return Err(ConfigError::InvalidRpId(id));
+                }
+                id
+            }
+            None => domain.to_owned(),
+        };
+
+        Ok(Config {
+            rp_origin: url.origin().ascii_serialization(),
+            rp_id: id,
+            extensions: ExtensionRegistry::new(),
+            counter_policy: self.counter_policy,
+            policies: PolicyChain::new(),
+            revocation: None,
+            cross_origin_policy: self.cross_origin_policy,
+        })
+    }
+}
+
+/// Configurations for several Relying Parties served by one application (e.g., a SaaS
+/// product hosting many customer domains), keyed by the hostname of their origin
+///
+/// Requests are built with the configuration for the request's `Host` header
+/// ([`ConfigSet::get`]); responses are validated with the configuration for the
+/// origin in their client data ([`ConfigSet::register`], [`ConfigSet::authenticate`]).
+#[derive(Clone, Debug, Default)]
+pub struct ConfigSet {
+    configs: HashMap<String, Config>,
+}
+
+impl ConfigSet {
+    /// Creates an empty set
+    pub fn new() -> ConfigSet {
+        Self::default()
+    }
+
+    /// Adds a configuration, replacing any configuration for the same hostname
+    ///
+    /// # Arguments
+    /// * `config` - Configuration of one Relying Party
+    pub fn insert(&mut self, config: Config) -> &mut Self {
+        self.configs.insert(hostname(config.origin()), config);
+        self
+    }
+
+    /// Returns the configuration for a host, ignoring any port
+    ///
+    /// # Arguments
+    /// * `host` - Host the request was made to (e.g., the `Host` header)
+    pub fn get(&self, host: &str) -> Option<&Config> {
+        self.configs.get(&hostname(host))
+    }
+
+    /// Returns the configuration whose origin matches exactly
+    ///
+    /// # Arguments
+    /// * `origin` - Origin of the client (e.g., `https://app.example.com`)
+    pub fn for_origin(&self, origin: &str) -> Option<&Config> {
+        self.get(origin).filter(|config| config.origin() == origin)
+    }
+
+    /// Validates a register response with the configuration for its origin, see
+    /// [`register`](super::register)
+    ///
+    /// # Arguments
+    /// * `form` - Deserialized JSON received from the client
+    /// * `challenge` - The base64url encoded challenge issued with the request
+    pub fn register<S: Into<String>>(
+        &self,
+        form: Response,
+        challenge: S,
+    ) -> Result<RegistrationResult, Error> {
+        let config = self.for_response(&form)?;
+        super::register(form, config, challenge)
+    }
+
+    /// Validates an authenticate response with the configuration for its origin, see
+    /// [`authenticate`](super::authenticate)
+    ///
+    /// # Arguments
+    /// * `form` - Deserialized JSON received from the client
+    /// * `challenge` - The base64url encoded challenge issued with the request
+    /// * `user` - User attempting to login
+    /// * `devices` - Devices the user may authenticate with
+    pub fn authenticate<S: Into<String>, U: WebAuthnUser>(
+        &self,
+        form: Response,
+        challenge: S,
+        user: &U,
+        devices: &[Device],
+    ) -> Result<AuthenticationResult, Error> {
+        let config = self.for_response(&form)?;
+        super::authenticate(form, config, challenge, user, devices)
+    }
+
+    /// Returns the configuration for the origin claimed by a response
+    fn for_response(&self, form: &Response) -> Result<&Config, Error> {
+        let origin = form.origin()?;
+        self.for_origin(&origin).ok_or(Error::UnknownOrigin(origin))
+    }
+}
+
+/// Returns the lowercased hostname of an origin or host, without scheme, port or path
+///
+/// # Arguments
+/// * `authority` - Origin (`https://app.example.com:8443`) or host (`app.example.com:8443`)
+fn hostname(authority: &str) -> String {
+    let authority = authority
+        .split_once("://")
+        .map_or(authority, |(_, rest)| rest);
+    let authority = authority.split('/').next().unwrap_or_default();
+    let host = match authority.strip_prefix('[') {
+        // IPv6 literal
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    host.to_ascii_lowercase()
+}
+
 impl Into<RelyingParty> for &Config {
     fn into(self) -> RelyingParty {
         RelyingParty::builder(self).finish()
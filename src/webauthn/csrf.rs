@@ -0,0 +1,104 @@
+//! CSRF tokens bound to a ceremony
+//!
+//! The second (POST) leg of a ceremony is authenticated only by the session cookie, making
+//! it a target for cross-site request forgery.  A [`CsrfToken`] derives a token from the
+//! challenge issued in the first leg and the client's session id (HMAC-SHA256 over both),
+//! which is returned alongside the request and must be echoed back (e.g., in the
+//! [`CSRF_HEADER`] header) with the response.  No additional server-side state is needed.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let csrf = CsrfToken::new(b"a long, random server-side secret");
+//!
+//! // first leg
+//! let req = auth.start_registration(&session, &user).await?;
+//! let token = csrf.derive(req.challenge(), &session);
+//!
+//! // second leg
+//! csrf.verify(&token, &challenge, &session)?;
+//! ```
+
+use crate::webauthn::Error;
+use ring::hmac;
+
+/// Header the token is expected in when echoed back by the client
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// Derives and verifies CSRF tokens
+pub struct CsrfToken {
+    /// Key used to derive tokens
+    key: hmac::Key,
+}
+
+impl CsrfToken {
+    /// Creates a new token helper
+    ///
+    /// # Arguments
+    /// * `secret` - Server-side secret used to derive tokens.  Should be at least 32 random bytes
+    pub fn new(secret: &[u8]) -> CsrfToken {
+        CsrfToken {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        }
+    }
+
+    /// Derives the token for a ceremony
+    ///
+    /// # Arguments
+    /// * `challenge` - Base64url-encoded challenge issued to the client
+    /// * `session` - Id of the client's session
+    pub fn derive(&self, challenge: &str, session: &str) -> String {
+        let tag = hmac::sign(&self.key, &message(challenge, session));
+        base64::encode_config(tag.as_ref(), base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Verifies a token echoed back by the client
+    ///
+    /// # Arguments
+    /// * `token` - Token sent by the client
+    /// * `challenge` - Base64url-encoded challenge issued to the client
+    /// * `session` - Id of the client's session
+    pub fn verify(&self, token: &str, challenge: &str, session: &str) -> Result<(), Error> {
+        let tag = base64::decode_config(token, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Error::InvalidCsrfToken)?;
+        hmac::verify(&self.key, &message(challenge, session), &tag)
+            .map_err(|_| Error::InvalidCsrfToken)
+    }
+}
+
+/// Builds the (length-prefixed, so the fields cannot be shifted) message to authenticate
+fn message(challenge: &str, session: &str) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(16 + challenge.len() + session.len());
+    for field in &[challenge, session] {
+        msg.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        msg.extend_from_slice(field.as_bytes());
+    }
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn derived_token_verifies() {
+        let csrf = CsrfToken::new(SECRET);
+        let token = csrf.derive("challenge", "session");
+        csrf.verify(&token, "challenge", "session").unwrap();
+    }
+
+    #[test]
+    fn token_is_bound_to_challenge_and_session() {
+        let csrf = CsrfToken::new(SECRET);
+        let token = csrf.derive("challenge", "session");
+
+        assert!(csrf.verify(&token, "other", "session").is_err());
+        assert!(csrf.verify(&token, "challenge", "other").is_err());
+        assert!(csrf.verify(&token, "challengesession", "").is_err());
+        assert!(CsrfToken::new(b"another secret")
+            .verify(&token, "challenge", "session")
+            .is_err());
+    }
+}
@@ -0,0 +1,715 @@
+//! Native client for security keys speaking the Client to Authenticator Protocol (CTAP2)
+//!
+//! Lets native Rust applications register and authenticate with a security key directly,
+//! without a browser.  [`Authenticator`] passes a [`RegisterRequest`] or
+//! [`AuthenticateRequest`] received from the server to the key with
+//! `authenticatorMakeCredential` or `authenticatorGetAssertion` and converts the key's
+//! answer into the same [`Response`] the server validates, so both sides share the same
+//! types.
+//!
+//! Commands are CBOR encoded ([CTAP 2.1](https://fidoalliance.org/specs/fido-v2.1-ps-20210615/fido-client-to-authenticator-protocol-v2.1-ps-20210615.html))
+//! and carried by a [`Transport`].  The [`hid`] module implements the USB HID transport
+//! (CTAPHID) over any [`hid::HidDevice`].  The `ctap-nfc` feature adds the NFC transport
+//! (ISO 7816 APDUs, e.g., over PC/SC) and the `ctap-ble` feature the Bluetooth Low
+//! Energy transport, which carry the same commands.  Authenticators protected by a PIN
+//! are unlocked with the PIN/UV auth protocols of the [`pin`] module, and
+//! [`soft::SoftAuthenticator`] stands in for a security key in tests.  The command
+//! structures themselves are public in the [`messages`] module.  Discoverable credentials
+//! stored on an authenticator are listed and deleted with the [`credman`] module, and
+//! fingerprints are enrolled with the [`bio`] module.  The [`largeblob`] module reads and
+//! writes the per-credential data kept in an authenticator's large-blob array, and the
+//! [`config`] module configures authenticators for enterprise fleets.  Security keys
+//! that only speak U2F are registered and used through the [`u2f`] fallback.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::{ctap::{hid::Hid, Authenticator}, RegisterRequest};
+//!
+//! let req: RegisterRequest = fetch_json("/fido/register")?;
+//! let mut key = Authenticator::new(Hid::new(device)?);
+//! let form = key.make_credential(&req, "https://app.example.com")?;
+//! ```
+
+pub mod bio;
+#[cfg(feature = "ctap-ble")]
+pub mod ble;
+pub mod config;
+pub mod credman;
+mod deflate;
+pub mod hid;
+pub mod largeblob;
+pub mod messages;
+#[cfg(feature = "ctap-nfc")]
+pub mod nfc;
+pub mod pin;
+pub mod soft;
+pub mod u2f;
+
+use self::{
+    messages::{
+        CredentialDescriptor, CredentialParameters, GetAssertionRequest, GetAssertionResponse,
+        MakeCredentialRequest, MakeCredentialResponse, RelyingPartyEntity, UserEntity,
+    },
+    pin::PinUvAuthToken,
+};
+use crate::webauthn::{
+    request::UserVerification, AuthenticateRequest, Error, RegisterRequest, Response, WebAuthnType,
+};
+use ring::digest::{digest, SHA256};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_cbor::Value;
+use std::{collections::BTreeMap, io};
+use thiserror::Error;
+
+/// Command byte of `authenticatorMakeCredential`
+const MAKE_CREDENTIAL: u8 = 0x01;
+
+/// Command byte of `authenticatorGetAssertion`
+const GET_ASSERTION: u8 = 0x02;
+
+/// Command byte of `authenticatorGetInfo`
+const GET_INFO: u8 = 0x04;
+
+/// Command byte of `authenticatorReset`
+const RESET: u8 = 0x07;
+
+/// Command byte of `authenticatorSelection`
+const SELECTION: u8 = 0x0b;
+
+/// Status byte of a successful command
+const STATUS_OK: u8 = 0x00;
+
+/// Flag set in the authenticator data when it contains attested credential data
+const FLAG_ATTESTED: u8 = 0x40;
+
+/// COSE identifier of ES256
+const ES256: i64 = -7;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum CtapError {
+    #[error("transport failed: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("authenticator returned status {0:#04x}")]
+    Status(u8),
+
+    #[error("authenticator returned status word {0:#06x}")]
+    Apdu(u16),
+
+    #[error("malformed response from authenticator: {0}")]
+    Malformed(&'static str),
+
+    #[error("{0}")]
+    Cbor(#[from] serde_cbor::Error),
+
+    #[error("{0}")]
+    WebAuthn(#[from] Error),
+}
+
+impl From<serde_json::Error> for CtapError {
+    fn from(e: serde_json::Error) -> CtapError {
+        CtapError::WebAuthn(Error::JsonError(e))
+    }
+}
+
+/// A connection to an authenticator that carries CTAP2 commands
+pub trait Transport {
+    /// Sends a command (its command byte followed by its CBOR encoded parameters) and
+    /// returns the authenticator's answer (a status byte followed by the CBOR encoded
+    /// response)
+    ///
+    /// # Arguments
+    /// * `request` - Encoded command
+    fn cbor(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError>;
+
+    /// Sends a U2F message (a command APDU with zero class and P2 bytes) and returns the
+    /// response data, failing with [`CtapError::Apdu`] unless the status word reports
+    /// success.  Transports that cannot carry U2F messages fail
+    ///
+    /// # Arguments
+    /// * `ins` - Instruction byte
+    /// * `p1` - First parameter
+    /// * `data` - Command data
+    fn msg(&mut self, ins: u8, p1: u8, data: &[u8]) -> Result<Vec<u8>, CtapError> {
+        let _ = (ins, p1, data);
+        Err(CtapError::Malformed(
+            "transport does not carry U2F messages",
+        ))
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn cbor(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError> {
+        (**self).cbor(request)
+    }
+
+    fn msg(&mut self, ins: u8, p1: u8, data: &[u8]) -> Result<Vec<u8>, CtapError> {
+        (**self).msg(ins, p1, data)
+    }
+}
+
+/// Information an authenticator reports about itself, from `authenticatorGetInfo`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Info {
+    /// Protocol versions supported (e.g., `FIDO_2_0`, `FIDO_2_1`, `U2F_V2`)
+    pub versions: Vec<String>,
+
+    /// Extensions supported
+    pub extensions: Vec<String>,
+
+    /// Identifier of the authenticator's model
+    pub aaguid: Vec<u8>,
+
+    /// Options supported, and whether they are enabled (e.g., `rk`, `uv`, `clientPin`)
+    pub options: BTreeMap<String, bool>,
+
+    /// Largest message the authenticator accepts, in bytes
+    pub max_msg_size: Option<u64>,
+
+    /// PIN/UV auth protocols supported, most preferred first
+    pub pin_uv_auth_protocols: Vec<u64>,
+}
+
+/// A CTAP2 authenticator reached through a transport
+pub struct Authenticator<T> {
+    transport: T,
+    info: Option<Info>,
+    pin_token: Option<PinUvAuthToken>,
+}
+
+impl<T: Transport> Authenticator<T> {
+    /// Creates a client for the authenticator at the other end of a transport
+    ///
+    /// # Arguments
+    /// * `transport` - Connection to the authenticator
+    pub fn new(transport: T) -> Authenticator<T> {
+        Authenticator {
+            transport,
+            info: None,
+            pin_token: None,
+        }
+    }
+
+    /// Returns the underlying transport
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Asks the authenticator for its capabilities
+    pub fn get_info(&mut self) -> Result<Info, CtapError> {
+        let info = self.command(GET_INFO, None)?;
+        let strings = |key| -> Vec<String> {
+            array(&info, key)
+                .iter()
+                .filter_map(|v| match v {
+                    Value::Text(s) => Some(s.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        Ok(Info {
+            versions: strings(0x01),
+            extensions: strings(0x02),
+            aaguid: bytes(&info, 0x03).unwrap_or_default(),
+            options: match info.get(&Value::Integer(0x04)) {
+                Some(Value::Map(options)) => options
+                    .iter()
+                    .filter_map(|(k, v)| match (k, v) {
+                        (Value::Text(k), Value::Bool(v)) => Some((k.clone(), *v)),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => BTreeMap::new(),
+            },
+            max_msg_size: integer(&info, 0x05),
+            pin_uv_auth_protocols: array(&info, 0x06)
+                .iter()
+                .filter_map(|v| match v {
+                    Value::Integer(i) => Some(*i as u64),
+                    _ => None,
+                })
+                .collect(),
+        })
+    }
+
+    /// Waits for the user to touch the authenticator, so they can pick it among several
+    /// connected ones (CTAP 2.1)
+    pub fn selection(&mut self) -> Result<(), CtapError> {
+        self.command(SELECTION, None)?;
+        Ok(())
+    }
+
+    /// Restores the authenticator to its factory state, deleting every credential and
+    /// its PIN.  Authenticators only accept a reset shortly after being powered up, once
+    /// the user confirms it by touching them
+    pub fn reset(&mut self) -> Result<(), CtapError> {
+        self.command(RESET, None)?;
+        self.info = None;
+        self.pin_token = None;
+        Ok(())
+    }
+
+    /// Registers a new credential on the authenticator, equivalent to calling
+    /// `navigator.credentials.create()`
+    ///
+    /// # Arguments
+    /// * `req` - Register request received from the server
+    /// * `origin` - Origin of the relying party, as expected by the server's `Config`
+    pub fn make_credential(
+        &mut self,
+        req: &RegisterRequest,
+        origin: &str,
+    ) -> Result<Response, CtapError> {
+        let client_data = client_data(WebAuthnType::Create, &req.challenge(), origin)?;
+        let client_data_hash = digest(&SHA256, &client_data).as_ref().to_vec();
+        let rp = req.relying_party();
+        let user = req.user();
+
+        let mut params = MakeCredentialRequest {
+            client_data_hash: client_data_hash.clone(),
+            rp: RelyingPartyEntity {
+                id: rp
+                    .id
+                    .clone()
+                    .ok_or(CtapError::Malformed("request has no relying party id"))?,
+                name: Some(rp.name.clone()),
+            },
+            user: UserEntity {
+                id: user.id.clone(),
+                name: Some(user.name.clone()),
+                display_name: Some(user.display_name.clone()),
+            },
+            pub_key_cred_params: req
+                .pub_key_cred_params()
+                .iter()
+                .map(|p| CredentialParameters::new(p.alg as i64))
+                .collect(),
+            ..Default::default()
+        };
+        if req.auth_criteria().require_resident_key {
+            params.options.rk = Some(true);
+        }
+        if let Some(ref token) = self.pin_token {
+            params.pin_uv_auth_param = Some(token.authenticate(&client_data_hash));
+            params.pin_uv_auth_protocol = Some(token.protocol().number().into());
+        }
+
+        let credential: MakeCredentialResponse = if self.ctap1_only()? {
+            self.u2f_register(&params)?
+        } else {
+            self.call(MAKE_CREDENTIAL, &params)?
+        };
+        let id = credential_id(&credential.auth_data)
+            .ok_or(CtapError::Malformed("missing attested credential data"))?
+            .to_vec();
+
+        let response = serde_json::json!({
+            "type": "create",
+            "attestationObject": base64::encode_config(
+                credential.attestation_object()?,
+                base64::STANDARD,
+            ),
+            "clientDataJSON": base64::encode_config(&client_data, base64::URL_SAFE),
+        });
+
+        into_response(&id, response)
+    }
+
+    /// Authenticates with a credential stored on the authenticator, equivalent to calling
+    /// `navigator.credentials.get()`
+    ///
+    /// # Arguments
+    /// * `req` - Authenticate request received from the server
+    /// * `origin` - Origin of the relying party, as expected by the server's `Config`
+    pub fn get_assertion(
+        &mut self,
+        req: &AuthenticateRequest,
+        origin: &str,
+    ) -> Result<Response, CtapError> {
+        let client_data = client_data(WebAuthnType::Get, &req.challenge(), origin)?;
+        let rp_id = req
+            .rp_id()
+            .ok_or(CtapError::Malformed("request has no relying party id"))?;
+
+        let client_data_hash = digest(&SHA256, &client_data).as_ref().to_vec();
+
+        let mut params = GetAssertionRequest {
+            allow_list: req
+                .allow_credentials()
+                .iter()
+                .map(|c| CredentialDescriptor::new(c.id().to_vec()))
+                .collect(),
+            ..GetAssertionRequest::new(rp_id, client_data_hash.clone())
+        };
+        // a PIN token verifies the user in place of built-in user verification
+        if let Some(ref token) = self.pin_token {
+            params.pin_uv_auth_param = Some(token.authenticate(&client_data_hash));
+            params.pin_uv_auth_protocol = Some(token.protocol().number().into());
+        } else if let UserVerification::Required = req.user_verification() {
+            params.options.uv = Some(true);
+        }
+
+        let assertion: GetAssertionResponse = if self.ctap1_only()? {
+            self.u2f_authenticate(&params)?
+        } else {
+            self.call(GET_ASSERTION, &params)?
+        };
+
+        // the credential may be omitted when the allow list named exactly one
+        let id = match assertion.credential {
+            Some(credential) => credential.id,
+            None => match req.allow_credentials() {
+                [only] => only.id().to_vec(),
+                _ => return Err(CtapError::Malformed("missing credential")),
+            },
+        };
+        let user_handle = assertion
+            .user
+            .map(|user| base64::encode_config(user.id, base64::STANDARD));
+
+        let response = serde_json::json!({
+            "type": "get",
+            "authenticatorData": base64::encode_config(&assertion.auth_data, base64::STANDARD),
+            "signature": base64::encode_config(&assertion.signature, base64::STANDARD),
+            "userHandle": user_handle,
+            "clientDataJSON": base64::encode_config(&client_data, base64::STANDARD),
+        });
+
+        into_response(&id, response)
+    }
+
+    /// Returns the authenticator's capabilities, asking for them only once.  U2F-only
+    /// authenticators, which reject `authenticatorGetInfo`, report `U2F_V2` alone
+    fn info(&mut self) -> Result<&Info, CtapError> {
+        if self.info.is_none() {
+            let info = match self.get_info() {
+                // CTAP1_ERR_INVALID_COMMAND, or an unknown instruction or class over NFC
+                Err(CtapError::Status(0x01))
+                | Err(CtapError::Apdu(0x6d00))
+                | Err(CtapError::Apdu(0x6e00)) => Info {
+                    versions: vec!["U2F_V2".to_owned()],
+                    ..Default::default()
+                },
+                info => info?,
+            };
+            self.info = Some(info);
+        }
+        Ok(self.info.as_ref().unwrap())
+    }
+
+    /// Returns true if the authenticator only speaks U2F
+    fn ctap1_only(&mut self) -> Result<bool, CtapError> {
+        let versions = &self.info()?.versions;
+        Ok(versions.iter().any(|v| v == "U2F_V2")
+            && !versions.iter().any(|v| v.starts_with("FIDO_2")))
+    }
+
+    /// Sends a command and decodes the (map) response, failing if the authenticator
+    /// returned an error status
+    ///
+    /// # Arguments
+    /// * `command` - Command byte
+    /// * `params` - Parameters of the command, if any
+    fn command(
+        &mut self,
+        command: u8,
+        params: Option<BTreeMap<Value, Value>>,
+    ) -> Result<BTreeMap<Value, Value>, CtapError> {
+        let mut request = vec![command];
+        if let Some(params) = params {
+            request.extend(serde_cbor::to_vec(&Value::Map(params))?);
+        }
+
+        match self.transact(&request)?.as_slice() {
+            [] => Ok(BTreeMap::new()),
+            body => match serde_cbor::from_slice(body)? {
+                Value::Map(map) => Ok(map),
+                _ => Err(CtapError::Malformed("response is not a map")),
+            },
+        }
+    }
+
+    /// Sends a command with typed parameters and decodes its typed response
+    ///
+    /// # Arguments
+    /// * `command` - Command byte
+    /// * `params` - Parameters of the command
+    fn call<P: Serialize, R: DeserializeOwned>(
+        &mut self,
+        command: u8,
+        params: &P,
+    ) -> Result<R, CtapError> {
+        let request = [vec![command], serde_cbor::to_vec(params)?].concat();
+        Ok(serde_cbor::from_slice(&self.transact(&request)?)?)
+    }
+
+    /// Sends an encoded command and returns the body of the response, failing if the
+    /// authenticator returned an error status
+    ///
+    /// # Arguments
+    /// * `request` - Command byte followed by the encoded parameters
+    fn transact(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError> {
+        let response = self.transport.cbor(request)?;
+        match response.split_first() {
+            Some((&STATUS_OK, body)) => Ok(body.to_vec()),
+            Some((&status, _)) => Err(CtapError::Status(status)),
+            None => Err(CtapError::Malformed("empty response")),
+        }
+    }
+}
+
+/// Serializes the client data the authenticator signs over
+///
+/// # Arguments
+/// * `ty` - Ceremony being performed
+/// * `challenge` - Base64url encoded challenge of the request
+/// * `origin` - Origin of the relying party
+fn client_data(ty: WebAuthnType, challenge: &str, origin: &str) -> Result<Vec<u8>, CtapError> {
+    Ok(serde_json::to_vec(&serde_json::json!({
+        "type": ty.as_str(),
+        "challenge": challenge,
+        "origin": origin,
+        "crossOrigin": false,
+    }))?)
+}
+
+/// Builds the crate's [`Response`] from a credential id and its (already encoded) response
+fn into_response(id: &[u8], response: serde_json::Value) -> Result<Response, CtapError> {
+    let form = serde_json::json!({
+        "id": base64::encode_config(id, base64::URL_SAFE_NO_PAD),
+        "rawId": base64::encode_config(id, base64::STANDARD),
+        "type": "public-key",
+        "response": response,
+    });
+
+    Ok(serde_json::from_value(form)?)
+}
+
+/// Returns the credential id of the attested credential data in authenticator data
+///
+/// # Arguments
+/// * `auth_data` - Authenticator data returned by `authenticatorMakeCredential`
+fn credential_id(auth_data: &[u8]) -> Option<&[u8]> {
+    // rpIdHash (32) | flags (1) | signCount (4) | aaguid (16) | length (2) | id
+    if auth_data.get(32)? & FLAG_ATTESTED == 0 {
+        return None;
+    }
+    let len = u16::from_be_bytes([*auth_data.get(53)?, *auth_data.get(54)?]) as usize;
+    auth_data.get(55..55 + len)
+}
+
+/// Encodes an uncompressed P-256 public key as an ES256 COSE key
+fn cose_key(public_key: &[u8]) -> Value {
+    let mut key = BTreeMap::new();
+    key.insert(Value::Integer(1), Value::Integer(2));
+    key.insert(Value::Integer(3), Value::Integer(ES256.into()));
+    key.insert(Value::Integer(-1), Value::Integer(1));
+    key.insert(Value::Integer(-2), Value::Bytes(public_key[1..33].to_vec()));
+    key.insert(
+        Value::Integer(-3),
+        Value::Bytes(public_key[33..65].to_vec()),
+    );
+    Value::Map(key)
+}
+
+/// Builds a CBOR map with text keys, skipping absent values
+fn map(entries: Vec<(&str, Option<Value>)>) -> Value {
+    Value::Map(
+        entries
+            .into_iter()
+            .filter_map(|(k, v)| Some((Value::Text(k.to_owned()), v?)))
+            .collect(),
+    )
+}
+
+/// Returns a byte string member of a response
+fn bytes(map: &BTreeMap<Value, Value>, key: i128) -> Option<Vec<u8>> {
+    match map.get(&Value::Integer(key)) {
+        Some(Value::Bytes(b)) => Some(b.clone()),
+        _ => None,
+    }
+}
+
+/// Returns an unsigned integer member of a response
+fn integer(map: &BTreeMap<Value, Value>, key: i128) -> Option<u64> {
+    match map.get(&Value::Integer(key)) {
+        Some(Value::Integer(i)) if *i >= 0 => Some(*i as u64),
+        _ => None,
+    }
+}
+
+/// Returns an array member of a response, or an empty slice if it is absent
+fn array(map: &BTreeMap<Value, Value>, key: i128) -> &[Value] {
+    match map.get(&Value::Integer(key)) {
+        Some(Value::Array(a)) => a,
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::{Config, Device};
+    use std::collections::VecDeque;
+
+    /// Answers every command with a canned response, remembering the last request
+    struct Canned {
+        request: Vec<u8>,
+        response: Vec<u8>,
+    }
+
+    impl Transport for Canned {
+        fn cbor(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError> {
+            self.request = request.to_vec();
+            Ok(self.response.clone())
+        }
+    }
+
+    /// Answers commands from a queue, remembering the requests
+    #[derive(Default)]
+    pub(crate) struct Script {
+        pub(crate) requests: Vec<Vec<u8>>,
+        pub(crate) answers: VecDeque<Vec<u8>>,
+    }
+
+    impl Transport for Script {
+        fn cbor(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError> {
+            self.requests.push(request.to_vec());
+            Ok(self.answers.pop_front().unwrap_or_else(|| vec![0x01]))
+        }
+    }
+
+    /// Encodes a successful response with integer keyed members
+    pub(crate) fn answer(members: Vec<(i128, Value)>) -> Vec<u8> {
+        let map: BTreeMap<Value, Value> = members
+            .into_iter()
+            .map(|(k, v)| (Value::Integer(k), v))
+            .collect();
+        [vec![STATUS_OK], serde_cbor::to_vec(&map).unwrap()].concat()
+    }
+
+    fn canned(status: u8, body: Value) -> Authenticator<Canned> {
+        let mut response = vec![status];
+        response.extend(serde_cbor::to_vec(&body).unwrap());
+        Authenticator::new(Canned {
+            request: vec![],
+            response,
+        })
+    }
+
+    /// Encodes a successful `authenticatorGetInfo` response
+    pub(crate) fn canned_info(options: Vec<(&str, bool)>, protocols: Vec<Value>) -> Vec<u8> {
+        let mut info = BTreeMap::new();
+        info.insert(
+            Value::Integer(0x01),
+            Value::Array(vec![Value::Text("FIDO_2_0".to_owned())]),
+        );
+        info.insert(
+            Value::Integer(0x04),
+            map(options
+                .into_iter()
+                .map(|(k, v)| (k, Some(Value::Bool(v))))
+                .collect()),
+        );
+        info.insert(Value::Integer(0x06), Value::Array(protocols));
+
+        let mut response = vec![STATUS_OK];
+        response.extend(serde_cbor::to_vec(&Value::Map(info)).unwrap());
+        response
+    }
+
+    #[test]
+    fn get_info() {
+        let mut info = BTreeMap::new();
+        info.insert(
+            Value::Integer(0x01),
+            Value::Array(vec![Value::Text("FIDO_2_0".to_owned())]),
+        );
+        info.insert(Value::Integer(0x03), Value::Bytes(vec![7; 16]));
+        info.insert(
+            Value::Integer(0x04),
+            map(vec![("rk", Some(Value::Bool(true)))]),
+        );
+        info.insert(
+            Value::Integer(0x06),
+            Value::Array(vec![Value::Integer(2), Value::Integer(1)]),
+        );
+
+        let mut key = canned(STATUS_OK, Value::Map(info));
+        let info = key.get_info().unwrap();
+        assert_eq!(key.transport.request, vec![GET_INFO]);
+        assert_eq!(info.versions, vec!["FIDO_2_0"]);
+        assert_eq!(info.aaguid, vec![7; 16]);
+        assert_eq!(info.options.get("rk"), Some(&true));
+        assert_eq!(info.pin_uv_auth_protocols, vec![2, 1]);
+        assert_eq!(info.max_msg_size, None);
+
+        let mut key = canned(0x2e, Value::Null);
+        assert!(matches!(key.get_info(), Err(CtapError::Status(0x2e))));
+    }
+
+    #[test]
+    fn get_assertion() {
+        let config = Config::new("https://app.example.com");
+        let req = AuthenticateRequest::new(&config, vec![Device::new(vec![1, 2, 3], vec![], 0)]);
+
+        let mut assertion = BTreeMap::new();
+        assertion.insert(Value::Integer(0x02), Value::Bytes(vec![0; 37]));
+        assertion.insert(Value::Integer(0x03), Value::Bytes(vec![9; 70]));
+        let mut key = canned(STATUS_OK, Value::Map(assertion));
+
+        let response = key.get_assertion(&req, config.origin()).unwrap();
+        assert_eq!(response.ty(), WebAuthnType::Get);
+        assert_eq!(response.raw_id(), &[1, 2, 3]);
+
+        // params are canonically encoded, with the allow list naming the device
+        let params: Value = serde_cbor::from_slice(&key.transport.request[1..]).unwrap();
+        assert_eq!(key.transport.request[0], GET_ASSERTION);
+        let params = match params {
+            Value::Map(params) => params,
+            _ => panic!("params are not a map"),
+        };
+        assert_eq!(
+            params.get(&Value::Integer(0x01)),
+            Some(&Value::Text("app.example.com".to_owned()))
+        );
+        assert_eq!(
+            params.get(&Value::Integer(0x03)),
+            Some(&Value::Array(vec![map(vec![
+                ("id", Some(Value::Bytes(vec![1, 2, 3]))),
+                ("type", Some(Value::Text("public-key".to_owned()))),
+            ])]))
+        );
+    }
+
+    #[test]
+    fn reset_forgets_state() {
+        let mut script = Script::default();
+        script.answers.push_back(canned_info(vec![], vec![]));
+        script.answers.push_back(vec![STATUS_OK]);
+        script.answers.push_back(vec![0x30]);
+        let mut key = Authenticator::new(script);
+
+        key.info().unwrap();
+        key.reset().unwrap();
+        assert!(key.info.is_none());
+        // CTAP2_ERR_NOT_ALLOWED, too long after power up
+        assert!(matches!(key.reset(), Err(CtapError::Status(0x30))));
+        assert_eq!(key.into_inner().requests[1], vec![RESET]);
+    }
+
+    #[test]
+    fn attested_credential_id() {
+        let mut auth_data = vec![0; 37];
+        assert_eq!(credential_id(&auth_data), None);
+
+        auth_data[32] = FLAG_ATTESTED;
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&[0, 3, 4, 5, 6, 0xa5]);
+        assert_eq!(credential_id(&auth_data), Some(&[4, 5, 6][..]));
+        assert_eq!(credential_id(&auth_data[..56]), None);
+    }
+}
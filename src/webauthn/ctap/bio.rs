@@ -0,0 +1,379 @@
+//! Biometric enrollment (CTAP 2.1)
+//!
+//! Authenticators with a fingerprint sensor support `authenticatorBioEnrollment`, which
+//! provisioning tools use to enroll fingerprints, list and rename the enrolled templates,
+//! and remove them.  Enrollment takes several samples: [`Authenticator::enroll_begin`]
+//! captures the first and returns the new template's id, then
+//! [`Authenticator::enroll_capture_next`] is called until no samples remain.  Commands
+//! that change enrollments are authorized with a PIN token holding the
+//! `PERMISSION_BIO_ENROLLMENT` permission.  Authenticators implementing the CTAP 2.1
+//! preview answer under a prototype command byte, used when they advertise
+//! `userVerificationMgmtPreview` instead of `bioEnroll`.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::ctap::pin::PERMISSION_BIO_ENROLLMENT;
+//!
+//! let token = key.pin_token("1234", PERMISSION_BIO_ENROLLMENT, None)?;
+//! let (template_id, mut status) = key.enroll_begin(&token, None)?;
+//! while status.remaining_samples > 0 {
+//!     println!("touch the sensor again ({} left)", status.remaining_samples);
+//!     status = key.enroll_capture_next(&token, &template_id, None)?;
+//! }
+//! key.set_friendly_name(&token, &template_id, "right index")?;
+//! ```
+
+use super::{bytes, integer, pin::PinUvAuthToken, Authenticator, CtapError, Transport};
+use serde_cbor::Value;
+use std::{collections::BTreeMap, time::Duration};
+
+/// Command byte of `authenticatorBioEnrollment`
+const BIO_ENROLLMENT: u8 = 0x09;
+
+/// Command byte of the CTAP 2.1 preview's `authenticatorBioEnrollment`
+const BIO_ENROLLMENT_PREVIEW: u8 = 0x40;
+
+/// Fingerprint modality, the only one defined
+const FINGERPRINT: u8 = 0x01;
+
+/// `enrollBegin` subcommand
+const ENROLL_BEGIN: u8 = 0x01;
+
+/// `enrollCaptureNextSample` subcommand
+const ENROLL_CAPTURE_NEXT_SAMPLE: u8 = 0x02;
+
+/// `cancelCurrentEnrollment` subcommand
+const CANCEL_CURRENT_ENROLLMENT: u8 = 0x03;
+
+/// `enumerateEnrollments` subcommand
+const ENUMERATE_ENROLLMENTS: u8 = 0x04;
+
+/// `setFriendlyName` subcommand
+const SET_FRIENDLY_NAME: u8 = 0x05;
+
+/// `removeEnrollment` subcommand
+const REMOVE_ENROLLMENT: u8 = 0x06;
+
+/// `getFingerprintSensorInfo` subcommand
+const GET_FINGERPRINT_SENSOR_INFO: u8 = 0x07;
+
+/// Status returned when no fingerprint is enrolled
+const ERR_INVALID_OPTION: u8 = 0x2c;
+
+/// Sample status of a good fingerprint capture
+pub const SAMPLE_GOOD: u8 = 0x00;
+
+/// Sample status when no finger touched the sensor before the timeout
+pub const SAMPLE_NO_USER_ACTIVITY: u8 = 0x0b;
+
+/// Capabilities of an authenticator's fingerprint sensor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SensorInfo {
+    /// Kind of sensor, 1 for touch and 2 for swipe
+    pub fingerprint_kind: u64,
+
+    /// Number of good samples needed to enroll a fingerprint
+    pub max_capture_samples: u64,
+
+    /// Longest friendly name a template can be given, in bytes
+    pub max_friendly_name: Option<u64>,
+}
+
+/// Progress of a fingerprint enrollment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnrollmentStatus {
+    /// Status of the last sample (`SAMPLE_GOOD`, or why it was rejected)
+    pub last_sample_status: u8,
+
+    /// Number of good samples still needed
+    pub remaining_samples: u64,
+}
+
+/// An enrolled fingerprint
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    /// Id of the template
+    pub id: Vec<u8>,
+
+    /// Name given to the template, if any
+    pub friendly_name: Option<String>,
+}
+
+impl<T: Transport> Authenticator<T> {
+    /// Returns the capabilities of the authenticator's fingerprint sensor
+    pub fn fingerprint_sensor_info(&mut self) -> Result<SensorInfo, CtapError> {
+        let response = self.bio_enrollment(GET_FINGERPRINT_SENSOR_INFO, None, None)?;
+        Ok(SensorInfo {
+            fingerprint_kind: integer(&response, 0x02)
+                .ok_or(CtapError::Malformed("missing fingerprint kind"))?,
+            max_capture_samples: integer(&response, 0x03)
+                .ok_or(CtapError::Malformed("missing sample count"))?,
+            max_friendly_name: integer(&response, 0x08),
+        })
+    }
+
+    /// Starts enrolling a fingerprint and captures its first sample, returning the id of
+    /// the new template
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the bio enrollment permission
+    /// * `timeout` - How long the authenticator waits for a finger, if not its default
+    pub fn enroll_begin(
+        &mut self,
+        token: &PinUvAuthToken,
+        timeout: Option<Duration>,
+    ) -> Result<(Vec<u8>, EnrollmentStatus), CtapError> {
+        let params = timeout.map(|t| vec![(0x03, milliseconds(t))]);
+        let response = self.bio_enrollment(ENROLL_BEGIN, params, Some(token))?;
+        let template_id =
+            bytes(&response, 0x04).ok_or(CtapError::Malformed("missing template id"))?;
+        Ok((template_id, enrollment_status(&response)?))
+    }
+
+    /// Captures the next sample of a fingerprint being enrolled
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the bio enrollment permission
+    /// * `template_id` - Id returned by `enroll_begin()`
+    /// * `timeout` - How long the authenticator waits for a finger, if not its default
+    pub fn enroll_capture_next(
+        &mut self,
+        token: &PinUvAuthToken,
+        template_id: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<EnrollmentStatus, CtapError> {
+        let mut params = vec![(0x01, Value::Bytes(template_id.to_vec()))];
+        if let Some(timeout) = timeout {
+            params.push((0x03, milliseconds(timeout)));
+        }
+        let response =
+            self.bio_enrollment(ENROLL_CAPTURE_NEXT_SAMPLE, Some(params), Some(token))?;
+        enrollment_status(&response)
+    }
+
+    /// Abandons the fingerprint enrollment in progress
+    pub fn cancel_enrollment(&mut self) -> Result<(), CtapError> {
+        self.bio_enrollment(CANCEL_CURRENT_ENROLLMENT, None, None)?;
+        Ok(())
+    }
+
+    /// Lists the enrolled fingerprints
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the bio enrollment permission
+    pub fn enumerate_enrollments(
+        &mut self,
+        token: &PinUvAuthToken,
+    ) -> Result<Vec<Template>, CtapError> {
+        let response = match self.bio_enrollment(ENUMERATE_ENROLLMENTS, None, Some(token)) {
+            Err(CtapError::Status(ERR_INVALID_OPTION)) => return Ok(vec![]),
+            response => response?,
+        };
+
+        match response.get(&Value::Integer(0x07)) {
+            Some(Value::Array(infos)) => infos
+                .iter()
+                .map(|info| match info {
+                    Value::Map(info) => Ok(Template {
+                        id: bytes(info, 0x01).ok_or(CtapError::Malformed("missing template id"))?,
+                        friendly_name: match info.get(&Value::Integer(0x02)) {
+                            Some(Value::Text(name)) => Some(name.clone()),
+                            _ => None,
+                        },
+                    }),
+                    _ => Err(CtapError::Malformed("template info is not a map")),
+                })
+                .collect(),
+            _ => Err(CtapError::Malformed("missing template infos")),
+        }
+    }
+
+    /// Names an enrolled fingerprint
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the bio enrollment permission
+    /// * `template_id` - Id of the template
+    /// * `name` - Name to give the template
+    pub fn set_friendly_name(
+        &mut self,
+        token: &PinUvAuthToken,
+        template_id: &[u8],
+        name: &str,
+    ) -> Result<(), CtapError> {
+        let params = vec![
+            (0x01, Value::Bytes(template_id.to_vec())),
+            (0x02, Value::Text(name.to_owned())),
+        ];
+        self.bio_enrollment(SET_FRIENDLY_NAME, Some(params), Some(token))?;
+        Ok(())
+    }
+
+    /// Removes an enrolled fingerprint
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the bio enrollment permission
+    /// * `template_id` - Id of the template
+    pub fn remove_enrollment(
+        &mut self,
+        token: &PinUvAuthToken,
+        template_id: &[u8],
+    ) -> Result<(), CtapError> {
+        let params = vec![(0x01, Value::Bytes(template_id.to_vec()))];
+        self.bio_enrollment(REMOVE_ENROLLMENT, Some(params), Some(token))?;
+        Ok(())
+    }
+
+    /// Sends an `authenticatorBioEnrollment` subcommand for the fingerprint modality,
+    /// authorized with a token if given
+    fn bio_enrollment(
+        &mut self,
+        subcommand: u8,
+        params: Option<Vec<(i128, Value)>>,
+        token: Option<&PinUvAuthToken>,
+    ) -> Result<BTreeMap<Value, Value>, CtapError> {
+        let options = &self.info()?.options;
+        let command = if options.contains_key("bioEnroll") {
+            BIO_ENROLLMENT
+        } else if options.contains_key("userVerificationMgmtPreview") {
+            BIO_ENROLLMENT_PREVIEW
+        } else {
+            return Err(CtapError::Malformed(
+                "authenticator does not support bio enrollment",
+            ));
+        };
+
+        let params = params.map(|params| {
+            Value::Map(
+                params
+                    .into_iter()
+                    .map(|(k, v)| (Value::Integer(k), v))
+                    .collect(),
+            )
+        });
+
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x01), Value::Integer(FINGERPRINT.into()));
+        map.insert(Value::Integer(0x02), Value::Integer(subcommand.into()));
+        if let Some(token) = token {
+            // the token authenticates the modality, subcommand and encoded parameters
+            let mut message = vec![FINGERPRINT, subcommand];
+            if let Some(ref params) = params {
+                message.extend(serde_cbor::to_vec(params)?);
+            }
+            map.insert(
+                Value::Integer(0x04),
+                Value::Integer(token.protocol().number().into()),
+            );
+            map.insert(
+                Value::Integer(0x05),
+                Value::Bytes(token.authenticate(&message)),
+            );
+        }
+        if let Some(params) = params {
+            map.insert(Value::Integer(0x03), params);
+        }
+
+        self.command(command, Some(map))
+    }
+}
+
+/// Decodes the progress of an enrollment from a response
+fn enrollment_status(response: &BTreeMap<Value, Value>) -> Result<EnrollmentStatus, CtapError> {
+    Ok(EnrollmentStatus {
+        last_sample_status: integer(response, 0x05)
+            .ok_or(CtapError::Malformed("missing sample status"))?
+            as u8,
+        remaining_samples: integer(response, 0x06)
+            .ok_or(CtapError::Malformed("missing remaining samples"))?,
+    })
+}
+
+/// Encodes a timeout in milliseconds
+fn milliseconds(timeout: Duration) -> Value {
+    Value::Integer(timeout.as_millis() as i128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::ctap::{
+        pin::PinUvAuthProtocol,
+        tests::{answer, canned_info, Script},
+    };
+
+    #[test]
+    fn enroll_and_enumerate() {
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::Two, vec![5; 32]);
+        let mut script = Script::default();
+        script.answers.push_back(canned_info(
+            vec![("bioEnroll", false)],
+            vec![Value::Integer(2)],
+        ));
+        script.answers.push_back(answer(vec![
+            (0x04, Value::Bytes(vec![7, 7])),
+            (0x05, Value::Integer(SAMPLE_GOOD.into())),
+            (0x06, Value::Integer(1)),
+        ]));
+        script.answers.push_back(answer(vec![
+            (0x05, Value::Integer(SAMPLE_GOOD.into())),
+            (0x06, Value::Integer(0)),
+        ]));
+        let mut info = BTreeMap::new();
+        info.insert(Value::Integer(0x01), Value::Bytes(vec![7, 7]));
+        info.insert(Value::Integer(0x02), Value::Text("thumb".to_owned()));
+        script
+            .answers
+            .push_back(answer(vec![(0x07, Value::Array(vec![Value::Map(info)]))]));
+        let mut key = Authenticator::new(script);
+
+        let (template_id, status) = key
+            .enroll_begin(&token, Some(Duration::from_secs(10)))
+            .unwrap();
+        assert_eq!(template_id, vec![7, 7]);
+        assert_eq!(status.remaining_samples, 1);
+        let status = key.enroll_capture_next(&token, &template_id, None).unwrap();
+        assert_eq!(status.remaining_samples, 0);
+
+        let templates = key.enumerate_enrollments(&token).unwrap();
+        assert_eq!(templates[0].friendly_name.as_deref(), Some("thumb"));
+
+        // the token authenticates the modality, subcommand and parameters
+        let requests = key.into_inner().requests;
+        assert_eq!(requests[1][0], BIO_ENROLLMENT);
+        let begin: BTreeMap<Value, Value> = serde_cbor::from_slice(&requests[1][1..]).unwrap();
+        let params = begin.get(&Value::Integer(0x03)).unwrap();
+        let message = [
+            vec![FINGERPRINT, ENROLL_BEGIN],
+            serde_cbor::to_vec(params).unwrap(),
+        ]
+        .concat();
+        assert_eq!(
+            begin.get(&Value::Integer(0x05)),
+            Some(&Value::Bytes(token.authenticate(&message)))
+        );
+        assert_eq!(
+            params,
+            &Value::Map(
+                vec![(Value::Integer(0x03), Value::Integer(10_000))]
+                    .into_iter()
+                    .collect()
+            )
+        );
+    }
+
+    #[test]
+    fn no_enrollments() {
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::One, vec![5; 32]);
+        let mut script = Script::default();
+        script.answers.push_back(canned_info(
+            vec![("userVerificationMgmtPreview", true)],
+            vec![],
+        ));
+        script.answers.push_back(vec![ERR_INVALID_OPTION]);
+        let mut key = Authenticator::new(script);
+
+        assert!(key.enumerate_enrollments(&token).unwrap().is_empty());
+        assert_eq!(key.into_inner().requests[1][0], BIO_ENROLLMENT_PREVIEW);
+    }
+}
@@ -0,0 +1,236 @@
+//! Bluetooth Low Energy transport
+//!
+//! Authenticators expose the FIDO GATT service (`0xFFFD`).  Messages are written to the
+//! `fidoControlPoint` characteristic and answered through notifications of the
+//! `fidoStatus` characteristic, split into frames no longer than `fidoControlPointLength`:
+//! an initial frame carrying the command and length, followed by continuation frames.
+//! [`Ble`] exchanges messages over any [`BleDevice`], so applications can bring their
+//! own Bluetooth stack.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::ctap::{ble::{Ble, BleDevice}, Authenticator};
+//!
+//! struct Key { peripheral: Peripheral, notifications: Receiver<Vec<u8>> }
+//!
+//! impl BleDevice for Key {
+//!     fn control_point_length(&mut self) -> std::io::Result<usize> { /* read fidoControlPointLength */ }
+//!     fn write_control_point(&mut self, frame: &[u8]) -> std::io::Result<()> { /* write fidoControlPoint */ }
+//!     fn read_status(&mut self, timeout: Duration) -> std::io::Result<Vec<u8>> {
+//!         self.notifications.recv_timeout(timeout).map_err(|_| std::io::ErrorKind::TimedOut.into())
+//!     }
+//! }
+//!
+//! let mut key = Authenticator::new(Ble::new(Key::connect(peripheral)?)?);
+//! ```
+
+use super::{u2f, CtapError, Transport};
+use std::{io, time::Duration};
+
+/// 16-bit UUID of the FIDO GATT service
+pub const FIDO_SERVICE: u16 = 0xfffd;
+
+/// UUID of the `fidoControlPoint` characteristic
+pub const FIDO_CONTROL_POINT: &str = "f1d0fff1-deaa-ecee-b42f-c9ba7ed623bb";
+
+/// UUID of the `fidoStatus` characteristic
+pub const FIDO_STATUS: &str = "f1d0fff2-deaa-ecee-b42f-c9ba7ed623bb";
+
+/// UUID of the `fidoControlPointLength` characteristic
+pub const FIDO_CONTROL_POINT_LENGTH: &str = "f1d0fff3-deaa-ecee-b42f-c9ba7ed623bb";
+
+/// Sent by the authenticator while it waits for the user
+const KEEPALIVE: u8 = 0x82;
+
+/// Carries a CTAP2 command or CTAP1/U2F message
+pub const MSG: u8 = 0x83;
+
+/// Sent by the authenticator when a message could not be processed
+const ERROR: u8 = 0xbf;
+
+/// A connected authenticator exposing the FIDO GATT service
+pub trait BleDevice {
+    /// Reads the `fidoControlPointLength` characteristic, the longest frame the
+    /// authenticator accepts
+    fn control_point_length(&mut self) -> io::Result<usize>;
+
+    /// Writes a frame to the `fidoControlPoint` characteristic
+    ///
+    /// # Arguments
+    /// * `frame` - Frame to write
+    fn write_control_point(&mut self, frame: &[u8]) -> io::Result<()>;
+
+    /// Waits for the next notification of the `fidoStatus` characteristic, failing with
+    /// `io::ErrorKind::TimedOut` if none arrives in time
+    ///
+    /// # Arguments
+    /// * `timeout` - How long to wait for a notification
+    fn read_status(&mut self, timeout: Duration) -> io::Result<Vec<u8>>;
+}
+
+/// A connection to an authenticator over Bluetooth Low Energy
+pub struct Ble<D> {
+    device: D,
+    frame_len: usize,
+    timeout: Duration,
+}
+
+impl<D: BleDevice> Ble<D> {
+    /// Starts using a connected device, reading the longest frame it accepts
+    ///
+    /// # Arguments
+    /// * `device` - The authenticator's GATT connection
+    pub fn new(mut device: D) -> Result<Ble<D>, CtapError> {
+        let frame_len = device.control_point_length()?;
+        if !(20..=512).contains(&frame_len) {
+            return Err(CtapError::Malformed("invalid fidoControlPointLength"));
+        }
+
+        Ok(Ble {
+            device,
+            frame_len,
+            timeout: Duration::from_secs(30),
+        })
+    }
+
+    /// Sets how long to wait for each notification, including while the user is asked
+    /// to touch the authenticator (default: 30 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - How long to wait
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends a message and waits for the answer, skipping keep-alives
+    ///
+    /// # Arguments
+    /// * `cmd` - Command of the message (e.g., `MSG`)
+    /// * `data` - Payload of the message
+    pub fn transact(&mut self, cmd: u8, data: &[u8]) -> Result<Vec<u8>, CtapError> {
+        for frame in frames(self.frame_len, cmd, data)? {
+            self.device.write_control_point(&frame)?;
+        }
+
+        loop {
+            let (cmd_in, response) = self.receive()?;
+            match cmd_in {
+                KEEPALIVE => continue,
+                ERROR => return Err(CtapError::Status(response.first().copied().unwrap_or(0x7f))),
+                c if c == cmd => return Ok(response),
+                _ => return Err(CtapError::Malformed("unexpected BLE command")),
+            }
+        }
+    }
+
+    /// Reassembles one message from notifications
+    fn receive(&mut self) -> Result<(u8, Vec<u8>), CtapError> {
+        let frame = self.device.read_status(self.timeout)?;
+        if frame.len() < 3 || frame[0] & 0x80 == 0 {
+            return Err(CtapError::Malformed("expected an initial BLE frame"));
+        }
+        let cmd = frame[0];
+        let len = u16::from_be_bytes([frame[1], frame[2]]) as usize;
+        let mut data = frame[3..].to_vec();
+
+        let mut seq = 0u8;
+        while data.len() < len {
+            let frame = self.device.read_status(self.timeout)?;
+            if frame.first() != Some(&seq) {
+                return Err(CtapError::Malformed("out of sequence BLE frame"));
+            }
+            data.extend_from_slice(&frame[1..]);
+            seq = (seq + 1) & 0x7f;
+        }
+        data.truncate(len);
+        Ok((cmd, data))
+    }
+}
+
+impl<D: BleDevice> Transport for Ble<D> {
+    fn cbor(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError> {
+        self.transact(MSG, request)
+    }
+
+    fn msg(&mut self, ins: u8, p1: u8, data: &[u8]) -> Result<Vec<u8>, CtapError> {
+        u2f::response_data(self.transact(MSG, &u2f::extended_apdu(ins, p1, data))?)
+    }
+}
+
+/// Splits a message into frames
+///
+/// # Arguments
+/// * `frame_len` - Longest frame the authenticator accepts
+/// * `cmd` - Command of the message
+/// * `data` - Payload of the message
+fn frames(frame_len: usize, cmd: u8, data: &[u8]) -> Result<Vec<Vec<u8>>, CtapError> {
+    if data.len() > u16::MAX as usize {
+        return Err(CtapError::Malformed("message too long for BLE"));
+    }
+
+    let split = data.len().min(frame_len - 3);
+    let mut init = vec![cmd];
+    init.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    init.extend_from_slice(&data[..split]);
+
+    let mut frames = vec![init];
+    for (seq, chunk) in data[split..].chunks(frame_len - 1).enumerate() {
+        let mut cont = vec![(seq & 0x7f) as u8];
+        cont.extend_from_slice(chunk);
+        frames.push(cont);
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Records written frames and notifies from a queue
+    #[derive(Default)]
+    struct Fake {
+        written: Vec<Vec<u8>>,
+        notifications: VecDeque<Vec<u8>>,
+    }
+
+    impl BleDevice for Fake {
+        fn control_point_length(&mut self) -> io::Result<usize> {
+            Ok(20)
+        }
+
+        fn write_control_point(&mut self, frame: &[u8]) -> io::Result<()> {
+            self.written.push(frame.to_vec());
+            Ok(())
+        }
+
+        fn read_status(&mut self, _: Duration) -> io::Result<Vec<u8>> {
+            self.notifications
+                .pop_front()
+                .ok_or_else(|| io::ErrorKind::TimedOut.into())
+        }
+    }
+
+    #[test]
+    fn frame_and_transact() {
+        let mut ble = Ble::new(Fake::default()).unwrap();
+        let data: Vec<u8> = (0..50).collect();
+
+        ble.device.notifications.push_back(vec![KEEPALIVE, 0, 1, 1]);
+        ble.device
+            .notifications
+            .extend(frames(20, MSG, &data).unwrap());
+        assert_eq!(ble.cbor(&data).unwrap(), data);
+
+        // 17 bytes in the initial frame, then 19 per continuation frame
+        assert_eq!(ble.device.written.len(), 3);
+        assert_eq!(&ble.device.written[0][..3], &[MSG, 0, 50]);
+        assert_eq!(ble.device.written[2][0], 1);
+        assert_eq!(ble.device.written[2].len(), 15);
+
+        ble.device.notifications.push_back(vec![ERROR, 0, 1, 0x04]);
+        assert!(matches!(ble.cbor(&[0x04]), Err(CtapError::Status(0x04))));
+    }
+}
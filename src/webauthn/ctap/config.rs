@@ -0,0 +1,192 @@
+//! Authenticator configuration (CTAP 2.1)
+//!
+//! Authenticators advertising the `authnrCfg` option accept `authenticatorConfig`, which
+//! fleet provisioning tools use to enable enterprise attestation, require user
+//! verification for every operation, and raise the minimum PIN length.  Every
+//! subcommand is authorized with a PIN token holding the
+//! `PERMISSION_AUTHENTICATOR_CONFIG` permission.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::ctap::pin::PERMISSION_AUTHENTICATOR_CONFIG;
+//!
+//! let token = key.pin_token("12345678", PERMISSION_AUTHENTICATOR_CONFIG, None)?;
+//! key.enable_enterprise_attestation(&token)?;
+//! key.set_min_pin_length(&token, Some(8), &["example.com"], false)?;
+//! ```
+
+use super::{pin::PinUvAuthToken, Authenticator, CtapError, Transport};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+/// Command byte of `authenticatorConfig`
+const AUTHENTICATOR_CONFIG: u8 = 0x0d;
+
+/// `enableEnterpriseAttestation` subcommand
+const ENABLE_ENTERPRISE_ATTESTATION: u8 = 0x01;
+
+/// `toggleAlwaysUv` subcommand
+const TOGGLE_ALWAYS_UV: u8 = 0x02;
+
+/// `setMinPINLength` subcommand
+const SET_MIN_PIN_LENGTH: u8 = 0x03;
+
+impl<T: Transport> Authenticator<T> {
+    /// Lets relying parties request enterprise attestation from the authenticator
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the authenticator config permission
+    pub fn enable_enterprise_attestation(
+        &mut self,
+        token: &PinUvAuthToken,
+    ) -> Result<(), CtapError> {
+        self.authenticator_config(token, ENABLE_ENTERPRISE_ATTESTATION, None)
+    }
+
+    /// Turns requiring user verification for every operation on or off
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the authenticator config permission
+    pub fn toggle_always_uv(&mut self, token: &PinUvAuthToken) -> Result<(), CtapError> {
+        self.authenticator_config(token, TOGGLE_ALWAYS_UV, None)
+    }
+
+    /// Raises the minimum PIN length, and lists the relying parties allowed to read it
+    /// with the `minPinLength` extension
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the authenticator config permission
+    /// * `length` - New minimum PIN length, in code points, if it changes
+    /// * `rp_ids` - Relying parties allowed to read the minimum PIN length
+    /// * `force_change_pin` - True to require the PIN to be changed before it is used again
+    pub fn set_min_pin_length(
+        &mut self,
+        token: &PinUvAuthToken,
+        length: Option<u64>,
+        rp_ids: &[&str],
+        force_change_pin: bool,
+    ) -> Result<(), CtapError> {
+        let mut params = BTreeMap::new();
+        if let Some(length) = length {
+            params.insert(Value::Integer(0x01), Value::Integer(length.into()));
+        }
+        if !rp_ids.is_empty() {
+            params.insert(
+                Value::Integer(0x02),
+                Value::Array(
+                    rp_ids
+                        .iter()
+                        .map(|id| Value::Text((*id).to_owned()))
+                        .collect(),
+                ),
+            );
+        }
+        if force_change_pin {
+            params.insert(Value::Integer(0x03), Value::Bool(true));
+        }
+        self.authenticator_config(token, SET_MIN_PIN_LENGTH, Some(params))
+    }
+
+    /// Sends an `authenticatorConfig` subcommand
+    fn authenticator_config(
+        &mut self,
+        token: &PinUvAuthToken,
+        subcommand: u8,
+        params: Option<BTreeMap<Value, Value>>,
+    ) -> Result<(), CtapError> {
+        if self.info()?.options.get("authnrCfg") != Some(&true) {
+            return Err(CtapError::Malformed(
+                "authenticator does not support configuration",
+            ));
+        }
+
+        // 32 x 0xff || command || subcommand || encoded parameters
+        let params = params.map(Value::Map);
+        let mut message = vec![0xff; 32];
+        message.extend_from_slice(&[AUTHENTICATOR_CONFIG, subcommand]);
+        if let Some(ref params) = params {
+            message.extend(serde_cbor::to_vec(params)?);
+        }
+
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x01), Value::Integer(subcommand.into()));
+        if let Some(params) = params {
+            map.insert(Value::Integer(0x02), params);
+        }
+        map.insert(
+            Value::Integer(0x03),
+            Value::Integer(token.protocol().number().into()),
+        );
+        map.insert(
+            Value::Integer(0x04),
+            Value::Bytes(token.authenticate(&message)),
+        );
+
+        self.command(AUTHENTICATOR_CONFIG, Some(map))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::ctap::{
+        pin::PinUvAuthProtocol,
+        tests::{canned_info, Script},
+        STATUS_OK,
+    };
+
+    #[test]
+    fn set_min_pin_length() {
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::Two, vec![5; 32]);
+        let mut script = Script::default();
+        script.answers.push_back(canned_info(
+            vec![("authnrCfg", true)],
+            vec![Value::Integer(2)],
+        ));
+        script.answers.push_back(vec![STATUS_OK]);
+        let mut key = Authenticator::new(script);
+
+        key.set_min_pin_length(&token, Some(8), &["example.com"], false)
+            .unwrap();
+
+        let requests = key.into_inner().requests;
+        assert_eq!(requests[1][0], AUTHENTICATOR_CONFIG);
+        let request: BTreeMap<Value, Value> = serde_cbor::from_slice(&requests[1][1..]).unwrap();
+        let params = request.get(&Value::Integer(0x02)).unwrap();
+        assert_eq!(
+            params,
+            &Value::Map(
+                vec![
+                    (Value::Integer(0x01), Value::Integer(8)),
+                    (
+                        Value::Integer(0x02),
+                        Value::Array(vec![Value::Text("example.com".to_owned())])
+                    ),
+                ]
+                .into_iter()
+                .collect()
+            )
+        );
+
+        let mut message = vec![0xff; 32];
+        message.extend_from_slice(&[AUTHENTICATOR_CONFIG, SET_MIN_PIN_LENGTH]);
+        message.extend(serde_cbor::to_vec(params).unwrap());
+        assert_eq!(
+            request.get(&Value::Integer(0x04)),
+            Some(&Value::Bytes(token.authenticate(&message)))
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::One, vec![5; 32]);
+        let mut script = Script::default();
+        script.answers.push_back(canned_info(vec![], vec![]));
+        let mut key = Authenticator::new(script);
+
+        assert!(key.enable_enterprise_attestation(&token).is_err());
+        assert_eq!(key.into_inner().requests.len(), 1);
+    }
+}
@@ -0,0 +1,395 @@
+//! Credential management (CTAP 2.1)
+//!
+//! Authenticators supporting `authenticatorCredentialManagement` let administration
+//! tools list the discoverable (resident) credentials they hold, grouped by relying
+//! party, delete them and update the user information stored with them.  Every command
+//! is authorized with a PIN token holding the `PERMISSION_CREDENTIAL_MANAGEMENT`
+//! permission.  Authenticators implementing the CTAP 2.1 preview answer the same
+//! subcommands under a prototype command byte, which is used when they advertise
+//! `credentialMgmtPreview` instead of `credMgmt`.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::ctap::pin::PERMISSION_CREDENTIAL_MANAGEMENT;
+//!
+//! let token = key.pin_token("1234", PERMISSION_CREDENTIAL_MANAGEMENT, None)?;
+//! for rp in key.enumerate_rps(&token)? {
+//!     for credential in key.enumerate_credentials(&token, &rp.rp_id_hash)? {
+//!         println!("{}: {:?}", rp.rp.id, credential.user.name);
+//!     }
+//! }
+//! ```
+
+use super::{
+    bytes, integer,
+    messages::{CredentialDescriptor, RelyingPartyEntity, UserEntity},
+    pin::PinUvAuthToken,
+    Authenticator, CtapError, Transport,
+};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+/// Command byte of `authenticatorCredentialManagement`
+const CREDENTIAL_MANAGEMENT: u8 = 0x0a;
+
+/// Command byte of the CTAP 2.1 preview's `authenticatorCredentialManagement`
+const CREDENTIAL_MANAGEMENT_PREVIEW: u8 = 0x41;
+
+/// `getCredsMetadata` subcommand
+const GET_CREDS_METADATA: u8 = 0x01;
+
+/// `enumerateRPsBegin` subcommand
+const ENUMERATE_RPS_BEGIN: u8 = 0x02;
+
+/// `enumerateRPsGetNextRP` subcommand
+const ENUMERATE_RPS_GET_NEXT_RP: u8 = 0x03;
+
+/// `enumerateCredentialsBegin` subcommand
+const ENUMERATE_CREDENTIALS_BEGIN: u8 = 0x04;
+
+/// `enumerateCredentialsGetNextCredential` subcommand
+const ENUMERATE_CREDENTIALS_GET_NEXT_CREDENTIAL: u8 = 0x05;
+
+/// `deleteCredential` subcommand
+const DELETE_CREDENTIAL: u8 = 0x06;
+
+/// `updateUserInformation` subcommand
+const UPDATE_USER_INFORMATION: u8 = 0x07;
+
+/// Status returned when there is nothing to enumerate
+const ERR_NO_CREDENTIALS: u8 = 0x2e;
+
+/// How many discoverable credentials an authenticator holds, and has room for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CredentialsMetadata {
+    /// Number of discoverable credentials stored
+    pub existing: u64,
+
+    /// Estimated number of discoverable credentials that can still be stored
+    pub remaining: u64,
+}
+
+/// A relying party with discoverable credentials on an authenticator
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResidentRp {
+    /// The relying party, as stored when its first credential was created
+    pub rp: RelyingPartyEntity,
+
+    /// SHA-256 hash of the relying party id, used to enumerate its credentials
+    pub rp_id_hash: Vec<u8>,
+}
+
+/// A discoverable credential stored on an authenticator
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResidentCredential {
+    /// Account the credential belongs to
+    pub user: UserEntity,
+
+    /// Descriptor of the credential
+    pub credential_id: CredentialDescriptor,
+
+    /// COSE encoded public key of the credential
+    pub public_key: Value,
+
+    /// Protection level of the `credProtect` extension, if set
+    pub cred_protect: Option<u64>,
+
+    /// Key of the credential's large blob, if it has one
+    pub large_blob_key: Option<Vec<u8>>,
+}
+
+impl<T: Transport> Authenticator<T> {
+    /// Returns how many discoverable credentials the authenticator holds
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the credential management permission
+    pub fn credentials_metadata(
+        &mut self,
+        token: &PinUvAuthToken,
+    ) -> Result<CredentialsMetadata, CtapError> {
+        let response = self.credential_management(GET_CREDS_METADATA, None, Some(token))?;
+        Ok(CredentialsMetadata {
+            existing: integer(&response, 0x01)
+                .ok_or(CtapError::Malformed("missing credential count"))?,
+            remaining: integer(&response, 0x02)
+                .ok_or(CtapError::Malformed("missing remaining credential count"))?,
+        })
+    }
+
+    /// Lists the relying parties with discoverable credentials on the authenticator
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the credential management permission
+    pub fn enumerate_rps(&mut self, token: &PinUvAuthToken) -> Result<Vec<ResidentRp>, CtapError> {
+        let first = match self.credential_management(ENUMERATE_RPS_BEGIN, None, Some(token)) {
+            Err(CtapError::Status(ERR_NO_CREDENTIALS)) => return Ok(vec![]),
+            response => response?,
+        };
+        let total = integer(&first, 0x05).ok_or(CtapError::Malformed("missing RP count"))?;
+
+        let mut rps = vec![resident_rp(first)?];
+        for _ in 1..total {
+            let next = self.credential_management(ENUMERATE_RPS_GET_NEXT_RP, None, None)?;
+            rps.push(resident_rp(next)?);
+        }
+        Ok(rps)
+    }
+
+    /// Lists the discoverable credentials of a relying party
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the credential management permission
+    /// * `rp_id_hash` - SHA-256 hash of the relying party id (see `enumerate_rps()`)
+    pub fn enumerate_credentials(
+        &mut self,
+        token: &PinUvAuthToken,
+        rp_id_hash: &[u8],
+    ) -> Result<Vec<ResidentCredential>, CtapError> {
+        let params = vec![(0x01, Value::Bytes(rp_id_hash.to_vec()))];
+        let first = match self.credential_management(
+            ENUMERATE_CREDENTIALS_BEGIN,
+            Some(params),
+            Some(token),
+        ) {
+            Err(CtapError::Status(ERR_NO_CREDENTIALS)) => return Ok(vec![]),
+            response => response?,
+        };
+        let total =
+            integer(&first, 0x09).ok_or(CtapError::Malformed("missing credential count"))?;
+
+        let mut credentials = vec![resident_credential(first)?];
+        for _ in 1..total {
+            let next =
+                self.credential_management(ENUMERATE_CREDENTIALS_GET_NEXT_CREDENTIAL, None, None)?;
+            credentials.push(resident_credential(next)?);
+        }
+        Ok(credentials)
+    }
+
+    /// Deletes a discoverable credential
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the credential management permission
+    /// * `id` - Id of the credential to delete
+    pub fn delete_credential(
+        &mut self,
+        token: &PinUvAuthToken,
+        id: &[u8],
+    ) -> Result<(), CtapError> {
+        let params = vec![(0x02, to_value(&CredentialDescriptor::new(id.to_vec()))?)];
+        self.credential_management(DELETE_CREDENTIAL, Some(params), Some(token))?;
+        Ok(())
+    }
+
+    /// Replaces the user information stored with a discoverable credential.  The user id
+    /// must match the one the credential was created with
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the credential management permission
+    /// * `id` - Id of the credential to update
+    /// * `user` - New user information
+    pub fn update_user(
+        &mut self,
+        token: &PinUvAuthToken,
+        id: &[u8],
+        user: &UserEntity,
+    ) -> Result<(), CtapError> {
+        let params = vec![
+            (0x02, to_value(&CredentialDescriptor::new(id.to_vec()))?),
+            (0x03, to_value(user)?),
+        ];
+        self.credential_management(UPDATE_USER_INFORMATION, Some(params), Some(token))?;
+        Ok(())
+    }
+
+    /// Sends an `authenticatorCredentialManagement` subcommand, authorized with a token
+    /// if given
+    fn credential_management(
+        &mut self,
+        subcommand: u8,
+        params: Option<Vec<(i128, Value)>>,
+        token: Option<&PinUvAuthToken>,
+    ) -> Result<BTreeMap<Value, Value>, CtapError> {
+        let options = &self.info()?.options;
+        let command = if options.get("credMgmt") == Some(&true) {
+            CREDENTIAL_MANAGEMENT
+        } else if options.get("credentialMgmtPreview") == Some(&true) {
+            CREDENTIAL_MANAGEMENT_PREVIEW
+        } else {
+            return Err(CtapError::Malformed(
+                "authenticator does not support credential management",
+            ));
+        };
+
+        let params = params.map(|params| {
+            Value::Map(
+                params
+                    .into_iter()
+                    .map(|(k, v)| (Value::Integer(k), v))
+                    .collect(),
+            )
+        });
+
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x01), Value::Integer(subcommand.into()));
+        if let Some(token) = token {
+            // the token authenticates the subcommand and its encoded parameters
+            let mut message = vec![subcommand];
+            if let Some(ref params) = params {
+                message.extend(serde_cbor::to_vec(params)?);
+            }
+            map.insert(
+                Value::Integer(0x03),
+                Value::Integer(token.protocol().number().into()),
+            );
+            map.insert(
+                Value::Integer(0x04),
+                Value::Bytes(token.authenticate(&message)),
+            );
+        }
+        if let Some(params) = params {
+            map.insert(Value::Integer(0x02), params);
+        }
+
+        self.command(command, Some(map))
+    }
+}
+
+/// Decodes a relying party from an enumeration response
+fn resident_rp(mut response: BTreeMap<Value, Value>) -> Result<ResidentRp, CtapError> {
+    let rp = response
+        .remove(&Value::Integer(0x03))
+        .ok_or(CtapError::Malformed("missing RP"))?;
+    Ok(ResidentRp {
+        rp: serde_cbor::value::from_value(rp)?,
+        rp_id_hash: bytes(&response, 0x04).ok_or(CtapError::Malformed("missing RP id hash"))?,
+    })
+}
+
+/// Decodes a credential from an enumeration response
+fn resident_credential(
+    mut response: BTreeMap<Value, Value>,
+) -> Result<ResidentCredential, CtapError> {
+    let mut take = |key, missing| {
+        response
+            .remove(&Value::Integer(key))
+            .ok_or(CtapError::Malformed(missing))
+    };
+    let user = take(0x06, "missing user")?;
+    let credential_id = take(0x07, "missing credential id")?;
+    let public_key = take(0x08, "missing public key")?;
+
+    Ok(ResidentCredential {
+        user: serde_cbor::value::from_value(user)?,
+        credential_id: serde_cbor::value::from_value(credential_id)?,
+        public_key,
+        cred_protect: integer(&response, 0x0a),
+        large_blob_key: bytes(&response, 0x0b),
+    })
+}
+
+/// Encodes a command structure as a CBOR value
+fn to_value<S: serde::Serialize>(value: &S) -> Result<Value, CtapError> {
+    Ok(serde_cbor::value::to_value(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::ctap::{
+        pin::PinUvAuthProtocol,
+        tests::{answer, canned_info, Script},
+        STATUS_OK,
+    };
+
+    fn credential(id: u8, name: &str) -> Vec<(i128, Value)> {
+        let user = UserEntity {
+            id: vec![id],
+            name: Some(name.to_owned()),
+            display_name: None,
+        };
+        vec![
+            (0x06, to_value(&user).unwrap()),
+            (
+                0x07,
+                to_value(&CredentialDescriptor::new(vec![id; 16])).unwrap(),
+            ),
+            (0x08, Value::Map(BTreeMap::new())),
+        ]
+    }
+
+    #[test]
+    fn enumerate_and_delete() {
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::Two, vec![5; 32]);
+        let mut script = Script::default();
+        script.answers.push_back(canned_info(
+            vec![("credMgmt", true)],
+            vec![Value::Integer(2)],
+        ));
+        let rp = RelyingPartyEntity {
+            id: "example.com".to_owned(),
+            name: None,
+        };
+        script.answers.push_back(answer(vec![
+            (0x03, to_value(&rp).unwrap()),
+            (0x04, Value::Bytes(vec![1; 32])),
+            (0x05, Value::Integer(1)),
+        ]));
+        let mut first = credential(1, "alice");
+        first.push((0x09, Value::Integer(2)));
+        first.push((0x0a, Value::Integer(2)));
+        script.answers.push_back(answer(first));
+        script.answers.push_back(answer(credential(2, "bob")));
+        script.answers.push_back(vec![STATUS_OK]);
+        let mut key = Authenticator::new(script);
+
+        let rps = key.enumerate_rps(&token).unwrap();
+        assert_eq!(rps.len(), 1);
+        assert_eq!(rps[0].rp, rp);
+
+        let credentials = key
+            .enumerate_credentials(&token, &rps[0].rp_id_hash)
+            .unwrap();
+        assert_eq!(credentials.len(), 2);
+        assert_eq!(credentials[0].cred_protect, Some(2));
+        assert_eq!(credentials[1].user.name.as_deref(), Some("bob"));
+
+        key.delete_credential(&token, &credentials[1].credential_id.id)
+            .unwrap();
+
+        // only the first command of an enumeration is authorized
+        let requests = &key.into_inner().requests;
+        let request = |i: usize| -> BTreeMap<Value, Value> {
+            assert_eq!(requests[i][0], CREDENTIAL_MANAGEMENT);
+            serde_cbor::from_slice(&requests[i][1..]).unwrap()
+        };
+        assert!(request(2).contains_key(&Value::Integer(0x04)));
+        assert!(!request(3).contains_key(&Value::Integer(0x04)));
+
+        let delete = request(4);
+        let params = delete.get(&Value::Integer(0x02)).unwrap();
+        let message = [vec![DELETE_CREDENTIAL], serde_cbor::to_vec(params).unwrap()].concat();
+        assert_eq!(
+            delete.get(&Value::Integer(0x04)),
+            Some(&Value::Bytes(token.authenticate(&message)))
+        );
+    }
+
+    #[test]
+    fn nothing_to_enumerate() {
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::One, vec![5; 32]);
+        let mut script = Script::default();
+        script
+            .answers
+            .push_back(canned_info(vec![("credentialMgmtPreview", true)], vec![]));
+        script.answers.push_back(vec![ERR_NO_CREDENTIALS]);
+        let mut key = Authenticator::new(script);
+
+        assert!(key.enumerate_rps(&token).unwrap().is_empty());
+        assert_eq!(
+            key.into_inner().requests[1][0],
+            CREDENTIAL_MANAGEMENT_PREVIEW
+        );
+    }
+}
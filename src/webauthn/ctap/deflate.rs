@@ -0,0 +1,399 @@
+//! Raw DEFLATE (RFC 1951), as required to compress large blobs
+//!
+//! Decompression handles stored, fixed and dynamic Huffman blocks, so blobs written by
+//! any platform can be read.  Compression emits a single fixed Huffman block with greedy
+//! LZ77 matching, which is small and good enough for the few kilobytes a large blob
+//! holds.
+
+use super::CtapError;
+
+/// Base lengths of length codes 257..285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+
+/// Extra bits of length codes 257..285
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base distances of distance codes 0..29
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+
+/// Extra bits of distance codes 0..29
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order in which code length code lengths are sent in a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Size of the sliding window
+const WINDOW: usize = 32 * 1024;
+
+/// Longest match
+const MAX_MATCH: usize = 258;
+
+/// Decompresses raw DEFLATE data
+///
+/// # Arguments
+/// * `data` - Compressed data
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, CtapError> {
+    let mut bits = BitReader { data, pos: 0 };
+    let mut out = vec![];
+
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                bits.align();
+                let len = bits.read(16)? as usize;
+                let nlen = bits.read(16)? as usize;
+                if len != !nlen & 0xffff {
+                    return Err(CtapError::Malformed("corrupt stored block"));
+                }
+                let start = bits.pos / 8;
+                let block = data
+                    .get(start..start + len)
+                    .ok_or(CtapError::Malformed("truncated stored block"))?;
+                out.extend_from_slice(block);
+                bits.pos += len * 8;
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].iter_mut().for_each(|l| *l = 9);
+                lengths[256..280].iter_mut().for_each(|l| *l = 7);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            _ => return Err(CtapError::Malformed("invalid DEFLATE block type")),
+        }
+
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Compresses data as raw DEFLATE
+///
+/// # Arguments
+/// * `data` - Data to compress
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // a single, final, fixed Huffman block
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & 0x7fff
+    };
+    let mut head = vec![usize::MAX; 0x8000];
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            let candidate = head[h];
+            head[h] = i;
+
+            if candidate != usize::MAX && i - candidate <= WINDOW {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[i..])
+                    .take(MAX_MATCH)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len >= 3 {
+                    best = (len, i - candidate);
+                }
+            }
+        }
+
+        match best {
+            (0, _) => {
+                fixed_literal(&mut bits, data[i] as u16);
+                i += 1;
+            }
+            (len, dist) => {
+                let code = LENGTH_BASE
+                    .iter()
+                    .rposition(|&b| b as usize <= len)
+                    .unwrap();
+                fixed_literal(&mut bits, 257 + code as u16);
+                bits.write(
+                    (len - LENGTH_BASE[code] as usize) as u32,
+                    LENGTH_EXTRA[code],
+                );
+
+                let code = DIST_BASE.iter().rposition(|&b| b as usize <= dist).unwrap();
+                bits.write_code(code as u32, 5);
+                bits.write((dist - DIST_BASE[code] as usize) as u32, DIST_EXTRA[code]);
+
+                // index the skipped positions so later data can refer to them
+                for j in i + 1..(i + len).min(data.len().saturating_sub(2)) {
+                    head[hash(j)] = j;
+                }
+                i += len;
+            }
+        }
+    }
+
+    fixed_literal(&mut bits, 256);
+    bits.finish()
+}
+
+/// Writes a literal/length symbol with the fixed Huffman code
+fn fixed_literal(bits: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => bits.write_code(0x30 + symbol, 8),
+        144..=255 => bits.write_code(0x190 + symbol - 144, 9),
+        256..=279 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+/// Reads the literal/length and distance codes of a dynamic block
+fn dynamic_codes(bits: &mut BitReader) -> Result<(Huffman, Huffman), CtapError> {
+    let hlit = bits.read(5)? as usize + 257;
+    let hdist = bits.read(5)? as usize + 1;
+    let hclen = bits.read(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..hclen] {
+        code_lengths[i] = bits.read(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(hlit + hdist);
+    while lengths.len() < hlit + hdist {
+        let (value, repeat) = match code_lengths.decode(bits)? {
+            len @ 0..=15 => (len as u8, 1),
+            16 => {
+                let prev = *lengths
+                    .last()
+                    .ok_or(CtapError::Malformed("repeat without a previous length"))?;
+                (prev, 3 + bits.read(2)?)
+            }
+            17 => (0, 3 + bits.read(3)?),
+            _ => (0, 11 + bits.read(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() != hlit + hdist {
+        return Err(CtapError::Malformed("too many code lengths"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..hlit]),
+        Huffman::new(&lengths[hlit..]),
+    ))
+}
+
+/// Decodes the symbols of a Huffman compressed block
+fn inflate_block(
+    bits: &mut BitReader,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), CtapError> {
+    loop {
+        match literals.decode(bits)? {
+            literal @ 0..=255 => out.push(literal as u8),
+            256 => return Ok(()),
+            symbol => {
+                let code = symbol as usize - 257;
+                let len = *LENGTH_BASE
+                    .get(code)
+                    .ok_or(CtapError::Malformed("invalid length code"))?
+                    as usize
+                    + bits.read(LENGTH_EXTRA[code])? as usize;
+
+                let code = distances.decode(bits)? as usize;
+                let dist = *DIST_BASE
+                    .get(code)
+                    .ok_or(CtapError::Malformed("invalid distance code"))?
+                    as usize
+                    + bits.read(DIST_EXTRA[code])? as usize;
+                if dist > out.len() {
+                    return Err(CtapError::Malformed("distance too far back"));
+                }
+
+                // copies may overlap the bytes they produce
+                let start = out.len() - dist;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+/// A canonical Huffman code, decoded one bit at a time
+struct Huffman {
+    /// Number of codes of each length
+    counts: [u16; 16],
+
+    /// Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Builds the canonical code from the code length of each symbol (0 if unused)
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+            .filter(|&s| lengths[s as usize] != 0)
+            .collect();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+        Huffman { counts, symbols }
+    }
+
+    /// Decodes the next symbol
+    fn decode(&self, bits: &mut BitReader) -> Result<u16, CtapError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.read(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(CtapError::Malformed("invalid Huffman code"))
+    }
+}
+
+/// Reads bits, least significant first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, n: u8) -> Result<u32, CtapError> {
+        let mut value = 0;
+        for i in 0..n {
+            let byte = self
+                .data
+                .get(self.pos / 8)
+                .ok_or(CtapError::Malformed("truncated DEFLATE data"))?;
+            value |= ((byte >> (self.pos % 8)) as u32 & 1) << i;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+}
+
+/// Writes bits, least significant first
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    n: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, n: u8) {
+        for i in 0..n {
+            self.acc |= ((value >> i) & 1) << self.n;
+            self.n += 1;
+            if self.n == 8 {
+                self.out.push(self.acc as u8);
+                self.acc = 0;
+                self.n = 0;
+            }
+        }
+    }
+
+    /// Writes a Huffman code, which is packed most significant bit first
+    fn write_code(&mut self, code: u32, n: u8) {
+        for i in (0..n).rev() {
+            self.write((code >> i) & 1, 1);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn sample() -> Vec<u8> {
+        let text = [
+            "the quick brown fox jumps over the lazy dog; ".repeat(3),
+            "large blobs hold per-credential data like certificates. ".to_owned(),
+        ]
+        .concat();
+        text.repeat(2).into_bytes()
+    }
+
+    #[test]
+    fn inflate_dynamic_block() {
+        // zlib, level 9, raw DEFLATE
+        let compressed = hex(concat!(
+            "bd8edb1582400c055bb90d68035693ddbd402412cc065fd5eba107fc9c33f3313911f74deb8c12",
+            "fe5c30f80bd7edb676f88381fc6993cf1bcdc7cb4ec7c4263112c5bc744c6e0d2be354838d4baa",
+            "189aa4c074262a2375d02ac97e3ef0e82ffb5f",
+        ));
+        assert_eq!(inflate(&compressed).unwrap(), sample());
+
+        // a stored block
+        assert_eq!(
+            inflate(&[0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c']).unwrap(),
+            b"abc"
+        );
+        assert!(inflate(&compressed[..40]).is_err());
+    }
+
+    #[test]
+    fn deflate_round_trip() {
+        let data = sample();
+        let compressed = deflate(&data);
+        assert!(compressed.len() < data.len() / 2);
+        assert_eq!(inflate(&compressed).unwrap(), data);
+
+        let noise: Vec<u8> = (0..2000u32).map(|i| (i * 7919 % 251) as u8).collect();
+        assert_eq!(inflate(&deflate(&noise)).unwrap(), noise);
+        assert_eq!(inflate(&deflate(&[])).unwrap(), b"");
+    }
+}
@@ -0,0 +1,334 @@
+//! USB HID transport (CTAPHID)
+//!
+//! Messages are split into 64 byte reports: an initialization packet carrying the
+//! channel, command and length, followed by as many continuation packets as needed.
+//! [`Hid`] allocates a channel when created and then exchanges messages over any
+//! [`HidDevice`], so applications can bring their own HID library.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::ctap::hid::{Hid, HidDevice, REPORT_LEN};
+//!
+//! struct Key(hidapi::HidDevice);
+//!
+//! impl HidDevice for Key {
+//!     fn write_report(&mut self, report: &[u8; REPORT_LEN]) -> std::io::Result<()> {
+//!         // hidapi expects the report id before the report
+//!         let mut buf = [0u8; REPORT_LEN + 1];
+//!         buf[1..].copy_from_slice(report);
+//!         self.0.write(&buf).map(|_| ()).map_err(to_io)
+//!     }
+//!
+//!     fn read_report(&mut self, timeout: Duration) -> std::io::Result<[u8; REPORT_LEN]> {
+//!         let mut buf = [0u8; REPORT_LEN];
+//!         match self.0.read_timeout(&mut buf, timeout.as_millis() as i32).map_err(to_io)? {
+//!             0 => Err(std::io::ErrorKind::TimedOut.into()),
+//!             _ => Ok(buf),
+//!         }
+//!     }
+//! }
+//!
+//! let hid = Hid::new(Key(api.open_path(path)?))?;
+//! ```
+
+use super::{u2f, CtapError, Transport};
+use rand::RngCore;
+use std::{io, time::Duration};
+
+/// Size of a HID report
+pub const REPORT_LEN: usize = 64;
+
+/// Payload carried by an initialization packet
+const INIT_DATA_LEN: usize = REPORT_LEN - 7;
+
+/// Payload carried by a continuation packet
+const CONT_DATA_LEN: usize = REPORT_LEN - 5;
+
+/// Channel used to allocate a channel
+const BROADCAST_CID: u32 = 0xffff_ffff;
+
+/// Sends a raw CTAP1/U2F message
+pub const CTAPHID_MSG: u8 = 0x03;
+
+/// Allocates a channel
+pub const CTAPHID_INIT: u8 = 0x06;
+
+/// Sends a CTAP2 command
+pub const CTAPHID_CBOR: u8 = 0x10;
+
+/// Sent by the authenticator while it waits for the user
+const CTAPHID_KEEPALIVE: u8 = 0x3b;
+
+/// Sent by the authenticator when a message could not be processed
+const CTAPHID_ERROR: u8 = 0x3f;
+
+/// A HID device exchanging 64 byte reports, without report ids
+pub trait HidDevice {
+    /// Writes an output report
+    ///
+    /// # Arguments
+    /// * `report` - Report to write
+    fn write_report(&mut self, report: &[u8; REPORT_LEN]) -> io::Result<()>;
+
+    /// Reads an input report, failing with `io::ErrorKind::TimedOut` if none arrives in
+    /// time
+    ///
+    /// # Arguments
+    /// * `timeout` - How long to wait for a report
+    fn read_report(&mut self, timeout: Duration) -> io::Result<[u8; REPORT_LEN]>;
+}
+
+/// A CTAPHID channel to an authenticator
+pub struct Hid<D> {
+    device: D,
+    cid: u32,
+    timeout: Duration,
+}
+
+impl<D: HidDevice> Hid<D> {
+    /// Allocates a channel on a device
+    ///
+    /// # Arguments
+    /// * `device` - The authenticator's HID device
+    pub fn new(device: D) -> Result<Hid<D>, CtapError> {
+        let mut hid = Hid {
+            device,
+            cid: BROADCAST_CID,
+            timeout: Duration::from_secs(30),
+        };
+
+        let mut nonce = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        // other clients may allocate channels at the same time, so skip their answers
+        loop {
+            let response = hid.transact(CTAPHID_INIT, &nonce)?;
+            if response.len() < 12 {
+                return Err(CtapError::Malformed("short CTAPHID_INIT response"));
+            }
+            if response[..8] == nonce {
+                hid.cid =
+                    u32::from_be_bytes([response[8], response[9], response[10], response[11]]);
+                return Ok(hid);
+            }
+        }
+    }
+
+    /// Sets how long to wait for each report, including while the user is asked to
+    /// touch the authenticator (default: 30 seconds)
+    ///
+    /// # Arguments
+    /// * `timeout` - How long to wait
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the allocated channel id
+    pub fn channel(&self) -> u32 {
+        self.cid
+    }
+
+    /// Sends a message on the channel and waits for the answer, skipping keep-alives
+    ///
+    /// # Arguments
+    /// * `cmd` - CTAPHID command
+    /// * `data` - Payload of the message
+    pub fn transact(&mut self, cmd: u8, data: &[u8]) -> Result<Vec<u8>, CtapError> {
+        for report in packets(self.cid, cmd, data)? {
+            self.device.write_report(&report)?;
+        }
+
+        loop {
+            let (cmd_in, response) = self.receive()?;
+            match cmd_in {
+                CTAPHID_KEEPALIVE => continue,
+                CTAPHID_ERROR => {
+                    return Err(CtapError::Status(response.first().copied().unwrap_or(0x7f)))
+                }
+                c if c == cmd => return Ok(response),
+                _ => return Err(CtapError::Malformed("unexpected CTAPHID command")),
+            }
+        }
+    }
+
+    /// Reads one message addressed to this channel
+    fn receive(&mut self) -> Result<(u8, Vec<u8>), CtapError> {
+        let mut message = Message::default();
+        loop {
+            let report = self.device.read_report(self.timeout)?;
+            if let Some(done) = message.push(self.cid, &report)? {
+                return Ok(done);
+            }
+        }
+    }
+}
+
+impl<D: HidDevice> Transport for Hid<D> {
+    fn cbor(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError> {
+        self.transact(CTAPHID_CBOR, request)
+    }
+
+    fn msg(&mut self, ins: u8, p1: u8, data: &[u8]) -> Result<Vec<u8>, CtapError> {
+        u2f::response_data(self.transact(CTAPHID_MSG, &u2f::extended_apdu(ins, p1, data))?)
+    }
+}
+
+/// Splits a message into reports
+///
+/// # Arguments
+/// * `cid` - Channel id
+/// * `cmd` - CTAPHID command
+/// * `data` - Payload of the message
+fn packets(cid: u32, cmd: u8, data: &[u8]) -> Result<Vec<[u8; REPORT_LEN]>, CtapError> {
+    let max = INIT_DATA_LEN + 0x80 * CONT_DATA_LEN;
+    if data.len() > max {
+        return Err(CtapError::Malformed("message too long for CTAPHID"));
+    }
+
+    let split = data.len().min(INIT_DATA_LEN);
+    let mut init = [0u8; REPORT_LEN];
+    init[..4].copy_from_slice(&cid.to_be_bytes());
+    init[4] = cmd | 0x80;
+    init[5..7].copy_from_slice(&(data.len() as u16).to_be_bytes());
+    init[7..7 + split].copy_from_slice(&data[..split]);
+
+    let mut reports = vec![init];
+    for (seq, chunk) in data[split..].chunks(CONT_DATA_LEN).enumerate() {
+        let mut cont = [0u8; REPORT_LEN];
+        cont[..4].copy_from_slice(&cid.to_be_bytes());
+        cont[4] = seq as u8;
+        cont[5..5 + chunk.len()].copy_from_slice(chunk);
+        reports.push(cont);
+    }
+    Ok(reports)
+}
+
+/// A message being reassembled from reports
+#[derive(Default)]
+struct Message {
+    cmd: u8,
+    len: usize,
+    seq: u8,
+    data: Vec<u8>,
+}
+
+impl Message {
+    /// Adds a report, returning the command and payload once the message is complete.
+    /// Reports for other channels are ignored
+    ///
+    /// # Arguments
+    /// * `cid` - Channel id the message is expected on
+    /// * `report` - Report read from the device
+    fn push(
+        &mut self,
+        cid: u32,
+        report: &[u8; REPORT_LEN],
+    ) -> Result<Option<(u8, Vec<u8>)>, CtapError> {
+        if report[..4] != cid.to_be_bytes() {
+            return Ok(None);
+        }
+
+        if report[4] & 0x80 != 0 {
+            self.cmd = report[4] & 0x7f;
+            self.len = u16::from_be_bytes([report[5], report[6]]) as usize;
+            self.seq = 0;
+            self.data.clear();
+            self.data
+                .extend_from_slice(&report[7..7 + self.len.min(INIT_DATA_LEN)]);
+        } else if self.cmd == 0 || report[4] != self.seq {
+            return Err(CtapError::Malformed("out of sequence CTAPHID packet"));
+        } else {
+            let remaining = self.len - self.data.len();
+            self.data
+                .extend_from_slice(&report[5..5 + remaining.min(CONT_DATA_LEN)]);
+            self.seq += 1;
+        }
+
+        if self.data.len() == self.len {
+            Ok(Some((self.cmd, std::mem::take(&mut self.data))))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Answers channel allocation with channel `0xcafef00d` and reads from a queue
+    #[derive(Default)]
+    struct Fake {
+        queued: VecDeque<[u8; REPORT_LEN]>,
+    }
+
+    impl HidDevice for Fake {
+        fn write_report(&mut self, report: &[u8; REPORT_LEN]) -> io::Result<()> {
+            if report[..5] == [0xff, 0xff, 0xff, 0xff, CTAPHID_INIT | 0x80] {
+                // an answer to another client's allocation comes first
+                let mut other = vec![0; 8];
+                let mut ours = report[7..15].to_vec();
+                for init in [&mut other, &mut ours].iter_mut() {
+                    init.extend_from_slice(&[0xca, 0xfe, 0xf0, 0x0d, 2, 1, 0, 0, 0x04]);
+                    self.queued
+                        .extend(packets(BROADCAST_CID, CTAPHID_INIT, init).unwrap());
+                }
+            }
+            Ok(())
+        }
+
+        fn read_report(&mut self, _: Duration) -> io::Result<[u8; REPORT_LEN]> {
+            self.queued
+                .pop_front()
+                .ok_or_else(|| io::ErrorKind::TimedOut.into())
+        }
+    }
+
+    fn reassemble(cid: u32, reports: &[[u8; REPORT_LEN]]) -> (u8, Vec<u8>) {
+        let mut message = Message::default();
+        for report in reports {
+            if let Some(done) = message.push(cid, report).unwrap() {
+                return done;
+            }
+        }
+        panic!("message incomplete");
+    }
+
+    #[test]
+    fn fragment_and_reassemble() {
+        let data: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let reports = packets(0x01020304, CTAPHID_CBOR, &data).unwrap();
+        assert_eq!(reports.len(), 4);
+        assert_eq!(&reports[0][..7], &[1, 2, 3, 4, 0x90, 0, 200]);
+        assert_eq!(reports[2][4], 1);
+        assert_eq!(reassemble(0x01020304, &reports), (CTAPHID_CBOR, data));
+
+        assert_eq!(
+            reassemble(7, &packets(7, CTAPHID_CBOR, &[]).unwrap()),
+            (CTAPHID_CBOR, vec![])
+        );
+        assert!(packets(7, CTAPHID_CBOR, &[0; 7610]).is_err());
+    }
+
+    #[test]
+    fn allocate_channel_and_transact() {
+        let mut hid = Hid::new(Fake::default()).unwrap();
+        assert_eq!(hid.channel(), 0xcafef00d);
+
+        // keep-alives are skipped, and other channels ignored
+        let queue = |hid: &mut Hid<Fake>, cid, cmd, data: &[u8]| {
+            hid.device.queued.extend(packets(cid, cmd, data).unwrap())
+        };
+        queue(&mut hid, 0xcafef00d, CTAPHID_KEEPALIVE, &[1]);
+        queue(&mut hid, 0x11111111, CTAPHID_CBOR, &[9]);
+        queue(&mut hid, 0xcafef00d, CTAPHID_CBOR, &[0, 0xa0]);
+        assert_eq!(hid.cbor(&[0x04]).unwrap(), vec![0, 0xa0]);
+
+        queue(&mut hid, 0xcafef00d, CTAPHID_ERROR, &[0x06]);
+        assert!(matches!(hid.cbor(&[0x04]), Err(CtapError::Status(0x06))));
+        assert!(matches!(hid.cbor(&[0x04]), Err(CtapError::Io(_))));
+    }
+}
@@ -0,0 +1,420 @@
+//! Large blobs (CTAP 2.1)
+//!
+//! Authenticators supporting `authenticatorLargeBlobs` store a single CBOR array, the
+//! large-blob array, that anyone can read and PIN token holders with the
+//! `PERMISSION_LARGE_BLOB_WRITE` permission can replace.  Each entry holds data for one
+//! credential: DEFLATE compressed, then encrypted with AES-256-GCM under the credential's
+//! `largeBlobKey`, which the authenticator only reveals to clients that request it with
+//! the `largeBlobKey` extension (see [`Authenticator::large_blob_key`]).  Entries that
+//! don't decrypt under a key belong to other credentials and are left untouched.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::ctap::pin::PERMISSION_LARGE_BLOB_WRITE;
+//!
+//! let key_for_blob = key.large_blob_key("example.com", &credential_id)?;
+//! let token = key.pin_token("1234", PERMISSION_LARGE_BLOB_WRITE, None)?;
+//! key.write_large_blob(&token, &key_for_blob, &certificate)?;
+//! assert_eq!(key.read_large_blob(&key_for_blob)?, Some(certificate));
+//! ```
+
+use super::{
+    bytes,
+    deflate::{deflate, inflate},
+    messages::{CredentialDescriptor, GetAssertionRequest, GetAssertionResponse, Options},
+    pin::PinUvAuthToken,
+    Authenticator, CtapError, Transport, GET_ASSERTION,
+};
+use rand::RngCore;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM},
+    digest::{digest, SHA256},
+};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+/// Command byte of `authenticatorLargeBlobs`
+const LARGE_BLOBS: u8 = 0x0c;
+
+/// Largest message an authenticator accepts when it does not say
+const DEFAULT_MAX_MSG_SIZE: u64 = 1024;
+
+/// Length of the truncated hash that ends a serialized large-blob array
+const HASH_LEN: usize = 16;
+
+impl<T: Transport> Authenticator<T> {
+    /// Asks the authenticator for the `largeBlobKey` of a credential, the key its large
+    /// blob is encrypted with.  The user is not asked to touch the authenticator, but a
+    /// PIN token set with `unlock()` or `set_pin_token()` is used if present
+    ///
+    /// # Arguments
+    /// * `rp_id` - Relying party the credential belongs to
+    /// * `credential_id` - Id of the credential
+    pub fn large_blob_key(
+        &mut self,
+        rp_id: &str,
+        credential_id: &[u8],
+    ) -> Result<Vec<u8>, CtapError> {
+        let mut client_data_hash = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut client_data_hash);
+
+        let mut req = GetAssertionRequest {
+            allow_list: vec![CredentialDescriptor::new(credential_id.to_vec())],
+            options: Options {
+                up: Some(false),
+                ..Default::default()
+            },
+            ..GetAssertionRequest::new(rp_id, client_data_hash.clone())
+        };
+        req.extensions
+            .insert("largeBlobKey".to_owned(), Value::Bool(true));
+        if let Some(ref token) = self.pin_token {
+            req.pin_uv_auth_param = Some(token.authenticate(&client_data_hash));
+            req.pin_uv_auth_protocol = Some(token.protocol().number().into());
+        }
+
+        let assertion: GetAssertionResponse = self.call(GET_ASSERTION, &req)?;
+        assertion
+            .large_blob_key
+            .ok_or(CtapError::Malformed("credential has no large blob key"))
+    }
+
+    /// Reads the large-blob array.  An array that fails its integrity check reads as
+    /// empty, as CTAP 2.1 requires
+    pub fn read_large_blob_array(&mut self) -> Result<Vec<Value>, CtapError> {
+        let fragment = self.max_fragment_len()?;
+        let mut serialized = vec![];
+        loop {
+            let mut params = BTreeMap::new();
+            params.insert(Value::Integer(0x01), Value::Integer(fragment as i128));
+            params.insert(
+                Value::Integer(0x03),
+                Value::Integer(serialized.len() as i128),
+            );
+
+            let response = self.command(LARGE_BLOBS, Some(params))?;
+            let chunk = bytes(&response, 0x01).ok_or(CtapError::Malformed("missing large blob"))?;
+            serialized.extend_from_slice(&chunk);
+            if chunk.len() < fragment {
+                break;
+            }
+        }
+
+        if serialized.len() < HASH_LEN + 1 {
+            return Ok(vec![]);
+        }
+        let (array, hash) = serialized.split_at(serialized.len() - HASH_LEN);
+        if &digest(&SHA256, array).as_ref()[..HASH_LEN] != hash {
+            return Ok(vec![]);
+        }
+        match serde_cbor::from_slice(array) {
+            Ok(Value::Array(entries)) => Ok(entries),
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Replaces the large-blob array
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the large blob write permission
+    /// * `entries` - Entries of the new array
+    pub fn write_large_blob_array(
+        &mut self,
+        token: &PinUvAuthToken,
+        entries: &[Value],
+    ) -> Result<(), CtapError> {
+        let mut serialized = serde_cbor::to_vec(&Value::Array(entries.to_vec()))?;
+        let hash = digest(&SHA256, &serialized);
+        serialized.extend_from_slice(&hash.as_ref()[..HASH_LEN]);
+
+        let fragment = self.max_fragment_len()?;
+        for (i, chunk) in serialized.chunks(fragment).enumerate() {
+            let offset = i * fragment;
+
+            // 32 x 0xff || command || 0x00 || offset (little endian) || SHA-256(chunk)
+            let mut message = vec![0xff; 32];
+            message.extend_from_slice(&[LARGE_BLOBS, 0x00]);
+            message.extend_from_slice(&(offset as u32).to_le_bytes());
+            message.extend_from_slice(digest(&SHA256, chunk).as_ref());
+
+            let mut params = BTreeMap::new();
+            params.insert(Value::Integer(0x02), Value::Bytes(chunk.to_vec()));
+            params.insert(Value::Integer(0x03), Value::Integer(offset as i128));
+            if offset == 0 {
+                params.insert(
+                    Value::Integer(0x04),
+                    Value::Integer(serialized.len() as i128),
+                );
+            }
+            params.insert(
+                Value::Integer(0x05),
+                Value::Bytes(token.authenticate(&message)),
+            );
+            params.insert(
+                Value::Integer(0x06),
+                Value::Integer(token.protocol().number().into()),
+            );
+            self.command(LARGE_BLOBS, Some(params))?;
+        }
+        Ok(())
+    }
+
+    /// Reads the large blob of a credential, if it has one
+    ///
+    /// # Arguments
+    /// * `key` - The credential's `largeBlobKey`
+    pub fn read_large_blob(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>, CtapError> {
+        for entry in self.read_large_blob_array()? {
+            if let Some(data) = open(key, &entry)? {
+                return Ok(Some(data));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Stores the large blob of a credential, replacing any it had
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the large blob write permission
+    /// * `key` - The credential's `largeBlobKey`
+    /// * `data` - Data to store
+    pub fn write_large_blob(
+        &mut self,
+        token: &PinUvAuthToken,
+        key: &[u8],
+        data: &[u8],
+    ) -> Result<(), CtapError> {
+        let mut entries = self.others(key)?;
+        entries.push(seal(key, data)?);
+        self.write_large_blob_array(token, &entries)
+    }
+
+    /// Removes the large blob of a credential
+    ///
+    /// # Arguments
+    /// * `token` - PIN token with the large blob write permission
+    /// * `key` - The credential's `largeBlobKey`
+    pub fn delete_large_blob(
+        &mut self,
+        token: &PinUvAuthToken,
+        key: &[u8],
+    ) -> Result<(), CtapError> {
+        let entries = self.others(key)?;
+        self.write_large_blob_array(token, &entries)
+    }
+
+    /// Returns the entries of the large-blob array that belong to other credentials
+    fn others(&mut self, key: &[u8]) -> Result<Vec<Value>, CtapError> {
+        let mut others = vec![];
+        for entry in self.read_large_blob_array()? {
+            if open(key, &entry)?.is_none() {
+                others.push(entry);
+            }
+        }
+        Ok(others)
+    }
+
+    /// Returns the longest fragment of the large-blob array sent in one message
+    fn max_fragment_len(&mut self) -> Result<usize, CtapError> {
+        let info = self.info()?;
+        if !info.options.contains_key("largeBlobs") {
+            return Err(CtapError::Malformed(
+                "authenticator does not support large blobs",
+            ));
+        }
+        Ok((info.max_msg_size.unwrap_or(DEFAULT_MAX_MSG_SIZE) - 64) as usize)
+    }
+}
+
+/// Compresses and encrypts data into a large-blob array entry
+///
+/// # Arguments
+/// * `key` - The credential's `largeBlobKey`
+/// * `data` - Data to store
+pub fn seal(key: &[u8], data: &[u8]) -> Result<Value, CtapError> {
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let mut ciphertext = deflate(data);
+    cipher(key)?
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(associated_data(data.len() as u64)),
+            &mut ciphertext,
+        )
+        .map_err(|_| CtapError::Malformed("failed to encrypt large blob"))?;
+
+    let mut entry = BTreeMap::new();
+    entry.insert(Value::Integer(0x01), Value::Bytes(ciphertext));
+    entry.insert(Value::Integer(0x02), Value::Bytes(nonce.to_vec()));
+    entry.insert(Value::Integer(0x03), Value::Integer(data.len() as i128));
+    Ok(Value::Map(entry))
+}
+
+/// Decrypts and decompresses a large-blob array entry, returning `None` if it belongs
+/// to another credential
+///
+/// # Arguments
+/// * `key` - The credential's `largeBlobKey`
+/// * `entry` - Entry of the large-blob array
+pub fn open(key: &[u8], entry: &Value) -> Result<Option<Vec<u8>>, CtapError> {
+    let entry = match entry {
+        Value::Map(entry) => entry,
+        _ => return Ok(None),
+    };
+    let (mut ciphertext, nonce, orig_size) = match (
+        bytes(entry, 0x01),
+        bytes(entry, 0x02),
+        entry.get(&Value::Integer(0x03)),
+    ) {
+        (Some(c), Some(n), Some(Value::Integer(size))) if n.len() == 12 && *size >= 0 => {
+            let mut nonce = [0u8; 12];
+            nonce.copy_from_slice(&n);
+            (c, nonce, *size as u64)
+        }
+        _ => return Ok(None),
+    };
+
+    let plaintext = match cipher(key)?.open_in_place(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(associated_data(orig_size)),
+        &mut ciphertext,
+    ) {
+        Ok(plaintext) => plaintext,
+        Err(_) => return Ok(None),
+    };
+
+    let data = inflate(plaintext)?;
+    if data.len() as u64 != orig_size {
+        return Err(CtapError::Malformed("large blob has the wrong size"));
+    }
+    Ok(Some(data))
+}
+
+/// Builds the AES-256-GCM key of a credential's large blob
+fn cipher(key: &[u8]) -> Result<LessSafeKey, CtapError> {
+    UnboundKey::new(&AES_256_GCM, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| CtapError::Malformed("large blob keys are 32 bytes"))
+}
+
+/// Associated data of an entry: "blob" || uint64LE(origSize)
+fn associated_data(orig_size: u64) -> [u8; 12] {
+    let mut ad = [0u8; 12];
+    ad[..4].copy_from_slice(b"blob");
+    ad[4..].copy_from_slice(&orig_size.to_le_bytes());
+    ad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::ctap::{
+        pin::PinUvAuthProtocol,
+        tests::{answer, canned_info, Script},
+        STATUS_OK,
+    };
+
+    /// Keeps a large-blob array, the way an authenticator would
+    struct Blobs {
+        stored: Vec<u8>,
+        pending: Vec<u8>,
+        expected: usize,
+        token: PinUvAuthToken,
+    }
+
+    impl Transport for Blobs {
+        fn cbor(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError> {
+            if request[0] == 0x04 {
+                return Ok(canned_info(vec![("largeBlobs", true)], vec![]));
+            }
+
+            let params: BTreeMap<Value, Value> = serde_cbor::from_slice(&request[1..]).unwrap();
+            let offset = match params.get(&Value::Integer(0x03)) {
+                Some(Value::Integer(offset)) => *offset as usize,
+                _ => panic!("missing offset"),
+            };
+            if let Some(Value::Integer(get)) = params.get(&Value::Integer(0x01)) {
+                let end = (offset + *get as usize).min(self.stored.len());
+                return Ok(answer(vec![(
+                    0x01,
+                    Value::Bytes(self.stored[offset..end].to_vec()),
+                )]));
+            }
+
+            let chunk = bytes(&params, 0x02).unwrap();
+            let mut message = vec![0xff; 32];
+            message.extend_from_slice(&[LARGE_BLOBS, 0x00]);
+            message.extend_from_slice(&(offset as u32).to_le_bytes());
+            message.extend_from_slice(digest(&SHA256, &chunk).as_ref());
+            assert_eq!(
+                bytes(&params, 0x05),
+                Some(self.token.authenticate(&message))
+            );
+
+            if let Some(Value::Integer(len)) = params.get(&Value::Integer(0x04)) {
+                self.pending.clear();
+                self.expected = *len as usize;
+            }
+            assert_eq!(offset, self.pending.len());
+            self.pending.extend_from_slice(&chunk);
+            if self.pending.len() == self.expected {
+                self.stored = self.pending.clone();
+            }
+            Ok(vec![STATUS_OK])
+        }
+    }
+
+    #[test]
+    fn write_and_read() {
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::Two, vec![5; 32]);
+        // the initial large-blob array: an empty array and its truncated hash
+        let mut stored = vec![0x80];
+        stored.extend_from_slice(&digest(&SHA256, &[0x80]).as_ref()[..HASH_LEN]);
+        let mut key = Authenticator::new(Blobs {
+            stored,
+            pending: vec![],
+            expected: 0,
+            token: token.clone(),
+        });
+        assert!(key.read_large_blob_array().unwrap().is_empty());
+
+        let (alice, bob) = ([1u8; 32], [2u8; 32]);
+        // spans two fragments of 960 bytes once encrypted
+        let certificate: Vec<u8> = (0..1500u32).map(|i| (i * 7919 % 251) as u8).collect();
+        key.write_large_blob(&token, &alice, &certificate).unwrap();
+        key.write_large_blob(&token, &bob, b"bob").unwrap();
+        key.write_large_blob(&token, &bob, b"bob, again").unwrap();
+
+        assert_eq!(key.read_large_blob_array().unwrap().len(), 2);
+        assert_eq!(key.read_large_blob(&alice).unwrap(), Some(certificate));
+        assert_eq!(
+            key.read_large_blob(&bob).unwrap().as_deref(),
+            Some(&b"bob, again"[..])
+        );
+
+        key.delete_large_blob(&token, &alice).unwrap();
+        assert_eq!(key.read_large_blob(&alice).unwrap(), None);
+    }
+
+    #[test]
+    fn large_blob_key() {
+        let mut script = Script::default();
+        script.answers.push_back(answer(vec![
+            (0x02, Value::Bytes(vec![0; 37])),
+            (0x03, Value::Bytes(vec![9; 70])),
+            (0x07, Value::Bytes(vec![3; 32])),
+        ]));
+        let mut key = Authenticator::new(script);
+        assert_eq!(
+            key.large_blob_key("example.com", &[1, 2, 3]).unwrap(),
+            vec![3; 32]
+        );
+
+        let requests = key.into_inner().requests;
+        let req: GetAssertionRequest = serde_cbor::from_slice(&requests[0][1..]).unwrap();
+        assert_eq!(req.extensions.get("largeBlobKey"), Some(&Value::Bool(true)));
+        assert_eq!(req.options.up, Some(false));
+    }
+}
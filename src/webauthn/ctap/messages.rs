@@ -0,0 +1,740 @@
+//! CBOR structures of `authenticatorMakeCredential` and `authenticatorGetAssertion`
+//!
+//! CTAP2 encodes command parameters and responses as CBOR maps keyed by small integers
+//! (and their nested entities as maps keyed by text).  The types in this module implement
+//! `Serialize` and `Deserialize` with exactly that encoding, so they can be read and
+//! written with `serde_cbor` by tooling that speaks to authenticators (or implements one)
+//! over any transport.  Members that are absent or empty are omitted, as CTAP2 requires.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::ctap::messages::{GetAssertionRequest, GetAssertionResponse};
+//!
+//! let req = GetAssertionRequest::new("example.com", client_data_hash);
+//! let request = [&[0x02][..], &serde_cbor::to_vec(&req)?].concat();
+//! let response = transport.cbor(&request)?;
+//! let assertion: GetAssertionResponse = serde_cbor::from_slice(&response[1..])?;
+//! ```
+
+use super::CtapError;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+/// Conversion between a structure and the CBOR value representing it
+trait Cbor: Sized {
+    /// Encodes the structure
+    fn to_value(&self) -> Value;
+
+    /// Decodes the structure
+    ///
+    /// # Arguments
+    /// * `value` - CBOR value to decode
+    fn from_value(value: Value) -> Result<Self, CtapError>;
+}
+
+/// Implements `Serialize` and `Deserialize` through a type's CBOR value
+macro_rules! serde_via_cbor {
+    ($($ty:ty),*) => {
+        $(
+            impl Serialize for $ty {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    self.to_value().serialize(serializer)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $ty {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    Self::from_value(Value::deserialize(deserializer)?).map_err(de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+serde_via_cbor!(
+    RelyingPartyEntity,
+    UserEntity,
+    CredentialParameters,
+    CredentialDescriptor,
+    Options,
+    MakeCredentialRequest,
+    MakeCredentialResponse,
+    GetAssertionRequest,
+    GetAssertionResponse
+);
+
+/// `PublicKeyCredentialRpEntity`, the relying party a credential is scoped to
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RelyingPartyEntity {
+    /// Relying party id (e.g., `example.com`)
+    pub id: String,
+
+    /// Human-readable name of the relying party
+    pub name: Option<String>,
+}
+
+/// `PublicKeyCredentialUserEntity`, the account a credential belongs to
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserEntity {
+    /// User handle
+    pub id: Vec<u8>,
+
+    /// Account name (e.g., an email address)
+    pub name: Option<String>,
+
+    /// Human-readable name of the user
+    pub display_name: Option<String>,
+}
+
+/// `PublicKeyCredentialParameters`, a credential type and algorithm accepted by the
+/// relying party
+#[derive(Clone, Debug, PartialEq)]
+pub struct CredentialParameters {
+    /// COSE algorithm identifier (e.g., -7 for ES256)
+    pub alg: i64,
+
+    /// Credential type, always `public-key`
+    pub ty: String,
+}
+
+impl CredentialParameters {
+    /// Creates parameters for a public key credential
+    ///
+    /// # Arguments
+    /// * `alg` - COSE algorithm identifier
+    pub fn new(alg: i64) -> CredentialParameters {
+        CredentialParameters {
+            alg,
+            ty: "public-key".to_owned(),
+        }
+    }
+}
+
+/// `PublicKeyCredentialDescriptor`, a reference to an existing credential
+#[derive(Clone, Debug, PartialEq)]
+pub struct CredentialDescriptor {
+    /// Credential id
+    pub id: Vec<u8>,
+
+    /// Credential type, always `public-key`
+    pub ty: String,
+
+    /// Transports the authenticator holding the credential can be reached with
+    pub transports: Vec<String>,
+}
+
+impl CredentialDescriptor {
+    /// Creates a descriptor for a public key credential
+    ///
+    /// # Arguments
+    /// * `id` - Credential id
+    pub fn new(id: Vec<u8>) -> CredentialDescriptor {
+        CredentialDescriptor {
+            id,
+            ty: "public-key".to_owned(),
+            transports: vec![],
+        }
+    }
+}
+
+/// Options of a command, left for the authenticator to decide when absent
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Options {
+    /// Create a discoverable (resident) credential
+    pub rk: Option<bool>,
+
+    /// Require user presence
+    pub up: Option<bool>,
+
+    /// Require built-in user verification
+    pub uv: Option<bool>,
+}
+
+impl Options {
+    /// Returns true if no option is set
+    fn is_empty(&self) -> bool {
+        self.rk.is_none() && self.up.is_none() && self.uv.is_none()
+    }
+}
+
+/// Parameters of `authenticatorMakeCredential`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MakeCredentialRequest {
+    /// SHA-256 hash of the serialized client data
+    pub client_data_hash: Vec<u8>,
+
+    /// Relying party the credential is scoped to
+    pub rp: RelyingPartyEntity,
+
+    /// Account the credential belongs to
+    pub user: UserEntity,
+
+    /// Accepted credential types and algorithms, most preferred first
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+
+    /// Credentials that must not already exist on the authenticator
+    pub exclude_list: Vec<CredentialDescriptor>,
+
+    /// Authenticator extension inputs, by extension identifier
+    pub extensions: BTreeMap<String, Value>,
+
+    /// Options of the command
+    pub options: Options,
+
+    /// Result of authenticating the client data hash with a PIN/UV auth token
+    pub pin_uv_auth_param: Option<Vec<u8>>,
+
+    /// PIN/UV auth protocol the token belongs to
+    pub pin_uv_auth_protocol: Option<u64>,
+
+    /// Kind of enterprise attestation requested
+    pub enterprise_attestation: Option<u64>,
+}
+
+/// Response of `authenticatorMakeCredential`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MakeCredentialResponse {
+    /// Attestation statement format (e.g., `packed`, `fido-u2f`)
+    pub fmt: String,
+
+    /// Authenticator data, including the attested credential data
+    pub auth_data: Vec<u8>,
+
+    /// Attestation statement, in the format named by `fmt`
+    pub att_stmt: Value,
+
+    /// Whether an enterprise attestation was returned
+    pub ep_att: Option<bool>,
+
+    /// Key of the credential's large blob, if requested
+    pub large_blob_key: Option<Vec<u8>>,
+}
+
+impl MakeCredentialResponse {
+    /// Encodes the WebAuthn attestation object (text keyed `fmt`, `attStmt` and
+    /// `authData`) a browser would return
+    pub fn attestation_object(&self) -> Result<Vec<u8>, CtapError> {
+        let mut object = Builder::default();
+        object.set("fmt", &self.fmt);
+        object.set("attStmt", &self.att_stmt);
+        object.set("authData", &self.auth_data);
+        Ok(serde_cbor::to_vec(&object.build())?)
+    }
+}
+
+/// Parameters of `authenticatorGetAssertion`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GetAssertionRequest {
+    /// Relying party id
+    pub rp_id: String,
+
+    /// SHA-256 hash of the serialized client data
+    pub client_data_hash: Vec<u8>,
+
+    /// Credentials acceptable to the relying party, any discoverable credential if empty
+    pub allow_list: Vec<CredentialDescriptor>,
+
+    /// Authenticator extension inputs, by extension identifier
+    pub extensions: BTreeMap<String, Value>,
+
+    /// Options of the command
+    pub options: Options,
+
+    /// Result of authenticating the client data hash with a PIN/UV auth token
+    pub pin_uv_auth_param: Option<Vec<u8>>,
+
+    /// PIN/UV auth protocol the token belongs to
+    pub pin_uv_auth_protocol: Option<u64>,
+}
+
+impl GetAssertionRequest {
+    /// Creates a request for any credential of a relying party
+    ///
+    /// # Arguments
+    /// * `rp_id` - Relying party id
+    /// * `client_data_hash` - SHA-256 hash of the serialized client data
+    pub fn new<S: Into<String>>(rp_id: S, client_data_hash: Vec<u8>) -> GetAssertionRequest {
+        GetAssertionRequest {
+            rp_id: rp_id.into(),
+            client_data_hash,
+            ..Default::default()
+        }
+    }
+}
+
+/// Response of `authenticatorGetAssertion`
+#[derive(Clone, Debug, PartialEq)]
+pub struct GetAssertionResponse {
+    /// Credential used, may be omitted when the allow list named exactly one
+    pub credential: Option<CredentialDescriptor>,
+
+    /// Authenticator data
+    pub auth_data: Vec<u8>,
+
+    /// Signature over the authenticator data and client data hash
+    pub signature: Vec<u8>,
+
+    /// Account the credential belongs to, for discoverable credentials
+    pub user: Option<UserEntity>,
+
+    /// Number of discoverable credentials matching the request
+    pub number_of_credentials: Option<u64>,
+
+    /// Whether the user picked the credential on the authenticator
+    pub user_selected: Option<bool>,
+
+    /// Key of the credential's large blob, if requested
+    pub large_blob_key: Option<Vec<u8>>,
+}
+
+impl Cbor for RelyingPartyEntity {
+    fn to_value(&self) -> Value {
+        let mut map = Builder::default();
+        map.set("id", &self.id);
+        map.optional("name", &self.name);
+        map.build()
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        let mut map = Members::new(value)?;
+        Ok(RelyingPartyEntity {
+            id: map.required("id", "relying party has no id")?,
+            name: map.optional("name")?,
+        })
+    }
+}
+
+impl Cbor for UserEntity {
+    fn to_value(&self) -> Value {
+        let mut map = Builder::default();
+        map.set("id", &self.id);
+        map.optional("name", &self.name);
+        map.optional("displayName", &self.display_name);
+        map.build()
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        let mut map = Members::new(value)?;
+        Ok(UserEntity {
+            id: map.required("id", "user has no id")?,
+            name: map.optional("name")?,
+            display_name: map.optional("displayName")?,
+        })
+    }
+}
+
+impl Cbor for CredentialParameters {
+    fn to_value(&self) -> Value {
+        let mut map = Builder::default();
+        map.set("alg", &self.alg);
+        map.set("type", &self.ty);
+        map.build()
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        let mut map = Members::new(value)?;
+        Ok(CredentialParameters {
+            alg: map.required("alg", "credential parameters have no algorithm")?,
+            ty: map.required("type", "credential parameters have no type")?,
+        })
+    }
+}
+
+impl Cbor for CredentialDescriptor {
+    fn to_value(&self) -> Value {
+        let mut map = Builder::default();
+        map.set("id", &self.id);
+        map.set("type", &self.ty);
+        map.array("transports", &self.transports);
+        map.build()
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        let mut map = Members::new(value)?;
+        Ok(CredentialDescriptor {
+            id: map.required("id", "credential has no id")?,
+            ty: map.required("type", "credential has no type")?,
+            transports: map.array("transports")?,
+        })
+    }
+}
+
+impl Cbor for Options {
+    fn to_value(&self) -> Value {
+        let mut map = Builder::default();
+        map.optional("rk", &self.rk);
+        map.optional("up", &self.up);
+        map.optional("uv", &self.uv);
+        map.build()
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        let mut map = Members::new(value)?;
+        Ok(Options {
+            rk: map.optional("rk")?,
+            up: map.optional("up")?,
+            uv: map.optional("uv")?,
+        })
+    }
+}
+
+impl Cbor for MakeCredentialRequest {
+    fn to_value(&self) -> Value {
+        let mut map = Builder::default();
+        map.set(0x01, &self.client_data_hash);
+        map.set(0x02, &self.rp);
+        map.set(0x03, &self.user);
+        map.array(0x04, &self.pub_key_cred_params);
+        map.array(0x05, &self.exclude_list);
+        if !self.extensions.is_empty() {
+            map.set(0x06, &self.extensions);
+        }
+        if !self.options.is_empty() {
+            map.set(0x07, &self.options);
+        }
+        map.optional(0x08, &self.pin_uv_auth_param);
+        map.optional(0x09, &self.pin_uv_auth_protocol);
+        map.optional(0x0a, &self.enterprise_attestation);
+        map.build()
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        let mut map = Members::new(value)?;
+        Ok(MakeCredentialRequest {
+            client_data_hash: map.required(0x01, "missing client data hash")?,
+            rp: map.required(0x02, "missing relying party")?,
+            user: map.required(0x03, "missing user")?,
+            pub_key_cred_params: map.array(0x04)?,
+            exclude_list: map.array(0x05)?,
+            extensions: map.optional(0x06)?.unwrap_or_default(),
+            options: map.optional(0x07)?.unwrap_or_default(),
+            pin_uv_auth_param: map.optional(0x08)?,
+            pin_uv_auth_protocol: map.optional(0x09)?,
+            enterprise_attestation: map.optional(0x0a)?,
+        })
+    }
+}
+
+impl Cbor for MakeCredentialResponse {
+    fn to_value(&self) -> Value {
+        let mut map = Builder::default();
+        map.set(0x01, &self.fmt);
+        map.set(0x02, &self.auth_data);
+        map.set(0x03, &self.att_stmt);
+        map.optional(0x04, &self.ep_att);
+        map.optional(0x05, &self.large_blob_key);
+        map.build()
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        let mut map = Members::new(value)?;
+        Ok(MakeCredentialResponse {
+            fmt: map.required(0x01, "missing attestation format")?,
+            auth_data: map.required(0x02, "missing authenticator data")?,
+            att_stmt: map.required(0x03, "missing attestation statement")?,
+            ep_att: map.optional(0x04)?,
+            large_blob_key: map.optional(0x05)?,
+        })
+    }
+}
+
+impl Cbor for GetAssertionRequest {
+    fn to_value(&self) -> Value {
+        let mut map = Builder::default();
+        map.set(0x01, &self.rp_id);
+        map.set(0x02, &self.client_data_hash);
+        map.array(0x03, &self.allow_list);
+        if !self.extensions.is_empty() {
+            map.set(0x04, &self.extensions);
+        }
+        if !self.options.is_empty() {
+            map.set(0x05, &self.options);
+        }
+        map.optional(0x06, &self.pin_uv_auth_param);
+        map.optional(0x07, &self.pin_uv_auth_protocol);
+        map.build()
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        let mut map = Members::new(value)?;
+        Ok(GetAssertionRequest {
+            rp_id: map.required(0x01, "missing relying party id")?,
+            client_data_hash: map.required(0x02, "missing client data hash")?,
+            allow_list: map.array(0x03)?,
+            extensions: map.optional(0x04)?.unwrap_or_default(),
+            options: map.optional(0x05)?.unwrap_or_default(),
+            pin_uv_auth_param: map.optional(0x06)?,
+            pin_uv_auth_protocol: map.optional(0x07)?,
+        })
+    }
+}
+
+impl Cbor for GetAssertionResponse {
+    fn to_value(&self) -> Value {
+        let mut map = Builder::default();
+        map.optional(0x01, &self.credential);
+        map.set(0x02, &self.auth_data);
+        map.set(0x03, &self.signature);
+        map.optional(0x04, &self.user);
+        map.optional(0x05, &self.number_of_credentials);
+        map.optional(0x06, &self.user_selected);
+        map.optional(0x07, &self.large_blob_key);
+        map.build()
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        let mut map = Members::new(value)?;
+        Ok(GetAssertionResponse {
+            credential: map.optional(0x01)?,
+            auth_data: map.required(0x02, "missing authenticator data")?,
+            signature: map.required(0x03, "missing signature")?,
+            user: map.optional(0x04)?,
+            number_of_credentials: map.optional(0x05)?,
+            user_selected: map.optional(0x06)?,
+            large_blob_key: map.optional(0x07)?,
+        })
+    }
+}
+
+impl Cbor for Value {
+    fn to_value(&self) -> Value {
+        self.clone()
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        Ok(value)
+    }
+}
+
+impl Cbor for Vec<u8> {
+    fn to_value(&self) -> Value {
+        Value::Bytes(self.clone())
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        match value {
+            Value::Bytes(b) => Ok(b),
+            _ => Err(CtapError::Malformed("expected a byte string")),
+        }
+    }
+}
+
+impl Cbor for String {
+    fn to_value(&self) -> Value {
+        Value::Text(self.clone())
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        match value {
+            Value::Text(s) => Ok(s),
+            _ => Err(CtapError::Malformed("expected a text string")),
+        }
+    }
+}
+
+impl Cbor for bool {
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            _ => Err(CtapError::Malformed("expected a boolean")),
+        }
+    }
+}
+
+impl Cbor for u64 {
+    fn to_value(&self) -> Value {
+        Value::Integer((*self).into())
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        match value {
+            Value::Integer(i) if i >= 0 && i <= u64::MAX.into() => Ok(i as u64),
+            _ => Err(CtapError::Malformed("expected an unsigned integer")),
+        }
+    }
+}
+
+impl Cbor for i64 {
+    fn to_value(&self) -> Value {
+        Value::Integer((*self).into())
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        match value {
+            Value::Integer(i) if i >= i64::MIN.into() && i <= i64::MAX.into() => Ok(i as i64),
+            _ => Err(CtapError::Malformed("expected an integer")),
+        }
+    }
+}
+
+impl Cbor for BTreeMap<String, Value> {
+    fn to_value(&self) -> Value {
+        Value::Map(
+            self.iter()
+                .map(|(k, v)| (Value::Text(k.clone()), v.clone()))
+                .collect(),
+        )
+    }
+
+    fn from_value(value: Value) -> Result<Self, CtapError> {
+        Members::new(value)?
+            .0
+            .into_iter()
+            .map(|(k, v)| Ok((String::from_value(k)?, v)))
+            .collect()
+    }
+}
+
+/// Key of a CBOR map member, an integer or a text string
+trait Key {
+    fn key(self) -> Value;
+}
+
+impl Key for i32 {
+    fn key(self) -> Value {
+        Value::Integer(self.into())
+    }
+}
+
+impl Key for &str {
+    fn key(self) -> Value {
+        Value::Text(self.to_owned())
+    }
+}
+
+/// Builds a CBOR map
+#[derive(Default)]
+struct Builder(BTreeMap<Value, Value>);
+
+impl Builder {
+    /// Sets a member
+    fn set<K: Key, T: Cbor>(&mut self, key: K, value: &T) {
+        self.0.insert(key.key(), value.to_value());
+    }
+
+    /// Sets a member if present
+    fn optional<K: Key, T: Cbor>(&mut self, key: K, value: &Option<T>) {
+        if let Some(value) = value {
+            self.set(key, value);
+        }
+    }
+
+    /// Sets an array member unless it is empty
+    fn array<K: Key, T: Cbor>(&mut self, key: K, values: &[T]) {
+        if !values.is_empty() {
+            let values = values.iter().map(Cbor::to_value).collect();
+            self.0.insert(key.key(), Value::Array(values));
+        }
+    }
+
+    fn build(self) -> Value {
+        Value::Map(self.0)
+    }
+}
+
+/// Members of a CBOR map being decoded
+struct Members(BTreeMap<Value, Value>);
+
+impl Members {
+    fn new(value: Value) -> Result<Members, CtapError> {
+        match value {
+            Value::Map(map) => Ok(Members(map)),
+            _ => Err(CtapError::Malformed("expected a map")),
+        }
+    }
+
+    /// Decodes a member if present
+    fn optional<K: Key, T: Cbor>(&mut self, key: K) -> Result<Option<T>, CtapError> {
+        self.0.remove(&key.key()).map(T::from_value).transpose()
+    }
+
+    /// Decodes a member, failing if it is absent
+    fn required<K: Key, T: Cbor>(&mut self, key: K, missing: &'static str) -> Result<T, CtapError> {
+        self.optional(key)?.ok_or(CtapError::Malformed(missing))
+    }
+
+    /// Decodes an array member, empty if absent
+    fn array<K: Key, T: Cbor>(&mut self, key: K) -> Result<Vec<T>, CtapError> {
+        match self.0.remove(&key.key()) {
+            Some(Value::Array(values)) => values.into_iter().map(T::from_value).collect(),
+            Some(_) => Err(CtapError::Malformed("expected an array")),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn make_credential_round_trip() {
+        let req = MakeCredentialRequest {
+            client_data_hash: vec![1; 32],
+            rp: RelyingPartyEntity {
+                id: "example.com".to_owned(),
+                name: Some("Example".to_owned()),
+            },
+            user: UserEntity {
+                id: vec![0, 1, 2, 3],
+                name: Some("user".to_owned()),
+                display_name: None,
+            },
+            pub_key_cred_params: vec![CredentialParameters::new(-7)],
+            extensions: vec![("credProtect".to_owned(), Value::Integer(2))]
+                .into_iter()
+                .collect(),
+            options: Options {
+                rk: Some(true),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let encoded = serde_cbor::to_vec(&req).unwrap();
+        let value: Value = serde_cbor::from_slice(&encoded).unwrap();
+        match value {
+            // absent and empty members are omitted
+            Value::Map(ref map) => assert_eq!(
+                map.keys().cloned().collect::<Vec<_>>(),
+                [1, 2, 3, 4, 6, 7]
+                    .iter()
+                    .map(|&k| Value::Integer(k))
+                    .collect::<Vec<_>>()
+            ),
+            _ => panic!("expected a map"),
+        }
+        assert_eq!(
+            serde_cbor::from_slice::<MakeCredentialRequest>(&encoded).unwrap(),
+            req
+        );
+    }
+
+    #[test]
+    fn get_assertion_response() {
+        let mut user = BTreeMap::new();
+        user.insert(Value::Text("id".to_owned()), Value::Bytes(vec![7]));
+        let mut response = BTreeMap::new();
+        response.insert(Value::Integer(0x02), Value::Bytes(vec![0; 37]));
+        response.insert(Value::Integer(0x03), Value::Bytes(vec![9; 70]));
+        response.insert(Value::Integer(0x04), Value::Map(user));
+        let encoded = serde_cbor::to_vec(&Value::Map(response)).unwrap();
+
+        let assertion: GetAssertionResponse = serde_cbor::from_slice(&encoded).unwrap();
+        assert_eq!(assertion.credential, None);
+        assert_eq!(assertion.user.unwrap().id, vec![7]);
+        assert_eq!(assertion.signature.len(), 70);
+
+        // a response without a signature is rejected
+        let mut response = BTreeMap::new();
+        response.insert(Value::Integer(0x02), Value::Bytes(vec![0; 37]));
+        let encoded = serde_cbor::to_vec(&Value::Map(response)).unwrap();
+        assert!(serde_cbor::from_slice::<GetAssertionResponse>(&encoded).is_err());
+    }
+}
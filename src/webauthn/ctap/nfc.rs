@@ -0,0 +1,204 @@
+//! NFC transport (ISO 7816-4 APDUs)
+//!
+//! Authenticators reached over NFC (or any contactless smart card reader) are selected by
+//! the FIDO applet id, then sent commands wrapped in `NFCCTAP_MSG` APDUs.  Commands too
+//! long for a short APDU are split with command chaining and long responses are
+//! collected with `GET RESPONSE`.  [`Nfc`] exchanges APDUs over any [`Card`], such as a
+//! card connected through PC/SC.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::ctap::{nfc::{Card, Nfc}, Authenticator};
+//!
+//! struct Reader(pcsc::Card);
+//!
+//! impl Card for Reader {
+//!     fn transmit(&mut self, apdu: &[u8]) -> std::io::Result<Vec<u8>> {
+//!         let mut buf = [0u8; pcsc::MAX_BUFFER_SIZE];
+//!         Ok(self.0.transmit(apdu, &mut buf).map_err(to_io)?.to_vec())
+//!     }
+//! }
+//!
+//! let mut key = Authenticator::new(Nfc::new(Reader(card))?);
+//! ```
+
+use super::{CtapError, Transport};
+use std::io;
+
+/// Application id of the FIDO applet
+const FIDO_AID: [u8; 8] = [0xa0, 0x00, 0x00, 0x06, 0x47, 0x2f, 0x00, 0x01];
+
+/// Largest command data carried by one short APDU
+const SHORT_LEN: usize = 0xff;
+
+/// Status word of a successful command
+pub const SW_NO_ERROR: u16 = 0x9000;
+
+/// A smart card (or contactless reader) that exchanges APDUs
+pub trait Card {
+    /// Sends a command APDU and returns the response APDU (data followed by the two
+    /// status bytes)
+    ///
+    /// # Arguments
+    /// * `apdu` - Command APDU
+    fn transmit(&mut self, apdu: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// A connection to the FIDO applet of a card
+pub struct Nfc<C> {
+    card: C,
+    version: String,
+}
+
+impl<C: Card> Nfc<C> {
+    /// Selects the FIDO applet on a card
+    ///
+    /// # Arguments
+    /// * `card` - The authenticator's card connection
+    pub fn new(card: C) -> Result<Nfc<C>, CtapError> {
+        let mut nfc = Nfc {
+            card,
+            version: String::new(),
+        };
+
+        let version = nfc.transmit(0x00, 0xa4, 0x04, &FIDO_AID)?;
+        nfc.version = String::from_utf8_lossy(&version).into_owned();
+        Ok(nfc)
+    }
+
+    /// Returns the version the applet answered selection with, `FIDO_2_0` or `U2F_V2`
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Sends a command, chaining it over several APDUs if needed, and returns the
+    /// complete response data
+    ///
+    /// # Arguments
+    /// * `cla` - Class byte
+    /// * `ins` - Instruction byte
+    /// * `p1` - First parameter
+    /// * `data` - Command data
+    pub fn transmit(
+        &mut self,
+        cla: u8,
+        ins: u8,
+        p1: u8,
+        data: &[u8],
+    ) -> Result<Vec<u8>, CtapError> {
+        let mut chunks = data.chunks(SHORT_LEN).peekable();
+        let mut response = loop {
+            let chunk = chunks.next().unwrap_or_default();
+            let last = chunks.peek().is_none();
+
+            // every chunk but the last is flagged as part of a chain
+            let cla = if last { cla } else { cla | 0x10 };
+            let response = self.card.transmit(&apdu(cla, ins, p1, chunk))?;
+            if last {
+                break response;
+            }
+            status(&response)?;
+        };
+
+        let mut data = vec![];
+        loop {
+            let (body, sw) = split(&response)?;
+            data.extend_from_slice(body);
+            match sw {
+                SW_NO_ERROR => return Ok(data),
+                // more data is available
+                sw if sw >> 8 == 0x61 => {
+                    response = self.card.transmit(&[0x00, 0xc0, 0x00, 0x00, sw as u8])?;
+                }
+                sw => return Err(CtapError::Apdu(sw)),
+            }
+        }
+    }
+}
+
+impl<C: Card> Transport for Nfc<C> {
+    fn cbor(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError> {
+        // NFCCTAP_MSG
+        self.transmit(0x80, 0x10, 0x00, request)
+    }
+
+    fn msg(&mut self, ins: u8, p1: u8, data: &[u8]) -> Result<Vec<u8>, CtapError> {
+        self.transmit(0x00, ins, p1, data)
+    }
+}
+
+/// Encodes a short command APDU expecting up to 256 bytes in response
+fn apdu(cla: u8, ins: u8, p1: u8, data: &[u8]) -> Vec<u8> {
+    let mut apdu = vec![cla, ins, p1, 0x00];
+    if !data.is_empty() {
+        apdu.push(data.len() as u8);
+        apdu.extend_from_slice(data);
+    }
+    apdu.push(0x00);
+    apdu
+}
+
+/// Splits a response APDU into its data and status word
+fn split(response: &[u8]) -> Result<(&[u8], u16), CtapError> {
+    if response.len() < 2 {
+        return Err(CtapError::Malformed("response APDU has no status word"));
+    }
+    let (body, sw) = response.split_at(response.len() - 2);
+    Ok((body, u16::from_be_bytes([sw[0], sw[1]])))
+}
+
+/// Fails unless a response APDU reports success
+fn status(response: &[u8]) -> Result<(), CtapError> {
+    match split(response)?.1 {
+        SW_NO_ERROR => Ok(()),
+        sw => Err(CtapError::Apdu(sw)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Records command APDUs and answers from a queue
+    #[derive(Default)]
+    struct Fake {
+        sent: Vec<Vec<u8>>,
+        answers: VecDeque<Vec<u8>>,
+    }
+
+    impl Card for Fake {
+        fn transmit(&mut self, apdu: &[u8]) -> io::Result<Vec<u8>> {
+            self.sent.push(apdu.to_vec());
+            Ok(self.answers.pop_front().unwrap_or_else(|| vec![0x6d, 0x00]))
+        }
+    }
+
+    #[test]
+    fn select_and_chain() {
+        let mut card = Fake::default();
+        card.answers.push_back(b"FIDO_2_0\x90\x00".to_vec());
+        let mut nfc = Nfc::new(card).unwrap();
+        assert_eq!(nfc.version(), "FIDO_2_0");
+        assert_eq!(
+            nfc.card.sent[0],
+            [&[0x00, 0xa4, 0x04, 0x00, 0x08][..], &FIDO_AID, &[0x00]].concat()
+        );
+
+        // a 300 byte command is chained, a 258 byte response fetched with GET RESPONSE
+        nfc.card.answers.extend(vec![
+            vec![0x90, 0x00],
+            [vec![0; 256], vec![0x61, 0x02]].concat(),
+            vec![1, 2, 0x90, 0x00],
+        ]);
+        let response = nfc.cbor(&[7; 300]).unwrap();
+        assert_eq!(response.len(), 258);
+        assert_eq!(&response[256..], &[1, 2]);
+        assert_eq!(&nfc.card.sent[1][..5], &[0x90, 0x10, 0x00, 0x00, 0xff]);
+        assert_eq!(&nfc.card.sent[2][..5], &[0x80, 0x10, 0x00, 0x00, 45]);
+        assert_eq!(nfc.card.sent[3], vec![0x00, 0xc0, 0x00, 0x00, 0x02]);
+
+        assert!(matches!(nfc.cbor(&[0x04]), Err(CtapError::Apdu(0x6d00))));
+    }
+}
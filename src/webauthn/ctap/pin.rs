@@ -0,0 +1,546 @@
+//! PIN/UV auth protocols
+//!
+//! Authenticators protected by a PIN only create credentials or assertions when the
+//! command carries a `pinUvAuthParam`: a MAC of the client data hash keyed by a PIN token
+//! the authenticator hands out in exchange for the PIN.  The PIN and token travel
+//! encrypted under a secret agreed with ECDH on P-256.  Protocol 1 (CTAP 2.0) hashes the
+//! ECDH result into a single key and encrypts with AES-256-CBC under a zero IV; protocol 2
+//! (CTAP 2.1) derives separate HMAC and AES keys with HKDF and uses random IVs.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut key = Authenticator::new(Hid::new(device)?);
+//! key.unlock("1234")?;
+//! let form = key.make_credential(&req, "https://app.example.com")?;
+//! ```
+
+use super::{Authenticator, CtapError, Transport};
+use aes::{Aes256, BlockCipher, NewBlockCipher};
+use rand::RngCore;
+use ring::{
+    agreement,
+    digest::{digest, SHA256},
+    hkdf, hmac,
+    rand::SystemRandom,
+};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+/// Command byte of `authenticatorClientPIN`
+const CLIENT_PIN: u8 = 0x06;
+
+/// `getPINRetries` subcommand
+const GET_PIN_RETRIES: i128 = 0x01;
+
+/// `getKeyAgreement` subcommand
+const GET_KEY_AGREEMENT: i128 = 0x02;
+
+/// `getPinToken` subcommand (CTAP 2.0)
+const GET_PIN_TOKEN: i128 = 0x05;
+
+/// `getPinUvAuthTokenUsingPinWithPermissions` subcommand (CTAP 2.1)
+const GET_PIN_TOKEN_WITH_PERMISSIONS: i128 = 0x09;
+
+/// Permission to create credentials with a token
+pub const PERMISSION_MAKE_CREDENTIAL: u8 = 0x01;
+
+/// Permission to get assertions with a token
+pub const PERMISSION_GET_ASSERTION: u8 = 0x02;
+
+/// Permission to manage discoverable credentials with a token
+pub const PERMISSION_CREDENTIAL_MANAGEMENT: u8 = 0x04;
+
+/// Permission to enroll biometrics with a token
+pub const PERMISSION_BIO_ENROLLMENT: u8 = 0x08;
+
+/// Permission to write large blobs with a token
+pub const PERMISSION_LARGE_BLOB_WRITE: u8 = 0x10;
+
+/// Permission to configure the authenticator with a token
+pub const PERMISSION_AUTHENTICATOR_CONFIG: u8 = 0x20;
+
+/// A PIN/UV auth protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinUvAuthProtocol {
+    /// pinUvAuthProtocol 1, supported by every authenticator with a PIN
+    One,
+
+    /// pinUvAuthProtocol 2, introduced by CTAP 2.1
+    Two,
+}
+
+impl PinUvAuthProtocol {
+    /// Returns the protocol's number on the wire
+    pub fn number(self) -> u8 {
+        match self {
+            PinUvAuthProtocol::One => 1,
+            PinUvAuthProtocol::Two => 2,
+        }
+    }
+
+    /// Agrees on a shared secret with an authenticator's key agreement key, returning
+    /// the platform's key (to send to the authenticator) and the secret
+    ///
+    /// # Arguments
+    /// * `peer` - The authenticator's COSE key agreement key
+    fn encapsulate(self, peer: &Value) -> Result<(Value, Vec<u8>), CtapError> {
+        let rng = SystemRandom::new();
+        let private = agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, &rng)
+            .map_err(|_| CtapError::Malformed("failed to generate key agreement key"))?;
+        let public = private
+            .compute_public_key()
+            .map_err(|_| CtapError::Malformed("failed to generate key agreement key"))?;
+
+        let peer = agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, cose_to_point(peer)?);
+        let secret = agreement::agree_ephemeral(
+            private,
+            &peer,
+            CtapError::Malformed("invalid key agreement key"),
+            |z| Ok(self.kdf(z)),
+        )?;
+
+        Ok((point_to_cose(public.as_ref()), secret))
+    }
+
+    /// Derives the shared secret from the x-coordinate of the ECDH result
+    fn kdf(self, z: &[u8]) -> Vec<u8> {
+        match self {
+            PinUvAuthProtocol::One => digest(&SHA256, z).as_ref().to_vec(),
+            PinUvAuthProtocol::Two => {
+                let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, &[0; 32]).extract(z);
+                let mut secret = vec![0u8; 64];
+                for (info, out) in [&b"CTAP2 HMAC key"[..], b"CTAP2 AES key"]
+                    .iter()
+                    .zip(secret.chunks_mut(32))
+                {
+                    prk.expand(&[info], hkdf::HKDF_SHA256)
+                        .and_then(|okm| okm.fill(out))
+                        .expect("32 bytes is a valid HKDF-SHA256 output length");
+                }
+                secret
+            }
+        }
+    }
+
+    /// Encrypts a message (a multiple of 16 bytes long) under a shared secret
+    ///
+    /// # Arguments
+    /// * `secret` - Shared secret
+    /// * `plaintext` - Message to encrypt
+    pub(super) fn encrypt(self, secret: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        match self {
+            PinUvAuthProtocol::One => cbc(secret, [0; 16], plaintext, true),
+            PinUvAuthProtocol::Two => {
+                let mut iv = [0u8; 16];
+                rand::thread_rng().fill_bytes(&mut iv);
+                let mut ciphertext = iv.to_vec();
+                ciphertext.extend(cbc(&secret[32..], iv, plaintext, true));
+                ciphertext
+            }
+        }
+    }
+
+    /// Decrypts a message encrypted under a shared secret
+    ///
+    /// # Arguments
+    /// * `secret` - Shared secret
+    /// * `ciphertext` - Message to decrypt
+    pub(super) fn decrypt(self, secret: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, CtapError> {
+        let (key, iv, ciphertext) = match self {
+            PinUvAuthProtocol::One => (secret, [0; 16], ciphertext),
+            PinUvAuthProtocol::Two if ciphertext.len() >= 16 => {
+                let mut iv = [0u8; 16];
+                iv.copy_from_slice(&ciphertext[..16]);
+                (&secret[32..], iv, &ciphertext[16..])
+            }
+            PinUvAuthProtocol::Two => return Err(CtapError::Malformed("ciphertext has no IV")),
+        };
+
+        if !ciphertext.len().is_multiple_of(16) {
+            return Err(CtapError::Malformed(
+                "ciphertext is not a whole number of blocks",
+            ));
+        }
+        Ok(cbc(key, iv, ciphertext, false))
+    }
+
+    /// Computes the MAC of a message, as sent in `pinUvAuthParam`
+    ///
+    /// # Arguments
+    /// * `key` - PIN token or shared secret
+    /// * `message` - Message to authenticate
+    pub(super) fn authenticate(self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        // protocol 2 keys the MAC with the HMAC half of a shared secret
+        let key = match self {
+            PinUvAuthProtocol::Two if key.len() > 32 => &key[..32],
+            _ => key,
+        };
+        let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), message);
+        match self {
+            PinUvAuthProtocol::One => tag.as_ref()[..16].to_vec(),
+            PinUvAuthProtocol::Two => tag.as_ref().to_vec(),
+        }
+    }
+}
+
+/// A PIN token issued by an authenticator
+#[derive(Clone)]
+pub struct PinUvAuthToken {
+    protocol: PinUvAuthProtocol,
+    token: Vec<u8>,
+}
+
+impl PinUvAuthToken {
+    /// Wraps a decrypted token
+    pub(super) fn new(protocol: PinUvAuthProtocol, token: Vec<u8>) -> PinUvAuthToken {
+        PinUvAuthToken { protocol, token }
+    }
+
+    /// Returns the protocol the token was issued with
+    pub fn protocol(&self) -> PinUvAuthProtocol {
+        self.protocol
+    }
+
+    /// Computes the `pinUvAuthParam` proving possession of the token for a message
+    ///
+    /// # Arguments
+    /// * `message` - Message to authenticate (e.g., the client data hash)
+    pub fn authenticate(&self, message: &[u8]) -> Vec<u8> {
+        self.protocol.authenticate(&self.token, message)
+    }
+}
+
+impl<T: Transport> Authenticator<T> {
+    /// Returns how many incorrect PINs may still be entered before the authenticator
+    /// locks
+    pub fn pin_retries(&mut self) -> Result<u32, CtapError> {
+        let protocol = self.pin_protocol()?;
+        let response = self.client_pin(protocol, GET_PIN_RETRIES, vec![])?;
+        match response.get(&Value::Integer(0x03)) {
+            Some(Value::Integer(retries)) => Ok(*retries as u32),
+            _ => Err(CtapError::Malformed("missing PIN retries")),
+        }
+    }
+
+    /// Exchanges the PIN for a PIN token.  Authenticators supporting CTAP 2.1 scope the
+    /// token to a set of permissions (and, optionally, a relying party); older ones
+    /// ignore them
+    ///
+    /// # Arguments
+    /// * `pin` - The authenticator's PIN
+    /// * `permissions` - Permissions requested for the token (`PERMISSION_*` flags)
+    /// * `rp_id` - Relying party the token is restricted to, if any
+    pub fn pin_token(
+        &mut self,
+        pin: &str,
+        permissions: u8,
+        rp_id: Option<&str>,
+    ) -> Result<PinUvAuthToken, CtapError> {
+        let protocol = self.pin_protocol()?;
+        let (platform_key, secret) = self.key_agreement(protocol)?;
+        let pin_hash = digest(&SHA256, pin.as_bytes());
+
+        let mut params = vec![
+            (0x03, platform_key),
+            (
+                0x06,
+                Value::Bytes(protocol.encrypt(&secret, &pin_hash.as_ref()[..16])),
+            ),
+        ];
+        let subcommand = if self.supports_permissions()? {
+            params.push((0x09, Value::Integer(permissions.into())));
+            if let Some(rp_id) = rp_id {
+                params.push((0x0a, Value::Text(rp_id.to_owned())));
+            }
+            GET_PIN_TOKEN_WITH_PERMISSIONS
+        } else {
+            GET_PIN_TOKEN
+        };
+
+        let response = self.client_pin(protocol, subcommand, params)?;
+        let token = match response.get(&Value::Integer(0x02)) {
+            Some(Value::Bytes(token)) => protocol.decrypt(&secret, token)?,
+            _ => return Err(CtapError::Malformed("missing PIN token")),
+        };
+
+        Ok(PinUvAuthToken::new(protocol, token))
+    }
+
+    /// Unlocks the authenticator with its PIN, so following `make_credential()` and
+    /// `get_assertion()` calls are authorized with a PIN token
+    ///
+    /// # Arguments
+    /// * `pin` - The authenticator's PIN
+    pub fn unlock(&mut self, pin: &str) -> Result<(), CtapError> {
+        let token = self.pin_token(
+            pin,
+            PERMISSION_MAKE_CREDENTIAL | PERMISSION_GET_ASSERTION,
+            None,
+        )?;
+        self.pin_token = Some(token);
+        Ok(())
+    }
+
+    /// Authorizes following `make_credential()` and `get_assertion()` calls with a PIN
+    /// token, or stops authorizing them
+    ///
+    /// # Arguments
+    /// * `token` - Token to authorize commands with
+    pub fn set_pin_token(&mut self, token: Option<PinUvAuthToken>) {
+        self.pin_token = token;
+    }
+
+    /// Returns the most preferred protocol supported by both sides
+    fn pin_protocol(&mut self) -> Result<PinUvAuthProtocol, CtapError> {
+        let info = self.info()?;
+        if !info.options.contains_key("clientPin") {
+            return Err(CtapError::Malformed("authenticator does not support a PIN"));
+        }
+
+        // authenticators listing no protocols predate protocol 2
+        Ok(info
+            .pin_uv_auth_protocols
+            .iter()
+            .find_map(|p| match p {
+                1 => Some(PinUvAuthProtocol::One),
+                2 => Some(PinUvAuthProtocol::Two),
+                _ => None,
+            })
+            .unwrap_or(PinUvAuthProtocol::One))
+    }
+
+    /// Returns true if the authenticator issues tokens scoped to permissions
+    fn supports_permissions(&mut self) -> Result<bool, CtapError> {
+        Ok(self.info()?.options.get("pinUvAuthToken") == Some(&true))
+    }
+
+    /// Agrees on a shared secret with the authenticator
+    fn key_agreement(
+        &mut self,
+        protocol: PinUvAuthProtocol,
+    ) -> Result<(Value, Vec<u8>), CtapError> {
+        let response = self.client_pin(protocol, GET_KEY_AGREEMENT, vec![])?;
+        let peer = response
+            .get(&Value::Integer(0x01))
+            .ok_or(CtapError::Malformed("missing key agreement key"))?;
+        protocol.encapsulate(peer)
+    }
+
+    /// Sends an `authenticatorClientPIN` subcommand
+    fn client_pin(
+        &mut self,
+        protocol: PinUvAuthProtocol,
+        subcommand: i128,
+        params: Vec<(i128, Value)>,
+    ) -> Result<BTreeMap<Value, Value>, CtapError> {
+        let mut map = BTreeMap::new();
+        map.insert(
+            Value::Integer(0x01),
+            Value::Integer(protocol.number().into()),
+        );
+        map.insert(Value::Integer(0x02), Value::Integer(subcommand));
+        for (key, value) in params {
+            map.insert(Value::Integer(key), value);
+        }
+        self.command(CLIENT_PIN, Some(map))
+    }
+}
+
+/// Encrypts or decrypts with AES-256-CBC, without padding
+///
+/// # Arguments
+/// * `key` - AES-256 key
+/// * `iv` - Initialization vector
+/// * `data` - Data to transform, a multiple of 16 bytes long
+/// * `encrypt` - True to encrypt, false to decrypt
+fn cbc(key: &[u8], iv: [u8; 16], data: &[u8], encrypt: bool) -> Vec<u8> {
+    let cipher = Aes256::new_varkey(key).expect("shared secrets hold 32 byte AES keys");
+    let mut chain = iv;
+    let mut out = Vec::with_capacity(data.len());
+
+    for block in data.chunks(16) {
+        let mut buf = [0u8; 16];
+        buf.copy_from_slice(block);
+        if encrypt {
+            buf.iter_mut().zip(chain.iter()).for_each(|(b, c)| *b ^= c);
+            cipher.encrypt_block((&mut buf).into());
+            chain = buf;
+        } else {
+            cipher.decrypt_block((&mut buf).into());
+            buf.iter_mut().zip(chain.iter()).for_each(|(b, c)| *b ^= c);
+            chain.copy_from_slice(block);
+        }
+        out.extend_from_slice(&buf);
+    }
+    out
+}
+
+/// Converts an uncompressed P-256 point into a COSE key agreement key
+fn point_to_cose(point: &[u8]) -> Value {
+    let mut key = BTreeMap::new();
+    key.insert(Value::Integer(1), Value::Integer(2));
+    key.insert(Value::Integer(3), Value::Integer(-25));
+    key.insert(Value::Integer(-1), Value::Integer(1));
+    key.insert(Value::Integer(-2), Value::Bytes(point[1..33].to_vec()));
+    key.insert(Value::Integer(-3), Value::Bytes(point[33..65].to_vec()));
+    Value::Map(key)
+}
+
+/// Converts a COSE P-256 key into an uncompressed point
+fn cose_to_point(key: &Value) -> Result<Vec<u8>, CtapError> {
+    let key = match key {
+        Value::Map(key) => key,
+        _ => return Err(CtapError::Malformed("key agreement key is not a map")),
+    };
+    let coordinate = |label| match key.get(&Value::Integer(label)) {
+        Some(Value::Bytes(c)) if c.len() == 32 => Ok(c.as_slice()),
+        _ => Err(CtapError::Malformed("invalid key agreement key")),
+    };
+
+    Ok([&[0x04][..], coordinate(-2)?, coordinate(-3)?].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::ctap::tests::canned_info;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn aes_256_cbc() {
+        // NIST SP 800-38A, F.2.5
+        let key = hex("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4");
+        let mut iv = [0u8; 16];
+        iv.copy_from_slice(&hex("000102030405060708090a0b0c0d0e0f"));
+        let plaintext = hex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51");
+        let ciphertext = cbc(&key, iv, &plaintext, true);
+        assert_eq!(
+            ciphertext,
+            hex("f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d")
+        );
+        assert_eq!(cbc(&key, iv, &ciphertext, false), plaintext);
+
+        // protocol 2 prefixes the IV and keys AES with the second half of the secret
+        let secret = [vec![0; 32], key].concat();
+        let two = PinUvAuthProtocol::Two;
+        assert_eq!(
+            two.decrypt(&secret, &[&iv[..], &ciphertext].concat())
+                .unwrap(),
+            plaintext
+        );
+        let encrypted = two.encrypt(&secret, &plaintext);
+        assert_eq!(encrypted.len(), 48);
+        assert_eq!(two.decrypt(&secret, &encrypted).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn authenticate_lengths() {
+        let one = PinUvAuthProtocol::One.authenticate(&[1; 32], b"hash");
+        let two = PinUvAuthProtocol::Two.authenticate(&[1; 32], b"hash");
+        assert_eq!(one.len(), 16);
+        assert_eq!(two.len(), 32);
+        assert_eq!(&two[..16], &one[..]);
+
+        // only the HMAC half of a protocol 2 shared secret keys the MAC
+        assert_eq!(
+            PinUvAuthProtocol::Two.authenticate(&[[1; 32], [2; 32]].concat(), b"hash"),
+            two
+        );
+    }
+
+    /// The authenticator side of `getKeyAgreement` followed by `getPinToken`
+    struct PinAuthenticator {
+        protocol: PinUvAuthProtocol,
+        private: Option<agreement::EphemeralPrivateKey>,
+        token: Vec<u8>,
+        subcommands: Vec<i128>,
+    }
+
+    impl Transport for PinAuthenticator {
+        fn cbor(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError> {
+            if request[0] == 0x04 {
+                let protocols = vec![Value::Integer(self.protocol.number().into())];
+                return Ok(canned_info(vec![("clientPin", true)], protocols));
+            }
+
+            let params: BTreeMap<Value, Value> = serde_cbor::from_slice(&request[1..])?;
+            let subcommand = match params.get(&Value::Integer(0x02)) {
+                Some(Value::Integer(s)) => *s,
+                _ => panic!("missing subcommand"),
+            };
+            self.subcommands.push(subcommand);
+
+            let mut response = BTreeMap::new();
+            if subcommand == GET_KEY_AGREEMENT {
+                let private = agreement::EphemeralPrivateKey::generate(
+                    &agreement::ECDH_P256,
+                    &SystemRandom::new(),
+                )
+                .unwrap();
+                let public = private.compute_public_key().unwrap();
+                response.insert(Value::Integer(0x01), point_to_cose(public.as_ref()));
+                self.private = Some(private);
+            } else {
+                let platform = cose_to_point(&params[&Value::Integer(0x03)])?;
+                let protocol = self.protocol;
+                let secret = agreement::agree_ephemeral(
+                    self.private.take().unwrap(),
+                    &agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, platform),
+                    (),
+                    |z| Ok(protocol.kdf(z)),
+                )
+                .unwrap();
+
+                let pin_hash = match &params[&Value::Integer(0x06)] {
+                    Value::Bytes(enc) => protocol.decrypt(&secret, enc)?,
+                    _ => panic!("missing pinHashEnc"),
+                };
+                if pin_hash != digest(&SHA256, b"1234").as_ref()[..16] {
+                    return Ok(vec![0x31]);
+                }
+                response.insert(
+                    Value::Integer(0x02),
+                    Value::Bytes(protocol.encrypt(&secret, &self.token)),
+                );
+            }
+
+            let mut answer = vec![0x00];
+            answer.extend(serde_cbor::to_vec(&response)?);
+            Ok(answer)
+        }
+    }
+
+    #[test]
+    fn pin_token() {
+        for protocol in [PinUvAuthProtocol::One, PinUvAuthProtocol::Two].iter() {
+            let mut key = Authenticator::new(PinAuthenticator {
+                protocol: *protocol,
+                private: None,
+                token: vec![7; 32],
+                subcommands: vec![],
+            });
+
+            let token = key
+                .pin_token("1234", PERMISSION_GET_ASSERTION, None)
+                .unwrap();
+            assert_eq!(token.protocol(), *protocol);
+            assert_eq!(token.token, vec![7; 32]);
+            assert_eq!(
+                key.transport.subcommands,
+                vec![GET_KEY_AGREEMENT, GET_PIN_TOKEN]
+            );
+
+            assert!(matches!(
+                key.pin_token("4321", PERMISSION_GET_ASSERTION, None),
+                Err(CtapError::Status(0x31))
+            ));
+        }
+    }
+}
@@ -0,0 +1,1065 @@
+//! Software authenticator for tests
+//!
+//! [`SoftAuthenticator`] answers `authenticatorMakeCredential` and
+//! `authenticatorGetAssertion` like a security key would, holding its P-256 credentials
+//! in memory.  Attestation objects use the `fido-u2f` format, or `packed` when asked,
+//! with a self-signed attestation certificate, and authenticator data carries the relying party id hash,
+//! flags and per-credential signature counters the server checks, so register and login
+//! handlers can be tested end to end without hardware.  Configured as U2F only, it
+//! rejects CTAP2 commands and answers `U2F_REGISTER` and `U2F_AUTHENTICATE` instead, to
+//! exercise the [`u2f`](super::u2f) fallback.  It is not a secure place to keep keys.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::{self, ctap::soft::SoftAuthenticator, RegisterRequest};
+//!
+//! let mut key = SoftAuthenticator::new();
+//! let req = RegisterRequest::new(&config, &user);
+//! let form = key.make_credential(&req, config.origin())?;
+//! let result = webauthn::register(form, &config, req.challenge())?;
+//! ```
+
+use super::{
+    cose_key, map,
+    messages::{
+        CredentialDescriptor, GetAssertionRequest, GetAssertionResponse, MakeCredentialRequest,
+        MakeCredentialResponse, UserEntity,
+    },
+    u2f::{self, SW_CONDITIONS_NOT_SATISFIED, SW_WRONG_DATA},
+    Authenticator, CtapError, Transport, ES256, FLAG_ATTESTED, GET_ASSERTION, GET_INFO,
+    MAKE_CREDENTIAL, STATUS_OK,
+};
+use crate::webauthn::{AuthenticateRequest, RegisterRequest, Response};
+use rand::RngCore;
+use ring::{
+    digest::{digest, SHA256},
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+/// Flag set in the authenticator data when the user is present
+const FLAG_USER_PRESENT: u8 = 0x01;
+
+/// Flag set in the authenticator data when the user is verified
+const FLAG_USER_VERIFIED: u8 = 0x04;
+
+/// DER encoding of the ecdsa-with-SHA256 algorithm identifier
+const ECDSA_WITH_SHA256: &[u8] = &[
+    0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02,
+];
+
+/// `id-fido-gen-ce-aaguid` (1.3.6.1.4.1.45724.1.1.4)
+const FIDO_GEN_CE_AAGUID: &[u8] = &[
+    0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xe5, 0x1c, 0x01, 0x01, 0x04,
+];
+
+/// DER encoding of the id-ecPublicKey / prime256v1 algorithm identifier
+const EC_P256: &[u8] = &[
+    0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48,
+    0xce, 0x3d, 0x03, 0x01, 0x07,
+];
+
+/// A credential created by the authenticator
+struct Credential {
+    id: Vec<u8>,
+    rp_id_hash: Vec<u8>,
+    user_id: Vec<u8>,
+    key: Vec<u8>,
+    counter: u32,
+}
+
+/// An authenticator implemented in software, for tests
+pub struct SoftAuthenticator {
+    aaguid: [u8; 16],
+    user_verified: bool,
+    ctap1_only: bool,
+    packed: bool,
+    attestation_key: Vec<u8>,
+    attestation_cert: Vec<u8>,
+    credentials: Vec<Credential>,
+    rng: SystemRandom,
+}
+
+impl Default for SoftAuthenticator {
+    fn default() -> SoftAuthenticator {
+        let rng = SystemRandom::new();
+        let attestation_key = generate(&rng);
+        let attestation_cert = self_signed(&attestation_key, None, &rng);
+
+        SoftAuthenticator {
+            aaguid: [0; 16],
+            user_verified: false,
+            ctap1_only: false,
+            packed: false,
+            attestation_key,
+            attestation_cert,
+            credentials: vec![],
+            rng,
+        }
+    }
+}
+
+impl SoftAuthenticator {
+    /// Creates an authenticator without credentials, with a fresh attestation key
+    pub fn new() -> SoftAuthenticator {
+        Self::default()
+    }
+
+    /// Sets the AAGUID identifying the authenticator's model (default: all zeros)
+    ///
+    /// # Arguments
+    /// * `aaguid` - AAGUID to report
+    pub fn aaguid(mut self, aaguid: [u8; 16]) -> Self {
+        self.aaguid = aaguid;
+        self
+    }
+
+    /// Reissues the attestation certificate with an `id-fido-gen-ce-aaguid` extension
+    /// (default: no extension)
+    ///
+    /// # Arguments
+    /// * `aaguid` - AAGUID the certificate names
+    pub fn certificate_aaguid(mut self, aaguid: [u8; 16]) -> Self {
+        self.attestation_cert = self_signed(&self.attestation_key, Some(&aaguid), &self.rng);
+        self
+    }
+
+    /// Sets whether the authenticator reports the user as verified (default: false)
+    ///
+    /// # Arguments
+    /// * `verified` - True to set the user verified flag
+    pub fn user_verified(mut self, verified: bool) -> Self {
+        self.user_verified = verified;
+        self
+    }
+
+    /// Sets whether the authenticator only speaks U2F, rejecting CTAP2 commands
+    /// (default: false)
+    ///
+    /// # Arguments
+    /// * `ctap1_only` - True to answer U2F messages only
+    pub fn ctap1_only(mut self, ctap1_only: bool) -> Self {
+        self.ctap1_only = ctap1_only;
+        self
+    }
+
+    /// Sets whether attestation objects use the `packed` format instead of `fido-u2f`
+    /// (default: false)
+    ///
+    /// # Arguments
+    /// * `packed` - True to attest credentials with `packed` statements
+    pub fn packed(mut self, packed: bool) -> Self {
+        self.packed = packed;
+        self
+    }
+
+    /// Returns the DER encoded attestation certificate
+    pub fn attestation_certificate(&self) -> &[u8] {
+        &self.attestation_cert
+    }
+
+    /// Registers a new credential, returning the response a browser would post
+    ///
+    /// # Arguments
+    /// * `req` - Register request generated by the server
+    /// * `origin` - Origin of the relying party
+    pub fn make_credential(
+        &mut self,
+        req: &RegisterRequest,
+        origin: &str,
+    ) -> Result<Response, CtapError> {
+        Authenticator::new(self).make_credential(req, origin)
+    }
+
+    /// Authenticates with a stored credential, returning the response a browser would
+    /// post
+    ///
+    /// # Arguments
+    /// * `req` - Authenticate request generated by the server
+    /// * `origin` - Origin of the relying party
+    pub fn get_assertion(
+        &mut self,
+        req: &AuthenticateRequest,
+        origin: &str,
+    ) -> Result<Response, CtapError> {
+        Authenticator::new(self).get_assertion(req, origin)
+    }
+
+    /// Answers `authenticatorGetInfo`
+    fn info(&self) -> Value {
+        let mut info = BTreeMap::new();
+        info.insert(
+            Value::Integer(0x01),
+            Value::Array(vec![Value::Text("FIDO_2_0".to_owned())]),
+        );
+        info.insert(Value::Integer(0x03), Value::Bytes(self.aaguid.to_vec()));
+        info.insert(
+            Value::Integer(0x04),
+            map(vec![
+                ("rk", Some(Value::Bool(true))),
+                ("up", Some(Value::Bool(true))),
+                ("uv", Some(Value::Bool(self.user_verified))),
+            ]),
+        );
+        Value::Map(info)
+    }
+
+    /// Answers `authenticatorMakeCredential`
+    fn make(&mut self, req: MakeCredentialRequest) -> Result<MakeCredentialResponse, u8> {
+        if !req.pub_key_cred_params.iter().any(|p| p.alg == ES256) {
+            return Err(ERR_UNSUPPORTED_ALGORITHM);
+        }
+        let rp_id_hash = digest(&SHA256, req.rp.id.as_bytes());
+        if req
+            .exclude_list
+            .iter()
+            .any(|c| self.find(rp_id_hash.as_ref(), Some(&c.id)).is_some())
+        {
+            return Err(ERR_CREDENTIAL_EXCLUDED);
+        }
+
+        let (id, public_key) = self.create(rp_id_hash.as_ref(), req.user.id);
+
+        let mut auth_data = self.auth_data(rp_id_hash.as_ref(), FLAG_ATTESTED, 0);
+        auth_data.extend_from_slice(&self.aaguid);
+        auth_data.extend_from_slice(&(id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&id);
+        auth_data.extend(serde_cbor::to_vec(&cose_key(&public_key)).unwrap());
+
+        let x5c = Value::Array(vec![Value::Bytes(self.attestation_cert.clone())]);
+        let (fmt, att_stmt) = if self.packed {
+            // authenticatorData || clientDataHash
+            let signed = [&auth_data[..], &req.client_data_hash].concat();
+            let sig = keypair(&self.attestation_key)
+                .sign(&self.rng, &signed)
+                .map_err(|_| ERR_OTHER)?;
+            let att_stmt = map(vec![
+                ("alg", Some(Value::Integer(ES256.into()))),
+                ("sig", Some(Value::Bytes(sig.as_ref().to_vec()))),
+                ("x5c", Some(x5c)),
+            ]);
+            ("packed", att_stmt)
+        } else {
+            let sig = self.attest(rp_id_hash.as_ref(), &req.client_data_hash, &id, &public_key)?;
+            let att_stmt = map(vec![("sig", Some(Value::Bytes(sig))), ("x5c", Some(x5c))]);
+            ("fido-u2f", att_stmt)
+        };
+
+        Ok(MakeCredentialResponse {
+            fmt: fmt.to_owned(),
+            auth_data,
+            att_stmt,
+            ep_att: None,
+            large_blob_key: None,
+        })
+    }
+
+    /// Answers `authenticatorGetAssertion`, with the first matching credential
+    fn assert(&mut self, req: GetAssertionRequest) -> Result<GetAssertionResponse, u8> {
+        let rp_id_hash = digest(&SHA256, req.rp_id.as_bytes());
+        let index = if req.allow_list.is_empty() {
+            self.find(rp_id_hash.as_ref(), None)
+        } else {
+            req.allow_list
+                .iter()
+                .find_map(|c| self.find(rp_id_hash.as_ref(), Some(&c.id)))
+        }
+        .ok_or(ERR_NO_CREDENTIALS)?;
+
+        self.credentials[index].counter += 1;
+        let auth_data = self.auth_data(rp_id_hash.as_ref(), 0, self.credentials[index].counter);
+
+        let credential = &self.credentials[index];
+        let signed = [&auth_data[..], &req.client_data_hash].concat();
+        let signature = keypair(&credential.key)
+            .sign(&self.rng, &signed)
+            .map_err(|_| ERR_OTHER)?;
+
+        Ok(GetAssertionResponse {
+            credential: Some(CredentialDescriptor::new(credential.id.clone())),
+            auth_data,
+            signature: signature.as_ref().to_vec(),
+            user: Some(UserEntity {
+                id: credential.user_id.clone(),
+                ..Default::default()
+            }),
+            number_of_credentials: None,
+            user_selected: None,
+            large_blob_key: None,
+        })
+    }
+
+    /// Answers `U2F_REGISTER` and `U2F_AUTHENTICATE`
+    ///
+    /// # Arguments
+    /// * `ins` - Instruction byte
+    /// * `p1` - First parameter
+    /// * `data` - Command data
+    fn u2f(&mut self, ins: u8, p1: u8, data: &[u8]) -> Result<Vec<u8>, u16> {
+        match ins {
+            // challenge parameter (32) | application parameter (32)
+            u2f::REGISTER if data.len() == 64 => {
+                let (client_data_hash, app_id_hash) = data.split_at(32);
+                let (id, public_key) = self.create(app_id_hash, vec![]);
+                let sig = self
+                    .attest(app_id_hash, client_data_hash, &id, &public_key)
+                    .map_err(|_| SW_WRONG_DATA)?;
+
+                Ok([
+                    &[0x05][..],
+                    &public_key,
+                    &[id.len() as u8],
+                    &id,
+                    &self.attestation_cert,
+                    &sig,
+                ]
+                .concat())
+            }
+            // challenge parameter (32) | application parameter (32) | length (1) | key handle
+            u2f::AUTHENTICATE if data.len() > 65 && data.len() == 65 + data[64] as usize => {
+                let (client_data_hash, app_id_hash) = data[..64].split_at(32);
+                let index = self
+                    .find(app_id_hash, Some(&data[65..]))
+                    .ok_or(SW_WRONG_DATA)?;
+                if p1 == u2f::CHECK_ONLY {
+                    return Err(SW_CONDITIONS_NOT_SATISFIED);
+                }
+
+                self.credentials[index].counter += 1;
+                let credential = &self.credentials[index];
+                let mut response = vec![FLAG_USER_PRESENT];
+                response.extend_from_slice(&credential.counter.to_be_bytes());
+                let signed = [app_id_hash, &response, client_data_hash].concat();
+                let signature = keypair(&credential.key)
+                    .sign(&self.rng, &signed)
+                    .map_err(|_| SW_WRONG_DATA)?;
+                response.extend_from_slice(signature.as_ref());
+                Ok(response)
+            }
+            u2f::REGISTER | u2f::AUTHENTICATE => Err(SW_WRONG_LENGTH),
+            _ => Err(SW_INS_NOT_SUPPORTED),
+        }
+    }
+
+    /// Creates a credential, returning its id and public key
+    ///
+    /// # Arguments
+    /// * `rp_id_hash` - SHA-256 hash of the relying party id (or U2F application id)
+    /// * `user_id` - User handle stored with the credential
+    fn create(&mut self, rp_id_hash: &[u8], user_id: Vec<u8>) -> (Vec<u8>, Vec<u8>) {
+        let key = generate(&self.rng);
+        let public_key = keypair(&key).public_key().as_ref().to_vec();
+        let mut id = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut id);
+
+        self.credentials.push(Credential {
+            id: id.clone(),
+            rp_id_hash: rp_id_hash.to_vec(),
+            user_id,
+            key,
+            counter: 0,
+        });
+        (id, public_key)
+    }
+
+    /// Signs a new credential with the attestation key, as `fido-u2f` attestation expects
+    fn attest(
+        &self,
+        rp_id_hash: &[u8],
+        client_data_hash: &[u8],
+        id: &[u8],
+        public_key: &[u8],
+    ) -> Result<Vec<u8>, u8> {
+        // 0x00 || rpIdHash || clientDataHash || credentialId || publicKey
+        let signed = [&[0x00][..], rp_id_hash, client_data_hash, id, public_key].concat();
+        let sig = keypair(&self.attestation_key)
+            .sign(&self.rng, &signed)
+            .map_err(|_| ERR_OTHER)?;
+        Ok(sig.as_ref().to_vec())
+    }
+
+    /// Returns the index of a credential for a relying party, optionally with a given id
+    fn find(&self, rp_id_hash: &[u8], id: Option<&[u8]>) -> Option<usize> {
+        self.credentials
+            .iter()
+            .position(|c| c.rp_id_hash == rp_id_hash && id.is_none_or(|id| id == c.id.as_slice()))
+    }
+
+    /// Builds authenticator data without attested credential data
+    fn auth_data(&self, rp_id_hash: &[u8], flags: u8, counter: u32) -> Vec<u8> {
+        let mut flags = flags | FLAG_USER_PRESENT;
+        if self.user_verified {
+            flags |= FLAG_USER_VERIFIED;
+        }
+
+        let mut auth_data = rp_id_hash.to_vec();
+        auth_data.push(flags);
+        auth_data.extend_from_slice(&counter.to_be_bytes());
+        auth_data
+    }
+}
+
+impl Transport for SoftAuthenticator {
+    fn cbor(&mut self, request: &[u8]) -> Result<Vec<u8>, CtapError> {
+        let (command, params) = request
+            .split_first()
+            .ok_or(CtapError::Malformed("empty command"))?;
+        if self.ctap1_only {
+            return Ok(vec![ERR_INVALID_COMMAND]);
+        }
+
+        let response = match *command {
+            MAKE_CREDENTIAL => serde_cbor::from_slice(params)
+                .map_err(|_| ERR_INVALID_CBOR)
+                .and_then(|req| self.make(req))
+                .map(|resp| serde_cbor::to_vec(&resp)),
+            GET_ASSERTION => serde_cbor::from_slice(params)
+                .map_err(|_| ERR_INVALID_CBOR)
+                .and_then(|req| self.assert(req))
+                .map(|resp| serde_cbor::to_vec(&resp)),
+            GET_INFO => Ok(serde_cbor::to_vec(&self.info())),
+            _ => Err(ERR_INVALID_COMMAND),
+        };
+
+        Ok(match response {
+            Ok(response) => [vec![STATUS_OK], response?].concat(),
+            Err(status) => vec![status],
+        })
+    }
+
+    fn msg(&mut self, ins: u8, p1: u8, data: &[u8]) -> Result<Vec<u8>, CtapError> {
+        self.u2f(ins, p1, data).map_err(CtapError::Apdu)
+    }
+}
+
+/// `CTAP1_ERR_INVALID_COMMAND`
+const ERR_INVALID_COMMAND: u8 = 0x01;
+
+/// `CTAP2_ERR_INVALID_CBOR`
+const ERR_INVALID_CBOR: u8 = 0x12;
+
+/// `CTAP2_ERR_CREDENTIAL_EXCLUDED`
+const ERR_CREDENTIAL_EXCLUDED: u8 = 0x19;
+
+/// `CTAP2_ERR_UNSUPPORTED_ALGORITHM`
+const ERR_UNSUPPORTED_ALGORITHM: u8 = 0x26;
+
+/// `CTAP2_ERR_NO_CREDENTIALS`
+const ERR_NO_CREDENTIALS: u8 = 0x2e;
+
+/// `CTAP1_ERR_OTHER`
+const ERR_OTHER: u8 = 0x7f;
+
+/// `SW_WRONG_LENGTH`
+const SW_WRONG_LENGTH: u16 = 0x6700;
+
+/// `SW_INS_NOT_SUPPORTED`
+const SW_INS_NOT_SUPPORTED: u16 = 0x6d00;
+
+/// Generates a P-256 key, returned PKCS#8 encoded
+fn generate(rng: &SystemRandom) -> Vec<u8> {
+    EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, rng)
+        .expect("failed to generate P-256 key")
+        .as_ref()
+        .to_vec()
+}
+
+/// Loads a key generated by `generate()`
+fn keypair(pkcs8: &[u8]) -> EcdsaKeyPair {
+    EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8)
+        .expect("keys are generated by ring")
+}
+
+/// Builds a self-signed X.509 v3 certificate for an attestation key
+///
+/// # Arguments
+/// * `pkcs8` - Attestation key
+/// * `aaguid` - AAGUID to name in an `id-fido-gen-ce-aaguid` extension, if any
+/// * `rng` - Random number generator used to sign
+fn self_signed(pkcs8: &[u8], aaguid: Option<&[u8; 16]>, rng: &SystemRandom) -> Vec<u8> {
+    let key = keypair(pkcs8);
+
+    // C=US, O=auth-rs, OU=Authenticator Attestation, CN=auth-rs soft authenticator
+    let attribute = |oid: u8, tag: u8, value: &[u8]| {
+        der(
+            0x31,
+            &der(
+                0x30,
+                &[der(0x06, &[0x55, 0x04, oid]), der(tag, value)].concat(),
+            ),
+        )
+    };
+    let name = der(
+        0x30,
+        &[
+            attribute(0x06, 0x13, b"US"),
+            attribute(0x0a, 0x0c, b"auth-rs"),
+            attribute(0x0b, 0x0c, b"Authenticator Attestation"),
+            attribute(0x03, 0x0c, b"auth-rs soft authenticator"),
+        ]
+        .concat(),
+    );
+    let validity = der(
+        0x30,
+        &[der(0x17, b"200101000000Z"), der(0x18, b"99991231235959Z")].concat(),
+    );
+    let spki = der(
+        0x30,
+        &[
+            EC_P256,
+            &der(0x03, &[&[0x00][..], key.public_key().as_ref()].concat()),
+        ]
+        .concat(),
+    );
+    // basicConstraints, not a CA
+    let mut extensions = der(
+        0x30,
+        &[der(0x06, &[0x55, 0x1d, 0x13]), der(0x04, &der(0x30, &[]))].concat(),
+    );
+    if let Some(aaguid) = aaguid {
+        extensions.extend(der(
+            0x30,
+            &[der(0x06, FIDO_GEN_CE_AAGUID), der(0x04, &der(0x04, aaguid))].concat(),
+        ));
+    }
+    let extensions = der(0xa3, &der(0x30, &extensions));
+
+    let tbs = der(
+        0x30,
+        &[
+            der(0xa0, &der(0x02, &[0x02])),
+            der(0x02, &[0x01]),
+            ECDSA_WITH_SHA256.to_vec(),
+            name.clone(),
+            validity,
+            name,
+            spki,
+            extensions,
+        ]
+        .concat(),
+    );
+    let signature = key.sign(rng, &tbs).expect("failed to sign certificate");
+
+    der(
+        0x30,
+        &[
+            tbs,
+            ECDSA_WITH_SHA256.to_vec(),
+            der(0x03, &[&[0x00][..], signature.as_ref()].concat()),
+        ]
+        .concat(),
+    )
+}
+
+/// Encodes a DER value
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut value = vec![tag];
+    match content.len() {
+        len if len < 0x80 => value.push(len as u8),
+        len if len < 0x100 => value.extend_from_slice(&[0x81, len as u8]),
+        len => value.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+    }
+    value.extend_from_slice(content);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::{
+        self, report::Outcome, request::AuthenticatorAttachment, Ceremony, CeremonyPolicy, Config,
+        ConfigSet, CounterPolicy, Device, Error, Response, VerifierContext, WebAuthnType,
+        WebAuthnUser,
+    };
+    use std::sync::{Arc, Mutex};
+
+    struct TestUser;
+
+    impl WebAuthnUser for TestUser {
+        type Conn = ();
+
+        fn id(&self) -> &[u8] {
+            &[0, 1, 2, 3]
+        }
+
+        fn name(&self) -> &str {
+            "user"
+        }
+
+        fn fetch_devices(&self, _: &()) -> Vec<Device> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn register_and_authenticate() {
+        let config = Config::new("https://app.example.com");
+        let mut key = SoftAuthenticator::new().user_verified(true);
+        assert!(webpki::EndEntityCert::from(key.attestation_certificate()).is_ok());
+
+        let req = RegisterRequest::new(&config, &TestUser);
+        let form = key.make_credential(&req, config.origin()).unwrap();
+        let result = webauthn::register(form, &config, req.challenge()).unwrap();
+        let mut device = result.device().clone();
+        assert_eq!(device.count(), 0);
+
+        for count in 1..3 {
+            let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+            let form = key.get_assertion(&req, config.origin()).unwrap();
+            let result = webauthn::authenticate(
+                form,
+                &config,
+                req.challenge(),
+                &TestUser,
+                &[device.clone()],
+            )
+            .unwrap();
+            assert_eq!(result.count(), count);
+            assert!(result.user_verified());
+            device.set_count(result.count());
+        }
+
+        // credentials are scoped to their relying party
+        let other = Config::new("https://other.example.com");
+        let req = AuthenticateRequest::new(&other, vec![device]);
+        assert!(matches!(
+            key.get_assertion(&req, other.origin()),
+            Err(CtapError::Status(ERR_NO_CREDENTIALS))
+        ));
+    }
+
+    #[test]
+    fn counter_policy() {
+        let mut config = Config::new("https://app.example.com");
+        let mut key = SoftAuthenticator::new();
+
+        let req = RegisterRequest::new(&config, &TestUser);
+        let form = key.make_credential(&req, config.origin()).unwrap();
+        let mut device = webauthn::register(form, &config, req.challenge())
+            .unwrap()
+            .device()
+            .clone();
+
+        // a stored count ahead of the authenticator looks like a cloned key
+        device.set_count(10);
+        for (policy, accepted) in &[
+            (CounterPolicy::Ignore, true),
+            (CounterPolicy::Warn, true),
+            (CounterPolicy::Reject, false),
+        ] {
+            config.set_counter_policy(*policy);
+            let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+            let form = key.get_assertion(&req, config.origin()).unwrap();
+            let result = webauthn::authenticate(
+                form,
+                &config,
+                req.challenge(),
+                &TestUser,
+                &[device.clone()],
+            );
+            match result {
+                Ok(_) => assert!(accepted),
+                Err(e) => {
+                    assert!(!accepted);
+                    assert!(matches!(e, Error::CounterRegressed));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn config_set_selects_tenant() {
+        let mut configs = ConfigSet::new();
+        configs
+            .insert(Config::new("https://a.example.com"))
+            .insert(Config::new("https://b.example.com"));
+        let mut key = SoftAuthenticator::new();
+
+        let config = configs.get("b.example.com").unwrap();
+        let req = RegisterRequest::new(config, &TestUser);
+        let form = key.make_credential(&req, config.origin()).unwrap();
+        let device = configs
+            .register(form, req.challenge())
+            .unwrap()
+            .device()
+            .clone();
+
+        let req = AuthenticateRequest::new(config, vec![device.clone()]);
+        let form = key.get_assertion(&req, config.origin()).unwrap();
+        assert!(configs
+            .authenticate(form, req.challenge(), &TestUser, &[device])
+            .is_ok());
+
+        // origins without a configuration are rejected
+        let other = Config::new("https://c.example.com");
+        let req = RegisterRequest::new(&other, &TestUser);
+        let form = key.make_credential(&req, other.origin()).unwrap();
+        assert!(matches!(
+            configs.register(form, req.challenge()),
+            Err(Error::UnknownOrigin(_))
+        ));
+    }
+
+    /// Records the hooks called and rejects logins at the last one
+    struct RecordingPolicy(Arc<Mutex<Vec<String>>>);
+
+    impl CeremonyPolicy for RecordingPolicy {
+        fn check_client_data(&self, ceremony: &Ceremony) -> Result<(), Error> {
+            let mut calls = self.0.lock().unwrap();
+            calls.push(format!("client_data {}", ceremony.origin()));
+            Ok(())
+        }
+
+        fn check_auth_data(&self, ceremony: &Ceremony) -> Result<(), Error> {
+            let mut calls = self.0.lock().unwrap();
+            calls.push(format!("auth_data {:?}", ceremony.count()));
+            Ok(())
+        }
+
+        fn check_result(&self, ceremony: &Ceremony) -> Result<(), Error> {
+            self.0.lock().unwrap().push("result".into());
+            match ceremony.aaguid() {
+                Some(_) => Ok(()),
+                None => Err(Error::policy("logins are disabled")),
+            }
+        }
+    }
+
+    #[test]
+    fn policy_hooks() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let mut config = Config::new("https://app.example.com");
+        config.add_policy(RecordingPolicy(calls.clone()));
+        let mut key = SoftAuthenticator::new();
+
+        let req = RegisterRequest::new(&config, &TestUser);
+        let form = key.make_credential(&req, config.origin()).unwrap();
+        let device = webauthn::register(form, &config, req.challenge())
+            .unwrap()
+            .device()
+            .clone();
+
+        let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+        let form = key.get_assertion(&req, config.origin()).unwrap();
+        let result = webauthn::authenticate(form, &config, req.challenge(), &TestUser, &[device]);
+        assert!(matches!(result, Err(Error::PolicyRejected(_))));
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(
+            *calls,
+            [
+                "client_data https://app.example.com",
+                "auth_data Some(0)",
+                "result",
+                "client_data https://app.example.com",
+                "auth_data Some(1)",
+                "result",
+            ]
+        );
+    }
+
+    #[test]
+    fn validation_report() {
+        let config = Config::new("https://app.example.com");
+        let mut key = SoftAuthenticator::new();
+
+        let req = RegisterRequest::new(&config, &TestUser);
+        let form = key.make_credential(&req, config.origin()).unwrap();
+        let (result, report) = webauthn::register_with_report(form, &config, req.challenge());
+        let device = result.unwrap().device().clone();
+        assert_eq!(report.ceremony(), "webauthn.create");
+        assert!(report.failure().is_none());
+        assert_eq!(
+            report.step("attestation").unwrap().value("fmt"),
+            Some("fido-u2f")
+        );
+
+        // signed for another origin: the report shows what was expected
+        let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+        let form = key.get_assertion(&req, "https://evil.example.com").unwrap();
+        let (result, report) = webauthn::authenticate_with_report(
+            form,
+            &config,
+            req.challenge(),
+            &TestUser,
+            &[device],
+        );
+        assert!(result.is_err());
+        let failure = report.failure().unwrap();
+        assert_eq!(failure.name(), "client_data");
+        assert_eq!(failure.value("origin"), Some("https://evil.example.com"));
+        assert_eq!(
+            failure.value("expected_origin"),
+            Some("https://app.example.com")
+        );
+        assert!(report.step("signature").is_none());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["ceremony"], "webauthn.get");
+        assert_eq!(json["steps"][0]["name"], "credential");
+    }
+
+    #[test]
+    fn dry_run() {
+        let mut config = Config::new("https://app.example.com");
+        config.set_counter_policy(CounterPolicy::Reject);
+        let mut key = SoftAuthenticator::new();
+
+        let req = RegisterRequest::new(&config, &TestUser);
+        let form = key.make_credential(&req, config.origin()).unwrap();
+        let mut device = webauthn::register(form, &config, req.challenge())
+            .unwrap()
+            .device()
+            .clone();
+        device.set_count(10);
+
+        // signed for another origin with a stale counter: both are reported
+        let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+        let form = key.get_assertion(&req, "https://evil.example.com").unwrap();
+        let (result, report) =
+            webauthn::authenticate_dry_run(form, &config, req.challenge(), &TestUser, &[device]);
+        assert_eq!(result.unwrap_err().code(), "client_data.origin_mismatch");
+        let failures: Vec<_> = report.failures().map(|step| step.name()).collect();
+        assert_eq!(failures, ["client_data", "counter"]);
+        let signature = report.step("signature").unwrap();
+        assert_eq!(signature.outcome(), Outcome::Passed);
+        assert_eq!(signature.value("alg"), Some("-7"));
+    }
+
+    #[test]
+    fn verifier_context() {
+        let config = Config::new("https://app.example.com");
+        let mut key = SoftAuthenticator::new();
+
+        let req = RegisterRequest::new(&config, &TestUser);
+        let form = key.make_credential(&req, config.origin()).unwrap();
+        let result = webauthn::register(form, &config, req.challenge()).unwrap();
+        let device = result.device().clone();
+        let mut context = VerifierContext::new(vec![device.clone()]).unwrap();
+
+        for count in 1..=2 {
+            let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+            let form = key.get_assertion(&req, config.origin()).unwrap();
+            let result = webauthn::authenticate_with_context(
+                form,
+                &config,
+                req.challenge(),
+                &TestUser,
+                &context,
+            )
+            .unwrap();
+            assert_eq!(result.count(), count);
+            context.set_count(device.id(), result.count());
+        }
+
+        // the same assertion fails once the device is removed
+        let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+        let form = key.get_assertion(&req, config.origin()).unwrap();
+        context.remove(device.id());
+        let result = webauthn::authenticate_with_context(
+            form,
+            &config,
+            req.challenge(),
+            &TestUser,
+            &context,
+        );
+        assert!(matches!(result, Err(Error::InvalidDeviceId)));
+    }
+
+    #[test]
+    fn response_round_trips() {
+        let config = Config::new("https://app.example.com");
+        let mut key = SoftAuthenticator::new();
+
+        // responses survive being queued as JSON and validated later
+        let req = RegisterRequest::new(&config, &TestUser);
+        let form = key.make_credential(&req, config.origin()).unwrap();
+        let json = serde_json::to_value(&form).unwrap();
+        assert!(json["rawId"].is_string() && json["response"]["clientDataJSON"].is_string());
+        let form = serde_json::from_value(json).unwrap();
+        let device = webauthn::register(form, &config, req.challenge())
+            .unwrap()
+            .device()
+            .clone();
+
+        let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+        let form = key.get_assertion(&req, config.origin()).unwrap();
+        let json = serde_json::to_string(&form).unwrap();
+        let form = serde_json::from_str(&json).unwrap();
+        assert!(
+            webauthn::authenticate(form, &config, req.challenge(), &TestUser, &[device]).is_ok()
+        );
+    }
+
+    /// Rewrites a response the way @simplewebauthn/browser sends it: base64url without
+    /// padding, no `type` in the inner response and a few extra members
+    fn simplewebauthn(form: &Response) -> Response {
+        let mut json = serde_json::to_value(form).unwrap();
+        let reencode = |value: &mut serde_json::Value| {
+            if let Some(enc) = value.as_str().filter(|enc| !enc.is_empty()) {
+                let raw = crate::parsers::decode_base64(enc).unwrap();
+                *value = base64::encode_config(raw, base64::URL_SAFE_NO_PAD).into();
+            }
+        };
+        reencode(&mut json["rawId"]);
+        let response = json["response"].as_object_mut().unwrap();
+        response.remove("type");
+        if response.contains_key("attestationObject") {
+            let transports = serde_json::json!(["usb", "hybrid", "smart-card"]);
+            response.insert("transports".to_owned(), transports);
+        }
+        for value in response.values_mut() {
+            reencode(value);
+        }
+        json["authenticatorAttachment"] = "cross-platform".into();
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn frontend_helper_payloads() {
+        let config = Config::new("https://app.example.com");
+        let mut key = SoftAuthenticator::new();
+
+        let req = RegisterRequest::new(&config, &TestUser);
+        let form = key.make_credential(&req, config.origin()).unwrap();
+        let form = simplewebauthn(&form);
+        assert_eq!(form.ty(), WebAuthnType::Create);
+        let result = webauthn::register(form, &config, req.challenge()).unwrap();
+        assert_eq!(
+            result.authenticator_attachment(),
+            Some(AuthenticatorAttachment::CrossPlatform)
+        );
+        let device = result.device().clone();
+        // unknown transports are dropped
+        assert_eq!(
+            device.transports(),
+            &[webauthn::Transport::Usb, webauthn::Transport::Hybrid]
+        );
+
+        let mut req = AuthenticateRequest::new(&config, vec![device.clone()]);
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(
+            json["allowCredentials"][0]["transports"],
+            serde_json::json!(["usb", "hybrid"])
+        );
+        req.omit_transports();
+        let json = serde_json::to_value(&req).unwrap();
+        assert!(json["allowCredentials"][0].get("transports").is_none());
+
+        let form = key.get_assertion(&req, config.origin()).unwrap();
+        let form = simplewebauthn(&form);
+        assert_eq!(form.ty(), WebAuthnType::Get);
+
+        // attachments added after this library was written are ignored
+        let mut json = serde_json::to_value(&form).unwrap();
+        json["authenticatorAttachment"] = "smart-watch".into();
+        let unknown: Response = serde_json::from_value(json).unwrap();
+        assert_eq!(unknown.authenticator_attachment(), None);
+
+        let result =
+            webauthn::authenticate(form, &config, req.challenge(), &TestUser, &[device]).unwrap();
+        assert_eq!(
+            result.authenticator_attachment(),
+            Some(AuthenticatorAttachment::CrossPlatform)
+        );
+    }
+
+    #[test]
+    fn certificate_aaguid() {
+        let config = Config::new("https://app.example.com");
+        let register = |key: &mut SoftAuthenticator| {
+            let req = RegisterRequest::new(&config, &TestUser);
+            let form = key.make_credential(&req, config.origin()).unwrap();
+            webauthn::register(form, &config, req.challenge())
+        };
+
+        let mut key = SoftAuthenticator::new()
+            .aaguid([1; 16])
+            .certificate_aaguid([1; 16]);
+        assert!(register(&mut key).is_ok());
+
+        // a certificate issued for another model
+        let mut key = SoftAuthenticator::new()
+            .aaguid([1; 16])
+            .certificate_aaguid([2; 16]);
+        assert_eq!(
+            register(&mut key).unwrap_err().code(),
+            "attestation_certificate.aaguid_mismatch"
+        );
+
+        // U2F registrations carry no AAGUID to compare
+        let mut key = SoftAuthenticator::new()
+            .ctap1_only(true)
+            .certificate_aaguid([2; 16]);
+        assert!(register(&mut key).is_ok());
+    }
+
+    #[test]
+    fn packed_attestation() {
+        let config = Config::new("https://app.example.com");
+        let register = |key: &mut SoftAuthenticator| {
+            let req = RegisterRequest::new(&config, &TestUser);
+            let form = key.make_credential(&req, config.origin()).unwrap();
+            webauthn::register(form, &config, req.challenge())
+        };
+
+        let mut key = SoftAuthenticator::new()
+            .packed(true)
+            .aaguid([1; 16])
+            .certificate_aaguid([1; 16]);
+        let result = register(&mut key).unwrap();
+        assert_eq!(result.aaguid(), Some(&[1; 16]));
+        let device = result.device().clone();
+
+        // credentials attested with packed statements log in like any other
+        let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+        let form = key.get_assertion(&req, config.origin()).unwrap();
+        assert!(
+            webauthn::authenticate(form, &config, req.challenge(), &TestUser, &[device]).is_ok()
+        );
+
+        let mut key = SoftAuthenticator::new()
+            .packed(true)
+            .aaguid([1; 16])
+            .certificate_aaguid([2; 16]);
+        assert_eq!(
+            register(&mut key).unwrap_err().code(),
+            "attestation_certificate.aaguid_mismatch"
+        );
+    }
+
+    #[test]
+    fn u2f_fallback() {
+        let config = Config::new("https://app.example.com");
+        let mut key = SoftAuthenticator::new().ctap1_only(true);
+
+        let req = RegisterRequest::new(&config, &TestUser);
+        let form = key.make_credential(&req, config.origin()).unwrap();
+        let result = webauthn::register(form, &config, req.challenge()).unwrap();
+        let device = result.device().clone();
+
+        let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+        let form = key.get_assertion(&req, config.origin()).unwrap();
+        let result =
+            webauthn::authenticate(form, &config, req.challenge(), &TestUser, &[device]).unwrap();
+        assert_eq!(result.count(), 1);
+        assert!(!result.user_verified());
+
+        // only key handles issued for the application are recognized
+        let id = key.credentials[0].id.clone();
+        let app_id_hash = digest(&SHA256, b"app.example.com");
+        let check = [&[0; 32][..], app_id_hash.as_ref(), &[32], &id].concat();
+        assert!(matches!(
+            key.msg(u2f::AUTHENTICATE, u2f::CHECK_ONLY, &check),
+            Err(CtapError::Apdu(SW_CONDITIONS_NOT_SATISFIED))
+        ));
+        let check = [&[0; 64][..], &[32], &id].concat();
+        assert!(matches!(
+            key.msg(u2f::AUTHENTICATE, u2f::CHECK_ONLY, &check),
+            Err(CtapError::Apdu(SW_WRONG_DATA))
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+//! CTAP1/U2F fallback
+//!
+//! Security keys that predate CTAP2 only understand the U2F raw messages: `U2F_REGISTER`
+//! and `U2F_AUTHENTICATE` framed as ISO 7816 APDUs.  When an authenticator rejects
+//! `authenticatorGetInfo`, [`Authenticator`] sends these instead and translates their
+//! answers into the same `authenticatorMakeCredential` and `authenticatorGetAssertion`
+//! responses a CTAP2 authenticator returns, wrapping the registration in a `fido-u2f`
+//! attestation statement.  U2F credentials are never discoverable, so an assertion needs
+//! the allow list of the request.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::ctap::{hid::Hid, Authenticator};
+//!
+//! // falls back to U2F_REGISTER for keys that only report U2F_V2
+//! let mut key = Authenticator::new(Hid::new(device)?);
+//! let form = key.make_credential(&req, "https://app.example.com")?;
+//! ```
+
+use super::{
+    cose_key, map,
+    messages::{
+        CredentialDescriptor, GetAssertionRequest, GetAssertionResponse, MakeCredentialRequest,
+        MakeCredentialResponse,
+    },
+    Authenticator, CtapError, Transport, FLAG_ATTESTED,
+};
+use ring::digest::{digest, SHA256};
+use serde_cbor::Value;
+use std::{thread, time::Duration};
+
+/// `U2F_REGISTER` instruction
+pub const REGISTER: u8 = 0x01;
+
+/// `U2F_AUTHENTICATE` instruction
+pub const AUTHENTICATE: u8 = 0x02;
+
+/// `U2F_AUTHENTICATE` control byte requiring a touch and signing
+pub const ENFORCE_USER_PRESENCE: u8 = 0x03;
+
+/// `U2F_AUTHENTICATE` control byte only checking whether a key handle is known
+pub const CHECK_ONLY: u8 = 0x07;
+
+/// Status word of a successful command
+pub const SW_NO_ERROR: u16 = 0x9000;
+
+/// Status word returned until the user touches the authenticator
+pub const SW_CONDITIONS_NOT_SATISFIED: u16 = 0x6985;
+
+/// Status word returned for a key handle the authenticator did not issue
+pub const SW_WRONG_DATA: u16 = 0x6a80;
+
+/// Delay between two attempts while waiting for the user's touch
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Attempts before giving up on the user's touch (30 seconds)
+const POLL_ATTEMPTS: usize = 300;
+
+/// Reserved byte starting a registration response
+const REGISTER_RESERVED: u8 = 0x05;
+
+/// Length of an uncompressed P-256 public key
+const PUBLIC_KEY_LEN: usize = 65;
+
+impl<T: Transport> Authenticator<T> {
+    /// Registers a credential with `U2F_REGISTER`, answering like
+    /// `authenticatorMakeCredential` would
+    ///
+    /// # Arguments
+    /// * `req` - Parameters of the `authenticatorMakeCredential` command
+    pub(super) fn u2f_register(
+        &mut self,
+        req: &MakeCredentialRequest,
+    ) -> Result<MakeCredentialResponse, CtapError> {
+        if req.options.rk == Some(true) {
+            // CTAP2_ERR_UNSUPPORTED_OPTION
+            return Err(CtapError::Status(0x2b));
+        }
+
+        let app_id_hash = digest(&SHA256, req.rp.id.as_bytes());
+        let challenge = [&req.client_data_hash[..], app_id_hash.as_ref()].concat();
+        let response = self.poll(REGISTER, 0x00, &challenge)?;
+
+        // 0x05 | public key (65) | key handle length (1) | key handle | certificate | signature
+        if response.len() < 2 + PUBLIC_KEY_LEN || response[0] != REGISTER_RESERVED {
+            return Err(CtapError::Malformed("invalid U2F registration"));
+        }
+        let public_key = &response[1..1 + PUBLIC_KEY_LEN];
+        let handle_len = response[1 + PUBLIC_KEY_LEN] as usize;
+        let rest = &response[2 + PUBLIC_KEY_LEN..];
+        if rest.len() < handle_len {
+            return Err(CtapError::Malformed("invalid U2F registration"));
+        }
+        let (key_handle, rest) = rest.split_at(handle_len);
+        let cert_len =
+            der_len(rest).ok_or(CtapError::Malformed("invalid U2F attestation certificate"))?;
+        let (cert, sig) = rest.split_at(cert_len);
+
+        let mut auth_data = app_id_hash.as_ref().to_vec();
+        auth_data.push(FLAG_ATTESTED | 0x01);
+        auth_data.extend_from_slice(&0u32.to_be_bytes());
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&(key_handle.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(key_handle);
+        auth_data.extend(serde_cbor::to_vec(&cose_key(public_key))?);
+
+        Ok(MakeCredentialResponse {
+            fmt: "fido-u2f".to_owned(),
+            auth_data,
+            att_stmt: map(vec![
+                ("sig", Some(Value::Bytes(sig.to_vec()))),
+                ("x5c", Some(Value::Array(vec![Value::Bytes(cert.to_vec())]))),
+            ]),
+            ep_att: None,
+            large_blob_key: None,
+        })
+    }
+
+    /// Signs with the first credential of the allow list the authenticator knows, with
+    /// `U2F_AUTHENTICATE`, answering like `authenticatorGetAssertion` would
+    ///
+    /// # Arguments
+    /// * `req` - Parameters of the `authenticatorGetAssertion` command
+    pub(super) fn u2f_authenticate(
+        &mut self,
+        req: &GetAssertionRequest,
+    ) -> Result<GetAssertionResponse, CtapError> {
+        let app_id_hash = digest(&SHA256, req.rp_id.as_bytes());
+
+        for credential in &req.allow_list {
+            if credential.id.len() > u8::MAX as usize {
+                continue;
+            }
+            let challenge = [
+                &req.client_data_hash[..],
+                app_id_hash.as_ref(),
+                &[credential.id.len() as u8],
+                &credential.id,
+            ]
+            .concat();
+
+            let response = match self.poll(AUTHENTICATE, ENFORCE_USER_PRESENCE, &challenge) {
+                Ok(response) => response,
+                // not one of this authenticator's key handles
+                Err(CtapError::Apdu(SW_WRONG_DATA)) => continue,
+                Err(e) => return Err(e),
+            };
+
+            // user presence (1) | counter (4) | signature
+            if response.len() < 5 {
+                return Err(CtapError::Malformed("invalid U2F authentication"));
+            }
+            let (counter, signature) = response.split_at(5);
+
+            return Ok(GetAssertionResponse {
+                credential: Some(CredentialDescriptor::new(credential.id.clone())),
+                auth_data: [app_id_hash.as_ref(), counter].concat(),
+                signature: signature.to_vec(),
+                user: None,
+                number_of_credentials: None,
+                user_selected: None,
+                large_blob_key: None,
+            });
+        }
+
+        // CTAP2_ERR_NO_CREDENTIALS
+        Err(CtapError::Status(0x2e))
+    }
+
+    /// Sends a U2F message, repeating it until the user touches the authenticator
+    ///
+    /// # Arguments
+    /// * `ins` - Instruction byte
+    /// * `p1` - First parameter
+    /// * `data` - Command data
+    fn poll(&mut self, ins: u8, p1: u8, data: &[u8]) -> Result<Vec<u8>, CtapError> {
+        for _ in 1..POLL_ATTEMPTS {
+            match self.transport.msg(ins, p1, data) {
+                Err(CtapError::Apdu(SW_CONDITIONS_NOT_SATISFIED)) => thread::sleep(POLL_INTERVAL),
+                result => return result,
+            }
+        }
+        self.transport.msg(ins, p1, data)
+    }
+}
+
+/// Encodes an extended length command APDU (CLA and P2 zero), as carried by the HID and
+/// BLE transports
+///
+/// # Arguments
+/// * `ins` - Instruction byte
+/// * `p1` - First parameter
+/// * `data` - Command data
+pub(super) fn extended_apdu(ins: u8, p1: u8, data: &[u8]) -> Vec<u8> {
+    let mut apdu = vec![0x00, ins, p1, 0x00];
+    if data.is_empty() {
+        apdu.extend_from_slice(&[0x00, 0x00, 0x00]);
+    } else {
+        apdu.push(0x00);
+        apdu.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        apdu.extend_from_slice(data);
+        apdu.extend_from_slice(&[0x00, 0x00]);
+    }
+    apdu
+}
+
+/// Returns the data of a response APDU, failing unless its status word reports success
+///
+/// # Arguments
+/// * `response` - Response data followed by the status word
+pub(super) fn response_data(mut response: Vec<u8>) -> Result<Vec<u8>, CtapError> {
+    if response.len() < 2 {
+        return Err(CtapError::Malformed("response APDU has no status word"));
+    }
+    let sw = response.split_off(response.len() - 2);
+    match u16::from_be_bytes([sw[0], sw[1]]) {
+        SW_NO_ERROR => Ok(response),
+        sw => Err(CtapError::Apdu(sw)),
+    }
+}
+
+/// Returns the length of the DER value at the start of a buffer, header included
+fn der_len(der: &[u8]) -> Option<usize> {
+    let (header, len) = match *der.get(1)? {
+        len if len < 0x80 => (2, len as usize),
+        0x81 => (3, *der.get(2)? as usize),
+        0x82 => (4, u16::from_be_bytes([*der.get(2)?, *der.get(3)?]) as usize),
+        _ => return None,
+    };
+    Some(header + len).filter(|&len| len <= der.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apdu_framing() {
+        assert_eq!(
+            extended_apdu(REGISTER, 0x00, &[1, 2, 3]),
+            vec![0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x03, 1, 2, 3, 0x00, 0x00]
+        );
+        assert_eq!(
+            extended_apdu(0x03, 0x00, &[]),
+            vec![0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+
+        assert_eq!(response_data(vec![7, 0x90, 0x00]).unwrap(), vec![7]);
+        assert!(matches!(
+            response_data(vec![0x6a, 0x80]),
+            Err(CtapError::Apdu(SW_WRONG_DATA))
+        ));
+        assert!(response_data(vec![0x90]).is_err());
+    }
+
+    #[test]
+    fn certificate_length() {
+        assert_eq!(der_len(&[0x30, 0x02, 0, 0, 9]), Some(4));
+        assert_eq!(der_len(&[0x30, 0x81, 0x01, 0, 9]), Some(4));
+        assert_eq!(der_len(&[0x30, 0x82, 0x01, 0x00]), None);
+    }
+}
@@ -1,13 +1,14 @@
 //! Top-Level WebAuthn Error
 
 use crate::{
-    common::cose::CoseError,
+    webauthn::common::cose::CoseError,
     webauthn::response::{AttestationError, AuthError, ClientDataError},
 };
 use base64::DecodeError;
 use std::fmt;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     IncorrectResponseType,
     InvalidPublicKey,
@@ -15,6 +16,22 @@ pub enum Error {
     DeviceNotFound,
     InvalidDeviceId,
     IncorrectUser(Vec<u8>, Vec<u8>),
+    InvalidExtension(String),
+    MissingChallenge,
+    MissingSession,
+    ReplayedChallenge,
+    ReplayProtectionUnavailable,
+    UnknownUser,
+    InvalidState,
+    ExpiredState,
+    InvalidCsrfToken,
+    UserNotVerified,
+    InvalidStepUpProof,
+    CounterRegressed,
+    UnknownOrigin(String),
+    PolicyRejected(String),
+    PayloadTooLarge(usize),
+    Store(Box<dyn std::error::Error + Send + Sync>),
     AuthenticationError(AuthError),
     ClientData(ClientDataError),
     Attestation(AttestationError),
@@ -36,6 +53,35 @@ impl fmt::Display for Error {
                 "User in response does not match expected user: got: {:?}, expected: {:?}",
                 a, b
             ),
+            Error::InvalidExtension(id) => {
+                write!(f, "Invalid input or output for extension `{}`", id)
+            }
+            Error::MissingChallenge => write!(f, "No challenge was issued for this session"),
+            Error::MissingSession => write!(f, "No session was established for this request"),
+            Error::ReplayedChallenge => write!(f, "Challenge has already been used"),
+            Error::ReplayProtectionUnavailable => {
+                write!(f, "Challenge store cannot detect replayed challenges")
+            }
+            Error::UnknownUser => write!(f, "Unable to determine the user for this request"),
+            Error::InvalidState => write!(f, "Ceremony state is invalid or has been tampered with"),
+            Error::ExpiredState => write!(f, "Ceremony state has expired"),
+            Error::InvalidCsrfToken => write!(f, "CSRF token is missing or invalid"),
+            Error::UserNotVerified => write!(f, "Authenticator did not verify the user"),
+            Error::InvalidStepUpProof => {
+                write!(f, "Step-up proof is missing, invalid or has expired")
+            }
+            Error::CounterRegressed => write!(
+                f,
+                "Signature counter did not increase, the authenticator may be cloned"
+            ),
+            Error::UnknownOrigin(origin) => {
+                write!(f, "No configuration for origin `{}`", origin)
+            }
+            Error::PolicyRejected(reason) => write!(f, "Rejected by policy: {}", reason),
+            Error::PayloadTooLarge(limit) => {
+                write!(f, "Request body exceeds the limit of {} bytes", limit)
+            }
+            Error::Store(e) => write!(f, "Store failure: {}", e),
             Error::AuthenticationError(e) => write!(f, "{}", e),
             Error::ClientData(e) => write!(f, "{}", e),
             Error::Attestation(e) => write!(f, "{}", e),
@@ -46,7 +92,100 @@ impl fmt::Display for Error {
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Store(e) => Some(e.as_ref()),
+            Error::AuthenticationError(e) => Some(e),
+            Error::ClientData(e) => Some(e),
+            Error::Attestation(e) => Some(e),
+            Error::Base64Error(e) => Some(e),
+            Error::JsonError(e) => Some(e),
+            Error::CborError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// Returns a stable, machine-readable code for the error (e.g., `signature_failed`,
+    /// `attestation.bad_signature`), which API servers can pass on to clients instead of
+    /// the display message.  Errors from one stage of validation share a prefix
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::IncorrectResponseType => "incorrect_response_type",
+            Error::InvalidPublicKey => "invalid_public_key",
+            Error::SignatureFailed => "signature_failed",
+            Error::DeviceNotFound => "device_not_found",
+            Error::InvalidDeviceId => "invalid_device_id",
+            Error::IncorrectUser(_, _) => "incorrect_user",
+            Error::InvalidExtension(_) => "invalid_extension",
+            Error::MissingChallenge => "missing_challenge",
+            Error::MissingSession => "missing_session",
+            Error::ReplayedChallenge => "replayed_challenge",
+            Error::ReplayProtectionUnavailable => "replay_protection_unavailable",
+            Error::UnknownUser => "unknown_user",
+            Error::InvalidState => "invalid_state",
+            Error::ExpiredState => "expired_state",
+            Error::InvalidCsrfToken => "invalid_csrf_token",
+            Error::UserNotVerified => "user_not_verified",
+            Error::InvalidStepUpProof => "invalid_step_up_proof",
+            Error::CounterRegressed => "counter_regressed",
+            Error::UnknownOrigin(_) => "unknown_origin",
+            Error::PolicyRejected(_) => "policy_rejected",
+            Error::PayloadTooLarge(_) => "payload_too_large",
+            Error::Store(_) => "store",
+            Error::AuthenticationError(e) => e.code(),
+            Error::ClientData(e) => e.code(),
+            Error::Attestation(e) => e.code(),
+            Error::Base64Error(_) => "base64",
+            Error::JsonError(_) => "json",
+            Error::CborError(_) => "cbor",
+        }
+    }
+
+    /// Returns the HTTP status code an API server should answer with: 400 for malformed
+    /// requests, 401 for failed ceremonies, 403 for a bad CSRF token, 413 for an oversized
+    /// request body and 500 for store failures (including a store without replay protection)
+    pub fn http_status(&self) -> u16 {
+        match self {
+            Error::IncorrectResponseType
+            | Error::Base64Error(_)
+            | Error::JsonError(_)
+            | Error::CborError(_)
+            | Error::InvalidExtension(_)
+            | Error::InvalidState
+            | Error::UnknownOrigin(_) => 400,
+            Error::InvalidCsrfToken => 403,
+            Error::PayloadTooLarge(_) => 413,
+            Error::Store(_) | Error::ReplayProtectionUnavailable => 500,
+            _ => 401,
+        }
+    }
+
+    /// Returns the stage of validation that failed, used as a metric label
+    #[cfg(feature = "metrics")]
+    pub(crate) fn label(&self) -> &'static str {
+        self.code().split('.').next().unwrap_or_default()
+    }
+
+    /// Builds the error a [`CeremonyPolicy`](super::policy::CeremonyPolicy) returns to
+    /// reject a ceremony
+    ///
+    /// # Arguments
+    /// * `reason` - Why the ceremony was rejected
+    pub fn policy<S: Into<String>>(reason: S) -> Error {
+        Error::PolicyRejected(reason.into())
+    }
+
+    /// Wraps an error returned by a challenge or device store
+    ///
+    /// # Arguments
+    /// * `e` - The underlying store error
+    pub fn store<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> Error {
+        Error::Store(e.into())
+    }
+}
 
 impl From<AuthError> for Error {
     fn from(e: AuthError) -> Error {
@@ -89,3 +228,33 @@ impl From<serde_cbor::Error> for Error {
         Error::CborError(e)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn codes_and_sources() {
+        let e = Error::from(AttestationError::BadCert);
+        assert_eq!(e.code(), "attestation.bad_certificate");
+        assert_eq!(e.http_status(), 401);
+        assert!(e.source().is_some());
+
+        let e = Error::from(serde_json::from_str::<u8>("x").unwrap_err());
+        assert_eq!((e.code(), e.http_status()), ("json", 400));
+        assert!(e.source().is_some());
+
+        assert_eq!(Error::InvalidCsrfToken.http_status(), 403);
+        assert_eq!(Error::PayloadTooLarge(1024).http_status(), 413);
+        assert_eq!(Error::ReplayProtectionUnavailable.http_status(), 500);
+        assert!(Error::DeviceNotFound.source().is_none());
+        assert_eq!(
+            (
+                Error::MissingSession.code(),
+                Error::MissingSession.http_status()
+            ),
+            ("missing_session", 401)
+        );
+    }
+}
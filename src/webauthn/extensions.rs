@@ -0,0 +1,420 @@
+//! WebAuthn Extensions
+//!
+//! Extensions allow a Relying Party to request additional processing from the client
+//! and/or authenticator during a ceremony. Each extension is identified by a string
+//! (e.g., `credProps`, `appid`) and carries its own inputs and outputs:
+//!
+//! * Client extension inputs are sent in the `extensions` member of the request (JSON)
+//! * Client extension outputs are returned in `clientExtensionResults` (JSON)
+//! * Authenticator extension inputs are sent to the authenticator by the client (CBOR)
+//! * Authenticator extension outputs are returned in the authenticator data (CBOR)
+//!
+//! Custom or proprietary extensions can be supported by implementing the [`Extension`]
+//! trait with the serde types matching the extension's inputs and outputs and adding
+//! the extension to a request with `add_extension`. Registering the extension with
+//! [`Config::register_extension`](../struct.Config.html#method.register_extension) will
+//! ensure any outputs returned for it decode into the supplied types before a response
+//! is accepted.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize)]
+//! struct ExampleInput { level: u8 }
+//!
+//! #[derive(Deserialize)]
+//! struct ExampleOutput { accepted: bool }
+//!
+//! struct ExampleExtension;
+//!
+//! impl Extension for ExampleExtension {
+//!     const ID: &'static str = "exampleExtension";
+//!     type ClientInput = ExampleInput;
+//!     type ClientOutput = ExampleOutput;
+//!     type AuthenticatorInput = ();
+//!     type AuthenticatorOutput = ();
+//! }
+//!
+//! let mut cfg = Config::new("https://app.example.com");
+//! cfg.register_extension::<ExampleExtension>();
+//!
+//! let mut req = RegisterRequest::new(&cfg, &user);
+//! req.add_extension::<ExampleExtension>(ExampleInput { level: 2 })?;
+//! ```
+
+use crate::webauthn::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// Raw client extension values (JSON), keyed by extension identifier
+pub type ClientExtensionMap = BTreeMap<String, serde_json::Value>;
+
+/// Raw authenticator extension values (CBOR), keyed by extension identifier
+pub type AuthenticatorExtensionMap = BTreeMap<String, serde_cbor::Value>;
+
+/// Describes a WebAuthn extension and the types used for its inputs and outputs.
+///
+/// Types that are not used by an extension (e.g., an extension with no authenticator
+/// processing) can be set to `()`
+pub trait Extension {
+    /// The extension identifier, as it appears in requests and responses
+    const ID: &'static str;
+
+    /// Input sent to the client in the request's `extensions` map (JSON)
+    type ClientInput: Serialize;
+
+    /// Output returned by the client in `clientExtensionResults` (JSON)
+    type ClientOutput: DeserializeOwned;
+
+    /// Input forwarded to the authenticator by the client (CBOR)
+    type AuthenticatorInput: Serialize;
+
+    /// Output returned by the authenticator in the authenticator data (CBOR)
+    type AuthenticatorOutput: DeserializeOwned;
+}
+
+/// Encodes the client input for an extension into its JSON representation
+///
+/// # Arguments
+/// * `input` - Client extension input to encode
+pub fn encode_client_input<E: Extension>(
+    input: &E::ClientInput,
+) -> Result<serde_json::Value, Error> {
+    serde_json::to_value(input).map_err(|_| Error::InvalidExtension(E::ID.to_owned()))
+}
+
+/// Encodes the authenticator input for an extension into its CBOR representation
+///
+/// # Arguments
+/// * `input` - Authenticator extension input to encode
+pub fn encode_authenticator_input<E: Extension>(
+    input: &E::AuthenticatorInput,
+) -> Result<Vec<u8>, Error> {
+    serde_cbor::to_vec(input).map_err(|_| Error::InvalidExtension(E::ID.to_owned()))
+}
+
+/// Decodes the client output for an extension from its JSON representation
+///
+/// # Arguments
+/// * `value` - Raw JSON value returned in `clientExtensionResults`
+pub fn decode_client_output<E: Extension>(
+    value: &serde_json::Value,
+) -> Result<E::ClientOutput, Error> {
+    serde_json::from_value(value.clone()).map_err(|_| Error::InvalidExtension(E::ID.to_owned()))
+}
+
+/// Decodes the authenticator output for an extension from its CBOR representation
+///
+/// # Arguments
+/// * `value` - Raw CBOR value returned in the authenticator data
+pub fn decode_authenticator_output<E: Extension>(
+    value: &serde_cbor::Value,
+) -> Result<E::AuthenticatorOutput, Error> {
+    serde_cbor::to_vec(value)
+        .and_then(|bytes| serde_cbor::from_slice(&bytes))
+        .map_err(|_| Error::InvalidExtension(E::ID.to_owned()))
+}
+
+/// Checks that the outputs for a registered extension decode into its types
+#[derive(Clone, Copy)]
+struct ExtensionDecoder {
+    client: fn(&serde_json::Value) -> Result<(), Error>,
+    authenticator: fn(&serde_cbor::Value) -> Result<(), Error>,
+}
+
+impl ExtensionDecoder {
+    fn new<E: Extension>() -> ExtensionDecoder {
+        ExtensionDecoder {
+            client: |v| decode_client_output::<E>(v).map(|_| ()),
+            authenticator: |v| decode_authenticator_output::<E>(v).map(|_| ()),
+        }
+    }
+}
+
+/// A set of extensions known to the Relying Party.
+///
+/// When a response contains outputs for a registered extension, the outputs must decode
+/// into the types supplied when the extension was registered or the response is rejected.
+/// Outputs for extensions that are not registered are passed through untouched.
+#[derive(Clone, Default)]
+pub struct ExtensionRegistry {
+    decoders: BTreeMap<&'static str, ExtensionDecoder>,
+}
+
+impl fmt::Debug for ExtensionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.decoders.keys()).finish()
+    }
+}
+
+impl ExtensionRegistry {
+    /// Creates a new, empty registry
+    pub fn new() -> ExtensionRegistry {
+        Self::default()
+    }
+
+    /// Registers an extension, replacing any extension with the same identifier
+    pub fn register<E: Extension>(&mut self) {
+        self.decoders.insert(E::ID, ExtensionDecoder::new::<E>());
+    }
+
+    /// Returns true if an extension with the identifier has been registered
+    ///
+    /// # Arguments
+    /// * `id` - Extension identifier to check
+    pub fn contains(&self, id: &str) -> bool {
+        self.decoders.contains_key(id)
+    }
+
+    /// Verifies the outputs of all registered extensions decode into their registered types
+    ///
+    /// # Arguments
+    /// * `client` - Client extension outputs from the response
+    /// * `authenticator` - Authenticator extension outputs from the authenticator data
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", name = "webauthn.extensions", skip_all, err)
+    )]
+    pub fn validate(
+        &self,
+        client: &ClientExtensionMap,
+        authenticator: Option<&AuthenticatorExtensionMap>,
+    ) -> Result<(), Error> {
+        for (id, value) in client {
+            if let Some(decoder) = self.decoders.get(id.as_str()) {
+                (decoder.client)(value)?;
+            }
+        }
+
+        if let Some(authenticator) = authenticator {
+            for (id, value) in authenticator {
+                if let Some(decoder) = self.decoders.get(id.as_str()) {
+                    (decoder.authenticator)(value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// FIDO AppID extension (`appid`), allowing credentials registered with the legacy
+/// FIDO U2F API to be used for authentication
+/// [WebAuthn Spec](https://www.w3.org/TR/webauthn/#sctn-appid-extension)
+pub struct AppId;
+
+impl Extension for AppId {
+    const ID: &'static str = "appid";
+    type ClientInput = String;
+    type ClientOutput = bool;
+    type AuthenticatorInput = ();
+    type AuthenticatorOutput = ();
+}
+
+/// Credential properties reported by the client
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CredentialProperties {
+    /// True if the credential created is a client-side discoverable (resident) credential
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rk: Option<bool>,
+}
+
+/// Credential properties extension (`credProps`), reporting if the created credential
+/// is discoverable
+/// [WebAuthn Spec](https://www.w3.org/TR/webauthn/#sctn-authenticator-credential-properties-extension)
+pub struct CredProps;
+
+impl Extension for CredProps {
+    const ID: &'static str = "credProps";
+    type ClientInput = bool;
+    type ClientOutput = CredentialProperties;
+    type AuthenticatorInput = ();
+    type AuthenticatorOutput = ();
+}
+
+/// Credential protection extension (`credProtect`), returning the protection level
+/// (1-3) the authenticator applied to the credential
+/// [CTAP Spec](https://fidoalliance.org/specs/fido-v2.1-ps-20210615/fido-client-to-authenticator-protocol-v2.1-ps-20210615.html#sctn-credProtect-extension)
+pub struct CredProtect;
+
+impl Extension for CredProtect {
+    const ID: &'static str = "credProtect";
+    type ClientInput = u8;
+    type ClientOutput = ();
+    type AuthenticatorInput = u8;
+    type AuthenticatorOutput = u8;
+}
+
+/// HMAC secret extension (`hmac-secret`), reporting if the authenticator created a
+/// secret bound to the credential
+/// [CTAP Spec](https://fidoalliance.org/specs/fido-v2.1-ps-20210615/fido-client-to-authenticator-protocol-v2.1-ps-20210615.html#sctn-hmac-secret-extension)
+pub struct HmacSecret;
+
+impl Extension for HmacSecret {
+    const ID: &'static str = "hmac-secret";
+    type ClientInput = bool;
+    type ClientOutput = ();
+    type AuthenticatorInput = bool;
+    type AuthenticatorOutput = bool;
+}
+
+/// Minimum PIN length extension (`minPinLength`), returning the authenticator's
+/// current minimum PIN length
+/// [CTAP Spec](https://fidoalliance.org/specs/fido-v2.1-ps-20210615/fido-client-to-authenticator-protocol-v2.1-ps-20210615.html#sctn-minpinlength-extension)
+pub struct MinPinLength;
+
+impl Extension for MinPinLength {
+    const ID: &'static str = "minPinLength";
+    type ClientInput = bool;
+    type ClientOutput = ();
+    type AuthenticatorInput = bool;
+    type AuthenticatorOutput = u64;
+}
+
+/// The client and authenticator extension outputs returned with a response
+#[derive(Clone, Debug, Default)]
+pub struct ExtensionOutputs {
+    /// Outputs returned by the client in `clientExtensionResults`
+    client: ClientExtensionMap,
+
+    /// Outputs returned by the authenticator in the authenticator data
+    authenticator: AuthenticatorExtensionMap,
+}
+
+impl ExtensionOutputs {
+    /// Creates a new set of extension outputs
+    ///
+    /// # Arguments
+    /// * `client` - Client extension outputs
+    /// * `authenticator` - Authenticator extension outputs
+    pub fn new(
+        client: ClientExtensionMap,
+        authenticator: AuthenticatorExtensionMap,
+    ) -> ExtensionOutputs {
+        ExtensionOutputs {
+            client,
+            authenticator,
+        }
+    }
+
+    /// Decodes the client output for an extension, returning `None` if the
+    /// client did not return an output for the extension
+    pub fn client<E: Extension>(&self) -> Option<Result<E::ClientOutput, Error>> {
+        self.client.get(E::ID).map(decode_client_output::<E>)
+    }
+
+    /// Decodes the authenticator output for an extension, returning `None` if the
+    /// authenticator did not return an output for the extension
+    pub fn authenticator<E: Extension>(&self) -> Option<Result<E::AuthenticatorOutput, Error>> {
+        self.authenticator
+            .get(E::ID)
+            .map(decode_authenticator_output::<E>)
+    }
+
+    /// Returns true if the client used the AppID to authenticate (`appid`)
+    pub fn appid(&self) -> Option<bool> {
+        self.client::<AppId>().and_then(Result::ok)
+    }
+
+    /// Returns the credential properties reported by the client (`credProps`)
+    pub fn cred_props(&self) -> Option<CredentialProperties> {
+        self.client::<CredProps>().and_then(Result::ok)
+    }
+
+    /// Returns the protection level applied to the credential (`credProtect`)
+    pub fn cred_protect(&self) -> Option<u8> {
+        self.authenticator::<CredProtect>().and_then(Result::ok)
+    }
+
+    /// Returns true if the authenticator created an HMAC secret for the credential (`hmac-secret`)
+    pub fn hmac_secret(&self) -> Option<bool> {
+        self.authenticator::<HmacSecret>().and_then(Result::ok)
+    }
+
+    /// Returns the authenticator's minimum PIN length (`minPinLength`)
+    pub fn min_pin_length(&self) -> Option<u64> {
+        self.authenticator::<MinPinLength>().and_then(Result::ok)
+    }
+
+    /// Returns the raw client extension outputs, including extensions unknown to this crate
+    pub fn raw_client(&self) -> &ClientExtensionMap {
+        &self.client
+    }
+
+    /// Returns the raw authenticator extension outputs, including extensions unknown to this crate
+    pub fn raw_authenticator(&self) -> &AuthenticatorExtensionMap {
+        &self.authenticator
+    }
+
+    /// Returns true if neither the client nor the authenticator returned any outputs
+    pub fn is_empty(&self) -> bool {
+        self.client.is_empty() && self.authenticator.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct TestOutput {
+        #[allow(dead_code)]
+        accepted: bool,
+    }
+
+    struct TestExtension;
+
+    impl Extension for TestExtension {
+        const ID: &'static str = "testExtension";
+        type ClientInput = u8;
+        type ClientOutput = TestOutput;
+        type AuthenticatorInput = ();
+        type AuthenticatorOutput = bool;
+    }
+
+    #[test]
+    fn registry_accepts_matching_outputs() {
+        let mut registry = ExtensionRegistry::new();
+        registry.register::<TestExtension>();
+
+        let mut client = ClientExtensionMap::new();
+        client.insert(
+            "testExtension".into(),
+            serde_json::json!({ "accepted": true }),
+        );
+        client.insert("unknown".into(), serde_json::json!(42));
+
+        let mut auth = AuthenticatorExtensionMap::new();
+        auth.insert("testExtension".into(), serde_cbor::Value::Bool(true));
+
+        assert!(registry.validate(&client, Some(&auth)).is_ok());
+    }
+
+    #[test]
+    fn registry_rejects_mismatched_outputs() {
+        let mut registry = ExtensionRegistry::new();
+        registry.register::<TestExtension>();
+
+        let mut client = ClientExtensionMap::new();
+        client.insert("testExtension".into(), serde_json::json!("not an object"));
+
+        assert!(registry.validate(&client, None).is_err());
+    }
+
+    #[test]
+    fn outputs_decode_known_extensions() {
+        let mut client = ClientExtensionMap::new();
+        client.insert("credProps".into(), serde_json::json!({ "rk": true }));
+
+        let mut auth = AuthenticatorExtensionMap::new();
+        auth.insert("credProtect".into(), serde_cbor::Value::Integer(2));
+
+        let outputs = ExtensionOutputs::new(client, auth);
+        assert_eq!(outputs.cred_props().and_then(|p| p.rk), Some(true));
+        assert_eq!(outputs.cred_protect(), Some(2));
+        assert_eq!(outputs.hmac_secret(), None);
+    }
+}
@@ -0,0 +1,361 @@
+//! Canned responses for tests
+//!
+//! A [`Fixture`] holds a single P-256 credential and synthesizes the [`Response`] a
+//! browser would post after `navigator.credentials.create()` or
+//! `navigator.credentials.get()`, so handlers built on [`register`](super::register) and
+//! [`authenticate`](super::authenticate) can be tested without collecting real browser
+//! captures.  Registrations carry a `packed` self attestation.  Each response can be
+//! spoiled with [`Defect`]s to exercise the error paths of a handler.
+//!
+//! The credential only lives in memory and responses are signed on the fly, so fixtures
+//! are meant for tests only: enable the `fixtures` feature in `[dev-dependencies]`.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::{self, fixtures::{Defect, Fixture}};
+//!
+//! let mut fixture = Fixture::new(&config);
+//! let form = fixture.create(req.challenge(), &[]);
+//! let device = webauthn::register(form, &config, req.challenge())?.into_device();
+//!
+//! let form = fixture.get(req.challenge(), &[Defect::WrongOrigin]);
+//! assert!(webauthn::authenticate(form, &config, req.challenge(), &user, &[device]).is_err());
+//! ```
+
+use crate::webauthn::{Config, Device, Response, WebAuthnType};
+use rand::RngCore;
+use ring::{
+    digest::{digest, SHA256},
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+/// Flag set in the authenticator data when the user is present
+const FLAG_USER_PRESENT: u8 = 0x01;
+
+/// Flag set in the authenticator data when attested credential data is included
+const FLAG_ATTESTED: u8 = 0x40;
+
+/// COSE identifier of ES256
+const ES256: i128 = -7;
+
+/// Signature counter reported when registering
+const REGISTERED_COUNT: u32 = 1;
+
+/// Origin responses are signed for with [`Defect::WrongOrigin`]
+const WRONG_ORIGIN: &str = "https://attacker.invalid";
+
+/// A defect to introduce into a response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Defect {
+    /// The client data names another origin than the relying party's
+    WrongOrigin,
+
+    /// The client data names another challenge than the one given
+    WrongChallenge,
+
+    /// The attestation statement (`create`) or assertion (`get`) signature does not verify
+    BadSignature,
+
+    /// The signature counter of an assertion repeats the count the credential was
+    /// registered with, so it does not increase over any stored count.  Only rejected when
+    /// the relying party uses [`CounterPolicy::Reject`](crate::webauthn::CounterPolicy::Reject)
+    StaleCounter,
+
+    /// The user present flag is not set in the authenticator data
+    UserNotPresent,
+}
+
+/// A credential that produces canned responses
+pub struct Fixture {
+    origin: String,
+    rp_id: String,
+    id: Vec<u8>,
+    key: Vec<u8>,
+    counter: u32,
+    rng: SystemRandom,
+}
+
+impl Fixture {
+    /// Creates a fixture with a fresh credential for a relying party
+    ///
+    /// # Arguments
+    /// * `config` - Configuration of the relying party under test
+    pub fn new(config: &Config) -> Fixture {
+        let rng = SystemRandom::new();
+        let key = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng)
+            .expect("failed to generate P-256 key")
+            .as_ref()
+            .to_vec();
+        let mut id = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut id);
+
+        Fixture {
+            origin: config.origin().to_owned(),
+            rp_id: config.id().to_owned(),
+            id,
+            key,
+            counter: 0,
+            rng,
+        }
+    }
+
+    /// Returns the id of the credential
+    pub fn credential_id(&self) -> &[u8] {
+        &self.id
+    }
+
+    /// Returns the credential as the relying party stores it once registered, with the
+    /// signature counter of the last response
+    pub fn device(&self) -> Device {
+        Device::new(self.id.clone(), self.public_key(), self.counter)
+    }
+
+    /// Builds the response to a registration, resetting the signature counter
+    ///
+    /// # Arguments
+    /// * `challenge` - Base64url encoded challenge of the [`RegisterRequest`](crate::webauthn::RegisterRequest)
+    /// * `defects` - Defects to introduce
+    pub fn create<S: Into<String>>(&mut self, challenge: S, defects: &[Defect]) -> Response {
+        self.counter = REGISTERED_COUNT;
+        let client_data = self.client_data(WebAuthnType::Create, challenge.into(), defects);
+
+        // attested credential data: aaguid (16) | length (2) | id | public key
+        let mut auth_data = self.auth_data(FLAG_ATTESTED, self.counter, defects);
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&(self.id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&self.id);
+        auth_data.extend(serde_cbor::to_vec(&self.cose_key()).expect("COSE key encodes"));
+
+        // self attestation signs authenticatorData || clientDataHash with the credential key
+        let sig = self.sign(&auth_data, &client_data, defects);
+        let att_stmt = map(vec![
+            ("alg", Value::Integer(ES256)),
+            ("sig", Value::Bytes(sig)),
+        ]);
+        let attestation = map(vec![
+            ("fmt", Value::Text("packed".to_owned())),
+            ("attStmt", att_stmt),
+            ("authData", Value::Bytes(auth_data)),
+        ]);
+
+        self.response(serde_json::json!({
+            "clientDataJSON": encode(&client_data),
+            "attestationObject": encode(
+                &serde_cbor::to_vec(&attestation).expect("attestation object encodes"),
+            ),
+        }))
+    }
+
+    /// Builds the response to an authentication, incrementing the signature counter
+    /// unless [`Defect::StaleCounter`] is given.  Defective responses increment it too, as
+    /// an authenticator would
+    ///
+    /// # Arguments
+    /// * `challenge` - Base64url encoded challenge of the [`AuthenticateRequest`](crate::webauthn::AuthenticateRequest)
+    /// * `defects` - Defects to introduce
+    pub fn get<S: Into<String>>(&mut self, challenge: S, defects: &[Defect]) -> Response {
+        let counter = if defects.contains(&Defect::StaleCounter) {
+            REGISTERED_COUNT
+        } else {
+            self.counter += 1;
+            self.counter
+        };
+        let client_data = self.client_data(WebAuthnType::Get, challenge.into(), defects);
+        let auth_data = self.auth_data(0, counter, defects);
+        let signature = self.sign(&auth_data, &client_data, defects);
+
+        self.response(serde_json::json!({
+            "clientDataJSON": encode(&client_data),
+            "authenticatorData": encode(&auth_data),
+            "signature": encode(&signature),
+        }))
+    }
+
+    /// Returns the credential public key as an uncompressed point
+    fn public_key(&self) -> Vec<u8> {
+        self.keypair().public_key().as_ref().to_vec()
+    }
+
+    /// Returns the credential public key as a COSE key
+    fn cose_key(&self) -> Value {
+        let public_key = self.public_key();
+        let mut key = BTreeMap::new();
+        key.insert(Value::Integer(1), Value::Integer(2));
+        key.insert(Value::Integer(3), Value::Integer(ES256));
+        key.insert(Value::Integer(-1), Value::Integer(1));
+        key.insert(Value::Integer(-2), Value::Bytes(public_key[1..33].to_vec()));
+        key.insert(Value::Integer(-3), Value::Bytes(public_key[33..].to_vec()));
+        Value::Map(key)
+    }
+
+    /// Loads the credential key
+    fn keypair(&self) -> EcdsaKeyPair {
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &self.key)
+            .expect("keys are generated by ring")
+    }
+
+    /// Serializes the client data of a response
+    fn client_data(&self, ty: WebAuthnType, challenge: String, defects: &[Defect]) -> Vec<u8> {
+        let origin = if defects.contains(&Defect::WrongOrigin) {
+            WRONG_ORIGIN
+        } else {
+            &self.origin
+        };
+        let challenge = if defects.contains(&Defect::WrongChallenge) {
+            let mut other = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut other);
+            encode(&other)
+        } else {
+            challenge
+        };
+
+        serde_json::to_vec(&serde_json::json!({
+            "type": ty.as_str(),
+            "challenge": challenge,
+            "origin": origin,
+            "crossOrigin": false,
+        }))
+        .expect("client data serializes")
+    }
+
+    /// Builds authenticator data without attested credential data
+    fn auth_data(&self, flags: u8, counter: u32, defects: &[Defect]) -> Vec<u8> {
+        let mut flags = flags | FLAG_USER_PRESENT;
+        if defects.contains(&Defect::UserNotPresent) {
+            flags &= !FLAG_USER_PRESENT;
+        }
+
+        let mut auth_data = digest(&SHA256, self.rp_id.as_bytes()).as_ref().to_vec();
+        auth_data.push(flags);
+        auth_data.extend_from_slice(&counter.to_be_bytes());
+        auth_data
+    }
+
+    /// Signs authenticatorData || SHA-256(clientDataJSON) with the credential key
+    fn sign(&self, auth_data: &[u8], client_data: &[u8], defects: &[Defect]) -> Vec<u8> {
+        let signed = [auth_data, digest(&SHA256, client_data).as_ref()].concat();
+        let mut sig = self
+            .keypair()
+            .sign(&self.rng, &signed)
+            .expect("failed to sign")
+            .as_ref()
+            .to_vec();
+        if defects.contains(&Defect::BadSignature) {
+            // still well formed DER, but no longer the signature of the data
+            if let Some(last) = sig.last_mut() {
+                *last ^= 0x01;
+            }
+        }
+        sig
+    }
+
+    /// Wraps the inner `response` member into the credential a browser would post
+    fn response(&self, response: serde_json::Value) -> Response {
+        serde_json::from_value(serde_json::json!({
+            "id": base64::encode_config(&self.id, base64::URL_SAFE_NO_PAD),
+            "rawId": encode(&self.id),
+            "type": "public-key",
+            "response": response,
+            "clientExtensionResults": {},
+        }))
+        .expect("fixture responses are well formed")
+    }
+}
+
+/// Base64url encodes binary members of a response
+fn encode(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+/// Builds a CBOR map with text keys
+fn map(entries: Vec<(&str, Value)>) -> Value {
+    Value::Map(
+        entries
+            .into_iter()
+            .map(|(k, v)| (Value::Text(k.to_owned()), v))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::{
+        self, AuthenticateRequest, CounterPolicy, RegisterRequest, WebAuthnUser,
+    };
+
+    struct TestUser;
+
+    impl WebAuthnUser for TestUser {
+        type Conn = ();
+
+        fn id(&self) -> &[u8] {
+            &[0, 1, 2, 3]
+        }
+
+        fn name(&self) -> &str {
+            "user"
+        }
+
+        fn fetch_devices(&self, _: &()) -> Vec<Device> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn defects() {
+        let mut config = Config::new("https://app.example.com");
+        config.set_counter_policy(CounterPolicy::Reject);
+        let mut fixture = Fixture::new(&config);
+
+        let req = RegisterRequest::new(&config, &TestUser);
+        for (defect, code) in &[
+            (Defect::WrongOrigin, "client_data.origin_mismatch"),
+            (Defect::WrongChallenge, "client_data.challenge_mismatch"),
+            (Defect::BadSignature, "authenticator_data.packed"),
+            (
+                Defect::UserNotPresent,
+                "authenticator_data.user_not_present",
+            ),
+        ] {
+            let form = fixture.create(req.challenge(), &[*defect]);
+            let err = webauthn::register(form, &config, req.challenge()).unwrap_err();
+            assert_eq!(err.code(), *code, "{:?}", defect);
+        }
+        let form = fixture.create(req.challenge(), &[]);
+        let device = webauthn::register(form, &config, req.challenge())
+            .unwrap()
+            .into_device();
+        assert_eq!(device.public_key(), fixture.device().public_key());
+
+        let req = AuthenticateRequest::new(&config, vec![device.clone()]);
+        let authenticate = |form| {
+            webauthn::authenticate(
+                form,
+                &config,
+                req.challenge(),
+                &TestUser,
+                std::slice::from_ref(&device),
+            )
+        };
+        for (defect, code) in &[
+            (Defect::WrongOrigin, "client_data.origin_mismatch"),
+            (Defect::BadSignature, "signature_failed"),
+            (Defect::StaleCounter, "counter_regressed"),
+        ] {
+            let err = authenticate(fixture.get(req.challenge(), &[*defect])).unwrap_err();
+            assert_eq!(err.code(), *code, "{:?}", defect);
+        }
+        assert_eq!(
+            authenticate(fixture.get(req.challenge(), &[]))
+                .unwrap()
+                .count(),
+            fixture.device().count()
+        );
+    }
+}
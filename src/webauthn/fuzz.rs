@@ -0,0 +1,116 @@
+//! Parser entry points for fuzzing
+//!
+//! Each function runs one of the parsers applied to untrusted client input, in
+//! isolation from the rest of a ceremony, and returns a typed error for malformed input.
+//! None of them panic, whatever the input, which makes them suitable as fuzz targets.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // fuzz/fuzz_targets/authenticator_data.rs
+//! #![no_main]
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+//!     let _ = auth_rs::webauthn::fuzz::parse_authenticator_data(data);
+//! });
+//! ```
+
+use crate::{
+    webauthn::common::cose::CoseKey,
+    webauthn::{
+        response::{self, AuthData, ClientData},
+        Error, Response,
+    },
+};
+
+/// Parses the JSON body a client posts to finish a ceremony
+///
+/// # Arguments
+/// * `json` - Raw request body
+pub fn parse_response(json: &[u8]) -> Result<Response, Error> {
+    Ok(serde_json::from_slice(json)?)
+}
+
+/// Parses a CBOR attestation object, including its authenticator data and the
+/// attestation statement
+///
+/// # Arguments
+/// * `data` - Raw (base64-decoded) attestation object
+pub fn parse_attestation_object(data: &[u8]) -> Result<(), Error> {
+    response::parse_attestation(data.to_vec()).map(|_| ())
+}
+
+/// Parses authenticator data, including the attested credential's COSE key and the
+/// extension outputs
+///
+/// # Arguments
+/// * `data` - Raw (base64-decoded) authenticator data
+pub fn parse_authenticator_data(data: &[u8]) -> Result<(), Error> {
+    AuthData::parse(data)?;
+    Ok(())
+}
+
+/// Parses client data JSON
+///
+/// # Arguments
+/// * `json` - Raw (base64-decoded) client data JSON
+pub fn parse_client_data(json: &[u8]) -> Result<(), Error> {
+    serde_json::from_slice::<ClientData>(json)?;
+    Ok(())
+}
+
+/// Parses a CBOR encoded COSE public key
+///
+/// # Arguments
+/// * `data` - Raw COSE_Key map
+pub fn parse_cose_key(data: &[u8]) -> Result<(), Error> {
+    CoseKey::parse(data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_empty_and_garbage() {
+        for data in &[&b""[..], b"\xff\x00not webauthn\x9f\xbf"] {
+            assert!(parse_response(data).is_err());
+            assert!(parse_attestation_object(data).is_err());
+            assert!(parse_authenticator_data(data).is_err());
+            assert!(parse_client_data(data).is_err());
+            assert!(parse_cose_key(data).is_err());
+        }
+    }
+
+    /// Runs every entry point over pseudo-random buffers and every truncation of a
+    /// valid authenticator data
+    #[test]
+    fn parsers_do_not_panic() {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        for len in 0..512 {
+            let data: Vec<u8> = (0..len)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect();
+            let _ = parse_response(&data);
+            let _ = parse_attestation_object(&data);
+            let _ = parse_authenticator_data(&data);
+            let _ = parse_client_data(&data);
+            let _ = parse_cose_key(&data);
+        }
+
+        // attested credential with an ES256 key, claiming a longer credential id than sent
+        let mut data = vec![0; 32];
+        data.extend_from_slice(&[0x41, 0, 0, 0, 1]);
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(&[0xff, 0xff, 1, 2, 3]);
+        for len in 0..data.len() {
+            assert!(parse_authenticator_data(&data[..len]).is_err());
+        }
+        assert!(parse_cose_key(&[0xa1, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,645 @@
+//! Web framework integrations
+//!
+//! [`Webauthn`] bundles the Relying Party configuration with the challenge and device
+//! stores and runs both legs of the register and login ceremonies for a client session.
+//! The framework specific modules (enabled with the feature of the same name) build on
+//! top of it to provide extractors, middleware and ready-made routes.
+//!
+//! The ceremonies are async so they can await the stores (see [`AsyncChallengeStore`]
+//! and [`AsyncDeviceStore`]) and the [`AsyncEventSink`] attached with
+//! [`Webauthn::event_sink`].  Applications without an async runtime use
+//! [`Webauthn::blocking`] with blocking stores, which runs the same ceremonies without
+//! any future.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let auth = Webauthn::new(config, PgDeviceStore::new(pool), MemoryChallengeStore::new())
+//!     .event_sink(PgAuditLog::new(pool));
+//!
+//! // async handlers
+//! let req = auth.start_login(&session, &user).await?;
+//!
+//! // blocking handlers, with blocking stores
+//! let result = auth.blocking().finish_login(&session, &user, form)?;
+//! ```
+
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "tide")]
+pub mod tide;
+#[cfg(feature = "tower")]
+pub mod tower;
+
+use crate::{
+    events::{AsyncEventSink, AuthEvent},
+    time::now,
+    webauthn::{
+        self,
+        state::DEFAULT_TTL,
+        store::{AsyncChallengeStore, AsyncDeviceStore, ChallengeStore, DeviceStore, SessionStore},
+        AuthenticateRequest, AuthenticationResult, Config, Error, RegisterRequest,
+        RegistrationResult, Response, WebAuthnUser,
+    },
+};
+use rand::RngCore;
+use std::sync::Arc;
+
+/// Name of the cookie containing the client's session id
+pub const SESSION_COOKIE: &str = "X-WebAuthn-Session";
+
+/// The client's session id, made available to handlers by the framework's session
+/// middleware
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session(pub String);
+
+/// Runs the register and login ceremonies against a set of stores
+pub struct Webauthn<D, C> {
+    /// Relying Party configuration
+    config: Config,
+
+    /// Where registered devices are stored
+    devices: D,
+
+    /// Where issued challenges are stored
+    challenges: C,
+
+    /// Receives the outcome of every ceremony validated by the async ceremonies
+    sink: Option<Arc<dyn AsyncEventSink>>,
+}
+
+impl<D, C> Webauthn<D, C>
+where
+    C: AsyncChallengeStore,
+{
+    /// Creates a new ceremony runner
+    ///
+    /// # Arguments
+    /// * `config` - Relying Party configuration
+    /// * `devices` - Store for registered devices
+    /// * `challenges` - Store for issued challenges
+    pub fn new(config: Config, devices: D, challenges: C) -> Webauthn<D, C> {
+        Webauthn {
+            config,
+            devices,
+            challenges,
+            sink: None,
+        }
+    }
+
+    /// Attaches a sink the async ceremonies deliver their outcome to, awaiting it before
+    /// they return.  Events are still delivered to the process-wide sink as well (see
+    /// [`events::set_sink`](crate::events::set_sink))
+    ///
+    /// # Arguments
+    /// * `sink` - Receives the outcome of every validated response
+    pub fn event_sink<S: AsyncEventSink + 'static>(mut self, sink: S) -> Self {
+        self.sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Returns the Relying Party configuration
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the store for registered devices
+    pub fn devices(&self) -> &D {
+        &self.devices
+    }
+
+    /// Returns the store for issued challenges
+    pub fn challenges(&self) -> &C {
+        &self.challenges
+    }
+
+    /// Builds a new register request for a user and saves the challenge for the session
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `user` - User registering a new device
+    pub async fn start_registration<U>(
+        &self,
+        session: &str,
+        user: &U,
+    ) -> Result<RegisterRequest, Error>
+    where
+        U: WebAuthnUser,
+    {
+        let req = RegisterRequest::new(&self.config, user);
+        self.challenges.insert(session, req.challenge()).await?;
+        Ok(req)
+    }
+
+    /// Validates a register response and saves the new device.  The CRLs the
+    /// configured [`RevocationChecker`](super::revocation::RevocationChecker) needs are
+    /// fetched with its async fetcher first
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `user` - User registering a new device
+    /// * `form` - Response received from the client
+    pub async fn finish_registration<U>(
+        &self,
+        session: &str,
+        user: &U,
+        form: Response,
+    ) -> Result<RegistrationResult, Error>
+    where
+        U: WebAuthnUser,
+        D: AsyncDeviceStore<U>,
+    {
+        let challenge = self.take_challenge(session).await?;
+        if let Some(checker) = self.config.revocation_checker() {
+            checker
+                .prefetch(&form.attestation_certificates(), now())
+                .await;
+        }
+        let result = webauthn::register(form, &self.config, challenge);
+        self.notify(registration_event(&result)).await;
+
+        let result = result?;
+        self.devices.save(user, result.device().clone()).await?;
+        Ok(result)
+    }
+
+    /// Builds a new authenticate request for a user's registered devices and saves
+    /// the challenge for the session
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `user` - User attempting to login
+    pub async fn start_login<U>(
+        &self,
+        session: &str,
+        user: &U,
+    ) -> Result<AuthenticateRequest, Error>
+    where
+        U: WebAuthnUser,
+        D: AsyncDeviceStore<U>,
+    {
+        let devices = self.devices.devices(user).await?;
+        let req = AuthenticateRequest::new(&self.config, devices);
+        self.challenges.insert(session, req.challenge()).await?;
+        Ok(req)
+    }
+
+    /// Validates an authenticate response against the user's registered devices and
+    /// updates the device's signature counter
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `user` - User attempting to login
+    /// * `form` - Response received from the client
+    pub async fn finish_login<U>(
+        &self,
+        session: &str,
+        user: &U,
+        form: Response,
+    ) -> Result<AuthenticationResult, Error>
+    where
+        U: WebAuthnUser,
+        D: AsyncDeviceStore<U>,
+    {
+        let challenge = self.take_challenge(session).await?;
+        let devices = self.devices.devices(user).await?;
+        let id = form.raw_id().to_vec();
+        let result = webauthn::authenticate(form, &self.config, challenge, user, &devices);
+        self.notify(authentication_event(user, &id, &result)).await;
+
+        let result = result?;
+        self.devices.update_count(user, &id, result.count()).await?;
+        Ok(result)
+    }
+
+    /// Returns a view of the runner whose ceremonies run on the calling thread against
+    /// blocking stores, for applications without an async runtime
+    pub fn blocking(&self) -> Blocking<'_, D, C> {
+        Blocking { auth: self }
+    }
+
+    /// Delivers an event to the attached sink, if any
+    async fn notify(&self, event: AuthEvent) {
+        if let Some(sink) = &self.sink {
+            sink.emit(&event).await;
+        }
+    }
+
+    /// Removes and returns the challenge issued to the session, marking it as consumed
+    /// so it can never be validated again
+    async fn take_challenge(&self, session: &str) -> Result<String, Error> {
+        let challenge = self
+            .challenges
+            .remove(session)
+            .await?
+            .ok_or(Error::MissingChallenge)?;
+        self.challenges
+            .consume(&challenge, now() + DEFAULT_TTL)
+            .await?;
+        Ok(challenge)
+    }
+}
+
+/// Runs the ceremonies of a [`Webauthn`] on the calling thread, see [`Webauthn::blocking`].
+///
+/// Only blocking stores ([`ChallengeStore`] and [`DeviceStore`]) can be used through this
+/// view.  Events reach the process-wide sink, but not the sink attached with
+/// [`Webauthn::event_sink`], which only the async ceremonies await.
+pub struct Blocking<'a, D, C> {
+    auth: &'a Webauthn<D, C>,
+}
+
+impl<'a, D, C> Blocking<'a, D, C>
+where
+    C: ChallengeStore,
+{
+    /// Blocking version of [`Webauthn::start_registration`]
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `user` - User registering a new device
+    pub fn start_registration<U>(&self, session: &str, user: &U) -> Result<RegisterRequest, Error>
+    where
+        U: WebAuthnUser,
+    {
+        let req = RegisterRequest::new(&self.auth.config, user);
+        ChallengeStore::insert(&self.auth.challenges, session, req.challenge())?;
+        Ok(req)
+    }
+
+    /// Blocking version of [`Webauthn::finish_registration`]
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `user` - User registering a new device
+    /// * `form` - Response received from the client
+    pub fn finish_registration<U>(
+        &self,
+        session: &str,
+        user: &U,
+        form: Response,
+    ) -> Result<RegistrationResult, Error>
+    where
+        U: WebAuthnUser,
+        D: DeviceStore<U>,
+    {
+        let challenge = self.take_challenge(session)?;
+        let result = webauthn::register(form, &self.auth.config, challenge)?;
+        DeviceStore::save(&self.auth.devices, user, result.device().clone())?;
+        Ok(result)
+    }
+
+    /// Blocking version of [`Webauthn::start_login`]
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `user` - User attempting to login
+    pub fn start_login<U>(&self, session: &str, user: &U) -> Result<AuthenticateRequest, Error>
+    where
+        U: WebAuthnUser,
+        D: DeviceStore<U>,
+    {
+        let devices = DeviceStore::devices(&self.auth.devices, user)?;
+        let req = AuthenticateRequest::new(&self.auth.config, devices);
+        ChallengeStore::insert(&self.auth.challenges, session, req.challenge())?;
+        Ok(req)
+    }
+
+    /// Blocking version of [`Webauthn::finish_login`]
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `user` - User attempting to login
+    /// * `form` - Response received from the client
+    pub fn finish_login<U>(
+        &self,
+        session: &str,
+        user: &U,
+        form: Response,
+    ) -> Result<AuthenticationResult, Error>
+    where
+        U: WebAuthnUser,
+        D: DeviceStore<U>,
+    {
+        let challenge = self.take_challenge(session)?;
+        let devices = DeviceStore::devices(&self.auth.devices, user)?;
+        let id = form.raw_id().to_vec();
+        let result = webauthn::authenticate(form, &self.auth.config, challenge, user, &devices)?;
+        DeviceStore::update_count(&self.auth.devices, user, &id, result.count())?;
+        Ok(result)
+    }
+
+    /// Blocking version of [`Webauthn::take_challenge`]
+    fn take_challenge(&self, session: &str) -> Result<String, Error> {
+        let challenges = &self.auth.challenges;
+        let challenge =
+            ChallengeStore::remove(challenges, session)?.ok_or(Error::MissingChallenge)?;
+        ChallengeStore::consume(challenges, &challenge, now() + DEFAULT_TTL)?;
+        Ok(challenge)
+    }
+}
+
+/// Describes the outcome of a registration for the event sink
+fn registration_event(result: &Result<RegistrationResult, Error>) -> AuthEvent {
+    match result {
+        Ok(result) => AuthEvent::RegistrationSucceeded {
+            credential: result.device().id().to_vec(),
+        },
+        Err(e) => AuthEvent::RegistrationFailed {
+            reason: e.to_string(),
+        },
+    }
+}
+
+/// Describes the outcome of an authentication for the event sink
+fn authentication_event<U: WebAuthnUser>(
+    user: &U,
+    credential: &[u8],
+    result: &Result<AuthenticationResult, Error>,
+) -> AuthEvent {
+    match result {
+        Ok(_) => AuthEvent::AssertionSucceeded {
+            user: user.id().to_vec(),
+            credential: credential.to_vec(),
+        },
+        Err(e) => AuthEvent::AssertionFailed {
+            user: user.id().to_vec(),
+            reason: e.to_string(),
+        },
+    }
+}
+
+/// Generates a new random session id
+pub fn new_session_id() -> String {
+    let mut id = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut id);
+    base64::encode_config(id, base64::URL_SAFE_NO_PAD)
+}
+
+/// Marks a client as logged in by a user under a freshly generated session id, removing
+/// the session the ceremony ran on.  Keeping the client's session id would let an attacker
+/// who planted it (e.g., through a cookie injection) share the authenticated session.
+/// Returns the new session id, which must be sent to the client with
+/// [`set_session_cookie`]
+///
+/// # Arguments
+/// * `sessions` - Where authenticated sessions are stored
+/// * `session` - Session id the login ceremony ran on
+/// * `user` - Id of the user that logged in
+pub fn rotate_session<S>(sessions: &S, session: &str, user: Vec<u8>) -> Result<String, Error>
+where
+    S: SessionStore + ?Sized,
+{
+    let rotated = new_session_id();
+    sessions.remove(session)?;
+    sessions.insert(&rotated, user)?;
+    Ok(rotated)
+}
+
+/// Finds the session id in the values of a request's `Cookie` headers
+///
+/// # Arguments
+/// * `headers` - Values of every `Cookie` header sent with the request
+pub fn session_cookie<'a, I>(headers: I) -> Option<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    headers
+        .into_iter()
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, value)| *name == SESSION_COOKIE && !value.is_empty())
+        .map(|(_, value)| value.to_owned())
+}
+
+/// Builds the `Set-Cookie` header value that assigns a session id to a client
+///
+/// # Arguments
+/// * `session` - Newly generated session id
+pub fn set_session_cookie(session: &str) -> String {
+    format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict",
+        SESSION_COOKIE, session
+    )
+}
+
+/// Returns the HTTP status code that best describes an error, see [`Error::http_status`]
+///
+/// # Arguments
+/// * `e` - Error to describe
+pub fn http_status(e: &Error) -> u16 {
+    e.http_status()
+}
+
+/// Builds the JSON body describing an error, with its display message and stable code
+///
+/// # Arguments
+/// * `e` - Error to describe
+pub fn error_body(e: &Error) -> serde_json::Value {
+    serde_json::json!({ "error": e.to_string(), "code": e.code() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::SinkFuture,
+        webauthn::{
+            store::{MemoryChallengeStore, MemoryDeviceStore, MemorySessionStore, StoreFuture},
+            Device,
+        },
+    };
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::Mutex,
+        task::{Context, Poll},
+    };
+
+    struct TestUser;
+
+    impl WebAuthnUser for TestUser {
+        type Conn = ();
+
+        fn id(&self) -> &[u8] {
+            &[0, 1, 2, 3]
+        }
+
+        fn name(&self) -> &str {
+            "user"
+        }
+
+        fn fetch_devices(&self, _: &()) -> Vec<Device> {
+            vec![]
+        }
+    }
+
+    /// Pending once before completing, like a store waiting on the network
+    struct YieldNow(bool);
+
+    impl Future for YieldNow {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    /// An async challenge store backed by a blocking one
+    struct AsyncStore(MemoryChallengeStore);
+
+    impl AsyncChallengeStore for AsyncStore {
+        fn insert<'a>(&'a self, session: &'a str, challenge: String) -> StoreFuture<'a, ()> {
+            Box::pin(async move {
+                YieldNow(false).await;
+                ChallengeStore::insert(&self.0, session, challenge)
+            })
+        }
+
+        fn remove<'a>(&'a self, session: &'a str) -> StoreFuture<'a, Option<String>> {
+            Box::pin(async move {
+                YieldNow(false).await;
+                ChallengeStore::remove(&self.0, session)
+            })
+        }
+
+        fn consume<'a>(&'a self, challenge: &'a str, expires: u64) -> StoreFuture<'a, ()> {
+            Box::pin(async move {
+                YieldNow(false).await;
+                ChallengeStore::consume(&self.0, challenge, expires)
+            })
+        }
+    }
+
+    /// An async event sink recording the events it receives
+    #[derive(Clone, Default)]
+    struct AsyncSink(Arc<Mutex<Vec<AuthEvent>>>);
+
+    impl AsyncEventSink for AsyncSink {
+        fn emit<'a>(&'a self, event: &'a AuthEvent) -> SinkFuture<'a> {
+            Box::pin(async move {
+                YieldNow(false).await;
+                self.0.lock().unwrap().push(event.clone());
+            })
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn async_runner_awaits_async_stores() {
+        let auth = Webauthn::new(
+            Config::new("https://app.example.com"),
+            MemoryDeviceStore::new(),
+            AsyncStore(MemoryChallengeStore::new()),
+        );
+
+        block_on(async {
+            let req = auth.start_registration("session", &TestUser).await.unwrap();
+            assert_eq!(
+                auth.take_challenge("session").await.unwrap(),
+                req.challenge()
+            );
+            assert!(matches!(
+                auth.take_challenge("session").await,
+                Err(Error::MissingChallenge)
+            ));
+
+            // a challenge is only accepted once, even if the store hands it out again
+            auth.challenges()
+                .insert("session", req.challenge())
+                .await
+                .unwrap();
+            assert!(matches!(
+                auth.take_challenge("session").await,
+                Err(Error::ReplayedChallenge)
+            ));
+        });
+    }
+
+    #[test]
+    fn async_runner_awaits_event_sink() {
+        let sink = AsyncSink::default();
+        let auth = Webauthn::new(
+            Config::new("https://app.example.com"),
+            MemoryDeviceStore::new(),
+            MemoryChallengeStore::new(),
+        )
+        .event_sink(sink.clone());
+        let form: Response = serde_json::from_value(serde_json::json!({
+            "id": "AAAA",
+            "rawId": "AAAA",
+            "type": "public-key",
+            "response": { "clientDataJSON": "e30", "attestationObject": "oA" },
+        }))
+        .unwrap();
+
+        block_on(async {
+            auth.start_registration("session", &TestUser).await.unwrap();
+            auth.finish_registration("session", &TestUser, form)
+                .await
+                .unwrap_err();
+        });
+        assert!(matches!(
+            sink.0.lock().unwrap().as_slice(),
+            [AuthEvent::RegistrationFailed { .. }]
+        ));
+    }
+
+    #[test]
+    fn blocking_runner_uses_blocking_stores() {
+        let auth = Webauthn::new(
+            Config::new("https://app.example.com"),
+            MemoryDeviceStore::new(),
+            MemoryChallengeStore::new(),
+        );
+        let blocking = auth.blocking();
+
+        let req = blocking.start_registration("session", &TestUser).unwrap();
+        assert_eq!(blocking.take_challenge("session").unwrap(), req.challenge());
+        assert!(matches!(
+            blocking.take_challenge("session"),
+            Err(Error::MissingChallenge)
+        ));
+
+        ChallengeStore::insert(auth.challenges(), "session", req.challenge()).unwrap();
+        assert!(matches!(
+            blocking.take_challenge("session"),
+            Err(Error::ReplayedChallenge)
+        ));
+    }
+
+    #[test]
+    fn login_rotates_session() {
+        let sessions = MemorySessionStore::new();
+        sessions.insert("planted", vec![9]).unwrap();
+
+        let rotated = rotate_session(&sessions, "planted", vec![0, 1, 2, 3]).unwrap();
+        assert_ne!(rotated, "planted");
+        assert_eq!(sessions.get("planted").unwrap(), None);
+        assert_eq!(sessions.get(&rotated).unwrap(), Some(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn session_cookie_is_read_from_headers() {
+        let headers = ["theme=dark", "lang=en; X-WebAuthn-Session=abc123"];
+        assert_eq!(
+            session_cookie(headers.iter().copied()).as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(
+            session_cookie(vec!["theme=dark; X-WebAuthn-Session="]),
+            None
+        );
+    }
+}
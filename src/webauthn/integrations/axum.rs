@@ -0,0 +1,283 @@
+//! Axum extractors, middleware and routes
+//!
+//! Provides a [`FromRequest`] extractor for [`Response`], a [`Session`] extractor, a
+//! [`SessionLayer`] that assigns each client an opaque session id (stored in the
+//! `X-WebAuthn-Session` cookie) and [`webauthn_router`] which serves the register and
+//! login ceremonies backed by the [`ChallengeStore`] and [`DeviceStore`] traits.  A
+//! successful login moves the client to a new session id, which the [`SessionStore`]
+//! maps to the id of the user that logged in.
+//!
+//! The user is resolved by the application: any type implementing both [`WebAuthnUser`]
+//! and [`FromRequestParts`] can be used with the router.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::{
+//!     integrations::{axum::webauthn_router, Webauthn},
+//!     store::{MemoryChallengeStore, MemoryDeviceStore, MemorySessionStore},
+//!     Config,
+//! };
+//! use std::sync::Arc;
+//!
+//! let auth = Arc::new(Webauthn::new(
+//!     Config::new("https://app.example.com"),
+//!     MemoryDeviceStore::new(),
+//!     MemoryChallengeStore::new(),
+//! ));
+//!
+//! let app = axum::Router::new().nest(
+//!     "/fido",
+//!     webauthn_router::<User, _, _, _>(auth, MemorySessionStore::new()),
+//! );
+//! ```
+
+use super::{
+    error_body, http_status, new_session_id, rotate_session, session_cookie, set_session_cookie,
+    Session, Webauthn,
+};
+use crate::webauthn::{
+    store::{AsyncChallengeStore, AsyncDeviceStore, SessionStore},
+    AuthenticateRequest, Error, RegisterRequest, Response, WebAuthnUser,
+};
+use ::axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request, State},
+    http::{
+        header::{COOKIE, SET_COOKIE},
+        request::Parts,
+        HeaderValue, StatusCode,
+    },
+    response::{AppendHeaders, IntoResponse, Response as HttpResponse},
+    routing::get,
+    Extension, Json, Router,
+};
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Session
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Session>()
+            .cloned()
+            .ok_or(Error::MissingSession)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for Response
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let body = Bytes::from_request(req, state).await.map_err(|e| {
+            Error::JsonError(serde_json::Error::io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                e.body_text(),
+            )))
+        })?;
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+impl IntoResponse for RegisterRequest {
+    fn into_response(self) -> HttpResponse {
+        Json(self).into_response()
+    }
+}
+
+impl IntoResponse for AuthenticateRequest {
+    fn into_response(self) -> HttpResponse {
+        Json(self).into_response()
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> HttpResponse {
+        let status =
+            StatusCode::from_u16(http_status(&self)).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = error_body(&self);
+        (status, Json(body)).into_response()
+    }
+}
+
+/// Layer that assigns each client a session id, see [`SessionService`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SessionLayer;
+
+impl SessionLayer {
+    pub fn new() -> SessionLayer {
+        SessionLayer
+    }
+}
+
+impl<S> Layer<S> for SessionLayer {
+    type Service = SessionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SessionService { inner }
+    }
+}
+
+/// Reads the client's session id from the `X-WebAuthn-Session` cookie (generating a
+/// new one if the client doesn't have one) and makes it available to handlers through
+/// the [`Session`] extractor
+#[derive(Clone, Debug)]
+pub struct SessionService<S> {
+    inner: S,
+}
+
+impl<S, B, RB> Service<::axum::http::Request<B>> for SessionService<S>
+where
+    S: Service<::axum::http::Request<B>, Response = ::axum::http::Response<RB>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: ::axum::http::Request<B>) -> Self::Future {
+        let cookies = req
+            .headers()
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok());
+
+        let (session, cookie) = match session_cookie(cookies) {
+            Some(session) => (session, None),
+            None => {
+                let session = new_session_id();
+                let cookie = HeaderValue::from_str(&set_session_cookie(&session)).ok();
+                (session, cookie)
+            }
+        };
+
+        req.extensions_mut().insert(Session(session));
+        let fut = self.inner.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if let Some(cookie) = cookie {
+                res.headers_mut().append(SET_COOKIE, cookie);
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// Builds a router serving the four standard WebAuthn endpoints:
+///
+/// * `GET /register` - Returns a new [`RegisterRequest`] for the user
+/// * `POST /register` - Validates a register [`Response`] and saves the new device
+/// * `GET /login` - Returns a new [`AuthenticateRequest`] for the user
+/// * `POST /login` - Validates an authenticate [`Response`], updates the device and
+///   moves the client to a new session (see [`rotate_session`]) recording the user in
+///   `sessions`
+///
+/// The routes are wrapped in a [`SessionLayer`] and can be nested anywhere in an
+/// application's router.
+///
+/// # Arguments
+/// * `auth` - Configuration and stores used to run the ceremonies
+/// * `sessions` - Where authenticated sessions are stored
+pub fn webauthn_router<U, D, C, S>(auth: Arc<Webauthn<D, C>>, sessions: S) -> Router
+where
+    U: WebAuthnUser + FromRequestParts<Arc<Webauthn<D, C>>> + Send + Sync + 'static,
+    D: AsyncDeviceStore<U> + 'static,
+    C: AsyncChallengeStore + 'static,
+    S: SessionStore + 'static,
+{
+    Router::new()
+        .route(
+            "/register",
+            get(register_request::<U, D, C>).post(register::<U, D, C>),
+        )
+        .route(
+            "/login",
+            get(login_request::<U, D, C>).post(login::<U, D, C, S>),
+        )
+        .layer(Extension(Arc::new(sessions)))
+        .layer(SessionLayer::new())
+        .with_state(auth)
+}
+
+async fn register_request<U, D, C>(
+    State(auth): State<Arc<Webauthn<D, C>>>,
+    Session(session): Session,
+    user: U,
+) -> Result<RegisterRequest, Error>
+where
+    U: WebAuthnUser + Send + Sync,
+    C: AsyncChallengeStore,
+{
+    auth.start_registration(&session, &user).await
+}
+
+async fn register<U, D, C>(
+    State(auth): State<Arc<Webauthn<D, C>>>,
+    Session(session): Session,
+    user: U,
+    form: Response,
+) -> Result<(), Error>
+where
+    U: WebAuthnUser + Send + Sync,
+    D: AsyncDeviceStore<U>,
+    C: AsyncChallengeStore,
+{
+    auth.finish_registration(&session, &user, form)
+        .await
+        .map(|_| ())
+}
+
+async fn login_request<U, D, C>(
+    State(auth): State<Arc<Webauthn<D, C>>>,
+    Session(session): Session,
+    user: U,
+) -> Result<AuthenticateRequest, Error>
+where
+    U: WebAuthnUser + Send + Sync,
+    D: AsyncDeviceStore<U>,
+    C: AsyncChallengeStore,
+{
+    auth.start_login(&session, &user).await
+}
+
+async fn login<U, D, C, S>(
+    State(auth): State<Arc<Webauthn<D, C>>>,
+    Extension(sessions): Extension<Arc<S>>,
+    Session(session): Session,
+    user: U,
+    form: Response,
+) -> Result<impl IntoResponse, Error>
+where
+    U: WebAuthnUser + Send + Sync,
+    D: AsyncDeviceStore<U>,
+    C: AsyncChallengeStore,
+    S: SessionStore,
+{
+    auth.finish_login(&session, &user, form).await?;
+    let rotated = rotate_session(&*sessions, &session, user.id().to_vec())?;
+    Ok(AppendHeaders([(SET_COOKIE, set_session_cookie(&rotated))]))
+}
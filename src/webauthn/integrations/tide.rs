@@ -0,0 +1,253 @@
+//! Tide middleware and endpoints
+//!
+//! Provides a [`SessionMiddleware`] that assigns each client an opaque session id
+//! (stored in the `X-WebAuthn-Session` cookie) and [`webauthn_server`] which serves the
+//! register and login ceremonies backed by the [`ChallengeStore`] and [`DeviceStore`]
+//! traits.
+//!
+//! The user is resolved by the application: the endpoints read the current user from
+//! the request extensions, so an authentication middleware must call
+//! `Request::set_ext` with a [`WebAuthnUser`] before the endpoints are reached.
+//! Requests without a user are rejected with `401 Unauthorized`.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::{
+//!     integrations::{tide::webauthn_server, Webauthn},
+//!     store::{MemoryChallengeStore, MemoryDeviceStore},
+//!     Config,
+//! };
+//! use std::sync::Arc;
+//!
+//! let auth = Arc::new(Webauthn::new(
+//!     Config::new("https://app.example.com"),
+//!     MemoryDeviceStore::new(),
+//!     MemoryChallengeStore::new(),
+//! ));
+//!
+//! let mut app = tide::new();
+//! app.with(LoadUser);
+//! app.at("/fido").nest(webauthn_server::<User, _, _>(auth));
+//! ```
+
+use super::{
+    error_body, http_status, new_session_id, session_cookie, set_session_cookie, Session, Webauthn,
+};
+use crate::webauthn::{
+    store::{AsyncChallengeStore, AsyncDeviceStore},
+    Error, Response, WebAuthnUser,
+};
+use ::tide::{
+    http::{
+        headers::{COOKIE, SET_COOKIE},
+        mime,
+    },
+    utils::async_trait,
+    Body, Middleware, Next, Request, Server, StatusCode,
+};
+use serde::Serialize;
+use std::{convert::TryFrom, io, sync::Arc};
+
+/// Type of the state used by the endpoints returned from [`webauthn_server`]
+pub type State<D, C> = Arc<Webauthn<D, C>>;
+
+/// Reads the client's session id from the `X-WebAuthn-Session` cookie (generating a
+/// new one if the client doesn't have one) and stores it in the request extensions as
+/// a [`Session`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SessionMiddleware;
+
+impl SessionMiddleware {
+    pub fn new() -> SessionMiddleware {
+        SessionMiddleware
+    }
+}
+
+#[async_trait]
+impl<S> Middleware<S> for SessionMiddleware
+where
+    S: Clone + Send + Sync + 'static,
+{
+    async fn handle(&self, mut req: Request<S>, next: Next<'_, S>) -> ::tide::Result {
+        let existing = req
+            .header(COOKIE)
+            .and_then(|values| session_cookie(values.iter().map(|value| value.as_str())));
+
+        let (session, cookie) = match existing {
+            Some(session) => (session, None),
+            None => {
+                let session = new_session_id();
+                let cookie = set_session_cookie(&session);
+                (session, Some(cookie))
+            }
+        };
+
+        req.set_ext(Session(session));
+        let mut res = next.run(req).await;
+        if let Some(cookie) = cookie {
+            res.append_header(SET_COOKIE, cookie);
+        }
+        Ok(res)
+    }
+}
+
+/// Builds a server serving the four standard WebAuthn endpoints:
+///
+/// * `GET /register` - Returns a new register request for the user
+/// * `POST /register` - Validates a register [`Response`] and saves the new device
+/// * `GET /login` - Returns a new authenticate request for the user
+/// * `POST /login` - Validates an authenticate [`Response`] and updates the device
+///
+/// The server runs a [`SessionMiddleware`] and is intended to be nested in an
+/// application's server (i.e., `app.at("/fido").nest(server)`)
+///
+/// # Arguments
+/// * `auth` - Configuration and stores used to run the ceremonies
+pub fn webauthn_server<U, D, C>(auth: State<D, C>) -> Server<State<D, C>>
+where
+    U: WebAuthnUser + Send + Sync + 'static,
+    D: AsyncDeviceStore<U> + 'static,
+    C: AsyncChallengeStore + 'static,
+{
+    let mut server = ::tide::with_state(auth);
+    server.with(SessionMiddleware::new());
+    server
+        .at("/register")
+        .get(register_request::<U, D, C>)
+        .post(register::<U, D, C>);
+    server
+        .at("/login")
+        .get(login_request::<U, D, C>)
+        .post(login::<U, D, C>);
+    server
+}
+
+async fn register_request<U, D, C>(req: Request<State<D, C>>) -> ::tide::Result
+where
+    U: WebAuthnUser + Send + Sync + 'static,
+    C: AsyncChallengeStore,
+{
+    let user = match req.ext::<U>() {
+        Some(user) => user,
+        None => return Ok(StatusCode::Unauthorized.into()),
+    };
+
+    let result = async {
+        let session = session(&req)?;
+        json(&req.state().start_registration(&session, user).await?)
+    }
+    .await;
+
+    respond(result)
+}
+
+async fn register<U, D, C>(mut req: Request<State<D, C>>) -> ::tide::Result
+where
+    U: WebAuthnUser + Send + Sync + 'static,
+    D: AsyncDeviceStore<U>,
+    C: AsyncChallengeStore,
+{
+    let form = body(&mut req).await;
+    let user = match req.ext::<U>() {
+        Some(user) => user,
+        None => return Ok(StatusCode::Unauthorized.into()),
+    };
+
+    let result = async {
+        let form = form?;
+        let session = session(&req)?;
+        req.state()
+            .finish_registration(&session, user, form)
+            .await
+            .map(|_| Body::empty())
+    }
+    .await;
+
+    respond(result)
+}
+
+async fn login_request<U, D, C>(req: Request<State<D, C>>) -> ::tide::Result
+where
+    U: WebAuthnUser + Send + Sync + 'static,
+    D: AsyncDeviceStore<U>,
+    C: AsyncChallengeStore,
+{
+    let user = match req.ext::<U>() {
+        Some(user) => user,
+        None => return Ok(StatusCode::Unauthorized.into()),
+    };
+
+    let result = async {
+        let session = session(&req)?;
+        json(&req.state().start_login(&session, user).await?)
+    }
+    .await;
+
+    respond(result)
+}
+
+async fn login<U, D, C>(mut req: Request<State<D, C>>) -> ::tide::Result
+where
+    U: WebAuthnUser + Send + Sync + 'static,
+    D: AsyncDeviceStore<U>,
+    C: AsyncChallengeStore,
+{
+    let form = body(&mut req).await;
+    let user = match req.ext::<U>() {
+        Some(user) => user,
+        None => return Ok(StatusCode::Unauthorized.into()),
+    };
+
+    let result = async {
+        let form = form?;
+        let session = session(&req)?;
+        req.state()
+            .finish_login(&session, user, form)
+            .await
+            .map(|_| Body::empty())
+    }
+    .await;
+
+    respond(result)
+}
+
+/// Returns the session id set by the [`SessionMiddleware`]
+fn session<S>(req: &Request<S>) -> Result<String, Error> {
+    req.ext::<Session>()
+        .map(|session| session.0.clone())
+        .ok_or(Error::MissingChallenge)
+}
+
+/// Reads and parses a [`Response`] from the request body
+async fn body<S>(req: &mut Request<S>) -> Result<Response, Error> {
+    let body = req.body_bytes().await.map_err(|e| {
+        Error::JsonError(serde_json::Error::io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            e.to_string(),
+        )))
+    })?;
+
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Serializes a value into a JSON body
+fn json<T: Serialize>(value: &T) -> Result<Body, Error> {
+    let mut body = Body::from(serde_json::to_vec(value)?);
+    body.set_mime(mime::JSON);
+    Ok(body)
+}
+
+/// Converts the result of a ceremony into a tide response, mapping errors to a JSON
+/// body with the matching status code
+fn respond(result: Result<Body, Error>) -> ::tide::Result {
+    match result {
+        Ok(body) => Ok(::tide::Response::builder(StatusCode::Ok).body(body).build()),
+        Err(e) => {
+            let status =
+                StatusCode::try_from(http_status(&e)).unwrap_or(StatusCode::InternalServerError);
+            let body = error_body(&e);
+            Ok(::tide::Response::builder(status).body(body).build())
+        }
+    }
+}
@@ -0,0 +1,468 @@
+//! Framework-agnostic tower middleware
+//!
+//! [`WebauthnLayer`] wraps any `http` based tower service (hyper, axum, tonic, warp,
+//! ...).  Requests to the configured register and login paths are intercepted and the
+//! ceremonies are run against the [`ChallengeStore`] and [`DeviceStore`] traits:
+//!
+//! * `GET <register>` - Returns a new register request for the user
+//! * `POST <register>` - Validates a register [`Response`] and saves the new device
+//! * `GET <login>` - Returns a new authenticate request for the user
+//! * `POST <login>` - Validates an authenticate [`Response`], updates the device and
+//!   moves the client to a new, authenticated session (see [`rotate_session`])
+//!
+//! Request bodies larger than [`BODY_LIMIT`] (see [`WebauthnLayer::body_limit`]) are
+//! rejected with `413 Payload Too Large`.
+//!
+//! Every other request is passed to the inner service with the client's [`Session`]
+//! and, if the session has logged in, the [`Authenticated`] user id inserted into the
+//! request extensions.
+//!
+//! The user a ceremony is run for is determined by a [`UserResolver`], which can be
+//! any closure taking the request's `Parts`.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::{
+//!     integrations::{tower::WebauthnLayer, Webauthn},
+//!     store::{MemoryChallengeStore, MemoryDeviceStore, MemorySessionStore},
+//!     Config, Error,
+//! };
+//! use std::sync::Arc;
+//!
+//! let auth = Arc::new(Webauthn::new(
+//!     Config::new("https://app.example.com"),
+//!     MemoryDeviceStore::new(),
+//!     MemoryChallengeStore::new(),
+//! ));
+//!
+//! let layer = WebauthnLayer::new(
+//!     auth,
+//!     |parts: &http::request::Parts| -> Result<Option<User>, Error> { User::from_parts(parts) },
+//!     MemorySessionStore::new(),
+//! )
+//! .login_path("/auth/login");
+//!
+//! let service = tower::ServiceBuilder::new().layer(layer).service(app);
+//! ```
+
+use super::{
+    error_body, http_status, new_session_id, rotate_session, session_cookie, set_session_cookie,
+    Session, Webauthn,
+};
+use crate::webauthn::{
+    store::{AsyncChallengeStore, AsyncDeviceStore, SessionStore},
+    Error, Response, WebAuthnUser,
+};
+use ::http::{
+    header::{CONTENT_TYPE, COOKIE, SET_COOKIE},
+    request::Parts,
+    HeaderValue, Method, Request, Response as HttpResponse, StatusCode,
+};
+use http_body::Body;
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use serde::Serialize;
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Default path of the register endpoints
+pub const REGISTER_PATH: &str = "/webauthn/register";
+
+/// Default path of the login endpoints
+pub const LOGIN_PATH: &str = "/webauthn/login";
+
+/// Default maximum size of a ceremony request body, in bytes
+pub const BODY_LIMIT: usize = 64 * 1024;
+
+/// Determines the user a ceremony is being run for
+pub trait UserResolver: Send + Sync {
+    /// The user type
+    type User: WebAuthnUser + Send + Sync;
+
+    /// Returns the user making a request, or `None` if the user is unknown
+    ///
+    /// # Arguments
+    /// * `parts` - Head of the request, with the [`Session`] and [`Authenticated`] user id
+    ///   (if logged in) in its extensions
+    fn resolve(&self, parts: &Parts) -> Result<Option<Self::User>, Error>;
+}
+
+impl<U, F> UserResolver for F
+where
+    U: WebAuthnUser + Send + Sync,
+    F: Fn(&Parts) -> Result<Option<U>, Error> + Send + Sync,
+{
+    type User = U;
+
+    fn resolve(&self, parts: &Parts) -> Result<Option<U>, Error> {
+        self(parts)
+    }
+}
+
+/// Id of the user that logged in on the request's session
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Authenticated(pub Vec<u8>);
+
+/// Layer that applies the [`WebauthnService`] middleware
+pub struct WebauthnLayer<D, C, R, S> {
+    /// Configuration and stores used to run the ceremonies
+    auth: Arc<Webauthn<D, C>>,
+
+    /// Determines the user for a request
+    resolver: Arc<R>,
+
+    /// Where authenticated sessions are stored
+    sessions: Arc<S>,
+
+    /// Path of the register endpoints
+    register: Arc<str>,
+
+    /// Path of the login endpoints
+    login: Arc<str>,
+
+    /// Maximum size of a ceremony request body, in bytes
+    body_limit: usize,
+}
+
+impl<D, C, R, S> WebauthnLayer<D, C, R, S>
+where
+    C: AsyncChallengeStore,
+    R: UserResolver,
+    S: SessionStore,
+{
+    /// Creates a new layer intercepting the default paths
+    ///
+    /// # Arguments
+    /// * `auth` - Configuration and stores used to run the ceremonies
+    /// * `resolver` - Determines the user for a request
+    /// * `sessions` - Where authenticated sessions are stored
+    pub fn new(auth: Arc<Webauthn<D, C>>, resolver: R, sessions: S) -> WebauthnLayer<D, C, R, S> {
+        WebauthnLayer {
+            auth,
+            resolver: Arc::new(resolver),
+            sessions: Arc::new(sessions),
+            register: Arc::from(REGISTER_PATH),
+            login: Arc::from(LOGIN_PATH),
+            body_limit: BODY_LIMIT,
+        }
+    }
+
+    /// Sets the path of the register endpoints
+    ///
+    /// # Arguments
+    /// * `path` - Request path to intercept (e.g., `/fido/register`)
+    pub fn register_path<P: Into<String>>(mut self, path: P) -> Self {
+        self.register = Arc::from(path.into());
+        self
+    }
+
+    /// Sets the path of the login endpoints
+    ///
+    /// # Arguments
+    /// * `path` - Request path to intercept (e.g., `/fido/login`)
+    pub fn login_path<P: Into<String>>(mut self, path: P) -> Self {
+        self.login = Arc::from(path.into());
+        self
+    }
+
+    /// Sets the maximum size of a ceremony request body (default: [`BODY_LIMIT`])
+    ///
+    /// # Arguments
+    /// * `bytes` - Largest body accepted, in bytes
+    pub fn body_limit(mut self, bytes: usize) -> Self {
+        self.body_limit = bytes;
+        self
+    }
+
+    /// Runs the ceremony for an intercepted request, returning the JSON body of the
+    /// response and, after a login, the client's new session id
+    async fn ceremony<B>(
+        &self,
+        session: &str,
+        mut parts: Parts,
+        body: B,
+        register: bool,
+    ) -> Result<(Option<Vec<u8>>, Option<String>), Error>
+    where
+        B: Body,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        D: AsyncDeviceStore<R::User>,
+    {
+        self.extend(session, &mut parts)?;
+
+        if parts.method == Method::GET {
+            let user = self.user(&parts)?;
+            let body = if register {
+                json(&self.auth.start_registration(session, &user).await?)?
+            } else {
+                json(&self.auth.start_login(session, &user).await?)?
+            };
+            return Ok((Some(body), None));
+        }
+
+        let body = Limited::new(body, self.body_limit)
+            .collect()
+            .await
+            .map_err(|e| {
+                if e.is::<LengthLimitError>() {
+                    Error::PayloadTooLarge(self.body_limit)
+                } else {
+                    Error::JsonError(serde_json::Error::io(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        e,
+                    )))
+                }
+            })?;
+        let form: Response = serde_json::from_slice(&body.to_bytes())?;
+
+        let user = self.user(&parts)?;
+        if register {
+            self.auth.finish_registration(session, &user, form).await?;
+            Ok((None, None))
+        } else {
+            self.auth.finish_login(session, &user, form).await?;
+            let rotated = rotate_session(&*self.sessions, session, user.id().to_vec())?;
+            Ok((None, Some(rotated)))
+        }
+    }
+
+    /// Inserts the [`Session`] and, if the session has logged in, the [`Authenticated`]
+    /// user id into the request extensions
+    fn extend(&self, session: &str, parts: &mut Parts) -> Result<(), Error> {
+        parts.extensions.insert(Session(session.to_owned()));
+        if let Some(user) = self.sessions.get(session)? {
+            parts.extensions.insert(Authenticated(user));
+        }
+        Ok(())
+    }
+
+    /// Resolves the user for a request, failing if the user is unknown
+    fn user(&self, parts: &Parts) -> Result<R::User, Error> {
+        self.resolver.resolve(parts)?.ok_or(Error::UnknownUser)
+    }
+}
+
+impl<D, C, R, S> Clone for WebauthnLayer<D, C, R, S> {
+    fn clone(&self) -> Self {
+        WebauthnLayer {
+            auth: Arc::clone(&self.auth),
+            resolver: Arc::clone(&self.resolver),
+            sessions: Arc::clone(&self.sessions),
+            register: Arc::clone(&self.register),
+            login: Arc::clone(&self.login),
+            body_limit: self.body_limit,
+        }
+    }
+}
+
+impl<I, D, C, R, S> Layer<I> for WebauthnLayer<D, C, R, S> {
+    type Service = WebauthnService<I, D, C, R, S>;
+
+    fn layer(&self, inner: I) -> Self::Service {
+        WebauthnService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Middleware that runs the WebAuthn ceremonies, see the module documentation
+pub struct WebauthnService<I, D, C, R, S> {
+    inner: I,
+    layer: WebauthnLayer<D, C, R, S>,
+}
+
+impl<I: Clone, D, C, R, S> Clone for WebauthnService<I, D, C, R, S> {
+    fn clone(&self) -> Self {
+        WebauthnService {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<I, D, C, R, S, B, RB> Service<Request<B>> for WebauthnService<I, D, C, R, S>
+where
+    I: Service<Request<B>, Response = HttpResponse<RB>> + Clone + Send + 'static,
+    I::Future: Send + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    RB: From<Vec<u8>> + Send + 'static,
+    D: AsyncDeviceStore<R::User> + 'static,
+    C: AsyncChallengeStore + 'static,
+    R: UserResolver + 'static,
+    S: SessionStore + 'static,
+{
+    type Response = HttpResponse<RB>;
+    type Error = I::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let cookies = req
+            .headers()
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok());
+
+        let (session, mut cookie) = match session_cookie(cookies) {
+            Some(session) => (session, None),
+            None => {
+                let session = new_session_id();
+                let cookie = HeaderValue::from_str(&set_session_cookie(&session)).ok();
+                (session, cookie)
+            }
+        };
+
+        // the service that was driven to readiness is used for this request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            let path = req.uri().path();
+            let register = path == &*layer.register;
+            let intercept = register || path == &*layer.login;
+
+            let mut res = if intercept {
+                let (parts, body) = req.into_parts();
+                if parts.method != Method::GET && parts.method != Method::POST {
+                    response(StatusCode::METHOD_NOT_ALLOWED, None)
+                } else {
+                    match layer.ceremony(&session, parts, body, register).await {
+                        Ok((body, rotated)) => {
+                            if let Some(rotated) = rotated {
+                                cookie = HeaderValue::from_str(&set_session_cookie(&rotated)).ok();
+                            }
+                            response(StatusCode::OK, body)
+                        }
+                        Err(e) => error(e),
+                    }
+                }
+            } else {
+                let (mut parts, body) = req.into_parts();
+                match layer.extend(&session, &mut parts) {
+                    Ok(()) => inner.call(Request::from_parts(parts, body)).await?,
+                    Err(e) => error(e),
+                }
+            };
+
+            if let Some(cookie) = cookie {
+                res.headers_mut().append(SET_COOKIE, cookie);
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// Serializes a value into a JSON body
+fn json<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    Ok(serde_json::to_vec(value)?)
+}
+
+/// Builds a response, marking the body as JSON if one is present
+fn response<RB: From<Vec<u8>>>(status: StatusCode, body: Option<Vec<u8>>) -> HttpResponse<RB> {
+    let json = body.is_some();
+    let mut res = HttpResponse::new(RB::from(body.unwrap_or_default()));
+    *res.status_mut() = status;
+    if json {
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+    res
+}
+
+/// Builds a response describing an error
+fn error<RB: From<Vec<u8>>>(e: Error) -> HttpResponse<RB> {
+    let status = StatusCode::from_u16(http_status(&e)).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let body = error_body(&e).to_string();
+    response(status, Some(body.into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::{
+        store::{MemoryChallengeStore, MemoryDeviceStore, MemorySessionStore},
+        Config, Device,
+    };
+    use http_body_util::Full;
+    use std::{convert::Infallible, future::Ready};
+
+    struct TestUser;
+
+    impl WebAuthnUser for TestUser {
+        type Conn = ();
+
+        fn id(&self) -> &[u8] {
+            &[0, 1, 2, 3]
+        }
+
+        fn name(&self) -> &str {
+            "user"
+        }
+
+        fn fetch_devices(&self, _: &()) -> Vec<Device> {
+            vec![]
+        }
+    }
+
+    /// Answers every request passed through with an empty `200 OK`
+    #[derive(Clone)]
+    struct Ok200;
+
+    impl Service<Request<Full<&'static [u8]>>> for Ok200 {
+        type Response = HttpResponse<Vec<u8>>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Infallible>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: Request<Full<&'static [u8]>>) -> Self::Future {
+            std::future::ready(Ok(HttpResponse::new(vec![])))
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_bodies() {
+        let auth = Arc::new(Webauthn::new(
+            Config::new("https://app.example.com"),
+            MemoryDeviceStore::new(),
+            MemoryChallengeStore::new(),
+        ));
+        let layer = WebauthnLayer::new(
+            auth,
+            |_: &Parts| -> Result<Option<TestUser>, Error> { Ok(Some(TestUser)) },
+            MemorySessionStore::new(),
+        )
+        .body_limit(16);
+        let mut service = layer.layer(Ok200);
+
+        let req = Request::post(LOGIN_PATH)
+            .header(COOKIE, "X-WebAuthn-Session=abc123")
+            .body(Full::new(&b"{\"id\": \"0123456789abcdef\"}"[..]))
+            .unwrap();
+        let res = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(service.call(req))
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["code"], "payload_too_large");
+    }
+}
@@ -70,27 +70,44 @@ pub struct PublicKeyCredential {
 }
 
 /// Different types of connections that authenticators can have
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// [WebAuthn Spec](https://www.w3.org/TR/webauthn-2/#enum-transport)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Transport {
     /// An authenticator connected via USB
-    #[serde(alias = "usb")]
+    #[serde(alias = "Usb")]
     Usb,
 
     /// An authenticator available via NFC
-    #[serde(alias = "nfc")]
+    #[serde(alias = "Nfc")]
     Nfc,
 
     /// An authenticator available via Bluetooth Low Energy (BLE)
-    #[serde(alias = "ble")]
+    #[serde(alias = "Ble")]
     Ble,
 
     /// An authenticator internal to the device (fingerprint, tpm, etc.)
-    #[serde(alias = "internal")]
+    #[serde(alias = "Internal")]
     Internal,
 
     /// An authenticator available via Apple's Lightning port
-    #[serde(alias = "lightning")]
+    #[serde(alias = "Lightning")]
     Lightning,
+
+    /// An authenticator reached through another device, e.g. a phone scanning a QR code
+    #[serde(alias = "Hybrid", alias = "cable")]
+    Hybrid,
+}
+
+impl Transport {
+    /// Parses a transport reported by the client, returning `None` for transports this
+    /// library does not know about (clients may report new ones at any time)
+    ///
+    /// # Arguments
+    /// * `s` - Transport, as returned by `getTransports()`
+    pub fn parse(s: &str) -> Option<Transport> {
+        serde_json::from_value(serde_json::Value::String(s.to_owned())).ok()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -104,16 +121,46 @@ pub struct PublicKeyDescriptor {
     id: Vec<u8>,
 
     /// Hint as to how the client might communicate with the managing authenticator of the public
-    /// key credential the caller is referring to
+    /// key credential the caller is referring to.  Omitted when empty, letting the client
+    /// try every transport
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     transports: Vec<Transport>,
 }
 
 impl PublicKeyDescriptor {
+    /// Creates a descriptor for a credential, without transport hints
+    ///
+    /// # Arguments
+    /// * `id` - Credential id
     pub fn new(id: Vec<u8>) -> PublicKeyDescriptor {
         PublicKeyDescriptor {
             ty: PublicKeyCredentialType::PublicKey,
             id,
-            transports: vec![Transport::Usb],
+            transports: vec![],
         }
     }
+
+    /// Sets the transports the client may use to reach the credential
+    ///
+    /// # Arguments
+    /// * `transports` - Transports reported when the credential was registered
+    pub fn with_transports(mut self, transports: Vec<Transport>) -> Self {
+        self.transports = transports;
+        self
+    }
+
+    /// Returns the credential id of the public key credential
+    pub fn id(&self) -> &[u8] {
+        &self.id
+    }
+
+    /// Returns the transport hints sent to the client
+    pub fn transports(&self) -> &[Transport] {
+        &self.transports
+    }
+
+    /// Removes the transport hints
+    pub(crate) fn clear_transports(&mut self) {
+        self.transports.clear();
+    }
 }
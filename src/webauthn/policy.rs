@@ -0,0 +1,172 @@
+//! Custom validation policy
+//!
+//! A [`CeremonyPolicy`] is consulted at fixed points of the register and authenticate
+//! ceremonies, after the checks required by the specification have passed at that
+//! point.  Each hook can veto the ceremony by returning an error (see
+//! [`Error::policy`]), which lets applications add their own rules (e.g., geo checks,
+//! device reputation, blocking authenticator models) without reimplementing the ceremony.
+//!
+//! The hooks are, in order:
+//!
+//! 1. [`CeremonyPolicy::check_client_data`], after the client data was verified
+//! 2. [`CeremonyPolicy::check_auth_data`], after the authenticator data was verified
+//! 3. [`CeremonyPolicy::check_result`], after every signature was verified, just before
+//!    the response is accepted
+//!
+//! # Example
+//!
+//! ```ignore
+//! struct BlockAaguids(Vec<[u8; 16]>);
+//!
+//! impl CeremonyPolicy for BlockAaguids {
+//!     fn check_auth_data(&self, ceremony: &Ceremony) -> Result<(), Error> {
+//!         match ceremony.aaguid() {
+//!             Some(aaguid) if self.0.contains(aaguid) => Err(Error::policy("blocked authenticator")),
+//!             _ => Ok(()),
+//!         }
+//!     }
+//! }
+//!
+//! let mut cfg = Config::new("https://app.example.com");
+//! cfg.add_policy(BlockAaguids(vec![...]));
+//! ```
+
+use crate::webauthn::{Error, WebAuthnType};
+use std::{fmt, sync::Arc};
+
+/// Hooks invoked while validating a ceremony.  Every hook accepts by default
+pub trait CeremonyPolicy: Send + Sync {
+    /// Called once the client data (type, challenge and origin) was verified
+    ///
+    /// # Arguments
+    /// * `ceremony` - What is known about the ceremony so far
+    fn check_client_data(&self, _ceremony: &Ceremony) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called once the authenticator data (RP ID hash and flags) was verified
+    ///
+    /// # Arguments
+    /// * `ceremony` - What is known about the ceremony so far
+    fn check_auth_data(&self, _ceremony: &Ceremony) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called once the attestation or assertion signature was verified, before the
+    /// response is accepted
+    ///
+    /// # Arguments
+    /// * `ceremony` - Everything verified about the ceremony
+    fn check_result(&self, _ceremony: &Ceremony) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// What has been verified about a ceremony when a policy hook is called
+#[derive(Clone, Debug)]
+pub struct Ceremony<'a> {
+    pub(crate) ty: WebAuthnType,
+    pub(crate) credential_id: &'a [u8],
+    pub(crate) origin: &'a str,
+    pub(crate) cross_origin: bool,
+    pub(crate) top_origin: Option<&'a str>,
+    pub(crate) count: Option<u32>,
+    pub(crate) user_verified: Option<bool>,
+    pub(crate) aaguid: Option<[u8; 16]>,
+}
+
+impl<'a> Ceremony<'a> {
+    /// Returns the kind of ceremony, register (`Create`) or authenticate (`Get`)
+    pub fn ty(&self) -> &WebAuthnType {
+        &self.ty
+    }
+
+    /// Returns the id of the credential being registered or used
+    pub fn credential_id(&self) -> &[u8] {
+        self.credential_id
+    }
+
+    /// Returns the origin the client data was collected for
+    pub fn origin(&self) -> &str {
+        self.origin
+    }
+
+    /// Returns true if the ceremony ran in a cross-origin iframe
+    pub fn cross_origin(&self) -> bool {
+        self.cross_origin
+    }
+
+    /// Returns the origin of the top-level page embedding a cross-origin ceremony, if
+    /// the client reported one
+    pub fn top_origin(&self) -> Option<&str> {
+        self.top_origin
+    }
+
+    /// Returns the signature counter, once the authenticator data has been verified
+    pub fn count(&self) -> Option<u32> {
+        self.count
+    }
+
+    /// Returns whether the authenticator verified the user, once the authenticator
+    /// data has been verified
+    pub fn user_verified(&self) -> Option<bool> {
+        self.user_verified
+    }
+
+    /// Returns the AAGUID identifying the authenticator's model, for registrations
+    /// once the authenticator data has been verified
+    pub fn aaguid(&self) -> Option<&[u8; 16]> {
+        self.aaguid.as_ref()
+    }
+}
+
+/// The policies added to a [`Config`](super::Config), consulted in the order they
+/// were added
+#[derive(Clone, Default)]
+pub struct PolicyChain {
+    policies: Vec<Arc<dyn CeremonyPolicy>>,
+}
+
+impl fmt::Debug for PolicyChain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PolicyChain")
+            .field("len", &self.policies.len())
+            .finish()
+    }
+}
+
+impl PolicyChain {
+    /// Creates a new, empty chain
+    pub fn new() -> PolicyChain {
+        Self::default()
+    }
+
+    /// Adds a policy to the end of the chain
+    ///
+    /// # Arguments
+    /// * `policy` - Policy to consult
+    pub fn push<P: CeremonyPolicy + 'static>(&mut self, policy: P) {
+        self.policies.push(Arc::new(policy));
+    }
+
+    /// Runs the client data hook of every policy, stopping at the first rejection
+    pub(crate) fn check_client_data(&self, ceremony: &Ceremony) -> Result<(), Error> {
+        self.policies
+            .iter()
+            .try_for_each(|p| p.check_client_data(ceremony))
+    }
+
+    /// Runs the authenticator data hook of every policy, stopping at the first rejection
+    pub(crate) fn check_auth_data(&self, ceremony: &Ceremony) -> Result<(), Error> {
+        self.policies
+            .iter()
+            .try_for_each(|p| p.check_auth_data(ceremony))
+    }
+
+    /// Runs the result hook of every policy, stopping at the first rejection
+    pub(crate) fn check_result(&self, ceremony: &Ceremony) -> Result<(), Error> {
+        self.policies
+            .iter()
+            .try_for_each(|p| p.check_result(ceremony))
+    }
+}
@@ -0,0 +1,290 @@
+//! Validation reports
+//!
+//! [`register_with_report`](super::register_with_report) and
+//! [`authenticate_with_report`](super::authenticate_with_report) run the same checks as
+//! [`register`](super::register) and [`authenticate`](super::authenticate), but also
+//! return a [`ValidationReport`] listing every step of the ceremony that ran, its outcome
+//! and the values it compared (e.g., the expected and received origin).  The report is
+//! serializable, so it can be logged or attached to a support ticket when a browser or
+//! authenticator misbehaves.
+//!
+//! [`register_dry_run`](super::register_dry_run) and
+//! [`authenticate_dry_run`](super::authenticate_dry_run) do not stop at the first failed
+//! check: every check that does not depend on an earlier one still runs, so the report
+//! lists every violation at once.  Steps that produce what later steps need (decoding the
+//! client data, parsing the authenticator data, verifying the attestation statement,
+//! looking up the credential) still end the ceremony when they fail.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let (result, report) = webauthn::authenticate_with_report(form, &cfg, challenge, &user, &devices);
+//! if result.is_err() {
+//!     log::info!("login failed: {}", serde_json::to_string(&report)?);
+//! }
+//!
+//! let (_, report) = webauthn::register_dry_run(form, &cfg, challenge);
+//! for step in report.failures() {
+//!     log::info!("{} failed: {}", step.name(), step.error().unwrap_or_default());
+//! }
+//! ```
+
+use crate::webauthn::Error;
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt};
+
+/// Outcome of a single step
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The check passed
+    Passed,
+
+    /// The check failed, ending the ceremony unless it is a dry run
+    Failed,
+}
+
+/// A step of a ceremony, with the values it looked at
+#[derive(Clone, Debug, Serialize)]
+pub struct Step {
+    name: &'static str,
+    outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    values: BTreeMap<&'static str, String>,
+}
+
+impl Step {
+    /// Returns the name of the step (e.g., `client_data`, `signature`)
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Returns whether the step passed
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    /// Returns the error the step failed with
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Returns a value recorded by the step
+    ///
+    /// # Arguments
+    /// * `key` - Name of the value (e.g., `origin`, `expected_origin`)
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+}
+
+/// Every step run while validating a response, in order
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ValidationReport {
+    ceremony: String,
+    steps: Vec<Step>,
+}
+
+impl ValidationReport {
+    /// Creates an empty report for a ceremony
+    pub(crate) fn new(ceremony: &str) -> ValidationReport {
+        ValidationReport {
+            ceremony: ceremony.to_owned(),
+            steps: vec![],
+        }
+    }
+
+    /// Returns the ceremony validated, `webauthn.create` or `webauthn.get`
+    pub fn ceremony(&self) -> &str {
+        &self.ceremony
+    }
+
+    /// Returns the steps run, in order
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Returns a step by name
+    ///
+    /// # Arguments
+    /// * `name` - Name of the step
+    pub fn step(&self, name: &str) -> Option<&Step> {
+        self.steps.iter().find(|step| step.name == name)
+    }
+
+    /// Returns the step that failed, if any.  For a dry run, this is the first of the
+    /// failed steps
+    pub fn failure(&self) -> Option<&Step> {
+        self.failures().next()
+    }
+
+    /// Returns every step that failed, in order.  Only a dry run can have more than one
+    pub fn failures(&self) -> impl Iterator<Item = &Step> {
+        self.steps
+            .iter()
+            .filter(|step| step.outcome == Outcome::Failed)
+    }
+}
+
+/// Records steps into a report, if one was requested.  Values are only formatted when
+/// recording, so validating without a report costs nothing
+pub(crate) struct Recorder<'a> {
+    report: Option<&'a mut ValidationReport>,
+
+    /// Keep going after a failed [`verify`](Recorder::verify)
+    dry_run: bool,
+
+    /// First error a dry run kept going after
+    deferred: Option<Error>,
+}
+
+impl<'a> Recorder<'a> {
+    /// Creates a recorder that discards every step
+    pub fn off() -> Recorder<'static> {
+        Recorder {
+            report: None,
+            dry_run: false,
+            deferred: None,
+        }
+    }
+
+    /// Creates a recorder that appends steps to a report
+    ///
+    /// # Arguments
+    /// * `report` - Report to fill in
+    pub fn on(report: &'a mut ValidationReport) -> Recorder<'a> {
+        Recorder {
+            report: Some(report),
+            dry_run: false,
+            deferred: None,
+        }
+    }
+
+    /// Creates a recorder that appends steps to a report and keeps validating after a
+    /// failed [`verify`](Recorder::verify)
+    ///
+    /// # Arguments
+    /// * `report` - Report to fill in
+    pub fn dry_run(report: &'a mut ValidationReport) -> Recorder<'a> {
+        Recorder {
+            report: Some(report),
+            dry_run: true,
+            deferred: None,
+        }
+    }
+
+    /// Records the outcome of a step, passing its result through
+    ///
+    /// # Arguments
+    /// * `name` - Name of the step
+    /// * `result` - Result of the step
+    /// * `values` - Values the step compared
+    pub fn check<T, E: fmt::Display>(
+        &mut self,
+        name: &'static str,
+        result: Result<T, E>,
+        values: &[(&'static str, &dyn fmt::Display)],
+    ) -> Result<T, E> {
+        if let Some(report) = self.report.as_mut() {
+            let (outcome, error) = match result {
+                Ok(_) => (Outcome::Passed, None),
+                Err(ref e) => (Outcome::Failed, Some(e.to_string())),
+            };
+            report.steps.push(Step {
+                name,
+                outcome,
+                error,
+                values: values
+                    .iter()
+                    .map(|(key, value)| (*key, value.to_string()))
+                    .collect(),
+            });
+        }
+        result
+    }
+
+    /// Records the outcome of a step later steps do not depend on.  During a dry run, a
+    /// failure is recorded and validation continues
+    ///
+    /// # Arguments
+    /// * `name` - Name of the step
+    /// * `result` - Result of the step
+    /// * `values` - Values the step compared
+    pub fn verify<E: fmt::Display + Into<Error>>(
+        &mut self,
+        name: &'static str,
+        result: Result<(), E>,
+        values: &[(&'static str, &dyn fmt::Display)],
+    ) -> Result<(), E> {
+        match self.check(name, result, values) {
+            Err(e) if self.dry_run => {
+                self.deferred.get_or_insert(e.into());
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Returns the result of a ceremony, or the first failure a dry run kept going after
+    ///
+    /// # Arguments
+    /// * `result` - Result of the ceremony
+    pub fn finish<T>(&mut self, result: Result<T, Error>) -> Result<T, Error> {
+        match self.deferred.take() {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_steps() {
+        let mut report = ValidationReport::new("webauthn.get");
+        let mut rec = Recorder::on(&mut report);
+        assert!(rec
+            .check("origin", Ok::<_, String>(()), &[("origin", &"https://a")])
+            .is_ok());
+        assert!(rec
+            .check("counter", Err::<(), _>("regressed"), &[("stored", &5)])
+            .is_err());
+
+        assert_eq!(report.steps().len(), 2);
+        let failure = report.failure().unwrap();
+        assert_eq!(failure.name(), "counter");
+        assert_eq!(failure.error(), Some("regressed"));
+        assert_eq!(failure.value("stored"), Some("5"));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["steps"][0]["outcome"], "passed");
+        assert_eq!(json["steps"][0]["values"]["origin"], "https://a");
+        assert!(json["steps"][0].get("error").is_none());
+
+        // without a report, nothing is recorded
+        assert!(Recorder::off()
+            .check("origin", Err::<(), _>("mismatch"), &[])
+            .is_err());
+    }
+
+    #[test]
+    fn dry_run() {
+        let mut report = ValidationReport::new("webauthn.get");
+        let mut rec = Recorder::dry_run(&mut report);
+        assert!(rec
+            .verify("origin", Err(Error::InvalidDeviceId), &[])
+            .is_ok());
+        assert!(rec
+            .verify("counter", Err(Error::CounterRegressed), &[])
+            .is_ok());
+        assert!(rec.finish(Ok(())).is_err());
+
+        let failures: Vec<_> = report.failures().map(Step::name).collect();
+        assert_eq!(failures, ["origin", "counter"]);
+        assert_eq!(report.failure().unwrap().name(), "origin");
+    }
+}
@@ -5,6 +5,7 @@ mod authenticator;
 mod user;
 
 use crate::webauthn::{
+    extensions::{self, ClientExtensionMap, Extension},
     pk::{PublicKeyDescriptor, PublicKeyParams},
     rp::RelyingParty,
     user::User,
@@ -14,7 +15,7 @@ use rand::RngCore;
 use serde::{Deserialize, Serialize};
 
 pub use self::attestation::AttestationPreference;
-pub use self::authenticator::AuthenticatorCritera;
+pub use self::authenticator::{AuthenticatorAttachment, AuthenticatorCritera};
 pub use self::user::UserVerification;
 
 /// Options for creating a new PublicKey.  This struct is passed to
@@ -50,6 +51,12 @@ pub struct RegisterRequest {
     /// Ordering is most-preferred (0-index) to least-preferred (n-index).  Client will make
     /// best effort to create the most-preferred credential it can.
     pub_key_cred_params: Vec<PublicKeyParams>,
+
+    /// Client extension inputs requesting additional processing by the client and authenticator
+    ///
+    /// Default: None
+    #[serde(default, skip_serializing_if = "ClientExtensionMap::is_empty")]
+    extensions: ClientExtensionMap,
 }
 
 #[allow(dead_code)]
@@ -61,6 +68,8 @@ impl RegisterRequest {
     /// * `rp` - Name of the Relying Party
     /// * `user` - The user to generate an attestation / credential for
     pub fn new<P: Into<RelyingParty>, U: WebAuthnUser>(rp: P, user: &U) -> Self {
+        metric_counter!("auth_ceremonies_started_total", "ceremony" => "register");
+
         let mut challenge = vec![0; 32];
         rand::thread_rng().fill_bytes(&mut challenge);
 
@@ -72,6 +81,7 @@ impl RegisterRequest {
             authenticator_selection: AuthenticatorCritera::default(),
             attestation: AttestationPreference::Direct,
             pub_key_cred_params: vec![PublicKeyParams::default()],
+            extensions: ClientExtensionMap::new(),
         }
     }
 
@@ -102,6 +112,20 @@ impl RegisterRequest {
         self
     }
 
+    /// Adds a client extension input to this request, replacing any existing input
+    /// for the same extension
+    ///
+    /// # Arguments
+    /// * `input` - Client input for the extension
+    pub fn add_extension<E: Extension>(
+        &mut self,
+        input: E::ClientInput,
+    ) -> Result<&mut Self, Error> {
+        let value = extensions::encode_client_input::<E>(&input)?;
+        self.extensions.insert(E::ID.to_owned(), value);
+        Ok(self)
+    }
+
     /// Returns the challenge as a base64url-encoded string
     pub fn challenge(&self) -> String {
         base64::encode_config(&self.challenge, base64::URL_SAFE_NO_PAD)
@@ -112,6 +136,21 @@ impl RegisterRequest {
         &self.rp
     }
 
+    /// Returns the user the credential is created for
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// Returns the acceptable credential algorithms, most preferred first
+    pub fn pub_key_cred_params(&self) -> &[PublicKeyParams] {
+        &self.pub_key_cred_params
+    }
+
+    /// Returns the requirements for the authenticator that will be used
+    pub fn auth_criteria(&self) -> &AuthenticatorCritera {
+        &self.authenticator_selection
+    }
+
     /// Converts this request into the equivalent JSON for sending to a client.
     /// This method is (usually) not required when working with web frameworks
     /// like Rocket or Actix-Web since the framework (usually) has it's own
@@ -149,10 +188,16 @@ pub struct AuthenticateRequest {
     /// Eligible authenticators are filtered to only those capable of satisfying this requirement.
     #[serde(rename = "userVerification")]
     user_verification: UserVerification,
+
+    /// Client extension inputs requesting additional processing by the client and authenticator
+    #[serde(default, skip_serializing_if = "ClientExtensionMap::is_empty")]
+    extensions: ClientExtensionMap,
 }
 
 impl AuthenticateRequest {
     pub fn new(config: &Config, devices: Vec<Device>) -> AuthenticateRequest {
+        metric_counter!("auth_ceremonies_started_total", "ceremony" => "authenticate");
+
         // generate a random challenge
         let mut challenge = vec![0; 32];
         rand::thread_rng().fill_bytes(&mut challenge);
@@ -163,9 +208,13 @@ impl AuthenticateRequest {
             rp_id: Some(config.id().to_owned()),
             allow_credentials: devices
                 .iter()
-                .map(|d| PublicKeyDescriptor::new(d.id().to_vec()))
+                .map(|d| {
+                    PublicKeyDescriptor::new(d.id().to_vec())
+                        .with_transports(d.transports().to_vec())
+                })
                 .collect(),
             user_verification: UserVerification::Preferred,
+            extensions: ClientExtensionMap::new(),
         }
     }
 
@@ -173,10 +222,48 @@ impl AuthenticateRequest {
         base64::encode_config(&self.challenge, base64::URL_SAFE_NO_PAD)
     }
 
+    /// Returns the id of the relying party requesting the assertion
+    pub fn rp_id(&self) -> Option<&str> {
+        self.rp_id.as_deref()
+    }
+
+    /// Returns the credentials acceptable to the relying party, most preferred first
+    pub fn allow_credentials(&self) -> &[PublicKeyDescriptor] {
+        &self.allow_credentials
+    }
+
+    /// Returns the relying party's user verification requirement
+    pub fn user_verification(&self) -> &UserVerification {
+        &self.user_verification
+    }
+
     pub fn set_user_verification(&mut self, uv: UserVerification) -> &mut Self {
         self.user_verification = uv;
         self
     }
+
+    /// Omits the `transports` member of every allowed credential, letting the client try
+    /// every transport it supports (e.g., when the stored transports may be stale)
+    pub fn omit_transports(&mut self) -> &mut Self {
+        self.allow_credentials
+            .iter_mut()
+            .for_each(PublicKeyDescriptor::clear_transports);
+        self
+    }
+
+    /// Adds a client extension input to this request, replacing any existing input
+    /// for the same extension
+    ///
+    /// # Arguments
+    /// * `input` - Client input for the extension
+    pub fn add_extension<E: Extension>(
+        &mut self,
+        input: E::ClientInput,
+    ) -> Result<&mut Self, Error> {
+        let value = extensions::encode_client_input::<E>(&input)?;
+        self.extensions.insert(E::ID.to_owned(), value);
+        Ok(self)
+    }
 }
 /*
 #[cfg(test)]
@@ -185,7 +272,7 @@ mod tests {
     use crate::webauthn::Config;
 
     fn setup() -> (Config, User) {
-        let config = Config::new("http:://www.example.com");
+        let config = Config::new("https://www.example.com");
         let user = User::new(vec![0, 1, 2, 3], "user", "user");
         (config, user)
     }
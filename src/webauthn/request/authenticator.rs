@@ -7,7 +7,7 @@ use std::default::Default;
 /// Specifies what type of authenticator we should prefer and to inform the client
 /// the best way to location an authenticator on the device
 /// #[WebAuthn Spec](https://www.w3.org/TR/webauthn/#enumdef-authenticatorattachment)
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AuthenticatorAttachment {
     /// A built-in authenticator (fingerprint reader on Win10, OSX, phones, etc.)
     #[serde(rename = "platform")]
@@ -3,26 +3,35 @@
 mod attestation;
 mod auth_data;
 mod client_data;
+mod result;
 
 pub use self::attestation::AttestationError;
 pub use self::auth_data::AuthError;
 pub use self::client_data::ClientDataError;
+pub use self::result::{AuthenticationResult, RegistrationResult};
+pub(crate) use self::{
+    attestation::{cert, parse as parse_attestation, AttestationCert, CertError},
+    auth_data::AuthData,
+    client_data::ClientData,
+};
 
 use crate::{
+    events::{self, AuthEvent},
     parsers,
+    time::now,
     webauthn::{
-        response::{attestation::AttestationFormat, auth_data::AuthData},
-        Config, Device, Error, WebAuthnType, WebAuthnUser,
+        extensions::{self, ClientExtensionMap, Extension, ExtensionOutputs},
+        policy::Ceremony,
+        report::{Recorder, ValidationReport},
+        request::AuthenticatorAttachment,
+        response::{attestation::AttestationFormat, auth_data::AuthDataRef},
+        verifier::{Credentials, VerifierContext},
+        Config, CounterPolicy, Device, Error, Transport, WebAuthnType, WebAuthnUser,
     },
 };
 
-use client_data::ClientData;
-use ring::{
-    digest::{digest, SHA256},
-    signature::{self, VerificationAlgorithm},
-};
-use serde::Deserialize;
-use untrusted::Input;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
 
 /// Validates a response received after a call to `navigator.credentials.create()` (i.e.,
 /// registering a token).  
@@ -33,8 +42,9 @@ use untrusted::Input;
 /// * `challenge` - The base64url encoded challenge string generated by the [`RegisterRequest`](struct.RegisterRequest.html) message
 ///
 /// # Returns
-/// A new [`Device`](struct.Device.html) containing all information needed to verify the enrolled token (e.g., Yubikey) on future
-/// authentication techniues
+/// A [`RegistrationResult`](struct.RegistrationResult.html) containing the new [`Device`](struct.Device.html) with all
+/// information needed to verify the enrolled token (e.g., Yubikey) on future authentication techniues and any extension
+/// outputs returned by the client and authenticator
 ///
 /// # Example
 ///
@@ -44,7 +54,7 @@ use untrusted::Input;
 /// let challenge = "GVuZ2UiOiIyZXlUWlo4Rml6anZ";
 ///
 /// match register(form, &cfg, challenge) {
-///     Ok(device) => println!("New device ({:?}) registered!", device),
+///     Ok(result) => println!("New device ({:?}) registered!", result.device()),
 ///     Err(e) => println!("Failed to register device: {}", e),
 /// }
 /// ```
@@ -52,12 +62,108 @@ pub fn register<S: Into<String>>(
     form: Response,
     config: &Config,
     challenge: S,
-) -> Result<Device, Error> {
+) -> Result<RegistrationResult, Error> {
+    run_registration(form, config, challenge, Recorder::off())
+}
+
+/// Validates a registration response like [`register`], also returning a report of
+/// every step that ran
+///
+/// # Arguments
+/// * `form` - Deserialized JSON received from the client
+/// * `config` - WebAuthn Configuration struct containing expected origin and Relying Party information
+/// * `challenge` - The base64url encoded challenge string generated by the [`RegisterRequest`](struct.RegisterRequest.html) message
+pub fn register_with_report<S: Into<String>>(
+    form: Response,
+    config: &Config,
+    challenge: S,
+) -> (Result<RegistrationResult, Error>, ValidationReport) {
+    let mut report = ValidationReport::new(WebAuthnType::Create.as_str());
+    let result = run_registration(form, config, challenge, Recorder::on(&mut report));
+    (result, report)
+}
+
+/// Validates a registration response like [`register_with_report`], but keeps going after
+/// a failed check so the report lists every violation.  The result is the first failure.
+/// Meant for debugging a new browser or authenticator against a staging relying party
+///
+/// # Arguments
+/// * `form` - Deserialized JSON received from the client
+/// * `config` - WebAuthn Configuration struct containing expected origin and Relying Party information
+/// * `challenge` - The base64url encoded challenge string generated by the [`RegisterRequest`](struct.RegisterRequest.html) message
+pub fn register_dry_run<S: Into<String>>(
+    form: Response,
+    config: &Config,
+    challenge: S,
+) -> (Result<RegistrationResult, Error>, ValidationReport) {
+    let mut report = ValidationReport::new(WebAuthnType::Create.as_str());
+    let result = run_registration(form, config, challenge, Recorder::dry_run(&mut report));
+    (result, report)
+}
+
+/// Validates a registration response and reports the outcome, see [`register`]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "webauthn.register",
+        skip_all,
+        fields(
+            rp_id = %config.id(),
+            aaguid = tracing::field::Empty,
+            alg = tracing::field::Empty,
+            outcome = tracing::field::Empty,
+        )
+    )
+)]
+fn run_registration<S: Into<String>>(
+    form: Response,
+    config: &Config,
+    challenge: S,
+    mut rec: Recorder,
+) -> Result<RegistrationResult, Error> {
+    let result = validate_registration(form, config, challenge, &mut rec);
+    let result = rec.finish(result);
+    match result {
+        Ok(ref result) => {
+            trace_record!("outcome", "success");
+            metric_counter!("auth_ceremonies_succeeded_total", "ceremony" => "register");
+            events::emit(AuthEvent::RegistrationSucceeded {
+                credential: result.device().id().to_vec(),
+            })
+        }
+        Err(ref e) => {
+            trace_record!("outcome", e);
+            metric_counter!(
+                "auth_ceremonies_failed_total",
+                "ceremony" => "register",
+                "reason" => e.label()
+            );
+            events::emit(AuthEvent::RegistrationFailed {
+                reason: e.to_string(),
+            })
+        }
+    }
+    result
+}
+
+/// Validates a registration response, see [`register`]
+fn validate_registration<S: Into<String>>(
+    form: Response,
+    config: &Config,
+    challenge: S,
+    rec: &mut Recorder,
+) -> Result<RegistrationResult, Error> {
     if let ResponseType::Create(ref resp) = form.response() {
-        let (id, pk, count) = resp.validate(WebAuthnType::Create, config, challenge)?;
-        Ok(Device::new(id, pk, count))
+        resp.validate(
+            WebAuthnType::Create,
+            config,
+            challenge,
+            &form.client_extension_results,
+            rec,
+        )
+        .map(|result| result.with_attachment(form.authenticator_attachment))
     } else {
-        Err(Error::IncorrectResponseType)
+        rec.check("response_type", Err(Error::IncorrectResponseType), &[])
     }
 }
 
@@ -70,7 +176,8 @@ pub fn register<S: Into<String>>(
 /// * `devices` - All valid devices that a user may use to authenticate with.  Should correspond to the devices list in the [AuthenticateRequest] message
 ///
 /// # Returns
-/// Empty message `()` response on success or an [Error] otherwise
+/// An [`AuthenticationResult`](struct.AuthenticationResult.html) containing the new signature counter and any
+/// extension outputs on success or an [Error] otherwise
 ///
 /// # Errors
 /// TBD
@@ -84,7 +191,7 @@ pub fn register<S: Into<String>>(
 /// let devices = vec![...];
 ///
 /// match authenticate(form, &cfg, challenge, &devices) {
-///     Ok(_) => println!("Success! User authenticated"),
+///     Ok(result) => println!("Success! User authenticated (count = {})", result.count()),
 ///     Err(e) => println!("Failed to authenticate user: {}", e),
 /// }
 /// ```
@@ -94,19 +201,143 @@ pub fn authenticate<S: Into<String>, U: WebAuthnUser>(
     challenge: S,
     user: &U,
     devices: &[Device],
-) -> Result<(), Error> {
+) -> Result<AuthenticationResult, Error> {
+    run_authentication(form, config, challenge, user, devices, Recorder::off())
+}
+
+/// Validates an authentication response like [`authenticate`], also returning a report
+/// of every step that ran
+///
+/// # Arguments
+/// * `form` - Deserialized JSON received from the client (`get()`)
+/// * `config` - WebAuthn Configuration struct containing expected origin and Relying Party information
+/// * `challenge` - The base64url encoded challenge string generated by the `AuthenticateRequest` message
+/// * `devices` - All valid devices that a user may use to authenticate with
+pub fn authenticate_with_report<S: Into<String>, U: WebAuthnUser>(
+    form: Response,
+    config: &Config,
+    challenge: S,
+    user: &U,
+    devices: &[Device],
+) -> (Result<AuthenticationResult, Error>, ValidationReport) {
+    let mut report = ValidationReport::new(WebAuthnType::Get.as_str());
+    let rec = Recorder::on(&mut report);
+    let result = run_authentication(form, config, challenge, user, devices, rec);
+    (result, report)
+}
+
+/// Validates an authentication response like [`authenticate_with_report`], but keeps
+/// going after a failed check so the report lists every violation.  The result is the
+/// first failure
+///
+/// # Arguments
+/// * `form` - Deserialized JSON received from the client (`get()`)
+/// * `config` - WebAuthn Configuration struct containing expected origin and Relying Party information
+/// * `challenge` - The base64url encoded challenge string generated by the `AuthenticateRequest` message
+/// * `devices` - All valid devices that a user may use to authenticate with
+pub fn authenticate_dry_run<S: Into<String>, U: WebAuthnUser>(
+    form: Response,
+    config: &Config,
+    challenge: S,
+    user: &U,
+    devices: &[Device],
+) -> (Result<AuthenticationResult, Error>, ValidationReport) {
+    let mut report = ValidationReport::new(WebAuthnType::Get.as_str());
+    let rec = Recorder::dry_run(&mut report);
+    let result = run_authentication(form, config, challenge, user, devices, rec);
+    (result, report)
+}
+
+/// Validates an authentication response like [`authenticate`], using public keys decoded
+/// ahead of time
+///
+/// # Arguments
+/// * `form` - Deserialized JSON received from the client (`get()`)
+/// * `config` - WebAuthn Configuration struct containing expected origin and Relying Party information
+/// * `challenge` - The base64url encoded challenge string generated by the `AuthenticateRequest` message
+/// * `context` - The devices a user may authenticate with and their decoded public keys
+pub fn authenticate_with_context<S: Into<String>, U: WebAuthnUser>(
+    form: Response,
+    config: &Config,
+    challenge: S,
+    user: &U,
+    context: &VerifierContext,
+) -> Result<AuthenticationResult, Error> {
+    run_authentication(form, config, challenge, user, context, Recorder::off())
+}
+
+/// Validates an authentication response and reports the outcome, see [`authenticate`]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "webauthn.authenticate",
+        skip_all,
+        fields(
+            rp_id = %config.id(),
+            alg = tracing::field::Empty,
+            outcome = tracing::field::Empty,
+        )
+    )
+)]
+fn run_authentication<S: Into<String>, U: WebAuthnUser, D: Credentials + ?Sized>(
+    form: Response,
+    config: &Config,
+    challenge: S,
+    user: &U,
+    devices: &D,
+    mut rec: Recorder,
+) -> Result<AuthenticationResult, Error> {
+    let credential = form.raw_id.clone();
+    let result = validate_authentication(form, config, challenge, user, devices, &mut rec);
+    let result = rec.finish(result);
+    match result {
+        Ok(_) => {
+            trace_record!("outcome", "success");
+            metric_counter!("auth_ceremonies_succeeded_total", "ceremony" => "authenticate");
+            events::emit(AuthEvent::AssertionSucceeded {
+                user: user.id().to_vec(),
+                credential,
+            })
+        }
+        Err(ref e) => {
+            trace_record!("outcome", e);
+            metric_counter!(
+                "auth_ceremonies_failed_total",
+                "ceremony" => "authenticate",
+                "reason" => e.label()
+            );
+            events::emit(AuthEvent::AssertionFailed {
+                user: user.id().to_vec(),
+                reason: e.to_string(),
+            })
+        }
+    }
+    result
+}
+
+/// Validates an authentication response, see [`authenticate`]
+fn validate_authentication<S: Into<String>, U: WebAuthnUser, D: Credentials + ?Sized>(
+    form: Response,
+    config: &Config,
+    challenge: S,
+    user: &U,
+    devices: &D,
+    rec: &mut Recorder,
+) -> Result<AuthenticationResult, Error> {
     // authenticates against a set of tokens
     if let ResponseType::Get(ref resp) = form.response() {
         // (7.2-1) Verify the credential id in the request matches the credential id in the response
-        if devices
-            .iter()
-            .filter(|device| device.id() == form.raw_id.as_slice())
-            .count()
-            != 1
-        {
-            // Returned credential id does not match any accepted credentials
-            return Err(Error::InvalidDeviceId);
-        }
+        let allowed = devices.find(&form.raw_id).is_some();
+        // Returned credential id does not match any accepted credentials
+        rec.verify(
+            "credential",
+            if allowed {
+                Ok(())
+            } else {
+                Err(Error::InvalidDeviceId)
+            },
+            &[("credential_id", &form.id), ("allowed", &devices.count())],
+        )?;
 
         // (7.2-2) Verify the credential id in the response is owed by the requesting user
         // (7.2-2a) User was identified before the authentication cermony: verify identifed user
@@ -119,21 +350,23 @@ pub fn authenticate<S: Into<String>, U: WebAuthnUser>(
 
         // (7.2-3) Using credential id returned, look up the credential's public key
         // (7.2 / 20.1) Retrieve and covert pubkey into the correct format
-        resp.validate(
-            WebAuthnType::Get,
-            config,
-            challenge,
-            &form.id,
+        let ctx = AssertionContext {
+            cfg: config,
+            challenge: challenge.into(),
+            id: &form.id,
             user,
             devices,
-        )
+            client_extensions: &form.client_extension_results,
+        };
+        resp.validate(ctx, rec)
+            .map(|result| result.with_attachment(form.authenticator_attachment))
     } else {
-        Err(Error::IncorrectResponseType)
+        rec.check("response_type", Err(Error::IncorrectResponseType), &[])
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(tag = "type")]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", from = "AnyResponseType")]
 enum ResponseType {
     #[serde(rename = "create")]
     Create(CreateResponse),
@@ -142,15 +375,51 @@ enum ResponseType {
     Get(GetResponse),
 }
 
-#[derive(Clone, Debug, Deserialize)]
+/// The `response` member as received: frontend helpers such as @simplewebauthn/browser
+/// and webauthn-json do not add the `type` tag, so the kind of response is inferred
+/// from the members present (`attestationObject` or `authenticatorData`)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AnyResponseType {
+    Create(CreateResponse),
+    Get(GetResponse),
+}
+
+impl From<AnyResponseType> for ResponseType {
+    fn from(response: AnyResponseType) -> ResponseType {
+        match response {
+            AnyResponseType::Create(resp) => ResponseType::Create(resp),
+            AnyResponseType::Get(resp) => ResponseType::Get(resp),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 struct CreateResponse {
     /// Base64-encoded CBOR data representing the attestation result
     #[serde(alias = "attestationData", alias = "attestationObject")]
+    #[serde(rename(serialize = "attestationObject"))]
     attestation_data: String,
 
     /// Base64-encode JSON that the client passed to the call
     #[serde(alias = "clientDataJson", alias = "clientDataJSON")]
+    #[serde(rename(serialize = "clientDataJSON"))]
     client_data_json: String,
+
+    /// Transports the authenticator can be reached with, from `getTransports()`.  Transports
+    /// this library does not know are dropped
+    #[serde(default, deserialize_with = "known_transports")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    transports: Vec<Transport>,
+}
+
+/// Deserializes a list of transports, skipping unknown transports
+fn known_transports<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<Transport>, D::Error> {
+    let transports: Vec<String> = Vec::deserialize(d)?;
+    Ok(transports
+        .iter()
+        .filter_map(|t| Transport::parse(t))
+        .collect())
 }
 
 impl CreateResponse {
@@ -159,71 +428,225 @@ impl CreateResponse {
         ty: WebAuthnType,
         cfg: &Config,
         challenge: S,
-    ) -> Result<(Vec<u8>, Vec<u8>, u32), Error> {
+        client_extensions: &ClientExtensionMap,
+        rec: &mut Recorder,
+    ) -> Result<RegistrationResult, Error> {
         // Get the client data the SHA256 hash of it
-        let client_data = base64::decode_config(&self.client_data_json, base64::URL_SAFE)?;
+        let client_data = rec.check(
+            "client_data.decode",
+            parsers::decode_base64(&self.client_data_json).map_err(Error::from),
+            &[],
+        )?;
         let client_data_hash = digest(&SHA256, &client_data);
-        let client_data: ClientData = serde_json::from_slice(&client_data)?;
+        let client_data: ClientData = rec.check(
+            "client_data.parse",
+            serde_json::from_slice(&client_data).map_err(Error::from),
+            &[],
+        )?;
 
         // Get the attestation data
-        let (auth_data, attestation_format) = attestation::parse(base64::decode_config(
-            &self.attestation_data,
-            base64::STANDARD,
-        )?)?;
+        let (auth_data, attestation_format) = rec.check(
+            "attestation.parse",
+            parsers::decode_base64(&self.attestation_data)
+                .map_err(Error::from)
+                .and_then(attestation::parse),
+            &[],
+        )?;
+        trace_record!(
+            "aaguid",
+            auth_data
+                .credential_data()
+                .map(|c| c.aa_guid.iter().map(|b| format!("{:02x}", b)).collect())
+                .unwrap_or_else(String::new)
+        );
+
+        let mut ceremony = Ceremony {
+            ty: ty.clone(),
+            credential_id: auth_data.credential_id().unwrap_or_default(),
+            origin: client_data.origin(),
+            cross_origin: client_data.cross_origin(),
+            top_origin: client_data.top_origin(),
+            count: None,
+            user_verified: None,
+            aaguid: None,
+        };
 
-        client_data.validate(ty, cfg, challenge)?;
-        auth_data.validate(cfg)?;
+        let challenge = challenge.into();
+        rec.verify(
+            "client_data",
+            client_data.validate(ty, cfg, challenge.as_str()),
+            &[
+                ("type", client_data.ty()),
+                ("challenge", &client_data.challenge()),
+                ("expected_challenge", &challenge),
+                ("origin", &client_data.origin()),
+                ("expected_origin", &cfg.origin()),
+                ("cross_origin", &client_data.cross_origin()),
+                ("top_origin", &client_data.top_origin().unwrap_or_default()),
+            ],
+        )?;
+        rec.verify(
+            "policy.client_data",
+            cfg.policies().check_client_data(&ceremony),
+            &[],
+        )?;
+
+        rec.verify(
+            "auth_data",
+            auth_data.validate(cfg),
+            &[
+                ("rp_id", &cfg.id()),
+                ("user_present", &auth_data.is_user_present()),
+                ("user_verified", &auth_data.is_user_verified()),
+            ],
+        )?;
+        ceremony.count = Some(auth_data.count());
+        ceremony.user_verified = Some(auth_data.is_user_verified());
+        ceremony.aaguid = auth_data.credential_data().map(|c| c.aa_guid);
+        rec.verify(
+            "policy.auth_data",
+            cfg.policies().check_auth_data(&ceremony),
+            &[],
+        )?;
+
+        // Verify the client and authenticator extension outputs for registered extensions
+        rec.verify(
+            "extensions",
+            cfg.extensions()
+                .validate(client_extensions, auth_data.extensions()),
+            &[],
+        )?;
 
         // Verify the attestation statement as specified by the attestation format
-        let (cred_id, cred_pubkey) = match attestation_format {
-            AttestationFormat::FidoU2f(fido) => fido.validate(&auth_data, client_data_hash)?,
-            _ => Err(AttestationError::UnsupportedAttestationFormat)?,
+        let (cred_id, cred_pubkey) = match &attestation_format {
+            AttestationFormat::FidoU2f(fido) => {
+                trace_record!("alg", "ES256");
+                rec.check(
+                    "attestation",
+                    fido.validate(&auth_data, client_data_hash),
+                    &[("fmt", &"fido-u2f"), ("alg", &"ES256")],
+                )?
+            }
+            AttestationFormat::Packed(packed) => {
+                trace_record!("alg", packed.alg);
+                rec.check(
+                    "attestation",
+                    packed.validate(&auth_data, client_data_hash),
+                    &[("fmt", &"packed"), ("alg", &packed.alg)],
+                )?
+            }
         };
 
-        Ok((cred_id, cred_pubkey, auth_data.count()))
+        // Check no certificate of the attestation trust path was revoked
+        if let Some(checker) = cfg.revocation_checker() {
+            rec.verify(
+                "attestation.revocation",
+                checker
+                    .check(attestation_format.certificates(), now())
+                    .map_err(AuthError::from),
+                &[],
+            )?;
+        }
+        rec.verify("policy.result", cfg.policies().check_result(&ceremony), &[])?;
+
+        let extensions = ExtensionOutputs::new(
+            client_extensions.clone(),
+            auth_data.extensions().cloned().unwrap_or_default(),
+        );
+
+        Ok(RegistrationResult::new(
+            Device::new(cred_id, cred_pubkey, auth_data.count())
+                .with_transports(self.transports.clone()),
+            extensions,
+        )
+        .with_aaguid(auth_data.credential_data().map(|c| c.aa_guid)))
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 struct GetResponse {
     /// Authenticator data returned by the authenticator
     #[serde(rename = "authenticatorData")]
-    #[serde(deserialize_with = "parsers::base64")]
+    #[serde(
+        deserialize_with = "parsers::base64",
+        serialize_with = "parsers::to_base64"
+    )]
     authenticator_data: Vec<u8>,
 
     /// Base64url-encoded raw signature returned from the authenticator
-    #[serde(deserialize_with = "parsers::base64")]
+    #[serde(
+        deserialize_with = "parsers::base64",
+        serialize_with = "parsers::to_base64"
+    )]
     signature: Vec<u8>,
 
     /// Base64url-encoded user handle returned from the authenticator
-    #[serde(rename = "userHandle")]
-    #[serde(deserialize_with = "parsers::optional_base64")]
+    #[serde(rename = "userHandle", default)]
+    #[serde(
+        deserialize_with = "parsers::optional_base64",
+        serialize_with = "parsers::to_optional_base64"
+    )]
     user_handle: Option<Vec<u8>>,
 
     /// Base64-encode JSON that the client passed to the call
     #[serde(rename = "clientDataJSON", alias = "clientDataJson")]
-    #[serde(deserialize_with = "parsers::base64")]
+    #[serde(
+        deserialize_with = "parsers::base64",
+        serialize_with = "parsers::to_base64"
+    )]
     client_data_json: Vec<u8>,
 }
 
+/// What an authentication response is validated against
+struct AssertionContext<'a, U, D: ?Sized> {
+    /// Relying party configuration
+    cfg: &'a Config,
+
+    /// Challenge issued for the ceremony
+    challenge: String,
+
+    /// Base64url-encoded id of the credential the client responded with
+    id: &'a str,
+
+    /// User logging in
+    user: &'a U,
+
+    /// Credentials registered by the user
+    devices: &'a D,
+
+    /// Outputs of the client extensions, as reported by the client
+    client_extensions: &'a ClientExtensionMap,
+}
+
 impl GetResponse {
-    fn validate<S: Into<String>, U: WebAuthnUser>(
+    fn validate<U: WebAuthnUser, D: Credentials + ?Sized>(
         &self,
-        ty: WebAuthnType,
-        cfg: &Config,
-        challenge: S,
-        id: &str,
-        user: &U,
-        devices: &[Device],
-    ) -> Result<(), Error> {
+        ctx: AssertionContext<'_, U, D>,
+        rec: &mut Recorder,
+    ) -> Result<AuthenticationResult, Error> {
+        let AssertionContext {
+            cfg,
+            challenge,
+            id,
+            user,
+            devices,
+            client_extensions,
+        } = ctx;
+        let ty = WebAuthnType::Get;
+
         // (7.2-2) Verify the credential id in the response is owed by the requesting user
         // (7.2-2a) User was identified before the authentication cermony: verify identifed user
         // owns the credential source and userHandle matches what is expected
         if let Some(ref uid) = self.user_handle {
-            println!("Verifying user id");
-            if uid.as_slice() != user.id() {
-                return Err(Error::IncorrectUser(uid.clone(), user.id().to_vec()));
-            }
+            rec.verify(
+                "user_handle",
+                if uid.as_slice() == user.id() {
+                    Ok(())
+                } else {
+                    Err(Error::IncorrectUser(uid.clone(), user.id().to_vec()))
+                },
+                &[],
+            )?;
         }
 
         // (7.2-2b) User was not identified before the authentication ceremony: verify user handle
@@ -233,16 +656,78 @@ impl GetResponse {
         // (7.2-3) Using credential id returned, look up the credential's public key
 
         // (10 - 14) Verify Client Data
-        let client_data: ClientData = serde_json::from_slice(&self.client_data_json)?;
-        client_data.validate(ty, cfg, challenge)?;
-
-        let auth_data = AuthData::parse(self.authenticator_data.clone())?;
+        let client_data: ClientData = rec.check(
+            "client_data.parse",
+            serde_json::from_slice(&self.client_data_json).map_err(Error::from),
+            &[],
+        )?;
+        let cred_id = rec.check(
+            "credential_id.decode",
+            parsers::decode_base64(id).map_err(Error::from),
+            &[],
+        )?;
+        let mut ceremony = Ceremony {
+            ty: ty.clone(),
+            credential_id: &cred_id,
+            origin: client_data.origin(),
+            cross_origin: client_data.cross_origin(),
+            top_origin: client_data.top_origin(),
+            count: None,
+            user_verified: None,
+            aaguid: None,
+        };
+        rec.verify(
+            "client_data",
+            client_data.validate(ty, cfg, challenge.as_str()),
+            &[
+                ("type", client_data.ty()),
+                ("challenge", &client_data.challenge()),
+                ("expected_challenge", &challenge),
+                ("origin", &client_data.origin()),
+                ("expected_origin", &cfg.origin()),
+                ("cross_origin", &client_data.cross_origin()),
+                ("top_origin", &client_data.top_origin().unwrap_or_default()),
+            ],
+        )?;
+        rec.verify(
+            "policy.client_data",
+            cfg.policies().check_client_data(&ceremony),
+            &[],
+        )?;
+
+        let auth_data = rec.check(
+            "auth_data.parse",
+            AuthDataRef::parse(&self.authenticator_data),
+            &[],
+        )?;
 
         // (15 - 17) verify auth data
-        auth_data.validate(cfg)?;
+        rec.verify(
+            "auth_data",
+            auth_data.validate(cfg),
+            &[
+                ("rp_id", &cfg.id()),
+                ("user_present", &auth_data.is_user_present()),
+                ("user_verified", &auth_data.is_user_verified()),
+            ],
+        )?;
+        ceremony.count = Some(auth_data.count());
+        ceremony.user_verified = Some(auth_data.is_user_verified());
+        rec.verify(
+            "policy.auth_data",
+            cfg.policies().check_auth_data(&ceremony),
+            &[],
+        )?;
 
         // (18) Verify extensions
-        // TODO
+        let authenticator_extensions =
+            rec.check("extensions.parse", auth_data.extensions(), &[])?;
+        rec.verify(
+            "extensions",
+            cfg.extensions()
+                .validate(client_extensions, authenticator_extensions.as_ref()),
+            &[],
+        )?;
 
         // (19) Compute SHA256 hash of client data
         let hash = digest(&SHA256, &self.client_data_json);
@@ -253,56 +738,120 @@ impl GetResponse {
         verification_data.extend_from_slice(hash.as_ref());
 
         // look up pub-key for cred id in response
-        let cred_id = base64::decode_config(id, base64::URL_SAFE_NO_PAD)?;
-        let mut matching_devices: Vec<&Device> = devices
-            .iter()
-            .filter(|d| d.id() == cred_id.as_slice())
-            .collect();
-        if matching_devices.len() != 1 {
-            return Err(Error::DeviceNotFound);
-        }
-        let device = matching_devices.remove(0);
-
-        signature::ECDSA_P256_SHA256_ASN1
-            .verify(
-                Input::from(&device.public_key()),
-                Input::from(&verification_data),
-                Input::from(&self.signature),
-            )
-            .map_err(|_| Error::SignatureFailed)?;
-
-        // (21) Verify signedCount
-        if device.count() != auth_data.count() {
-            println!(
-                "Sign count mismatch: stored = {}, received = {}",
+        let (device, key) = rec.check(
+            "public_key",
+            devices
+                .find(&cred_id)
+                .ok_or(Error::DeviceNotFound)
+                .and_then(|device| Ok((device, devices.key(device)?))),
+            &[],
+        )?;
+
+        trace_record!("alg", key.alg());
+        rec.verify(
+            "signature",
+            key.verify(&verification_data, &self.signature),
+            &[("alg", &key.alg())],
+        )?;
+
+        // (21) Verify signCount: a counter that fails to increase may indicate a cloned
+        // authenticator (authenticators that don't implement a counter always return zero)
+        let counted = device.count() != 0 || auth_data.count() != 0;
+        let regressed = counted && auth_data.count() <= device.count();
+        let rejected = regressed && cfg.counter_policy() == CounterPolicy::Reject;
+        if regressed && cfg.counter_policy() != CounterPolicy::Ignore {
+            log::warn!(
+                "signature counter of credential {} did not increase: stored = {}, received = {}",
+                id,
                 device.count(),
                 auth_data.count()
             );
+            events::emit(AuthEvent::AssertionCounterRegressed {
+                credential: cred_id.clone(),
+                stored: device.count(),
+                received: auth_data.count(),
+            });
         }
-
-        Ok(())
+        rec.verify(
+            "counter",
+            if rejected {
+                Err(Error::CounterRegressed)
+            } else {
+                Ok(())
+            },
+            &[
+                ("stored", &device.count()),
+                ("received", &auth_data.count()),
+                ("regressed", &regressed),
+            ],
+        )?;
+        rec.verify("policy.result", cfg.policies().check_result(&ceremony), &[])?;
+
+        let extensions = ExtensionOutputs::new(
+            client_extensions.clone(),
+            authenticator_extensions.unwrap_or_default(),
+        );
+
+        Ok(AuthenticationResult::new(
+            auth_data.count(),
+            auth_data.is_user_verified(),
+            extensions,
+        ))
     }
 }
 
 /// A `WebAuthnResponse` is the result received from the browser/client
 /// after a call to `navigator.credentials.create()` on the client side
 /// has been completed.  All fields are required to be present
-#[derive(Clone, Debug, Deserialize)]
+///
+/// Binary members may be encoded with either base64 alphabet, padded or not, and the
+/// `type` of the inner `response` may be left out, so payloads produced by
+/// @simplewebauthn/browser or webauthn-json deserialize as is.
+///
+/// Responses serialize back into the JSON layout browsers produce (`rawId`,
+/// `clientDataJSON`, ...), so they can be queued or persisted and validated later
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Response {
     /// Base64-encoded id
     id: String,
 
     /// Base64-encoded id (overriden in the public key response) without padding
-    #[serde(alias = "rawId", alias = "rawID")]
-    #[serde(deserialize_with = "parsers::base64")]
+    #[serde(alias = "rawId", alias = "rawID", rename(serialize = "rawId"))]
+    #[serde(
+        deserialize_with = "parsers::base64",
+        serialize_with = "parsers::to_base64"
+    )]
     raw_id: Vec<u8>,
 
     /// The contained response for credential registration
     response: ResponseType,
 
     /// The type of credential we tried to register
-    #[serde(alias = "type")]
+    #[serde(alias = "type", rename(serialize = "type"))]
     ty: String,
+
+    /// Outputs of any client extensions processed by the client
+    #[serde(alias = "clientExtensionResults", default)]
+    #[serde(rename(serialize = "clientExtensionResults"))]
+    client_extension_results: ClientExtensionMap,
+
+    /// How the authenticator is attached to the client, if reported.  Values this library
+    /// does not know are treated as missing
+    #[serde(
+        alias = "authenticatorAttachment",
+        rename(serialize = "authenticatorAttachment")
+    )]
+    #[serde(default, deserialize_with = "known_attachment")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    authenticator_attachment: Option<AuthenticatorAttachment>,
+}
+
+/// Deserializes an authenticator attachment, treating unknown values as missing
+fn known_attachment<'de, D: serde::Deserializer<'de>>(
+    d: D,
+) -> Result<Option<AuthenticatorAttachment>, D::Error> {
+    let attachment: Option<serde_json::Value> = Option::deserialize(d)?;
+    Ok(attachment.and_then(|a| serde_json::from_value(a).ok()))
 }
 
 impl Response {
@@ -316,6 +865,57 @@ impl Response {
         }
     }
 
+    /// Returns the raw credential id of the device that generated this response
+    pub fn raw_id(&self) -> &[u8] {
+        &self.raw_id
+    }
+
+    /// Returns how the authenticator is attached to the client (`authenticatorAttachment`),
+    /// if the client reported it
+    pub fn authenticator_attachment(&self) -> Option<AuthenticatorAttachment> {
+        self.authenticator_attachment
+    }
+
+    /// Returns the raw client extension outputs contained in this response
+    pub fn client_extension_results(&self) -> &ClientExtensionMap {
+        &self.client_extension_results
+    }
+
+    /// Decodes the client output for an extension, returning `None` if the
+    /// client did not return an output for the extension
+    pub fn client_extension<E: Extension>(&self) -> Option<Result<E::ClientOutput, Error>> {
+        self.client_extension_results
+            .get(E::ID)
+            .map(extensions::decode_client_output::<E>)
+    }
+
+    /// Returns the origin the client data claims to have been collected for, e.g., to
+    /// select the configuration to validate the response with.  The origin can only be
+    /// trusted once the response has been validated
+    pub fn origin(&self) -> Result<String, Error> {
+        let client_data: ClientData = match &self.response {
+            ResponseType::Create(resp) => {
+                serde_json::from_slice(&parsers::decode_base64(&resp.client_data_json)?)?
+            }
+            ResponseType::Get(resp) => serde_json::from_slice(&resp.client_data_json)?,
+        };
+        Ok(client_data.origin().to_owned())
+    }
+
+    /// Returns the DER encoded attestation certificates (`x5c`) of a registration
+    /// response, or none if the response has none or cannot be decoded
+    pub(crate) fn attestation_certificates(&self) -> Vec<Vec<u8>> {
+        let data = match &self.response {
+            ResponseType::Create(resp) => &resp.attestation_data,
+            ResponseType::Get(_) => return vec![],
+        };
+        parsers::decode_base64(data)
+            .map_err(Error::from)
+            .and_then(attestation::parse)
+            .map(|(_, fmt)| fmt.certificates().iter().map(|c| c.to_vec()).collect())
+            .unwrap_or_default()
+    }
+
     fn response(&self) -> &ResponseType {
         &self.response
     }
@@ -1,9 +1,12 @@
 //! Attestation Response Code
 
+pub(crate) mod cert;
 mod error;
 mod fidou2f;
+mod packed;
 
-pub use self::{error::AttestationError, fidou2f::U2fError};
+pub(crate) use self::cert::AttestationCert;
+pub use self::{cert::CertError, error::AttestationError, fidou2f::U2fError, packed::PackedError};
 use crate::webauthn::{response::auth_data::AuthData, Error};
 use serde::Deserialize;
 
@@ -14,12 +17,23 @@ use serde::Deserialize;
 #[serde(tag = "fmt", content = "attStmt")]
 pub enum AttestationFormat {
     #[serde(alias = "packed")]
-    Packed,
+    Packed(packed::PackedAttestation),
 
     #[serde(alias = "fido-u2f")]
     FidoU2f(fidou2f::FidoU2fAttestation),
 }
 
+impl AttestationFormat {
+    /// Returns the DER encoded attestation certificate followed by its chain, empty for
+    /// self attestation
+    pub fn certificates(&self) -> &[fidou2f::Buffer] {
+        match self {
+            AttestationFormat::Packed(packed) => &packed.x5c,
+            AttestationFormat::FidoU2f(fido) => &fido.x5c,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 struct AttestationData {
     #[serde(flatten)]
@@ -35,7 +49,11 @@ struct AttestationData {
 /// # Arguments
 /// * `data` - The base64url-decoded attestation_data field
 pub fn parse(data: Vec<u8>) -> Result<(AuthData, AttestationFormat), Error> {
-    let inner = serde_cbor::from_slice::<AttestationData>(&data)?;
-    let auth_data = AuthData::parse(inner.auth_data)?;
+    let mut inner = serde_cbor::from_slice::<AttestationData>(&data)?;
+    let auth_data = AuthData::parse(&inner.auth_data)?;
+    if let AttestationFormat::Packed(ref mut packed) = inner.fmt {
+        // packed statements sign the raw authenticator data
+        packed.auth_data = inner.auth_data;
+    }
     Ok((auth_data, inner.fmt))
 }
@@ -0,0 +1,509 @@
+//! Attestation certificate inspection
+//!
+//! webpki checks the signature made with an attestation certificate, but not the
+//! contents WebAuthn requires of the certificate itself.  [`AttestationCert`] reads the
+//! parts of the certificate those requirements are about: the version, the subject, the
+//! basic constraints and the `id-fido-gen-ce-aaguid` extension naming the authenticator
+//! model the certificate was issued for.  It also reads what revocation checking needs
+//! (see [`revocation`](crate::webauthn::revocation)): the serial number, the issuer and
+//! subject names, the public key and the CRL distribution points.
+
+use std::fmt;
+use untrusted::{Input, Reader};
+
+/// DER tags
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const OID: u8 = 0x06;
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+
+/// `uniformResourceIdentifier` choice of a `GeneralName`
+const URI: u8 = 0x86;
+
+/// `id-ce-basicConstraints` (2.5.29.19)
+const BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+
+/// `id-ce-cRLDistributionPoints` (2.5.29.31)
+const CRL_DISTRIBUTION_POINTS: &[u8] = &[0x55, 0x1d, 0x1f];
+
+/// Attribute types of the subject names
+const COUNTRY: &[u8] = &[0x55, 0x04, 0x06];
+const ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0a];
+const ORGANIZATIONAL_UNIT: &[u8] = &[0x55, 0x04, 0x0b];
+const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+/// Subject-OU every attestation certificate must carry
+const AUTHENTICATOR_ATTESTATION: &str = "Authenticator Attestation";
+
+/// `id-fido-gen-ce-aaguid` (1.3.6.1.4.1.45724.1.1.4)
+const FIDO_GEN_CE_AAGUID: &[u8] = &[
+    0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xe5, 0x1c, 0x01, 0x01, 0x04,
+];
+
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum CertError {
+    /// Occurs when the certificate is not valid DER
+    Malformed(&'static str),
+
+    /// Occurs when the certificate is not an X.509 version 3 certificate
+    UnsupportedVersion,
+
+    /// Occurs when the subject country is not an ISO 3166 code
+    InvalidCountry,
+
+    /// Occurs when the subject has no organization (the authenticator vendor)
+    MissingOrganization,
+
+    /// Occurs when the subject organizational unit is not `Authenticator Attestation`
+    InvalidOrganizationalUnit,
+
+    /// Occurs when the subject has no common name
+    MissingCommonName,
+
+    /// Occurs when the basic constraints are missing or mark the certificate as a CA
+    CertificateAuthority,
+
+    /// Occurs when the `id-fido-gen-ce-aaguid` extension is marked critical
+    CriticalAaguidExtension,
+
+    /// Occurs when the AAGUID in the certificate differs from the one in the
+    /// authenticator data
+    AaguidMismatch,
+}
+
+impl std::error::Error for CertError {}
+
+impl CertError {
+    /// Returns a stable, machine-readable code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            CertError::Malformed(_) => "attestation_certificate.malformed",
+            CertError::UnsupportedVersion => "attestation_certificate.unsupported_version",
+            CertError::InvalidCountry => "attestation_certificate.invalid_country",
+            CertError::MissingOrganization => "attestation_certificate.missing_organization",
+            CertError::InvalidOrganizationalUnit => {
+                "attestation_certificate.invalid_organizational_unit"
+            }
+            CertError::MissingCommonName => "attestation_certificate.missing_common_name",
+            CertError::CertificateAuthority => "attestation_certificate.certificate_authority",
+            CertError::CriticalAaguidExtension => "attestation_certificate.critical_aaguid",
+            CertError::AaguidMismatch => "attestation_certificate.aaguid_mismatch",
+        }
+    }
+}
+
+impl fmt::Display for CertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CertError::Malformed(what) => write!(f, "malformed attestation certificate: {}", what),
+            CertError::UnsupportedVersion => {
+                write!(f, "attestation certificate is not an X.509 v3 certificate")
+            }
+            CertError::InvalidCountry => {
+                write!(
+                    f,
+                    "attestation certificate subject country is not an ISO 3166 code"
+                )
+            }
+            CertError::MissingOrganization => {
+                write!(f, "attestation certificate subject has no organization")
+            }
+            CertError::InvalidOrganizationalUnit => write!(
+                f,
+                "attestation certificate subject OU is not `{}`",
+                AUTHENTICATOR_ATTESTATION
+            ),
+            CertError::MissingCommonName => {
+                write!(f, "attestation certificate subject has no common name")
+            }
+            CertError::CertificateAuthority => {
+                write!(f, "attestation certificate is not constrained to a non-CA")
+            }
+            CertError::CriticalAaguidExtension => {
+                write!(
+                    f,
+                    "AAGUID extension of the attestation certificate is critical"
+                )
+            }
+            CertError::AaguidMismatch => write!(
+                f,
+                "AAGUID of the attestation certificate does not match the authenticator data"
+            ),
+        }
+    }
+}
+
+/// The contents of an attestation certificate WebAuthn places requirements on
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AttestationCert {
+    /// X.509 version, 0 for v1 and 2 for v3
+    version: u8,
+
+    /// Serial number, as encoded
+    serial: Vec<u8>,
+
+    /// DER encoded issuer name
+    issuer_name: Vec<u8>,
+
+    /// DER encoded subject name
+    subject_name: Vec<u8>,
+
+    /// Subject attributes, as (attribute type, value) pairs
+    subject: Vec<(Vec<u8>, String)>,
+
+    /// Contents of the subject public key BIT STRING
+    public_key: Vec<u8>,
+
+    /// URIs of the CRL distribution points
+    crl_urls: Vec<String>,
+
+    /// The `cA` component of the basic constraints, if the extension is present
+    ca: Option<bool>,
+
+    /// Value of the `id-fido-gen-ce-aaguid` extension, if present
+    aaguid: Option<[u8; 16]>,
+}
+
+impl AttestationCert {
+    /// Parses a DER encoded X.509 certificate
+    ///
+    /// # Arguments
+    /// * `der` - Attestation certificate (first entry of `x5c`)
+    pub fn parse(der: &[u8]) -> Result<AttestationCert, CertError> {
+        let mut cert = Reader::new(expect(&mut Reader::new(Input::from(der)), SEQUENCE)?);
+        let mut tbs = Reader::new(expect(&mut cert, SEQUENCE)?);
+
+        let mut parsed = AttestationCert::default();
+        if tbs.peek(0xa0) {
+            let version = expect(&mut tbs, 0xa0)?;
+            parsed.version = match expect(&mut Reader::new(version), INTEGER)?.as_slice_less_safe()
+            {
+                [version] => *version,
+                _ => return Err(CertError::Malformed("version")),
+            };
+        }
+
+        parsed.serial = expect(&mut tbs, INTEGER)?.as_slice_less_safe().to_vec();
+        read_tlv(&mut tbs)?;
+        let (issuer, _) = tbs.read_partial(|tbs| expect(tbs, SEQUENCE))?;
+        parsed.issuer_name = issuer.as_slice_less_safe().to_vec();
+        read_tlv(&mut tbs)?;
+        let (subject, name) = tbs.read_partial(|tbs| expect(tbs, SEQUENCE))?;
+        parsed.subject_name = subject.as_slice_less_safe().to_vec();
+        parsed.subject = parse_name(name)?;
+
+        // subject public key info, the algorithm followed by the key
+        let mut spki = Reader::new(expect(&mut tbs, SEQUENCE)?);
+        read_tlv(&mut spki)?;
+        parsed.public_key = match expect(&mut spki, BIT_STRING)?.as_slice_less_safe() {
+            [0x00, key @ ..] => key.to_vec(),
+            _ => return Err(CertError::Malformed("public key")),
+        };
+
+        // issuer/subject unique ids and the extensions
+        while !tbs.at_end() {
+            let (tag, value) = read_tlv(&mut tbs)?;
+            if tag != 0xa3 {
+                continue;
+            }
+
+            let mut extensions = Reader::new(expect(&mut Reader::new(value), SEQUENCE)?);
+            while !extensions.at_end() {
+                let mut extension = Reader::new(expect(&mut extensions, SEQUENCE)?);
+                let oid = expect(&mut extension, OID)?;
+                let critical = if extension.peek(BOOLEAN) {
+                    expect(&mut extension, BOOLEAN)?.as_slice_less_safe() != [0x00]
+                } else {
+                    false
+                };
+                let value = expect(&mut extension, OCTET_STRING)?;
+
+                match oid.as_slice_less_safe() {
+                    BASIC_CONSTRAINTS => parsed.ca = Some(parse_ca(value)?),
+                    CRL_DISTRIBUTION_POINTS => parsed.crl_urls = parse_crl_urls(value)?,
+                    FIDO_GEN_CE_AAGUID if critical => {
+                        return Err(CertError::CriticalAaguidExtension)
+                    }
+                    FIDO_GEN_CE_AAGUID => parsed.aaguid = Some(parse_aaguid(value)?),
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Verifies the certificate meets the requirements of WebAuthn § 8.2.1: it must be
+    /// an X.509 v3 certificate, its subject must name the vendor's country (C) and
+    /// organization (O), with an OU of `Authenticator Attestation` and a common name, and
+    /// its basic constraints must not allow it to act as a CA
+    pub fn check_requirements(&self) -> Result<(), CertError> {
+        if self.version != 2 {
+            return Err(CertError::UnsupportedVersion);
+        }
+
+        let country = self.subject_attribute(COUNTRY).unwrap_or_default();
+        if country.len() != 2 || !country.bytes().all(|b| b.is_ascii_uppercase()) {
+            return Err(CertError::InvalidCountry);
+        }
+        if self
+            .subject_attribute(ORGANIZATION)
+            .unwrap_or_default()
+            .is_empty()
+        {
+            return Err(CertError::MissingOrganization);
+        }
+        if self.subject_attribute(ORGANIZATIONAL_UNIT) != Some(AUTHENTICATOR_ATTESTATION) {
+            return Err(CertError::InvalidOrganizationalUnit);
+        }
+        if self
+            .subject_attribute(COMMON_NAME)
+            .unwrap_or_default()
+            .is_empty()
+        {
+            return Err(CertError::MissingCommonName);
+        }
+
+        match self.ca {
+            Some(false) => Ok(()),
+            _ => Err(CertError::CertificateAuthority),
+        }
+    }
+
+    /// Returns the first value of a subject attribute
+    fn subject_attribute(&self, ty: &[u8]) -> Option<&str> {
+        self.subject
+            .iter()
+            .find(|(attribute, _)| attribute.as_slice() == ty)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the serial number
+    pub(crate) fn serial(&self) -> &[u8] {
+        &self.serial
+    }
+
+    /// Returns the DER encoded issuer name
+    pub(crate) fn issuer_name(&self) -> &[u8] {
+        &self.issuer_name
+    }
+
+    /// Returns the DER encoded subject name
+    pub(crate) fn subject_name(&self) -> &[u8] {
+        &self.subject_name
+    }
+
+    /// Returns the subject public key, without its algorithm
+    pub(crate) fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Returns the URIs of the CRL distribution points
+    pub(crate) fn crl_urls(&self) -> &[String] {
+        &self.crl_urls
+    }
+
+    /// Verifies the AAGUID of the certificate, when it has one, matches the AAGUID of the
+    /// authenticator data
+    ///
+    /// # Arguments
+    /// * `aaguid` - AAGUID of the attested credential data
+    pub fn check_aaguid(&self, aaguid: &[u8; 16]) -> Result<(), CertError> {
+        match self.aaguid {
+            Some(ref expected) if expected != aaguid => Err(CertError::AaguidMismatch),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parses a `Name` into (attribute type, value) pairs
+fn parse_name(name: Input) -> Result<Vec<(Vec<u8>, String)>, CertError> {
+    let mut attributes = vec![];
+    let mut rdns = Reader::new(name);
+    while !rdns.at_end() {
+        let mut rdn = Reader::new(expect(&mut rdns, SET)?);
+        while !rdn.at_end() {
+            let mut attribute = Reader::new(expect(&mut rdn, SEQUENCE)?);
+            let oid = expect(&mut attribute, OID)?;
+            let (tag, value) = read_tlv(&mut attribute)?;
+
+            // UTF8String, PrintableString, TeletexString and IA5String
+            if let (0x0c | 0x13 | 0x14 | 0x16, Ok(value)) =
+                (tag, std::str::from_utf8(value.as_slice_less_safe()))
+            {
+                attributes.push((oid.as_slice_less_safe().to_vec(), value.to_owned()));
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+/// Parses the value of a basic constraints extension, returning its `cA` component
+fn parse_ca(value: Input) -> Result<bool, CertError> {
+    let mut constraints = Reader::new(expect(&mut Reader::new(value), SEQUENCE)?);
+    if constraints.peek(BOOLEAN) {
+        Ok(expect(&mut constraints, BOOLEAN)?.as_slice_less_safe() != [0x00])
+    } else {
+        Ok(false)
+    }
+}
+
+/// Parses the value of a CRL distribution points extension, returning the URIs of the
+/// full names of every distribution point
+fn parse_crl_urls(value: Input) -> Result<Vec<String>, CertError> {
+    let mut urls = vec![];
+    let mut points = Reader::new(expect(&mut Reader::new(value), SEQUENCE)?);
+    while !points.at_end() {
+        let mut point = Reader::new(expect(&mut points, SEQUENCE)?);
+        if !point.peek(0xa0) {
+            continue;
+        }
+
+        // distributionPoint [0], fullName [0]
+        let mut name = Reader::new(expect(&mut point, 0xa0)?);
+        if !name.peek(0xa0) {
+            continue;
+        }
+        let mut names = Reader::new(expect(&mut name, 0xa0)?);
+        while !names.at_end() {
+            if let (URI, uri) = read_tlv(&mut names)? {
+                let uri = std::str::from_utf8(uri.as_slice_less_safe())
+                    .map_err(|_| CertError::Malformed("distribution point"))?;
+                urls.push(uri.to_owned());
+            }
+        }
+    }
+    Ok(urls)
+}
+
+/// Parses the value of an `id-fido-gen-ce-aaguid` extension, an OCTET STRING holding
+/// the 16 byte AAGUID
+fn parse_aaguid(value: Input) -> Result<[u8; 16], CertError> {
+    let mut reader = Reader::new(value);
+    let aaguid = expect(&mut reader, OCTET_STRING)?.as_slice_less_safe();
+    if !reader.at_end() || aaguid.len() != 16 {
+        return Err(CertError::Malformed("aaguid extension"));
+    }
+
+    let mut parsed = [0u8; 16];
+    parsed.copy_from_slice(aaguid);
+    Ok(parsed)
+}
+
+/// Reads a DER tag-length-value, returning the tag and value
+pub(crate) fn read_tlv<'a>(reader: &mut Reader<'a>) -> Result<(u8, Input<'a>), CertError> {
+    let malformed = |_| CertError::Malformed("truncated DER");
+    let tag = reader.read_byte().map_err(malformed)?;
+    if tag & 0x1f == 0x1f {
+        return Err(CertError::Malformed("unsupported DER tag"));
+    }
+
+    let len = match reader.read_byte().map_err(malformed)? {
+        len if len < 0x80 => usize::from(len),
+        0x81 => usize::from(reader.read_byte().map_err(malformed)?),
+        0x82 => {
+            let high = reader.read_byte().map_err(malformed)?;
+            let low = reader.read_byte().map_err(malformed)?;
+            usize::from(u16::from_be_bytes([high, low]))
+        }
+        0x83 => {
+            let mut len = 0;
+            for _ in 0..3 {
+                len = (len << 8) | usize::from(reader.read_byte().map_err(malformed)?);
+            }
+            len
+        }
+        _ => return Err(CertError::Malformed("unsupported DER length")),
+    };
+
+    let value = reader.read_bytes(len).map_err(malformed)?;
+    Ok((tag, value))
+}
+
+/// Reads a DER tag-length-value, failing if the tag isn't the expected tag
+pub(crate) fn expect<'a>(reader: &mut Reader<'a>, expected: u8) -> Result<Input<'a>, CertError> {
+    match read_tlv(reader)? {
+        (tag, value) if tag == expected => Ok(value),
+        _ => Err(CertError::Malformed("unexpected DER tag")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a certificate meeting every requirement
+    fn compliant() -> AttestationCert {
+        AttestationCert {
+            version: 2,
+            subject: vec![
+                (COUNTRY.to_vec(), "US".to_owned()),
+                (ORGANIZATION.to_vec(), "auth-rs".to_owned()),
+                (
+                    ORGANIZATIONAL_UNIT.to_vec(),
+                    AUTHENTICATOR_ATTESTATION.to_owned(),
+                ),
+                (
+                    COMMON_NAME.to_vec(),
+                    "auth-rs soft authenticator".to_owned(),
+                ),
+            ],
+            ca: Some(false),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn check_requirements() {
+        assert!(compliant().check_requirements().is_ok());
+
+        let cert = AttestationCert {
+            version: 0,
+            ..compliant()
+        };
+        assert_eq!(
+            cert.check_requirements().unwrap_err().code(),
+            "attestation_certificate.unsupported_version"
+        );
+
+        let mut cert = compliant();
+        cert.subject[0].1 = "USA".to_owned();
+        assert_eq!(
+            cert.check_requirements().unwrap_err().code(),
+            "attestation_certificate.invalid_country"
+        );
+
+        let mut cert = compliant();
+        cert.subject.remove(1);
+        assert_eq!(
+            cert.check_requirements().unwrap_err().code(),
+            "attestation_certificate.missing_organization"
+        );
+
+        let mut cert = compliant();
+        cert.subject[2].1 = "Security Keys".to_owned();
+        assert_eq!(
+            cert.check_requirements().unwrap_err().code(),
+            "attestation_certificate.invalid_organizational_unit"
+        );
+
+        let mut cert = compliant();
+        cert.subject.pop();
+        assert_eq!(
+            cert.check_requirements().unwrap_err().code(),
+            "attestation_certificate.missing_common_name"
+        );
+
+        for ca in [Some(true), None] {
+            let cert = AttestationCert { ca, ..compliant() };
+            assert_eq!(
+                cert.check_requirements().unwrap_err().code(),
+                "attestation_certificate.certificate_authority"
+            );
+        }
+    }
+}
@@ -1,9 +1,10 @@
 //! Attestation Error Code
 
-use crate::common::cose::CoseError;
+use crate::{webauthn::common::cose::CoseError, fido};
 use std::{error::Error, fmt};
 
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum AttestationError {
     /// Occurs when the RP ID hash in the attestation auth data does not match
     /// the value supplied with the creation request. (Potentially MitM!)
@@ -35,11 +36,45 @@ pub enum AttestationError {
     /// Occurs when converting the credential public key to X9.62 fails
     BadCredentialPublicKey,
 
+    /// Occurs when the extensions in the authenticator data fail to parse
+    InvalidExtensions,
+
+    /// Occurs when the authenticator data is truncated or malformed
+    InvalidAuthData(fido::Error),
+
     /// Occurs when the attestation fails
     BadSignature(webpki::Error),
 }
 
-impl Error for AttestationError {}
+impl Error for AttestationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AttestationError::InvalidAuthData(e) => Some(e),
+            AttestationError::BadSignature(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl AttestationError {
+    /// Returns a stable, machine-readable code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            AttestationError::RpIdHashMismatch => "attestation.rp_id_hash_mismatch",
+            AttestationError::UserNotPresent => "attestation.user_not_present",
+            AttestationError::UserNotVerified => "attestation.user_not_verified",
+            AttestationError::TooManyX509Certs => "attestation.too_many_certificates",
+            AttestationError::BadCert => "attestation.bad_certificate",
+            AttestationError::UnsupportedAlgorithm => "attestation.unsupported_algorithm",
+            AttestationError::UnsupportedAttestationFormat => "attestation.unsupported_format",
+            AttestationError::InvalidCoseKey => "attestation.invalid_cose_key",
+            AttestationError::BadCredentialPublicKey => "attestation.bad_credential_public_key",
+            AttestationError::InvalidExtensions => "attestation.invalid_extensions",
+            AttestationError::InvalidAuthData(_) => "attestation.invalid_auth_data",
+            AttestationError::BadSignature(_) => "attestation.bad_signature",
+        }
+    }
+}
 
 impl fmt::Display for AttestationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -59,6 +94,10 @@ impl fmt::Display for AttestationError {
             AttestationError::BadCredentialPublicKey => {
                 format!("Converting public key to X9.62 failed")
             }
+            AttestationError::InvalidExtensions => {
+                format!("Failed to parse authenticator extensions")
+            }
+            AttestationError::InvalidAuthData(e) => format!("Malformed Authenticator Data: {}", e),
             AttestationError::BadSignature(_) => format!("Signature Verification Failed"),
         };
 
@@ -71,3 +110,9 @@ impl From<CoseError> for AttestationError {
         AttestationError::InvalidCoseKey
     }
 }
+
+impl From<fido::Error> for AttestationError {
+    fn from(e: fido::Error) -> AttestationError {
+        AttestationError::InvalidAuthData(e)
+    }
+}
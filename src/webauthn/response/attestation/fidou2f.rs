@@ -1,5 +1,6 @@
 //! FIDO-U2F Attestation Support
 
+use super::cert::AttestationCert;
 use crate::webauthn::response::{AuthData, AuthError};
 use ring::digest::Digest;
 use serde::Deserialize;
@@ -7,6 +8,7 @@ use std::{fmt, ops::Deref};
 use webpki::{EndEntityCert, ECDSA_P256_SHA256};
 
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum U2fError {
     /// Occurs when too many X.509 certs are includded in the response
     TooManyX509Certificates,
@@ -46,6 +48,12 @@ impl Deref for Buffer {
     }
 }
 
+impl AsRef<[u8]> for Buffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.cert
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct FidoU2fAttestation {
     pub x5c: Vec<Buffer>,
@@ -56,13 +64,22 @@ pub struct FidoU2fAttestation {
 impl FidoU2fAttestation {
     /// Parses the X.509 certificate stored in the attestation data
     fn get_cert(&self) -> Result<EndEntityCert, U2fError> {
-        if self.x5c.len() != 1 {
-            return Err(U2fError::TooManyX509Certificates);
+        match self.x5c.as_slice() {
+            [cert] => EndEntityCert::from(cert).map_err(|_| U2fError::BadX509Certificate),
+            _ => Err(U2fError::TooManyX509Certificates),
         }
-
-        EndEntityCert::from(&self.x5c[0]).map_err(|_| U2fError::BadX509Certificate)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "webauthn.attestation",
+            skip_all,
+            fields(fmt = "fido-u2f"),
+            err
+        )
+    )]
     pub fn validate(
         &self,
         auth_data: &AuthData,
@@ -74,6 +91,15 @@ impl FidoU2fAttestation {
         // this algorithm and return an appropriate error.
         let cert = self.get_cert()?;
 
+        // fido-u2f does not require it, but a certificate naming an authenticator model in
+        // its id-fido-gen-ce-aaguid extension must not be used to attest another model.
+        // Authenticators answering over U2F report an all zero AAGUID, which is skipped
+        if let Some(credential) = auth_data.credential_data() {
+            if credential.aa_guid != [0; 16] {
+                AttestationCert::parse(&self.x5c[0])?.check_aaguid(&credential.aa_guid)?;
+            }
+        }
+
         // Convert the COSE_KEY formatted credentialPublicKey (see Section 7 of [RFC8152]) to
         // Raw ANSI X9.62 public key format (see ALG_KEY_ECC_X962_RAW in Section 3.6.2 Public Key
         // Representation Formats of [FIDO-Registry]).
@@ -0,0 +1,128 @@
+//! Packed Attestation Support
+
+use super::{cert::AttestationCert, fidou2f::Buffer};
+use crate::{
+    fido::cose::{ES256, RS256},
+    webauthn::response::{AuthData, AuthError},
+};
+use ring::{
+    digest::Digest,
+    signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1},
+};
+use serde::Deserialize;
+use std::fmt;
+use webpki::{EndEntityCert, SignatureAlgorithm};
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum PackedError {
+    /// Occurs when the statement is signed with an algorithm that is not supported
+    UnsupportedAlgorithm(i64),
+
+    /// Occurs when a self attestation is not signed with the algorithm of the
+    /// credential public key
+    AlgorithmMismatch,
+
+    /// Occurs when the certificate fails to parse
+    BadX509Certificate,
+
+    /// Occurs when a self attestation signature does not verify
+    BadSignature,
+}
+
+impl std::error::Error for PackedError {}
+
+impl fmt::Display for PackedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PackedError::UnsupportedAlgorithm(alg) => {
+                write!(f, "unsupported algorithm {} in packed statement", alg)
+            }
+            PackedError::AlgorithmMismatch => {
+                write!(f, "self attestation algorithm differs from the credential")
+            }
+            PackedError::BadX509Certificate => write!(f, "failed to parse x.509 certificate"),
+            PackedError::BadSignature => write!(f, "self attestation signature failed"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PackedAttestation {
+    pub alg: i64,
+
+    #[serde(with = "serde_bytes")]
+    pub sig: Vec<u8>,
+
+    /// Attestation certificate followed by its chain, empty for self attestation
+    #[serde(default)]
+    pub x5c: Vec<Buffer>,
+
+    /// Raw authenticator data the statement signs, filled in after parsing
+    #[serde(skip)]
+    pub auth_data: Vec<u8>,
+}
+
+impl PackedAttestation {
+    /// Returns the webpki algorithm matching the COSE algorithm of the statement
+    fn algorithm(&self) -> Result<&'static SignatureAlgorithm, PackedError> {
+        match self.alg {
+            ES256 => Ok(&webpki::ECDSA_P256_SHA256),
+            RS256 => Ok(&webpki::RSA_PKCS1_2048_8192_SHA256),
+            alg => Err(PackedError::UnsupportedAlgorithm(alg)),
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            name = "webauthn.attestation",
+            skip_all,
+            fields(fmt = "packed"),
+            err
+        )
+    )]
+    pub fn validate(
+        &self,
+        auth_data: &AuthData,
+        client_data_hash: Digest,
+    ) -> Result<(Vec<u8>, Vec<u8>), AuthError> {
+        let pubkey = auth_data.public_key()?;
+        let cred_id = auth_data.credential_id()?;
+
+        // Let verificationData be the concatenation of authenticatorData and clientDataHash
+        let verification_data = [&self.auth_data[..], client_data_hash.as_ref()].concat();
+
+        match self.x5c.first() {
+            Some(cert) => {
+                // Verify that sig is a valid signature over verificationData using the
+                // attestation public key in attestnCert with the algorithm specified in alg
+                EndEntityCert::from(cert)
+                    .map_err(|_| PackedError::BadX509Certificate)?
+                    .verify_signature(self.algorithm()?, &verification_data, &self.sig)?;
+
+                // Verify that attestnCert meets the requirements in § 8.2.1 and, if it
+                // contains an id-fido-gen-ce-aaguid extension, that it matches the aaguid
+                // in authenticatorData
+                let cert = AttestationCert::parse(cert)?;
+                cert.check_requirements()?;
+                if let Some(credential) = auth_data.credential_data() {
+                    cert.check_aaguid(&credential.aa_guid)?;
+                }
+            }
+            None => {
+                // Self attestation: validate that alg matches the algorithm of the
+                // credentialPublicKey and verify sig with the credential public key
+                if self.alg != ES256 {
+                    return Err(PackedError::AlgorithmMismatch.into());
+                }
+                UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, &pubkey)
+                    .verify(&verification_data, &self.sig)
+                    .map_err(|_| PackedError::BadSignature)?;
+            }
+        }
+
+        Ok((cred_id.to_vec(), pubkey))
+    }
+}
@@ -1,9 +1,15 @@
 //! Authentication Data contained in the Attestation Response
 
 use crate::{
-    common::cose::CoseKey,
+    webauthn::common::cose::CoseKey,
+    fido,
     webauthn::{
-        response::{attestation::U2fError, AttestationError},
+        extensions::AuthenticatorExtensionMap,
+        response::{
+            attestation::{CertError, PackedError, U2fError},
+            AttestationError,
+        },
+        revocation::RevocationError,
         Config,
     },
 };
@@ -11,6 +17,7 @@ use ring::digest::{digest, SHA256};
 use std::fmt;
 
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum AuthError {
     /// Occurs when the RP ID hash in the attestation auth data does not match
     /// the value supplied with the creation request. (Potentially MitM!)
@@ -35,12 +42,52 @@ pub enum AuthError {
     /// Occurs when an error occurs during fido-u2f attestation
     U2fError(U2fError),
 
+    /// Occurs when an error occurs during packed attestation
+    PackedError(PackedError),
+
+    /// Occurs when the attestation certificate does not meet WebAuthn's requirements
+    Certificate(CertError),
+
+    /// Occurs when a certificate of the attestation chain was revoked, or its status is
+    /// required but unknown
+    Revocation(RevocationError),
+
     /// Occurs when the message built fails to validate against the
     /// signature provided
     SignatureVerificationFailed(webpki::Error),
 }
 
-impl std::error::Error for AuthError {}
+impl std::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AuthError::U2fError(e) => Some(e),
+            AuthError::PackedError(e) => Some(e),
+            AuthError::Certificate(e) => Some(e),
+            AuthError::Revocation(e) => Some(e),
+            AuthError::SignatureVerificationFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl AuthError {
+    /// Returns a stable, machine-readable code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthError::RpIdHashMismatch => "authenticator_data.rp_id_hash_mismatch",
+            AuthError::UserNotPresent => "authenticator_data.user_not_present",
+            AuthError::UserNotVerified => "authenticator_data.user_not_verified",
+            AuthError::CredDataMissing => "authenticator_data.credential_data_missing",
+            AuthError::PublicKeyMissing => "authenticator_data.public_key_missing",
+            AuthError::PrivateKeyMissing => "authenticator_data.private_key_missing",
+            AuthError::U2fError(_) => "authenticator_data.fido_u2f",
+            AuthError::PackedError(_) => "authenticator_data.packed",
+            AuthError::Certificate(e) => e.code(),
+            AuthError::Revocation(e) => e.code(),
+            AuthError::SignatureVerificationFailed(_) => "authenticator_data.bad_signature",
+        }
+    }
+}
 
 impl fmt::Display for AuthError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -52,6 +99,9 @@ impl fmt::Display for AuthError {
             AuthError::PublicKeyMissing => format!("public key components missing"),
             AuthError::PrivateKeyMissing => format!("private key components missing"),
             AuthError::U2fError(e) => format!("fido-u2f failed attestation: {}", e),
+            AuthError::PackedError(e) => format!("packed failed attestation: {}", e),
+            AuthError::Certificate(e) => e.to_string(),
+            AuthError::Revocation(e) => e.to_string(),
             AuthError::SignatureVerificationFailed(e) => {
                 format!("failed to verify messate with x.509 certificate: {:?}", e)
             }
@@ -67,6 +117,24 @@ impl From<webpki::Error> for AuthError {
     }
 }
 
+impl From<PackedError> for AuthError {
+    fn from(e: PackedError) -> AuthError {
+        AuthError::PackedError(e)
+    }
+}
+
+impl From<CertError> for AuthError {
+    fn from(e: CertError) -> AuthError {
+        AuthError::Certificate(e)
+    }
+}
+
+impl From<RevocationError> for AuthError {
+    fn from(e: RevocationError) -> AuthError {
+        AuthError::Revocation(e)
+    }
+}
+
 impl From<U2fError> for AuthError {
     fn from(e: U2fError) -> AuthError {
         AuthError::U2fError(e)
@@ -74,6 +142,7 @@ impl From<U2fError> for AuthError {
 }
 
 #[derive(Clone, Debug)]
+#[allow(dead_code)]
 pub struct CredentialData {
     pub aa_guid: [u8; 16],
     pub length: u16,
@@ -82,25 +151,16 @@ pub struct CredentialData {
 }
 
 impl CredentialData {
-    pub fn parse(data: &[u8]) -> Result<Self, AttestationError> {
-        let mut aa_guid = [0; 16];
-        aa_guid.copy_from_slice(&data[..16]);
-
-        let mut length = [0; 2];
-        length.copy_from_slice(&data[16..18]);
-        let length = u16::from_be_bytes(length);
-
-        let cred_id_end: usize = 18 + length as usize;
-        let mut cred_id: Vec<u8> = Vec::new();
-        cred_id.extend_from_slice(&data[18..cred_id_end]);
-
-        let cred_pub_key = CoseKey::parse(&data[cred_id_end..])?;
-
+    /// Converts the attested credential data decoded by the parsing core
+    ///
+    /// # Arguments
+    /// * `credential` - Attested credential data of the authenticator data
+    fn from_attested(credential: fido::AttestedCredentialRef) -> Result<Self, AttestationError> {
         Ok(CredentialData {
-            aa_guid,
-            length,
-            cred_id,
-            cred_pub_key,
+            aa_guid: *credential.aaguid(),
+            length: credential.id().len() as u16,
+            cred_id: credential.id().to_vec(),
+            cred_pub_key: CoseKey::parse(credential.public_key_bytes())?,
         })
     }
 }
@@ -111,6 +171,7 @@ pub struct AuthData {
     flags: u8,
     counter: u32,
     cred_data: Option<CredentialData>,
+    extensions: Option<AuthenticatorExtensionMap>,
 }
 
 #[allow(dead_code)]
@@ -128,49 +189,129 @@ pub enum AuthDataFlag {
     ExtensionData,
 }
 
+/// Verifies the relying party id hash and flags common to both views of the data
+///
+/// # Arguments
+/// * `cfg` - Relying party configuration
+/// * `rp_id_hash` - Hash of the relying party id in the auth data
+/// * `user_present` - Whether the user present flag is set
+fn verify(cfg: &Config, rp_id_hash: &[u8; 32], user_present: bool) -> Result<(), AuthError> {
+    // Verify the relying party's id matches what we configured
+    let expected = digest(&SHA256, cfg.id().as_bytes());
+    if rp_id_hash != expected.as_ref() {
+        return Err(AuthError::RpIdHashMismatch);
+    }
+
+    // Verify that the User Present bit of the flags in authData is set.
+    if !user_present {
+        return Err(AuthError::UserNotPresent);
+    }
+
+    // if user verification is required, check for the user verification flag
+    // TODO
+
+    Ok(())
+}
+
+/// A view of the authentication data borrowing the response buffer, used when checking
+/// assertions so the credential id, public key and extensions are not copied on every
+/// login.  Convert it with `into_owned` when the data has to outlive the buffer
+#[derive(Clone, Copy, Debug)]
+pub struct AuthDataRef<'a> {
+    data: fido::AuthenticatorDataRef<'a>,
+}
+
 #[allow(dead_code)]
-impl AuthData {
-    /// Parse the authentication data from a raw byte vector / slice
+impl<'a> AuthDataRef<'a> {
+    /// Parse the authentication data from a raw byte slice, without copying it
     ///
     /// # Arguments
-    /// * `data` - Data to parse into an AuthData
-    pub fn parse(data: Vec<u8>) -> Result<Self, AttestationError> {
-        let mut rp_id_hash = [0; 32];
-        rp_id_hash.copy_from_slice(&data[..32]);
+    /// * `data` - Data to parse into an AuthDataRef
+    pub fn parse(data: &'a [u8]) -> Result<Self, AttestationError> {
+        Ok(AuthDataRef {
+            data: fido::AuthenticatorDataRef::parse(data)?,
+        })
+    }
 
-        let mut counter = [0; 4];
-        counter.copy_from_slice(&data[33..37]);
+    /// Verify this data
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", name = "webauthn.auth_data", skip_all, err)
+    )]
+    pub fn validate(&self, cfg: &Config) -> Result<(), AuthError> {
+        verify(cfg, self.data.rp_id_hash(), self.data.user_present())
+    }
 
-        let cred_data = match data.len() > 37 {
-            true => Some(CredentialData::parse(&data[37..])?),
-            false => None,
-        };
+    /// Returns a reference to the hash of the relying party's id
+    pub fn rp_id_hash(&self) -> &'a [u8; 32] {
+        self.data.rp_id_hash()
+    }
+
+    /// Returns the bytes of the credential id stored in the credential data
+    pub fn credential_id(&self) -> Result<&'a [u8], AuthError> {
+        let data = self.data.credential().ok_or(AuthError::CredDataMissing)?;
+        Ok(data.id())
+    }
+
+    /// Decodes the authenticator extension outputs, if the extension data flag is set
+    pub fn extensions(&self) -> Result<Option<AuthenticatorExtensionMap>, AttestationError> {
+        self.data
+            .extensions()
+            .map(serde_cbor::from_slice)
+            .transpose()
+            .map_err(|_| AttestationError::InvalidExtensions)
+    }
+
+    /// Returns the signed counter (aka number of times this authenticator has been used)
+    pub fn count(&self) -> u32 {
+        self.data.counter()
+    }
+
+    /// Returns true if the user present flag is set in the response
+    /// Returns false otherwise
+    pub fn is_user_present(&self) -> bool {
+        self.data.user_present()
+    }
+
+    /// Returns true if the user verified flag is set in the response
+    /// Returns false otherwise
+    pub fn is_user_verified(&self) -> bool {
+        self.data.user_verified()
+    }
 
+    /// Decodes the credential public key and extensions into an owned `AuthData`
+    pub fn into_owned(self) -> Result<AuthData, AttestationError> {
         Ok(AuthData {
-            rp_id_hash,
-            flags: data[32],
-            counter: u32::from_be_bytes(counter),
-            cred_data,
+            rp_id_hash: *self.data.rp_id_hash(),
+            flags: self.data.flags(),
+            counter: self.data.counter(),
+            cred_data: self
+                .data
+                .credential()
+                .map(CredentialData::from_attested)
+                .transpose()?,
+            extensions: self.extensions()?,
         })
     }
+}
+
+#[allow(dead_code)]
+impl AuthData {
+    /// Parse the authentication data from a raw byte slice
+    ///
+    /// # Arguments
+    /// * `data` - Data to parse into an AuthData
+    pub fn parse(data: &[u8]) -> Result<Self, AttestationError> {
+        AuthDataRef::parse(data)?.into_owned()
+    }
 
     /// Verify this data
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", name = "webauthn.auth_data", skip_all, err)
+    )]
     pub fn validate(&self, cfg: &Config) -> Result<(), AuthError> {
-        // Verify the relying party's id matches what we configured
-        let rp_id_hash = digest(&SHA256, cfg.id().as_bytes());
-        if self.rp_id_hash != rp_id_hash.as_ref() {
-            return Err(AuthError::RpIdHashMismatch);
-        }
-
-        // Verify that the User Present bit of the flags in authData is set.
-        if !self.is_user_present() {
-            return Err(AuthError::UserNotPresent);
-        }
-
-        // if user verification is required, check for the user verification flag
-        // TODO
-
-        Ok(())
+        verify(cfg, &self.rp_id_hash, self.is_user_present())
     }
 
     /// Returns a reference to the hash of the relying party's id
@@ -197,6 +338,11 @@ impl AuthData {
         Ok(data.cred_id.as_slice())
     }
 
+    /// Returns the authenticator extension outputs, if the extension data flag is set
+    pub fn extensions(&self) -> Option<&AuthenticatorExtensionMap> {
+        self.extensions.as_ref()
+    }
+
     /// Returns the signed counter (aka number of times this authenticator has been used)
     /// Can be used to check for "cloned" authenticators when the value registered with
     /// us is (abnormally) higher than the one the authenticator has
@@ -1,10 +1,11 @@
 //! Client data related code
 
-use crate::webauthn::{response::WebAuthnType, Config};
+use crate::webauthn::{response::WebAuthnType, Config, CrossOriginPolicy};
 use serde::Deserialize;
 use std::fmt;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ClientDataError {
     /// Occurs when the response we received does not match the operation
     /// we were expecting. For example, requested `webauthn.create` but got
@@ -18,6 +19,14 @@ pub enum ClientDataError {
     /// Occurs when the origin the reponse specifies does not match the
     /// origin in our config
     OriginMismatch(String, String),
+
+    /// Occurs when the ceremony ran in a cross-origin iframe but the config does
+    /// not accept cross-origin ceremonies
+    CrossOriginDenied,
+
+    /// Occurs when the ceremony ran in a cross-origin iframe embedded in a
+    /// top-level origin the config does not accept (or one the client did not report)
+    TopOriginNotAllowed(Option<String>),
 }
 
 impl fmt::Display for ClientDataError {
@@ -31,12 +40,36 @@ impl fmt::Display for ClientDataError {
             ClientDataError::OriginMismatch(got, exp) => {
                 format!("Origin Mismatch: Got '{}', Expected: '{}'", got, exp)
             }
+            ClientDataError::CrossOriginDenied => {
+                "Cross-origin ceremonies are not allowed".to_owned()
+            }
+            ClientDataError::TopOriginNotAllowed(Some(top)) => {
+                format!("Top-level origin '{}' is not allowed", top)
+            }
+            ClientDataError::TopOriginNotAllowed(None) => {
+                "Top-level origin is required but missing".to_owned()
+            }
         };
 
         write!(f, "{}", msg)
     }
 }
 
+impl std::error::Error for ClientDataError {}
+
+impl ClientDataError {
+    /// Returns a stable, machine-readable code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            ClientDataError::InvalidWebAuthnType(_, _) => "client_data.type_mismatch",
+            ClientDataError::ChallengeMismatch => "client_data.challenge_mismatch",
+            ClientDataError::OriginMismatch(_, _) => "client_data.origin_mismatch",
+            ClientDataError::CrossOriginDenied => "client_data.cross_origin_denied",
+            ClientDataError::TopOriginNotAllowed(_) => "client_data.top_origin_not_allowed",
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub enum TokenBindingStatus {
     /// Token binding was used when communicating with the Relying Party.
@@ -84,6 +117,12 @@ pub struct ClientData {
     #[serde(default)]
     cross_origin: bool,
 
+    /// OPTIONAL - Fully qualified origin of the top-level page, present when the
+    /// ceremony ran in an iframe that is not same-origin with its ancestors
+    #[serde(alias = "topOrigin")]
+    #[serde(default)]
+    top_origin: Option<String>,
+
     /// OPTIONAL - Information about the state of the Token Binding protocol
     /// used when communicating with the Relying Party. Its absence indicates
     /// that the client doesn’t support token binding.
@@ -92,12 +131,41 @@ pub struct ClientData {
 }
 
 impl ClientData {
+    /// Returns the kind of ceremony the client data was collected for
+    pub fn ty(&self) -> &WebAuthnType {
+        &self.ty
+    }
+
+    /// Returns the base64url encoded challenge the client signed
+    pub fn challenge(&self) -> &str {
+        &self.challenge
+    }
+
+    /// Returns the origin the client data was collected for
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// Returns true if the client data was collected in a cross-origin iframe
+    pub fn cross_origin(&self) -> bool {
+        self.cross_origin
+    }
+
+    /// Returns the origin of the top-level page, if the client reported one
+    pub fn top_origin(&self) -> Option<&str> {
+        self.top_origin.as_deref()
+    }
+
     /// Ensures all criteria match what is anticipated
     ///
     /// # Arguments
     /// * `ty` - What kind of WebAuthn message to validate (i.e., Create or Get)
     /// * `cfg` - The configuration the request was created with (contains, origin, etc.)
     /// * `challenge` - The base64url encoded challenege string that was generated with the request
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", name = "webauthn.client_data", skip_all, err)
+    )]
     pub fn validate<S: Into<String>>(
         &self,
         ty: WebAuthnType,
@@ -119,6 +187,73 @@ impl ClientData {
             ));
         }
 
+        // A top-level origin is only reported for cross-origin ceremonies, but is
+        // checked whenever present
+        if self.cross_origin || self.top_origin.is_some() {
+            match cfg.cross_origin_policy() {
+                CrossOriginPolicy::Deny => return Err(ClientDataError::CrossOriginDenied),
+                CrossOriginPolicy::AllowAny => (),
+                CrossOriginPolicy::AllowTopOrigins(origins) => match self.top_origin {
+                    Some(ref top) if origins.iter().any(|o| o == top) => (),
+                    ref top => return Err(ClientDataError::TopOriginNotAllowed(top.clone())),
+                },
+            }
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_data(embedding: &str) -> ClientData {
+        let json = format!(
+            r#"{{"type":"webauthn.get","challenge":"c2l0ZQ","origin":"https://app.example.com"{}}}"#,
+            embedding
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn cross_origin_policy() {
+        let mut cfg = Config::new("https://app.example.com");
+        let validate = |embedding: &str, cfg: &Config| {
+            client_data(embedding)
+                .validate(WebAuthnType::Get, cfg, "c2l0ZQ")
+                .map_err(|e| e.code())
+        };
+        let iframe = r#","crossOrigin":true,"topOrigin":"https://shop.example.net""#;
+
+        assert!(validate(r#","crossOrigin":false"#, &cfg).is_ok());
+        assert_eq!(
+            validate(iframe, &cfg),
+            Err("client_data.cross_origin_denied")
+        );
+        assert_eq!(
+            validate(r#","topOrigin":"https://shop.example.net""#, &cfg),
+            Err("client_data.cross_origin_denied")
+        );
+
+        cfg.set_cross_origin_policy(CrossOriginPolicy::AllowAny);
+        assert!(validate(iframe, &cfg).is_ok());
+
+        cfg.set_cross_origin_policy(CrossOriginPolicy::AllowTopOrigins(vec![
+            "https://shop.example.net".to_owned(),
+        ]));
+        assert!(validate(iframe, &cfg).is_ok());
+        assert_eq!(
+            validate(
+                r#","crossOrigin":true,"topOrigin":"https://evil.example.net""#,
+                &cfg
+            ),
+            Err("client_data.top_origin_not_allowed")
+        );
+        // older browsers report crossOrigin alone
+        assert_eq!(
+            validate(r#","crossOrigin":true"#, &cfg),
+            Err("client_data.top_origin_not_allowed")
+        );
+    }
+}
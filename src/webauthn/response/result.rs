@@ -0,0 +1,159 @@
+//! Results of successfully validated ceremonies
+
+use crate::webauthn::{extensions::ExtensionOutputs, request::AuthenticatorAttachment, Device};
+
+/// The result of a successful registration ceremony (i.e., `register()`)
+#[derive(Debug)]
+pub struct RegistrationResult {
+    /// The newly registered device
+    device: Device,
+
+    /// Extension outputs returned by the client and authenticator
+    extensions: ExtensionOutputs,
+
+    /// How the authenticator was attached to the client, if the client reported it
+    attachment: Option<AuthenticatorAttachment>,
+
+    /// AAGUID reported in the attested credential data
+    aaguid: Option<[u8; 16]>,
+}
+
+impl RegistrationResult {
+    /// Creates a new registration result
+    ///
+    /// # Arguments
+    /// * `device` - The newly registered device
+    /// * `extensions` - Extension outputs returned with the response
+    pub fn new(device: Device, extensions: ExtensionOutputs) -> RegistrationResult {
+        RegistrationResult {
+            device,
+            extensions,
+            attachment: None,
+            aaguid: None,
+        }
+    }
+
+    /// Sets the authenticator attachment reported by the client
+    ///
+    /// # Arguments
+    /// * `attachment` - `authenticatorAttachment` of the response
+    pub(crate) fn with_attachment(mut self, attachment: Option<AuthenticatorAttachment>) -> Self {
+        self.attachment = attachment;
+        self
+    }
+
+    /// Sets the AAGUID reported by the authenticator
+    ///
+    /// # Arguments
+    /// * `aaguid` - AAGUID from the attested credential data
+    pub(crate) fn with_aaguid(mut self, aaguid: Option<[u8; 16]>) -> Self {
+        self.aaguid = aaguid;
+        self
+    }
+
+    /// Returns the newly registered device
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    /// Consumes this result, returning the newly registered device
+    pub fn into_device(self) -> Device {
+        self.device
+    }
+
+    /// Returns the extension outputs returned with the response
+    pub fn extensions(&self) -> &ExtensionOutputs {
+        &self.extensions
+    }
+
+    /// Returns how the authenticator was attached to the client: `Platform` for a
+    /// built-in authenticator (e.g., a platform passkey), `CrossPlatform` for a roaming one
+    /// (e.g., a security key or a phone).  `None` if the client did not report it
+    pub fn authenticator_attachment(&self) -> Option<AuthenticatorAttachment> {
+        self.attachment
+    }
+
+    /// Returns the AAGUID identifying the authenticator's make and model.  Authenticators
+    /// (or clients) that do not identify the model report all zeros
+    pub fn aaguid(&self) -> Option<&[u8; 16]> {
+        self.aaguid.as_ref()
+    }
+
+    /// Returns the name of the authenticator (e.g., "iCloud Keychain") if its AAGUID is in
+    /// the embedded [table](crate::webauthn::aaguid)
+    #[cfg(feature = "aaguid-names")]
+    pub fn authenticator_name(&self) -> Option<&'static str> {
+        self.aaguid
+            .as_ref()
+            .and_then(crate::webauthn::aaguid::authenticator_name)
+    }
+}
+
+/// The result of a successful authentication ceremony (i.e., `authenticate()`)
+#[derive(Debug)]
+pub struct AuthenticationResult {
+    /// Signature counter reported by the authenticator
+    count: u32,
+
+    /// True if the authenticator verified the user (e.g., PIN or biometric)
+    user_verified: bool,
+
+    /// Extension outputs returned by the client and authenticator
+    extensions: ExtensionOutputs,
+
+    /// How the authenticator was attached to the client, if the client reported it
+    attachment: Option<AuthenticatorAttachment>,
+}
+
+impl AuthenticationResult {
+    /// Creates a new authentication result
+    ///
+    /// # Arguments
+    /// * `count` - Signature counter reported by the authenticator
+    /// * `user_verified` - If the user verified flag was set
+    /// * `extensions` - Extension outputs returned with the response
+    pub fn new(
+        count: u32,
+        user_verified: bool,
+        extensions: ExtensionOutputs,
+    ) -> AuthenticationResult {
+        AuthenticationResult {
+            count,
+            user_verified,
+            extensions,
+            attachment: None,
+        }
+    }
+
+    /// Sets the authenticator attachment reported by the client
+    ///
+    /// # Arguments
+    /// * `attachment` - `authenticatorAttachment` of the response
+    pub(crate) fn with_attachment(mut self, attachment: Option<AuthenticatorAttachment>) -> Self {
+        self.attachment = attachment;
+        self
+    }
+
+    /// Returns the signature counter reported by the authenticator.  This should be
+    /// stored with the device for use in future authentications
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Returns true if the authenticator verified the user
+    pub fn user_verified(&self) -> bool {
+        self.user_verified
+    }
+
+    /// Returns the extension outputs returned with the response
+    pub fn extensions(&self) -> &ExtensionOutputs {
+        &self.extensions
+    }
+
+    /// Returns how the authenticator was attached to the client: `Platform` for a
+    /// built-in authenticator (e.g., a platform passkey), `CrossPlatform` for a roaming one
+    /// (e.g., a security key or a phone).  `None` if the client did not report it
+    pub fn authenticator_attachment(&self) -> Option<AuthenticatorAttachment> {
+        self.attachment
+    }
+}
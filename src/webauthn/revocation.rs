@@ -0,0 +1,1419 @@
+//! Revocation checking of attestation certificates
+//!
+//! Authenticator vendors revoke the batch certificates of production runs whose
+//! attestation keys were compromised, so a registration attested by one of those keys
+//! proves nothing about the authenticator.  A [`RevocationChecker`] set on the
+//! [`Config`](super::Config) checks every certificate of an attestation chain (`x5c`)
+//! once the attestation signature was verified, using:
+//!
+//! 1. OCSP responses stapled with [`RevocationChecker::staple_ocsp`]
+//! 2. CRLs added with [`RevocationChecker::add_crl`]
+//! 3. CRLs fetched from the certificate's CRL distribution points, when a [`CrlFetcher`]
+//!    is set (the `crl-fetch` feature provides one built on `reqwest`)
+//!
+//! OCSP responses and CRLs are verified with the certificate that issued the checked
+//! certificate: the next certificate of the chain or, when the chain holds the
+//! attestation certificate alone (as `fido-u2f` chains do), an issuer added with
+//! [`RevocationChecker::add_issuer`].  Every certificate's signature is verified with
+//! its issuer's key first, and a chain whose issuer did not sign a certificate is
+//! rejected.  OCSP responses must be signed by the issuer itself; delegated responders
+//! are not supported.  Self-signed certificates are not checked.
+//!
+//! The distribution points are named by the client's certificates, so CRLs are only
+//! fetched for chains leading to an issuer added to the checker, and only from the URLs
+//! the fetcher [allows](CrlFetcher::allows): `http` or `https` URLs naming a host (not an
+//! IP address or `localhost`), optionally restricted to a list of hosts.
+//!
+//! The async ceremony runner ([`integrations::Webauthn`](super::integrations::Webauthn))
+//! fetches CRLs ahead of validating a registration with an [`AsyncCrlFetcher`] instead,
+//! see [`RevocationChecker::prefetch`].  The blocking [`CrlFetcher`] must not be used
+//! there.
+//!
+//! A certificate whose status cannot be determined is accepted, unless the checker was
+//! built with [`RevocationChecker::require_status`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! let checker = Arc::new(RevocationChecker::new().require_status(true));
+//! checker.add_issuer(vendor_intermediate_der)?;
+//! checker.add_crl(vendor_crl_der)?;
+//!
+//! let mut cfg = Config::new("https://app.example.com");
+//! cfg.set_revocation_checker(checker.clone());
+//!
+//! // later, e.g. from a refresh task
+//! checker.staple_ocsp(ocsp_response_der)?;
+//! ```
+
+use crate::webauthn::response::{cert, AttestationCert, CertError};
+use ring::digest::{digest, Algorithm, SHA1_FOR_LEGACY_USE_ONLY, SHA256};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::RwLock,
+};
+use untrusted::{Input, Reader};
+use url::{Host, Url};
+use webpki::{EndEntityCert, SignatureAlgorithm};
+
+/// How long an OCSP response or CRL without a next update is considered current, in
+/// seconds (7 days)
+const MAX_AGE: u64 = 7 * 24 * 60 * 60;
+
+/// Clock skew tolerated when checking a this update time, in seconds
+const SKEW: u64 = 5 * 60;
+
+/// DER tags
+const SEQUENCE: u8 = 0x30;
+const OID: u8 = 0x06;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const ENUMERATED: u8 = 0x0a;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+
+/// `id-pkix-ocsp-basic` (1.3.6.1.5.5.7.48.1.1)
+const OCSP_BASIC: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+
+/// Hash algorithms of an OCSP `CertID`
+const SHA1_OID: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+const SHA256_OID: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+
+/// Signature algorithms of OCSP responses and CRLs
+const ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const ECDSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+const RSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const RSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const RSA_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum RevocationError {
+    /// Occurs when a certificate, OCSP response or CRL is not valid DER
+    Malformed(&'static str),
+
+    /// Occurs when an OCSP response reports an error instead of a status
+    OcspStatus(u8),
+
+    /// Occurs when an OCSP response or CRL is signed with an unsupported algorithm
+    UnsupportedAlgorithm,
+
+    /// Occurs when an OCSP response or CRL is not signed by the certificate's issuer
+    BadSignature,
+
+    /// Occurs when a certificate of the attestation chain was revoked
+    Revoked,
+
+    /// Occurs when the status of a certificate is required but only outdated OCSP
+    /// responses or CRLs cover it
+    Stale,
+
+    /// Occurs when the status of a certificate is required but nothing covers it
+    Unknown,
+
+    /// Occurs when a CRL could not be fetched
+    Fetch(String),
+}
+
+impl RevocationError {
+    /// Returns a stable, machine readable code identifying the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            RevocationError::Malformed(_) => "revocation.malformed",
+            RevocationError::OcspStatus(_) => "revocation.ocsp_status",
+            RevocationError::UnsupportedAlgorithm => "revocation.unsupported_algorithm",
+            RevocationError::BadSignature => "revocation.bad_signature",
+            RevocationError::Revoked => "revocation.revoked",
+            RevocationError::Stale => "revocation.stale",
+            RevocationError::Unknown => "revocation.unknown",
+            RevocationError::Fetch(_) => "revocation.fetch",
+        }
+    }
+}
+
+impl std::error::Error for RevocationError {}
+
+impl fmt::Display for RevocationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RevocationError::Malformed(what) => write!(f, "malformed {}", what),
+            RevocationError::OcspStatus(status) => {
+                write!(f, "OCSP responder returned status {}", status)
+            }
+            RevocationError::UnsupportedAlgorithm => {
+                write!(f, "unsupported revocation signature algorithm")
+            }
+            RevocationError::BadSignature => {
+                write!(f, "revocation information is not signed by the issuer")
+            }
+            RevocationError::Revoked => write!(f, "attestation certificate was revoked"),
+            RevocationError::Stale => {
+                write!(
+                    f,
+                    "revocation information for the attestation chain is outdated"
+                )
+            }
+            RevocationError::Unknown => {
+                write!(f, "revocation status of the attestation chain is unknown")
+            }
+            RevocationError::Fetch(e) => write!(f, "failed to fetch CRL: {}", e),
+        }
+    }
+}
+
+impl From<CertError> for RevocationError {
+    fn from(e: CertError) -> RevocationError {
+        match e {
+            CertError::Malformed(what) => RevocationError::Malformed(what),
+            _ => RevocationError::Malformed("certificate"),
+        }
+    }
+}
+
+/// Fetches CRLs from the distribution points named by certificates.  Called while
+/// validating a registration, so implementations should time out quickly
+pub trait CrlFetcher: Send + Sync {
+    /// Returns the DER encoded CRL published at a URL
+    ///
+    /// # Arguments
+    /// * `url` - URI of a CRL distribution point
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, RevocationError>;
+
+    /// Returns true if a CRL may be fetched from a URL.  The default allows `http` and
+    /// `https` URLs naming a host, other than `localhost`
+    ///
+    /// # Arguments
+    /// * `url` - URI of a CRL distribution point
+    fn allows(&self, url: &str) -> bool {
+        allowed(url, &[])
+    }
+}
+
+/// The future returned by [`AsyncCrlFetcher::fetch`]
+pub type FetchFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<u8>, RevocationError>> + Send + 'a>>;
+
+/// Fetches CRLs without blocking, see [`CrlFetcher`]
+pub trait AsyncCrlFetcher: Send + Sync {
+    /// Returns the DER encoded CRL published at a URL
+    ///
+    /// # Arguments
+    /// * `url` - URI of a CRL distribution point
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a>;
+
+    /// Returns true if a CRL may be fetched from a URL, see [`CrlFetcher::allows`]
+    ///
+    /// # Arguments
+    /// * `url` - URI of a CRL distribution point
+    fn allows(&self, url: &str) -> bool {
+        allowed(url, &[])
+    }
+}
+
+/// Returns true if `url` is an `http` or `https` URL naming a host other than
+/// `localhost` and, unless `hosts` is empty, one of `hosts`
+fn allowed(url: &str, hosts: &[String]) -> bool {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+    let host = match url.host() {
+        Some(Host::Domain(host)) => host,
+        _ => return false,
+    };
+
+    matches!(url.scheme(), "http" | "https")
+        && host != "localhost"
+        && !host.ends_with(".localhost")
+        && (hosts.is_empty() || hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+}
+
+/// Fetches CRLs with a blocking `reqwest` client, for registrations validated outside
+/// an async runtime.  Use [`AsyncReqwestCrlFetcher`] with the async ceremony runner
+#[cfg(feature = "crl-fetch")]
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestCrlFetcher {
+    client: reqwest::blocking::Client,
+    hosts: Vec<String>,
+}
+
+#[cfg(feature = "crl-fetch")]
+impl ReqwestCrlFetcher {
+    /// Creates a fetcher with a default client, allowing every host
+    pub fn new() -> ReqwestCrlFetcher {
+        Self::default()
+    }
+
+    /// Only fetches CRLs from the hosts allowed with this method (by default, every
+    /// host is allowed)
+    ///
+    /// # Arguments
+    /// * `host` - Host name of a CRL distribution point, e.g. `crl.vendor.com`
+    pub fn allow_host<S: Into<String>>(mut self, host: S) -> Self {
+        self.hosts.push(host.into());
+        self
+    }
+}
+
+#[cfg(feature = "crl-fetch")]
+impl CrlFetcher for ReqwestCrlFetcher {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, RevocationError> {
+        let fetch = |e: reqwest::Error| RevocationError::Fetch(e.to_string());
+        let resp = self
+            .client
+            .get(url)
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .map_err(fetch)?;
+        Ok(resp.bytes().map_err(fetch)?.to_vec())
+    }
+
+    fn allows(&self, url: &str) -> bool {
+        allowed(url, &self.hosts)
+    }
+}
+
+/// Fetches CRLs with an async `reqwest` client
+#[cfg(feature = "crl-fetch")]
+#[derive(Clone, Debug, Default)]
+pub struct AsyncReqwestCrlFetcher {
+    client: reqwest::Client,
+    hosts: Vec<String>,
+}
+
+#[cfg(feature = "crl-fetch")]
+impl AsyncReqwestCrlFetcher {
+    /// Creates a fetcher with a default client, allowing every host
+    pub fn new() -> AsyncReqwestCrlFetcher {
+        Self::default()
+    }
+
+    /// Only fetches CRLs from the hosts allowed with this method (by default, every
+    /// host is allowed)
+    ///
+    /// # Arguments
+    /// * `host` - Host name of a CRL distribution point, e.g. `crl.vendor.com`
+    pub fn allow_host<S: Into<String>>(mut self, host: S) -> Self {
+        self.hosts.push(host.into());
+        self
+    }
+}
+
+#[cfg(feature = "crl-fetch")]
+impl AsyncCrlFetcher for AsyncReqwestCrlFetcher {
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+        Box::pin(async move {
+            let fetch = |e: reqwest::Error| RevocationError::Fetch(e.to_string());
+            let resp = self
+                .client
+                .get(url)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status())
+                .map_err(fetch)?;
+            Ok(resp.bytes().await.map_err(fetch)?.to_vec())
+        })
+    }
+
+    fn allows(&self, url: &str) -> bool {
+        allowed(url, &self.hosts)
+    }
+}
+
+/// The status of a certificate
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Status {
+    Good,
+    Revoked,
+    Unknown,
+    Stale,
+}
+
+/// Data signed by an issuer
+#[derive(Clone, Debug)]
+struct Signed {
+    tbs: Vec<u8>,
+    alg: Vec<u8>,
+    sig: Vec<u8>,
+}
+
+impl Signed {
+    /// Reads the signature algorithm and signature following the signed data
+    ///
+    /// # Arguments
+    /// * `tbs` - The DER encoded signed data
+    /// * `reader` - Reader positioned after the signed data
+    fn read(tbs: Input, reader: &mut Reader) -> Result<Signed, RevocationError> {
+        let alg = cert::expect(&mut Reader::new(cert::expect(reader, SEQUENCE)?), OID)?;
+        let sig = match cert::expect(reader, BIT_STRING)?.as_slice_less_safe() {
+            [0x00, sig @ ..] => sig.to_vec(),
+            _ => return Err(RevocationError::Malformed("signature")),
+        };
+
+        Ok(Signed {
+            tbs: tbs.as_slice_less_safe().to_vec(),
+            alg: alg.as_slice_less_safe().to_vec(),
+            sig,
+        })
+    }
+
+    /// Verifies the signature with the public key of the issuer
+    ///
+    /// # Arguments
+    /// * `issuer` - DER encoded certificate of the issuer
+    fn verify(&self, issuer: &[u8]) -> Result<(), RevocationError> {
+        let algs: &[&SignatureAlgorithm] = match self.alg.as_slice() {
+            ECDSA_SHA256 => &[&webpki::ECDSA_P256_SHA256, &webpki::ECDSA_P384_SHA256],
+            ECDSA_SHA384 => &[&webpki::ECDSA_P256_SHA384, &webpki::ECDSA_P384_SHA384],
+            RSA_SHA256 => &[&webpki::RSA_PKCS1_2048_8192_SHA256],
+            RSA_SHA384 => &[&webpki::RSA_PKCS1_2048_8192_SHA384],
+            RSA_SHA512 => &[&webpki::RSA_PKCS1_2048_8192_SHA512],
+            _ => return Err(RevocationError::UnsupportedAlgorithm),
+        };
+
+        let issuer = EndEntityCert::from(issuer)
+            .map_err(|_| RevocationError::Malformed("issuer certificate"))?;
+        if algs
+            .iter()
+            .any(|alg| issuer.verify_signature(alg, &self.tbs, &self.sig).is_ok())
+        {
+            Ok(())
+        } else {
+            Err(RevocationError::BadSignature)
+        }
+    }
+}
+
+/// Verifies a certificate was signed by an issuer
+///
+/// # Arguments
+/// * `cert` - DER encoded certificate
+/// * `issuer` - DER encoded certificate of the issuer
+fn verify_issued(cert: &[u8], issuer: &[u8]) -> Result<(), RevocationError> {
+    let mut cert = Reader::new(cert::expect(&mut Reader::new(Input::from(cert)), SEQUENCE)?);
+    let (tbs, _) = cert.read_partial(|cert| cert::expect(cert, SEQUENCE))?;
+    Signed::read(tbs, &mut cert)?.verify(issuer)
+}
+
+/// The issuer of a certificate of a chain, which signed the certificate
+struct Issuer {
+    /// DER encoded certificate of the issuer
+    der: Vec<u8>,
+
+    /// Parsed certificate of the issuer
+    cert: AttestationCert,
+
+    /// True if the issuer was added to the checker or leads to one that was
+    anchored: bool,
+}
+
+/// A certificate revocation list
+#[derive(Clone, Debug)]
+struct Crl {
+    issuer_name: Vec<u8>,
+    this_update: u64,
+    next_update: Option<u64>,
+    revoked: HashSet<Vec<u8>>,
+    signed: Signed,
+}
+
+impl Crl {
+    /// Parses a DER encoded `CertificateList`
+    fn parse(der: &[u8]) -> Result<Crl, RevocationError> {
+        let mut list = Reader::new(cert::expect(&mut Reader::new(Input::from(der)), SEQUENCE)?);
+        let (tbs, tbs_value) = list.read_partial(|list| cert::expect(list, SEQUENCE))?;
+        let signed = Signed::read(tbs, &mut list)?;
+
+        // version, signature algorithm and issuer
+        let mut tbs = Reader::new(tbs_value);
+        if tbs.peek(INTEGER) {
+            cert::read_tlv(&mut tbs)?;
+        }
+        cert::expect(&mut tbs, SEQUENCE)?;
+        let (issuer_name, _) = tbs.read_partial(|tbs| cert::expect(tbs, SEQUENCE))?;
+
+        let this_update = read_time(&mut tbs)?;
+        let next_update = if tbs.peek(UTC_TIME) || tbs.peek(GENERALIZED_TIME) {
+            Some(read_time(&mut tbs)?)
+        } else {
+            None
+        };
+
+        let mut revoked = HashSet::new();
+        if tbs.peek(SEQUENCE) {
+            let mut entries = Reader::new(cert::expect(&mut tbs, SEQUENCE)?);
+            while !entries.at_end() {
+                let mut entry = Reader::new(cert::expect(&mut entries, SEQUENCE)?);
+                let serial = cert::expect(&mut entry, INTEGER)?;
+                revoked.insert(serial.as_slice_less_safe().to_vec());
+            }
+        }
+
+        Ok(Crl {
+            issuer_name: issuer_name.as_slice_less_safe().to_vec(),
+            this_update,
+            next_update,
+            revoked,
+            signed,
+        })
+    }
+
+    /// Returns the status of a certificate issued by the CRL's issuer
+    fn status(&self, cert: &AttestationCert, now: u64) -> Status {
+        if self.revoked.contains(cert.serial()) {
+            Status::Revoked
+        } else if current(self.this_update, self.next_update, now) {
+            Status::Good
+        } else {
+            Status::Stale
+        }
+    }
+}
+
+/// The status of one certificate, from an OCSP response
+#[derive(Clone, Debug)]
+struct Ocsp {
+    hash: &'static Algorithm,
+    issuer_key_hash: Vec<u8>,
+    serial: Vec<u8>,
+    status: Status,
+    this_update: u64,
+    next_update: Option<u64>,
+    signed: Signed,
+}
+
+impl Ocsp {
+    /// Parses a DER encoded `OCSPResponse`, returning the status of every certificate
+    /// it covers
+    fn parse(der: &[u8]) -> Result<Vec<Ocsp>, RevocationError> {
+        let mut resp = Reader::new(cert::expect(&mut Reader::new(Input::from(der)), SEQUENCE)?);
+        match cert::expect(&mut resp, ENUMERATED)?.as_slice_less_safe() {
+            [0x00] => (),
+            [status] => return Err(RevocationError::OcspStatus(*status)),
+            _ => return Err(RevocationError::Malformed("OCSP response status")),
+        }
+
+        // responseBytes [0]
+        let mut bytes = Reader::new(cert::expect(
+            &mut Reader::new(cert::expect(&mut resp, 0xa0)?),
+            SEQUENCE,
+        )?);
+        if cert::expect(&mut bytes, OID)?.as_slice_less_safe() != OCSP_BASIC {
+            return Err(RevocationError::Malformed("OCSP response type"));
+        }
+        let basic = cert::expect(&mut bytes, OCTET_STRING)?;
+
+        let mut basic = Reader::new(cert::expect(&mut Reader::new(basic), SEQUENCE)?);
+        let (tbs, tbs_value) = basic.read_partial(|basic| cert::expect(basic, SEQUENCE))?;
+        let signed = Signed::read(tbs, &mut basic)?;
+
+        // version, responder id and produced at
+        let mut data = Reader::new(tbs_value);
+        if data.peek(0xa0) {
+            cert::read_tlv(&mut data)?;
+        }
+        cert::read_tlv(&mut data)?;
+        cert::expect(&mut data, GENERALIZED_TIME)?;
+
+        let mut parsed = vec![];
+        let mut responses = Reader::new(cert::expect(&mut data, SEQUENCE)?);
+        while !responses.at_end() {
+            let mut single = Reader::new(cert::expect(&mut responses, SEQUENCE)?);
+
+            let mut cert_id = Reader::new(cert::expect(&mut single, SEQUENCE)?);
+            let hash_alg =
+                cert::expect(&mut Reader::new(cert::expect(&mut cert_id, SEQUENCE)?), OID)?;
+            let hash = match hash_alg.as_slice_less_safe() {
+                SHA1_OID => &SHA1_FOR_LEGACY_USE_ONLY,
+                SHA256_OID => &SHA256,
+                _ => return Err(RevocationError::UnsupportedAlgorithm),
+            };
+            cert::expect(&mut cert_id, OCTET_STRING)?;
+            let issuer_key_hash = cert::expect(&mut cert_id, OCTET_STRING)?;
+            let serial = cert::expect(&mut cert_id, INTEGER)?;
+
+            // good [0], revoked [1] and unknown [2]
+            let status = match cert::read_tlv(&mut single)? {
+                (0x80, _) => Status::Good,
+                (0xa1, _) => Status::Revoked,
+                _ => Status::Unknown,
+            };
+            let this_update = read_time(&mut single)?;
+            let next_update = if single.peek(0xa0) {
+                Some(read_time(&mut Reader::new(cert::expect(
+                    &mut single,
+                    0xa0,
+                )?))?)
+            } else {
+                None
+            };
+
+            parsed.push(Ocsp {
+                hash,
+                issuer_key_hash: issuer_key_hash.as_slice_less_safe().to_vec(),
+                serial: serial.as_slice_less_safe().to_vec(),
+                status,
+                this_update,
+                next_update,
+                signed: signed.clone(),
+            });
+        }
+        Ok(parsed)
+    }
+
+    /// Returns true if the response covers a certificate
+    ///
+    /// # Arguments
+    /// * `cert` - Certificate to check
+    /// * `issuer` - Issuer of the certificate
+    fn covers(&self, cert: &AttestationCert, issuer: &AttestationCert) -> bool {
+        self.serial == cert.serial()
+            && self.issuer_key_hash == digest(self.hash, issuer.public_key()).as_ref()
+    }
+
+    /// Returns the status of the certificate the response covers
+    fn status(&self, now: u64) -> Status {
+        match self.status {
+            Status::Good if !current(self.this_update, self.next_update, now) => Status::Stale,
+            status => status,
+        }
+    }
+}
+
+/// Checks attestation certificate chains against OCSP responses and CRLs
+pub struct RevocationChecker {
+    /// Issuers of attestation certificates, as DER and parsed
+    issuers: RwLock<Vec<(Vec<u8>, AttestationCert)>>,
+
+    /// CRLs added by the application
+    crls: RwLock<Vec<Crl>>,
+
+    /// Statuses from stapled OCSP responses
+    ocsp: RwLock<Vec<Ocsp>>,
+
+    /// CRLs fetched from distribution points, keyed by URI
+    fetched: RwLock<HashMap<String, Crl>>,
+
+    /// Fetches CRLs from distribution points
+    fetcher: Option<Box<dyn CrlFetcher>>,
+
+    /// Fetches CRLs from distribution points ahead of checking a chain
+    async_fetcher: Option<Box<dyn AsyncCrlFetcher>>,
+
+    /// Reject certificates whose status is unknown
+    require: bool,
+}
+
+impl fmt::Debug for RevocationChecker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RevocationChecker")
+            .field("issuers", &self.issuers.read().unwrap().len())
+            .field("crls", &self.crls.read().unwrap().len())
+            .field("ocsp", &self.ocsp.read().unwrap().len())
+            .field("fetch", &self.fetcher.is_some())
+            .field("async_fetch", &self.async_fetcher.is_some())
+            .field("require", &self.require)
+            .finish()
+    }
+}
+
+impl Default for RevocationChecker {
+    fn default() -> RevocationChecker {
+        RevocationChecker {
+            issuers: RwLock::new(vec![]),
+            crls: RwLock::new(vec![]),
+            ocsp: RwLock::new(vec![]),
+            fetched: RwLock::new(HashMap::new()),
+            fetcher: None,
+            async_fetcher: None,
+            require: false,
+        }
+    }
+}
+
+impl RevocationChecker {
+    /// Creates a checker without any revocation information, that does not fetch CRLs
+    pub fn new() -> RevocationChecker {
+        Self::default()
+    }
+
+    /// Sets whether certificates whose status cannot be determined are rejected
+    /// (default: false)
+    ///
+    /// # Arguments
+    /// * `require` - True to reject certificates without a current status
+    pub fn require_status(mut self, require: bool) -> Self {
+        self.require = require;
+        self
+    }
+
+    /// Fetches CRLs from the distribution points of certificates not covered by an
+    /// OCSP response or a CRL added to the checker, in chains leading to an added issuer.
+    /// Fetched CRLs are cached until their next update.  The fetcher is called from
+    /// [`check`](RevocationChecker::check), so it must not block an async runtime: use
+    /// [`with_async_fetcher`](RevocationChecker::with_async_fetcher) there
+    ///
+    /// # Arguments
+    /// * `fetcher` - Fetcher to fetch CRLs with
+    pub fn with_fetcher<F: CrlFetcher + 'static>(mut self, fetcher: F) -> Self {
+        self.fetcher = Some(Box::new(fetcher));
+        self
+    }
+
+    /// Fetches CRLs without blocking when [prefetching](RevocationChecker::prefetch),
+    /// as the async ceremony runner does before validating a registration
+    ///
+    /// # Arguments
+    /// * `fetcher` - Fetcher to fetch CRLs with
+    pub fn with_async_fetcher<F: AsyncCrlFetcher + 'static>(mut self, fetcher: F) -> Self {
+        self.async_fetcher = Some(Box::new(fetcher));
+        self
+    }
+
+    /// Adds the certificate of an issuer of attestation certificates, used to verify the
+    /// OCSP responses and CRLs covering certificates the chain does not include the
+    /// issuer of
+    ///
+    /// # Arguments
+    /// * `der` - DER encoded issuer certificate
+    pub fn add_issuer(&self, der: Vec<u8>) -> Result<(), RevocationError> {
+        let cert = AttestationCert::parse(&der)?;
+        self.issuers.write().unwrap().push((der, cert));
+        Ok(())
+    }
+
+    /// Replaces the issuers added to the checker, e.g. with the certificates of a reloaded
+    /// [`TrustBundle`](super::trust::TrustBundle).  On error the issuers are left as they
+    /// were
+    ///
+    /// # Arguments
+    /// * `certs` - DER encoded issuer certificates
+    pub fn set_issuers<I: IntoIterator<Item = Vec<u8>>>(
+        &self,
+        certs: I,
+    ) -> Result<(), RevocationError> {
+        let issuers = certs
+            .into_iter()
+            .map(|der| AttestationCert::parse(&der).map(|cert| (der, cert)))
+            .collect::<Result<Vec<_>, _>>()?;
+        *self.issuers.write().unwrap() = issuers;
+        Ok(())
+    }
+
+    /// Adds a CRL, replacing any earlier CRL of the same issuer.  The signature is
+    /// verified when a certificate of the issuer is checked
+    ///
+    /// # Arguments
+    /// * `der` - DER encoded CRL
+    pub fn add_crl(&self, der: &[u8]) -> Result<(), RevocationError> {
+        let crl = Crl::parse(der)?;
+        let mut crls = self.crls.write().unwrap();
+        crls.retain(|c| c.issuer_name != crl.issuer_name);
+        crls.push(crl);
+        Ok(())
+    }
+
+    /// Staples an OCSP response, replacing earlier responses about the same
+    /// certificates.  The signature is verified when a covered certificate is checked
+    ///
+    /// # Arguments
+    /// * `der` - DER encoded OCSP response
+    pub fn staple_ocsp(&self, der: &[u8]) -> Result<(), RevocationError> {
+        let responses = Ocsp::parse(der)?;
+        let mut ocsp = self.ocsp.write().unwrap();
+        ocsp.retain(|o| {
+            !responses
+                .iter()
+                .any(|r| r.serial == o.serial && r.issuer_key_hash == o.issuer_key_hash)
+        });
+        ocsp.extend(responses);
+        Ok(())
+    }
+
+    /// Checks no certificate of an attestation chain was revoked
+    ///
+    /// # Arguments
+    /// * `chain` - DER encoded certificates, the attestation certificate first (`x5c`)
+    /// * `now` - Current time, in seconds since the unix epoch
+    pub fn check<C: AsRef<[u8]>>(&self, chain: &[C], now: u64) -> Result<(), RevocationError> {
+        let certs = parse_chain(chain)?;
+        for (cert, issuer) in certs.iter().zip(self.issuers_of(chain, &certs)?) {
+            if cert.issuer_name() == cert.subject_name() {
+                continue;
+            }
+
+            let status = match issuer {
+                Some(issuer) => self.status(cert, &issuer, now, self.fetcher.as_deref())?,
+                None => Status::Unknown,
+            };
+            match status {
+                Status::Good => (),
+                Status::Revoked => return Err(RevocationError::Revoked),
+                Status::Stale if self.require => return Err(RevocationError::Stale),
+                _ if self.require => return Err(RevocationError::Unknown),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetches, with the [async fetcher](RevocationChecker::with_async_fetcher), the CRLs
+    /// [`check`](RevocationChecker::check) would need for a chain, so checking it does
+    /// not block.  CRLs that cannot be fetched are skipped, leaving the status of the
+    /// certificates they cover unknown
+    ///
+    /// # Arguments
+    /// * `chain` - DER encoded certificates, the attestation certificate first (`x5c`)
+    /// * `now` - Current time, in seconds since the unix epoch
+    pub async fn prefetch<C: AsRef<[u8]>>(&self, chain: &[C], now: u64) {
+        let fetcher = match self.async_fetcher.as_ref() {
+            Some(fetcher) => fetcher,
+            None => return,
+        };
+        let certs = match parse_chain(chain) {
+            Ok(certs) => certs,
+            Err(_) => return,
+        };
+        let issuers = match self.issuers_of(chain, &certs) {
+            Ok(issuers) => issuers,
+            Err(_) => return,
+        };
+
+        for (cert, issuer) in certs.iter().zip(issuers) {
+            let issuer = match issuer {
+                Some(issuer) if issuer.anchored => issuer,
+                _ => continue,
+            };
+            match self.status(cert, &issuer, now, None) {
+                Ok(Status::Unknown) | Ok(Status::Stale) => (),
+                _ => continue,
+            }
+
+            for url in cert.crl_urls().iter().filter(|url| fetcher.allows(url)) {
+                let crl = match fetcher.fetch(url).await {
+                    Ok(der) => Crl::parse(&der),
+                    Err(e) => Err(e),
+                };
+                if let Ok(crl) = crl {
+                    if crl.issuer_name == cert.issuer_name()
+                        && crl.signed.verify(&issuer.der).is_ok()
+                    {
+                        self.fetched.write().unwrap().insert(url.clone(), crl);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the issuer of each certificate of a chain, after verifying it signed the
+    /// certificate, or `None` if no issuer is known.  Self-signed certificates have no
+    /// issuer
+    ///
+    /// # Arguments
+    /// * `chain` - DER encoded certificates, the attestation certificate first
+    /// * `certs` - The parsed certificates of `chain`
+    fn issuers_of<C: AsRef<[u8]>>(
+        &self,
+        chain: &[C],
+        certs: &[AttestationCert],
+    ) -> Result<Vec<Option<Issuer>>, RevocationError> {
+        let issuers = self.issuers.read().unwrap();
+        let added = |der: &[u8]| issuers.iter().any(|(issuer, _)| issuer.as_slice() == der);
+
+        // walk from the root, so whether the next certificate is anchored is known
+        let mut found = Vec::with_capacity(certs.len());
+        let mut anchored = false;
+        for (i, cert) in certs.iter().enumerate().rev() {
+            let der = chain[i].as_ref();
+            if cert.issuer_name() == cert.subject_name() {
+                anchored = added(der);
+                found.push(None);
+                continue;
+            }
+
+            let next = chain
+                .get(i + 1)
+                .map(AsRef::as_ref)
+                .zip(certs.get(i + 1))
+                .filter(|(_, issuer)| issuer.subject_name() == cert.issuer_name());
+            let issuer = match next {
+                Some((issuer, parsed)) => {
+                    verify_issued(der, issuer)?;
+                    Some(Issuer {
+                        der: issuer.to_vec(),
+                        cert: parsed.clone(),
+                        anchored: anchored || added(issuer),
+                    })
+                }
+                None => {
+                    let mut named = issuers
+                        .iter()
+                        .filter(|(_, issuer)| issuer.subject_name() == cert.issuer_name())
+                        .peekable();
+                    if named.peek().is_none() {
+                        None
+                    } else {
+                        let (issuer, parsed) = named
+                            .find(|(issuer, _)| verify_issued(der, issuer).is_ok())
+                            .ok_or(RevocationError::BadSignature)?;
+                        Some(Issuer {
+                            der: issuer.clone(),
+                            cert: parsed.clone(),
+                            anchored: true,
+                        })
+                    }
+                }
+            };
+
+            anchored = added(der) || issuer.as_ref().is_some_and(|issuer| issuer.anchored);
+            found.push(issuer);
+        }
+
+        found.reverse();
+        Ok(found)
+    }
+
+    /// Returns the status of a certificate, from the first source that knows it
+    ///
+    /// # Arguments
+    /// * `cert` - Certificate to check
+    /// * `issuer` - Verified issuer of the certificate
+    /// * `now` - Current time, in seconds since the unix epoch
+    /// * `fetcher` - Fetcher to fetch CRLs that were not fetched before with
+    fn status(
+        &self,
+        cert: &AttestationCert,
+        issuer: &Issuer,
+        now: u64,
+        fetcher: Option<&dyn CrlFetcher>,
+    ) -> Result<Status, RevocationError> {
+        // stale information is only reported when nothing current is found
+        let mut status = Status::Unknown;
+        let mut merge = |next: Status| match next {
+            Status::Good | Status::Revoked => Some(next),
+            Status::Stale => {
+                status = Status::Stale;
+                None
+            }
+            Status::Unknown => None,
+        };
+
+        for ocsp in self.ocsp.read().unwrap().iter() {
+            if ocsp.covers(cert, &issuer.cert) {
+                ocsp.signed.verify(&issuer.der)?;
+                if let Some(status) = merge(ocsp.status(now)) {
+                    return Ok(status);
+                }
+            }
+        }
+
+        for crl in self.crls.read().unwrap().iter() {
+            if crl.issuer_name == cert.issuer_name() {
+                crl.signed.verify(&issuer.der)?;
+                if let Some(status) = merge(crl.status(cert, now)) {
+                    return Ok(status);
+                }
+            }
+        }
+
+        // distribution points are only trusted once the chain leads to an added issuer
+        if !issuer.anchored {
+            return Ok(status);
+        }
+        for url in cert.crl_urls() {
+            let cached = self.fetched.read().unwrap().get(url).cloned();
+            let (crl, fetched) = match (cached, fetcher) {
+                (Some(crl), _) if current(crl.this_update, crl.next_update, now) => (crl, false),
+                (_, Some(fetcher)) if fetcher.allows(url) => {
+                    (Crl::parse(&fetcher.fetch(url)?)?, true)
+                }
+                _ => continue,
+            };
+            if crl.issuer_name != cert.issuer_name() {
+                return Err(RevocationError::BadSignature);
+            }
+            crl.signed.verify(&issuer.der)?;
+            if fetched {
+                self.fetched
+                    .write()
+                    .unwrap()
+                    .insert(url.clone(), crl.clone());
+            }
+            if let Some(status) = merge(crl.status(cert, now)) {
+                return Ok(status);
+            }
+        }
+
+        Ok(status)
+    }
+}
+
+/// Parses the certificates of a chain
+fn parse_chain<C: AsRef<[u8]>>(chain: &[C]) -> Result<Vec<AttestationCert>, RevocationError> {
+    chain
+        .iter()
+        .map(|der| AttestationCert::parse(der.as_ref()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(RevocationError::from)
+}
+
+/// Returns true if revocation information issued at `this_update` is current
+fn current(this_update: u64, next_update: Option<u64>, now: u64) -> bool {
+    let expires = next_update.unwrap_or_else(|| this_update.saturating_add(MAX_AGE));
+    this_update <= now.saturating_add(SKEW) && now <= expires
+}
+
+/// Reads a UTCTime or GeneralizedTime, returning seconds since the unix epoch
+fn read_time(reader: &mut Reader) -> Result<u64, RevocationError> {
+    let malformed = RevocationError::Malformed("time");
+    let (tag, value) = cert::read_tlv(reader)?;
+    let value = std::str::from_utf8(value.as_slice_less_safe()).map_err(|_| malformed.clone())?;
+
+    // YYMMDDHHMMSSZ or YYYYMMDDHHMMSSZ
+    let (year, rest) = match (tag, value.len()) {
+        (UTC_TIME, 13) => {
+            let year: u64 = value[..2].parse().map_err(|_| malformed.clone())?;
+            (
+                if year < 50 { 2000 + year } else { 1900 + year },
+                &value[2..],
+            )
+        }
+        (GENERALIZED_TIME, 15) => (
+            value[..4].parse().map_err(|_| malformed.clone())?,
+            &value[4..],
+        ),
+        _ => return Err(malformed),
+    };
+    if !rest.ends_with('Z') || !rest[..10].bytes().all(|b| b.is_ascii_digit()) {
+        return Err(malformed);
+    }
+
+    let field = |i: usize| rest[i..i + 2].parse::<u64>().unwrap_or(0);
+    let (month, day) = (field(0), field(2));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(malformed);
+    }
+
+    // days from the civil date, March based so the leap day ends the year
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = (era * 146_097 + doe)
+        .checked_sub(719_468)
+        .ok_or(malformed)?;
+
+    Ok(days * 86_400 + field(4) * 3_600 + field(6) * 60 + field(8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// 2025-01-01T00:00:00Z
+    const NOW: u64 = 1_735_689_600;
+
+    /// DER encoding of the ecdsa-with-SHA256 algorithm identifier
+    const ECDSA_WITH_SHA256: &[u8] = &[
+        0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02,
+    ];
+
+    /// Encodes a DER value
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut value = vec![tag];
+        match content.len() {
+            len if len < 0x80 => value.push(len as u8),
+            len if len < 0x100 => value.extend_from_slice(&[0x81, len as u8]),
+            len => value.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+        }
+        value.extend_from_slice(content);
+        value
+    }
+
+    /// Encodes a name with a single common name
+    fn name(cn: &str) -> Vec<u8> {
+        let cn = der(
+            0x30,
+            &[der(OID, &[0x55, 0x04, 0x03]), der(0x0c, cn.as_bytes())].concat(),
+        );
+        der(SEQUENCE, &der(0x31, &cn))
+    }
+
+    fn key() -> EcdsaKeyPair {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref()).unwrap()
+    }
+
+    /// Signs `tbs`, appending the algorithm and signature
+    fn sign(tbs: Vec<u8>, key: &EcdsaKeyPair) -> Vec<u8> {
+        let sig = key.sign(&SystemRandom::new(), &tbs).unwrap();
+        let sig = der(BIT_STRING, &[&[0x00][..], sig.as_ref()].concat());
+        der(SEQUENCE, &[tbs, ECDSA_WITH_SHA256.to_vec(), sig].concat())
+    }
+
+    /// Issues a v3 certificate
+    fn cert(
+        serial: u8,
+        issuer: &str,
+        subject: &str,
+        public_key: &[u8],
+        signer: &EcdsaKeyPair,
+        crl_url: Option<&str>,
+    ) -> Vec<u8> {
+        let spki = der(
+            SEQUENCE,
+            &[
+                der(
+                    SEQUENCE,
+                    &[
+                        der(OID, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01]),
+                        der(OID, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07]),
+                    ]
+                    .concat(),
+                ),
+                der(BIT_STRING, &[&[0x00][..], public_key].concat()),
+            ]
+            .concat(),
+        );
+
+        let mut extensions = der(
+            SEQUENCE,
+            &[
+                der(OID, &[0x55, 0x1d, 0x13]),
+                der(OCTET_STRING, &der(SEQUENCE, &[])),
+            ]
+            .concat(),
+        );
+        if let Some(url) = crl_url {
+            let point = der(SEQUENCE, &der(0xa0, &der(0xa0, &der(0x86, url.as_bytes()))));
+            extensions.extend(der(
+                SEQUENCE,
+                &[
+                    der(OID, &[0x55, 0x1d, 0x1f]),
+                    der(OCTET_STRING, &der(SEQUENCE, &point)),
+                ]
+                .concat(),
+            ));
+        }
+
+        let tbs = der(
+            SEQUENCE,
+            &[
+                der(0xa0, &der(INTEGER, &[0x02])),
+                der(INTEGER, &[serial]),
+                ECDSA_WITH_SHA256.to_vec(),
+                name(issuer),
+                der(
+                    SEQUENCE,
+                    &[
+                        der(UTC_TIME, b"200101000000Z"),
+                        der(GENERALIZED_TIME, b"99991231235959Z"),
+                    ]
+                    .concat(),
+                ),
+                name(subject),
+                spki,
+                der(0xa3, &der(SEQUENCE, &extensions)),
+            ]
+            .concat(),
+        );
+        sign(tbs, signer)
+    }
+
+    /// Issues a CRL revoking certificates by serial
+    fn crl(issuer: &str, signer: &EcdsaKeyPair, revoked: &[u8], next_update: &[u8]) -> Vec<u8> {
+        let entries = revoked
+            .iter()
+            .map(|serial| {
+                der(
+                    SEQUENCE,
+                    &[der(INTEGER, &[*serial]), der(UTC_TIME, b"241201000000Z")].concat(),
+                )
+            })
+            .collect::<Vec<_>>()
+            .concat();
+        let tbs = der(
+            SEQUENCE,
+            &[
+                der(INTEGER, &[0x01]),
+                ECDSA_WITH_SHA256.to_vec(),
+                name(issuer),
+                der(UTC_TIME, b"241231000000Z"),
+                der(UTC_TIME, next_update),
+                der(SEQUENCE, &entries),
+            ]
+            .concat(),
+        );
+        sign(tbs, signer)
+    }
+
+    /// Builds an OCSP response about one certificate
+    ///
+    /// # Arguments
+    /// * `status` - Encoded certificate status
+    fn ocsp(issuer: &EcdsaKeyPair, signer: &EcdsaKeyPair, serial: u8, status: &[u8]) -> Vec<u8> {
+        let key_hash = digest(&SHA1_FOR_LEGACY_USE_ONLY, issuer.public_key().as_ref());
+        let cert_id = der(
+            SEQUENCE,
+            &[
+                der(SEQUENCE, &der(OID, SHA1_OID)),
+                der(OCTET_STRING, &[0; 20]),
+                der(OCTET_STRING, key_hash.as_ref()),
+                der(INTEGER, &[serial]),
+            ]
+            .concat(),
+        );
+        let single = der(
+            SEQUENCE,
+            &[
+                cert_id,
+                status.to_vec(),
+                der(GENERALIZED_TIME, b"20241231000000Z"),
+                der(0xa0, &der(GENERALIZED_TIME, b"20250107000000Z")),
+            ]
+            .concat(),
+        );
+        let tbs = der(
+            SEQUENCE,
+            &[
+                der(0xa2, &der(OCTET_STRING, key_hash.as_ref())),
+                der(GENERALIZED_TIME, b"20241231000000Z"),
+                der(SEQUENCE, &single),
+            ]
+            .concat(),
+        );
+
+        let basic = sign(tbs, signer);
+        let bytes = der(
+            SEQUENCE,
+            &[der(OID, OCSP_BASIC), der(OCTET_STRING, &basic)].concat(),
+        );
+        der(
+            SEQUENCE,
+            &[der(ENUMERATED, &[0x00]), der(0xa0, &bytes)].concat(),
+        )
+    }
+
+    /// A CA and an attestation certificate it issued, with serial 5
+    fn chain(crl_url: Option<&str>) -> (EcdsaKeyPair, Vec<u8>, Vec<u8>) {
+        let (ca_key, leaf_key) = (key(), key());
+        let ca = cert(1, "CA", "CA", ca_key.public_key().as_ref(), &ca_key, None);
+        let leaf = cert(
+            5,
+            "CA",
+            "Batch",
+            leaf_key.public_key().as_ref(),
+            &ca_key,
+            crl_url,
+        );
+        (ca_key, ca, leaf)
+    }
+
+    #[test]
+    fn check_crl() {
+        let (ca_key, ca, leaf) = chain(None);
+        let chain = [leaf, ca];
+
+        // nothing is known about the chain
+        assert!(RevocationChecker::new().check(&chain, NOW).is_ok());
+        let checker = RevocationChecker::new().require_status(true);
+        assert_eq!(checker.check(&chain, NOW), Err(RevocationError::Unknown));
+
+        checker
+            .add_crl(&crl("CA", &ca_key, &[4, 6], b"250107000000Z"))
+            .unwrap();
+        assert!(checker.check(&chain, NOW).is_ok());
+
+        // an outdated CRL only tells the certificate was not revoked back then
+        checker
+            .add_crl(&crl("CA", &ca_key, &[], b"241231120000Z"))
+            .unwrap();
+        assert_eq!(checker.check(&chain, NOW), Err(RevocationError::Stale));
+
+        checker
+            .add_crl(&crl("CA", &ca_key, &[5], b"250107000000Z"))
+            .unwrap();
+        assert_eq!(checker.check(&chain, NOW), Err(RevocationError::Revoked));
+
+        // a CRL signed by someone else
+        checker
+            .add_crl(&crl("CA", &key(), &[], b"250107000000Z"))
+            .unwrap();
+        assert_eq!(
+            checker.check(&chain, NOW),
+            Err(RevocationError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn check_ocsp() {
+        let (ca_key, ca, leaf) = chain(None);
+        let checker = RevocationChecker::new().require_status(true);
+
+        // the chain holds the attestation certificate alone, like fido-u2f chains
+        checker
+            .staple_ocsp(&ocsp(&ca_key, &ca_key, 5, &[0x80, 0x00]))
+            .unwrap();
+        assert_eq!(checker.check(&[&leaf], NOW), Err(RevocationError::Unknown));
+        checker.add_issuer(ca).unwrap();
+        assert!(checker.check(&[&leaf], NOW).is_ok());
+        assert_eq!(
+            checker.check(&[&leaf], NOW + 7 * 24 * 60 * 60),
+            Err(RevocationError::Stale)
+        );
+
+        let revoked = der(0xa1, &der(GENERALIZED_TIME, b"20241201000000Z"));
+        checker
+            .staple_ocsp(&ocsp(&ca_key, &ca_key, 5, &revoked))
+            .unwrap();
+        assert_eq!(checker.check(&[&leaf], NOW), Err(RevocationError::Revoked));
+
+        checker
+            .staple_ocsp(&ocsp(&ca_key, &key(), 5, &[0x80, 0x00]))
+            .unwrap();
+        assert_eq!(
+            checker.check(&[&leaf], NOW),
+            Err(RevocationError::BadSignature)
+        );
+
+        // responder errors are reported when stapling
+        let unauthorized = der(SEQUENCE, &der(ENUMERATED, &[0x06]));
+        assert_eq!(
+            checker.staple_ocsp(&unauthorized),
+            Err(RevocationError::OcspStatus(6))
+        );
+    }
+
+    #[test]
+    fn check_signatures() {
+        let (ca_key, ca, leaf) = chain(None);
+        let checker = RevocationChecker::new().require_status(true);
+        checker
+            .add_crl(&crl("CA", &ca_key, &[], b"250107000000Z"))
+            .unwrap();
+        assert!(checker.check(&[&leaf, &ca], NOW).is_ok());
+
+        // an issuer with the right name that did not sign the certificate
+        let forger = key();
+        let forged = cert(1, "CA", "CA", forger.public_key().as_ref(), &forger, None);
+        checker
+            .add_crl(&crl("CA", &forger, &[], b"250107000000Z"))
+            .unwrap();
+        assert_eq!(
+            checker.check(&[&leaf, &forged], NOW),
+            Err(RevocationError::BadSignature)
+        );
+
+        checker.add_issuer(forged).unwrap();
+        assert_eq!(
+            checker.check(&[&leaf], NOW),
+            Err(RevocationError::BadSignature)
+        );
+
+        // the added issuer that signed the certificate is picked
+        checker.add_issuer(ca).unwrap();
+        checker
+            .add_crl(&crl("CA", &ca_key, &[], b"250107000000Z"))
+            .unwrap();
+        assert!(checker.check(&[&leaf], NOW).is_ok());
+    }
+
+    struct Fetcher(Vec<u8>, AtomicUsize);
+
+    impl CrlFetcher for Arc<Fetcher> {
+        fn fetch(&self, url: &str) -> Result<Vec<u8>, RevocationError> {
+            assert_eq!(url, "http://crl.example.com/batch.crl");
+            self.1.fetch_add(1, Ordering::SeqCst);
+            Ok(self.0.clone())
+        }
+    }
+
+    impl AsyncCrlFetcher for Arc<Fetcher> {
+        fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+            Box::pin(async move { CrlFetcher::fetch(self, url) })
+        }
+    }
+
+    #[test]
+    fn fetch_crl() {
+        let (ca_key, ca, leaf) = chain(Some("http://crl.example.com/batch.crl"));
+        let fetcher = Arc::new(Fetcher(
+            crl("CA", &ca_key, &[5], b"250107000000Z"),
+            AtomicUsize::new(0),
+        ));
+        let checker = RevocationChecker::new().with_fetcher(fetcher.clone());
+        let chain = [leaf, ca.clone()];
+
+        // the chain is not known to lead to a trusted issuer
+        assert!(checker.check(&chain, NOW).is_ok());
+        assert_eq!(fetcher.1.load(Ordering::SeqCst), 0);
+
+        checker.add_issuer(ca).unwrap();
+        assert_eq!(checker.check(&chain, NOW), Err(RevocationError::Revoked));
+        assert_eq!(checker.check(&chain, NOW), Err(RevocationError::Revoked));
+        assert_eq!(fetcher.1.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn prefetch_crl() {
+        let (ca_key, ca, leaf) = chain(Some("http://crl.example.com/batch.crl"));
+        let fetcher = Arc::new(Fetcher(
+            crl("CA", &ca_key, &[5], b"250107000000Z"),
+            AtomicUsize::new(0),
+        ));
+        let checker = RevocationChecker::new().with_async_fetcher(fetcher.clone());
+        checker.add_issuer(ca).unwrap();
+
+        assert!(checker.check(&[&leaf], NOW).is_ok());
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(checker.prefetch(&[&leaf], NOW));
+        assert_eq!(checker.check(&[&leaf], NOW), Err(RevocationError::Revoked));
+        assert_eq!(fetcher.1.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn fetched_urls_are_restricted() {
+        assert!(allowed("http://crl.example.com/batch.crl", &[]));
+        assert!(allowed("https://crl.example.com/batch.crl", &[]));
+        assert!(!allowed("ftp://crl.example.com/batch.crl", &[]));
+        assert!(!allowed("file:///etc/passwd", &[]));
+        assert!(!allowed("http://169.254.169.254/latest/meta-data", &[]));
+        assert!(!allowed("http://[::1]/batch.crl", &[]));
+        assert!(!allowed("http://localhost:8080/batch.crl", &[]));
+        assert!(!allowed("not a url", &[]));
+
+        let hosts = ["crl.example.com".to_owned()];
+        assert!(allowed("http://CRL.example.com/batch.crl", &hosts));
+        assert!(!allowed("http://crl.example.org/batch.crl", &hosts));
+    }
+
+    #[test]
+    fn parse_time() {
+        let time = |tag, value: &[u8]| read_time(&mut Reader::new(Input::from(&der(tag, value))));
+        assert_eq!(time(GENERALIZED_TIME, b"20250101000000Z"), Ok(NOW));
+        assert_eq!(time(UTC_TIME, b"491231235959Z"), Ok(2_524_607_999));
+        assert_eq!(time(UTC_TIME, b"000229120000Z"), Ok(951_825_600));
+        assert!(time(UTC_TIME, b"20250101000000Z").is_err());
+        assert!(time(GENERALIZED_TIME, b"20251301000000Z").is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//! Stateless ceremony state stored in a signed cookie
+//!
+//! Instead of saving the issued challenge in a [`ChallengeStore`](store/trait.ChallengeStore.html),
+//! the challenge (along with the ceremony type, the user it was issued for and an expiry
+//! time) can be sealed into a cookie value with a [`StateCookie`] and handed to the client.
+//! When the response arrives, the cookie is opened, which verifies the HMAC-SHA256 tag,
+//! decrypts the value (if encryption is enabled) and rejects expired state.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let cookies = StateCookie::new(b"a long, random server-side secret");
+//!
+//! // first leg
+//! let req = RegisterRequest::new(&config, &user);
+//! let value = cookies.seal(WebAuthnType::Create, req.challenge(), user.id())?;
+//!
+//! // second leg
+//! let state = cookies.open(&value, WebAuthnType::Create)?;
+//! let result = register(form, &config, state.into_challenge())?;
+//! ```
+//!
+//! A cookie stays valid until it expires, so a client could send it again along with
+//! the same signed response.  [`StateCookie::open_once`] records the challenge in a
+//! [`ChallengeStore`] when opening the cookie and rejects any later attempt to use it.
+
+use crate::{
+    time::now,
+    webauthn::{store::ChallengeStore, Error, WebAuthnType},
+};
+use rand::RngCore;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    hmac,
+};
+use serde::{Deserialize, Serialize};
+
+/// Default lifetime (in seconds) of sealed state
+pub const DEFAULT_TTL: u64 = 300;
+
+/// State carried between the two legs of a ceremony
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CeremonyState {
+    /// Ceremony the state was issued for
+    ty: WebAuthnType,
+
+    /// Base64url-encoded challenge issued to the client
+    challenge: String,
+
+    /// Id of the user the challenge was issued for
+    user: Vec<u8>,
+
+    /// Time (seconds since the unix epoch) after which the state is no longer valid
+    expires: u64,
+}
+
+impl CeremonyState {
+    /// Returns the ceremony the state was issued for
+    pub fn ty(&self) -> &WebAuthnType {
+        &self.ty
+    }
+
+    /// Returns the base64url-encoded challenge
+    pub fn challenge(&self) -> &str {
+        &self.challenge
+    }
+
+    /// Consumes the state, returning the base64url-encoded challenge
+    pub fn into_challenge(self) -> String {
+        self.challenge
+    }
+
+    /// Returns the id of the user the challenge was issued for
+    pub fn user_id(&self) -> &[u8] {
+        &self.user
+    }
+
+    /// Returns the time (seconds since the unix epoch) the state expires
+    pub fn expires(&self) -> u64 {
+        self.expires
+    }
+}
+
+/// Seals and opens [`CeremonyState`] cookie values
+pub struct StateCookie {
+    /// Key used to sign cookie values
+    key: hmac::Key,
+
+    /// Key used to encrypt cookie values, if encryption is enabled
+    cipher: Option<LessSafeKey>,
+
+    /// Lifetime of sealed state, in seconds
+    ttl: u64,
+}
+
+impl StateCookie {
+    /// Creates a new sealer that signs (but does not encrypt) state
+    ///
+    /// # Arguments
+    /// * `secret` - Server-side secret used to sign cookies.  Should be at least 32 random bytes
+    pub fn new(secret: &[u8]) -> StateCookie {
+        StateCookie {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            cipher: None,
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// Enables encryption (ChaCha20-Poly1305) of the state so the challenge and user id
+    /// are not visible to the client
+    ///
+    /// # Arguments
+    /// * `key` - 256-bit encryption key
+    pub fn encrypted(mut self, key: &[u8; 32]) -> Self {
+        let key = UnboundKey::new(&CHACHA20_POLY1305, key).expect("key has the correct length");
+        self.cipher = Some(LessSafeKey::new(key));
+        self
+    }
+
+    /// Sets how long sealed state is valid for
+    ///
+    /// # Arguments
+    /// * `ttl` - Lifetime, in seconds
+    pub fn ttl(mut self, ttl: u64) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Seals the state of a ceremony into a cookie value
+    ///
+    /// # Arguments
+    /// * `ty` - Ceremony the challenge was issued for
+    /// * `challenge` - Base64url-encoded challenge (i.e., `RegisterRequest::challenge()`)
+    /// * `user` - Id of the user the challenge was issued for
+    pub fn seal<S: Into<String>>(
+        &self,
+        ty: WebAuthnType,
+        challenge: S,
+        user: &[u8],
+    ) -> Result<String, Error> {
+        let state = CeremonyState {
+            ty,
+            challenge: challenge.into(),
+            user: user.to_vec(),
+            expires: now() + self.ttl,
+        };
+
+        let mut body = serde_json::to_vec(&state)?;
+        if let Some(ref cipher) = self.cipher {
+            let mut nonce = [0u8; NONCE_LEN];
+            rand::thread_rng().fill_bytes(&mut nonce);
+
+            cipher
+                .seal_in_place_append_tag(
+                    Nonce::assume_unique_for_key(nonce),
+                    Aad::empty(),
+                    &mut body,
+                )
+                .map_err(|_| Error::InvalidState)?;
+            body.splice(0..0, nonce.iter().copied());
+        }
+
+        let tag = hmac::sign(&self.key, &body);
+        Ok(format!(
+            "{}.{}",
+            base64::encode_config(&body, base64::URL_SAFE_NO_PAD),
+            base64::encode_config(tag.as_ref(), base64::URL_SAFE_NO_PAD)
+        ))
+    }
+
+    /// Opens a cookie value, verifying it was sealed by this server for the expected
+    /// ceremony and has not expired
+    ///
+    /// # Arguments
+    /// * `value` - Cookie value returned by the client
+    /// * `ty` - Ceremony the response is for
+    pub fn open(&self, value: &str, ty: WebAuthnType) -> Result<CeremonyState, Error> {
+        let mut parts = value.splitn(2, '.');
+        let (body, tag) = match (parts.next(), parts.next()) {
+            (Some(body), Some(tag)) => (body, tag),
+            _ => return Err(Error::InvalidState),
+        };
+
+        let mut body = base64::decode_config(body, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Error::InvalidState)?;
+        let tag =
+            base64::decode_config(tag, base64::URL_SAFE_NO_PAD).map_err(|_| Error::InvalidState)?;
+        hmac::verify(&self.key, &body, &tag).map_err(|_| Error::InvalidState)?;
+
+        let plain = match self.cipher {
+            Some(ref cipher) => {
+                if body.len() < NONCE_LEN {
+                    return Err(Error::InvalidState);
+                }
+
+                let mut nonce = [0u8; NONCE_LEN];
+                nonce.copy_from_slice(&body[..NONCE_LEN]);
+                cipher
+                    .open_within(
+                        Nonce::assume_unique_for_key(nonce),
+                        Aad::empty(),
+                        &mut body,
+                        NONCE_LEN..,
+                    )
+                    .map_err(|_| Error::InvalidState)?
+            }
+            None => &body[..],
+        };
+
+        let state: CeremonyState =
+            serde_json::from_slice(plain).map_err(|_| Error::InvalidState)?;
+        if state.ty != ty {
+            return Err(Error::IncorrectResponseType);
+        }
+
+        if state.expires < now() {
+            return Err(Error::ExpiredState);
+        }
+
+        Ok(state)
+    }
+
+    /// Opens a cookie value (see [`open`](Self::open)) and consumes its challenge, so the
+    /// same state can never be opened again
+    ///
+    /// # Arguments
+    /// * `value` - Cookie value returned by the client
+    /// * `ty` - Ceremony the response is for
+    /// * `store` - Store remembering consumed challenges
+    ///
+    /// # Errors
+    /// Fails with `ReplayedChallenge` if the state was already opened
+    pub fn open_once<S: ChallengeStore + ?Sized>(
+        &self,
+        value: &str,
+        ty: WebAuthnType,
+        store: &S,
+    ) -> Result<CeremonyState, Error> {
+        let state = self.open(value, ty)?;
+        store.consume(&state.challenge, state.expires)?;
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webauthn::store::MemoryChallengeStore;
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn signed_state_round_trips() {
+        let cookies = StateCookie::new(SECRET);
+        let value = cookies
+            .seal(WebAuthnType::Create, "challenge", &[0, 1, 2, 3])
+            .unwrap();
+
+        let state = cookies.open(&value, WebAuthnType::Create).unwrap();
+        assert_eq!(state.challenge(), "challenge");
+        assert_eq!(state.user_id(), &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn encrypted_state_round_trips() {
+        let cookies = StateCookie::new(SECRET).encrypted(&[7; 32]);
+        let value = cookies.seal(WebAuthnType::Get, "challenge", &[9]).unwrap();
+        assert!(!value.contains(&base64::encode_config("challenge", base64::URL_SAFE_NO_PAD)));
+
+        let state = cookies.open(&value, WebAuthnType::Get).unwrap();
+        assert_eq!(state.into_challenge(), "challenge");
+    }
+
+    #[test]
+    fn tampered_state_is_rejected() {
+        let cookies = StateCookie::new(SECRET);
+        let value = cookies.seal(WebAuthnType::Get, "challenge", &[9]).unwrap();
+        let other = StateCookie::new(b"another secret");
+
+        assert!(matches!(
+            other.open(&value, WebAuthnType::Get),
+            Err(Error::InvalidState)
+        ));
+        assert!(matches!(
+            cookies.open(&value, WebAuthnType::Create),
+            Err(Error::IncorrectResponseType)
+        ));
+    }
+
+    #[test]
+    fn state_opens_once() {
+        let cookies = StateCookie::new(SECRET);
+        let store = MemoryChallengeStore::new();
+        let value = cookies.seal(WebAuthnType::Get, "challenge", &[9]).unwrap();
+
+        let state = cookies
+            .open_once(&value, WebAuthnType::Get, &store)
+            .unwrap();
+        assert_eq!(state.challenge(), "challenge");
+        assert!(matches!(
+            cookies.open_once(&value, WebAuthnType::Get, &store),
+            Err(Error::ReplayedChallenge)
+        ));
+    }
+
+    #[test]
+    fn expired_state_is_rejected() {
+        let cookies = StateCookie::new(SECRET).ttl(0);
+        let state = CeremonyState {
+            ty: WebAuthnType::Get,
+            challenge: "challenge".into(),
+            user: vec![],
+            expires: 0,
+        };
+        let body = serde_json::to_vec(&state).unwrap();
+        let tag = hmac::sign(&cookies.key, &body);
+        let value = format!(
+            "{}.{}",
+            base64::encode_config(&body, base64::URL_SAFE_NO_PAD),
+            base64::encode_config(tag.as_ref(), base64::URL_SAFE_NO_PAD)
+        );
+
+        assert!(matches!(
+            cookies.open(&value, WebAuthnType::Get),
+            Err(Error::ExpiredState)
+        ));
+    }
+}
@@ -0,0 +1,250 @@
+//! Step-up authentication for sensitive actions
+//!
+//! Before a sensitive action (changing a password, deleting an account, approving a
+//! payment, ...) a logged-in user can be asked for a fresh WebAuthn assertion with user
+//! verification required.  [`StepUp::request`] builds the [`AuthenticateRequest`] scoped to
+//! the user's devices and [`StepUp::verify`] validates the response, returning a proof
+//! signed with HMAC-SHA256 that binds the user, the action and the time of the assertion.
+//! The action's handler then calls [`StepUp::check`], which rejects proofs older than the
+//! configured maximum age.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let step_up = StepUp::new(b"a long, random server-side secret").max_age(300);
+//!
+//! // first leg
+//! let req = step_up.request(&config, devices.clone());
+//!
+//! // second leg
+//! let proof = step_up.verify(form, &config, challenge, &user, &devices, "delete-account")?;
+//! // save `proof.result().count()` as the device's new signature counter
+//!
+//! // before executing the action
+//! step_up.check(proof.token(), user.id(), "delete-account")?;
+//! ```
+
+use crate::{
+    time::now,
+    webauthn::{
+        authenticate, request::UserVerification, AuthenticateRequest, AuthenticationResult, Config,
+        Device, Error, Response, WebAuthnUser,
+    },
+};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+
+/// Default maximum age (in seconds) of a step-up assertion
+pub const DEFAULT_MAX_AGE: u64 = 300;
+
+/// Signed contents of a step-up proof
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    /// Id of the user that performed the assertion
+    user: Vec<u8>,
+
+    /// Action the assertion was performed for
+    action: String,
+
+    /// Time (seconds since the unix epoch) the assertion was verified
+    verified: u64,
+}
+
+/// Result of a successful step-up assertion
+#[derive(Debug)]
+pub struct StepUpProof {
+    /// Result of validating the assertion
+    result: AuthenticationResult,
+
+    /// Signed proof of the assertion
+    token: String,
+}
+
+impl StepUpProof {
+    /// Returns the result of validating the assertion (e.g., to update the device's counter)
+    pub fn result(&self) -> &AuthenticationResult {
+        &self.result
+    }
+
+    /// Returns the signed proof to check before executing the action
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Consumes the proof, returning the validation result and signed proof
+    pub fn into_parts(self) -> (AuthenticationResult, String) {
+        (self.result, self.token)
+    }
+}
+
+/// Issues and checks step-up proofs
+pub struct StepUp {
+    /// Key used to sign proofs
+    key: hmac::Key,
+
+    /// Maximum age, in seconds, of an assertion
+    max_age: u64,
+}
+
+impl StepUp {
+    /// Creates a new step-up helper
+    ///
+    /// # Arguments
+    /// * `secret` - Server-side secret used to sign proofs.  Should be at least 32 random bytes
+    pub fn new(secret: &[u8]) -> StepUp {
+        StepUp {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+
+    /// Sets the maximum age of an assertion, after which the proof is rejected
+    ///
+    /// # Arguments
+    /// * `max_age` - Maximum age, in seconds
+    pub fn max_age(mut self, max_age: u64) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Builds a request for an assertion, with user verification required, from one of
+    /// the user's devices
+    ///
+    /// # Arguments
+    /// * `config` - WebAuthn configuration
+    /// * `devices` - Devices registered by the user
+    pub fn request(&self, config: &Config, devices: Vec<Device>) -> AuthenticateRequest {
+        let mut req = AuthenticateRequest::new(config, devices);
+        req.set_user_verification(UserVerification::Required);
+        req
+    }
+
+    /// Validates the assertion, requiring the user was verified, and signs a proof for
+    /// an action
+    ///
+    /// # Arguments
+    /// * `form` - Response received from the client
+    /// * `config` - WebAuthn configuration
+    /// * `challenge` - Base64url-encoded challenge issued with the request
+    /// * `user` - User performing the action
+    /// * `devices` - Devices registered by the user
+    /// * `action` - Name of the action being authorized
+    pub fn verify<S: Into<String>, U: WebAuthnUser>(
+        &self,
+        form: Response,
+        config: &Config,
+        challenge: S,
+        user: &U,
+        devices: &[Device],
+        action: &str,
+    ) -> Result<StepUpProof, Error> {
+        let result = authenticate(form, config, challenge, user, devices)?;
+        if !result.user_verified() {
+            return Err(Error::UserNotVerified);
+        }
+
+        let token = self.sign(&Claims {
+            user: user.id().to_vec(),
+            action: action.to_owned(),
+            verified: now(),
+        })?;
+
+        Ok(StepUpProof { result, token })
+    }
+
+    /// Checks a proof was issued for the user and action within the maximum age
+    ///
+    /// # Arguments
+    /// * `token` - Signed proof returned by `verify()`
+    /// * `user` - Id of the user performing the action
+    /// * `action` - Name of the action being executed
+    pub fn check(&self, token: &str, user: &[u8], action: &str) -> Result<(), Error> {
+        self.check_at(token, user, action, now())
+    }
+
+    fn check_at(&self, token: &str, user: &[u8], action: &str, now: u64) -> Result<(), Error> {
+        let mut parts = token.splitn(2, '.');
+        let (body, tag) = match (parts.next(), parts.next()) {
+            (Some(body), Some(tag)) => (body, tag),
+            _ => return Err(Error::InvalidStepUpProof),
+        };
+
+        let body = base64::decode_config(body, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Error::InvalidStepUpProof)?;
+        let tag = base64::decode_config(tag, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Error::InvalidStepUpProof)?;
+        hmac::verify(&self.key, &body, &tag).map_err(|_| Error::InvalidStepUpProof)?;
+
+        let claims: Claims =
+            serde_json::from_slice(&body).map_err(|_| Error::InvalidStepUpProof)?;
+        if claims.user != user || claims.action != action {
+            return Err(Error::InvalidStepUpProof);
+        }
+
+        if now.saturating_sub(claims.verified) > self.max_age {
+            return Err(Error::InvalidStepUpProof);
+        }
+
+        Ok(())
+    }
+
+    fn sign(&self, claims: &Claims) -> Result<String, Error> {
+        let body = serde_json::to_vec(claims)?;
+        let tag = hmac::sign(&self.key, &body);
+        Ok(format!(
+            "{}.{}",
+            base64::encode_config(&body, base64::URL_SAFE_NO_PAD),
+            base64::encode_config(tag.as_ref(), base64::URL_SAFE_NO_PAD)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn proof(step_up: &StepUp, verified: u64) -> String {
+        step_up
+            .sign(&Claims {
+                user: vec![0, 1, 2, 3],
+                action: "delete-account".into(),
+                verified,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn proof_is_bound_to_user_and_action() {
+        let step_up = StepUp::new(SECRET);
+        let token = proof(&step_up, 1000);
+
+        step_up
+            .check_at(&token, &[0, 1, 2, 3], "delete-account", 1000)
+            .unwrap();
+        assert!(step_up
+            .check_at(&token, &[9], "delete-account", 1000)
+            .is_err());
+        assert!(step_up
+            .check_at(&token, &[0, 1, 2, 3], "change-password", 1000)
+            .is_err());
+        assert!(StepUp::new(b"another secret")
+            .check_at(&token, &[0, 1, 2, 3], "delete-account", 1000)
+            .is_err());
+    }
+
+    #[test]
+    fn stale_proof_is_rejected() {
+        let step_up = StepUp::new(SECRET).max_age(60);
+        let token = proof(&step_up, 1000);
+
+        step_up
+            .check_at(&token, &[0, 1, 2, 3], "delete-account", 1060)
+            .unwrap();
+        assert!(matches!(
+            step_up.check_at(&token, &[0, 1, 2, 3], "delete-account", 1061),
+            Err(Error::InvalidStepUpProof)
+        ));
+    }
+}
@@ -0,0 +1,425 @@
+//! Storage for issued challenges and registered devices
+//!
+//! The register and authenticate ceremonies are split across two requests: the
+//! challenge generated on the first request must be available when validating the
+//! second, and devices registered by a user must be available on every login.  The
+//! traits in this module describe where that state lives so the framework integrations
+//! can drive the full ceremony.  Simple in-memory implementations are provided for
+//! testing and single-process deployments.
+//!
+//! Stores backed by an async database client implement [`AsyncChallengeStore`] and
+//! [`AsyncDeviceStore`] instead; every blocking store implements the async traits as
+//! well, so either kind can be handed to the ceremony runner.
+//!
+//! A challenge store can also remember which challenges have been consumed (see
+//! [`ChallengeStore::consume`]) so a signed response cannot be validated twice, even when
+//! the challenge itself travels in a [`StateCookie`](super::state::StateCookie) the client
+//! could send again.  Stores that don't override `consume` reject every challenge with
+//! [`Error::ReplayProtectionUnavailable`], so a store can't silently skip replay protection.
+
+use crate::{
+    time::now,
+    webauthn::{Device, Error, WebAuthnUser},
+};
+use std::{
+    collections::HashMap,
+    future::{self, Future},
+    pin::Pin,
+    sync::Mutex,
+};
+
+/// The future returned by the methods of the async stores
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// Stores the challenges issued to clients, keyed by an opaque session identifier
+pub trait ChallengeStore: Send + Sync {
+    /// Saves the challenge issued for a session, replacing any existing challenge
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `challenge` - Base64url-encoded challenge issued to the client
+    fn insert(&self, session: &str, challenge: String) -> Result<(), Error>;
+
+    /// Removes and returns the challenge issued for a session, if one exists
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    fn remove(&self, session: &str) -> Result<Option<String>, Error>;
+
+    /// Marks a challenge as used until it expires.  Must be atomic: when called
+    /// concurrently with the same challenge, exactly one call succeeds and every other
+    /// call (up to the expiry time) fails with `ReplayedChallenge`
+    ///
+    /// The default implementation fails with `ReplayProtectionUnavailable`: only removing
+    /// the challenge from the session would stop a response being validated twice, which
+    /// does not help when the challenge travels in a
+    /// [`StateCookie`](super::state::StateCookie)
+    ///
+    /// # Arguments
+    /// * `challenge` - Base64url-encoded challenge about to be validated
+    /// * `expires` - Time (seconds since the unix epoch) after which the challenge can be
+    ///   forgotten, as it would be rejected anyway
+    fn consume(&self, _challenge: &str, _expires: u64) -> Result<(), Error> {
+        Err(Error::ReplayProtectionUnavailable)
+    }
+}
+
+/// Stores the devices registered by users
+pub trait DeviceStore<U: WebAuthnUser>: Send + Sync {
+    /// Returns all devices registered by a user
+    ///
+    /// # Arguments
+    /// * `user` - User to load devices for
+    fn devices(&self, user: &U) -> Result<Vec<Device>, Error>;
+
+    /// Saves a newly registered device for a user
+    ///
+    /// # Arguments
+    /// * `user` - User that registered the device
+    /// * `device` - Newly registered device
+    fn save(&self, user: &U, device: Device) -> Result<(), Error>;
+
+    /// Updates the signature counter for a user's device after a successful authentication
+    ///
+    /// # Arguments
+    /// * `user` - User that owns the device
+    /// * `id` - Credential id of the device
+    /// * `count` - New signature counter
+    fn update_count(&self, user: &U, id: &[u8], count: u32) -> Result<(), Error>;
+}
+
+/// Stores the challenges issued to clients without blocking, see [`ChallengeStore`]
+pub trait AsyncChallengeStore: Send + Sync {
+    /// Saves the challenge issued for a session, replacing any existing challenge
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `challenge` - Base64url-encoded challenge issued to the client
+    fn insert<'a>(&'a self, session: &'a str, challenge: String) -> StoreFuture<'a, ()>;
+
+    /// Removes and returns the challenge issued for a session, if one exists
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    fn remove<'a>(&'a self, session: &'a str) -> StoreFuture<'a, Option<String>>;
+
+    /// Marks a challenge as used until it expires, failing with `ReplayedChallenge` if it
+    /// already was, see [`ChallengeStore::consume`].  The default implementation fails with
+    /// `ReplayProtectionUnavailable`
+    ///
+    /// # Arguments
+    /// * `challenge` - Base64url-encoded challenge about to be validated
+    /// * `expires` - Time (seconds since the unix epoch) after which the challenge can be
+    ///   forgotten
+    fn consume<'a>(&'a self, _challenge: &'a str, _expires: u64) -> StoreFuture<'a, ()> {
+        Box::pin(future::ready(Err(Error::ReplayProtectionUnavailable)))
+    }
+}
+
+impl<T: ChallengeStore> AsyncChallengeStore for T {
+    fn insert<'a>(&'a self, session: &'a str, challenge: String) -> StoreFuture<'a, ()> {
+        Box::pin(future::ready(ChallengeStore::insert(
+            self, session, challenge,
+        )))
+    }
+
+    fn remove<'a>(&'a self, session: &'a str) -> StoreFuture<'a, Option<String>> {
+        Box::pin(future::ready(ChallengeStore::remove(self, session)))
+    }
+
+    fn consume<'a>(&'a self, challenge: &'a str, expires: u64) -> StoreFuture<'a, ()> {
+        Box::pin(future::ready(ChallengeStore::consume(
+            self, challenge, expires,
+        )))
+    }
+}
+
+/// Stores the devices registered by users without blocking, see [`DeviceStore`]
+pub trait AsyncDeviceStore<U: WebAuthnUser>: Send + Sync {
+    /// Returns all devices registered by a user
+    ///
+    /// # Arguments
+    /// * `user` - User to load devices for
+    fn devices<'a>(&'a self, user: &'a U) -> StoreFuture<'a, Vec<Device>>;
+
+    /// Saves a newly registered device for a user
+    ///
+    /// # Arguments
+    /// * `user` - User that registered the device
+    /// * `device` - Newly registered device
+    fn save<'a>(&'a self, user: &'a U, device: Device) -> StoreFuture<'a, ()>;
+
+    /// Updates the signature counter for a user's device after a successful authentication
+    ///
+    /// # Arguments
+    /// * `user` - User that owns the device
+    /// * `id` - Credential id of the device
+    /// * `count` - New signature counter
+    fn update_count<'a>(&'a self, user: &'a U, id: &'a [u8], count: u32) -> StoreFuture<'a, ()>;
+}
+
+impl<U: WebAuthnUser, T: DeviceStore<U>> AsyncDeviceStore<U> for T {
+    fn devices<'a>(&'a self, user: &'a U) -> StoreFuture<'a, Vec<Device>> {
+        Box::pin(future::ready(DeviceStore::devices(self, user)))
+    }
+
+    fn save<'a>(&'a self, user: &'a U, device: Device) -> StoreFuture<'a, ()> {
+        Box::pin(future::ready(DeviceStore::save(self, user, device)))
+    }
+
+    fn update_count<'a>(&'a self, user: &'a U, id: &'a [u8], count: u32) -> StoreFuture<'a, ()> {
+        Box::pin(future::ready(DeviceStore::update_count(
+            self, user, id, count,
+        )))
+    }
+}
+
+/// Stores the identity of the user authenticated on a session
+pub trait SessionStore: Send + Sync {
+    /// Marks a session as authenticated by a user, replacing any existing user
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    /// * `user` - Id of the user that authenticated
+    fn insert(&self, session: &str, user: Vec<u8>) -> Result<(), Error>;
+
+    /// Returns the id of the user authenticated on a session, if any
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    fn get(&self, session: &str) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Removes the authenticated user from a session (i.e., logout)
+    ///
+    /// # Arguments
+    /// * `session` - Opaque identifier for the client's session
+    fn remove(&self, session: &str) -> Result<(), Error>;
+}
+
+/// A simple in-memory challenge store
+#[derive(Debug, Default)]
+pub struct MemoryChallengeStore {
+    challenges: Mutex<HashMap<String, String>>,
+
+    /// Consumed challenges and when they expire
+    consumed: Mutex<HashMap<String, u64>>,
+}
+
+impl MemoryChallengeStore {
+    pub fn new() -> MemoryChallengeStore {
+        Self::default()
+    }
+}
+
+impl ChallengeStore for MemoryChallengeStore {
+    fn insert(&self, session: &str, challenge: String) -> Result<(), Error> {
+        let mut challenges = self
+            .challenges
+            .lock()
+            .map_err(|_| Error::store("poisoned lock"))?;
+        challenges.insert(session.to_owned(), challenge);
+        Ok(())
+    }
+
+    fn remove(&self, session: &str) -> Result<Option<String>, Error> {
+        let mut challenges = self
+            .challenges
+            .lock()
+            .map_err(|_| Error::store("poisoned lock"))?;
+        Ok(challenges.remove(session))
+    }
+
+    fn consume(&self, challenge: &str, expires: u64) -> Result<(), Error> {
+        let mut consumed = self
+            .consumed
+            .lock()
+            .map_err(|_| Error::store("poisoned lock"))?;
+
+        let now = now();
+        consumed.retain(|_, expires| *expires >= now);
+        if consumed.contains_key(challenge) {
+            return Err(Error::ReplayedChallenge);
+        }
+        consumed.insert(challenge.to_owned(), expires);
+        Ok(())
+    }
+}
+
+/// A simple in-memory device store, keyed by the user's id
+#[derive(Debug, Default)]
+pub struct MemoryDeviceStore {
+    devices: Mutex<HashMap<Vec<u8>, Vec<Device>>>,
+}
+
+impl MemoryDeviceStore {
+    pub fn new() -> MemoryDeviceStore {
+        Self::default()
+    }
+}
+
+impl<U: WebAuthnUser> DeviceStore<U> for MemoryDeviceStore {
+    fn devices(&self, user: &U) -> Result<Vec<Device>, Error> {
+        let devices = self
+            .devices
+            .lock()
+            .map_err(|_| Error::store("poisoned lock"))?;
+        Ok(devices.get(user.id()).cloned().unwrap_or_default())
+    }
+
+    fn save(&self, user: &U, device: Device) -> Result<(), Error> {
+        let mut devices = self
+            .devices
+            .lock()
+            .map_err(|_| Error::store("poisoned lock"))?;
+        devices.entry(user.id().to_vec()).or_default().push(device);
+        Ok(())
+    }
+
+    fn update_count(&self, user: &U, id: &[u8], count: u32) -> Result<(), Error> {
+        let mut devices = self
+            .devices
+            .lock()
+            .map_err(|_| Error::store("poisoned lock"))?;
+        let device = devices
+            .get_mut(user.id())
+            .and_then(|devices| devices.iter_mut().find(|d| d.id() == id))
+            .ok_or(Error::DeviceNotFound)?;
+        device.set_count(count);
+        Ok(())
+    }
+}
+
+/// A simple in-memory session store
+#[derive(Debug, Default)]
+pub struct MemorySessionStore {
+    sessions: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> MemorySessionStore {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn insert(&self, session: &str, user: Vec<u8>) -> Result<(), Error> {
+        let mut sessions = self
+            .sessions
+            .lock()
+            .map_err(|_| Error::store("poisoned lock"))?;
+        sessions.insert(session.to_owned(), user);
+        Ok(())
+    }
+
+    fn get(&self, session: &str) -> Result<Option<Vec<u8>>, Error> {
+        let sessions = self
+            .sessions
+            .lock()
+            .map_err(|_| Error::store("poisoned lock"))?;
+        Ok(sessions.get(session).cloned())
+    }
+
+    fn remove(&self, session: &str) -> Result<(), Error> {
+        let mut sessions = self
+            .sessions
+            .lock()
+            .map_err(|_| Error::store("poisoned lock"))?;
+        sessions.remove(session);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        now, ChallengeStore, Device, DeviceStore, Error, MemoryChallengeStore, MemoryDeviceStore,
+        MemorySessionStore, SessionStore, WebAuthnUser,
+    };
+
+    struct TestUser;
+
+    impl WebAuthnUser for TestUser {
+        type Conn = ();
+
+        fn id(&self) -> &[u8] {
+            &[0, 1, 2, 3]
+        }
+
+        fn name(&self) -> &str {
+            "user"
+        }
+
+        fn fetch_devices(&self, _: &()) -> Vec<Device> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn memory_challenge_store_removes_challenge() {
+        let store = MemoryChallengeStore::new();
+        store.insert("session", "challenge".into()).unwrap();
+        assert_eq!(
+            store.remove("session").unwrap().as_deref(),
+            Some("challenge")
+        );
+        assert_eq!(store.remove("session").unwrap(), None);
+    }
+
+    #[test]
+    fn memory_challenge_store_consumes_once() {
+        let store = MemoryChallengeStore::new();
+        let expires = now() + 60;
+        store.consume("challenge", expires).unwrap();
+        assert!(matches!(
+            store.consume("challenge", expires),
+            Err(Error::ReplayedChallenge)
+        ));
+        store.consume("other", expires).unwrap();
+
+        // expired challenges are forgotten
+        store.consume("stale", 0).unwrap();
+        store.consume("stale", 0).unwrap();
+    }
+
+    #[test]
+    fn default_consume_rejects() {
+        struct SessionOnly;
+
+        impl ChallengeStore for SessionOnly {
+            fn insert(&self, _: &str, _: String) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn remove(&self, _: &str) -> Result<Option<String>, Error> {
+                Ok(None)
+            }
+        }
+
+        assert!(matches!(
+            SessionOnly.consume("challenge", now() + 60),
+            Err(Error::ReplayProtectionUnavailable)
+        ));
+    }
+
+    #[test]
+    fn memory_device_store_updates_count() {
+        let store = MemoryDeviceStore::new();
+        store
+            .save(&TestUser, Device::new(vec![9], vec![], 0))
+            .unwrap();
+        store.update_count(&TestUser, &[9], 5).unwrap();
+
+        let devices = DeviceStore::<TestUser>::devices(&store, &TestUser).unwrap();
+        assert_eq!(devices[0].count(), 5);
+    }
+
+    #[test]
+    fn memory_session_store_logs_out() {
+        let store = MemorySessionStore::new();
+        store.insert("session", vec![0, 1, 2, 3]).unwrap();
+        assert_eq!(store.get("session").unwrap(), Some(vec![0, 1, 2, 3]));
+
+        store.remove("session").unwrap();
+        assert_eq!(store.get("session").unwrap(), None);
+    }
+}
@@ -1,7 +1,7 @@
 //! Represents a user to be validated
 
-use serde::{Deserialize, Serialize};
 use crate::webauthn::Device;
+use serde::{Deserialize, Serialize};
 
 pub trait WebAuthnUser {
     type Conn;
@@ -15,7 +15,7 @@ pub trait WebAuthnUser {
     fn name(&self) -> &str;
 
     /// Loads all WebAuthn Devices associated with this user
-    /// 
+    ///
     /// # Arguments
     /// * `conn` - Connection to wherever the devices are stored (SQL, Redis, etc.)
     fn fetch_devices(&self, conn: &Self::Conn) -> Vec<Device>;
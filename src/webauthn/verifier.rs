@@ -0,0 +1,242 @@
+//! Precomputed verification keys
+//!
+//! [`authenticate`](super::authenticate) decodes the stored public key of the device on
+//! every assertion.  Services that check an assertion on every API call can instead keep
+//! a [`VerifierContext`]: the public key of each device is decoded (from a raw X9.62
+//! point or a COSE key) once, when the device is added, and each assertion
+//! is then checked with [`authenticate_with_context`](super::authenticate_with_context).
+//!
+//! # Example
+//!
+//! ```ignore
+//! // once, e.g. when the user's session is created
+//! let mut context = VerifierContext::new(devices)?;
+//!
+//! // on every call
+//! let result = webauthn::authenticate_with_context(form, &cfg, challenge, &user, &context)?;
+//! context.set_count(&credential_id, result.count());
+//! ```
+
+use crate::{
+    fido::cose::{self, CoseKey},
+    webauthn::{Device, Error},
+};
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+/// A credential public key, decoded and ready to verify signatures
+#[derive(Clone)]
+pub struct VerificationKey(Key);
+
+#[derive(Clone)]
+enum Key {
+    /// ES256 key, as an uncompressed P-256 point
+    Es256(UnparsedPublicKey<Vec<u8>>),
+
+    /// EdDSA key on Ed25519
+    Ed25519(UnparsedPublicKey<Vec<u8>>),
+
+    /// RS256 key
+    Rs256(RsaPublicKeyComponents<Vec<u8>>),
+}
+
+impl fmt::Debug for VerificationKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VerificationKey")
+            .field("alg", &self.alg())
+            .finish()
+    }
+}
+
+impl VerificationKey {
+    /// Decodes a stored public key, either a raw uncompressed P-256 point (as stored by
+    /// [`register`](super::register)) or a CBOR encoded COSE key
+    ///
+    /// # Arguments
+    /// * `public_key` - Public key of a device
+    pub fn parse(public_key: &[u8]) -> Result<VerificationKey, Error> {
+        if public_key.len() == 65 && public_key[0] == 0x04 {
+            return Ok(Self::es256(public_key.to_vec()));
+        }
+
+        match CoseKey::parse(public_key).map_err(|_| Error::InvalidPublicKey)? {
+            key @ CoseKey::Ec2 { .. } => key
+                .to_uncompressed()
+                .map(Self::es256)
+                .ok_or(Error::InvalidPublicKey),
+            CoseKey::Okp { x } => Ok(VerificationKey(Key::Ed25519(UnparsedPublicKey::new(
+                &signature::ED25519,
+                x,
+            )))),
+            CoseKey::Rsa { n, e } => {
+                Ok(VerificationKey(Key::Rs256(RsaPublicKeyComponents { n, e })))
+            }
+        }
+    }
+
+    fn es256(point: Vec<u8>) -> VerificationKey {
+        VerificationKey(Key::Es256(UnparsedPublicKey::new(
+            &signature::ECDSA_P256_SHA256_ASN1,
+            point,
+        )))
+    }
+
+    /// Returns the COSE identifier of the key's algorithm
+    pub fn alg(&self) -> i64 {
+        match self.0 {
+            Key::Es256(_) => cose::ES256,
+            Key::Ed25519(_) => cose::EDDSA,
+            Key::Rs256(_) => cose::RS256,
+        }
+    }
+
+    /// Verifies a signature made with the credential's private key
+    ///
+    /// # Arguments
+    /// * `message` - Signed message
+    /// * `sig` - Signature (ASN.1 DER encoded for ES256)
+    pub fn verify(&self, message: &[u8], sig: &[u8]) -> Result<(), Error> {
+        let result = match &self.0 {
+            Key::Es256(key) | Key::Ed25519(key) => key.verify(message, sig),
+            Key::Rs256(key) => key.verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, sig),
+        };
+        result.map_err(|_| Error::SignatureFailed)
+    }
+}
+
+/// A user's devices, with their public keys decoded ahead of time
+#[derive(Clone, Debug, Default)]
+pub struct VerifierContext {
+    devices: HashMap<Vec<u8>, (Device, VerificationKey)>,
+}
+
+impl VerifierContext {
+    /// Creates a context holding a set of devices
+    ///
+    /// # Arguments
+    /// * `devices` - Devices a user may authenticate with
+    ///
+    /// # Errors
+    /// Fails with `InvalidPublicKey` if a device's public key cannot be decoded
+    pub fn new<I: IntoIterator<Item = Device>>(devices: I) -> Result<VerifierContext, Error> {
+        let mut context = VerifierContext::default();
+        for device in devices {
+            context.insert(device)?;
+        }
+        Ok(context)
+    }
+
+    /// Adds a device, replacing any device with the same credential id
+    ///
+    /// # Arguments
+    /// * `device` - Device to add
+    pub fn insert(&mut self, device: Device) -> Result<(), Error> {
+        let key = VerificationKey::parse(device.public_key())?;
+        self.devices.insert(device.id().to_vec(), (device, key));
+        Ok(())
+    }
+
+    /// Removes a device
+    ///
+    /// # Arguments
+    /// * `id` - Credential id of the device
+    pub fn remove(&mut self, id: &[u8]) -> Option<Device> {
+        self.devices.remove(id).map(|(device, _)| device)
+    }
+
+    /// Returns a device and its decoded key
+    ///
+    /// # Arguments
+    /// * `id` - Credential id of the device
+    pub fn get(&self, id: &[u8]) -> Option<(&Device, &VerificationKey)> {
+        self.devices.get(id).map(|(device, key)| (device, key))
+    }
+
+    /// Updates the signature counter of a device after a successful assertion
+    ///
+    /// # Arguments
+    /// * `id` - Credential id of the device
+    /// * `count` - Signature counter returned from the assertion
+    pub fn set_count(&mut self, id: &[u8], count: u32) {
+        if let Some((device, _)) = self.devices.get_mut(id) {
+            device.set_count(count);
+        }
+    }
+
+    /// Returns the number of devices
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Returns true if the context holds no device
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+}
+
+/// Where an assertion's device and public key are looked up
+pub(crate) trait Credentials {
+    /// Returns the device with a credential id, if exactly one matches
+    fn find(&self, id: &[u8]) -> Option<&Device>;
+
+    /// Returns the number of devices
+    fn count(&self) -> usize;
+
+    /// Returns the decoded public key of a device
+    fn key(&self, device: &Device) -> Result<Cow<'_, VerificationKey>, Error>;
+}
+
+impl Credentials for [Device] {
+    fn find(&self, id: &[u8]) -> Option<&Device> {
+        let mut matching = self.iter().filter(|device| device.id() == id);
+        match (matching.next(), matching.next()) {
+            (Some(device), None) => Some(device),
+            _ => None,
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn key(&self, device: &Device) -> Result<Cow<'_, VerificationKey>, Error> {
+        VerificationKey::parse(device.public_key()).map(Cow::Owned)
+    }
+}
+
+impl Credentials for VerifierContext {
+    fn find(&self, id: &[u8]) -> Option<&Device> {
+        self.get(id).map(|(device, _)| device)
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn key(&self, device: &Device) -> Result<Cow<'_, VerificationKey>, Error> {
+        let (_, key) = self.get(device.id()).ok_or(Error::DeviceNotFound)?;
+        Ok(Cow::Borrowed(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_invalid_keys() {
+        let valid = Device::new(vec![1], [&[0x04][..], &[7; 64]].concat(), 0);
+        let invalid = Device::new(vec![2], vec![0x05; 65], 0);
+
+        let mut context = VerifierContext::new(vec![valid]).unwrap();
+        assert!(matches!(
+            context.insert(invalid),
+            Err(Error::InvalidPublicKey)
+        ));
+        assert_eq!(context.len(), 1);
+
+        context.set_count(&[1], 5);
+        assert_eq!(context.get(&[1]).unwrap().0.count(), 5);
+        assert!(context.remove(&[1]).is_some() && context.is_empty());
+    }
+}
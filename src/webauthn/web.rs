@@ -1 +1,227 @@
 //! Rocket (web) related config options
+//!
+//! Provides a managed [`Webauthn`] state type that drives the register and login
+//! ceremonies against pluggable [`ChallengeStore`] and [`DeviceStore`] backends, a
+//! request body guard for [`Response`], and responders for the request and error types.
+//!
+//! Each client is assigned an opaque session id (stored in the `X-WebAuthn-Session`
+//! cookie) which is used to look up the challenge issued to it.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use auth_rs::webauthn::{
+//!     store::{MemoryChallengeStore, MemoryDeviceStore},
+//!     web::Webauthn,
+//!     AuthenticateRequest, Config, Error, RegisterRequest, Response,
+//! };
+//! use rocket::{get, http::Cookies, post, routes, State};
+//!
+//! type Auth = Webauthn<MemoryDeviceStore, MemoryChallengeStore>;
+//!
+//! #[get("/fido/register")]
+//! fn register_request(auth: State<Auth>, user: User, mut cookies: Cookies) -> Result<RegisterRequest, Error> {
+//!     auth.register_request(&user, &mut cookies)
+//! }
+//!
+//! #[post("/fido/register", data = "<form>")]
+//! fn register(auth: State<Auth>, user: User, form: Response, mut cookies: Cookies) -> Result<(), Error> {
+//!     auth.register(&user, form, &mut cookies).map(|_| ())
+//! }
+//!
+//! #[get("/fido/login")]
+//! fn login_request(auth: State<Auth>, user: User, mut cookies: Cookies) -> Result<AuthenticateRequest, Error> {
+//!     auth.login_request(&user, &mut cookies)
+//! }
+//!
+//! #[post("/fido/login", data = "<form>")]
+//! fn login(auth: State<Auth>, user: User, form: Response, mut cookies: Cookies) -> Result<(), Error> {
+//!     auth.login(&user, form, &mut cookies).map(|_| ())
+//! }
+//!
+//! fn main() {
+//!     let auth = Auth::new(
+//!         Config::new("https://app.example.com"),
+//!         MemoryDeviceStore::new(),
+//!         MemoryChallengeStore::new(),
+//!     );
+//!
+//!     rocket::ignite()
+//!         .manage(auth)
+//!         .mount("/", routes![register_request, register, login_request, login])
+//!         .launch();
+//! }
+//! ```
+
+use crate::webauthn::{
+    integrations,
+    store::{ChallengeStore, DeviceStore},
+    AuthenticateRequest, AuthenticationResult, Error, RegisterRequest, RegistrationResult,
+    Response, WebAuthnUser,
+};
+use rocket::{
+    data::{self, Data, FromDataSimple},
+    http::{ContentType, Cookie, Cookies, SameSite, Status},
+    request::Request,
+    response::{self, Responder},
+    Outcome,
+};
+use rocket_contrib::json::Json;
+use std::io::{Cursor, Read};
+
+pub use crate::webauthn::integrations::{Webauthn, SESSION_COOKIE};
+
+/// Maximum size (in bytes) of a response body that will be read
+const LIMIT: u64 = 64 * 1024;
+
+impl<D, C> Webauthn<D, C>
+where
+    C: ChallengeStore,
+{
+    /// Builds a new register request for a user and saves the challenge for the
+    /// client's session
+    ///
+    /// # Arguments
+    /// * `user` - User registering a new device
+    /// * `cookies` - Cookies for the current request
+    pub fn register_request<U>(
+        &self,
+        user: &U,
+        cookies: &mut Cookies,
+    ) -> Result<RegisterRequest, Error>
+    where
+        U: WebAuthnUser,
+    {
+        self.blocking().start_registration(&session(cookies), user)
+    }
+
+    /// Validates a register response and saves the new device
+    ///
+    /// # Arguments
+    /// * `user` - User registering a new device
+    /// * `form` - Response received from the client
+    /// * `cookies` - Cookies for the current request
+    pub fn register<U>(
+        &self,
+        user: &U,
+        form: Response,
+        cookies: &mut Cookies,
+    ) -> Result<RegistrationResult, Error>
+    where
+        U: WebAuthnUser,
+        D: DeviceStore<U>,
+    {
+        self.blocking()
+            .finish_registration(&existing_session(cookies)?, user, form)
+    }
+
+    /// Builds a new authenticate request for a user's registered devices and saves
+    /// the challenge for the client's session
+    ///
+    /// # Arguments
+    /// * `user` - User attempting to login
+    /// * `cookies` - Cookies for the current request
+    pub fn login_request<U>(
+        &self,
+        user: &U,
+        cookies: &mut Cookies,
+    ) -> Result<AuthenticateRequest, Error>
+    where
+        U: WebAuthnUser,
+        D: DeviceStore<U>,
+    {
+        self.blocking().start_login(&session(cookies), user)
+    }
+
+    /// Validates an authenticate response against the user's registered devices and
+    /// updates the device's signature counter
+    ///
+    /// # Arguments
+    /// * `user` - User attempting to login
+    /// * `form` - Response received from the client
+    /// * `cookies` - Cookies for the current request
+    pub fn login<U>(
+        &self,
+        user: &U,
+        form: Response,
+        cookies: &mut Cookies,
+    ) -> Result<AuthenticationResult, Error>
+    where
+        U: WebAuthnUser,
+        D: DeviceStore<U>,
+    {
+        self.blocking()
+            .finish_login(&existing_session(cookies)?, user, form)
+    }
+}
+
+/// Returns the client's session id, failing if the client doesn't have one
+fn existing_session(cookies: &mut Cookies) -> Result<String, Error> {
+    cookies
+        .get(SESSION_COOKIE)
+        .map(|c| c.value().to_owned())
+        .ok_or(Error::MissingChallenge)
+}
+
+/// Returns the client's session id, generating a new one if the client doesn't have one
+fn session(cookies: &mut Cookies) -> String {
+    if let Some(cookie) = cookies.get(SESSION_COOKIE) {
+        return cookie.value().to_owned();
+    }
+
+    let id = integrations::new_session_id();
+    cookies.add(
+        Cookie::build(SESSION_COOKIE, id.clone())
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Strict)
+            .finish(),
+    );
+
+    id
+}
+
+impl FromDataSimple for Response {
+    type Error = Error;
+
+    fn from_data(_: &Request, data: Data) -> data::Outcome<Self, Self::Error> {
+        let mut body = String::new();
+        if let Err(e) = data.open().take(LIMIT).read_to_string(&mut body) {
+            return Outcome::Failure((
+                Status::BadRequest,
+                Error::JsonError(serde_json::Error::io(e)),
+            ));
+        }
+
+        match serde_json::from_str(&body) {
+            Ok(form) => Outcome::Success(form),
+            Err(e) => Outcome::Failure((Status::BadRequest, Error::JsonError(e))),
+        }
+    }
+}
+
+impl<'r> Responder<'r> for RegisterRequest {
+    fn respond_to(self, req: &Request) -> response::Result<'r> {
+        Json(self).respond_to(req)
+    }
+}
+
+impl<'r> Responder<'r> for AuthenticateRequest {
+    fn respond_to(self, req: &Request) -> response::Result<'r> {
+        Json(self).respond_to(req)
+    }
+}
+
+impl<'r> Responder<'r> for Error {
+    fn respond_to(self, _: &Request) -> response::Result<'r> {
+        let status = Status::from_code(integrations::http_status(&self))
+            .unwrap_or(Status::InternalServerError);
+
+        let body = integrations::error_body(&self).to_string();
+        rocket::Response::build()
+            .status(status)
+            .header(ContentType::JSON)
+            .sized_body(Cursor::new(body))
+            .ok()
+    }
+}